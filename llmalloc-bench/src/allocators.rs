@@ -0,0 +1,132 @@
+//! The allocators under comparison.
+//!
+//! Each allocator is exercised through the same narrow interface, so that the workloads measure the allocators rather
+//! than the plumbing around them:
+//!
+//! -   llmalloc, through `LLAllocator`.
+//! -   The system allocator, through `std::alloc::System`.
+//! -   jemalloc, if `libjemalloc.so.2` can be loaded at run-time, through its `mallocx` and `sdallocx` entry points,
+//!     which are not interposed with the system allocator.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    mem,
+};
+
+use llmalloc::LLAllocator;
+
+/// An allocator under comparison.
+pub trait Allocator: Sync {
+    /// Returns the name of the allocator, as used on the command line and in the results.
+    fn name(&self) -> &'static str;
+
+    /// Allocates memory for `layout`, returning a null pointer on failure.
+    fn allocate(&self, layout: Layout) -> *mut u8;
+
+    /// Deallocates the memory located at `pointer`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` was returned by `allocate`, with `layout`, and not deallocated since.
+    unsafe fn deallocate(&self, pointer: *mut u8, layout: Layout);
+}
+
+/// Names of the known allocators, in their default order.
+pub const NAMES: [&str; 3] = ["llmalloc", "system", "jemalloc"];
+
+/// Returns the allocator named `name`, or None if it is unknown or unavailable.
+pub fn by_name(name: &str) -> Option<&'static dyn Allocator> {
+    match name {
+        "llmalloc" => Some(&LL_ALLOCATOR),
+        "system" => Some(&SystemAllocator),
+        "jemalloc" => Jemalloc::load().map(|jemalloc| &*Box::leak(Box::new(jemalloc)) as &dyn Allocator),
+        _ => None,
+    }
+}
+
+//
+//  Implementation Details
+//
+
+static LL_ALLOCATOR: LLAllocator = LLAllocator::new();
+
+impl Allocator for LLAllocator {
+    fn name(&self) -> &'static str { "llmalloc" }
+
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        LLAllocator::allocate(self, layout).map_or(std::ptr::null_mut(), |pointer| pointer.as_ptr())
+    }
+
+    unsafe fn deallocate(&self, pointer: *mut u8, layout: Layout) { self.dealloc(pointer, layout) }
+}
+
+struct SystemAllocator;
+
+impl Allocator for SystemAllocator {
+    fn name(&self) -> &'static str { "system" }
+
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        //  Safety:
+        //  -   The workloads never allocate zero-sized layouts.
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn deallocate(&self, pointer: *mut u8, layout: Layout) { System.dealloc(pointer, layout) }
+}
+
+type Mallocx = unsafe extern "C" fn(usize, libc::c_int) -> *mut libc::c_void;
+type Sdallocx = unsafe extern "C" fn(*mut libc::c_void, usize, libc::c_int);
+
+//  jemalloc, loaded at run-time.
+struct Jemalloc {
+    mallocx: Mallocx,
+    sdallocx: Sdallocx,
+}
+
+impl Jemalloc {
+    const LIBRARIES: [&'static [u8]; 2] = [b"libjemalloc.so.2\0", b"libjemalloc.so\0"];
+
+    //  Loads jemalloc, if installed.
+    fn load() -> Option<Self> {
+        let library = Self::LIBRARIES.iter().find_map(|name| {
+            //  Safety:
+            //  -   `name` is NUL-terminated.
+            let library = unsafe { libc::dlopen(name.as_ptr() as *const libc::c_char, libc::RTLD_NOW) };
+
+            if library.is_null() { None } else { Some(library) }
+        })?;
+
+        //  Safety:
+        //  -   `library` is a valid handle, and the names are NUL-terminated.
+        let symbol = |name: &[u8]| unsafe { libc::dlsym(library, name.as_ptr() as *const libc::c_char) };
+        let (mallocx, sdallocx) = (symbol(b"mallocx\0"), symbol(b"sdallocx\0"));
+
+        if mallocx.is_null() || sdallocx.is_null() {
+            return None;
+        }
+
+        //  Safety:
+        //  -   The signatures are those documented by jemalloc.
+        let mallocx = unsafe { mem::transmute::<*mut libc::c_void, Mallocx>(mallocx) };
+        let sdallocx = unsafe { mem::transmute::<*mut libc::c_void, Sdallocx>(sdallocx) };
+
+        Some(Self { mallocx, sdallocx })
+    }
+
+    //  Returns the flags requesting the alignment of `layout`, that is `MALLOCX_LG_ALIGN`.
+    fn flags(layout: Layout) -> libc::c_int { layout.align().trailing_zeros() as libc::c_int }
+}
+
+impl Allocator for Jemalloc {
+    fn name(&self) -> &'static str { "jemalloc" }
+
+    fn allocate(&self, layout: Layout) -> *mut u8 {
+        //  Safety:
+        //  -   The workloads never allocate zero-sized layouts, for which `mallocx` is undefined.
+        unsafe { (self.mallocx)(layout.size(), Self::flags(layout)) as *mut u8 }
+    }
+
+    unsafe fn deallocate(&self, pointer: *mut u8, layout: Layout) {
+        (self.sdallocx)(pointer as *mut libc::c_void, layout.size(), Self::flags(layout))
+    }
+}
@@ -0,0 +1,141 @@
+//! A benchmark comparison harness.
+//!
+//! Runs standardized workloads against llmalloc, the system allocator, and jemalloc if installed, emitting one JSON
+//! object per line and per measurement on the standard output, so that the results may be archived, compared across
+//! versions, and plotted.
+//!
+//! Run `llmalloc-bench --help` for the options.
+
+mod allocators;
+mod workloads;
+
+use std::{env, fmt::Write as _, process, thread};
+
+use allocators::Allocator;
+use workloads::{Measurement, Parameters};
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        },
+    };
+
+    let allocators: Vec<&'static dyn Allocator> = options.allocators.iter()
+        .filter_map(|name| {
+            let allocator = allocators::by_name(name);
+
+            if allocator.is_none() {
+                eprintln!("Skipping {}: not available.", name);
+            }
+
+            allocator
+        })
+        .collect();
+
+    for workload in &options.workloads {
+        for allocator in &allocators {
+            let measurements = workloads::run(workload, *allocator, &options.parameters).unwrap_or_default();
+
+            for measurement in &measurements {
+                println!("{}", to_json(measurement));
+            }
+        }
+    }
+}
+
+//
+//  Implementation Details
+//
+
+const USAGE: &str = "\
+Usage: llmalloc-bench [OPTIONS]
+
+Options:
+    --allocators A,B,...    Allocators to compare, among llmalloc, system, and jemalloc [default: all available]
+    --workloads W,X,...     Workloads to run, among cache-thrash, larson, producer-consumer, and size-sweep
+                            [default: all]
+    --threads N             Number of threads [default: available parallelism]
+    --operations N          Number of operations per thread [default: 1000000]
+    --seed N                Seed of the pseudo-random sizes [default: 42]
+    --help                  Prints this message
+
+Each measurement is printed as a JSON object, on its own line.";
+
+struct Options {
+    allocators: Vec<String>,
+    workloads: Vec<String>,
+    parameters: Parameters,
+}
+
+impl Options {
+    fn parse<I>(mut arguments: I) -> Result<Self, String>
+        where
+            I: Iterator<Item = String>,
+    {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+
+        let mut options = Options {
+            allocators: allocators::NAMES.iter().map(|name| name.to_string()).collect(),
+            workloads: workloads::NAMES.iter().map(|name| name.to_string()).collect(),
+            parameters: Parameters { threads, operations: 1_000_000, seed: 42 },
+        };
+
+        while let Some(argument) = arguments.next() {
+            if argument == "--help" {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+
+            let value = arguments.next().ok_or_else(|| format!("Missing value for {}", argument))?;
+
+            match argument.as_str() {
+                "--allocators" => options.allocators = Self::list(&value, &allocators::NAMES)?,
+                "--workloads" => options.workloads = Self::list(&value, &workloads::NAMES)?,
+                "--threads" => options.parameters.threads = Self::number(&argument, &value)?.max(1),
+                "--operations" => options.parameters.operations = Self::number(&argument, &value)?,
+                "--seed" => options.parameters.seed = Self::number(&argument, &value)? as u64,
+                _ => return Err(format!("Unknown option {}", argument)),
+            }
+        }
+
+        Ok(options)
+    }
+
+    //  Parses a comma-separated list, each element of which is among `known`.
+    fn list(value: &str, known: &[&str]) -> Result<Vec<String>, String> {
+        value.split(',')
+            .map(|name| if known.contains(&name) { Ok(name.to_string()) } else { Err(format!("Unknown {}", name)) })
+            .collect()
+    }
+
+    fn number(argument: &str, value: &str) -> Result<usize, String> {
+        value.parse().map_err(|_| format!("Invalid value for {}: {}", argument, value))
+    }
+}
+
+fn to_json(measurement: &Measurement) -> String {
+    let elapsed = measurement.elapsed.as_nanos();
+    let operations = measurement.operations.max(1) as f64;
+
+    let mut result = String::new();
+
+    //  Writing into a `String` never fails.
+    let _ = write!(
+        result,
+        "{{\"workload\":\"{}\",\"allocator\":\"{}\",\"threads\":{},\"size\":{},\"operations\":{},\"elapsed_ns\":{},\
+            \"ns_per_operation\":{:.3},\"operations_per_second\":{:.0}}}",
+        measurement.workload,
+        measurement.allocator,
+        measurement.threads,
+        measurement.size.map_or_else(|| "null".to_string(), |size| size.to_string()),
+        measurement.operations,
+        elapsed,
+        elapsed as f64 / operations,
+        operations / measurement.elapsed.as_secs_f64().max(f64::MIN_POSITIVE),
+    );
+
+    result
+}
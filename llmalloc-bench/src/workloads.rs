@@ -0,0 +1,303 @@
+//! The standardized workloads.
+//!
+//! Each workload is a classic of the allocator literature, scaled by the number of threads and the number of
+//! operations per thread, and seeded, so that all allocators are subjected to the exact same sequence of requests:
+//!
+//! -   cache-thrash: each thread repeatedly allocates, writes, and deallocates a small object, exposing the false
+//!     sharing of cache lines between the objects of different threads.
+//! -   larson: each thread repeatedly replaces random objects among its slots, the slots being handed over to another
+//!     thread at each round, as a server handing connections over to its workers.
+//! -   producer-consumer: half of the threads allocate objects, which the other half deallocate.
+//! -   size-sweep: each thread allocates and deallocates batches of objects of a given size, for sizes ranging from
+//!     16 bytes to 1 MB.
+//!
+//! An operation is a pair of allocation and deallocation.
+
+use std::{
+    alloc::Layout,
+    ptr,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::allocators::Allocator;
+
+/// Names of the workloads, in their default order.
+pub const NAMES: [&str; 4] = ["cache-thrash", "larson", "producer-consumer", "size-sweep"];
+
+/// Parameters of a run.
+#[derive(Clone, Copy, Debug)]
+pub struct Parameters {
+    /// Number of threads.
+    pub threads: usize,
+    /// Number of operations, per thread.
+    pub operations: usize,
+    /// Seed of the pseudo-random sizes.
+    pub seed: u64,
+}
+
+/// Measurement of a run.
+#[derive(Clone, Debug)]
+pub struct Measurement {
+    /// The workload.
+    pub workload: &'static str,
+    /// The allocator.
+    pub allocator: &'static str,
+    /// Number of threads.
+    pub threads: usize,
+    /// Size of the allocations, for workloads of a single size.
+    pub size: Option<usize>,
+    /// Number of operations, across all threads.
+    pub operations: usize,
+    /// Wall-clock time of the run.
+    pub elapsed: Duration,
+}
+
+/// Runs the workload named `name` against `allocator`, returning its measurements, or None if it is unknown.
+pub fn run(name: &str, allocator: &dyn Allocator, parameters: &Parameters) -> Option<Vec<Measurement>> {
+    let measure = |workload: &'static str, size: Option<usize>, operations: usize, elapsed: Duration| Measurement {
+        workload,
+        allocator: allocator.name(),
+        threads: parameters.threads,
+        size,
+        operations,
+        elapsed,
+    };
+
+    let result = match name {
+        "cache-thrash" => {
+            let (operations, elapsed) = cache_thrash(allocator, parameters);
+            vec!(measure("cache-thrash", Some(CACHE_THRASH_SIZE), operations, elapsed))
+        },
+        "larson" => {
+            let (operations, elapsed) = larson(allocator, parameters);
+            vec!(measure("larson", None, operations, elapsed))
+        },
+        "producer-consumer" => {
+            let (operations, elapsed) = producer_consumer(allocator, parameters);
+            vec!(measure("producer-consumer", None, operations, elapsed))
+        },
+        "size-sweep" => SIZE_SWEEP_SIZES.iter()
+            .map(|&size| {
+                let (operations, elapsed) = size_sweep(allocator, parameters, size);
+                measure("size-sweep", Some(size), operations, elapsed)
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+//
+//  Implementation Details
+//
+
+const CACHE_THRASH_SIZE: usize = 8;
+const CACHE_THRASH_WRITES: usize = 64;
+
+const LARSON_SLOTS: usize = 1024;
+const LARSON_ROUNDS: usize = 8;
+const LARSON_SIZES: (usize, usize) = (16, 512);
+
+const PRODUCER_CONSUMER_BATCH: usize = 64;
+const PRODUCER_CONSUMER_SIZES: (usize, usize) = (16, 256);
+
+const SIZE_SWEEP_BATCH: usize = 32;
+const SIZE_SWEEP_SIZES: [usize; 8] = [16, 64, 256, 1 << 10, 4 << 10, 16 << 10, 64 << 10, 1 << 20];
+
+//  An allocation, as an address, so as to be sent across threads.
+#[derive(Clone, Copy)]
+struct Allocation {
+    address: usize,
+    size: usize,
+}
+
+impl Allocation {
+    //  Allocates `size` bytes, touching the first one.
+    fn new(allocator: &dyn Allocator, size: usize) -> Self {
+        let pointer = allocator.allocate(layout(size));
+        assert!(!pointer.is_null(), "{} failed to allocate {} bytes", allocator.name(), size);
+
+        //  Safety:
+        //  -   `pointer` is valid for writes of `size` bytes, with `size` at least 1.
+        unsafe { ptr::write_volatile(pointer, size as u8) };
+
+        Self { address: pointer as usize, size }
+    }
+
+    //  Deallocates the allocation.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes the allocation was created from `allocator`, and not deallocated since.
+    unsafe fn release(self, allocator: &dyn Allocator) {
+        allocator.deallocate(self.address as *mut u8, layout(self.size))
+    }
+}
+
+//  A xorshift pseudo-random generator, good enough for picking sizes and slots.
+struct Random(u64);
+
+impl Random {
+    //  Creates a generator for the `stream`-th thread of a run seeded with `seed`.
+    fn new(seed: u64, stream: usize) -> Self {
+        Self((seed ^ (stream as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    //  Returns a number in `[0, bound)`.
+    fn below(&mut self, bound: usize) -> usize { (self.next() % bound as u64) as usize }
+
+    //  Returns a number in `[range.0, range.1]`.
+    fn within(&mut self, range: (usize, usize)) -> usize { range.0 + self.below(range.1 - range.0 + 1) }
+}
+
+fn layout(size: usize) -> Layout {
+    //  Safety:
+    //  -   8 is a power of 2, and the sizes are small enough not to overflow once rounded.
+    unsafe { Layout::from_size_align_unchecked(size, 8) }
+}
+
+fn cache_thrash(allocator: &dyn Allocator, parameters: &Parameters) -> (usize, Duration) {
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for _ in 0..parameters.threads {
+            scope.spawn(|| {
+                for _ in 0..parameters.operations {
+                    let pointer = allocator.allocate(layout(CACHE_THRASH_SIZE));
+                    assert!(!pointer.is_null(), "{} failed to allocate", allocator.name());
+
+                    for index in 0..CACHE_THRASH_WRITES {
+                        //  Safety:
+                        //  -   `pointer` is valid for writes of `CACHE_THRASH_SIZE` bytes.
+                        unsafe { ptr::write_volatile(pointer.add(index % CACHE_THRASH_SIZE), index as u8) };
+                    }
+
+                    //  Safety:
+                    //  -   `pointer` was allocated with this layout, just above.
+                    unsafe { allocator.deallocate(pointer, layout(CACHE_THRASH_SIZE)) };
+                }
+            });
+        }
+    });
+
+    (parameters.threads * parameters.operations, start.elapsed())
+}
+
+fn larson(allocator: &dyn Allocator, parameters: &Parameters) -> (usize, Duration) {
+    let start = Instant::now();
+
+    let mut slots: Vec<Vec<Allocation>> = (0..parameters.threads)
+        .map(|stream| {
+            let mut random = Random::new(parameters.seed, stream);
+            (0..LARSON_SLOTS).map(|_| Allocation::new(allocator, random.within(LARSON_SIZES))).collect()
+        })
+        .collect();
+
+    let operations = parameters.operations / LARSON_ROUNDS;
+
+    for round in 0..LARSON_ROUNDS {
+        thread::scope(|scope| {
+            for (stream, slots) in slots.iter_mut().enumerate() {
+                scope.spawn(move || {
+                    let mut random = Random::new(parameters.seed, (round + 1) * parameters.threads + stream);
+
+                    for _ in 0..operations {
+                        let slot = &mut slots[random.below(LARSON_SLOTS)];
+                        let replacement = Allocation::new(allocator, random.within(LARSON_SIZES));
+
+                        //  Safety:
+                        //  -   The slot holds a live allocation, which it no longer refers to.
+                        unsafe { std::mem::replace(slot, replacement).release(allocator) };
+                    }
+                });
+            }
+        });
+
+        //  Hand the slots of each thread over to the next, so that objects are deallocated by other threads.
+        slots.rotate_left(1);
+    }
+
+    for allocation in slots.into_iter().flatten() {
+        //  Safety:
+        //  -   The slot holds a live allocation, which is dropped.
+        unsafe { allocation.release(allocator) };
+    }
+
+    (parameters.threads * operations * LARSON_ROUNDS, start.elapsed())
+}
+
+fn producer_consumer(allocator: &dyn Allocator, parameters: &Parameters) -> (usize, Duration) {
+    let pairs = (parameters.threads / 2).max(1);
+    let batches = parameters.operations / PRODUCER_CONSUMER_BATCH;
+
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for stream in 0..pairs {
+            let (sender, receiver) = mpsc::sync_channel::<Vec<Allocation>>(16);
+
+            scope.spawn(move || {
+                let mut random = Random::new(parameters.seed, stream);
+
+                for _ in 0..batches {
+                    let batch = (0..PRODUCER_CONSUMER_BATCH)
+                        .map(|_| Allocation::new(allocator, random.within(PRODUCER_CONSUMER_SIZES)))
+                        .collect();
+
+                    sender.send(batch).expect("Consumer alive");
+                }
+            });
+
+            scope.spawn(move || {
+                for batch in receiver {
+                    for allocation in batch {
+                        //  Safety:
+                        //  -   The allocation was handed over by the producer, and is dropped.
+                        unsafe { allocation.release(allocator) };
+                    }
+                }
+            });
+        }
+    });
+
+    (pairs * batches * PRODUCER_CONSUMER_BATCH, start.elapsed())
+}
+
+fn size_sweep(allocator: &dyn Allocator, parameters: &Parameters, size: usize) -> (usize, Duration) {
+    //  The larger allocations are scaled down, so that each size takes a comparable amount of time.
+    let batches = (parameters.operations / SIZE_SWEEP_BATCH / (size / 4096).max(1)).max(1);
+
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for _ in 0..parameters.threads {
+            scope.spawn(|| {
+                let mut batch = [Allocation { address: 0, size }; SIZE_SWEEP_BATCH];
+
+                for _ in 0..batches {
+                    for allocation in batch.iter_mut() {
+                        *allocation = Allocation::new(allocator, size);
+                    }
+
+                    for allocation in batch.iter() {
+                        //  Safety:
+                        //  -   The allocation was created just above, and is dropped.
+                        unsafe { allocation.release(allocator) };
+                    }
+                }
+            });
+        }
+    });
+
+    (parameters.threads * batches * SIZE_SWEEP_BATCH, start.elapsed())
+}
@@ -6,9 +6,10 @@
 use core::{
     alloc::Layout,
     ptr::{self, NonNull},
+    time::Duration,
 };
 
-use llmalloc::LLAllocator;
+use llmalloc::{InitStage, LLAllocator, ThreadProfile};
 
 /// Prepares the socket-local and thread-local structures for allocation.
 ///
@@ -20,7 +21,25 @@ use llmalloc::LLAllocator;
 /// -   The socket-local structure cannot allocate a thread-local structure.
 #[cold]
 #[no_mangle]
-pub extern fn ll_warm_up() -> i32 { if ALLOCATOR.warm_up().is_ok() { 0 } else { -1 } }
+pub extern "C" fn ll_warm_up() -> i32 { if ALLOCATOR.warm_up().is_ok() { 0 } else { -1 } }
+
+/// Performs all stages of the initialization, in order, for the current thread.
+///
+/// Returns 0 on success, and otherwise a negative value identifying the first stage which failed:
+///
+/// -   -1: the creation of the thread-local key.
+/// -   -2: the creation of the socket-local structure.
+/// -   -3: the creation of the thread-local structure.
+#[cold]
+#[no_mangle]
+pub extern "C" fn ll_init() -> i32 {
+    match ALLOCATOR.init() {
+        Ok(_) => 0,
+        Err(InitStage::Key) => -1,
+        Err(InitStage::Socket) => -2,
+        Err(InitStage::Thread) => -3,
+    }
+}
 
 /// Ensures that at least `target` `HugePage` are allocated on the socket.
 ///
@@ -32,7 +51,63 @@ pub extern fn ll_warm_up() -> i32 { if ALLOCATOR.warm_up().is_ok() { 0 } else {
 /// -   The underlying `Platform` is failing to allocate more `HugePage`.
 #[cold]
 #[no_mangle]
-pub extern fn ll_reserve(target: usize) -> usize { ALLOCATOR.reserve(target) }
+pub extern "C" fn ll_reserve(target: usize) -> usize { ALLOCATOR.reserve(target) }
+
+/// Flushes the cache of the current thread, returning the memory it caches for the other threads to use.
+///
+/// The thread remains usable; a thread pool may thus flush the cache of a worker about to go idle for a long time.
+#[cold]
+#[no_mangle]
+pub extern "C" fn ll_flush_thread_cache() { ALLOCATOR.flush_thread_cache() }
+
+/// Trims the cache of the current thread, if it has been idle for the threshold selected by `ll_set_idle_trim`.
+///
+/// To be called as the thread parks, and as it wakes up on a timeout. Returns 1 if the cache was trimmed, 0 otherwise.
+#[cold]
+#[no_mangle]
+pub extern "C" fn ll_trim_idle_thread_cache() -> i32 { ALLOCATOR.trim_idle_thread_cache() as i32 }
+
+/// Selects the threshold of inactivity, in milliseconds, past which the cache of a thread is trimmed, or disables the
+/// trimming if 0.
+#[cold]
+#[no_mangle]
+pub extern "C" fn ll_set_idle_trim(milliseconds: u64) {
+    ALLOCATOR.set_idle_trim(if milliseconds == 0 { None } else { Some(Duration::from_millis(milliseconds)) })
+}
+
+/// Registers the current thread, creating its thread-local state, for the threads whose exit the platform does not
+/// know of, such as those created by raw `clone`.
+///
+/// Returns 0 on success, and a negative value otherwise.
+#[cold]
+#[no_mangle]
+pub extern "C" fn ll_register_thread() -> i32 { if ALLOCATOR.register_thread().is_ok() { 0 } else { -1 } }
+
+/// Registers the current thread, as `ll_register_thread`, and declares its profile: 0 for normal, 1 for
+/// latency-critical, and 2 for background, see `ThreadProfile`.
+///
+/// Returns 0 on success, and a negative value otherwise, including for an unknown profile.
+#[cold]
+#[no_mangle]
+pub extern "C" fn ll_register_thread_with(profile: i32) -> i32 {
+    let profile = match profile {
+        0 => ThreadProfile::Normal,
+        1 => ThreadProfile::LatencyCritical,
+        2 => ThreadProfile::Background,
+        _ => return -1,
+    };
+
+    if ALLOCATOR.register_thread_with(profile).is_ok() { 0 } else { -1 }
+}
+
+/// Unregisters the current thread, releasing its thread-local state, and the memory it caches.
+///
+/// #   Safety
+///
+/// -   Assumes that the allocations of the frame region of the thread, if in frame mode, are no longer in use.
+#[cold]
+#[no_mangle]
+pub unsafe extern "C" fn ll_unregister_thread() { ALLOCATOR.unregister_thread() }
 
 /// Allocates `size` bytes of memory, generally suitably aligned.
 ///
@@ -44,7 +119,7 @@ pub extern fn ll_reserve(target: usize) -> usize { ALLOCATOR.reserve(target) }
 /// -   The alignment of the type for which memory is allocated must be a power of 2.
 /// -   The size of the type for which memory is allocated must be a multiple of its alignment.
 /// -   Therefore, the greatest power of 2 which divides `size` is greater than the required alignment.
-pub extern fn ll_malloc(size: usize) -> *mut u8 {
+pub extern "C" fn ll_malloc(size: usize) -> *mut u8 {
     let shift = size.trailing_zeros();
     let alignment = 1usize << shift;
 
@@ -66,7 +141,7 @@ pub extern fn ll_malloc(size: usize) -> *mut u8 {
 /// -   Assumes that `alignment` is non-zero.
 /// -   Assumes that `alignment` is a power of 2.
 /// -   Assumes that `size` is a multiple of `alignment`.
-pub unsafe extern fn ll_aligned_malloc(size: usize, alignment: usize) -> *mut u8 {
+pub unsafe extern "C" fn ll_aligned_malloc(size: usize, alignment: usize) -> *mut u8 {
         //  Safety:
     //  -   `alignment` is non-zero.
     //  -   `alignment` is a power of 2.
@@ -83,7 +158,7 @@ pub unsafe extern fn ll_aligned_malloc(size: usize, alignment: usize) -> *mut u8
 /// -   Assumes `pointer` has been returned by a prior call to `allocate`.
 /// -   Assumes `pointer` has not been deallocated since its allocation.
 /// -   Assumes the memory pointed by `pointer` is no longer in use.
-pub unsafe extern fn ll_free(pointer: *mut u8) {
+pub unsafe extern "C" fn ll_free(pointer: *mut u8) {
     if let Some(pointer) = NonNull::new(pointer) {
         ALLOCATOR.deallocate(pointer)
     }
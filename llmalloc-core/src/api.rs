@@ -3,13 +3,17 @@
 mod configuration;
 mod description;
 mod domain;
+mod histogram;
 mod platform;
 mod socket;
+mod statistics;
 mod thread;
 
 pub use configuration::{Configuration, Properties};
-pub use description::{AllocationSize, Category, ClassSize, Layout, PowerOf2};
+pub use description::{AllocationSize, Category, ClassSize, Criticality, Layout, PowerOf2};
 pub use domain::DomainHandle;
+pub use histogram::SizeHistogram;
 pub use platform::Platform;
 pub use socket::{AtomicSocketHandle, SocketHandle};
+pub use statistics::{CategoryStatistics, Statistics, StatisticsEpoch};
 pub use thread::ThreadHandle;
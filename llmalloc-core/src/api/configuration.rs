@@ -100,14 +100,36 @@ impl<C> Properties<C>
     }
 
     /// Returns the allocation size and alignment of an allocation, based on its size.
+    ///
+    /// With the `buddy` feature, the size of Large allocations up to half a Huge Page is rounded up to a power of 2
+    /// number of Large Pages.
     pub fn layout_of_size(size: usize) -> Layout {
         match Self::category_of_size(size) {
-            Category::Normal => Self::class_size_of_size(size).expect("Normal").layout(),
-            Category::Large => Self::page_layout(C::LARGE_PAGE_SIZE, size),
+            //  A size of 0 is served by the smallest class size.
+            Category::Normal => Self::class_size_of_size(size).unwrap_or_default().layout(),
+            Category::Large => Self::large_layout(size),
             Category::Huge => Self::page_layout(C::HUGE_PAGE_SIZE, size),
         }
     }
 
+    #[cfg(not(feature = "buddy"))]
+    fn large_layout(size: usize) -> Layout { Self::page_layout(C::LARGE_PAGE_SIZE, size) }
+
+    #[cfg(feature = "buddy")]
+    fn large_layout(size: usize) -> Layout {
+        let layout = Self::page_layout(C::LARGE_PAGE_SIZE, size);
+
+        let number_pages = layout.size() / C::LARGE_PAGE_SIZE;
+
+        if number_pages > C::HUGE_PAGE_SIZE / C::LARGE_PAGE_SIZE / 2 {
+            return layout;
+        }
+
+        //  Safety:
+        //  -   The size is a multiple of the alignment, a power of 2.
+        unsafe { Layout::from_size_align_unchecked(number_pages.next_power_of_two() * C::LARGE_PAGE_SIZE, layout.align()) }
+    }
+
     fn page_layout(page_size: PowerOf2, size: usize) -> Layout {
         let size = page_size.round_up(size);
         let align = page_size.value();
@@ -232,6 +254,15 @@ fn properties_layout_of_size() {
     assert_eq!((2048, 2048), layout(2048));
     assert_eq!((4096, 2048), layout(2049));
     assert_eq!((4096, 2048), layout(4096));
+
+    if cfg!(feature = "buddy") {
+        assert_eq!((8192, 2048), layout(4097));
+        assert_eq!((1 << 19, 2048), layout((1 << 18) + 1));
+    } else {
+        assert_eq!((6144, 2048), layout(4097));
+        assert_eq!(((1 << 18) + 2048, 2048), layout((1 << 18) + 1));
+    }
+
     assert_eq!((1_046_528, 2048), layout(1_046_527));
     assert_eq!((1_046_528, 2048), layout(1_046_528));
 
@@ -38,6 +38,27 @@ pub enum Category {
     Huge,
 }
 
+/// Criticality
+///
+/// The Criticality of a thread, as declared through its ThreadHandle.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum Criticality {
+    /// Background.
+    ///
+    /// Background threads are served as Normal threads, it being up to the user of the handle to keep their caches
+    /// minimal.
+    Background,
+    /// Normal.
+    ///
+    /// Normal threads are served from the LargePages of the socket, never from those reserved for Critical threads.
+    #[default]
+    Normal,
+    /// Critical.
+    ///
+    /// Critical threads are served from the LargePages of the socket, and once exhausted from those reserved for them.
+    Critical,
+}
+
 /// ClassSize
 ///
 /// The class size of a Normal allocation.
@@ -218,6 +239,7 @@ mod tests {
 use super::*;
 
 #[test]
+#[allow(clippy::assertions_on_constants)]
 fn assumptions() {
     assert!(MIN_ALLOCATION_SIZE >= 4);
     assert_eq!(1, MIN_ALLOCATION_SIZE.count_ones());
@@ -241,7 +263,7 @@ fn class_size_number_classes() {
     assert_eq!(28, number_classes(256 * MIN_ALLOCATION_SIZE));  //    8KB page
     assert_eq!(32, number_classes(512 * MIN_ALLOCATION_SIZE));  //   16KB page
 
-    assert_eq!(36, number_classes(  1 * 1024 * MIN_ALLOCATION_SIZE));   //   32KB page
+    assert_eq!(36, number_classes(  1024 * MIN_ALLOCATION_SIZE));   //   32KB page
     assert_eq!(40, number_classes(  2 * 1024 * MIN_ALLOCATION_SIZE));   //   64KB page
     assert_eq!(44, number_classes(  4 * 1024 * MIN_ALLOCATION_SIZE));   //  128KB page
     assert_eq!(48, number_classes(  8 * 1024 * MIN_ALLOCATION_SIZE));   //  256KB page
@@ -331,16 +353,16 @@ fn class_size_layout() {
     let base = MIN_ALLOCATION_SIZE;
 
     assert_eq!((4 * base / 4, 4 * base / 4), layout(0));
-    assert_eq!((5 * base / 4, 1 * base / 4), layout(1));
+    assert_eq!((5 * base / 4, base / 4), layout(1));
     assert_eq!((6 * base / 4, 2 * base / 4), layout(2));
-    assert_eq!((7 * base / 4, 1 * base / 4), layout(3));
+    assert_eq!((7 * base / 4, base / 4), layout(3));
 
     assert_eq!((4 * base / 2, 4 * base / 2), layout(4));
-    assert_eq!((5 * base / 2, 1 * base / 2), layout(5));
+    assert_eq!((5 * base / 2, base / 2), layout(5));
     assert_eq!((6 * base / 2, 2 * base / 2), layout(6));
-    assert_eq!((7 * base / 2, 1 * base / 2), layout(7));
+    assert_eq!((7 * base / 2, base / 2), layout(7));
 
-    assert_eq!((4 * base / 1, 4 * base / 1), layout(8));
+    assert_eq!((4 * base, 4 * base), layout(8));
 }
 
 #[test]
@@ -392,16 +414,16 @@ fn class_size_properties() {
     let base = MIN_ALLOCATION_SIZE;
 
     assert_eq!((4 * base / 4, 4 * base / 4), properties(0));
-    assert_eq!((5 * base / 4, 1 * base / 4), properties(1));
+    assert_eq!((5 * base / 4, base / 4), properties(1));
     assert_eq!((6 * base / 4, 2 * base / 4), properties(2));
-    assert_eq!((7 * base / 4, 1 * base / 4), properties(3));
+    assert_eq!((7 * base / 4, base / 4), properties(3));
 
     assert_eq!((4 * base / 2, 4 * base / 2), properties(4));
-    assert_eq!((5 * base / 2, 1 * base / 2), properties(5));
+    assert_eq!((5 * base / 2, base / 2), properties(5));
     assert_eq!((6 * base / 2, 2 * base / 2), properties(6));
-    assert_eq!((7 * base / 2, 1 * base / 2), properties(7));
+    assert_eq!((7 * base / 2, base / 2), properties(7));
 
-    assert_eq!((4 * base / 1, 4 * base / 1), properties(8));
+    assert_eq!((4 * base, 4 * base), properties(8));
 }
 
 #[test]
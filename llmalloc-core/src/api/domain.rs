@@ -12,6 +12,9 @@
 //!
 //! Typically, applications will use a global Domain.
 
+use core::ptr::NonNull;
+
+use crate::{Configuration, Platform};
 use crate::internals::huge_allocator::HugeAllocator;
 
 /// Domain Handle.
@@ -35,10 +38,49 @@ impl<C, P> DomainHandle<C, P> {
     /// Returns a reference to the underlying platform.
     pub fn platform(&self) -> &P { self.0.platform() }
 
+    /// Returns whether deallocated Huge allocations are retained for reuse, which they are by default.
+    pub fn is_retaining(&self) -> bool { self.0.is_retaining() }
+
+    /// Sets whether deallocated Huge allocations are retained for reuse.
+    ///
+    /// When not retaining, Huge allocations are returned to the `platform` on deallocation, except for those carved
+    /// from the blocks retained beforehand.
+    pub fn set_retaining(&self, retaining: bool) { self.0.set_retaining(retaining) }
+
     /// Returns a reference to the raw handle.
     pub(crate) fn as_raw(&self) -> &HugeAllocator<C, P> { &self.0 }
 }
 
+impl<C, P> DomainHandle<C, P>
+    where
+        C: Configuration,
+        P: Platform,
+{
+    /// Invokes `f` with the address and size of each Huge block retained for reuse, see `set_retaining`.
+    ///
+    /// The blocks are neither in use nor returned to the `platform`. The enumeration races with concurrent Huge
+    /// allocations and deallocations, and is therefore only a snapshot.
+    pub fn for_each_retained<F>(&self, f: F)
+        where
+            F: FnMut(NonNull<u8>, usize),
+    {
+        self.0.for_each_retained(f)
+    }
+
+    /// Decays the Huge blocks retained for reuse, see `set_retaining`, returning the number of bytes purged.
+    ///
+    /// Each pass ages the retained blocks, and forcibly purges, through the `platform`, those aged by a previous pass
+    /// and not reused since: a block is thus purged by the second pass following its retention. Purged blocks remain
+    /// retained, and available for reuse. Passes are expected to be spaced by a decay window.
+    pub fn decay(&self) -> usize { self.0.decay() }
+
+    /// Forcibly purges the Huge blocks retained for reuse, see `set_retaining`, returning the number of bytes purged.
+    ///
+    /// Unlike `decay`, every retained block is purged, however recently retained, for example to return memory to the
+    /// OS ahead of a mapping. Purged blocks remain retained, and available for reuse.
+    pub fn purge(&self) -> usize { self.0.purge() }
+}
+
 impl<C, P> Default for DomainHandle<C, P>
     where
         P: Default
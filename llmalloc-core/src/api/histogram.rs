@@ -0,0 +1,148 @@
+//! Size Histogram.
+//!
+//! A log-bucketed histogram of the requested allocation sizes, to check whether the geometry of the class sizes matches
+//! the workload.
+//!
+//! The recording of the histogram is only enabled with the `histogram` feature, otherwise it always reads as empty.
+
+use core::ops::RangeInclusive;
+
+/// Size Histogram.
+///
+/// The bucket of index `i` counts the requests of a size within `(2^(i-1), 2^i]`, with the bucket of index 0 also
+/// counting the requests of size 0, and the last bucket also counting all larger requests.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct SizeHistogram {
+    buckets: [usize; SizeHistogram::BUCKETS],
+}
+
+impl SizeHistogram {
+    /// The number of buckets.
+    pub const BUCKETS: usize = 64;
+
+    /// Creates an instance from its buckets.
+    pub const fn from_buckets(buckets: [usize; SizeHistogram::BUCKETS]) -> Self { Self { buckets } }
+
+    /// Returns the index of the bucket counting the requests of `size`.
+    pub fn bucket_of(size: usize) -> usize {
+        if size <= 1 {
+            return 0;
+        }
+
+        let index = (usize::BITS - (size - 1).leading_zeros()) as usize;
+
+        if index < Self::BUCKETS { index } else { Self::BUCKETS - 1 }
+    }
+
+    /// Returns the range of sizes counted by the bucket of index `index`.
+    ///
+    /// #   Panics
+    ///
+    /// If `index` is not less than `BUCKETS`.
+    pub fn bucket_range(index: usize) -> RangeInclusive<usize> {
+        assert!(index < Self::BUCKETS);
+
+        match index {
+            0 => 0..=1,
+            _ if index == Self::BUCKETS - 1 => ((1 << (index - 1)) + 1)..=usize::MAX,
+            _ => ((1 << (index - 1)) + 1)..=(1 << index),
+        }
+    }
+
+    /// Returns the counts of all buckets.
+    pub fn buckets(&self) -> &[usize; SizeHistogram::BUCKETS] { &self.buckets }
+
+    /// Returns the count of the bucket of index `index`.
+    ///
+    /// #   Panics
+    ///
+    /// If `index` is not less than `BUCKETS`.
+    pub fn count(&self, index: usize) -> usize { self.buckets[index] }
+
+    /// Returns the total count, across all buckets.
+    pub fn total(&self) -> usize { self.buckets.iter().fold(0, |total, count| total.wrapping_add(*count)) }
+
+    /// Returns an iterator over the non-empty buckets, as pairs of range of sizes and count.
+    pub fn iter(&self) -> impl Iterator<Item = (RangeInclusive<usize>, usize)> + '_ {
+        self.buckets.iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| (Self::bucket_range(index), *count))
+    }
+
+    /// Returns the histogram accumulated since `baseline`.
+    pub fn since(&self, baseline: &SizeHistogram) -> SizeHistogram {
+        let mut result = *self;
+
+        for (count, base) in result.buckets.iter_mut().zip(baseline.buckets.iter()) {
+            *count = count.wrapping_sub(*base);
+        }
+
+        result
+    }
+
+    /// Accumulates `other` into `self`.
+    pub fn merge(&mut self, other: &SizeHistogram) {
+        for (count, other) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *count = count.wrapping_add(*other);
+        }
+    }
+}
+
+impl Default for SizeHistogram {
+    fn default() -> Self { Self::from_buckets([0; SizeHistogram::BUCKETS]) }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn size_histogram_bucket_of() {
+    assert_eq!(0, SizeHistogram::bucket_of(0));
+    assert_eq!(0, SizeHistogram::bucket_of(1));
+    assert_eq!(1, SizeHistogram::bucket_of(2));
+    assert_eq!(2, SizeHistogram::bucket_of(3));
+    assert_eq!(2, SizeHistogram::bucket_of(4));
+    assert_eq!(3, SizeHistogram::bucket_of(5));
+    assert_eq!(10, SizeHistogram::bucket_of(1024));
+    assert_eq!(11, SizeHistogram::bucket_of(1025));
+    assert_eq!(63, SizeHistogram::bucket_of(usize::MAX));
+}
+
+#[test]
+fn size_histogram_bucket_range() {
+    assert_eq!(0..=1, SizeHistogram::bucket_range(0));
+    assert_eq!(2..=2, SizeHistogram::bucket_range(1));
+    assert_eq!(3..=4, SizeHistogram::bucket_range(2));
+    assert_eq!(513..=1024, SizeHistogram::bucket_range(10));
+    assert_eq!(((1 << 62) + 1)..=usize::MAX, SizeHistogram::bucket_range(63));
+
+    for index in 0..SizeHistogram::BUCKETS {
+        let range = SizeHistogram::bucket_range(index);
+
+        assert_eq!(index, SizeHistogram::bucket_of(*range.start()));
+        assert_eq!(index, SizeHistogram::bucket_of(*range.end()));
+    }
+}
+
+#[test]
+fn size_histogram_since_merge() {
+    let mut buckets = [0; SizeHistogram::BUCKETS];
+    buckets[3] = 5;
+    buckets[7] = 2;
+
+    let histogram = SizeHistogram::from_buckets(buckets);
+
+    assert_eq!(7, histogram.total());
+    assert_eq!(vec![(5..=8, 5), (65..=128, 2)], histogram.iter().collect::<Vec<_>>());
+
+    let mut doubled = histogram;
+    doubled.merge(&histogram);
+
+    assert_eq!(10, doubled.count(3));
+    assert_eq!(histogram, doubled.since(&histogram));
+}
+
+} // mod tests
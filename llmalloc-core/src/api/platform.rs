@@ -35,4 +35,103 @@ pub trait Platform {
     /// -   `pointer` was allocated by this instance of `Platform`, with `layout` as argument.
     /// -   `pointer` is the value returned by `Plaform`, and not an interior pointer.
     unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout);
+
+    /// Resizes the supplied block of memory to `new_size` bytes, preserving its content, without copying it.
+    ///
+    /// Returns the pointer to the resized block, which may differ from `pointer`, or None if the block cannot be
+    /// resized in this fashion, in which case it is left untouched.
+    ///
+    /// The default implementation always returns None.
+    ///
+    /// #   Safety
+    ///
+    /// On success, the caller should no longer reference the memory through `pointer`, unless returned, and the
+    /// resized block is to be deallocated with a size of `new_size`.
+    ///
+    /// The caller may assume that if the returned pointer is not null then it is _at least_ aligned to
+    /// `layout.align()`.
+    ///
+    /// `reallocate` assumes that:
+    /// -   `pointer` points to a block of `layout.size()` bytes allocated by this instance of `Platform`, or carved out
+    ///     of such blocks.
+    /// -   `pointer` is aligned to `layout.align()`.
+    /// -   `new_size` is a non-zero multiple of `layout.align()`.
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        let _ = (pointer, layout, new_size);
+
+        None
+    }
+
+    /// Names the supplied block of memory as hosting a Huge allocation.
+    ///
+    /// Invoked on the blocks freshly allocated for a Huge allocation, unlike those allocated for a `HugePage`, so that
+    /// the platform may tell both apart, for example when labelling its mappings.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// #   Safety
+    ///
+    /// `name_huge` assumes that:
+    /// -   `pointer` points to a block of `size` bytes allocated by this instance of `Platform`.
+    unsafe fn name_huge(&self, pointer: NonNull<u8>, size: usize) { let _ = (pointer, size); }
+
+    /// Decommits the supplied range of memory, releasing its physical memory while keeping its address range.
+    ///
+    /// Invoked on the `LargePage`s of a `HugePage` as they become free, the range stays reserved for future
+    /// allocations, by which its content is no longer expected to be preserved.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// #   Safety
+    ///
+    /// `decommit` assumes that:
+    /// -   `pointer` points to `size` bytes carved out of a block allocated by this instance of `Platform`.
+    /// -   `pointer` and `size` are multiples of the size of a `LargePage`.
+    /// -   The memory is no longer in use.
+    unsafe fn decommit(&self, pointer: NonNull<u8>, size: usize) { let _ = (pointer, size); }
+
+    /// Lazily purges the supplied range of memory, allowing the platform to reclaim its physical memory at leisure.
+    ///
+    /// Invoked on the Huge blocks retained for reuse as they are deallocated, the range may be reused as is, its
+    /// content being either preserved or lost, until then.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// #   Safety
+    ///
+    /// `purge_lazy` assumes that:
+    /// -   `pointer` points to `size` bytes carved out of a block allocated by this instance of `Platform`.
+    /// -   `pointer` and `size` are multiples of the size of a `HugePage`.
+    /// -   The memory is no longer in use.
+    unsafe fn purge_lazy(&self, pointer: NonNull<u8>, size: usize) { let _ = (pointer, size); }
+
+    /// Forcibly purges the supplied range of memory, releasing its physical memory while keeping its address range.
+    ///
+    /// Invoked on the Huge blocks retained for reuse which have decayed, see `DomainHandle::decay`.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// #   Safety
+    ///
+    /// `purge_forced` assumes that:
+    /// -   `pointer` points to `size` bytes carved out of a block allocated by this instance of `Platform`.
+    /// -   `pointer` and `size` are multiples of the size of a `HugePage`.
+    /// -   The memory is no longer in use.
+    unsafe fn purge_forced(&self, pointer: NonNull<u8>, size: usize) { let _ = (pointer, size); }
+
+    /// Forcibly purges the supplied ranges of memory, each as a pointer and a size, as `purge_forced` does.
+    ///
+    /// Invoked on the batches of Huge blocks retained for reuse which are purged together, so that the platform may
+    /// purge a whole batch at once.
+    ///
+    /// The default implementation purges each range in turn, with `purge_forced`.
+    ///
+    /// #   Safety
+    ///
+    /// `purge_forced_batch` assumes that each of `ranges` meets the requirements of `purge_forced`.
+    unsafe fn purge_forced_batch(&self, ranges: &[(NonNull<u8>, usize)]) {
+        for &(pointer, size) in ranges {
+            self.purge_forced(pointer, size);
+        }
+    }
 }
@@ -15,11 +15,13 @@
 use core::{
     alloc::Layout,
     ptr::{self, NonNull},
-    sync::atomic::{AtomicPtr, Ordering},
 };
 
-use crate::{Configuration, DomainHandle, Platform, ThreadHandle};
-use crate::internals::socket_local::SocketLocal;
+use crate::{Configuration, DomainHandle, Platform, SizeHistogram, Statistics, ThreadHandle};
+use crate::internals::{
+    socket_local::SocketLocal,
+    sync::{AtomicPtr, Ordering},
+};
 
 /// A handle to socket-local memory structures.
 ///
@@ -66,6 +68,24 @@ where
         socket_local.release_thread_local(handle.into_raw());
     }
 
+    /// Flushes a `ThreadHandle`, returning the memory it caches to the socket, for the other threads to use.
+    ///
+    /// Unlike `release_thread_handle`, the `ThreadHandle` remains usable, fetching memory anew on its next allocations.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the `ThreadHandle` came from `self`.
+    /// -   Assumes that the `ThreadHandle` is not concurrently accessed by another thread.
+    pub unsafe fn flush_thread_handle(&self, handle: &ThreadHandle<C>) {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+
+        //  Safety:
+        //  -   `handle` is assumed to come from `socket`, and not to be concurrently accessed.
+        socket_local.flush_thread_local(handle.as_ref());
+    }
+
     /// Attempts to ensure that at least `target` `HugePage` are allocated on the socket.
     ///
     /// Returns the minimum of the currently allocated number of pages and `target`.
@@ -82,6 +102,137 @@ where
         socket_local.reserve(target)
     }
 
+    /// Attempts to ensure that `pages_per_class` LargePages of each Normal class size are reserved for the threads
+    /// declared Critical, so that Normal threads exhausting the memory cannot starve them.
+    ///
+    /// Returns the minimum, across class sizes, of the number of reserved pages and `pages_per_class`.
+    ///
+    /// The reserved pages are only handed out to Critical threads once no other page is available, and the reserve is
+    /// replenished first as pages are freed up. Lowering `pages_per_class` returns the reserved pages in excess.
+    pub fn reserve_critical(&self, pages_per_class: usize) -> usize {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = unsafe { self.0.as_ref() };
+
+        socket_local.reserve_critical(pages_per_class)
+    }
+
+    /// Attempts to ensure that the next `count` allocations of `layout`, through `thread_handle`, take the fast path,
+    /// pulling a LargePage with sufficiently many free blocks into its cache if need be.
+    ///
+    /// Returns the minimum of the number of such allocations and `count`, which is limited by the number of blocks of
+    /// a LargePage, and is 0 unless `layout` is a Normal allocation, as only those are cached by a thread.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `thread_handle` is not concurrently accessed by another thread.
+    /// -   Assumes that `thread_handle` belongs to this socket.
+    /// -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
+    pub unsafe fn reserve_ahead(&self, thread_handle: &ThreadHandle<C>, layout: Layout, count: usize) -> usize {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+        let thread_local = thread_handle.as_ref();
+
+        socket_local.reserve_ahead(thread_local, layout, count)
+    }
+
+    /// Returns the statistics of the allocations and deallocations performed by the socket, since its creation.
+    ///
+    /// The counters of each thread are updated without synchronization, hence the statistics are approximate while
+    /// threads are concurrently allocating or deallocating.
+    pub fn statistics(&self) -> Statistics {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = unsafe { self.0.as_ref() };
+
+        socket_local.statistics()
+    }
+
+    /// Returns the number of Normal allocations of the socket deallocated by the threads of other sockets, since its
+    /// creation.
+    ///
+    /// Such deallocations are queued onto the inbound queue of the socket, touching its memory from afar.
+    pub fn remote_deallocations(&self) -> usize {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = unsafe { self.0.as_ref() };
+
+        socket_local.remote_deallocations()
+    }
+
+    /// Returns whether `ptr` is a Normal or Large allocation of the socket.
+    ///
+    /// Huge allocations are shared by all sockets of a domain, hence are owned by none.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `ptr` is a value allocated by an instance of `Self`, and the same underlying `Platform`.
+    pub unsafe fn owns(&self, ptr: NonNull<u8>) -> bool {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+
+        socket_local.owns(ptr)
+    }
+
+    /// Returns the usable size of the block located at `ptr`, that is the size of its class for a Normal allocation,
+    /// or of its pages for a Large or Huge allocation, at least the size it was requested with.
+    ///
+    /// The block need not be owned by the socket, as long as it was allocated by a socket of the same domain.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `ptr` is a value allocated by an instance of `Self`, and the same underlying `Platform`.
+    /// -   Assumes that `ptr` has not been deallocated since.
+    pub unsafe fn size_of(&self, ptr: NonNull<u8>) -> usize {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+
+        socket_local.size_of(ptr)
+    }
+
+    /// Returns the histogram of the requested sizes of the allocations performed by the socket, since its creation.
+    ///
+    /// The histogram is always empty, unless the `histogram` feature is enabled.
+    pub fn size_histogram(&self) -> SizeHistogram {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = unsafe { self.0.as_ref() };
+
+        socket_local.size_histogram()
+    }
+
+    /// Returns the allocations deallocated by the threads of other sockets, pending on the inbound queue of the socket,
+    /// to their `LargePage`, returning whether any was pending.
+    ///
+    /// The pending allocations are otherwise only returned as the socket runs out of pages, on the path of an
+    /// allocation, or as one of its threads is flushed or released; this may be called from any thread, for example a
+    /// background thread, or ahead of a purge.
+    pub fn drain_inbound(&self) -> bool {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = unsafe { self.0.as_ref() };
+
+        socket_local.drain()
+    }
+
+    /// Invokes `f` with the address of each `HugePage` currently allocated by the socket.
+    ///
+    /// Each `HugePage` spans `C::HUGE_PAGE_SIZE` bytes, starting at its address. The first `HugePage` is the one
+    /// hosting the socket itself.
+    pub fn for_each_huge_page<F>(&self, f: F)
+        where
+            F: FnMut(NonNull<u8>),
+    {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = unsafe { self.0.as_ref() };
+
+        socket_local.for_each_huge_page(f)
+    }
+
     /// Deallocates all HugePages allocated by the socket.
     ///
     /// This may involve deallocating the memory used by the socket itself, after which it can no longer be used.
@@ -125,6 +276,111 @@ where
         socket_local.allocate(thread_local, layout)
     }
 
+    /// Allocates a fresh block of memory as per the specified layout, with a bounded latency.
+    ///
+    /// The block is served from the memory already acquired by the socket only: the `Platform` is never called upon,
+    /// and the deallocations queued by the threads of other sockets are not drained. Hence, unlike `allocate`, this
+    /// call may fail even though memory is available from the `Platform`.
+    ///
+    /// #   Safety
+    ///
+    /// The caller may assume that if the returned pointer is not null then:
+    /// -   The number of usable bytes is _greater than or equal_ to `layout.size()`.
+    /// -   The pointer is _at least_ aligned to `layout.align()`.
+    ///
+    /// `allocate_bounded` assumes that:
+    /// -   `thread_handle` is not concurrently accessed by another thread.
+    /// -   `thread_handle` belongs to this socket.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub unsafe fn allocate_bounded(&self, thread_handle: &ThreadHandle<C>, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+        let thread_local = thread_handle.as_ref();
+
+        socket_local.allocate_bounded(thread_local, layout)
+    }
+
+    /// Allocates a fresh block of memory, as per `layout`, as a Huge allocation regardless of its size.
+    ///
+    /// The block is a dedicated extent of whole `HugePage`, bypassing the caches of Normal and Large allocations; it
+    /// is deallocated as any other block.
+    ///
+    /// #   Safety
+    ///
+    /// The caller may assume that if the returned pointer is not null then:
+    /// -   The number of usable bytes is _greater than or equal_ to `layout.size()`.
+    /// -   The pointer is _at least_ aligned to `layout.align()`.
+    ///
+    /// `allocate_direct` assumes that:
+    /// -   `thread_handle` is not concurrently accessed by another thread.
+    /// -   `thread_handle` belongs to this socket.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub unsafe fn allocate_direct(&self, thread_handle: &ThreadHandle<C>, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+        let thread_local = thread_handle.as_ref();
+
+        socket_local.allocate_direct(thread_local, layout)
+    }
+
+    /// Allocates a fresh block of memory, as per `layout`, as a Huge allocation regardless of its size, with a bounded
+    /// latency.
+    ///
+    /// The block is served from the Huge allocations retained by the socket only: the `Platform` is never called upon.
+    ///
+    /// #   Safety
+    ///
+    /// The caller may assume that if the returned pointer is not null then:
+    /// -   The number of usable bytes is _greater than or equal_ to `layout.size()`.
+    /// -   The pointer is _at least_ aligned to `layout.align()`.
+    ///
+    /// `allocate_direct_bounded` assumes that:
+    /// -   `thread_handle` is not concurrently accessed by another thread.
+    /// -   `thread_handle` belongs to this socket.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub unsafe fn allocate_direct_bounded(&self, thread_handle: &ThreadHandle<C>, layout: Layout)
+        -> Option<NonNull<u8>>
+    {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+        let thread_local = thread_handle.as_ref();
+
+        socket_local.allocate_direct_bounded(thread_local, layout)
+    }
+
+    /// Reallocates a Huge allocation, as per `layout`, without copying its content, if the `Platform` supports it.
+    ///
+    /// Returns the pointer to the reallocated block, which may differ from `ptr`, or None if the block cannot be
+    /// resized in this fashion, in which case it is left untouched.
+    ///
+    /// #   Safety
+    ///
+    /// On success, the caller should no longer reference the memory through `ptr`, unless returned.
+    ///
+    /// `reallocate_huge` assumes that:
+    /// -   `thread_handle` is not concurrently accessed by another thread.
+    /// -   `thread_handle` belongs to this socket.
+    /// -   `ptr` is a Huge allocation, as per `Properties::category_of_pointer`, allocated by an instance of `Self`,
+    ///     and the same underlying `Platform`.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub unsafe fn reallocate_huge(&self, thread_handle: &ThreadHandle<C>, ptr: NonNull<u8>, layout: Layout)
+        -> Option<NonNull<u8>>
+    {
+        //  Safety:
+        //  -   Local lifetime.
+        let socket_local = self.0.as_ref();
+        let thread_local = thread_handle.as_ref();
+
+        socket_local.reallocate_huge(thread_local, ptr, layout)
+    }
+
     /// Deallocates the supplied block of memory.
     ///
     /// #   Safety
@@ -179,6 +435,12 @@ impl<'a, C, P> Clone for SocketHandle<'a, C, P> {
 
 impl<'a, C, P> Copy for SocketHandle<'a, C, P> {}
 
+impl<'a, C, P> PartialEq for SocketHandle<'a, C, P> {
+    fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+}
+
+impl<'a, C, P> Eq for SocketHandle<'a, C, P> {}
+
 /// A thread-safe handle to socket-local memory structures.
 ///
 /// #   Recommendation
@@ -0,0 +1,182 @@
+//! Statistics.
+//!
+//! Statistics are kept per allocation category, and cover both the number of allocations and deallocations and the
+//! number of bytes they account for.
+//!
+//! The counters are monotonic, and may wrap around on overflow; deltas should therefore be computed with `since`,
+//! which uses wrapping arithmetic.
+
+use crate::Category;
+use crate::internals::statistics::AtomicStatistics;
+
+/// Statistics of a single Category.
+///
+/// The number of bytes accounts for the effective size of the allocations, that is after rounding up to the class size
+/// or page size, rather than the requested size.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct CategoryStatistics {
+    /// The number of allocations.
+    pub allocations: usize,
+    /// The number of deallocations.
+    pub deallocations: usize,
+    /// The number of bytes allocated.
+    pub allocated_bytes: usize,
+    /// The number of bytes deallocated.
+    pub deallocated_bytes: usize,
+}
+
+impl CategoryStatistics {
+    /// Returns the number of live allocations.
+    pub fn live(&self) -> usize { self.allocations.wrapping_sub(self.deallocations) }
+
+    /// Returns the number of live bytes.
+    pub fn live_bytes(&self) -> usize { self.allocated_bytes.wrapping_sub(self.deallocated_bytes) }
+
+    /// Returns the statistics accumulated since `baseline`.
+    pub fn since(&self, baseline: &CategoryStatistics) -> CategoryStatistics {
+        CategoryStatistics {
+            allocations: self.allocations.wrapping_sub(baseline.allocations),
+            deallocations: self.deallocations.wrapping_sub(baseline.deallocations),
+            allocated_bytes: self.allocated_bytes.wrapping_sub(baseline.allocated_bytes),
+            deallocated_bytes: self.deallocated_bytes.wrapping_sub(baseline.deallocated_bytes),
+        }
+    }
+
+    /// Accumulates `other` into `self`.
+    pub fn merge(&mut self, other: &CategoryStatistics) {
+        self.allocations = self.allocations.wrapping_add(other.allocations);
+        self.deallocations = self.deallocations.wrapping_add(other.deallocations);
+        self.allocated_bytes = self.allocated_bytes.wrapping_add(other.allocated_bytes);
+        self.deallocated_bytes = self.deallocated_bytes.wrapping_add(other.deallocated_bytes);
+    }
+}
+
+/// Statistics of all Categories.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Statistics {
+    /// Statistics of Normal allocations.
+    pub normal: CategoryStatistics,
+    /// Statistics of Large allocations.
+    pub large: CategoryStatistics,
+    /// Statistics of Huge allocations.
+    pub huge: CategoryStatistics,
+}
+
+impl Statistics {
+    /// Returns the statistics of a given category.
+    pub fn category(&self, category: Category) -> &CategoryStatistics {
+        match category {
+            Category::Normal => &self.normal,
+            Category::Large => &self.large,
+            Category::Huge => &self.huge,
+        }
+    }
+
+    /// Returns the statistics of all categories, combined.
+    pub fn total(&self) -> CategoryStatistics {
+        let mut total = self.normal;
+        total.merge(&self.large);
+        total.merge(&self.huge);
+        total
+    }
+
+    /// Returns the statistics accumulated since `baseline`.
+    pub fn since(&self, baseline: &Statistics) -> Statistics {
+        Statistics {
+            normal: self.normal.since(&baseline.normal),
+            large: self.large.since(&baseline.large),
+            huge: self.huge.since(&baseline.huge),
+        }
+    }
+
+    /// Accumulates `other` into `self`.
+    pub fn merge(&mut self, other: &Statistics) {
+        self.normal.merge(&other.normal);
+        self.large.merge(&other.large);
+        self.huge.merge(&other.huge);
+    }
+}
+
+/// Statistics Epoch.
+///
+/// A thread-safe baseline of statistics, from which deltas can be computed.
+///
+/// Advancing the epoch atomically swaps the baseline counter by counter, hence concurrent calls to `advance` partition
+/// the deltas between themselves, without any allocation or deallocation being reported twice.
+#[derive(Default)]
+pub struct StatisticsEpoch(AtomicStatistics);
+
+impl StatisticsEpoch {
+    /// Creates an instance, with a zero baseline.
+    pub const fn new() -> Self { Self(AtomicStatistics::new()) }
+
+    /// Returns the current baseline.
+    pub fn baseline(&self) -> Statistics { self.0.snapshot() }
+
+    /// Resets the baseline to `current`.
+    pub fn reset(&self, current: &Statistics) { self.0.store(current); }
+
+    /// Returns the statistics accumulated between the baseline and `current`.
+    pub fn since(&self, current: &Statistics) -> Statistics { current.since(&self.0.snapshot()) }
+
+    /// Returns the statistics accumulated between the baseline and `current`, and sets the baseline to `current`.
+    pub fn advance(&self, current: &Statistics) -> Statistics { current.since(&self.0.swap(current)) }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+fn category(allocations: usize, deallocations: usize) -> CategoryStatistics {
+    let allocated_bytes = allocations * 16;
+    let deallocated_bytes = deallocations * 16;
+
+    CategoryStatistics { allocations, deallocations, allocated_bytes, deallocated_bytes }
+}
+
+#[test]
+fn category_statistics_live() {
+    let statistics = category(5, 3);
+
+    assert_eq!(2, statistics.live());
+    assert_eq!(32, statistics.live_bytes());
+}
+
+#[test]
+fn category_statistics_since() {
+    assert_eq!(category(2, 1), category(5, 3).since(&category(3, 2)));
+
+    //  Wrapping around.
+    let before = CategoryStatistics { allocations: usize::MAX, ..category(0, 0) };
+    let after = CategoryStatistics { allocations: 1, ..category(0, 0) };
+
+    assert_eq!(2, after.since(&before).allocations);
+}
+
+#[test]
+fn statistics_total() {
+    let statistics = Statistics { normal: category(1, 0), large: category(2, 1), huge: category(3, 2) };
+
+    assert_eq!(&category(2, 1), statistics.category(Category::Large));
+    assert_eq!(category(6, 3), statistics.total());
+}
+
+#[test]
+fn statistics_epoch_advance() {
+    let epoch = StatisticsEpoch::new();
+
+    let first = Statistics { normal: category(4, 2), ..Statistics::default() };
+    let second = Statistics { normal: category(7, 6), ..Statistics::default() };
+
+    assert_eq!(first, epoch.since(&first));
+    assert_eq!(first, epoch.advance(&first));
+
+    assert_eq!(first, epoch.baseline());
+    assert_eq!(Statistics { normal: category(3, 4), ..Statistics::default() }, epoch.advance(&second));
+
+    epoch.reset(&Statistics::default());
+    assert_eq!(second, epoch.since(&second));
+}
+
+} // mod tests
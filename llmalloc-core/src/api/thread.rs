@@ -12,7 +12,7 @@
 
 use core::ptr::NonNull;
 
-use crate::{Configuration, SocketHandle};
+use crate::{Configuration, Criticality, SocketHandle, Statistics};
 use crate::internals::thread_local::ThreadLocal;
 
 /// Handle to thread-local cache.
@@ -46,6 +46,99 @@ impl<C> ThreadHandle<C>
         SocketHandle::from(NonNull::new_unchecked(socket).cast())
     }
 
+    /// Returns the criticality of the thread, Normal unless declared otherwise.
+    pub fn criticality(&self) -> Criticality {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().criticality() }
+    }
+
+    /// Declares the criticality of the thread.
+    ///
+    /// Critical threads may dip into the LargePages reserved for them, see `SocketHandle::reserve_critical`.
+    pub fn set_criticality(&self, criticality: Criticality) {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().set_criticality(criticality) }
+    }
+
+    /// Returns the statistics of the allocations and deallocations performed through the handle.
+    ///
+    /// The statistics are preserved when the underlying thread-local cache is reused by another thread, hence they may
+    /// predate the current thread.
+    pub fn statistics(&self) -> Statistics {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().statistics().snapshot() }
+    }
+
+    /// Returns the number of scopes forbidding allocations entered by the thread, 0 unless declared otherwise.
+    ///
+    /// The scopes are only recorded, it is up to the user of the handle to refuse the allocations within them.
+    pub fn forbidden_scopes(&self) -> u32 {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().forbidden_scopes() }
+    }
+
+    /// Declares the number of scopes forbidding allocations entered by the thread.
+    pub fn set_forbidden_scopes(&self, scopes: u32) {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().set_forbidden_scopes(scopes) }
+    }
+
+    /// Returns the cached NUMA node of the thread, 0 unless declared otherwise.
+    ///
+    /// The cache is merely recorded, and opaque to the core: it is up to the user of the handle to encode the node, and
+    /// to decide when to refresh it.
+    pub fn node_cache(&self) -> u64 {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().node_cache() }
+    }
+
+    /// Declares the cached NUMA node of the thread.
+    pub fn set_node_cache(&self, node_cache: u64) {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().set_node_cache(node_cache) }
+    }
+
+    /// Returns the recorded idleness of the thread, 0 unless declared otherwise.
+    ///
+    /// The idleness is merely recorded, and opaque to the core: it is up to the user of the handle to encode it, and to
+    /// decide when to trim the cache of the thread.
+    pub fn idle_cache(&self) -> u64 {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().idle_cache() }
+    }
+
+    /// Declares the recorded idleness of the thread.
+    pub fn set_idle_cache(&self, idle_cache: u64) {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().set_idle_cache(idle_cache) }
+    }
+
+    /// Returns the frame region of the thread, if any.
+    ///
+    /// The frame region is only recorded, it is up to the user of the handle to allocate from it, and to release it
+    /// before releasing the handle.
+    pub fn frame(&self) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().frame() }
+    }
+
+    /// Declares the frame region of the thread, or its absence.
+    pub fn set_frame(&self, frame: Option<NonNull<u8>>) {
+        //  Safety:
+        //  -   The handle is assumed to be used from a single thread.
+        unsafe { self.as_ref().set_frame(frame) }
+    }
+
     /// Creates an instance.
     pub(crate) fn new(value: NonNull<ThreadLocal<C>>) -> Self { Self(value) }
 
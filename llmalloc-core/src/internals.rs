@@ -3,11 +3,16 @@
 //! The internals provide all the heavy-lifting.
 
 pub mod blocks;
+#[cfg(feature = "histogram")]
+pub mod histogram;
 pub mod huge_allocator;
 pub mod huge_page;
 pub mod large_page;
 pub mod socket_local;
+pub mod statistics;
+pub mod sync;
 pub mod thread_local;
 
 mod atomic;
 mod atomic_stack;
+mod purge_queue;
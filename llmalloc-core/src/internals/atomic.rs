@@ -1,13 +1,12 @@
 //! Building brick for List and Stack.
 
-use core::{
-    ptr::{self, NonNull},
-    sync::atomic::{self, Ordering},
-};
+use core::ptr::{self, NonNull};
+
+use super::sync::{self, Ordering};
 
 //  Automatically uses Acquire/Release, to synchronize before CellLocal conversion.
 #[derive(Default)]
-pub(crate) struct AtomicLength(atomic::AtomicUsize);
+pub(crate) struct AtomicLength(sync::AtomicUsize);
 
 impl AtomicLength {
     pub(crate) fn load(&self) -> usize { self.0.load(Ordering::Acquire) }
@@ -16,7 +15,7 @@ impl AtomicLength {
 }
 
 //  Automatically uses Acquire/Release, to synchronize before CellLocal conversion.
-pub(crate) struct AtomicPtr<T>(atomic::AtomicPtr<T>);
+pub(crate) struct AtomicPtr<T>(sync::AtomicPtr<T>);
 
 impl<T> AtomicPtr<T> {
     pub(crate) fn load(&self) -> Option<NonNull<T>> { NonNull::new(self.0.load(Ordering::Acquire)) }
@@ -37,7 +36,7 @@ impl<T> AtomicPtr<T> {
 }
 
 impl<T> Default for AtomicPtr<T> {
-    fn default() -> Self { Self(atomic::AtomicPtr::new(ptr::null_mut())) }
+    fn default() -> Self { Self(sync::AtomicPtr::new(ptr::null_mut())) }
 }
 
 //
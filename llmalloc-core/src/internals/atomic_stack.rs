@@ -27,10 +27,10 @@
 use core::{
     marker::PhantomData,
     ptr::NonNull,
-    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use crate::PowerOf2;
+use crate::internals::sync::{AtomicUsize, Ordering};
 
 /// AtomicStackElement
 pub(crate) trait AtomicStackElement : Sized {
@@ -264,6 +264,7 @@ fn atomic_stack_pop_push() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_stack_concurrent_push_concurrent_pop_fuzzing() {
     //  The test aims at validating that:
     //  -   Multiple threads can push concurrently.
@@ -289,7 +290,7 @@ fn atomic_stack_concurrent_push_concurrent_pop_fuzzing() {
     builder.add_simple_step(|| |stack: &Stack, local: &mut Local| {
         //  Safety:
         //  -   Let's not access stack elements after Local dies, eh?
-        stack.push(unsafe { mem::transmute(&mut local.0) });
+        stack.push(unsafe { mem::transmute::<&mut Element, &'static mut Element>(&mut local.0) });
     });
 
     //  Step 2: Pop one of the pushed elements.
@@ -308,6 +309,7 @@ fn atomic_stack_concurrent_push_concurrent_pop_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_stack_concurrent_push_pop_fuzzing() {
     //  The test aims at validating that multiple threads can push _and_ pop concurrently.
     //
@@ -324,14 +326,14 @@ fn atomic_stack_concurrent_push_pop_fuzzing() {
     }
 
     impl Local {
-        fn is_pop_then_push(&self) -> bool { self.index % 2 != 0 }
+        fn is_pop_then_push(&self) -> bool { !self.index.is_multiple_of(2) }
     }
 
     //  Safety:
     //  -   Guaranteed to have exclusive access to its `element`.
     unsafe impl Send for Local {}
 
-    let store = vec!(Element::default(), Element::default(), Element::default(), Element::default());
+    let store = [Element::default(), Element::default(), Element::default(), Element::default()];
 
     let elements: Vec<_> = store.iter()
         .enumerate()
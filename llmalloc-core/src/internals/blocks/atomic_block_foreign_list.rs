@@ -3,9 +3,10 @@
 use core::{
     ops::Deref,
     ptr::NonNull,
-    sync::atomic::{self, Ordering},
 };
 
+use crate::internals::sync::{self, Ordering};
+
 use super::{AtomicPtr, AtomicBlockForeign, BlockForeignList};
 
 /// AtomicBlockForeignList.
@@ -40,7 +41,7 @@ impl AtomicBlockForeignList {
         //  -   The list is assumed not to be empty.
         let (head, tail) = list.steal();
 
-        atomic::fence(Ordering::Release);
+        sync::fence(Ordering::Release);
 
         //  Safety:
         //  -   Access to the list blocks is exclusive.
@@ -99,7 +100,10 @@ impl Deref for AtomicBlockForeignList {
 #[cfg(test)]
 mod tests {
 
-use std::ops::Range;
+use std::{
+    ops::Range,
+    sync::atomic,
+};
 
 use llmalloc_test::BurstyBuilder;
 
@@ -158,6 +162,7 @@ fn block_foreign_ptr_extend_steal() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_block_foreign_list_concurrent_steal_fuzzing() {
     //  This test aims at validating that multiple threads can steal concurrently.
     //
@@ -219,6 +224,7 @@ fn atomic_block_foreign_list_concurrent_steal_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_block_foreign_list_concurrent_extend_fuzzing() {
     //  This test aims at validating that multiple threads can extend concurrently.
     //
@@ -279,6 +285,7 @@ fn atomic_block_foreign_list_concurrent_extend_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_block_foreign_list_concurrent_extend_steal_fuzzing() {
     //  This test aims at validating that multiple threads can extend _and_ steal concurrently.
     //
@@ -187,6 +187,27 @@ impl BlockLocalStack {
         self.set(Some(head));
     }
 
+    /// Returns the number of blocks of the tail-list, counting no further than `limit`.
+    #[cfg_attr(feature = "bitmap", allow(dead_code))]
+    pub(crate) fn count(&self, limit: usize) -> usize {
+        let mut count = 0;
+        let mut current = self.get();
+
+        while let Some(block) = current {
+            if count == limit {
+                break;
+            }
+
+            count += 1;
+
+            //  Safety:
+            //  -   Non-null, and valid instance.
+            current = unsafe { block.as_ref().next.get() };
+        }
+
+        count
+    }
+
     /// Returns the pointer, possibly null.
     #[cfg(test)]
     pub(crate) fn peek(&self) -> Option<NonNull<BlockLocal>> { self.get() }
@@ -0,0 +1,76 @@
+//! Histogram
+//!
+//! Atomic counters of requested allocation sizes, log-bucketed.
+
+use crate::internals::sync::{AtomicUsize, Ordering};
+
+use crate::SizeHistogram;
+
+/// Atomic Size Histogram.
+pub(crate) struct AtomicSizeHistogram {
+    buckets: [AtomicUsize; SizeHistogram::BUCKETS],
+}
+
+impl AtomicSizeHistogram {
+    /// Creates an instance, with all buckets at 0.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+        Self { buckets: [ZERO; SizeHistogram::BUCKETS] }
+    }
+
+    /// Records a request of `size` bytes.
+    ///
+    /// Concurrent writers may lose updates, but never cause memory unsafety.
+    #[inline(always)]
+    pub(crate) fn record(&self, size: usize) {
+        //  Safety:
+        //  -   `bucket_of` always returns an index less than `BUCKETS`.
+        let counter = unsafe { self.buckets.get_unchecked(SizeHistogram::bucket_of(size)) };
+
+        let value = counter.load(Ordering::Relaxed);
+        counter.store(value.wrapping_add(1), Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of the buckets.
+    ///
+    /// In the presence of concurrent writers, the snapshot is not necessarily consistent across buckets.
+    pub(crate) fn snapshot(&self) -> SizeHistogram {
+        let mut buckets = [0; SizeHistogram::BUCKETS];
+
+        for (count, counter) in buckets.iter_mut().zip(self.buckets.iter()) {
+            *count = counter.load(Ordering::Relaxed);
+        }
+
+        SizeHistogram::from_buckets(buckets)
+    }
+}
+
+impl Default for AtomicSizeHistogram {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn atomic_size_histogram_record() {
+    let histogram = AtomicSizeHistogram::new();
+
+    histogram.record(1);
+    histogram.record(24);
+    histogram.record(32);
+    histogram.record(33);
+
+    let snapshot = histogram.snapshot();
+
+    assert_eq!(4, snapshot.total());
+    assert_eq!(1, snapshot.count(0));
+    assert_eq!(2, snapshot.count(5));
+    assert_eq!(1, snapshot.count(6));
+}
+
+} // mod tests
@@ -5,24 +5,45 @@
 //!
 //! The Huge Allocations Manager bridges the gap by recording the original layout on allocation and providing back on
 //! deallocation.
+//!
+//! Deallocated Huge allocations are retained for reuse, rather than returned to the `Platform`, and coalesced with the
+//! adjacent retained blocks, if any. A retained block larger than a new allocation is split, and its remainder is kept
+//! available for further allocations.
+//!
+//! Huge allocations may be resized without copying their content, if the `Platform` supports it, in which case their
+//! record is updated in place.
+//!
+//! The retention may be disabled, in which case deallocated Huge allocations fresh from the `Platform` are returned to
+//! it immediately, while those carved from blocks retained beforehand are still retained.
+//!
+//! The retained blocks decay: a deallocated block is lazily purged on retention, and each decay pass ages the retained
+//! blocks, forcibly purging those aged by the previous pass, so that a block retained for a whole pass no longer
+//! consumes physical memory, yet remains available for reuse. The blocks purged by a pass are handed over to the
+//! `Platform` in batches, see `Platform::purge_forced_batch`.
 
 use core::{
     alloc::Layout,
     cmp,
     marker::PhantomData,
     ptr::NonNull,
-    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use crate::{Configuration, Platform, PowerOf2};
+use crate::internals::{
+    purge_queue::PurgeQueue,
+    sync::{AtomicU8, AtomicUsize, Ordering},
+};
 use crate::utils;
 
 /// Manager of Huge Allocations (ie, 1 or more HugePages)
 pub(crate) struct HugeAllocator<C, P> {
-    //  Array of allocation ptr+size.
+    //  Array of allocation ptr+size, either in use or retained for reuse.
     //
-    //  As an optimization, allocations for which `size == C::HUGE_PAGE_SIZE` are not recorded.
+    //  As an optimization, allocations for which `size == C::HUGE_PAGE_SIZE` are not recorded, unless carved from a
+    //  retained block.
     allocations: [AtomicHugeAllocation<C>; 128],
+    //  Whether deallocated allocations are retained for reuse, 1 if so.
+    retaining: AtomicU8,
     platform: P,
     _configuration: PhantomData<*const C>,
 }
@@ -54,11 +75,19 @@ impl<C, P> HugeAllocator<C, P> {
 
         let _configuration = PhantomData;
 
-        Self { allocations, platform, _configuration, }
+        let retaining = AtomicU8::new(1);
+
+        Self { allocations, retaining, platform, _configuration, }
     }
 
     /// Returns a reference to the platform.
     pub(crate) fn platform(&self) -> &P { &self.platform }
+
+    /// Returns whether deallocated allocations are retained for reuse.
+    pub(crate) fn is_retaining(&self) -> bool { self.retaining.load(Ordering::Relaxed) != 0 }
+
+    /// Sets whether deallocated allocations are retained for reuse.
+    pub(crate) fn set_retaining(&self, retaining: bool) { self.retaining.store(retaining as u8, Ordering::Relaxed); }
 }
 
 impl<C, P> HugeAllocator<C, P>
@@ -69,18 +98,14 @@ impl<C, P> HugeAllocator<C, P>
     /// Allocates a Huge allocation.
     #[inline(never)]
     pub(crate) fn allocate_huge(&self, layout: Layout) -> Option<NonNull<u8>> {
-        debug_assert!(layout.align().count_ones() == 1, "Invalid layout!");
-
-        let align = cmp::max(C::HUGE_PAGE_SIZE.value(), layout.align());
+        let (size, align) = Self::size_align_of(layout)?;
 
         //  Safety:
+        //  -   `size` is <= `HugeAllocation::<C>::MAX_SIZE`.
+        //  -   `size` is a multiple of `C::HUGE_PAGE_SIZE`.
         //  -   `align` is a power of 2.
-        let size = unsafe { PowerOf2::new_unchecked(align) }.round_up(layout.size());
-
-        debug_assert!(size >= C::HUGE_PAGE_SIZE.value());
-
-        if size > HugeAllocation::<C>::MAX_SIZE {
-            return None;
+        if let Some(result) = unsafe { self.reuse_allocation(size, align) } {
+            return Some(result);
         }
 
         //  Safety:
@@ -94,10 +119,14 @@ impl<C, P> HugeAllocator<C, P>
         //  If null, no need to memorize it, there's nothing to deallocate.
         let result = result?;
 
+        //  Safety:
+        //  -   `result` points to a block of `size` bytes, freshly allocated by the platform.
+        unsafe { self.platform.name_huge(result, size) };
+
         //  Safety:
         //  -   `size` is <= `HugeAllocation::<C>::MAX_SIZE`.
         //  -   `size` is >= `C::HUGE_PAGE_SIZE`.
-        if unsafe { self.push_allocation(result, size) } {
+        if unsafe { self.push_allocation(result, size, !self.is_retaining()) } {
             return Some(result);
         }
 
@@ -107,34 +136,268 @@ impl<C, P> HugeAllocator<C, P>
         None
     }
 
+    /// Allocates a Huge allocation, from the retained allocations only, never from the `Platform`.
+    #[inline(never)]
+    pub(crate) fn allocate_huge_cached(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let (size, align) = Self::size_align_of(layout)?;
+
+        //  Safety:
+        //  -   `size` is <= `HugeAllocation::<C>::MAX_SIZE`.
+        //  -   `size` is a multiple of `C::HUGE_PAGE_SIZE`.
+        //  -   `align` is a power of 2.
+        unsafe { self.reuse_allocation(size, align) }
+    }
+
+    /// Returns the size of a Huge allocation, a multiple of `C::HUGE_PAGE_SIZE`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `ptr` was allocated by `self`.
+    /// -   Assumes that `ptr` points to the start of the allocation.
+    pub(crate) unsafe fn size_of_huge(&self, ptr: NonNull<u8>) -> usize {
+        debug_assert!(utils::is_sufficiently_aligned_for(ptr, C::HUGE_PAGE_SIZE));
+
+        //  Unrecorded allocations are single HugePages fresh from the `Platform`, as those carved are recorded.
+        self.find_allocation(ptr)
+            .map(|home| home.load().inflate().1)
+            .unwrap_or(C::HUGE_PAGE_SIZE.value())
+    }
+
     /// Deallocates a Huge allocation.
     ///
+    /// The memory is retained for reuse by further Huge allocations, and only returned to the `Platform` if there is
+    /// no room left to record it, or if it was allocated fresh from the `Platform` while retention was disabled.
+    ///
+    /// Returns the number of bytes deallocated.
+    ///
     /// #   Safety
     ///
     /// -   Assumes that `ptr` was allocated by `self`.
     /// -   Assumes that `ptr` points to the start of the allocation.
     #[inline(never)]
-    pub(crate) unsafe fn deallocate_huge(&self, ptr: NonNull<u8>) {
+    pub(crate) unsafe fn deallocate_huge(&self, ptr: NonNull<u8>) -> usize {
         debug_assert!(utils::is_sufficiently_aligned_for(ptr, C::HUGE_PAGE_SIZE));
 
-        let size = self.pop_allocation(ptr);
+        let home = self.find_allocation(ptr);
+
+        let allocation = home.map(|home| home.load());
+
+        let size = allocation.map(|allocation| allocation.inflate().1).unwrap_or(C::HUGE_PAGE_SIZE.value());
 
         debug_assert!(size % C::HUGE_PAGE_SIZE == 0);
         debug_assert!(size >= C::HUGE_PAGE_SIZE.value());
         debug_assert!(size <= HugeAllocation::<C>::MAX_SIZE);
 
-        let align = C::HUGE_PAGE_SIZE.value();
+        //  Unrecorded allocations are single HugePages fresh from the `Platform`, as those carved are recorded.
+        let direct = allocation.map(|allocation| allocation.is_direct()).unwrap_or(!self.is_retaining());
+
+        if direct {
+            if let Some(home) = home {
+                home.store(HugeAllocation::default());
+            }
+
+            //  Safety:
+            //  -   `C::HUGE_PAGE_SIZE` is a power of 2.
+            //  -   `size` is a multiple of `C::HUGE_PAGE_SIZE`.
+            let layout = Layout::from_size_align_unchecked(size, C::HUGE_PAGE_SIZE.value());
+
+            self.platform.deallocate(ptr, layout);
+
+            return size;
+        }
+
+        self.retain_allocation(ptr, size, home);
+
+        size
+    }
+
+    /// Ages the blocks retained for reuse, forcibly purging those already aged by a previous pass.
+    ///
+    /// Aged blocks are purged anew by each pass for as long as they are retained, at little cost as their memory is no
+    /// longer committed.
+    ///
+    /// Returns the number of bytes purged.
+    #[inline(never)]
+    pub(crate) fn decay(&self) -> usize {
+        let mut purged = 0;
+        let mut queue = PurgeQueue::new(&self.platform, Self::release_purged);
+
+        for huge in &self.allocations[..] {
+            let allocation = huge.load();
+
+            if !allocation.is_free() {
+                continue;
+            }
+
+            let (ptr, size) = match allocation.inflate() {
+                (Some(ptr), size) => (ptr, size),
+                (None, _) => continue,
+            };
+
+            if !allocation.is_aged() {
+                //  Should the block be claimed concurrently, it is no longer retained, and need not age.
+                let _aged = huge.replace(allocation, allocation.into_aged());
+                continue;
+            }
+
+            if self.claim_retained(huge, allocation, ptr, size) {
+                //  Safety:
+                //  -   The block is no longer in use, and exclusively owned since claimed, until released.
+                purged += unsafe { queue.push(ptr, size, (huge, allocation)) };
+            }
+        }
+
+        purged + queue.flush()
+    }
+
+    /// Forcibly purges the blocks retained for reuse, whether aged or not, ageing them.
+    ///
+    /// Returns the number of bytes purged.
+    #[inline(never)]
+    pub(crate) fn purge(&self) -> usize {
+        let mut purged = 0;
+        let mut queue = PurgeQueue::new(&self.platform, Self::release_purged);
+
+        for huge in &self.allocations[..] {
+            let allocation = huge.load();
+
+            if !allocation.is_free() {
+                continue;
+            }
+
+            if let (Some(ptr), size) = allocation.inflate() {
+                if self.claim_retained(huge, allocation, ptr, size) {
+                    //  Safety:
+                    //  -   The block is no longer in use, and exclusively owned since claimed, until released.
+                    purged += unsafe { queue.push(ptr, size, (huge, allocation.into_aged())) };
+                }
+            }
+        }
+
+        purged + queue.flush()
+    }
+
+    /// Invokes `f` with the address and size of each block retained for reuse.
+    pub(crate) fn for_each_retained<F>(&self, mut f: F)
+        where
+            F: FnMut(NonNull<u8>, usize),
+    {
+        for huge in &self.allocations {
+            let allocation = huge.load();
+
+            if !allocation.is_free() {
+                continue;
+            }
+
+            if let (Some(ptr), size) = allocation.inflate() {
+                f(ptr, size);
+            }
+        }
+    }
+
+    /// Reallocates a Huge allocation, to fit `layout`, without copying its content.
+    ///
+    /// Returns the pointer to the reallocated block and its previous size, or None if the `Platform` cannot resize it,
+    /// or would need to realign it, in which case the allocation is left untouched.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `ptr` was allocated by `self`.
+    /// -   Assumes that `ptr` points to the start of the allocation.
+    #[inline(never)]
+    pub(crate) unsafe fn reallocate_huge(&self, ptr: NonNull<u8>, layout: Layout) -> Option<(NonNull<u8>, usize)> {
+        debug_assert!(utils::is_sufficiently_aligned_for(ptr, C::HUGE_PAGE_SIZE));
+        debug_assert!(layout.align().count_ones() == 1, "Invalid layout!");
+
+        let align = PowerOf2::new_unchecked(cmp::max(C::HUGE_PAGE_SIZE.value(), layout.align()));
+        let size = align.round_up(layout.size());
+
+        if size > HugeAllocation::<C>::MAX_SIZE || !utils::is_sufficiently_aligned_for(ptr, align) {
+            return None;
+        }
+
+        let home = self.find_allocation(ptr);
+        let allocation = home.map(|home| home.load());
+
+        let current_size = allocation.map(|allocation| allocation.inflate().1).unwrap_or(C::HUGE_PAGE_SIZE.value());
+        let direct = allocation.map(|allocation| allocation.is_direct()).unwrap_or(!self.is_retaining());
+
+        if size == current_size {
+            return Some((ptr, current_size));
+        }
+
+        //  An unrecorded allocation is a single HugePage, hence grows, and requires recording.
+        let (home, claimed) = match home {
+            Some(home) => (home, false),
+            None => (self.claim(HugeAllocation::new(ptr, current_size))?, true),
+        };
 
         //  Safety:
-        //  -   `align` is not zero.
         //  -   `align` is a power of 2.
-        //  -   `size` is a multiple of `align`.
-        let layout = Layout::from_size_align_unchecked(size, align);
+        //  -   `current_size` is a multiple of `align`, as `ptr` is aligned on `align`.
+        let current = Layout::from_size_align_unchecked(current_size, align.value());
 
-        self.platform.deallocate(ptr, layout);
+        let result = match self.platform.reallocate(ptr, current, size) {
+            Some(result) => result,
+            None => {
+                if claimed {
+                    home.store(HugeAllocation::default());
+                }
+
+                return None;
+            },
+        };
+
+        //  Safety:
+        //  -   `size` is <= `HugeAllocation::<C>::MAX_SIZE`.
+        //  -   `size` is a multiple of `C::HUGE_PAGE_SIZE`.
+        let reallocated = HugeAllocation::new(result, size);
+
+        home.store(if direct { reallocated.into_direct() } else { reallocated });
+
+        Some((result, current_size))
+    }
+
+    //  Claims the retained block of `size` bytes at `ptr`, recorded as `current` in `huge`, so that it may be purged,
+    //  unless claimed concurrently.
+    //
+    //  Returns whether the block was claimed, in which case it is to be released once purged, see `release_purged`.
+    fn claim_retained(&self, huge: &AtomicHugeAllocation<C>, current: HugeAllocation<C>, ptr: NonNull<u8>, size: usize)
+        -> bool
+    {
+        //  Safety:
+        //  -   `size` is at most `MAX_SIZE`, and a multiple of `C::HUGE_PAGE_SIZE`, as recorded.
+        let claimed = unsafe { HugeAllocation::new(ptr, size) };
+
+        //  While claimed, the block is recorded as in use, so that concurrent threads leave it alone.
+        huge.replace(current, claimed)
+    }
+
+    //  Releases a block claimed by `claim_retained`, once purged, recording it in `huge` as `purged`.
+    fn release_purged((huge, purged): (&AtomicHugeAllocation<C>, HugeAllocation<C>)) { huge.store(purged); }
+
+    //  Internal; Returns the size and alignment of the Huge allocation serving `layout`, or None if it is too large.
+    //
+    //  The size is a multiple of `C::HUGE_PAGE_SIZE`, and the alignment a power of 2.
+    fn size_align_of(layout: Layout) -> Option<(usize, usize)> {
+        debug_assert!(layout.align().count_ones() == 1, "Invalid layout!");
+
+        let align = cmp::max(C::HUGE_PAGE_SIZE.value(), layout.align());
+
+        //  Safety:
+        //  -   `align` is a power of 2.
+        let size = unsafe { PowerOf2::new_unchecked(align) }.round_up(layout.size());
+
+        debug_assert!(size >= C::HUGE_PAGE_SIZE.value());
+
+        if size > HugeAllocation::<C>::MAX_SIZE {
+            return None;
+        }
+
+        Some((size, align))
     }
 
-    //  Internal; Pushes a new HugeAllocation into the array.
+    //  Internal; Pushes a new HugeAllocation into the array, marked as direct if `direct`.
     //
     //  Returns true on success, false on failure.
     //
@@ -143,7 +406,7 @@ impl<C, P> HugeAllocator<C, P>
     //  -   Assumes that `size` is less than or equal to `MAX_SIZE`.
     //  -   Assumes that `size` is strictly greater than `C::HUGE_PAGE_SIZE`.
     #[must_use]
-    unsafe fn push_allocation(&self, ptr: NonNull<u8>, size: usize) -> bool {
+    unsafe fn push_allocation(&self, ptr: NonNull<u8>, size: usize, direct: bool) -> bool {
         //  Optimize storage of single huge pages.
         if size == C::HUGE_PAGE_SIZE.value() {
             return true;
@@ -154,36 +417,184 @@ impl<C, P> HugeAllocator<C, P>
         //  -   `size` is greater than or equal to `C::HUGE_PAGE_SIZE`.
         let allocation = HugeAllocation::new(ptr, size);
 
-        let null = HugeAllocation::default();
+        let allocation = if direct { allocation.into_direct() } else { allocation };
 
-        for huge in &self.allocations[..] {
-            if huge.replace(null, allocation) {
-                return true;
-            }
-        }
+        self.push(allocation)
+    }
+
+    //  Internal; Pushes `allocation` into the first empty entry of the array.
+    //
+    //  Returns true on success, false on failure.
+    #[must_use]
+    fn push(&self, allocation: HugeAllocation<C>) -> bool { self.claim(allocation).is_some() }
 
-        false
+    //  Internal; Stores `allocation` into the first empty entry of the array.
+    //
+    //  Returns the entry on success, None on failure.
+    #[must_use]
+    fn claim(&self, allocation: HugeAllocation<C>) -> Option<&AtomicHugeAllocation<C>> {
+        let null = HugeAllocation::default();
+
+        self.allocations.iter().find(|huge| huge.replace(null, allocation))
     }
 
-    //  Internal; Pops a previous HugeAllocation from the array.
+    //  Internal; Finds the entry of an allocation in use, if recorded.
     //
     //  #   Safety
     //
     //  -   Assumes that `ptr` was allocated by `self`.
-    unsafe fn pop_allocation(&self, ptr: NonNull<u8>) -> usize {
+    unsafe fn find_allocation(&self, ptr: NonNull<u8>) -> Option<&AtomicHugeAllocation<C>> {
+        self.allocations.iter().find(|huge| {
+            let allocation = huge.load();
+
+            !allocation.is_free() && allocation.inflate().0 == Some(ptr)
+        })
+    }
+
+    //  Internal; Claims a retained block of at least `size` bytes, aligned on `align`, if any.
+    //
+    //  If the block is larger than `size`, the remainder is split off and remains available, unless there is no room
+    //  to record it, in which case the block is left untouched.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `size` is less than or equal to `MAX_SIZE`.
+    //  -   Assumes that `size` is a multiple of `C::HUGE_PAGE_SIZE`.
+    //  -   Assumes that `align` is a power of 2.
+    unsafe fn reuse_allocation(&self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        let align = PowerOf2::new_unchecked(align);
+
         for huge in &self.allocations[..] {
             let allocation = huge.load();
-            let (p, size) = allocation.inflate();
 
-            if p != Some(ptr) {
+            if !allocation.is_free() {
                 continue;
             }
 
-            let _ = huge.replace(allocation, HugeAllocation::default());
-            return size;
+            let (ptr, available) = allocation.inflate();
+
+            let ptr = match ptr {
+                Some(ptr) if available >= size && utils::is_sufficiently_aligned_for(ptr, align) => ptr,
+                _ => continue,
+            };
+
+            let claimed = HugeAllocation::new(ptr, size);
+
+            if !huge.replace(allocation, claimed) {
+                continue;
+            }
+
+            if available == size {
+                return Some(ptr);
+            }
+
+            //  Safety:
+            //  -   The remainder is within the block, hence at most `MAX_SIZE` and aligned on `C::HUGE_PAGE_SIZE`.
+            let remainder = HugeAllocation::new_free(NonNull::new_unchecked(ptr.as_ptr().add(size)), available - size);
+
+            if self.push(remainder) {
+                return Some(ptr);
+            }
+
+            //  No room to record the remainder, hence restore the block; the entry is exclusively owned since claimed.
+            let _restored = huge.replace(claimed, allocation);
+            debug_assert!(_restored);
         }
 
-        C::HUGE_PAGE_SIZE.value()
+        None
+    }
+
+    //  Internal; Retains the block `[ptr, ptr + size)` for reuse, coalescing it with the adjacent retained blocks.
+    //
+    //  `home` is the entry recording the block, if any; otherwise the block is a whole single HugePage, and is returned
+    //  to the `Platform` if it can neither be coalesced nor recorded.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that the block is no longer in use.
+    //  -   Assumes that `home`, if any, records the block.
+    unsafe fn retain_allocation<'a>(&'a self, ptr: NonNull<u8>, size: usize, home: Option<&'a AtomicHugeAllocation<C>>)
+    {
+        //  Safety:
+        //  -   The block is assumed to be no longer in use, and is exclusively owned until recorded.
+        self.platform.purge_lazy(ptr, size);
+
+        let mut home = home;
+        let (mut start, mut end) = (ptr.as_ptr() as usize, ptr.as_ptr() as usize + size);
+
+        //  Each claimed block is emptied, except the first if there is no home, which becomes the home. While claimed,
+        //  the home is recorded as in use, so that concurrent threads leave it alone.
+        let mut coalesced = true;
+
+        while coalesced {
+            coalesced = false;
+
+            for huge in &self.allocations[..] {
+                let allocation = huge.load();
+
+                if !allocation.is_free() {
+                    continue;
+                }
+
+                let (other, other_size) = match allocation.inflate() {
+                    (Some(other), other_size) => (other.as_ptr() as usize, other_size),
+                    (None, _) => continue,
+                };
+
+                if other + other_size != start && other != end {
+                    continue;
+                }
+
+                let (merged_start, merged_end) = (cmp::min(start, other), cmp::max(end, other + other_size));
+
+                if merged_end - merged_start > HugeAllocation::<C>::MAX_SIZE {
+                    continue;
+                }
+
+                let claimed = if home.is_some() {
+                    HugeAllocation::default()
+                } else {
+                    HugeAllocation::new(NonNull::new_unchecked(other as *mut u8), other_size)
+                };
+
+                if !huge.replace(allocation, claimed) {
+                    continue;
+                }
+
+                home = home.or(Some(huge));
+
+                start = merged_start;
+                end = merged_end;
+                coalesced = true;
+            }
+        }
+
+        //  Safety:
+        //  -   `start` is non-null, and aligned on `C::HUGE_PAGE_SIZE`.
+        //  -   `end - start` is at most `MAX_SIZE`.
+        let retained = HugeAllocation::new_free(NonNull::new_unchecked(start as *mut u8), end - start);
+
+        if let Some(home) = home {
+            home.store(retained);
+            return;
+        }
+
+        if self.push(retained) {
+            return;
+        }
+
+        //  No room to record it, hence return the whole single HugePage to the `Platform`.
+        debug_assert!(end - start == C::HUGE_PAGE_SIZE.value());
+
+        let align = C::HUGE_PAGE_SIZE.value();
+
+        //  Safety:
+        //  -   `align` is not zero.
+        //  -   `align` is a power of 2.
+        //  -   `size` is a multiple of `align`.
+        let layout = Layout::from_size_align_unchecked(size, align);
+
+        self.platform.deallocate(ptr, layout);
     }
 }
 
@@ -215,14 +626,19 @@ impl<C> AtomicHugeAllocation<C>
 {
     /// Returns the `HugeAllocation`.
     fn load(&self) -> HugeAllocation<C> {
-        let huge = self.0.load(Ordering::Relaxed);
+        let huge = self.0.load(Ordering::Acquire);
         HugeAllocation(huge, PhantomData)
     }
 
+    /// Sets to `HugeAllocation`.
+    fn store(&self, new: HugeAllocation<C>) { self.0.store(new.0, Ordering::Release); }
+
     /// Sets to `HugeAllocation` if equal to `current`; returns true on success, false on failure.
+    ///
+    /// The memory of retained blocks changes hands through the entries, hence the acquire-release semantics.
     #[must_use]
     fn replace(&self, current: HugeAllocation<C>, new: HugeAllocation<C>) -> bool {
-        self.0.compare_exchange(current.0, new.0, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+        self.0.compare_exchange(current.0, new.0, Ordering::AcqRel, Ordering::Acquire).is_ok()
     }
 }
 
@@ -230,7 +646,12 @@ impl<C> Default for AtomicHugeAllocation<C> {
     fn default() -> Self { Self(AtomicUsize::new(0), PhantomData) }
 }
 
-//  A compressed representation of a pointer to a HugePage and the number of HugePages.
+//  A compressed representation of a pointer to a HugePage, the number of HugePages, whether the block is free, and
+//  whether the block in use is direct, that is to be returned to the `Platform` on deallocation, or the free block
+//  aged.
+//
+//  The highest bit below `C::HUGE_PAGE_SIZE` is the free flag, the next highest the direct or aged flag, and the lower
+//  bits the number of HugePages.
 struct HugeAllocation<C>(usize, PhantomData<*const C>);
 
 impl<C> HugeAllocation<C>
@@ -238,9 +659,64 @@ impl<C> HugeAllocation<C>
         C: Configuration,
 {
     /// Maximum size which can be encoded in HugeAllocation.
-    const MAX_SIZE: usize = (C::HUGE_PAGE_SIZE.value() - 1) * C::HUGE_PAGE_SIZE.value();
+    const MAX_SIZE: usize = (Self::DIRECT - 1) * C::HUGE_PAGE_SIZE.value();
 
-    /// Creates a new instance.
+    /// Flag of free blocks.
+    const FREE: usize = C::HUGE_PAGE_SIZE.value() / 2;
+
+    /// Flag of direct blocks.
+    const DIRECT: usize = Self::FREE / 2;
+
+    /// Flag of free blocks aged by a decay pass, sharing the bit of the direct flag of blocks in use.
+    const AGED: usize = Self::DIRECT;
+
+    /// Creates a new instance, of a free block.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `size` is less than or equal to `MAX_SIZE`.
+    /// -   Assumes that `size` is greater than or equal to `C::HUGE_PAGE_SIZE`.
+    unsafe fn new_free(ptr: NonNull<u8>, size: usize) -> Self {
+        debug_assert!(size >= C::HUGE_PAGE_SIZE.value());
+
+        let allocation = Self::new(ptr, size);
+
+        Self(allocation.0 + Self::FREE, PhantomData)
+    }
+
+    /// Returns whether the block is free.
+    fn is_free(&self) -> bool { self.0 & Self::FREE != 0 }
+
+    /// Returns whether the block in use is direct.
+    fn is_direct(&self) -> bool {
+        debug_assert!(!self.is_free());
+
+        self.0 & Self::DIRECT != 0
+    }
+
+    /// Returns a copy of the block in use, marked as direct.
+    fn into_direct(self) -> Self {
+        debug_assert!(!self.is_free());
+
+        Self(self.0 | Self::DIRECT, PhantomData)
+    }
+
+    /// Returns whether the free block is aged.
+    fn is_aged(&self) -> bool {
+        debug_assert!(self.is_free());
+
+        self.0 & Self::AGED != 0
+    }
+
+
+    /// Returns a copy of the free block, marked as aged.
+    fn into_aged(self) -> Self {
+        debug_assert!(self.is_free());
+
+        Self(self.0 | Self::AGED, PhantomData)
+    }
+
+    /// Creates a new instance, of a block in use.
     ///
     /// #   Safety
     ///
@@ -261,7 +737,7 @@ impl<C> HugeAllocation<C>
     /// Returns the uncompressed pointer and size.
     fn inflate(&self) -> (Option<NonNull<u8>>, usize) {
         let compressed_ptr = self.0 / C::HUGE_PAGE_SIZE;
-        let compressed_size = self.0 % C::HUGE_PAGE_SIZE;
+        let compressed_size = (self.0 % C::HUGE_PAGE_SIZE) & !(Self::FREE | Self::DIRECT);
 
         let ptr = NonNull::new((compressed_ptr * C::HUGE_PAGE_SIZE) as *mut u8);
         let size = compressed_size * C::HUGE_PAGE_SIZE;
@@ -284,7 +760,7 @@ impl<C> Default for HugeAllocation<C> {
 mod tests {
 
 use core::{
-    cell::UnsafeCell,
+    cell::{Cell, UnsafeCell},
     mem,
 };
 
@@ -306,11 +782,19 @@ impl Configuration for TestConfiguration {
 #[repr(align(1024))]
 struct TestPlatform {
     pool: UnsafeCell<[u8; 1024]>,
+    //  Number of bytes purged, lazily and forcibly.
+    purged: [Cell<usize>; 2],
+    //  Number of bytes named as hosting a Huge allocation.
+    named: Cell<usize>,
 }
 
 impl TestPlatform {
     fn new() -> Self { unsafe { mem::zeroed() } }
 
+    fn purged(&self) -> [usize; 2] { [self.purged[0].get(), self.purged[1].get()] }
+
+    fn named(&self) -> usize { self.named.get() }
+
     fn occupied(&self) -> [bool; 4] {
         let starters = self.starters();
         unsafe { [*starters[0] == 1, *starters[1] == 1, *starters[2] == 1, *starters[3] == 1] }
@@ -333,14 +817,14 @@ impl Platform for TestPlatform {
         let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
 
         assert!(layout.align() >= huge);
-        assert!(layout.size() % huge == 0);
+        assert!(layout.size().is_multiple_of(huge));
         assert!(layout.size() > 0);
 
         let starters = self.starters();
         let number_pages = layout.size() / huge;
 
         for slice in starters.windows(number_pages) {
-            if slice[0] as usize % layout.align() != 0 {
+            if !(slice[0] as usize).is_multiple_of(layout.align()) {
                 continue;
             }
 
@@ -357,7 +841,7 @@ impl Platform for TestPlatform {
         let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
 
         assert_eq!(layout.align(), huge);
-        assert!(layout.size() % huge == 0);
+        assert!(layout.size().is_multiple_of(huge));
         assert!(layout.size() > 0);
 
         let starters = self.starters();
@@ -376,6 +860,38 @@ impl Platform for TestPlatform {
 
         unreachable!();
     }
+
+    unsafe fn reallocate(&self, ptr: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+        let starters = self.starters();
+        let first = starters.iter().position(|starter| *starter == ptr.as_ptr())?;
+
+        let (current, new) = (first + layout.size() / huge, first + new_size / huge);
+
+        if new > starters.len() {
+            return None;
+        }
+
+        //  Resizes in place only.
+        if new > current {
+            if starters[current..new].iter().any(|x| **x == 1) {
+                return None;
+            }
+
+            starters[current..new].iter().for_each(|x| **x = 1);
+        } else {
+            starters[new..current].iter().for_each(|x| **x = 0);
+        }
+
+        Some(ptr)
+    }
+
+    unsafe fn purge_lazy(&self, _ptr: NonNull<u8>, size: usize) { self.purged[0].set(self.purged[0].get() + size); }
+
+    unsafe fn name_huge(&self, _ptr: NonNull<u8>, size: usize) { self.named.set(self.named.get() + size); }
+
+    unsafe fn purge_forced(&self, _ptr: NonNull<u8>, size: usize) { self.purged[1].set(self.purged[1].get() + size); }
 }
 
 impl Default for TestPlatform {
@@ -398,13 +914,69 @@ fn huge_allocation_new_inflate() {
         (ptr / page_size, size / page_size)
     }
 
-    let huge = TestConfiguration::HUGE_PAGE_SIZE.value() - 1;
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value() / 4 - 1;
 
     assert_eq!((  7,    1), new_inflate(  7,    1));
     assert_eq!(( 42,   23), new_inflate( 42,   23));
     assert_eq!((245, huge), new_inflate(245, huge));
 }
 
+#[test]
+fn huge_allocation_new_free() {
+    type C = TestConfiguration;
+
+    let page_size = C::HUGE_PAGE_SIZE.value();
+    let ptr = NonNull::new((42 * page_size) as *mut u8).unwrap();
+
+    let used = unsafe { Allocation::new(ptr, 23 * page_size) };
+    let free = unsafe { Allocation::new_free(ptr, 23 * page_size) };
+
+    assert!(!used.is_free());
+    assert!(free.is_free());
+
+    assert_eq!((Some(ptr), 23 * page_size), used.inflate());
+    assert_eq!((Some(ptr), 23 * page_size), free.inflate());
+
+    let max = unsafe { Allocation::new_free(ptr, Allocation::MAX_SIZE) };
+
+    assert!(max.is_free());
+    assert_eq!((Some(ptr), Allocation::MAX_SIZE), max.inflate());
+}
+
+#[test]
+fn huge_allocation_into_direct() {
+    type C = TestConfiguration;
+
+    let page_size = C::HUGE_PAGE_SIZE.value();
+    let ptr = NonNull::new((42 * page_size) as *mut u8).unwrap();
+
+    let used = unsafe { Allocation::new(ptr, Allocation::MAX_SIZE) };
+    let direct = used.into_direct();
+
+    assert!(!used.is_direct());
+    assert!(direct.is_direct());
+    assert!(!direct.is_free());
+
+    assert_eq!((Some(ptr), Allocation::MAX_SIZE), direct.inflate());
+}
+
+#[test]
+fn huge_allocation_into_aged() {
+    type C = TestConfiguration;
+
+    let page_size = C::HUGE_PAGE_SIZE.value();
+    let ptr = NonNull::new((42 * page_size) as *mut u8).unwrap();
+
+    let free = unsafe { Allocation::new_free(ptr, Allocation::MAX_SIZE) };
+    let aged = free.into_aged();
+
+    assert!(!free.is_aged());
+    assert!(aged.is_aged());
+    assert!(aged.is_free());
+
+    assert_eq!((Some(ptr), Allocation::MAX_SIZE), aged.inflate());
+}
+
 #[test]
 fn atomic_huge_allocation_load_replace() {
     fn huge_allocation(ptr: usize, size: usize) -> Allocation {
@@ -501,11 +1073,304 @@ fn huge_allocator_deallocate() {
 
     assert_eq!([true, true, true, true], platform.occupied());
 
-    unsafe { allocator.deallocate_huge(two.unwrap()) };
-    assert_eq!([true, true, true, false], platform.occupied());
+    //  Deallocated memory is retained for reuse.
+    let deallocated = unsafe { allocator.deallocate_huge(two.unwrap()) };
+    assert_eq!(huge, deallocated);
+    assert_eq!([true, true, true, true], platform.occupied());
+
+    let deallocated = unsafe { allocator.deallocate_huge(one.unwrap()) };
+    assert_eq!(huge * 3, deallocated);
+    assert_eq!([true, true, true, true], platform.occupied());
+}
 
+#[test]
+fn huge_allocator_name_huge() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+
+    let one = allocator.allocate_huge(layout(huge * 2));
+    assert_eq!(huge * 2, platform.named());
+
+    //  Memory reused from the retained allocations was already named.
     unsafe { allocator.deallocate_huge(one.unwrap()) };
-    assert_eq!([false, false, false, false], platform.occupied());
+
+    let two = allocator.allocate_huge(layout(huge * 2));
+    assert_eq!(one, two);
+    assert_eq!(huge * 2, platform.named());
+}
+
+#[test]
+fn huge_allocator_for_each_retained() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let starters = allocator.platform().starters();
+
+    //  Returns the number of retained blocks, and the last one.
+    let retained = |allocator: &Allocator| {
+        let mut result = (0, None);
+        allocator.for_each_retained(|ptr, size| result = (result.0 + 1, Some((ptr.as_ptr(), size))));
+        result
+    };
+
+    let one = allocator.allocate_huge(layout(huge * 2)).unwrap();
+    let two = allocator.allocate_huge(layout(huge)).unwrap();
+
+    assert_eq!((0, None), retained(&allocator));
+
+    unsafe { allocator.deallocate_huge(one) };
+    assert_eq!((1, Some((starters[0], huge * 2))), retained(&allocator));
+
+    //  Coalesced with the adjacent retained block.
+    unsafe { allocator.deallocate_huge(two) };
+    assert_eq!((1, Some((starters[0], huge * 3))), retained(&allocator));
+}
+
+#[test]
+fn huge_allocator_decay() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+
+    let one = allocator.allocate_huge(layout(huge * 2)).unwrap();
+    let two = allocator.allocate_huge(layout(huge)).unwrap();
+
+    //  Nothing to decay.
+    assert_eq!(0, allocator.decay());
+
+    //  Lazily purged on retention.
+    unsafe { allocator.deallocate_huge(one) };
+    assert_eq!([huge * 2, 0], platform.purged());
+
+    //  Aged by the first pass, forcibly purged by the second, and anew by each following one.
+    assert_eq!(0, allocator.decay());
+    assert_eq!(huge * 2, allocator.decay());
+    assert_eq!(huge * 2, allocator.decay());
+    assert_eq!([huge * 2, huge * 4], platform.purged());
+
+    //  Purged blocks remain available for reuse.
+    let three = allocator.allocate_huge(layout(huge * 2)).unwrap();
+    assert_eq!(one, three);
+
+    //  Reused blocks no longer decay.
+    assert_eq!(0, allocator.decay());
+    assert_eq!(0, allocator.decay());
+
+    unsafe { allocator.deallocate_huge(three) };
+    unsafe { allocator.deallocate_huge(two) };
+    assert_eq!([huge * 5, huge * 4], platform.purged());
+
+    //  Coalesced blocks decay anew.
+    assert_eq!(0, allocator.decay());
+    assert_eq!(huge * 3, allocator.decay());
+    assert_eq!([huge * 5, huge * 7], platform.purged());
+}
+
+#[test]
+fn huge_allocator_purge() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+
+    let one = allocator.allocate_huge(layout(huge * 2)).unwrap();
+    let two = allocator.allocate_huge(layout(huge)).unwrap();
+
+    //  Nothing to purge, the blocks in use being left alone.
+    assert_eq!(0, allocator.purge());
+
+    //  Purged regardless of age.
+    unsafe { allocator.deallocate_huge(one) };
+
+    assert_eq!(huge * 2, allocator.purge());
+    assert_eq!([huge * 2, huge * 2], platform.purged());
+
+    //  Aged by the purge, hence purged anew by the next decay pass.
+    assert_eq!(huge * 2, allocator.decay());
+    assert_eq!([huge * 2, huge * 4], platform.purged());
+
+    //  Purged blocks remain available for reuse.
+    let three = allocator.allocate_huge(layout(huge * 2)).unwrap();
+    assert_eq!(one, three);
+
+    unsafe { allocator.deallocate_huge(three) };
+    unsafe { allocator.deallocate_huge(two) };
+}
+
+#[test]
+fn huge_allocator_deallocate_not_retaining() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+
+    assert!(allocator.is_retaining());
+
+    //  Retained beforehand, and carved afterwards.
+    let one = allocator.allocate_huge(layout(huge)).unwrap();
+    assert_eq!(huge, unsafe { allocator.deallocate_huge(one) });
+
+    allocator.set_retaining(false);
+    assert!(!allocator.is_retaining());
+
+    let one = allocator.allocate_huge(layout(huge)).unwrap();
+    let two = allocator.allocate_huge(layout(huge * 2)).unwrap();
+    let three = allocator.allocate_huge(layout(huge)).unwrap();
+
+    assert_eq!([true, true, true, true], platform.occupied());
+
+    //  Fresh from the platform, hence returned to it.
+    assert_eq!(huge * 2, unsafe { allocator.deallocate_huge(two) });
+    assert_eq!([true, false, false, true], platform.occupied());
+
+    assert_eq!(huge, unsafe { allocator.deallocate_huge(three) });
+    assert_eq!([true, false, false, false], platform.occupied());
+
+    //  Carved from a retained block, hence retained.
+    assert_eq!(huge, unsafe { allocator.deallocate_huge(one) });
+    assert_eq!([true, false, false, false], platform.occupied());
+}
+
+#[test]
+fn huge_allocator_reallocate() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+
+    let one = allocator.allocate_huge(layout(huge)).unwrap();
+
+    //  Same number of pages.
+    assert_eq!(Some((one, huge)), unsafe { allocator.reallocate_huge(one, layout(huge - 1)) });
+
+    //  Grow in place, recording the allocation.
+    assert_eq!(Some((one, huge)), unsafe { allocator.reallocate_huge(one, layout(huge * 2)) });
+    assert_eq!([true, true, false, false], platform.occupied());
+
+    let two = allocator.allocate_huge(layout(huge)).unwrap();
+    assert_eq!([true, true, true, false], platform.occupied());
+
+    //  No room to grow, the allocation is left untouched.
+    assert_eq!(None, unsafe { allocator.reallocate_huge(one, layout(huge * 3)) });
+    assert_eq!(None, unsafe { allocator.reallocate_huge(two, layout(huge * 3)) });
+    assert_eq!([true, true, true, false], platform.occupied());
+
+    //  Shrink in place.
+    assert_eq!(Some((one, huge * 2)), unsafe { allocator.reallocate_huge(one, layout(huge)) });
+    assert_eq!([true, false, true, false], platform.occupied());
+
+    assert_eq!(huge, unsafe { allocator.deallocate_huge(one) });
+    assert_eq!(huge, unsafe { allocator.deallocate_huge(two) });
+}
+
+#[test]
+fn huge_allocator_reuse_split() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+    let starters = platform.starters();
+
+    let one = allocator.allocate_huge(layout(huge * 3)).unwrap();
+    assert_eq!(starters[0], one.as_ptr());
+
+    assert_eq!(huge * 3, unsafe { allocator.deallocate_huge(one) });
+
+    //  Carved from the retained block, the remainder of which is kept available.
+    let two = allocator.allocate_huge(layout(huge)).unwrap();
+    assert_eq!(starters[0], two.as_ptr());
+
+    let three = allocator.allocate_huge(layout(huge * 2)).unwrap();
+    assert_eq!(starters[1], three.as_ptr());
+
+    assert_eq!([true, true, true, false], platform.occupied());
+
+    //  Nothing retained left, hence allocated from the platform.
+    let four = allocator.allocate_huge(layout(huge)).unwrap();
+    assert_eq!(starters[3], four.as_ptr());
+
+    assert!(allocator.allocate_huge(layout(huge)).is_none());
+
+    //  Carved blocks are recorded, and deallocated with their own size.
+    assert_eq!(huge, unsafe { allocator.deallocate_huge(two) });
+    assert_eq!(huge * 2, unsafe { allocator.deallocate_huge(three) });
+    assert_eq!(huge, unsafe { allocator.deallocate_huge(four) });
+}
+
+#[test]
+fn huge_allocator_reuse_aligned() {
+    fn layout(size: usize, align: usize) -> Layout { Layout::from_size_align(size, align).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+    let starters = platform.starters();
+
+    let one = allocator.allocate_huge(layout(huge, 1)).unwrap();
+    let two = allocator.allocate_huge(layout(huge * 3, 1)).unwrap();
+    assert_eq!(starters[1], two.as_ptr());
+
+    unsafe { allocator.deallocate_huge(two) };
+
+    //  The retained block is not sufficiently aligned.
+    assert!(allocator.allocate_huge(layout(huge * 2, huge * 2)).is_none());
+
+    unsafe { allocator.deallocate_huge(one) };
+
+    //  Once coalesced, the retained block is.
+    let three = allocator.allocate_huge(layout(huge * 2, huge * 2)).unwrap();
+    assert_eq!(starters[0], three.as_ptr());
+}
+
+#[test]
+fn huge_allocator_coalesce() {
+    fn layout(size: usize) -> Layout { Layout::from_size_align(size, 1).unwrap() }
+
+    let huge = TestConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = Allocator::default();
+    let platform = allocator.platform();
+    let starters = platform.starters();
+
+    let one = allocator.allocate_huge(layout(huge * 4)).unwrap();
+    unsafe { allocator.deallocate_huge(one) };
+
+    let blocks = [
+        allocator.allocate_huge(layout(huge)).unwrap(),
+        allocator.allocate_huge(layout(huge)).unwrap(),
+        allocator.allocate_huge(layout(huge)).unwrap(),
+        allocator.allocate_huge(layout(huge)).unwrap(),
+    ];
+
+    assert_eq!(starters, [blocks[0].as_ptr(), blocks[1].as_ptr(), blocks[2].as_ptr(), blocks[3].as_ptr()]);
+
+    //  Deallocated out of order, merging both with predecessors and successors.
+    for &index in &[1, 3, 0, 2] {
+        assert_eq!(huge, unsafe { allocator.deallocate_huge(blocks[index]) });
+    }
+
+    let two = allocator.allocate_huge(layout(huge * 4)).unwrap();
+    assert_eq!(starters[0], two.as_ptr());
+
+    assert_eq!(huge * 4, unsafe { allocator.deallocate_huge(two) });
 }
 
 }
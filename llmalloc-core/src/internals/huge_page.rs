@@ -6,6 +6,8 @@
 //! A `SocketLocal` may own multiple 
 
 mod atomic_bit_mask;
+#[cfg(feature = "buddy")]
+mod buddy;
 mod foreign;
 mod number_pages;
 mod page_index;
@@ -18,10 +20,10 @@ use core::{
     mem,
     ptr::{self, NonNull},
     slice,
-    sync::atomic::{self, Ordering},
 };
 
 use crate::{Configuration, PowerOf2};
+use crate::internals::sync::{self, Ordering};
 use crate::utils;
 
 use atomic_bit_mask::AtomicBitMask;
@@ -70,7 +72,7 @@ impl HugePage {
         ptr::write(huge_page, HugePage::new::<C>(owner));
 
         //  Enforce memory ordering, later Acquire need to see those 0s and 1s.
-        atomic::fence(Ordering::Release);
+        sync::fence(Ordering::Release);
 
         at.cast()
     }
@@ -118,13 +120,33 @@ impl HugePage {
         }
     }
 
+    /// Returns the size, in bytes, of the one or multiple pages allocated at the pointer.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the pointer is pointing to an allocated `LargePage` inside _this_ `HugePage`.
+    pub(crate) unsafe fn size_of(&self, ptr: NonNull<u8>) -> usize {
+        debug_assert!(utils::is_sufficiently_aligned_for(ptr, self.common.page_size));
+
+        let index = (ptr.as_ptr() as usize - self.address() as usize) / self.common.page_size;
+        debug_assert!(index > 0 && index <= self.common.number_pages.0);
+
+        //  Safety:
+        //  -   `index` is assumed not to be 0.
+        let number_pages = self.foreign.size_of(PageIndex::new_unchecked(index));
+
+        number_pages.0 * self.common.page_size.value()
+    }
+
     /// Deallocates one or multiple pages from this page.
     ///
+    /// Returns the number of bytes deallocated.
+    ///
     /// #   Safety
     ///
     /// -   Assumes that the pointer is pointing to a `LargePage` inside _this_ `HugePage`.
     /// -   Assumes that the pointed page is no longer in use.
-    pub(crate) unsafe fn deallocate(&self, ptr: NonNull<u8>) {
+    pub(crate) unsafe fn deallocate(&self, ptr: NonNull<u8>) -> usize {
         debug_assert!(utils::is_sufficiently_aligned_for(ptr, self.common.page_size));
 
         let index = (ptr.as_ptr() as usize - self.address() as usize) / self.common.page_size;
@@ -133,7 +155,9 @@ impl HugePage {
         //  Safety:
         //  -   `index` is assumed not to be 0.
         //  -   `index` is assumed to point to pages no longer in use.
-        self.foreign.deallocate(PageIndex::new_unchecked(index));
+        let number_pages = self.foreign.deallocate(PageIndex::new_unchecked(index));
+
+        number_pages.0 * self.common.page_size.value()
     }
 
     /// Returns the owner of the page.
@@ -237,7 +261,7 @@ fn huge_page_smoke_test() {
     }
 
     #[repr(align(131072))]
-    struct AlignedPage(u8);
+    struct AlignedPage(#[allow(dead_code)] u8);
 
     let owner = 1234usize as *mut ();
 
@@ -278,7 +302,8 @@ fn huge_page_smoke_test() {
     let failed = unsafe { huge_page.allocate(layout) };
     assert_eq!(None, failed);
 
-    unsafe { huge_page.deallocate(allocated.unwrap()) };
+    let deallocated = unsafe { huge_page.deallocate(allocated.unwrap()) };
+    assert_eq!(2 * LARGE_PAGE_SIZE, deallocated);
 }
 
 } // mod tests
@@ -1,6 +1,6 @@
 //! An atomic bit mask representing the occupation (or not) of a block.
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use crate::internals::sync::{AtomicU64, Ordering};
 
 use crate::PowerOf2;
 
@@ -14,6 +14,10 @@ impl AtomicBitMask {
     /// Initializes the AtomicBitMask with the given mask.
     pub(crate) fn initialize(&self, mask: u64) { self.0.store(mask, Ordering::Relaxed); }
 
+    /// Returns the current mask.
+    #[cfg(feature = "buddy")]
+    pub(crate) fn load(&self) -> u64 { self.0.load(Ordering::Acquire) }
+
     /// Claims a 0 bit, returns its index or None if all bits are claimed.
     pub(crate) fn claim_single(&self) -> Option<usize> {
         loop {
@@ -503,6 +507,7 @@ impl Global {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_claim_single_success_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_single concurrently.
     //
@@ -538,6 +543,7 @@ fn atomic_bit_mask_concurrent_claim_single_success_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_claim_single_failure_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_single concurrently.
     //
@@ -577,6 +583,7 @@ fn atomic_bit_mask_concurrent_claim_single_failure_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_independent_claim_single_release_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_single and release(_single/multi) concurrently.
     //
@@ -598,7 +605,7 @@ fn atomic_bit_mask_concurrent_independent_claim_single_release_fuzzing() {
 
     //  Step 2: Claim or release.
     builder.add_simple_step(|| |global: &Global, index: &mut usize| {
-        if *index % 2 == 0 {
+        if (*index).is_multiple_of(2) {
             if let Some(claimed) = global.victim.claim_single() {
                 global.claimed[*index].store(1u64 << claimed, Ordering::Relaxed);
             }
@@ -632,6 +639,7 @@ fn atomic_bit_mask_concurrent_independent_claim_single_release_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_overlapping_claim_single_release_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_single and release(_single/multi) concurrently.
     //
@@ -653,7 +661,7 @@ fn atomic_bit_mask_concurrent_overlapping_claim_single_release_fuzzing() {
 
     //  Step 2: Claim or release.
     builder.add_simple_step(|| |global: &Global, index: &mut usize| {
-        if *index % 2 == 0 {
+        if (*index).is_multiple_of(2) {
             if let Some(claimed) = global.victim.claim_single() {
                 global.claimed[*index].store(1u64 << claimed, Ordering::Relaxed);
             }
@@ -707,6 +715,7 @@ impl LocalAt {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_claim_at_success_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_at concurrently.
     //
@@ -746,6 +755,7 @@ fn atomic_bit_mask_concurrent_claim_at_success_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_claim_at_failure_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_at concurrently.
     //
@@ -789,6 +799,7 @@ fn atomic_bit_mask_concurrent_claim_at_failure_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_independent_claim_at_release_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_at and release concurrently.
     //
@@ -811,7 +822,7 @@ fn atomic_bit_mask_concurrent_independent_claim_at_release_fuzzing() {
 
     //  Step 2: Claim or release.
     builder.add_simple_step(|| |global: &Global, local: &mut LocalAt| {
-        if local.index % 2 == 0 {
+        if local.index.is_multiple_of(2) {
             if global.victim.claim_at(local.at(), local.number()) {
                 global.claimed[local.index].store(local.mask(), Ordering::Relaxed);
             }
@@ -826,7 +837,7 @@ fn atomic_bit_mask_concurrent_independent_claim_at_release_fuzzing() {
         let released = global.released_exact();
         let claimed = global.claimed_exact();
 
-        if local.index % 2 == 0 {
+        if local.index.is_multiple_of(2) {
             assert!(claimed.contains(&local.mask()), "{:?} should contain {:b} ({})", claimed, local.mask(), local.mask());
         } else {
             assert!(released.contains(&local.mask()), "{:?} should contain {:b} ({})", released, local.mask(), local.mask());
@@ -845,6 +856,7 @@ fn atomic_bit_mask_concurrent_independent_claim_at_release_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_overlapping_claim_at_release_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_at and release concurrently.
     //
@@ -867,7 +879,7 @@ fn atomic_bit_mask_concurrent_overlapping_claim_at_release_fuzzing() {
 
     //  Step 2: Claim or release.
     builder.add_simple_step(|| |global: &Global, local: &mut LocalAt| {
-        if local.index % 2 == 0 {
+        if local.index.is_multiple_of(2) {
             if global.victim.claim_at(local.at(), local.number()) {
                 global.claimed[local.index].store(local.mask(), Ordering::Relaxed);
             }
@@ -882,11 +894,11 @@ fn atomic_bit_mask_concurrent_overlapping_claim_at_release_fuzzing() {
         let released = global.released_exact();
         let claimed = global.claimed_exact();
 
-        if local.index % 2 != 0 {
+        if !local.index.is_multiple_of(2) {
             assert!(released.contains(&local.mask()), "{:?} should contain {:b} ({})", released, local.mask(), local.mask());
         }
 
-        if claimed.len() == 2 && local.index % 2 == 0  {
+        if claimed.len() == 2 && local.index.is_multiple_of(2)  {
             assert!(claimed.contains(&local.mask()), "{:?} should contain {:b} ({})", claimed, local.mask(), local.mask());
         }
 
@@ -913,7 +925,7 @@ impl LocalMulti {
         Self { index, number, alignment: PowerOf2::new(alignment).unwrap(), }
     }
 
-    fn at(&self) -> usize { self.alignment.value() as usize }
+    fn at(&self) -> usize { self.alignment.value() }
 
     fn number(&self) -> usize { self.number }
 
@@ -921,6 +933,7 @@ impl LocalMulti {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_claim_multiple_full_success_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_multiple concurrently.
     //
@@ -974,6 +987,7 @@ fn atomic_bit_mask_concurrent_claim_multiple_full_success_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_claim_multiple_partial_success_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_multiple concurrently.
     //
@@ -1024,6 +1038,7 @@ fn atomic_bit_mask_concurrent_claim_multiple_partial_success_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_claim_multiple_failure_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_multiple concurrently.
     //
@@ -1076,6 +1091,7 @@ fn atomic_bit_mask_concurrent_claim_multiple_failure_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_independent_claim_multiple_release_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_multiple and release concurrently.
     //
@@ -1098,7 +1114,7 @@ fn atomic_bit_mask_concurrent_independent_claim_multiple_release_fuzzing() {
 
     //  Step 2: Claim or release.
     builder.add_simple_step(|| |global: &Global, local: &mut LocalMulti| {
-        if local.index % 2 == 0 {
+        if local.index.is_multiple_of(2) {
             if let Some((index, n)) = global.victim.claim_multiple(local.number, local.alignment) {
                 global.claimed[local.index].store(AtomicBitMask::low(n) << index, Ordering::Relaxed);
             }
@@ -1113,11 +1129,11 @@ fn atomic_bit_mask_concurrent_independent_claim_multiple_release_fuzzing() {
         let released = global.released_exact();
         let claimed = global.claimed_exact();
 
-        if local.index % 2 != 0 {
+        if !local.index.is_multiple_of(2) {
             assert!(released.contains(&local.mask()), "{:?} should contain {:b} ({})", released, local.mask(), local.mask());
         }
 
-        if local.index % 2 == 0  {
+        if local.index.is_multiple_of(2)  {
             assert_eq!(vec!(AtomicBitMask::low(8) << 40, AtomicBitMask::low(4) << 60), claimed);
         }
 
@@ -1134,6 +1150,7 @@ fn atomic_bit_mask_concurrent_independent_claim_multiple_release_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn atomic_bit_mask_concurrent_overlapping_claim_multiple_release_fuzzing() {
     //  This test aims at validating that multiple threads can call claim_multiple and release concurrently.
     //
@@ -1156,7 +1173,7 @@ fn atomic_bit_mask_concurrent_overlapping_claim_multiple_release_fuzzing() {
 
     //  Step 2: Claim or release.
     builder.add_simple_step(|| |global: &Global, local: &mut LocalMulti| {
-        if local.index % 2 == 0 {
+        if local.index.is_multiple_of(2) {
             if let Some((index, n)) = global.victim.claim_multiple(local.number, local.alignment) {
                 global.claimed[local.index].store(AtomicBitMask::low(n) << index, Ordering::Relaxed);
             }
@@ -1171,11 +1188,11 @@ fn atomic_bit_mask_concurrent_overlapping_claim_multiple_release_fuzzing() {
         let released = global.released_exact();
         let claimed = global.claimed_exact();
 
-        if local.index % 2 != 0 {
+        if !local.index.is_multiple_of(2) {
             assert!(released.contains(&local.mask()), "{:?} should contain {:b} ({})", released, local.mask(), local.mask());
         }
 
-        if claimed.len() == 2 && local.index % 2 == 0  {
+        if claimed.len() == 2 && local.index.is_multiple_of(2)  {
             assert_eq!(vec!(AtomicBitMask::low(8) << 16, AtomicBitMask::low(4) << 32), claimed);
         }
 
@@ -0,0 +1,186 @@
+//! Buddy selection of blocks within an AtomicBitMask.
+//!
+//! With the `buddy` feature, multi-page allocations are rounded up to a power of 2 number of pages, and naturally
+//! aligned, so that the Large Pages of a Huge Page are carved in blocks of `2^order` pages.
+//!
+//! The occupancy of the pages is still tracked in the bitmap of `PageTokens`, which makes splitting and merging
+//! implicit: a block is split by claiming part of it, and merged with its buddy as soon as both are released. The
+//! selection of the block, however, follows the best-fit policy of a buddy system: the block is carved out of the
+//! smallest free block available, keeping the larger free blocks intact for larger allocations.
+
+use super::NumberPages;
+
+/// The maximum order handled within a single AtomicBitMask.
+pub(crate) const WORD_ORDER: usize = 6;
+
+/// The maximum order of a block.
+///
+/// The first Large Page is always reserved for the header of the Huge Page, hence the largest block available is only
+/// half the capacity of `PageTokens`.
+pub(crate) const MAXIMUM_ORDER: usize = 8;
+
+/// Returns the order of the block fulfilling an allocation of `number_pages`, aligned on `align_pages`, within a Huge
+/// Page of `capacity` Large Pages, excluding its header.
+///
+/// Returns None if the allocation is too large for a buddy block, in which case it should be carved exactly.
+pub(crate) fn order_of(number_pages: NumberPages, align_pages: usize, capacity: NumberPages) -> Option<usize> {
+    debug_assert!(number_pages.0 > 0);
+    debug_assert!(align_pages.is_power_of_two());
+
+    let block = number_pages.0.next_power_of_two().max(align_pages);
+
+    //  The header occupies the first Large Page, hence the largest block is half the Huge Page.
+    if block <= capacity.0.div_ceil(2) {
+        Some(block.trailing_zeros() as usize)
+    } else {
+        None
+    }
+}
+
+/// Selects a free block of `order` within `occupied`, where 1 bits are occupied.
+///
+/// Returns the index of the lowest page of the block, and the order of the smallest free block it is carved from, or
+/// None if no block of `order` is free.
+///
+/// Among the blocks carved from equally small free blocks, the highest is selected, to avoid interfering with the
+/// single-page allocations which favor the lowest pages.
+pub(crate) fn select(occupied: u64, order: usize) -> Option<(usize, usize)> {
+    debug_assert!(order <= WORD_ORDER);
+
+    let mut candidates = free_blocks(occupied, order);
+
+    if candidates == 0 {
+        return None;
+    }
+
+    for parent in (order + 1)..=WORD_ORDER {
+        //  The candidates which belong to a free block of order `parent`; since the blocks are disjoint and aligned,
+        //  the multiplication spreads each of them over the whole block without carry.
+        let covered = free_blocks(occupied, parent).wrapping_mul(low(1 << parent)) & candidates;
+
+        if covered != candidates {
+            return Some((highest(candidates & !covered), parent - 1));
+        }
+
+        candidates = covered;
+    }
+
+    Some((highest(candidates), WORD_ORDER))
+}
+
+/// Returns the mask of the free blocks of `order` within `occupied`, with only the lowest bit of each block set.
+pub(crate) fn free_blocks(occupied: u64, order: usize) -> u64 {
+    debug_assert!(order <= WORD_ORDER);
+
+    //  The lowest bit of each block of order 0 to 6, respectively.
+    const ALIGNED: [u64; WORD_ORDER + 1] = [
+        u64::MAX,
+        0x5555_5555_5555_5555,
+        0x1111_1111_1111_1111,
+        0x0101_0101_0101_0101,
+        0x0001_0001_0001_0001,
+        0x0000_0001_0000_0001,
+        0x0000_0000_0000_0001,
+    ];
+
+    //  After each step, a bit is set only if the `2 * shift` bits starting from it are free.
+    let mut free = !occupied;
+    let mut shift = 1;
+
+    while shift < (1 << order) {
+        free &= free >> shift;
+        shift *= 2;
+    }
+
+    free & ALIGNED[order]
+}
+
+//
+//  Implementation Details
+//
+
+//  Computes a mask with the `number` low bits set, and all others unset.
+fn low(number: usize) -> u64 {
+    debug_assert!(number <= 64);
+
+    if number == 64 { u64::MAX } else { (1u64 << number) - 1 }
+}
+
+//  Returns the index of the highest bit set.
+fn highest(mask: u64) -> usize {
+    debug_assert!(mask != 0);
+
+    63 - mask.leading_zeros() as usize
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn buddy_order_of() {
+    fn order_of(number_pages: usize, align_pages: usize) -> Option<usize> {
+        super::order_of(NumberPages(number_pages), align_pages, NumberPages(511))
+    }
+
+    assert_eq!(Some(0), order_of(1, 1));
+    assert_eq!(Some(1), order_of(2, 1));
+    assert_eq!(Some(2), order_of(3, 1));
+    assert_eq!(Some(2), order_of(4, 1));
+    assert_eq!(Some(3), order_of(2, 8));
+    assert_eq!(Some(8), order_of(129, 1));
+    assert_eq!(Some(8), order_of(256, 1));
+
+    assert_eq!(None, order_of(257, 1));
+    assert_eq!(None, order_of(1, 512));
+
+    assert_eq!(Some(3), super::order_of(NumberPages(8), 1, NumberPages(15)));
+    assert_eq!(None, super::order_of(NumberPages(9), 1, NumberPages(15)));
+}
+
+#[test]
+fn buddy_free_blocks() {
+    assert_eq!(!0b1011, free_blocks(0b1011, 0));
+    assert_eq!(0x5555_5555_5555_5550, free_blocks(0b1011, 1));
+    assert_eq!(0x1111_1111_1111_1110, free_blocks(0b1011, 2));
+    assert_eq!(0x0101_0101_0101_0100, free_blocks(0b1000_0000, 3));
+    assert_eq!(0x0000_0001_0000_0000, free_blocks(0b1, 5));
+
+    assert_eq!(1, free_blocks(0, 6));
+    assert_eq!(0, free_blocks(1 << 63, 6));
+}
+
+#[test]
+fn buddy_select_empty() {
+    for order in 0..=WORD_ORDER {
+        assert_eq!(Some((64 - (1 << order), WORD_ORDER)), select(0, order));
+    }
+}
+
+#[test]
+fn buddy_select_full() {
+    for order in 0..=WORD_ORDER {
+        assert_eq!(None, select(u64::MAX, order));
+    }
+
+    assert_eq!(None, select(0x5555_5555_5555_5555, 1));
+}
+
+#[test]
+fn buddy_select_best_fit() {
+    //  Pages 0 and 1 occupied: page 2 and 3 are a free block of order 1, carved first.
+    assert_eq!(Some((3, 1)), select(0b0011, 0));
+    assert_eq!(Some((2, 1)), select(0b0011, 1));
+
+    //  Page 0 occupied: page 1 is a free block of order 0, carved first.
+    assert_eq!(Some((1, 0)), select(0b0001, 0));
+
+    //  Page 0 occupied: pages 2-3 are a free block of order 1, carved before splitting 4-7.
+    assert_eq!(Some((2, 1)), select(0b0001, 1));
+
+    //  Pages 0 and 2 occupied: pages 4-7 are a free block of order 2, carved before splitting 8-15.
+    assert_eq!(Some((4, 2)), select(0b0101, 2));
+}
+
+} // mod tests
@@ -1,5 +1,8 @@
 use crate::PowerOf2;
 
+#[cfg(feature = "buddy")]
+use super::buddy;
+
 use super::{
     NumberPages,
     PageIndex,
@@ -32,7 +35,15 @@ impl Foreign {
     /// The index returned is a multiple of `align_pages`.
     ///
     /// Returns 0 if no allocation could be made.
+    ///
+    /// With the `buddy` feature, the allocation is rounded up to a block of a power of 2 number of pages, aligned on its
+    /// size, unless too large.
     pub(crate) unsafe fn allocate(&self, number_pages: NumberPages, align_pages: PowerOf2) -> Option<PageIndex> {
+        #[cfg(feature = "buddy")]
+        if let Some(order) = buddy::order_of(number_pages, align_pages.value(), self.number_pages) {
+            return self.buddy_allocate(order);
+        }
+
         if number_pages.0 == 1 {
             self.fast_allocate()
         } else {
@@ -40,8 +51,20 @@ impl Foreign {
         }
     }
 
+    /// Returns the number of pages allocated at the given index.
+    pub(crate) unsafe fn size_of(&self, index: PageIndex) -> NumberPages {
+        debug_assert!(index.value() > 0);
+        debug_assert!(index.value() <= self.number_pages.0);
+
+        //  Safety:
+        //  -   `index` is assumed to be within bounds.
+        self.sizes.get(index)
+    }
+
     /// Deallocates all cells allocated at the given index.
-    pub(crate) unsafe fn deallocate(&self, index: PageIndex) {
+    ///
+    /// Returns the number of pages deallocated.
+    pub(crate) unsafe fn deallocate(&self, index: PageIndex) -> NumberPages {
         debug_assert!(index.value() > 0);
         debug_assert!(index.value() <= self.number_pages.0);
 
@@ -54,6 +77,8 @@ impl Foreign {
         } else {
             self.flexible_deallocate(index, number_pages);
         }
+
+        number_pages
     }
 
     //  Internal: fast-allocate a single page.
@@ -72,6 +97,27 @@ impl Foreign {
         index
     }
 
+    //  Internal: allocate a block of `2^order` pages aligned on its size, if possible.
+    //
+    //  Single pages are allocated on the fast path, which is compatible as any single page is a block of order 0.
+    #[cfg(feature = "buddy")]
+    fn buddy_allocate(&self, order: usize) -> Option<PageIndex> {
+        if order == 0 {
+            return self.fast_allocate();
+        }
+
+        let index = self.pages.buddy_allocate(order);
+
+        if let Some(index) = index {
+            //  Safety:
+            //  -   `index` is within bounds.
+            //  -   `1 << order` is at most 256.
+            unsafe { self.sizes.set(index, NumberPages(1 << order)) };
+        }
+
+        index
+    }
+
     //  Internal.
     unsafe fn fast_deallocate(&self, index: PageIndex) { self.pages.fast_deallocate(index) }
 
@@ -106,6 +152,7 @@ fn foreign_allocate_deallocate_fast() {
     assert_eq!(Some(reused.value()), allocate_fast(&foreign));
 }
 
+#[cfg(not(feature = "buddy"))]
 #[test]
 fn foreign_allocate_deallocate_flexible() {
     fn allocate_flexible(foreign: &Foreign, number_pages: usize) -> Option<usize> {
@@ -126,4 +173,29 @@ fn foreign_allocate_deallocate_flexible() {
     assert_eq!(Some(64 * 6 + 51), allocate_flexible(&foreign, 24));
 }
 
+#[cfg(feature = "buddy")]
+#[test]
+fn foreign_allocate_deallocate_buddy() {
+    fn allocate_buddy(foreign: &Foreign, number_pages: usize) -> Option<usize> {
+        unsafe { foreign.allocate(NumberPages(number_pages), PowerOf2::ONE) }.map(|x| x.value())
+    }
+
+    let foreign = Foreign::new(NumberPages(511));
+
+    //  Rounded up to 32 pages, carved from the free block next to the header.
+    assert_eq!(Some(32), allocate_buddy(&foreign, 26));
+    //  Rounded up to 32 pages, carved from the highest free block.
+    assert_eq!(Some(64 * 7 + 32), allocate_buddy(&foreign, 25));
+    //  Rounded up to 4 pages, carved from the free block next to the header.
+    assert_eq!(Some(4), allocate_buddy(&foreign, 3));
+
+    //  Merged back with its buddy on deallocation.
+    assert_eq!(32, unsafe { foreign.deallocate(PageIndex::new(64 * 7 + 32).unwrap()) }.0);
+    assert_eq!(Some(64 * 7), allocate_buddy(&foreign, 64));
+
+    //  Too large for a buddy block, carved exactly.
+    assert_eq!(4, unsafe { foreign.deallocate(PageIndex::new(4).unwrap()) }.0);
+    assert_eq!(Some(64 * 2 + 30), allocate_buddy(&foreign, 290));
+}
+
 } // mod tests
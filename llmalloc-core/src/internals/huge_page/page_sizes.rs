@@ -1,9 +1,8 @@
 //! A mapping of how many pages are allocated.
 
-use core::{
-    mem,
-    sync::atomic::{AtomicU8, Ordering},
-};
+use core::mem;
+
+use crate::internals::sync::{AtomicU8, Ordering};
 
 use super::{NumberPages, PageIndex};
 
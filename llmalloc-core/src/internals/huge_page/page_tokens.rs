@@ -6,6 +6,9 @@ use crate::PowerOf2;
 
 use super::{AtomicBitMask, NumberPages, PageIndex};
 
+#[cfg(feature = "buddy")]
+use super::buddy;
+
 //  Page Tokens.
 //
 //  A bitmap of which Large Pages are available, and which are not, where 0 means available and 1 occupied.
@@ -73,7 +76,7 @@ impl PageTokens {
         //  -   1 is a power of 2.
         //  -   Hence the maximum is a power of 2.
         let align_outer = align_pages / AtomicBitMask::CAPACITY;
-        debug_assert!(align_outer == 0 || self.0.len() % align_outer == 0);
+        debug_assert!(align_outer == 0 || self.0.len().is_multiple_of(align_outer));
         debug_assert!(self.0.len() == 8, "{} != 8 => review `match`!", self.0.len());
 
         //  Do a single pass over the bits, attempting to find `number` consecutive ones.
@@ -106,6 +109,51 @@ impl PageTokens {
         None
     }
 
+    /// Allocates a block of `2^order` Large Pages, aligned on its size, return the index of the first page, or none if
+    /// it could not allocate.
+    ///
+    /// The block is carved out of the smallest free block available, see `buddy::select`.
+    #[cfg(feature = "buddy")]
+    pub(crate) fn buddy_allocate(&self, order: usize) -> Option<PageIndex> {
+        debug_assert!(order <= buddy::MAXIMUM_ORDER);
+
+        let number_pages = 1usize << order;
+
+        if order > buddy::WORD_ORDER {
+            //  Safety:
+            //  -   `number_pages` is a power of 2.
+            let align_pages = unsafe { PowerOf2::new_unchecked(number_pages) };
+
+            return self.flexible_allocate(NumberPages(number_pages), align_pages);
+        }
+
+        loop {
+            //  Locate the best fit across all bits, preferring the highest in case of ties.
+            let mut best: Option<(usize, usize, usize)> = None;
+
+            for (outer, bits) in self.0.iter().enumerate().rev() {
+                let (inner, fit) = match buddy::select(bits.load(), order) {
+                    Some(selected) => selected,
+                    None => continue,
+                };
+
+                if best.is_none_or(|(_, _, best_fit)| fit < best_fit) {
+                    best = Some((outer, inner, fit));
+                }
+
+                //  An exact fit cannot be beaten.
+                if fit == order { break; }
+            }
+
+            let (outer, inner, _) = best?;
+
+            //  Another thread may have claimed some of the bits in the meantime, in which case try again.
+            if self.0[outer].claim_at(inner, number_pages) {
+                return PageIndex::new(outer * AtomicBitMask::CAPACITY + inner);
+            }
+        }
+    }
+
     /// Deallocates the large page at the specified `index`.
     ///
     /// #   Safety
@@ -311,8 +359,8 @@ fn create_page_tokens(tokens: RawPageTokens) -> PageTokens {
 
     let page_tokens = PageTokens::new(NumberPages(511));
 
-    for i in 0..tokens.len() {
-        page_tokens.0[i].initialize(tokens[i]);
+    for (token, raw) in page_tokens.0.iter().zip(tokens.iter()) {
+        token.initialize(*raw);
     }
 
     page_tokens
@@ -606,6 +654,41 @@ fn page_tokens_flexible_deallocate() {
     );
 }
 
+#[cfg(feature = "buddy")]
+#[test]
+fn page_tokens_buddy_allocate() {
+    fn buddy_allocate(order: usize, initial: RawPageTokens, expected: RawPageTokens) -> Option<usize> {
+        let tokens = create_page_tokens(initial);
+        let index = tokens.buddy_allocate(order).map(|x| x.value());
+
+        check_tokens(load_page_tokens(&tokens), expected);
+
+        index
+    }
+
+    let full = u64::MAX;
+    let all_empty = [1, 0, 0, 0, 0, 0, 0, 0];
+
+    //  Best fit, within the first AtomicBitMask, next to the header.
+    assert_eq!(Some(1), buddy_allocate(0, all_empty, [0b11, 0, 0, 0, 0, 0, 0, 0]));
+    assert_eq!(Some(2), buddy_allocate(1, all_empty, [0b1101, 0, 0, 0, 0, 0, 0, 0]));
+    assert_eq!(Some(32), buddy_allocate(5, all_empty, [1 + high(32), 0, 0, 0, 0, 0, 0, 0]));
+
+    //  No fit within the first AtomicBitMask, hence the highest.
+    assert_eq!(Some(448), buddy_allocate(6, all_empty, [1, 0, 0, 0, 0, 0, 0, full]));
+    assert_eq!(Some(384), buddy_allocate(7, all_empty, [1, 0, 0, 0, 0, 0, full, full]));
+    assert_eq!(Some(256), buddy_allocate(8, all_empty, [1, 0, 0, 0, full, full, full, full]));
+
+    //  Best fit, within a partially occupied AtomicBitMask.
+    let before = [full, 0, 0, 0, 0, 0, 0, high(48)];
+    assert_eq!(Some(64 * 7 + 8), buddy_allocate(3, before, [full, 0, 0, 0, 0, 0, 0, high(56)]));
+
+    //  No fit at all.
+    let all_full = [full, full, full, full, full, full, full, full];
+    assert_eq!(None, buddy_allocate(0, all_full, all_full));
+    assert_eq!(None, buddy_allocate(8, all_full, all_full));
+}
+
 struct Global {
     victim: PageTokens,
     page_indexes: [AtomicUsize; 4],
@@ -671,6 +754,7 @@ impl Local {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn page_tokens_concurrent_fast_allocate_deallocate_success_fuzzing() {
     //  This test aims at validating that fast_allocate can be called concurrently.
     //
@@ -714,6 +798,7 @@ fn page_tokens_concurrent_fast_allocate_deallocate_success_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn page_tokens_concurrent_fast_allocate_deallocate_failure_fuzzing() {
     //  This test aims at validating that fast_allocate can be called concurrently.
     //
@@ -757,6 +842,7 @@ fn page_tokens_concurrent_fast_allocate_deallocate_failure_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn page_tokens_concurrent_flexible_allocate_deallocate_success_fuzzing() {
     //  This test aims at validating that flexible_allocate can be called concurrently.
     //
@@ -811,6 +897,7 @@ fn page_tokens_concurrent_flexible_allocate_deallocate_success_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn page_tokens_concurrent_flexible_allocate_deallocate_failure_fuzzing() {
     //  This test aims at validating that flexible_allocate can be called concurrently.
     //
@@ -3,9 +3,18 @@
 //! A Large Page is a slab of `Configuration::LARGE_PAGE_SIZE` bytes, which fulfills allocations of the Normal category.
 //!
 //! Each instance of a Large Page can only fulfill allocations of a specific ClassSize.
+//!
+//! The cells of successive Large Pages are offset by varying multiples of the cache line size, when space allows, so
+//! that homogeneous objects allocated in bulk do not all collide on the same cache sets.
+//!
+//! The cells freed by the local thread are tracked either in a linked list, by default, or in occupancy bitmaps, with
+//! the `bitmap` feature.
 
 mod adrift;
+#[cfg_attr(not(feature = "bitmap"), allow(dead_code))]
+mod bitmap;
 mod foreign;
+#[cfg_attr(feature = "bitmap", allow(dead_code))]
 mod local;
 
 #[cfg(test)]
@@ -15,7 +24,6 @@ use core::{
     cmp,
     mem,
     ptr::{self, NonNull},
-    sync::atomic::{self, Ordering},
 };
 
 use crate::{ClassSize, Configuration};
@@ -23,11 +31,17 @@ use crate::{
     internals::{
         atomic_stack::{AtomicStackElement, AtomicStackLink},
         blocks::BlockForeignList,
+        sync::{self, Ordering},
     },
     utils,
 };
 
 use foreign::Foreign;
+
+#[cfg(feature = "bitmap")]
+use bitmap::BitmapLocal as Local;
+
+#[cfg(not(feature = "bitmap"))]
 use local::Local;
 
 /// The header of a Large Page, for normal allocations.
@@ -70,7 +84,7 @@ impl LargePage {
         ptr::write(large_page, Self::new::<C>(at, owner, class_size));
 
         //  Enforce memory ordering, later Acquire need to see those 0s and 1s.
-        atomic::fence(Ordering::Release);
+        sync::fence(Ordering::Release);
 
         at.cast()
     }
@@ -119,6 +133,24 @@ impl LargePage {
             .or_else(|| self.foreign.allocate(&self.local))
     }
 
+    /// Returns the number of blocks available for allocation, counting no further than `limit`.
+    ///
+    /// The blocks freed by foreign threads are only counted if they suffice to catch the page, as otherwise they are
+    /// not handed out by `allocate`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that a single thread calls `available` at a time, the one calling `allocate`.
+    pub(crate) unsafe fn available(&self, limit: usize) -> usize {
+        let local = self.local.available(limit);
+
+        if local >= limit {
+            return limit;
+        }
+
+        cmp::min(limit, local + self.foreign.available())
+    }
+
     /// Deallocate a block from the local thread.
     ///
     /// #   Safety
@@ -169,7 +201,7 @@ impl LargePage {
             F: FnMut(NonNull<LargePage>),
     {
         debug_assert!(!list.is_empty());
-        debug_assert!(self.common.is_local_cell_pointer(list.head().expect("Not empty").cast()));
+        debug_assert!(list.head().is_some_and(|head| self.common.is_local_cell_pointer(head.cast())));
 
         if self.foreign.refill(list) {
             recycler(NonNull::from(self));
@@ -186,11 +218,17 @@ impl LargePage {
         let layout = class_size.layout();
         let block_size = layout.size();
 
-        let reserved = cmp::max(mem::size_of::<Self>(), layout.align());
+        let header = mem::size_of::<Self>() + Self::metadata_size(class_size, large_page_size.value());
+
+        let reserved = cmp::max(header, layout.align());
         let number_cells = class_size.number_elements(large_page_size.value() - reserved);
         debug_assert!(number_cells >= 1);
 
-        let end = NonNull::new_unchecked(at.as_ptr().add(large_page_size.value()));
+        let slack = large_page_size.value() - reserved - number_cells * block_size;
+        let color = Self::color_offset(at, large_page_size.value(), slack, layout.align());
+        debug_assert!(color <= slack);
+
+        let end = NonNull::new_unchecked(at.as_ptr().add(large_page_size.value() - color));
         let begin = NonNull::new_unchecked(end.as_ptr().sub(number_cells * block_size));
     
         let flush_threshold = cmp::max(number_cells / 64, 1);
@@ -198,11 +236,61 @@ impl LargePage {
 
         let _prefetch = utils::PrefetchGuard::default();
         let common = Common::new(owner, class_size, flush_threshold, begin, end);
-        let local = Local::new(block_size, begin, end);
+        let local = Self::new_local(at, block_size, begin, end);
         let foreign = Foreign::new(catch_threshold);
 
         Self { _prefetch, common, local, foreign, }
     }
+
+    //  Internal: Returns the offset of the cells from the end of the page, for a page starting at `at`.
+    //
+    //  The cells of homogeneous pages would otherwise all start at the same offset within their page, and thus collide
+    //  on the same cache and TLB sets when accessed in bulk. Instead, the unused `slack` bytes are distributed between
+    //  the head and the tail of the page, by multiples of the cache line size, so that successive pages are offset by
+    //  successive colors.
+    fn color_offset(at: NonNull<u8>, large_page_size: usize, slack: usize, align: usize) -> usize {
+        const CACHE_LINE_SIZE: usize = 64;
+
+        let step = cmp::max(CACHE_LINE_SIZE, align);
+        let number_colors = slack / step + 1;
+
+        let index = at.as_ptr() as usize / large_page_size;
+
+        (index % number_colors) * step
+    }
+
+    //  Internal: Returns the size of the meta-data stored after the header, for a page of `large_page_size` bytes.
+    #[cfg(not(feature = "bitmap"))]
+    fn metadata_size(_: ClassSize, _: usize) -> usize { 0 }
+
+    //  Internal: Returns the size of the meta-data stored after the header, for a page of `large_page_size` bytes.
+    //
+    //  The number of cells is over-estimated, by ignoring the space taken by the meta-data itself.
+    #[cfg(feature = "bitmap")]
+    fn metadata_size(class_size: ClassSize, large_page_size: usize) -> usize {
+        let number_cells = class_size.number_elements(large_page_size - mem::size_of::<Self>());
+
+        Local::bitmap_size(number_cells)
+    }
+
+    //  Internal: Creates the instance of `Local`, for a page starting at `at`.
+    #[cfg(not(feature = "bitmap"))]
+    unsafe fn new_local(_: NonNull<u8>, block_size: usize, begin: NonNull<u8>, end: NonNull<u8>) -> Local {
+        Local::new(block_size, begin, end)
+    }
+
+    //  Internal: Creates the instance of `Local`, for a page starting at `at`.
+    //
+    //  The bitmap is stored immediately after the header.
+    #[cfg(feature = "bitmap")]
+    unsafe fn new_local(at: NonNull<u8>, block_size: usize, begin: NonNull<u8>, end: NonNull<u8>) -> Local {
+        //  Safety:
+        //  -   The header is followed by `metadata_size` bytes, reserved for the bitmap.
+        //  -   The header size is a multiple of 128, hence the bitmap is suitably aligned.
+        let bitmap = NonNull::new_unchecked(at.as_ptr().add(mem::size_of::<Self>())).cast();
+
+        Local::new(block_size, bitmap, begin, end)
+    }
 }
 
 impl AtomicStackElement for LargePage {
@@ -265,6 +353,7 @@ impl Common {
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_range_loop)]
 mod tests {
 
 use core::{
@@ -306,6 +395,25 @@ fn common_is_local_cell_pointer() {
     assert!(!common.is_local_cell_pointer(pointer(0x60)));
 }
 
+#[test]
+fn large_page_color_offset() {
+    fn color_offset(index: usize, slack: usize, align: usize) -> usize {
+        let at = NonNull::new((index * 2048) as *mut u8).unwrap();
+
+        LargePage::color_offset(at, 2048, slack, align)
+    }
+
+    let colors: Vec<_> = (1..=6).map(|index| color_offset(index, 200, 8)).collect();
+    assert_eq!(vec![64, 128, 192, 0, 64, 128], colors);
+
+    //  Not enough slack for a single cache line.
+    assert_eq!(0, color_offset(3, 63, 8));
+
+    //  Offsets preserve the alignment of the cells.
+    assert_eq!(0, color_offset(3, 200, 256));
+    assert_eq!(256, color_offset(3, 300, 256));
+}
+
 struct TestConfiguration;
 
 impl Configuration for TestConfiguration {
@@ -315,7 +423,7 @@ impl Configuration for TestConfiguration {
 
 #[derive(Clone, Copy)]
 #[repr(align(2048))]
-struct LargePageStore([usize; 256]);
+struct LargePageStore(#[allow(dead_code)] [usize; 256]);
 
 impl LargePageStore {
     unsafe fn initialize(&mut self, class_size: ClassSize) -> NonNull<LargePage> {
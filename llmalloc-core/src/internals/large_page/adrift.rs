@@ -1,6 +1,6 @@
 //! The Adrift flag.
 
-use core::sync::atomic::{AtomicU64, Ordering};
+use crate::internals::sync::{AtomicU64, Ordering};
 
 //  Adrift "boolean"
 // 
@@ -32,7 +32,7 @@ impl Adrift {
     //  If adrift, returns the current value, otherwise returns None.
     pub(crate) fn is_adrift(&self) -> Option<u64> {
         let current = self.load();
-        if current % 2 != 0 { Some(current) } else { None }
+        if !current.is_multiple_of(2) { Some(current) } else { None }
     }
 
     //  Casts the value adrift, incrementing the counter.
@@ -40,14 +40,14 @@ impl Adrift {
     //  Returns the (new) current value.
     pub(crate) fn cast_adrift(&self) -> u64 {
         let before = self.0.fetch_add(1, Ordering::AcqRel);
-        debug_assert!(before % 2 == 0, "before: {}", before);
+        debug_assert!(before.is_multiple_of(2), "before: {}", before);
 
         before + 1
     }
 
     //  Attempts to catch the value, returns true if it succeeds.
     pub(crate) fn catch(&self, current: u64) -> bool { 
-        debug_assert!(current % 2 != 0);
+        debug_assert!(!current.is_multiple_of(2));
 
         self.0.compare_exchange(current, current + 1, Ordering::AcqRel, Ordering::Relaxed).is_ok()
     }
@@ -92,6 +92,7 @@ fn adrift() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn adrift_concurrent_catch_fuzzing() {
     //  This test aims at testing that a single thread can catch an adrift page.
     //
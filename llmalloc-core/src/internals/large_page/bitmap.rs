@@ -0,0 +1,423 @@
+//! Local data, only accessible from the local thread, tracking free cells with occupancy bitmaps.
+//!
+//! An alternative to the linked free list of `Local`, enabled with the `bitmap` feature.
+//!
+//! The cells are grouped in chunks of 64, and each chunk is associated with a 64-bits word in which a set bit denotes a
+//! free cell. This enables:
+//!
+//! -   Branch-light allocation, with `trailing_zeros` locating the first free cell of a chunk.
+//! -   O(1) detection of double-frees, as the bit of a cell being freed must be clear.
+//! -   Bulk frees which do not touch the memory of the freed cells, beyond the one bit of each.
+//!
+//! The bitmap itself is stored inline, for pages of up to 512 cells, and otherwise within the LargePage, following its
+//! header.
+
+use core::{
+    cell::Cell,
+    cmp,
+    mem,
+    ptr::NonNull,
+};
+
+use crate::internals::blocks::{BlockForeignList, BlockLocal, BlockLocalStack};
+
+//  Local data. Only accessible from the local thread.
+#[repr(align(128))]
+pub(crate) struct BitmapLocal {
+    //  Occupancy bitmaps, one word per chunk of 64 cells; a set bit denotes a free cell.
+    //
+    //  Only used if `words > INLINE_WORDS`, otherwise `inline` is used instead.
+    bitmap: NonNull<Cell<u64>>,
+    //  Number of words of the bitmap.
+    words: usize,
+    //  Index of the first word which may contain a set bit, all words prior to it are known to be 0.
+    hint: Cell<usize>,
+    //  Pointer to the beginning of the cells.
+    begin: NonNull<u8>,
+    //  Pointer to the beginning of the uncarved area of the page.
+    //
+    //  Zeroing the bitmap is cheap, however marking all cells as free would require requesting all the memory of the
+    //  page from the OS. Instead cells are carved on demand, and their bit is only ever set once freed.
+    watermark: Cell<NonNull<u8>>,
+    //  Pointer to the end of the page; when `watermark == end`, the entire page has been carved.
+    end: NonNull<u8>,
+    //  Size, in bytes, of the cells.
+    block_size: usize,
+    //  Occupancy bitmaps, used if `words <= INLINE_WORDS`.
+    inline: [Cell<u64>; INLINE_WORDS],
+}
+
+impl BitmapLocal {
+    /// Returns the number of bytes of bitmap to be stored outside the instance, for `number_cells` cells.
+    pub(crate) fn bitmap_size(number_cells: usize) -> usize {
+        let words = Self::number_words(number_cells);
+
+        if words <= INLINE_WORDS { 0 } else { words * mem::size_of::<u64>() }
+    }
+
+    /// Creates a new instance of `BitmapLocal`.
+    ///
+    /// #   Safety
+    ///
+    /// -   `bitmap` is assumed to be valid for `bitmap_size((end - begin) / block_size)` bytes, and exclusively owned;
+    ///     it is not accessed if this size is 0.
+    /// -   `end - begin` is assumed to be a multiple of `block_size`.
+    pub(crate) unsafe fn new(block_size: usize, bitmap: NonNull<u64>, begin: NonNull<u8>, end: NonNull<u8>) -> Self {
+        debug_assert!(block_size >= 1);
+        debug_assert!((end.as_ptr() as usize - begin.as_ptr() as usize).is_multiple_of(block_size),
+            "block_size: {}, begin: {:x}, end: {:x}", block_size, begin.as_ptr() as usize, end.as_ptr() as usize);
+
+        let number_cells = (end.as_ptr() as usize - begin.as_ptr() as usize) / block_size;
+        let words = Self::number_words(number_cells);
+
+        if Self::bitmap_size(number_cells) > 0 {
+            //  Safety:
+            //  -   `bitmap` is assumed to be valid for `words` words.
+            bitmap.as_ptr().write_bytes(0, words);
+        }
+
+        let bitmap = bitmap.cast();
+        let hint = Cell::new(words);
+        let watermark = Cell::new(begin);
+        let inline = Default::default();
+
+        Self { bitmap, words, hint, begin, watermark, end, block_size, inline, }
+    }
+
+    /// Allocates one cell from the page, if any.
+    ///
+    /// Returns a null pointer is no cell is available.
+    pub(crate) fn allocate(&self) -> Option<NonNull<u8>> {
+        //  Fast Path.
+        let mut index = self.hint.get();
+
+        while index < self.words {
+            //  Safety:
+            //  -   `index` is within bounds.
+            let word = unsafe { self.word(index) };
+            let bits = word.get();
+
+            if bits != 0 {
+                word.set(bits & (bits - 1));
+                self.hint.set(index);
+
+                let cell = index * 64 + bits.trailing_zeros() as usize;
+
+                //  Safety:
+                //  -   `cell` was freed, hence was carved, hence is within bounds.
+                return Some(unsafe { self.cell(cell) });
+            }
+
+            index += 1;
+        }
+
+        self.hint.set(self.words);
+
+        //  Cruise path.
+        if self.watermark.get() == self.end {
+            return None;
+        }
+
+        //  Expansion path.
+        let result = self.watermark.get();
+
+        //  Safety:
+        //  -   `self.block_size` matches the size of the cells.
+        //  -   `self.watermark` is still within bounds.
+        unsafe { self.watermark.set(NonNull::new_unchecked(result.as_ptr().add(self.block_size))) };
+
+        Some(result)
+    }
+
+    /// Deallocates one cell from the page.
+    ///
+    /// #   Panics
+    ///
+    /// If the cell is already free.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `ptr` points to a cell of this page.
+    pub(crate) unsafe fn deallocate(&self, ptr: NonNull<u8>) { self.free(ptr); }
+
+    /// Extends the local bitmap from a foreign list.
+    ///
+    /// #   Panics
+    ///
+    /// If any cell is already free.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the access to the linked cells, is exclusive.
+    pub(crate) unsafe fn extend(&self, list: &BlockForeignList) {
+        debug_assert!(!list.is_empty());
+
+        let stack = BlockLocalStack::default();
+
+        //  Safety:
+        //  -   It is assumed that access to the cell, and all linked cells, is exclusive.
+        stack.extend(list);
+
+        self.free_all(&stack);
+    }
+
+    /// Refills the local bitmap from a foreign list.
+    ///
+    /// #   Panics
+    ///
+    /// If any cell is already free.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that access to the cell, and all linked cells, is exclusive.
+    pub(crate) unsafe fn refill(&self, list: NonNull<BlockLocal>) {
+        let stack = BlockLocalStack::new(Some(list));
+
+        self.free_all(&stack);
+    }
+
+    /// Returns the number of cells available for allocation, counting no further than `limit`.
+    pub(crate) fn available(&self, limit: usize) -> usize {
+        let mut available = (self.end.as_ptr() as usize - self.watermark.get().as_ptr() as usize) / self.block_size;
+
+        for index in self.hint.get()..self.words {
+            if available >= limit {
+                break;
+            }
+
+            //  Safety:
+            //  -   `index` is within bounds.
+            available += unsafe { self.word(index) }.get().count_ones() as usize;
+        }
+
+        cmp::min(available, limit)
+    }
+
+    /// Returns the size of the blocks.
+    #[cfg(test)]
+    pub(crate) fn block_size(&self) -> usize { self.block_size }
+
+    //  Internal; Returns the number of words required for `number_cells` cells.
+    fn number_words(number_cells: usize) -> usize { number_cells.div_ceil(64) }
+
+    //  Internal; Frees all the cells of `stack`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that all cells of `stack` belong to this page.
+    unsafe fn free_all(&self, stack: &BlockLocalStack) {
+        while let Some(block) = stack.pop() {
+            self.free(block.cast());
+        }
+    }
+
+    //  Internal; Frees a single cell.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `ptr` points to a cell of this page.
+    #[inline(always)]
+    unsafe fn free(&self, ptr: NonNull<u8>) {
+        debug_assert!(self.begin <= ptr && ptr < self.watermark.get());
+
+        let cell = (ptr.as_ptr() as usize - self.begin.as_ptr() as usize) / self.block_size;
+        let (index, bit) = (cell / 64, 1u64 << (cell % 64));
+
+        //  Safety:
+        //  -   `ptr` is assumed to point within the page, hence `index` is within bounds.
+        let word = self.word(index);
+        let bits = word.get();
+
+        assert!(bits & bit == 0, "Double free of {:x}", ptr.as_ptr() as usize);
+
+        word.set(bits | bit);
+
+        if index < self.hint.get() {
+            self.hint.set(index);
+        }
+    }
+
+    //  Internal; Returns the word at `index`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes `index` is less than `self.words`.
+    #[inline(always)]
+    unsafe fn word(&self, index: usize) -> &Cell<u64> {
+        debug_assert!(index < self.words);
+
+        if self.words <= INLINE_WORDS {
+            self.inline.get_unchecked(index)
+        } else {
+            &*self.bitmap.as_ptr().add(index)
+        }
+    }
+
+    //  Internal; Returns the pointer to the cell at `index`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes `index` is within bounds.
+    #[inline(always)]
+    unsafe fn cell(&self, index: usize) -> NonNull<u8> {
+        NonNull::new_unchecked(self.begin.as_ptr().add(index * self.block_size))
+    }
+}
+
+//  The number of words of bitmap stored inline, filling up the cache lines.
+const INLINE_WORDS: usize = 8;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+use super::super::test::{BlockStore, BLOCK_SIZE};
+
+#[test]
+fn bitmap_local_size() {
+    assert_eq!(128, mem::size_of::<BitmapLocal>());
+
+    assert_eq!(0, BitmapLocal::bitmap_size(0));
+    assert_eq!(0, BitmapLocal::bitmap_size(512));
+    assert_eq!(72, BitmapLocal::bitmap_size(513));
+    assert_eq!(128, BitmapLocal::bitmap_size(1024));
+}
+
+#[test]
+fn bitmap_local_allocate_expansion() {
+    let block_store = BlockStore::default();
+    let local = unsafe { block_store.create_bitmap_local(BLOCK_SIZE) };
+
+    //  Bump watermark until it is no longer possible.
+    for i in 0..64 {
+        assert_eq!(block_store.get(4 * i), local.allocate().unwrap());
+    }
+
+    assert_eq!(local.end, local.watermark.get());
+
+    assert_eq!(None, local.allocate());
+}
+
+#[test]
+fn bitmap_local_allocate_deallocate_lowest_first() {
+    let block_store = BlockStore::default();
+    let local = unsafe { block_store.create_bitmap_local(BLOCK_SIZE) };
+
+    let pointers: Vec<_> = (0..8).map(|_| local.allocate().unwrap()).collect();
+
+    unsafe {
+        local.deallocate(pointers[5]);
+        local.deallocate(pointers[2]);
+        local.deallocate(pointers[7]);
+    }
+
+    //  The lowest free cells are allocated first, then the watermark is bumped.
+    assert_eq!(Some(pointers[2]), local.allocate());
+    assert_eq!(Some(pointers[5]), local.allocate());
+    assert_eq!(Some(pointers[7]), local.allocate());
+    assert_eq!(block_store.get(4 * 8), local.allocate().unwrap());
+}
+
+#[test]
+fn bitmap_local_allocate_deallocate_external() {
+    const NUMBER_CELLS: usize = 1024;
+
+    let blocks = vec![0usize; NUMBER_CELLS];
+    let mut bitmap = vec![0u64; BitmapLocal::bitmap_size(NUMBER_CELLS) / 8];
+
+    let block_size = mem::size_of::<usize>();
+    let begin: NonNull<u8> = NonNull::from(&blocks[0]).cast();
+    let end = unsafe { NonNull::new_unchecked(begin.as_ptr().add(NUMBER_CELLS * block_size)) };
+
+    let local = unsafe { BitmapLocal::new(block_size, NonNull::from(&mut bitmap[0]), begin, end) };
+
+    let pointers: Vec<_> = (0..NUMBER_CELLS).map(|_| local.allocate().unwrap()).collect();
+    assert_eq!(None, local.allocate());
+
+    //  Free a cell in the last and first chunks.
+    unsafe {
+        local.deallocate(pointers[NUMBER_CELLS - 1]);
+        local.deallocate(pointers[3]);
+    }
+
+    assert_eq!(1 << 3, bitmap[0]);
+    assert_eq!(1 << 63, bitmap[15]);
+
+    assert_eq!(Some(pointers[3]), local.allocate());
+    assert_eq!(Some(pointers[NUMBER_CELLS - 1]), local.allocate());
+    assert_eq!(None, local.allocate());
+}
+
+#[test]
+#[should_panic(expected = "Double free")]
+fn bitmap_local_double_free() {
+    let block_store = BlockStore::default();
+    let local = unsafe { block_store.create_bitmap_local(BLOCK_SIZE) };
+
+    let ptr = local.allocate().unwrap();
+
+    unsafe {
+        local.deallocate(ptr);
+        local.deallocate(ptr);
+    }
+}
+
+#[test]
+fn bitmap_local_extend() {
+    let block_store = BlockStore::default();
+    let local = unsafe { block_store.create_bitmap_local(BLOCK_SIZE) };
+
+    //  Allocate all.
+    while local.allocate().is_some() {}
+
+    let foreign_list = unsafe { block_store.create_foreign_list(BLOCK_SIZE, 3..7) };
+
+    unsafe { local.extend(&foreign_list) };
+    assert!(foreign_list.is_empty());
+
+    for i in 3..7 {
+        assert_eq!(block_store.get(4 * i), local.allocate().unwrap());
+    }
+
+    assert_eq!(None, local.allocate());
+}
+
+#[test]
+fn bitmap_local_refill() {
+    let block_store = BlockStore::default();
+    let local = unsafe { block_store.create_bitmap_local(BLOCK_SIZE) };
+
+    //  Allocate all.
+    while local.allocate().is_some() {}
+
+    let foreign = unsafe { block_store.create_foreign_stack(BLOCK_SIZE, 3..7) };
+
+    unsafe { local.refill(BlockLocal::from_atomic(foreign)) };
+
+    for i in 3..7 {
+        assert_eq!(block_store.get(4 * i), local.allocate().unwrap());
+    }
+
+    assert_eq!(None, local.allocate());
+}
+
+#[test]
+fn bitmap_local_available() {
+    let block_store = BlockStore::default();
+    let local = unsafe { block_store.create_bitmap_local(BLOCK_SIZE) };
+
+    assert_eq!(64, local.available(usize::MAX));
+    assert_eq!(10, local.available(10));
+
+    let pointers: Vec<_> = (0..64).map(|_| local.allocate().unwrap()).collect();
+    assert_eq!(0, local.available(usize::MAX));
+
+    for pointer in &pointers[..5] {
+        unsafe { local.deallocate(*pointer) };
+    }
+
+    assert_eq!(5, local.available(usize::MAX));
+    assert_eq!(3, local.available(3));
+}
+
+} // mod tests
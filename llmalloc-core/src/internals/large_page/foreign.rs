@@ -5,8 +5,8 @@ use core::ptr::NonNull;
 use crate::internals::blocks::{AtomicBlockForeignList, BlockForeignList, BlockLocal};
 
 use super::{
+    Local,
     adrift::Adrift,
-    local::Local,
 };
 
 //  Foreign data. Accessible both from the local thread and foreign threads, at the cost of synchronization.
@@ -45,7 +45,7 @@ impl Foreign {
 
         //  Cast the page adrift.
         let generation = self.adrift.cast_adrift();
-        debug_assert!(generation % 2 != 0);
+        debug_assert!(!generation.is_multiple_of(2));
 
         //  There is an inherent race-condition, above, as a foreign thread may have extended the freed list beyond
         //  the catch threshold and yet seen a non-adrift page. The current thread therefore needs to check again.
@@ -87,6 +87,13 @@ impl Foreign {
         }
     }
 
+    /// Returns the number of freed cells available for allocation, that is none unless they suffice to catch the page.
+    pub(crate) fn available(&self) -> usize {
+        let freed = self.freed.len();
+
+        if freed >= self.catch_threshold { freed } else { 0 }
+    }
+
     /// Returns the catch threshold of the page.
     #[cfg(test)]
     pub(crate) fn catch_threshold(&self) -> usize { self.catch_threshold }
@@ -142,23 +149,23 @@ fn foreign_refill() {
     let local = unsafe { block_store.create_local(BLOCK_SIZE) };
 
     //  Allocate all.
-    while let Some(_) = local.allocate() {}
+    while local.allocate().is_some() {}
 
     let foreign = Foreign::new(16);
 
     //  Insufficient number of elements.
-    let list = unsafe { block_store.create_foreign_list(&local, 3..7) };
+    let list = unsafe { block_store.create_foreign_list(BLOCK_SIZE, 3..7) };
     assert!(unsafe { !foreign.refill(&list) });
 
     //  Sufficient number, but not adrift.
-    let list = unsafe { block_store.create_foreign_list(&local, 7..32) };
+    let list = unsafe { block_store.create_foreign_list(BLOCK_SIZE, 7..32) };
     assert!(unsafe { !foreign.refill(&list) });
 
     //  Number already sufficient, and now was adrift.
     foreign.adrift.cast_adrift();
     assert_eq!(Some(1), foreign.adrift.is_adrift());
 
-    let list = unsafe { block_store.create_foreign_list(&local, 0..3) };
+    let list = unsafe { block_store.create_foreign_list(BLOCK_SIZE, 0..3) };
     assert!(unsafe { foreign.refill(&list) });
 }
 
@@ -168,12 +175,12 @@ fn foreign_allocate() {
     let local = unsafe { block_store.create_local(BLOCK_SIZE) };
 
     //  Allocate all.
-    while let Some(_) = local.allocate() {}
+    while local.allocate().is_some() {}
 
     let foreign = Foreign::new(16);
 
     //  Enough elements, immediate recycling.
-    let list = unsafe { block_store.create_foreign_list(&local, 0..32) };
+    let list = unsafe { block_store.create_foreign_list(BLOCK_SIZE, 0..32) };
     assert!(unsafe { !foreign.refill(&list) });
 
     assert_eq!(block_store.get(0), unsafe { foreign.allocate(&local).unwrap() });
@@ -186,7 +193,7 @@ fn foreign_allocate() {
     assert_eq!(None, local.allocate());
 
     //  Not enough elements, cast the page adrift.
-    let list = unsafe { block_store.create_foreign_list(&local, 32..40) };
+    let list = unsafe { block_store.create_foreign_list(BLOCK_SIZE, 32..40) };
     assert!(unsafe { !foreign.refill(&list) });
 
     assert_eq!(None, unsafe { foreign.allocate(&local) });
@@ -233,16 +240,15 @@ impl Global {
 
     fn exaust_local(&self, thread: usize) {
         if thread == 0 {
-            while let Some(_) = self.local.allocate() {}
+            while self.local.allocate().is_some() {}
         }
     }
 
     fn cast_adrift(&self, thread: usize) {
-        if thread == 0 {
-            if self.victim.adrift.is_adrift().is_none() {
+        if thread == 0
+            && self.victim.adrift.is_adrift().is_none() {
                 self.victim.adrift.cast_adrift();
             }
-        }
     }
 
     fn was_adrift(&self) -> bool {
@@ -255,7 +261,7 @@ impl Global {
     //
     //  -   Assumes that `blocks` does not overlap with any live range...
     unsafe fn create_foreign_list(&self, blocks: Range<usize>) -> BlockForeignList {
-        self.store.create_foreign_list(&self.local, blocks)
+        self.store.create_foreign_list(self.local.block_size(), blocks)
     }
 
     //  Verify that the number of freed blocks matches expectations.
@@ -294,6 +300,7 @@ unsafe impl Send for Global {}
 unsafe impl Sync for Global {}
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn foreign_concurrent_refill_uncaught_fuzzing() {
     //  This test aims at testing that refill can be called concurrently, without attempting to catch.
     //
@@ -336,6 +343,7 @@ fn foreign_concurrent_refill_uncaught_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn foreign_concurrent_refill_caught_fuzzing() {
     //  This test aims at testing that refill can be called concurrently, and only one will succeed in catching.
     //
@@ -384,6 +392,7 @@ fn foreign_concurrent_refill_caught_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn foreign_concurrent_allocate_refill_fuzzing() {
     //  This test aims at testing that allocate and refill can be called concurrently.
     //
@@ -421,7 +430,7 @@ fn foreign_concurrent_allocate_refill_fuzzing() {
         };
         let step = |global: &Global, local: &mut usize, list: BlockForeignList| {
             let caught = if *local == 0 {
-                if let Some(_) = unsafe { global.victim.allocate(&global.local) } {
+                if unsafe { global.victim.allocate(&global.local) }.is_some() {
                     global.allocated.store(true, Ordering::Relaxed);
                     global.was_adrift()
                 } else {
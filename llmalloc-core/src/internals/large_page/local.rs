@@ -39,7 +39,7 @@ impl Local {
     /// -   `end - begin` is assumed to be a multiple of `block_size`.
     pub(crate) unsafe fn new(block_size: usize, begin: NonNull<u8>, end: NonNull<u8>) -> Self {
         debug_assert!(block_size >= 1);
-        debug_assert!((end.as_ptr() as usize - begin.as_ptr() as usize) % block_size == 0,
+        debug_assert!((end.as_ptr() as usize - begin.as_ptr() as usize).is_multiple_of(block_size),
             "block_size: {}, begin: {:x}, end: {:x}", block_size, begin.as_ptr() as usize, end.as_ptr() as usize);
 
         let next = BlockLocalStack::from_raw(begin);
@@ -114,13 +114,20 @@ impl Local {
         self.next.refill(list);
     }
 
+    /// Returns the number of cells available for allocation, counting no further than `limit`.
+    pub(crate) fn available(&self, limit: usize) -> usize {
+        let uncarved = (self.end.as_ptr() as usize - self.watermark.get().as_ptr() as usize) / self.block_size;
+
+        if uncarved >= limit {
+            return limit;
+        }
+
+        uncarved + self.next.count(limit - uncarved)
+    }
+
     /// Returns the size of the blocks.
     #[cfg(test)]
     pub(crate) fn block_size(&self) -> usize { self.block_size }
-
-    /// Returns the end of the watermark area.
-    #[cfg(test)]
-    pub(crate) fn end(&self) -> NonNull<u8> { self.end }
 }
 
 #[cfg(test)]
@@ -131,20 +138,20 @@ use super::super::test::{BlockStore, BLOCK_SIZE};
 
 #[test]
 fn local_new() {
-    //  This test actually tests `block_store.create_local` more than anything.
+    //  This test actually tests `block_store.create_linked_local` more than anything.
     //  Since further tests will depend on it correctly initializing `Local`, it is better to validate it early.
     let block_store = BlockStore::default();
     let end_store = block_store.end();
 
     {
-        let local = unsafe { block_store.create_local(BLOCK_SIZE * 2) };
+        let local = unsafe { block_store.create_linked_local(BLOCK_SIZE * 2) };
         assert_eq!(block_store.get(0), local.next.peek().unwrap().cast());
         assert_eq!(block_store.get(8), local.watermark.get());
         assert_eq!(end_store, local.end);
     }
 
     {
-        let local = unsafe { block_store.create_local(BLOCK_SIZE * 2 + BLOCK_SIZE / 2) };
+        let local = unsafe { block_store.create_linked_local(BLOCK_SIZE * 2 + BLOCK_SIZE / 2) };
         assert_eq!(block_store.get(6), local.next.peek().unwrap().cast());
         assert_eq!(block_store.get(16), local.watermark.get());
         assert_eq!(end_store, local.end);
@@ -154,7 +161,7 @@ fn local_new() {
 #[test]
 fn local_allocate_expansion() {
     let block_store = BlockStore::default();
-    let local = unsafe { block_store.create_local(BLOCK_SIZE) };
+    let local = unsafe { block_store.create_linked_local(BLOCK_SIZE) };
 
     //  Bump watermark until it is no longer possible.
     for i in 0..64 {
@@ -169,7 +176,7 @@ fn local_allocate_expansion() {
 #[test]
 fn local_allocate_deallocate_ping_pong() {
     let block_store = BlockStore::default();
-    let local = unsafe { block_store.create_local(BLOCK_SIZE) };
+    let local = unsafe { block_store.create_linked_local(BLOCK_SIZE) };
 
     let ptr = local.allocate();
 
@@ -184,12 +191,12 @@ fn local_allocate_deallocate_ping_pong() {
 #[test]
 fn local_extend() {
     let block_store = BlockStore::default();
-    let local = unsafe { block_store.create_local(BLOCK_SIZE) };
+    let local = unsafe { block_store.create_linked_local(BLOCK_SIZE) };
 
     //  Allocate all.
-    while let Some(_) = local.allocate() {}
+    while local.allocate().is_some() {}
 
-    let foreign_list = unsafe { block_store.create_foreign_list(&local, 3..7) };
+    let foreign_list = unsafe { block_store.create_foreign_list(BLOCK_SIZE, 3..7) };
 
     unsafe { local.extend(&foreign_list) };
     assert!(foreign_list.is_empty());
@@ -202,12 +209,12 @@ fn local_extend() {
 #[test]
 fn local_refill() {
     let block_store = BlockStore::default();
-    let local = unsafe { block_store.create_local(BLOCK_SIZE) };
+    let local = unsafe { block_store.create_linked_local(BLOCK_SIZE) };
 
     //  Allocate all.
-    while let Some(_) = local.allocate() {}
+    while local.allocate().is_some() {}
 
-    let foreign = unsafe { block_store.create_foreign_stack(&local, 3..7) };
+    let foreign = unsafe { block_store.create_foreign_stack(BLOCK_SIZE, 3..7) };
 
     unsafe { local.refill(BlockLocal::from_atomic(foreign)) };
 
@@ -216,4 +223,23 @@ fn local_refill() {
     }
 }
 
+#[test]
+fn local_available() {
+    let block_store = BlockStore::default();
+    let local = unsafe { block_store.create_linked_local(BLOCK_SIZE) };
+
+    assert_eq!(64, local.available(usize::MAX));
+    assert_eq!(10, local.available(10));
+
+    let pointers: Vec<_> = (0..64).map(|_| local.allocate().unwrap()).collect();
+    assert_eq!(0, local.available(usize::MAX));
+
+    for pointer in &pointers[..5] {
+        unsafe { local.deallocate(*pointer) };
+    }
+
+    assert_eq!(5, local.available(usize::MAX));
+    assert_eq!(3, local.available(3));
+}
+
 } // mod tests
@@ -8,16 +8,20 @@ use core::{
 
 use crate::internals::blocks::{AtomicBlockForeign, AtomicBlockForeignList, BlockForeign, BlockForeignList};
 
-use super::local::Local;
+use super::Local;
+use super::{bitmap::BitmapLocal, local};
 
 pub(crate) const BLOCK_SIZE: usize = 32;
 
-pub(crate) struct BlockStore([usize; 256]);
+pub(crate) struct BlockStore {
+    blocks: [usize; 256],
+    bitmap: [u64; 4],
+}
 
 impl BlockStore {
     pub(crate) const CAPACITY: usize = 256;
 
-    pub(crate) fn get(&self, index: usize) -> NonNull<u8> { NonNull::from(&self.0[index]).cast() }
+    pub(crate) fn get(&self, index: usize) -> NonNull<u8> { NonNull::from(&self.blocks[index]).cast() }
 
     pub(crate) fn end(&self) -> NonNull<u8> {
         let pointer = unsafe { self.get(0).as_ptr().add(BLOCK_SIZE / 4 * BlockStore::CAPACITY) };
@@ -25,27 +29,46 @@ impl BlockStore {
     }
 
     /// Borrows self, outside of the compiler's overview.
-    pub(crate) unsafe fn create_local(&self, block_size: usize) -> Local {
+    ///
+    /// Creates the `Local` selected by the features.
+    #[cfg(not(feature = "bitmap"))]
+    pub(crate) unsafe fn create_local(&self, block_size: usize) -> Local { self.create_linked_local(block_size) }
+
+    /// Borrows self, outside of the compiler's overview.
+    ///
+    /// Creates the `Local` selected by the features.
+    #[cfg(feature = "bitmap")]
+    pub(crate) unsafe fn create_local(&self, block_size: usize) -> Local { self.create_bitmap_local(block_size) }
+
+    /// Borrows self, outside of the compiler's overview.
+    pub(crate) unsafe fn create_linked_local(&self, block_size: usize) -> local::Local {
         assert!(block_size >= mem::size_of::<BlockForeign>());
 
         let (begin, end) = self.begin_end(block_size);
 
-        Local::new(block_size, begin, end)
+        local::Local::new(block_size, begin, end)
+    }
+
+    /// Borrows self, outside of the compiler's overview.
+    pub(crate) unsafe fn create_bitmap_local(&self, block_size: usize) -> BitmapLocal {
+        assert!(block_size >= mem::size_of::<BlockForeign>());
+
+        let (begin, end) = self.begin_end(block_size);
+        let bitmap = NonNull::from(&self.bitmap).cast();
+
+        BitmapLocal::new(block_size, bitmap, begin, end)
     }
 
     /// Creates a `BlockForeignList` containing the specified range of cells.
     ///
     /// #   Safety
     ///
-    /// -   `local` should have been created from this instance.
-    /// -   The cells should not _also_ be available through `local.next`.
-    pub(crate) unsafe fn create_foreign_list(&self, local: &Local, blocks: Range<usize>) -> BlockForeignList {
+    /// -   The local should have been created from this instance, with `block_size`.
+    /// -   The cells should not _also_ be available through the local.
+    pub(crate) unsafe fn create_foreign_list(&self, block_size: usize, blocks: Range<usize>) -> BlockForeignList {
         assert!(blocks.start <= blocks.end);
 
-        let block_size = local.block_size();
-
         let (begin, end) = self.begin_end(block_size);
-        assert_eq!(end, local.end());
         assert!(blocks.end <= (end.as_ptr() as usize - begin.as_ptr() as usize) / block_size);
 
         let list = BlockForeignList::default();
@@ -63,10 +86,12 @@ impl BlockStore {
     ///
     /// #   Safety
     ///
-    /// -   `local` should have been created from this instance.
-    /// -   The blocks should not _also_ be available through `local.next`.
-    pub(crate) unsafe fn create_foreign_stack(&self, local: &Local, blocks: Range<usize>) -> NonNull<AtomicBlockForeign> {
-        let list = self.create_foreign_list(local, blocks);
+    /// -   The local should have been created from this instance, with `block_size`.
+    /// -   The blocks should not _also_ be available through the local.
+    pub(crate) unsafe fn create_foreign_stack(&self, block_size: usize, blocks: Range<usize>)
+        -> NonNull<AtomicBlockForeign>
+    {
+        let list = self.create_foreign_list(block_size, blocks);
 
         let block = AtomicBlockForeignList::default();
         block.extend(&list);
@@ -76,8 +101,8 @@ impl BlockStore {
 
     //  Internal: Compute begin and end for a given `block_size`.
     unsafe fn begin_end(&self, block_size: usize) -> (NonNull<u8>, NonNull<u8>) {
-        let begin = self as *const Self as *mut Self as *mut u8;
-        let end = begin.add(mem::size_of::<Self>());
+        let begin = &self.blocks as *const _ as *mut u8;
+        let end = begin.add(mem::size_of_val(&self.blocks));
 
         let number_elements = (end as usize - begin as usize) / block_size;
         let begin = end.sub(number_elements * block_size);
@@ -89,7 +114,7 @@ impl BlockStore {
 impl Default for BlockStore {
     fn default() -> Self {
         let result: Self = unsafe { mem::zeroed() };
-        assert_eq!(Self::CAPACITY, result.0.len());
+        assert_eq!(Self::CAPACITY, result.blocks.len());
 
         result
     }
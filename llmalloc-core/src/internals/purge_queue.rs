@@ -0,0 +1,177 @@
+//! Purge Queue
+//!
+//! Forcibly purging a range of memory costs a system call on most platforms, hence purging the retained blocks one at
+//! a time costs one system call per block. The purge queue instead accumulates the ranges to be purged, and hands them
+//! over to the `Platform` in batches, see `Platform::purge_forced_batch`, which may purge a whole batch at once.
+//!
+//! Each range is queued along with a token, released once the range is purged, so that the memory stays claimed until
+//! then.
+
+use core::ptr::NonNull;
+
+use crate::Platform;
+
+/// Queue of ranges of memory to be forcibly purged together.
+///
+/// The queue is flushed when full, when flushed explicitly, and when dropped.
+pub(crate) struct PurgeQueue<'a, P, T, F>
+    where
+        P: Platform,
+        T: Copy,
+        F: FnMut(T),
+{
+    platform: &'a P,
+    release: F,
+    ranges: [(NonNull<u8>, usize); CAPACITY],
+    tokens: [Option<T>; CAPACITY],
+    length: usize,
+}
+
+impl<'a, P, T, F> PurgeQueue<'a, P, T, F>
+    where
+        P: Platform,
+        T: Copy,
+        F: FnMut(T),
+{
+    /// Creates an empty instance, purging through `platform`, and invoking `release` on each token once purged.
+    pub(crate) fn new(platform: &'a P, release: F) -> Self {
+        Self { platform, release, ranges: [(NonNull::dangling(), 0); CAPACITY], tokens: [None; CAPACITY], length: 0 }
+    }
+
+    /// Queues the `size` bytes at `pointer`, along with `token`, flushing the queue if it is then full.
+    ///
+    /// Returns the number of bytes purged by the flush, if any.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the range meets the requirements of `Platform::purge_forced`.
+    /// -   Assumes that the range remains exclusively owned until `token` is released.
+    pub(crate) unsafe fn push(&mut self, pointer: NonNull<u8>, size: usize, token: T) -> usize {
+        debug_assert!(self.length < CAPACITY);
+
+        self.ranges[self.length] = (pointer, size);
+        self.tokens[self.length] = Some(token);
+        self.length += 1;
+
+        if self.length < CAPACITY { 0 } else { self.flush() }
+    }
+
+    /// Purges the ranges queued, if any, then releases their tokens.
+    ///
+    /// Returns the number of bytes purged.
+    pub(crate) fn flush(&mut self) -> usize {
+        if self.length == 0 {
+            return 0;
+        }
+
+        let ranges = &self.ranges[..self.length];
+
+        //  Safety:
+        //  -   Each range meets the requirements of `purge_forced`, as per `push`.
+        unsafe { self.platform.purge_forced_batch(ranges) };
+
+        let purged = ranges.iter().map(|range| range.1).sum();
+
+        for token in &mut self.tokens[..self.length] {
+            if let Some(token) = token.take() {
+                (self.release)(token);
+            }
+        }
+
+        self.length = 0;
+
+        purged
+    }
+}
+
+impl<'a, P, T, F> Drop for PurgeQueue<'a, P, T, F>
+    where
+        P: Platform,
+        T: Copy,
+        F: FnMut(T),
+{
+    fn drop(&mut self) { self.flush(); }
+}
+
+//
+//  Implementation Details
+//
+
+//  The number of ranges purged together, at most.
+const CAPACITY: usize = 16;
+
+#[cfg(test)]
+mod tests {
+
+use core::{
+    alloc::Layout,
+    cell::Cell,
+};
+
+use super::*;
+
+#[derive(Default)]
+struct TestPlatform {
+    //  Number of batches, and of bytes, purged.
+    batches: Cell<usize>,
+    purged: Cell<usize>,
+}
+
+impl Platform for TestPlatform {
+    unsafe fn allocate(&self, _layout: Layout) -> Option<NonNull<u8>> { None }
+
+    unsafe fn deallocate(&self, _pointer: NonNull<u8>, _layout: Layout) {}
+
+    unsafe fn purge_forced_batch(&self, ranges: &[(NonNull<u8>, usize)]) {
+        self.batches.set(self.batches.get() + 1);
+        self.purged.set(self.purged.get() + ranges.iter().map(|range| range.1).sum::<usize>());
+    }
+}
+
+#[test]
+fn purge_queue_push_flush() {
+    let platform = TestPlatform::default();
+    let released = Cell::new(0);
+
+    let mut queue = PurgeQueue::new(&platform, |token: usize| released.set(released.get() + token));
+
+    //  Flushed once full.
+    for _ in 0..(CAPACITY - 1) {
+        assert_eq!(0, unsafe { queue.push(NonNull::dangling(), 4, 1) });
+    }
+
+    assert_eq!(0, platform.batches.get());
+    assert_eq!(0, released.get());
+
+    assert_eq!(4 * CAPACITY, unsafe { queue.push(NonNull::dangling(), 4, 1) });
+
+    assert_eq!(1, platform.batches.get());
+    assert_eq!(CAPACITY, released.get());
+
+    //  Flushed explicitly, unless empty.
+    assert_eq!(0, queue.flush());
+    assert_eq!(1, platform.batches.get());
+
+    assert_eq!(0, unsafe { queue.push(NonNull::dangling(), 8, 2) });
+    assert_eq!(8, queue.flush());
+
+    assert_eq!(2, platform.batches.get());
+    assert_eq!(CAPACITY + 2, released.get());
+}
+
+#[test]
+fn purge_queue_drop() {
+    let platform = TestPlatform::default();
+    let released = Cell::new(0);
+
+    {
+        let mut queue = PurgeQueue::new(&platform, |token: usize| released.set(released.get() + token));
+
+        assert_eq!(0, unsafe { queue.push(NonNull::dangling(), 4, 3) });
+    }
+
+    assert_eq!((1, 4), (platform.batches.get(), platform.purged.get()));
+    assert_eq!(3, released.get());
+}
+
+} // mod tests
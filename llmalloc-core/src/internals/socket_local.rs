@@ -11,7 +11,18 @@
 //!
 //! The name comes from the socket in which a CPU is plugged in, as a recommendation to use one instance of SocketLocal
 //! for each socket.
+//!
+//! Normal allocations deallocated by a thread of another SocketLocal are batched in the foreign lists of that thread,
+//! then pushed, a batch at a time, onto the inbound queue of the owning SocketLocal, rather than onto their LargePage,
+//! so that the remote thread touches a single cache line of the owning socket, instead of the cache lines of each
+//! LargePage. The inbound queue is drained by the owning SocketLocal when one of its threads requires a LargePage, is
+//! flushed or is released.
+//!
+//! A slice of the LargePages of each class size may be reserved for Critical threads: those reserved pages are only
+//! handed out to Critical threads, once no other LargePage is available and no fresh one can be allocated, so that
+//! Normal threads exhausting the memory cannot starve the Critical ones.
 
+mod critical_reserve;
 mod huge_pages_manager;
 mod thread_locals_manager;
 
@@ -20,25 +31,30 @@ mod test;
 
 use core::{
     alloc::Layout,
+    cmp,
     mem,
     num,
     ptr::{self, NonNull},
+    ops,
     slice,
 };
 
-use crate::{Category, ClassSize, Configuration, Platform, PowerOf2, Properties};
+use crate::{Category, ClassSize, Configuration, Criticality, Platform, PowerOf2, Properties, SizeHistogram, Statistics};
 use crate::{
     internals::{
         atomic_stack::AtomicStack,
-        blocks::{BlockForeign, BlockForeignList},
+        blocks::{AtomicBlockForeignList, BlockForeign, BlockForeignList},
         huge_allocator::HugeAllocator,
         huge_page::HugePage,
         large_page::LargePage,
+        statistics::AtomicStatistics,
+        sync::{AtomicPtr, AtomicUsize, Ordering},
         thread_local::{ThreadLocal},
     },
     utils,
 };
 
+use critical_reserve::CriticalReserve;
 use huge_pages_manager::HugePagesManager;
 use thread_locals_manager::ThreadLocalsManager;
 
@@ -64,6 +80,14 @@ pub(crate) struct SocketLocal<'a, C, P> {
     thread_locals: ThreadLocalsManager<C>,
     //  Huge (unmanaged) allocations, directly allocated/deallocated by the OS.
     huge_allocator: &'a HugeAllocator<C, P>,
+    //  Statistics of the operations performed without a ThreadLocal.
+    statistics: AtomicStatistics,
+    //  Normal allocations deallocated by threads of other sockets, pending their return to their LargePage.
+    inbound: Inbound,
+    //  Number of Normal allocations deallocated by threads of other sockets.
+    remote_deallocations: AtomicUsize,
+    //  LargePages reserved for Critical threads, allocated within a LargePage on first use.
+    critical: AtomicPtr<CriticalReserve>,
 }
 
 impl<'a, C, P> SocketLocal<'a, C, P>
@@ -140,6 +164,84 @@ where
         self.huge_pages.reserve(target, self.as_owner(), self.platform())
     }
 
+    /// Attempts to ensure that `pages_per_class` LargePages of each Normal class size are reserved for Critical threads.
+    ///
+    /// Returns the minimum, across class sizes, of the number of reserved pages and `pages_per_class`.
+    ///
+    /// Lowering the number of pages returns the reserved pages in excess to the socket.
+    pub(crate) fn reserve_critical(&self, pages_per_class: usize) -> usize {
+        let critical = match self.critical_or_create() {
+            Some(critical) => critical,
+            None => return 0,
+        };
+
+        critical.set_target(pages_per_class);
+
+        let number_classes = Self::number_normal_classes();
+        let mut minimum = pages_per_class;
+
+        for class_size in (0..number_classes).map(ClassSize::new) {
+            //  Safety:
+            //  -   `class_size` is within bounds, as a Normal class size.
+            unsafe {
+                while critical.count(class_size) > pages_per_class {
+                    match critical.pop(class_size) {
+                        Some(page) => self.large_pages.get_unchecked(class_size.value()).push(&mut *page.as_ptr()),
+                        None => break,
+                    }
+                }
+
+                while critical.count(class_size) < pages_per_class {
+                    let page = match self.allocate_fresh_large_page(class_size, false) {
+                        Some(page) => page,
+                        None => break,
+                    };
+
+                    if let Some(page) = critical.offer(page) {
+                        self.large_pages.get_unchecked(class_size.value()).push(&mut *page.as_ptr());
+                        break;
+                    }
+                }
+
+                minimum = cmp::min(minimum, critical.count(class_size));
+            }
+        }
+
+        minimum
+    }
+
+    /// Attempts to ensure that the next `count` allocations of `layout`, by `thread_local`, take the fast path.
+    ///
+    /// Returns the minimum of the number of such allocations and `count`, which is 0 unless `layout` is a Normal
+    /// allocation, as only those are cached by `thread_local`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `thread_local` is not concurrently accessed by another thread.
+    /// -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
+    pub(crate) unsafe fn reserve_ahead(&self, thread_local: &ThreadLocal<C>, layout: Layout, count: usize) -> usize {
+        debug_assert!(Self::is_valid_layout(layout));
+
+        if Properties::<C>::category_of_size(layout.size()) != Category::Normal {
+            return 0;
+        }
+
+        //  Safety:
+        //  -   `layout.size()` is assumed not to be zero.
+        let size = num::NonZeroUsize::new_unchecked(layout.size());
+
+        let class_size = ClassSize::from_size(size);
+        let criticality = thread_local.criticality();
+
+        //  Safety:
+        //  -   `thread_local` is assumed not be accessed concurrently from another thread.
+        thread_local.reserve_ahead(
+            class_size,
+            count,
+            |class_size| self.allocate_large_page(class_size, criticality, false),
+            |page| Self::catch_large_page(page))
+    }
+
     /// Deallocates all HugePagesManager allocated by the socket.
     ///
     /// This may involve deallocating the memory used by the socket itself, after which it can no longer be used.
@@ -152,6 +254,94 @@ where
         self.huge_pages.close(self.as_owner(), self.platform());
     }
 
+    /// Returns the statistics of the socket, including those of all its `ThreadLocal`.
+    pub(crate) fn statistics(&self) -> Statistics {
+        let mut statistics = self.statistics.snapshot();
+
+        self.thread_locals.accumulate_statistics(&mut statistics);
+
+        statistics
+    }
+
+    /// Returns the number of Normal allocations of the socket deallocated by threads of other sockets.
+    pub(crate) fn remote_deallocations(&self) -> usize { self.remote_deallocations.load(Ordering::Relaxed) }
+
+    /// Returns whether `ptr` is a Normal or Large allocation of the socket, hosted in one of its `HugePage`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `ptr` is a value allocated by an instance of `Self`, and the same underlying `Platform`.
+    pub(crate) unsafe fn owns(&self, ptr: NonNull<u8>) -> bool {
+        //  Huge allocations are owned by the `HugeAllocator`, rather than by any socket.
+        if Properties::<C>::category_of_pointer(ptr) == Category::Huge {
+            return false;
+        }
+
+        //  Safety:
+        //  -   `ptr` is strictly within a `HugePage`, not being a Huge allocation.
+        let page = HugePage::from_raw::<C>(ptr);
+
+        //  Safety:
+        //  -   `page` is not null.
+        page.as_ref().owner() == self.as_owner()
+    }
+
+    /// Returns the usable size of the block located at `ptr`, that is the size of its class for a Normal allocation,
+    /// or of its pages otherwise.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `ptr` is a value allocated by an instance of `Self`, and the same underlying `Platform`.
+    /// -   Assumes that `ptr` has not been deallocated since.
+    pub(crate) unsafe fn size_of(&self, ptr: NonNull<u8>) -> usize {
+        match Properties::<C>::category_of_pointer(ptr) {
+            //  Safety:
+            //  -   `ptr` is assumed to belong to a `LargePage`, which is not null.
+            Category::Normal => LargePage::from_raw::<C>(ptr).as_ref().class_size().layout().size(),
+            //  Safety:
+            //  -   `ptr` is strictly within a `HugePage`, which is not null, not being a Huge allocation.
+            Category::Large => HugePage::from_raw::<C>(ptr).as_ref().size_of(ptr),
+            Category::Huge => self.huge_allocator.size_of_huge(ptr),
+        }
+    }
+
+    /// Returns the histogram of the requested sizes of the socket, accumulated over all its `ThreadLocal`.
+    ///
+    /// The histogram is always empty, unless the `histogram` feature is enabled.
+    pub(crate) fn size_histogram(&self) -> SizeHistogram {
+        #[allow(unused_mut)]
+        let mut histogram = SizeHistogram::default();
+
+        #[cfg(feature = "histogram")]
+        self.thread_locals.accumulate_histogram(&mut histogram);
+
+        histogram
+    }
+
+    /// Invokes `f` with the address of each `HugePage` currently allocated by the socket.
+    pub(crate) fn for_each_huge_page<F>(&self, mut f: F)
+        where
+            F: FnMut(NonNull<u8>),
+    {
+        self.huge_pages.for_each(|page| f(page.cast()));
+    }
+
+    /// Returns the allocations pending on the inbound queue to their LargePage, returning whether any was pending.
+    ///
+    /// The inbound queue is stolen atomically, hence it may be drained from any thread, concurrently with the threads
+    /// of the socket.
+    pub(crate) fn drain(&self) -> bool {
+        if self.inbound.load().is_none() {
+            return false;
+        }
+
+        //  Safety:
+        //  -   The blocks of the inbound queue are exclusively accessed once stolen.
+        unsafe { self.drain_inbound() };
+
+        true
+    }
+
     /// Attempts to acquire a `ThreadLocal` from within the buffer area of the first HugePage.
     ///
     /// Returns a valid pointer to `ThreadLocal` if successful, and None otherwise.
@@ -161,13 +351,18 @@ where
 
     /// Releases a `ThreadLocal`.
     ///
+    /// The LargePages cached by the `ThreadLocal`, and the allocations it deallocated to foreign LargePages, are
+    /// donated back to `self`, as are the allocations pending on the inbound queue, so that the memory cached by
+    /// short-lived threads is available to the other threads rather than stranded.
+    ///
     /// #   Safety
     ///
     /// -   Assumes that the `ThreadLocal` comes from `self`.
     pub(crate) unsafe fn release_thread_local(&self, thread_local: NonNull<ThreadLocal<C>>) {
         //  Safety:
         //  -   `thread_local` is not null.
-        thread_local.as_ref().flush(|page| Self::catch_large_page(page));
+        //  -   `thread_local` is assumed to come from `self`.
+        self.flush_thread_local(thread_local.as_ref());
 
         //  Safety:
         //  -   `thread_local` points to valid memory.
@@ -175,6 +370,25 @@ where
         self.thread_locals.release(thread_local)
     }
 
+    /// Flushes a `ThreadLocal`, which remains acquired.
+    ///
+    /// The LargePages cached by the `ThreadLocal`, and the allocations it deallocated to foreign LargePages, are
+    /// donated back to `self`, as are the allocations pending on the inbound queue, as on release; the `ThreadLocal`
+    /// then fetches pages anew from `self` on its next allocations.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the `ThreadLocal` comes from `self`.
+    /// -   Assumes that the `ThreadLocal` is not concurrently accessed by another thread.
+    pub(crate) unsafe fn flush_thread_local(&self, thread_local: &ThreadLocal<C>) {
+        thread_local.flush(|page| Self::catch_large_page(page), |page, list| Self::push_inbound(page, list));
+
+        //  The pending deallocations of threads of other sockets may free up the pages just donated.
+        if self.inbound.load().is_some() {
+            self.drain_inbound();
+        }
+    }
+
     /// Allocates a fresh block of memory as per the specified layout.
     ///
     /// May return a null pointer if the allocation request cannot be satisfied.
@@ -190,13 +404,84 @@ where
     /// -   `layout` is valid, as per `Self::is_valid_layout`.
     #[inline(always)]
     pub(crate) unsafe fn allocate(&self, thread_local: &ThreadLocal<C>, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocate_impl(thread_local, layout, false)
+    }
+
+    /// Allocates a fresh block of memory as per the specified layout, with a bounded latency.
+    ///
+    /// The block is served from the memory already acquired by the socket only: the `Platform` is never called upon,
+    /// and the deallocations queued by the threads of other sockets are not drained.
+    ///
+    /// #   Safety
+    ///
+    /// `allocate_bounded` assumes that:
+    /// -   `thread_local` is not concurrently accessed by another thread.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub(crate) unsafe fn allocate_bounded(&self, thread_local: &ThreadLocal<C>, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocate_impl(thread_local, layout, true)
+    }
+
+    /// Allocates a fresh block of memory, as per `layout`, as a Huge allocation regardless of its size.
+    ///
+    /// #   Safety
+    ///
+    /// `allocate_direct` assumes that:
+    /// -   `thread_local` is not concurrently accessed by another thread.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub(crate) unsafe fn allocate_direct(&self, thread_local: &ThreadLocal<C>, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocate_direct_impl(thread_local, layout, false)
+    }
+
+    /// Allocates a fresh block of memory, as per `layout`, as a Huge allocation regardless of its size, with a bounded
+    /// latency.
+    ///
+    /// The block is served from the Huge allocations retained by the socket only: the `Platform` is never called upon.
+    ///
+    /// #   Safety
+    ///
+    /// `allocate_direct_bounded` assumes that:
+    /// -   `thread_local` is not concurrently accessed by another thread.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub(crate) unsafe fn allocate_direct_bounded(&self, thread_local: &ThreadLocal<C>, layout: Layout)
+        -> Option<NonNull<u8>>
+    {
+        self.allocate_direct_impl(thread_local, layout, true)
+    }
+
+    /// Reallocates a Huge allocation, to fit `layout`, without copying its content.
+    ///
+    /// Returns the pointer to the reallocated block, or None if it cannot be resized in this fashion, in which case it
+    /// is left untouched.
+    ///
+    /// #   Safety
+    ///
+    /// On success, the caller should no longer reference the memory through `ptr`, unless returned.
+    ///
+    /// `reallocate_huge` assumes that:
+    /// -   `thread_local` is not concurrently accessed by another thread.
+    /// -   `ptr` is a Huge allocation allocated by an instance of `Self`, and the same underlying `Platform`.
+    /// -   `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub(crate) unsafe fn reallocate_huge(&self, thread_local: &ThreadLocal<C>, ptr: NonNull<u8>, layout: Layout)
+        -> Option<NonNull<u8>>
+    {
         debug_assert!(Self::is_valid_layout(layout));
+        debug_assert!(Properties::<C>::category_of_pointer(ptr) == Category::Huge);
 
-        match Properties::<C>::category_of_size(layout.size()) {
-            Category::Normal => self.allocate_normal(thread_local, layout),
-            Category::Large => self.allocate_large(layout),
-            Category::Huge => self.allocate_huge(layout),
+        let (result, previous) = self.huge_allocator.reallocate_huge(ptr, layout)?;
+
+        let bytes = C::HUGE_PAGE_SIZE.round_up(layout.size());
+
+        //  A resize is accounted as the deallocation of the previous block, and the allocation of the new one.
+        if bytes != previous {
+            thread_local.statistics().record_deallocation(Category::Huge, previous);
+            thread_local.statistics().record_allocation(Category::Huge, bytes);
         }
+
+        Some(result)
     }
 
     /// Deallocates the supplied block of memory.
@@ -210,11 +495,15 @@ where
     /// -   `ptr` is a value allocated by an instance of `Self`, and the same underlying `Platform`.
     #[inline(always)]
     pub(crate) unsafe fn deallocate(&self, thread_local: &ThreadLocal<C>, ptr: NonNull<u8>) {
-        match Properties::<C>::category_of_pointer(ptr) {
+        let category = Properties::<C>::category_of_pointer(ptr);
+
+        let bytes = match category {
             Category::Normal => self.deallocate_normal(thread_local, ptr),
             Category::Large => self.deallocate_large(ptr),
             Category::Huge => self.deallocate_huge(ptr),
-        }
+        };
+
+        thread_local.statistics().record_deallocation(category, bytes);
     }
 
     /// Deallocates the supplied block of memory.
@@ -231,11 +520,15 @@ where
     /// -   `ptr` is a value allocated by an instance of `Self`, and the same underlying `Platform`.
     #[inline(always)]
     pub(crate) unsafe fn deallocate_uncached(&self, ptr: NonNull<u8>) {
-        match Properties::<C>::category_of_pointer(ptr) {
+        let category = Properties::<C>::category_of_pointer(ptr);
+
+        let bytes = match category {
             Category::Normal => self.deallocate_normal_uncached(ptr),
             Category::Large => self.deallocate_large(ptr),
             Category::Huge => self.deallocate_huge(ptr),
-        }
+        };
+
+        self.statistics.add_deallocation(category, bytes);
     }
 
     //  Internal; Creates a new instance of SocketLocal.
@@ -248,8 +541,14 @@ where
     {
         let large_pages = unsafe { mem::zeroed() };
         let huge_pages = HugePagesManager::new(Some(page));
+        let statistics = AtomicStatistics::new();
+        let inbound = Inbound::default();
+        let remote_deallocations = AtomicUsize::new(0);
+        let critical = AtomicPtr::default();
 
-        SocketLocal { large_pages, huge_pages, huge_allocator, thread_locals, }
+        SocketLocal {
+            large_pages, huge_pages, huge_allocator, thread_locals, statistics, inbound, remote_deallocations, critical,
+        }
     }
 
     //  Internal; Returns a reference to the Platform.
@@ -288,14 +587,81 @@ where
         result
     }
 
-    //  Internal; Allocates a Normal allocation.
+    //  Internal; Allocates a fresh block of memory as per the specified layout, never calling upon the `Platform`, nor
+    //  draining the inbound deallocations, if `bounded`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes `thread_local` is not concurrently accessed by another thread.
+    //  -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(always)]
+    unsafe fn allocate_impl(&self, thread_local: &ThreadLocal<C>, layout: Layout, bounded: bool)
+        -> Option<NonNull<u8>>
+    {
+        debug_assert!(Self::is_valid_layout(layout));
+
+        #[cfg(feature = "histogram")]
+        thread_local.histogram().record(layout.size());
+
+        let category = Properties::<C>::category_of_size(layout.size());
+
+        let result = match category {
+            Category::Normal => self.allocate_normal(thread_local, layout, bounded),
+            Category::Large if bounded => self.huge_pages.allocate_large_cached(layout),
+            Category::Large => self.allocate_large(layout),
+            Category::Huge if bounded => self.huge_allocator.allocate_huge_cached(layout),
+            Category::Huge => self.allocate_huge(layout),
+        };
+
+        if result.is_some() {
+            let bytes = Properties::<C>::layout_of_size(layout.size()).size();
+            thread_local.statistics().record_allocation(category, bytes);
+        }
+
+        result
+    }
+
+    //  Internal; Allocates a fresh block of memory, as per `layout`, as a Huge allocation regardless of its size, never
+    //  calling upon the `Platform` if `bounded`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes `thread_local` is not concurrently accessed by another thread.
+    //  -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(always)]
+    unsafe fn allocate_direct_impl(&self, thread_local: &ThreadLocal<C>, layout: Layout, bounded: bool)
+        -> Option<NonNull<u8>>
+    {
+        debug_assert!(Self::is_valid_layout(layout));
+
+        #[cfg(feature = "histogram")]
+        thread_local.histogram().record(layout.size());
+
+        let result = if bounded {
+            self.huge_allocator.allocate_huge_cached(layout)
+        } else {
+            self.allocate_huge(layout)
+        };
+
+        if result.is_some() {
+            let bytes = C::HUGE_PAGE_SIZE.round_up(layout.size());
+            thread_local.statistics().record_allocation(Category::Huge, bytes);
+        }
+
+        result
+    }
+
+    //  Internal; Allocates a Normal allocation, never calling upon the `Platform`, nor draining the inbound
+    //  deallocations, if `bounded`.
     //
     //  #   Safety
     //
     //  -   Assumes `thread_local` is not concurrently accessed by another thread.
     //  -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
     #[inline(always)]
-    unsafe fn allocate_normal(&self, thread_local: &ThreadLocal<C>, layout: Layout) -> Option<NonNull<u8>> {
+    unsafe fn allocate_normal(&self, thread_local: &ThreadLocal<C>, layout: Layout, bounded: bool)
+        -> Option<NonNull<u8>>
+    {
         debug_assert!(Self::is_valid_layout(layout));
 
         //  Safety:
@@ -303,36 +669,138 @@ where
         let size = num::NonZeroUsize::new_unchecked(layout.size());
 
         let class_size = ClassSize::from_size(size);
+        let criticality = thread_local.criticality();
 
         //  Safety:
         //  -   `thread_local` is assumed not be accessed concurrently from another thread.
-        thread_local.allocate(class_size, |class_size| self.allocate_large_page(class_size))
+        thread_local.allocate(class_size, |class_size| self.allocate_large_page(class_size, criticality, bounded))
     }
 
-    //  Internal; Deallocates a Large allocation.
+    //  Internal; Deallocates a Normal allocation.
+    //
+    //  Returns the number of bytes deallocated.
     //
     //  #   Safety
     //
     //  -   Assumes `thread_local` is not concurrently accessed by another thread.
     //  -   Assumes that `ptr` is a Normal allocation allocated by an instance of `Self`.
     #[inline(never)]
-    unsafe fn deallocate_normal(&self, thread_local: &ThreadLocal<C>, ptr: NonNull<u8>) {
+    unsafe fn deallocate_normal(&self, thread_local: &ThreadLocal<C>, ptr: NonNull<u8>) -> usize {
         debug_assert!((ptr.as_ptr() as usize) % C::LARGE_PAGE_SIZE != 0);
 
+        //  Safety:
+        //  -   `ptr` is assumed to belong to a `LargePage`.
+        let page = LargePage::from_raw::<C>(ptr);
+
+        //  Safety:
+        //  -   `page` is not null.
+        let large_page = page.as_ref();
+
+        let bytes = large_page.class_size().layout().size();
+
+        //  The allocations of other sockets are batched by `thread_local`, as those of its foreign pages, each batch
+        //  being then pushed onto the inbound queue of their socket.
+        //
         //  Safety:
         //  -   `thread_local` is assumed not be accessed concurrently from another thread.
-        thread_local.deallocate(ptr, |page| Self::catch_large_page(page));
+        thread_local.deallocate(ptr, |page| Self::catch_large_page(page), |page, list| Self::push_inbound(page, list));
+
+        bytes
     }
 
-    //  Internal; Deallocates a Large allocation.
+    //  Internal; Pushes a batch of Normal allocations, deallocated by a thread of another socket, onto the inbound
+    //  queue of the socket owning their LargePage.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `foreign_list` is not empty.
+    //  -   Assumes that the blocks of `foreign_list` belong to `large_page`, owned by an instance of `Self`.
+    #[inline(never)]
+    unsafe fn push_inbound(large_page: &LargePage, foreign_list: &BlockForeignList) {
+        let owner = large_page.owner();
+        debug_assert!(!owner.is_null());
+
+        //  Safety:
+        //  -   `owner` is not null.
+        //  -   `owner` points to an instance of `Self`.
+        let socket = &*(owner as *mut Self);
+
+        let length = foreign_list.len();
+
+        //  Safety:
+        //  -   `foreign_list` is assumed not to be empty.
+        socket.inbound.extend(foreign_list);
+
+        socket.remote_deallocations.fetch_add(length, Ordering::Relaxed);
+    }
+
+    //  Internal; Returns the Normal allocations of the inbound queue to their LargePage.
+    //
+    //  Consecutive allocations belonging to the same LargePage are returned together.
+    #[inline(never)]
+    unsafe fn drain_inbound(&self) {
+        let mut next = self.inbound.steal();
+
+        let foreign_list = BlockForeignList::default();
+
+        while let Some(block) = next {
+            //  Safety:
+            //  -   `block` is not null.
+            //  -   The access to `block`, and all tail blocks, is exclusive since stolen.
+            next = block.as_ref().next.load();
+
+            //  Safety:
+            //  -   `block` points to memory that is no longer in use.
+            //  -   `block` points to a sufficiently large, and correctly aligned, memory area.
+            let block = BlockForeign::initialize(block.cast());
+
+            if !foreign_list.is_compatible::<C>(block) {
+                Self::refill_foreign(&foreign_list);
+            }
+
+            foreign_list.push(block);
+        }
+
+        if !foreign_list.is_empty() {
+            Self::refill_foreign(&foreign_list);
+        }
+    }
+
+    //  Internal; Returns a list of Normal allocations to their LargePage, catching it if adrift.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `foreign_list` is not empty.
+    //  -   Assumes that all the blocks of `foreign_list` belong to the same `LargePage`.
+    unsafe fn refill_foreign(foreign_list: &BlockForeignList) {
+        debug_assert!(!foreign_list.is_empty());
+
+        if let Some(head) = foreign_list.head() {
+            //  Safety:
+            //  -   `head` is assumed to belong to a `LargePage`.
+            let page = LargePage::from_raw::<C>(head.cast());
+
+            //  Safety:
+            //  -   `page` is not null.
+            page.as_ref().refill_foreign(foreign_list, |page| Self::catch_large_page(page));
+        }
+
+        debug_assert!(foreign_list.is_empty());
+    }
+
+    //  Internal; Deallocates a Normal allocation, without caching.
+    //
+    //  Returns the number of bytes deallocated.
     //
     //  #   Safety
     //
     //  -   Assumes that `ptr` is a Normal allocation allocated by an instance of `Self`.
     #[inline(never)]
-    unsafe fn deallocate_normal_uncached(&self, ptr: NonNull<u8>) {
+    unsafe fn deallocate_normal_uncached(&self, ptr: NonNull<u8>) -> usize {
         debug_assert!((ptr.as_ptr() as usize) % C::LARGE_PAGE_SIZE != 0);
 
+        let bytes = Self::normal_size_of(ptr);
+
         //  Safety:
         //  -   `ptr` is assumed to point to memory that is no longer in use.
         //  -   `ptr` is assumed to point to a sufficiently large memory area.
@@ -352,6 +820,24 @@ where
 
         large_page.refill_foreign(&foreign_list, |page| Self::catch_large_page(page));
         debug_assert!(foreign_list.is_empty());
+
+        bytes
+    }
+
+    //  Internal; Returns the size of a Normal allocation.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `ptr` is a Normal allocation, belonging to a `LargePage`.
+    #[inline(always)]
+    unsafe fn normal_size_of(ptr: NonNull<u8>) -> usize {
+        //  Safety:
+        //  -   `ptr` is assumed to belong to a `LargePage`.
+        let page = LargePage::from_raw::<C>(ptr);
+
+        //  Safety:
+        //  -   `page` is not null.
+        page.as_ref().class_size().layout().size()
     }
 
     //  Internal; Allocates a Large allocation.
@@ -365,10 +851,14 @@ where
 
     //  Internal; Deallocates a Large allocation.
     //
+    //  Returns the number of bytes deallocated.
+    //
     //  #   Safety
     //
     //  -   Assumes that `ptr` is a Large allocation allocated by an instance of `Self`.
-    unsafe fn deallocate_large(&self, ptr: NonNull<u8>) { self.huge_pages.deallocate_large(ptr); }
+    unsafe fn deallocate_large(&self, ptr: NonNull<u8>) -> usize {
+        self.huge_pages.deallocate_large(ptr, self.platform())
+    }
 
     // Internal;  Allocates a Huge allocation.
     //
@@ -383,24 +873,86 @@ where
 
     //  Internal; Deallocates a Huge allocation.
     //
+    //  Returns the number of bytes deallocated.
+    //
     //  #   Safety
     //
     //  -   Assumes that `ptr` is a Huge allocation allocated by an instance of `Self` sharing the same manager.
-    unsafe fn deallocate_huge(&self, ptr: NonNull<u8>) { self.huge_allocator.deallocate_huge(ptr); }
+    unsafe fn deallocate_huge(&self, ptr: NonNull<u8>) -> usize { self.huge_allocator.deallocate_huge(ptr) }
 
     //  Internal; Returns the address of `self`.
     fn as_owner(&self) -> *mut () { self as *const Self as *mut Self as *mut () }
 
-    //  Internal; Allocates a LargePage, as defined by C.
-    //
-    //  #   Safety
-    //
-    //  -   Assumes that `class_size` is in bounds.
-    //  -   Assumes that `C::LARGE_PAGE_SIZE` is large enough for a `LargePage`.
+    //  Internal; Returns the reserve of Critical threads, if created.
+    fn critical(&self) -> Option<&CriticalReserve> {
+        //  Safety:
+        //  -   The pointer, if not null, points to an initialized instance, living as long as `self`.
+        unsafe { self.critical.load(Ordering::Acquire).as_ref() }
+    }
+
+    //  Internal; Returns the reserve of Critical threads, creating it if necessary.
+    #[cold]
+    fn critical_or_create(&self) -> Option<&CriticalReserve> {
+        if let Some(critical) = self.critical() {
+            return Some(critical);
+        }
+
+        debug_assert!(mem::size_of::<CriticalReserve>() <= C::LARGE_PAGE_SIZE.value());
+
+        let size = C::LARGE_PAGE_SIZE.value();
+
+        //  Safety:
+        //  -   `size` is not zero.
+        //  -   `size` is a power of 2.
+        let layout = unsafe { Layout::from_size_align_unchecked(size, size) };
+        debug_assert!(Self::is_valid_layout(layout));
+
+        //  Safety:
+        //  -   `layout` is valid.
+        let place = unsafe { self.allocate_large(layout) }?;
+        let critical: NonNull<CriticalReserve> = place.cast();
+
+        //  Safety:
+        //  -   `place` is valid for writes, sufficiently sized and sufficiently aligned.
+        unsafe { ptr::write(critical.as_ptr(), CriticalReserve::new()) };
+
+        //  Let's race to see who gets to install the reserve; if this thread loses, free the superfluous one.
+        match self.critical.compare_exchange(ptr::null_mut(), critical.as_ptr(), Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => self.critical(),
+            Err(_) => {
+                //  Safety:
+                //  -   `place` was allocated by `self`, just above, and was never shared.
+                unsafe { self.deallocate_large(place) };
+                self.critical()
+            },
+        }
+    }
+
+    //  Internal; Returns the number of class sizes of Normal allocations.
+    fn number_normal_classes() -> usize {
+        let threshold = Properties::<C>::normal_threshold().value();
+
+        Properties::<C>::class_size_of_size(threshold).map_or(0, |class_size| class_size.value() + 1)
+    }
+
+    //  Internal; Allocates a LargePage, as defined by C, never calling upon the `Platform`, nor draining the inbound
+    //  deallocations, if `bounded`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `class_size` is in bounds.
+    //  -   Assumes that `C::LARGE_PAGE_SIZE` is large enough for a `LargePage`.
     #[inline(never)]
-    unsafe fn allocate_large_page(&self, class_size: ClassSize) -> Option<NonNull<LargePage>> {
+    unsafe fn allocate_large_page(&self, class_size: ClassSize, criticality: Criticality, bounded: bool)
+        -> Option<NonNull<LargePage>>
+    {
         debug_assert!(class_size.value() < self.large_pages.len());
 
+        //  The allocations deallocated by threads of other sockets may free up existing pages.
+        if !bounded && self.inbound.load().is_some() {
+            self.drain_inbound();
+        }
+
         //  Fast Path: locate an existing one!
 
         //  Safety:
@@ -412,6 +964,24 @@ where
         }
 
         //  Slow Path: allocate a fresh one!
+        let large_page = self.allocate_fresh_large_page(class_size, bounded);
+
+        if large_page.is_some() || criticality != Criticality::Critical {
+            return large_page;
+        }
+
+        //  Last Resort: dip into the reserve of Critical threads.
+        self.critical().and_then(|critical| critical.pop(class_size))
+    }
+
+    //  Internal; Allocates a fresh LargePage, for a given ClassSize, from the existing HugePages only if `bounded`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `class_size` is in bounds.
+    //  -   Assumes that `C::LARGE_PAGE_SIZE` is large enough for a `LargePage`.
+    #[inline(never)]
+    unsafe fn allocate_fresh_large_page(&self, class_size: ClassSize, bounded: bool) -> Option<NonNull<LargePage>> {
         let size = C::LARGE_PAGE_SIZE.value();
 
         //  Safety:
@@ -422,7 +992,13 @@ where
 
         //  Safety:
         //  -   `layout` is valid.
-        let large_page = self.allocate_large(layout)?;
+        let large_page = if bounded {
+            self.huge_pages.allocate_large_cached(layout)
+        } else {
+            self.allocate_large(layout)
+        };
+
+        let large_page = large_page?;
 
         //  Safety:
         //  -   `large_page` is not null.
@@ -452,16 +1028,40 @@ where
         let socket = &*(owner as *mut Self);
         debug_assert!(class_size.value() < socket.large_pages.len());
 
+        //  The reserve of Critical threads, if any, is replenished first.
+        let page = match socket.critical() {
+            Some(critical) => match critical.offer(page) {
+                Some(page) => page,
+                None => return,
+            },
+            None => page,
+        };
+
         //  Safety:
         //  -   `class_size` is within bounds.
         socket.large_pages.get_unchecked(class_size.value()).push(&mut *page.as_ptr());
     }
 }
 
+//
+//  Implementation Details
+//
+
+//  Inbound queue, aligned on its own cache lines, as it is written to by the threads of other sockets.
+#[repr(align(128))]
+#[derive(Default)]
+struct Inbound(AtomicBlockForeignList);
+
+impl ops::Deref for Inbound {
+    type Target = AtomicBlockForeignList;
+
+    fn deref(&self) -> &Self::Target { &self.0 }
+}
+
 #[cfg(test)]
 mod tests {
 
-use crate::Properties;
+use crate::{CategoryStatistics, Properties};
 
 use super::*;
 use super::test::{HugePageStore, TestConfiguration, TestHugeAllocator, TestPlatform};
@@ -476,7 +1076,7 @@ const HUGE_PAGE_LAYOUT: Layout = unsafe { Layout::from_size_align_unchecked(HUGE
 
 #[test]
 fn socket_local_size() {
-    assert_eq!(1152, mem::size_of::<TestSocketLocal<'static>>());
+    assert_eq!(1536, mem::size_of::<TestSocketLocal<'static>>());
 }
 
 #[test]
@@ -502,9 +1102,8 @@ fn socket_local_bootstrap_out_of_memory() {
 #[test]
 fn socket_local_is_valid_layout() {
     fn is_valid_layout(size: usize, align: usize) -> bool {
-        //  Actually, the assumptions do not hold; the test is all about verifying them.
-        let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
-        TestSocketLocal::<'static>::is_valid_layout(layout)
+        //  A 0 or non-power of 2 alignment cannot even be represented by `Layout`, hence is trivially invalid.
+        Layout::from_size_align(size, align).is_ok_and(TestSocketLocal::<'static>::is_valid_layout)
     }
 
     //  Cannot handle 0-sized or 0-aligned allocations.
@@ -596,6 +1195,95 @@ fn socket_local_acquire_release_thread_local() {
     unsafe { socket.release_thread_local(thread_local.unwrap()) };
 }
 
+#[test]
+fn socket_local_statistics() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+
+    //  Allocate one piece of each category.
+    let normal_layout = Layout::from_size_align(1, 1).unwrap();
+    let normal_size = Properties::<TestConfiguration>::layout_of_size(1).size();
+
+    let (normal, large, huge) = unsafe {
+        let thread_local = thread_local.as_ref();
+
+        let normal = socket.allocate(thread_local, normal_layout).unwrap();
+        let large = socket.allocate(thread_local, LARGE_PAGE_LAYOUT).unwrap();
+        let huge = socket.allocate(thread_local, HUGE_PAGE_LAYOUT).unwrap();
+
+        (normal, large, huge)
+    };
+
+    let statistics = socket.statistics();
+
+    assert_eq!(CategoryStatistics { allocations: 1, allocated_bytes: normal_size, ..CategoryStatistics::default() },
+        statistics.normal);
+    assert_eq!(CategoryStatistics { allocations: 1, allocated_bytes: LARGE_PAGE_SIZE, ..CategoryStatistics::default() },
+        statistics.large);
+    assert_eq!(CategoryStatistics { allocations: 1, allocated_bytes: HUGE_PAGE_SIZE, ..CategoryStatistics::default() },
+        statistics.huge);
+
+    //  Statistics survive the release of the thread-local.
+    unsafe { socket.release_thread_local(thread_local) };
+
+    assert_eq!(statistics, socket.statistics());
+
+    //  Deallocate, with and without a thread-local.
+    let thread_local = socket.acquire_thread_local().unwrap();
+
+    unsafe {
+        socket.deallocate(thread_local.as_ref(), normal);
+        socket.deallocate_uncached(large);
+        socket.deallocate(thread_local.as_ref(), huge);
+    }
+
+    let statistics = socket.statistics();
+
+    assert_eq!(3, statistics.total().allocations);
+    assert_eq!(3, statistics.total().deallocations);
+    assert_eq!(0, statistics.total().live_bytes());
+
+    unsafe { socket.release_thread_local(thread_local) };
+}
+
+#[test]
+fn socket_local_size_histogram() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+
+    for size in &[1, 24, 32, LARGE_PAGE_SIZE] {
+        let layout = Layout::from_size_align(*size, 1).unwrap();
+
+        unsafe {
+            let allocation = socket.allocate(thread_local.as_ref(), layout).unwrap();
+            socket.deallocate(thread_local.as_ref(), allocation);
+        }
+    }
+
+    unsafe { socket.release_thread_local(thread_local) };
+
+    let histogram = socket.size_histogram();
+
+    if cfg!(feature = "histogram") {
+        assert_eq!(4, histogram.total());
+        assert_eq!(1, histogram.count(0));
+        assert_eq!(2, histogram.count(5));
+        assert_eq!(1, histogram.count(SizeHistogram::bucket_of(LARGE_PAGE_SIZE)));
+    } else {
+        assert_eq!(SizeHistogram::default(), histogram);
+    }
+}
+
 #[test]
 fn socket_local_allocate_deallocate_huge() {
     let store = HugePageStore::default();
@@ -617,6 +1305,75 @@ fn socket_local_allocate_deallocate_huge() {
     unsafe { socket.deallocate(thread_local, allocation.unwrap()) };
 }
 
+#[test]
+fn socket_local_allocate_direct() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    //  Allocate a large allocation, as a dedicated huge page.
+    let allocation = unsafe { socket.allocate_direct(thread_local, LARGE_PAGE_LAYOUT) };
+
+    assert_ne!(None, allocation);
+    assert_eq!(2, allocator.platform().allocated());
+    assert_eq!(0, allocation.unwrap().as_ptr() as usize % HUGE_PAGE_SIZE);
+
+    assert_eq!(CategoryStatistics { allocations: 1, allocated_bytes: HUGE_PAGE_SIZE, ..CategoryStatistics::default() },
+        socket.statistics().huge);
+
+    //  Deallocate the huge page.
+    unsafe { socket.deallocate(thread_local, allocation.unwrap()) };
+
+    assert_eq!(HUGE_PAGE_SIZE, socket.statistics().huge.deallocated_bytes);
+}
+
+#[test]
+fn socket_local_allocate_bounded() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    let available = allocator.platform().available();
+
+    //  Allocations are carved from the existing HugePage, as long as it has room.
+    assert_ne!(None, unsafe { socket.allocate_bounded(thread_local, LARGE_PAGE_LAYOUT) });
+    assert_eq!(available, allocator.platform().available());
+
+    //  Once the existing HugePages are exhausted, no fresh one is allocated.
+    let normal = Layout::from_size_align(8, 8).unwrap();
+
+    assert_eq!(None, unsafe { socket.allocate_bounded(thread_local, normal) });
+    assert_eq!(available, allocator.platform().available());
+
+    assert_ne!(None, unsafe { socket.allocate(thread_local, normal) });
+    assert_eq!(available - 1, allocator.platform().available());
+
+    //  Huge allocations are only served from the retained ones.
+    assert_eq!(None, unsafe { socket.allocate_bounded(thread_local, HUGE_PAGE_LAYOUT) });
+    assert_eq!(None, unsafe { socket.allocate_direct_bounded(thread_local, LARGE_PAGE_LAYOUT) });
+
+    let huge = unsafe { socket.allocate(thread_local, HUGE_PAGE_LAYOUT) }.unwrap();
+    unsafe { socket.deallocate(thread_local, huge) };
+
+    let available = allocator.platform().available();
+
+    assert_eq!(Some(huge), unsafe { socket.allocate_direct_bounded(thread_local, LARGE_PAGE_LAYOUT) });
+    assert_eq!(available, allocator.platform().available());
+
+    let expected = CategoryStatistics { allocations: 1, allocated_bytes: LARGE_PAGE_SIZE, ..Default::default() };
+    assert_eq!(expected, socket.statistics().large);
+}
+
 #[test]
 fn socket_local_allocate_huge_failure() {
     let store = HugePageStore::default();
@@ -658,6 +1415,32 @@ fn socket_local_allocate_deallocate_large() {
     unsafe { socket.deallocate(thread_local, allocation.unwrap()) };
 }
 
+#[test]
+fn socket_local_size_of() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    //  The usable size is that of the class, or of the pages.
+    let normal = Layout::from_size_align(129, 1).unwrap();
+    let class_size = Properties::<TestConfiguration>::class_size_of_size(129).unwrap().layout().size();
+
+    let layouts = [(normal, class_size), (LARGE_PAGE_LAYOUT, LARGE_PAGE_SIZE), (HUGE_PAGE_LAYOUT, HUGE_PAGE_SIZE)];
+
+    for (layout, usable) in layouts {
+        let allocation = unsafe { socket.allocate(thread_local, layout) }.unwrap();
+
+        assert_eq!(usable, unsafe { socket.size_of(allocation) });
+
+        unsafe { socket.deallocate(thread_local, allocation) };
+    }
+}
+
 #[test]
 fn socket_local_allocate_large_failure() {
     let store = HugePageStore::default();
@@ -703,6 +1486,35 @@ fn socket_local_allocate_deallocate_normal() {
     unsafe { socket.deallocate(thread_local, allocation.unwrap()) };
 }
 
+#[test]
+fn socket_local_reserve_ahead() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    //  Large allocations are not cached by the thread.
+    assert_eq!(0, unsafe { socket.reserve_ahead(thread_local, LARGE_PAGE_LAYOUT, 4) });
+
+    //  Normal allocations are, up to the number of cells of a LargePage.
+    let layout = Layout::from_size_align(32, 8).unwrap();
+
+    assert_eq!(4, unsafe { socket.reserve_ahead(thread_local, layout, 4) });
+
+    //  A fresh LargePage is pulled, requiring a fresh HugePage, but holds no more cells than the cached one.
+    let number_cells = unsafe { socket.reserve_ahead(thread_local, layout, usize::MAX) };
+    assert!(number_cells > 4 && number_cells < LARGE_PAGE_SIZE / 32, "{}", number_cells);
+    assert_eq!(2, allocator.platform().allocated());
+
+    for _ in 0..number_cells {
+        assert_ne!(None, unsafe { socket.allocate_bounded(thread_local, layout) });
+    }
+}
+
 #[test]
 fn socket_local_allocate_normal_failure() {
     let store = HugePageStore::default();
@@ -727,6 +1539,60 @@ fn socket_local_allocate_normal_failure() {
     assert_eq!(None, allocation);
 }
 
+#[test]
+fn socket_local_reserve_critical() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let number_classes = TestSocketLocal::number_normal_classes();
+    assert_eq!(24, number_classes);
+
+    //  The reserve itself occupies the spare LargePage of the first HugePage.
+    assert_eq!(1, socket.reserve_critical(1));
+    assert_eq!(1 + number_classes, allocator.platform().allocated());
+
+    //  Exhaust manager platform.
+    allocator.platform().exhaust(&socket.huge_pages);
+    allocator.platform().shrink(0);
+
+    let layout = Layout::from_size_align(1, 1).unwrap();
+
+    //  A Normal thread cannot dip into the reserve.
+    let thread_local = socket.acquire_thread_local().unwrap();
+
+    assert_eq!(None, unsafe { socket.allocate(thread_local.as_ref(), layout) });
+
+    //  A Critical thread can.
+    unsafe { thread_local.as_ref().set_criticality(Criticality::Critical) };
+
+    let allocation = unsafe { socket.allocate(thread_local.as_ref(), layout) };
+    assert_ne!(None, allocation);
+    assert_eq!(0, unsafe { socket.critical().unwrap().count(ClassSize::new(0)) });
+
+    //  Once released, the page replenishes the reserve.
+    unsafe { socket.deallocate(thread_local.as_ref(), allocation.unwrap()) };
+    unsafe { socket.release_thread_local(thread_local) };
+
+    assert_eq!(1, unsafe { socket.critical().unwrap().count(ClassSize::new(0)) });
+
+    //  Once the reserve is lowered, the pages are available to Normal threads.
+    assert_eq!(0, socket.reserve_critical(0));
+    assert_eq!(0, unsafe { socket.critical().unwrap().count(ClassSize::new(0)) });
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    assert_eq!(Criticality::Normal, thread_local.criticality());
+
+    let allocation = unsafe { socket.allocate(thread_local, layout) };
+    assert_ne!(None, allocation);
+
+    unsafe { socket.deallocate(thread_local, allocation.unwrap()) };
+}
+
 #[test]
 fn socket_local_allocate_deallocate_normal_catch() {
     let store = HugePageStore::default();
@@ -769,4 +1635,257 @@ fn socket_local_allocate_deallocate_normal_catch() {
     assert_ne!(None, further);
 }
 
+#[test]
+fn socket_local_deallocate_normal_inbound() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let remote = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let remote = unsafe { remote.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    let remote_thread_local = remote.acquire_thread_local().unwrap();
+    let remote_thread_local = unsafe { remote_thread_local.as_ref() };
+
+    //  Exhaust platform.
+    allocator.platform().shrink(0);
+
+    //  Determine the largest allocation size still normal.
+    let size = Properties::<TestConfiguration>::normal_threshold().value();
+    let class_size = ClassSize::from_size(num::NonZeroUsize::new(size).unwrap());
+    let layout = Layout::from_size_align(size, 1).unwrap();
+
+    //  There are only 2 allocations on a given LargePage, so exhaust it.
+    let allocations = [
+        unsafe { socket.allocate(thread_local, layout) },
+        unsafe { socket.allocate(thread_local, layout) },
+    ];
+
+    assert_ne!(None, allocations[0]);
+    assert_ne!(None, allocations[1]);
+
+    //  Deallocate 1 of the two allocations from the remote socket, it is queued on the owning socket.
+    unsafe { remote.deallocate(remote_thread_local, allocations[0].unwrap()) };
+
+    assert_eq!(1, socket.inbound.len());
+    assert_eq!(0, remote.inbound.len());
+    assert!(socket.large_pages[class_size.value()].is_empty());
+
+    assert_eq!((1, 0), (socket.remote_deallocations(), remote.remote_deallocations()));
+
+    //  Further allocation drains the inbound queue, catching the LargePage.
+    let further = unsafe { socket.allocate(thread_local, layout) };
+    assert_eq!(allocations[0], further);
+
+    assert_eq!(0, socket.inbound.len());
+}
+
+#[test]
+fn socket_local_deallocate_normal_inbound_flushed() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let remote = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let remote = unsafe { remote.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    let remote_thread_local = remote.acquire_thread_local().unwrap();
+    let remote_thread_local = unsafe { remote_thread_local.as_ref() };
+
+    //  Exhaust platform.
+    allocator.platform().shrink(0);
+
+    //  Determine the largest allocation size still normal.
+    let size = Properties::<TestConfiguration>::normal_threshold().value();
+    let class_size = ClassSize::from_size(num::NonZeroUsize::new(size).unwrap());
+    let layout = Layout::from_size_align(size, 1).unwrap();
+
+    //  There are only 2 allocations on a given LargePage, so exhaust it.
+    let allocations = [
+        unsafe { socket.allocate(thread_local, layout) }.unwrap(),
+        unsafe { socket.allocate(thread_local, layout) }.unwrap(),
+    ];
+
+    //  Deallocate both allocations from the remote socket, then flush the owning thread, which never allocates again.
+    unsafe { remote.deallocate(remote_thread_local, allocations[0]) };
+    unsafe { remote.deallocate(remote_thread_local, allocations[1]) };
+
+    assert_eq!(2, socket.inbound.len());
+
+    unsafe { socket.flush_thread_local(thread_local) };
+
+    assert_eq!(0, socket.inbound.len());
+    assert!(!socket.large_pages[class_size.value()].is_empty());
+}
+
+#[test]
+fn socket_local_owns() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let remote = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let remote = unsafe { remote.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    let normal = unsafe { socket.allocate(thread_local, Layout::from_size_align(8, 8).unwrap()) }.unwrap();
+    let large = unsafe { socket.allocate(thread_local, LARGE_PAGE_LAYOUT) }.unwrap();
+    let huge = unsafe { socket.allocate(thread_local, HUGE_PAGE_LAYOUT) }.unwrap();
+
+    assert!(unsafe { socket.owns(normal) });
+    assert!(unsafe { socket.owns(large) });
+
+    assert!(!unsafe { remote.owns(normal) });
+    assert!(!unsafe { remote.owns(large) });
+
+    //  Huge allocations are owned by no socket.
+    assert!(!unsafe { socket.owns(huge) });
+    assert!(!unsafe { remote.owns(huge) });
+
+    unsafe { socket.deallocate(thread_local, huge) };
+    unsafe { socket.deallocate(thread_local, large) };
+    unsafe { socket.deallocate(thread_local, normal) };
+}
+
+#[test]
+fn socket_local_drain() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let remote = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let remote = unsafe { remote.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    let remote_thread_local = remote.acquire_thread_local().unwrap();
+    let remote_thread_local = unsafe { remote_thread_local.as_ref() };
+
+    //  Exhaust platform.
+    allocator.platform().shrink(0);
+
+    let size = Properties::<TestConfiguration>::normal_threshold().value();
+    let class_size = ClassSize::from_size(num::NonZeroUsize::new(size).unwrap());
+    let layout = Layout::from_size_align(size, 1).unwrap();
+
+    //  Nothing to drain, yet.
+    assert!(!socket.drain());
+
+    //  There are only 2 allocations on a given LargePage, so exhaust it.
+    let allocations = unsafe {
+        [socket.allocate(thread_local, layout).unwrap(), socket.allocate(thread_local, layout).unwrap()]
+    };
+
+    unsafe { remote.deallocate(remote_thread_local, allocations[0]) };
+
+    assert_eq!(1, socket.inbound.len());
+    assert!(socket.large_pages[class_size.value()].is_empty());
+
+    //  Draining returns the allocation to its LargePage, ahead of any allocation.
+    assert!(socket.drain());
+    assert!(!socket.drain());
+
+    assert_eq!(0, socket.inbound.len());
+
+    assert_eq!(Some(allocations[0]), unsafe { socket.allocate(thread_local, layout) });
+}
+
+#[test]
+fn socket_local_release_thread_local_donates() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let remote = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let remote = unsafe { remote.as_ref() };
+
+    let short_lived = socket.acquire_thread_local().unwrap();
+
+    let remote_thread_local = remote.acquire_thread_local().unwrap();
+    let remote_thread_local = unsafe { remote_thread_local.as_ref() };
+
+    //  Exhaust platform.
+    allocator.platform().shrink(0);
+
+    let size = Properties::<TestConfiguration>::normal_threshold().value();
+    let class_size = ClassSize::from_size(num::NonZeroUsize::new(size).unwrap());
+    let layout = Layout::from_size_align(size, 1).unwrap();
+
+    //  There are only 2 allocations on a given LargePage, so exhaust it.
+    let allocations = unsafe {
+        let short_lived = short_lived.as_ref();
+
+        [socket.allocate(short_lived, layout).unwrap(), socket.allocate(short_lived, layout).unwrap()]
+    };
+
+    //  One is deallocated by the remote socket, and left pending on the inbound queue.
+    unsafe { remote.deallocate(remote_thread_local, allocations[0]) };
+
+    assert_eq!(1, socket.inbound.len());
+    assert!(socket.large_pages[class_size.value()].is_empty());
+
+    //  On release, the cached page is donated, and the inbound queue drained.
+    unsafe { socket.release_thread_local(short_lived) };
+
+    assert_eq!(0, socket.inbound.len());
+    assert!(!socket.large_pages[class_size.value()].is_empty());
+
+    //  Another thread may then reuse the donated memory, without further memory from the platform.
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    assert_eq!(Some(allocations[0]), unsafe { socket.allocate(thread_local, layout) });
+}
+
+#[test]
+fn socket_local_flush_thread_local() {
+    let store = HugePageStore::default();
+    let allocator = unsafe { TestPlatform::allocator(&store) };
+
+    let socket = TestSocketLocal::bootstrap(&allocator).unwrap();
+    let socket = unsafe { socket.as_ref() };
+
+    let thread_local = socket.acquire_thread_local().unwrap();
+    let thread_local = unsafe { thread_local.as_ref() };
+
+    //  Exhaust platform.
+    allocator.platform().shrink(0);
+
+    let size = Properties::<TestConfiguration>::normal_threshold().value();
+    let class_size = ClassSize::from_size(num::NonZeroUsize::new(size).unwrap());
+    let layout = Layout::from_size_align(size, 1).unwrap();
+
+    let allocation = unsafe { socket.allocate(thread_local, layout) }.unwrap();
+    unsafe { socket.deallocate(thread_local, allocation) };
+
+    assert!(socket.large_pages[class_size.value()].is_empty());
+
+    //  On flush, the cached page is donated, yet the thread-local remains usable.
+    unsafe { socket.flush_thread_local(thread_local) };
+
+    assert!(!socket.large_pages[class_size.value()].is_empty());
+
+    assert_eq!(Some(allocation), unsafe { socket.allocate(thread_local, layout) });
+    assert!(socket.large_pages[class_size.value()].is_empty());
+}
+
 } // mod tests
@@ -0,0 +1,87 @@
+//! Reserve of Large pages for Critical threads.
+
+use core::{mem, ptr::NonNull};
+
+use crate::ClassSize;
+use crate::internals::{
+    atomic_stack::AtomicStack,
+    large_page::LargePage,
+    sync::{AtomicUsize, Ordering},
+};
+
+//  Reserve of Large Pages, per class size, only handed out to Critical threads.
+//
+//  The counts are approximate: concurrent offers may overshoot the target by the number of offering threads.
+pub(crate) struct CriticalReserve {
+    //  Linked-lists of the reserved LargePages.
+    pages: [AtomicStack<LargePage>; 64],
+    //  Number of reserved LargePages.
+    counts: [AtomicUsize; 64],
+    //  Target number of reserved LargePages, for each class size.
+    target: AtomicUsize,
+}
+
+impl CriticalReserve {
+    //  Creates a new instance, with a target of 0.
+    pub(crate) fn new() -> Self {
+        //  Safety:
+        //  -   Null stacks, and 0 counts, are valid.
+        unsafe { mem::zeroed() }
+    }
+
+    //  Returns the target number of reserved LargePages, for each class size.
+    pub(crate) fn target(&self) -> usize { self.target.load(Ordering::Relaxed) }
+
+    //  Sets the target number of reserved LargePages, for each class size.
+    pub(crate) fn set_target(&self, target: usize) { self.target.store(target, Ordering::Relaxed); }
+
+    //  Returns the number of reserved LargePages of `class_size`.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `class_size` is within bounds.
+    pub(crate) unsafe fn count(&self, class_size: ClassSize) -> usize {
+        debug_assert!(class_size.value() < self.counts.len());
+
+        self.counts.get_unchecked(class_size.value()).load(Ordering::Relaxed)
+    }
+
+    //  Pops a reserved LargePage of `class_size`, if any.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `class_size` is within bounds.
+    pub(crate) unsafe fn pop(&self, class_size: ClassSize) -> Option<NonNull<LargePage>> {
+        debug_assert!(class_size.value() < self.pages.len());
+
+        let page = self.pages.get_unchecked(class_size.value()).pop()?;
+
+        self.counts.get_unchecked(class_size.value()).fetch_sub(1, Ordering::Relaxed);
+
+        Some(page)
+    }
+
+    //  Offers `page` to the reserve, which accepts it if below target.
+    //
+    //  Returns the page if it is not accepted.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `page` is not null, and not referenced by any other stack.
+    pub(crate) unsafe fn offer(&self, page: NonNull<LargePage>) -> Option<NonNull<LargePage>> {
+        let class_size = page.as_ref().class_size();
+        debug_assert!(class_size.value() < self.pages.len());
+
+        let count = self.counts.get_unchecked(class_size.value());
+
+        if count.load(Ordering::Relaxed) >= self.target() {
+            return Some(page);
+        }
+
+        count.fetch_add(1, Ordering::Relaxed);
+
+        self.pages.get_unchecked(class_size.value()).push(&mut *page.as_ptr());
+
+        None
+    }
+}
@@ -5,7 +5,7 @@ use core::{
     cmp,
     marker::PhantomData,
     mem,
-    ptr::NonNull,
+    ptr::{self, NonNull},
     slice,
 };
 
@@ -32,6 +32,20 @@ impl<C, P> HugePagesManager<C, P> {
 
         HugePagesManager(huge_pages, _configuration, _platform)
     }
+
+    //  Invokes `f` on each HugePage currently allocated, in order of allocation.
+    pub(crate) fn for_each<F>(&self, mut f: F)
+        where
+            F: FnMut(NonNull<HugePage>),
+    {
+        for huge_page in &self.0[..] {
+            match huge_page.load() {
+                //  The array is filled in order, there's none after that.
+                None => break,
+                Some(page) => f(page),
+            }
+        }
+    }
 }
 
 impl<C, P> HugePagesManager<C, P>
@@ -90,7 +104,7 @@ impl<C, P> HugePagesManager<C, P>
                 break;
             }
 
-            if !huge_page.load().is_none() {
+            if huge_page.load().is_some() {
                 continue;
             }
 
@@ -105,7 +119,7 @@ impl<C, P> HugePagesManager<C, P>
             }
 
             //  If the replacement is successful, null `fresh_page` to indicate it should not be deallocated or reused.
-            if let Ok(_) = huge_page.compare_exchange(None, fresh_page) {
+            if huge_page.compare_exchange(None, fresh_page).is_ok() {
                 fresh_page = None;
             }
         }
@@ -127,6 +141,26 @@ impl<C, P> HugePagesManager<C, P>
     //  -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
     #[inline(never)]
     pub(crate) unsafe fn allocate_large(&self, layout: Layout, owner: *mut (), platform: &P) -> Option<NonNull<u8>> {
+        self.allocate_large_impl(layout, owner, Some(platform))
+    }
+
+    //  Allocates a Large allocation, from the existing HugePages only, never from the platform.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(never)]
+    pub(crate) unsafe fn allocate_large_cached(&self, layout: Layout) -> Option<NonNull<u8>> {
+        self.allocate_large_impl(layout, ptr::null_mut(), None)
+    }
+
+    //  Allocates a Large allocation, from the existing HugePages, or failing that a fresh one from `platform`, if any.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `layout` is valid, as per `Self::is_valid_layout`.
+    #[inline(always)]
+    unsafe fn allocate_large_impl(&self, layout: Layout, owner: *mut (), platform: Option<&P>) -> Option<NonNull<u8>> {
         let mut first_null = self.0.len();
 
         //  Check if any existing page can accomodate the request.
@@ -149,6 +183,9 @@ impl<C, P> HugePagesManager<C, P>
             }
         }
 
+        //  No fresh page may be allocated without a platform.
+        let platform = platform?;
+
         //  This is typically where a lock would be acquired to avoid over-acquiring memory from the system.
         //
         //  The chances of over-acquiring are slim, though, so instead a lock-free algorithm is used, betting on the
@@ -202,13 +239,16 @@ impl<C, P> HugePagesManager<C, P>
         None
     }
 
-    //  Deallocates a Large allocation.
+    //  Deallocates a Large allocation, decommitting its pages through `platform` beforehand.
+    //
+    //  Returns the number of bytes deallocated.
     //
     //  #   Safety
     //
     //  -   Assumes that `ptr` is a Large allocation allocated by an instance of `Self`.
+    //  -   Assumes that the HugePage containing `ptr` was allocated by `platform`.
     #[inline(never)]
-    pub(crate) unsafe fn deallocate_large(&self, ptr: NonNull<u8>) {
+    pub(crate) unsafe fn deallocate_large(&self, ptr: NonNull<u8>, platform: &P) -> usize {
         debug_assert!((ptr.as_ptr() as usize) % C::LARGE_PAGE_SIZE == 0);
         debug_assert!((ptr.as_ptr() as usize) % C::HUGE_PAGE_SIZE != 0);
 
@@ -220,7 +260,13 @@ impl<C, P> HugePagesManager<C, P>
         //  -   `huge_page` is not null.
         let huge_page = huge_page.as_ref();
 
-        huge_page.deallocate(ptr);
+        //  The pages are decommitted while still allocated, lest they be reused in the meantime.
+        //
+        //  Safety:
+        //  -   `ptr` is assumed to be a large allocation within `huge_page`, no longer in use.
+        platform.decommit(ptr, huge_page.size_of(ptr));
+
+        huge_page.deallocate(ptr)
     }
 
     //  Allocates a HugePage, as defined by C.
@@ -415,6 +461,34 @@ fn huge_pages_close() {
     assert_eq!(0, platform.allocated());
 }
 
+#[test]
+fn huge_pages_for_each() {
+    let owner = 0x1234 as *mut ();
+
+    let store = HugePageStore::default();
+    let platform = unsafe { TestPlatform::new(&store) };
+
+    let manager = TestHugePagesManager::default();
+
+    //  Created empty.
+    let mut visited = vec!();
+    manager.for_each(|page| visited.push(page.as_ptr() as usize));
+
+    assert_eq!(Vec::<usize>::new(), visited);
+
+    //  Reserve a few pages.
+    let reserved = manager.reserve(3, owner, &platform);
+
+    assert_eq!(3, reserved);
+
+    manager.for_each(|page| visited.push(page.as_ptr() as usize));
+
+    let expected: Vec<_> = (0..3).map(|i| store.as_ptr() as usize + i * HUGE_PAGE_SIZE).collect();
+    assert_eq!(expected, visited);
+
+    unsafe { manager.close(owner, &platform) };
+}
+
 #[test]
 fn huge_pages_allocate_initial_fresh() {
     let owner = 0x1234 as *mut ();
@@ -569,7 +643,9 @@ fn huge_pages_deallocate() {
     assert_eq!(1, platform.allocated());
 
     //  Deallocate it.
-    unsafe { manager.deallocate_large(large.unwrap()) };
+    let deallocated = unsafe { manager.deallocate_large(large.unwrap(), &platform) };
+    assert_eq!(LARGE_PAGE_SIZE, deallocated);
+    assert_eq!(LARGE_PAGE_SIZE, platform.decommitted());
 
     //  Allocate a page again, it's the same one!
     let other = unsafe { manager.allocate_large(LARGE_PAGE_LAYOUT, owner, &platform) };
@@ -742,7 +818,7 @@ impl Global {
             while let Some((head, tail)) = slice.split_first() {
                 slice = tail;
 
-                if head.load(Ordering::Relaxed) != ptr::null_mut() {
+                if !head.load(Ordering::Relaxed).is_null() {
                     continue;
                 }
 
@@ -775,6 +851,7 @@ unsafe impl Send for Global {}
 unsafe impl Sync for Global {}
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn huge_pages_reserve_fuzzing() {
     //  This test aims at testing that multiple threads can call reserve in parallel.
     //
@@ -824,6 +901,7 @@ fn huge_pages_reserve_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn huge_pages_allocate_large_multiple_huge_additions_fuzzing() {
     //  This test aims at testing that multiple threads can add huge pages in parallel while allocating.
     const THREADS: usize = 4;
@@ -841,7 +919,7 @@ fn huge_pages_allocate_large_multiple_huge_additions_fuzzing() {
             global.store.pop(*local..(*local + 1))
         };
         let step = |global: &Global, _: &mut usize, platform: LocalPlatform| {
-            const EMPTY: &'static [NonNull<u8>] = &[];
+            const EMPTY: &[NonNull<u8>] = &[];
 
             let allocated = unsafe { global.victim.allocate_large(LARGE_PAGE_LAYOUT, ptr::null_mut(), &platform) };
             assert_ne!(None, allocated);
@@ -862,6 +940,7 @@ fn huge_pages_allocate_large_multiple_huge_additions_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn huge_pages_allocate_large_race_fuzzing() {
     //  This test aims at testing that a single thread can acquire a large page in case of memory exhaustion.
     const THREADS: usize = 4;
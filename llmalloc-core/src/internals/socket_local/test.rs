@@ -25,7 +25,7 @@ impl Configuration for TestConfiguration {
 }
 
 /// Test Platform
-pub(crate) struct TestPlatform([Cell<Option<NonNull<u8>>>; 32]);
+pub(crate) struct TestPlatform([Cell<Option<NonNull<u8>>>; 32], Cell<usize>);
 
 impl TestPlatform {
     pub(crate) const HUGE_PAGE_SIZE: usize = TestConfiguration::HUGE_PAGE_SIZE.value();
@@ -37,7 +37,7 @@ impl TestPlatform {
             cell.set(NonNull::new(store.as_ptr().add(i * Self::HUGE_PAGE_SIZE)));
         }
 
-        TestPlatform(stores)
+        TestPlatform(stores, Cell::new(0))
     }
 
     //  Creates a TestHugeAllocator.
@@ -69,6 +69,9 @@ impl TestPlatform {
 
     //  Returns the number of available pages.
     pub(crate) fn available(&self) -> usize { self.0.iter().filter(|p| p.get().is_some()).count() }
+
+    //  Returns the number of bytes decommitted.
+    pub(crate) fn decommitted(&self) -> usize { self.1.get() }
 }
 
 impl Platform for TestPlatform {
@@ -100,6 +103,12 @@ impl Platform for TestPlatform {
             return;
         }
     }
+
+    unsafe fn decommit(&self, _pointer: NonNull<u8>, size: usize) {
+        assert_eq!(0, size % LARGE_PAGE_SIZE);
+
+        self.1.set(self.1.get() + size);
+    }
 }
 
 impl Default for TestPlatform {
@@ -132,4 +141,4 @@ const LARGE_PAGE_LAYOUT: Layout = unsafe { Layout::from_size_align_unchecked(LAR
 
 #[repr(align(8192))]
 #[derive(Clone, Default)]
-struct HugePageCell(u8);
+struct HugePageCell(#[allow(dead_code)] u8);
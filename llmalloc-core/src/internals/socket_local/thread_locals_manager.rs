@@ -4,18 +4,22 @@ use core::{
     marker::PhantomData,
     mem::{self, ManuallyDrop},
     ptr::{self, NonNull},
-    sync::atomic::{self, Ordering},
 };
 
-use crate::{Configuration, PowerOf2};
+use crate::{Configuration, PowerOf2, Statistics};
 use crate::{
     internals::{
         atomic_stack::{AtomicStack, AtomicStackElement, AtomicStackLink},
+        statistics::AtomicStatistics,
+        sync::{AtomicPtr, Ordering},
         thread_local::ThreadLocal,
     },
     utils,
 };
 
+#[cfg(feature = "histogram")]
+use crate::{SizeHistogram, internals::histogram::AtomicSizeHistogram};
+
 //  Manager of Thread Locals.
 pub(crate) struct ThreadLocalsManager<C> {
     //  Owner.
@@ -30,7 +34,7 @@ pub(crate) struct ThreadLocalsManager<C> {
     //  be sufficient to guard against those rare cases.
     stack: AtomicStack<MaybeThreadLocal<C>>,
     //  Current watermark for fresh allocations into the buffer area.
-    watermark: atomic::AtomicPtr<u8>,
+    watermark: AtomicPtr<u8>,
     //  Begin of buffer area.
     begin: NonNull<u8>,
     //  End of buffer area.
@@ -60,12 +64,18 @@ impl<C> ThreadLocalsManager<C>
         //  Safety:
         //  -   `watermark` still points inside `buffer`, as `x / y * y <= x`.
         let start = unsafe { end.as_ptr().sub(nb_thread_locals * Self::THREAD_LOCAL_SIZE) };
-        let watermark = atomic::AtomicPtr::new(start);
+        let watermark = AtomicPtr::new(start);
 
         //  Safety:
         //  -   `start` is not null, since `watermark` is not null.
         let begin = unsafe { NonNull::new_unchecked(start) };
 
+        //  Zero the area, so that the statistics of not yet acquired `ThreadLocal` read as 0.
+        //
+        //  Safety:
+        //  -   `[start, end)` is within `buffer`.
+        unsafe { ptr::write_bytes(start, 0, nb_thread_locals * Self::THREAD_LOCAL_SIZE) };
+
         let stack = AtomicStack::default();
 
         Self { owner, stack, watermark, begin, end, _configuration, }
@@ -133,6 +143,73 @@ impl<C> ThreadLocalsManager<C>
         self.push(thread_local);
     }
 
+    //  Accumulates the statistics of all ThreadLocals ever acquired into `statistics`.
+    //
+    //  The statistics of a ThreadLocal survive its release, and are further accumulated by its next user.
+    pub(crate) fn accumulate_statistics(&self, statistics: &mut Statistics) {
+        let offset = ThreadLocal::<C>::statistics_offset();
+
+        //  Safety:
+        //  -   The statistics are never overwritten, and are valid when zeroed.
+        unsafe {
+            self.for_each_retained(offset, |thread_statistics: &AtomicStatistics| {
+                statistics.merge(&thread_statistics.snapshot());
+            });
+        }
+    }
+
+    //  Accumulates the histograms of all ThreadLocals ever acquired into `histogram`.
+    //
+    //  The histogram of a ThreadLocal survives its release, and is further accumulated by its next user.
+    #[cfg(feature = "histogram")]
+    pub(crate) fn accumulate_histogram(&self, histogram: &mut SizeHistogram) {
+        let offset = ThreadLocal::<C>::histogram_offset();
+
+        //  Safety:
+        //  -   The histogram is never overwritten, and is valid when zeroed.
+        unsafe {
+            self.for_each_retained(offset, |thread_histogram: &AtomicSizeHistogram| {
+                histogram.merge(&thread_histogram.snapshot());
+            });
+        }
+    }
+
+    //  Internal; Invokes `f` with the `T` located at `offset` within each ThreadLocal ever acquired.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `offset` is the offset of a field of type `T` of `ThreadLocal`, which is never overwritten even
+    //      when the ThreadLocal is released, and is valid when zeroed.
+    unsafe fn for_each_retained<T, F>(&self, offset: usize, mut f: F)
+        where
+            F: FnMut(&T),
+    {
+        debug_assert!(offset + mem::size_of::<T>() <= mem::size_of::<ThreadLocal<C>>());
+
+        let watermark = self.watermark.load(Ordering::Relaxed);
+
+        let mut current = self.begin.as_ptr();
+
+        while current < watermark {
+            //  Safety:
+            //  -   `current` points to a `GuardedThreadLocal`, as it is within `[begin, watermark)`.
+            //  -   The field at `offset` is never overwritten, even when released.
+            //  -   The field at `offset` is either zeroed, or initialized, hence always valid for reads.
+            let retained = {
+                let guarded = current as *const GuardedThreadLocal<C>;
+                let thread_local = ptr::addr_of!((*guarded).maybe_thread_local) as *const u8;
+
+                &*(thread_local.add(offset) as *const T)
+            };
+
+            f(retained);
+
+            //  Safety:
+            //  -   `current` is less than `watermark`, hence the result is at most `end`.
+            current = current.add(Self::THREAD_LOCAL_SIZE);
+        }
+    }
+
     //  Internal; Pops a ThreadLocal off the stack, if any.
     fn pop(&self) -> Option<NonNull<ThreadLocal<C>>> {
         self.stack.pop().map(|maybe| {
@@ -201,17 +278,22 @@ impl<C: Configuration> MaybeThreadLocal<C> {
         this
     }
 
-    //  Returns `this` memory with freshly initialized `ThreadLocal` instance inside.
+    //  Returns `this` memory with freshly re-initialized `ThreadLocal` instance inside.
+    //
+    //  The statistics of the previous `ThreadLocal` instance are preserved.
     //
     //  #   Safety:
     //
-    //  -   Assumes exclusive access to the memory pointed to by `this`.
-    unsafe fn into_thread_local(mut this: NonNull<Self>, owner: *mut ()) -> NonNull<ThreadLocal<C>> {
-        let this = this.as_mut();
+    //  -   Assumes exclusive access to the memory pointed to by `this`, with the exception of the statistics.
+    //  -   Assumes that `this` previously contained a `ThreadLocal` instance.
+    unsafe fn into_thread_local(this: NonNull<Self>, owner: *mut ()) -> NonNull<ThreadLocal<C>> {
+        debug_assert!(mem::size_of::<AtomicStackLink<Self>>() <= ThreadLocal::<C>::statistics_offset());
+
+        let thread_local: NonNull<ThreadLocal<C>> = this.cast();
 
-        this.thread_local = ManuallyDrop::new(ThreadLocal::new(owner));
+        ThreadLocal::reinitialize(thread_local, owner);
 
-        NonNull::from(&mut *this.thread_local)
+        thread_local
     }
 }
 
@@ -223,6 +305,7 @@ impl<C> AtomicStackElement for MaybeThreadLocal<C> {
 mod tests {
 
 use std::{
+    cell::UnsafeCell,
     ptr,
     slice,
     sync::atomic::Ordering,
@@ -243,6 +326,9 @@ struct ThreadLocalsStore([u8; 8192]);
 impl ThreadLocalsStore {
     const THREAD_LOCAL_SIZE: usize = TestThreadLocalsManager::THREAD_LOCAL_SIZE;
 
+    //  The histogram, when enabled, is recorded in each ThreadLocal, leaving room for fewer of them.
+    const NUMBER_THREAD_LOCALS: usize = if cfg!(feature = "histogram") { 5 } else { 9 };
+
     //  Creates a ThreadLocalsManager.
     //
     //  #   Safety
@@ -271,9 +357,8 @@ fn thread_locals_manager_new() {
 
     let bytes = manager.end.as_ptr() as usize - watermark as usize;
 
-    assert_eq!(7680, bytes);
     assert_eq!(0, bytes % ThreadLocalsStore::THREAD_LOCAL_SIZE);
-    assert_eq!(10, bytes / ThreadLocalsStore::THREAD_LOCAL_SIZE);
+    assert_eq!(ThreadLocalsStore::NUMBER_THREAD_LOCALS, bytes / ThreadLocalsStore::THREAD_LOCAL_SIZE);
 }
 
 #[test]
@@ -282,7 +367,7 @@ fn thread_locals_acquire_release() {
     let manager = unsafe { store.create() };
 
     //  Acquire fresh pointers, by bumping the watermark.
-    let mut thread_locals = [ptr::null_mut(); 10];
+    let mut thread_locals = [ptr::null_mut(); ThreadLocalsStore::NUMBER_THREAD_LOCALS];
 
     for ptr in &mut thread_locals {
         *ptr = manager.acquire().unwrap().as_ptr();
@@ -307,20 +392,26 @@ fn thread_locals_acquire_release() {
 }
 
 struct Global {
-    victim: TestThreadLocalsManager,
-    buffer: Vec<TestGuardedThreadLocal>,
+    victim: UnsafeCell<TestThreadLocalsManager>,
+    buffer: UnsafeCell<Vec<TestGuardedThreadLocal>>,
 }
 
 impl Global {
     fn new(n: usize) -> Global {
-        let mut buffer = vec!();
-        buffer.reserve(n);
+        let mut buffer = Vec::with_capacity(n);
 
-        let victim = TestThreadLocalsManager::new(ptr::null_mut(), Self::buffer(&mut buffer));
+        let victim = UnsafeCell::new(TestThreadLocalsManager::new(ptr::null_mut(), Self::buffer(&mut buffer)));
+        let buffer = UnsafeCell::new(buffer);
 
         Self { victim, buffer, }
     }
 
+    fn victim(&self) -> &TestThreadLocalsManager {
+        //  Safety:
+        //  -   The victim is only ever mutated by `reset`, which is never invoked concurrently with other methods.
+        unsafe { &*self.victim.get() }
+    }
+
     //  Safety:
     //  -   No other method should be invoked concurrently.
     //  -   When invoked concurrently, a single index should be 0.
@@ -331,9 +422,9 @@ impl Global {
 
         //  Safety:
         //  -   A single index is 0, hence temporarily access is exclusive.
-        let this: &mut Global = &mut *(self as *const _ as *mut _);
+        let buffer = &mut *self.buffer.get();
 
-        this.victim = TestThreadLocalsManager::new(ptr::null_mut(), Self::buffer(&mut this.buffer));
+        *self.victim.get() = TestThreadLocalsManager::new(ptr::null_mut(), Self::buffer(buffer));
     }
 
     fn buffer(buffer: &mut Vec<TestGuardedThreadLocal>) -> &mut [u8] {
@@ -354,17 +445,17 @@ struct Local {
 
 impl Local {
     fn vec(n: usize) -> Vec<Local> {
-        (0..n).map(|index| Local::new(index)).collect()
+        (0..n).map(Local::new).collect()
     }
 
     fn new(index: usize) -> Self { Self { index, thread_local: None, } }
 
-    fn is_even(&self) -> bool { self.index % 2 == 0 }
+    fn is_even(&self) -> bool { self.index.is_multiple_of(2) }
 
     fn acquire(&mut self, global: &Global) {
         debug_assert_eq!(None, self.thread_local);
 
-        self.thread_local = global.victim.acquire();
+        self.thread_local = global.victim().acquire();
 
         assert_ne!(None, self.thread_local);
     }
@@ -373,7 +464,7 @@ impl Local {
         debug_assert_ne!(None, self.thread_local);
 
         if let Some(thread_local) = self.thread_local.take() {
-            unsafe { global.victim.release(thread_local) };
+            unsafe { global.victim().release(thread_local) };
         }
     }
 }
@@ -381,6 +472,7 @@ impl Local {
 unsafe impl Send for Local {}
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn thread_locals_acquire_concurrent_watermark_fuzzing() {
     //  This test aims at testing that multiple threads can bump the watermark in a concurrent fashion.
     const THREADS: usize = 4;
@@ -394,7 +486,7 @@ fn thread_locals_acquire_concurrent_watermark_fuzzing() {
 
     //  Step 2: Concurrently attempt to acquire a thread-local.
     builder.add_simple_step(|| |global: &Global, _: &mut usize| {
-        let acquired = global.victim.acquire();
+        let acquired = global.victim().acquire();
         assert_ne!(None, acquired);
     });
 
@@ -402,9 +494,9 @@ fn thread_locals_acquire_concurrent_watermark_fuzzing() {
     builder.add_simple_step(|| |global: &Global, _: &mut usize| {
         const THREAD_SIZE: usize = mem::size_of::<TestGuardedThreadLocal>();
 
-        let begin = global.victim.begin.as_ptr() as usize;
-        let end = global.victim.end.as_ptr() as usize;
-        let watermark = global.victim.watermark.load(Ordering::Relaxed) as usize;
+        let begin = global.victim().begin.as_ptr() as usize;
+        let end = global.victim().end.as_ptr() as usize;
+        let watermark = global.victim().watermark.load(Ordering::Relaxed) as usize;
 
         assert!(begin <= end, "{:x} > {:x}", begin, end);
         assert!(begin <= watermark, "{:x} > {:x}", begin, watermark);
@@ -418,6 +510,7 @@ fn thread_locals_acquire_concurrent_watermark_fuzzing() {
 }
 
 #[test]
+#[cfg_attr(feature = "single-threaded", ignore)]
 fn thread_locals_acquire_release_concurrent_fuzzing() {
     //  This test aims at testing that multiple threads can acquire and release in parallel.
     //
@@ -462,9 +555,9 @@ fn thread_locals_acquire_release_concurrent_fuzzing() {
     builder.add_simple_step(|| |global: &Global, _: &mut Local| {
         const THREAD_SIZE: usize = mem::size_of::<TestGuardedThreadLocal>();
 
-        let begin = global.victim.begin.as_ptr() as usize;
-        let end = global.victim.end.as_ptr() as usize;
-        let watermark = global.victim.watermark.load(Ordering::Relaxed) as usize;
+        let begin = global.victim().begin.as_ptr() as usize;
+        let end = global.victim().end.as_ptr() as usize;
+        let watermark = global.victim().watermark.load(Ordering::Relaxed) as usize;
 
         assert!(begin <= end, "{:x} > {:x}", begin, end);
         assert!(begin <= watermark, "{:x} > {:x}", begin, watermark);
@@ -0,0 +1,198 @@
+//! Statistics
+//!
+//! Atomic counters of allocations and deallocations, per category.
+//!
+//! Two ways of updating the counters are provided:
+//!
+//! -   `record_*`, for counters with a single writer, such as those of a `ThreadLocal`, which avoids the cost of a
+//!     read-modify-write instruction.
+//! -   `add_*`, for counters shared between multiple writers, such as those of a `SocketLocal`.
+
+use crate::internals::sync::{AtomicUsize, Ordering};
+
+use crate::{Category, CategoryStatistics, Statistics};
+
+/// Atomic Statistics.
+#[derive(Default)]
+pub(crate) struct AtomicStatistics {
+    normal: AtomicCategoryStatistics,
+    large: AtomicCategoryStatistics,
+    huge: AtomicCategoryStatistics,
+}
+
+impl AtomicStatistics {
+    /// Creates an instance, with all counters at 0.
+    pub(crate) const fn new() -> Self {
+        let normal = AtomicCategoryStatistics::new();
+        let large = AtomicCategoryStatistics::new();
+        let huge = AtomicCategoryStatistics::new();
+
+        Self { normal, large, huge, }
+    }
+
+    /// Records an allocation of `bytes` in `category`.
+    ///
+    /// Concurrent writers may lose updates, but never cause memory unsafety.
+    #[inline(always)]
+    pub(crate) fn record_allocation(&self, category: Category, bytes: usize) {
+        let counters = self.category(category);
+
+        record(&counters.allocations, 1);
+        record(&counters.allocated_bytes, bytes);
+    }
+
+    /// Records a deallocation of `bytes` in `category`.
+    ///
+    /// Concurrent writers may lose updates, but never cause memory unsafety.
+    #[inline(always)]
+    pub(crate) fn record_deallocation(&self, category: Category, bytes: usize) {
+        let counters = self.category(category);
+
+        record(&counters.deallocations, 1);
+        record(&counters.deallocated_bytes, bytes);
+    }
+
+    /// Adds a deallocation of `bytes` in `category`.
+    ///
+    /// Concurrent writers are safe.
+    pub(crate) fn add_deallocation(&self, category: Category, bytes: usize) {
+        let counters = self.category(category);
+
+        counters.deallocations.fetch_add(1, Ordering::Relaxed);
+        counters.deallocated_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of the counters.
+    ///
+    /// In the presence of concurrent writers, the snapshot is not necessarily consistent across counters.
+    pub(crate) fn snapshot(&self) -> Statistics {
+        let normal = self.normal.snapshot();
+        let large = self.large.snapshot();
+        let huge = self.huge.snapshot();
+
+        Statistics { normal, large, huge, }
+    }
+
+    /// Stores `statistics` into the counters.
+    pub(crate) fn store(&self, statistics: &Statistics) {
+        self.normal.store(&statistics.normal);
+        self.large.store(&statistics.large);
+        self.huge.store(&statistics.huge);
+    }
+
+    /// Swaps `statistics` into the counters, returning their previous values.
+    pub(crate) fn swap(&self, statistics: &Statistics) -> Statistics {
+        let normal = self.normal.swap(&statistics.normal);
+        let large = self.large.swap(&statistics.large);
+        let huge = self.huge.swap(&statistics.huge);
+
+        Statistics { normal, large, huge, }
+    }
+
+    #[inline(always)]
+    fn category(&self, category: Category) -> &AtomicCategoryStatistics {
+        match category {
+            Category::Normal => &self.normal,
+            Category::Large => &self.large,
+            Category::Huge => &self.huge,
+        }
+    }
+}
+
+//
+//  Implementation Details
+//
+
+#[derive(Default)]
+struct AtomicCategoryStatistics {
+    allocations: AtomicUsize,
+    deallocations: AtomicUsize,
+    allocated_bytes: AtomicUsize,
+    deallocated_bytes: AtomicUsize,
+}
+
+impl AtomicCategoryStatistics {
+    const fn new() -> Self {
+        Self {
+            allocations: AtomicUsize::new(0),
+            deallocations: AtomicUsize::new(0),
+            allocated_bytes: AtomicUsize::new(0),
+            deallocated_bytes: AtomicUsize::new(0),
+        }
+    }
+
+    fn snapshot(&self) -> CategoryStatistics {
+        CategoryStatistics {
+            allocations: self.allocations.load(Ordering::Relaxed),
+            deallocations: self.deallocations.load(Ordering::Relaxed),
+            allocated_bytes: self.allocated_bytes.load(Ordering::Relaxed),
+            deallocated_bytes: self.deallocated_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn store(&self, statistics: &CategoryStatistics) {
+        self.allocations.store(statistics.allocations, Ordering::Relaxed);
+        self.deallocations.store(statistics.deallocations, Ordering::Relaxed);
+        self.allocated_bytes.store(statistics.allocated_bytes, Ordering::Relaxed);
+        self.deallocated_bytes.store(statistics.deallocated_bytes, Ordering::Relaxed);
+    }
+
+    fn swap(&self, statistics: &CategoryStatistics) -> CategoryStatistics {
+        CategoryStatistics {
+            allocations: self.allocations.swap(statistics.allocations, Ordering::Relaxed),
+            deallocations: self.deallocations.swap(statistics.deallocations, Ordering::Relaxed),
+            allocated_bytes: self.allocated_bytes.swap(statistics.allocated_bytes, Ordering::Relaxed),
+            deallocated_bytes: self.deallocated_bytes.swap(statistics.deallocated_bytes, Ordering::Relaxed),
+        }
+    }
+}
+
+//  Increments a single-writer counter, without a read-modify-write instruction.
+#[inline(always)]
+fn record(counter: &AtomicUsize, increment: usize) {
+    let value = counter.load(Ordering::Relaxed);
+    counter.store(value.wrapping_add(increment), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn atomic_statistics_record() {
+    let statistics = AtomicStatistics::new();
+
+    statistics.record_allocation(Category::Normal, 16);
+    statistics.record_allocation(Category::Normal, 32);
+    statistics.record_deallocation(Category::Normal, 16);
+    statistics.add_deallocation(Category::Huge, 1024);
+
+    let snapshot = statistics.snapshot();
+
+    assert_eq!(CategoryStatistics { allocations: 2, deallocations: 1, allocated_bytes: 48, deallocated_bytes: 16 },
+        snapshot.normal);
+    assert_eq!(CategoryStatistics::default(), snapshot.large);
+    assert_eq!(CategoryStatistics { deallocations: 1, deallocated_bytes: 1024, ..CategoryStatistics::default() },
+        snapshot.huge);
+}
+
+#[test]
+fn atomic_statistics_store_swap() {
+    let statistics = AtomicStatistics::new();
+
+    let mut increment = Statistics::default();
+    increment.large.allocations = 3;
+
+    statistics.store(&increment);
+    statistics.record_allocation(Category::Large, 4096);
+
+    assert_eq!(4, statistics.snapshot().large.allocations);
+
+    let previous = statistics.swap(&increment);
+
+    assert_eq!(4, previous.large.allocations);
+    assert_eq!(increment, statistics.snapshot());
+}
+
+} // mod tests
@@ -0,0 +1,220 @@
+//! Synchronization primitives.
+//!
+//! By default, the primitives are those of `core::sync::atomic`.
+//!
+//! With the `single-threaded` feature, the primitives are instead plain cells mimicking the API of their atomic
+//! counterparts, and the fences are no-ops, for the benefit of targets without threads. The memory orderings are
+//! accepted, and ignored.
+//!
+//! It is then Undefined Behavior to use the allocator from more than a single thread.
+
+#[cfg(not(feature = "single-threaded"))]
+pub(crate) use core::sync::atomic::{fence, AtomicPtr, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+#[cfg(feature = "single-threaded")]
+pub(crate) use core::sync::atomic::Ordering;
+
+#[cfg(feature = "single-threaded")]
+pub(crate) use cells::{fence, AtomicPtr, AtomicU64, AtomicU8, AtomicUsize};
+
+#[cfg(feature = "single-threaded")]
+#[allow(dead_code)]
+mod cells {
+
+use core::{cell::Cell, ptr};
+
+use super::Ordering;
+
+/// Fence, a no-op.
+#[inline(always)]
+pub(crate) fn fence(_: Ordering) {}
+
+macro_rules! cell_integer {
+    ($name:ident, $integer:ty) => {
+        /// Cell mimicking the API of its atomic counterpart.
+        #[derive(Default)]
+        pub(crate) struct $name(Cell<$integer>);
+
+        impl $name {
+            /// Creates an instance.
+            pub(crate) const fn new(value: $integer) -> Self { Self(Cell::new(value)) }
+
+            /// Returns the value.
+            #[inline(always)]
+            pub(crate) fn load(&self, _: Ordering) -> $integer { self.0.get() }
+
+            /// Sets the value.
+            #[inline(always)]
+            pub(crate) fn store(&self, value: $integer, _: Ordering) { self.0.set(value) }
+
+            /// Sets the value, returns the previous value.
+            #[inline(always)]
+            pub(crate) fn swap(&self, value: $integer, _: Ordering) -> $integer { self.0.replace(value) }
+
+            /// Sets the value if equal to `current`, returns the previous value on success, the actual value on
+            /// failure.
+            #[inline(always)]
+            pub(crate) fn compare_exchange(&self, current: $integer, new: $integer, _: Ordering, _: Ordering)
+                -> Result<$integer, $integer>
+            {
+                let value = self.0.get();
+
+                if value != current {
+                    return Err(value);
+                }
+
+                self.0.set(new);
+                Ok(value)
+            }
+
+            /// Identical to `compare_exchange`, as there is no spurious failure.
+            #[inline(always)]
+            pub(crate) fn compare_exchange_weak(
+                &self,
+                current: $integer,
+                new: $integer,
+                success: Ordering,
+                failure: Ordering,
+            )
+                -> Result<$integer, $integer>
+            {
+                self.compare_exchange(current, new, success, failure)
+            }
+
+            /// Adds to the value, wrapping around, returns the previous value.
+            #[inline(always)]
+            pub(crate) fn fetch_add(&self, value: $integer, _: Ordering) -> $integer {
+                self.0.replace(self.0.get().wrapping_add(value))
+            }
+
+            /// Subtracts from the value, wrapping around, returns the previous value.
+            #[inline(always)]
+            pub(crate) fn fetch_sub(&self, value: $integer, _: Ordering) -> $integer {
+                self.0.replace(self.0.get().wrapping_sub(value))
+            }
+
+            /// Bitwise ands the value, returns the previous value.
+            #[inline(always)]
+            pub(crate) fn fetch_and(&self, value: $integer, _: Ordering) -> $integer {
+                self.0.replace(self.0.get() & value)
+            }
+
+            /// Bitwise ors the value, returns the previous value.
+            #[inline(always)]
+            pub(crate) fn fetch_or(&self, value: $integer, _: Ordering) -> $integer {
+                self.0.replace(self.0.get() | value)
+            }
+
+            /// Sets the value to the maximum of the current value and `value`, returns the previous value.
+            #[inline(always)]
+            pub(crate) fn fetch_max(&self, value: $integer, _: Ordering) -> $integer {
+                self.0.replace(self.0.get().max(value))
+            }
+
+            /// Returns the value.
+            pub(crate) fn into_inner(self) -> $integer { self.0.into_inner() }
+        }
+
+        //  Safety:
+        //  -   The `single-threaded` feature requires that the allocator be used from a single thread.
+        unsafe impl Sync for $name {}
+    };
+}
+
+cell_integer!(AtomicU8, u8);
+cell_integer!(AtomicU64, u64);
+cell_integer!(AtomicUsize, usize);
+
+/// Cell mimicking the API of `AtomicPtr`.
+pub(crate) struct AtomicPtr<T>(Cell<*mut T>);
+
+impl<T> AtomicPtr<T> {
+    /// Creates an instance.
+    pub(crate) const fn new(value: *mut T) -> Self { Self(Cell::new(value)) }
+
+    /// Returns the value.
+    #[inline(always)]
+    pub(crate) fn load(&self, _: Ordering) -> *mut T { self.0.get() }
+
+    /// Sets the value.
+    #[inline(always)]
+    pub(crate) fn store(&self, value: *mut T, _: Ordering) { self.0.set(value) }
+
+    /// Sets the value, returns the previous value.
+    #[inline(always)]
+    pub(crate) fn swap(&self, value: *mut T, _: Ordering) -> *mut T { self.0.replace(value) }
+
+    /// Sets the value if equal to `current`, returns the previous value on success, the actual value on failure.
+    #[inline(always)]
+    pub(crate) fn compare_exchange(&self, current: *mut T, new: *mut T, _: Ordering, _: Ordering)
+        -> Result<*mut T, *mut T>
+    {
+        let value = self.0.get();
+
+        if value != current {
+            return Err(value);
+        }
+
+        self.0.set(new);
+        Ok(value)
+    }
+
+    /// Identical to `compare_exchange`, as there is no spurious failure.
+    #[inline(always)]
+    pub(crate) fn compare_exchange_weak(&self, current: *mut T, new: *mut T, success: Ordering, failure: Ordering)
+        -> Result<*mut T, *mut T>
+    {
+        self.compare_exchange(current, new, success, failure)
+    }
+}
+
+impl<T> Default for AtomicPtr<T> {
+    fn default() -> Self { Self::new(ptr::null_mut()) }
+}
+
+//  Safety:
+//  -   The `single-threaded` feature requires that the allocator be used from a single thread.
+unsafe impl<T> Send for AtomicPtr<T> {}
+
+//  Safety:
+//  -   The `single-threaded` feature requires that the allocator be used from a single thread.
+unsafe impl<T> Sync for AtomicPtr<T> {}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn cell_integer_operations() {
+    let cell = AtomicUsize::new(3);
+
+    assert_eq!(3, cell.fetch_add(4, Ordering::Relaxed));
+    assert_eq!(7, cell.fetch_sub(2, Ordering::Relaxed));
+    assert_eq!(5, cell.fetch_or(8, Ordering::Relaxed));
+    assert_eq!(13, cell.fetch_and(9, Ordering::Relaxed));
+    assert_eq!(9, cell.fetch_max(4, Ordering::Relaxed));
+    assert_eq!(9, cell.swap(1, Ordering::Relaxed));
+
+    assert_eq!(Err(1), cell.compare_exchange(2, 3, Ordering::Relaxed, Ordering::Relaxed));
+    assert_eq!(Ok(1), cell.compare_exchange(1, 3, Ordering::Relaxed, Ordering::Relaxed));
+    assert_eq!(3, cell.into_inner());
+}
+
+#[test]
+fn cell_ptr_operations() {
+    let (mut a, mut b) = (1, 2);
+    let (a, b): (*mut i32, *mut i32) = (&mut a, &mut b);
+
+    let cell = AtomicPtr::<i32>::default();
+    assert!(cell.load(Ordering::Relaxed).is_null());
+
+    assert_eq!(Ok(ptr::null_mut()), cell.compare_exchange(ptr::null_mut(), a, Ordering::Relaxed, Ordering::Relaxed));
+    assert_eq!(Err(a), cell.compare_exchange(ptr::null_mut(), b, Ordering::Relaxed, Ordering::Relaxed));
+    assert_eq!(a, cell.swap(b, Ordering::Relaxed));
+    assert_eq!(b, cell.load(Ordering::Relaxed));
+}
+
+} // mod tests
+
+} // mod cells
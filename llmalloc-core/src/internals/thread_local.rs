@@ -3,28 +3,66 @@
 //! A ThreadLocal instance is a thread-local cache used to speed up Normal allocations.
 
 use core::{
+    cell::Cell,
     marker,
     mem,
     ptr::{self, NonNull},
 };
 
-use crate::{ClassSize, Configuration};
+use crate::{ClassSize, Configuration, Criticality};
 use crate::internals::{
     blocks::{BlockForeign, BlockForeignList, BlockPtr},
     large_page::LargePage,
+    statistics::AtomicStatistics,
 };
 
+#[cfg(feature = "histogram")]
+use crate::internals::histogram::AtomicSizeHistogram;
+
 /// ThreadLocal
 ///
 /// Thread-local caching to speed up Normal allocations.
-#[repr(C)]
+///
+/// The fields are laid out by temperature: the owner and the counters of Normal allocations, touched by each and every
+/// allocation and deallocation, share the first cache line, followed by the locally cached pages, starting on a cache
+/// line boundary so that the pages of the smallest class sizes share a single cache line, and lastly by the cold
+/// fields, the first of which fills the last cache line of the locally cached pages.
+#[repr(C, align(64))]
 pub(crate) struct ThreadLocal<C> {
     //  Owner (socket).
+    //
+    //  Kept first, as the only field overwritten while the instance is not in use, see `reinitialize`.
     owner: *mut (),
+    //  State of the owning thread, reset by `reinitialize`.
+    //
+    //  Packed between the owner and the statistics, 24 bytes at most, so that the counters of Normal allocations still
+    //  share the cache line of the owner.
+    //
+    //  Criticality, as declared by the owning thread.
+    criticality: Cell<Criticality>,
+    //  Number of scopes forbidding allocations, as entered by the owning thread.
+    forbidden_scopes: Cell<u32>,
+    //  Cached NUMA node, as recorded by the owning thread, opaque to the core.
+    node_cache: Cell<u64>,
+    //  Frame region, as entered by the owning thread, opaque to the core.
+    frame: Cell<Option<NonNull<u8>>>,
+    //  Statistics, written by the owning thread only, read by any thread.
+    //
+    //  Kept right after the state of the owning thread, within the first cache line, so that the counters of Normal
+    //  allocations share the cache line of the owner.
+    statistics: AtomicStatistics,
     //  Locally cached pages, 1 per class-size.
     local_pages: [LargePagePtr; 63],
+    //  Idleness, as recorded by the owning thread, opaque to the core.
+    //
+    //  Kept right after the locally cached pages, in the last 8 bytes of their last cache line, as only touched as the
+    //  thread is checked for idleness.
+    idle_cache: Cell<u64>,
     //  Foreign allocations, temporarily stored here to minimize touching another thread's cache lines.
     foreign_allocations: [BlockForeignList; 8],
+    //  Histogram of the requested sizes, written by the owning thread only, read by any thread.
+    #[cfg(feature = "histogram")]
+    histogram: AtomicSizeHistogram,
     _configuration: marker::PhantomData<C>,
 }
 
@@ -36,22 +74,131 @@ impl<C> ThreadLocal<C>
     pub(crate) fn new(owner: *mut ()) -> Self {
         //  Safety:
         //  -   Pointers can safely be zeroed.
+        let criticality = Cell::new(Criticality::Normal);
+        let forbidden_scopes = Cell::new(0);
+        let node_cache = Cell::new(0);
+        let frame = Cell::new(None);
+        let statistics = AtomicStatistics::new();
         let local_pages: [LargePagePtr; 63] = unsafe { mem::zeroed() };
+        let idle_cache = Cell::new(0);
         let foreign_allocations = Default::default();
+        #[cfg(feature = "histogram")]
+        let histogram = AtomicSizeHistogram::new();
         let _configuration = marker::PhantomData;
 
         assert!(local_pages.len() >= ClassSize::number_classes(C::LARGE_PAGE_SIZE));
 
-        Self { owner, local_pages, foreign_allocations, _configuration, }
+        Self {
+            owner,
+            criticality,
+            forbidden_scopes,
+            node_cache,
+            frame,
+            statistics,
+            local_pages,
+            idle_cache,
+            foreign_allocations,
+            #[cfg(feature = "histogram")]
+            histogram,
+            _configuration,
+        }
+    }
+
+    /// In-place re-initializes an instance, preserving its statistics and histogram.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `this` points to memory previously initialized as an instance of `ThreadLocal`, of which only
+    ///     the memory preceding the statistics may have been overwritten since.
+    /// -   Assumes exclusive access to the memory of the instance, with the exception of the statistics and histogram.
+    pub(crate) unsafe fn reinitialize(this: NonNull<Self>, owner: *mut ()) {
+        let this = this.as_ptr();
+
+        ptr::write(ptr::addr_of_mut!((*this).owner), owner);
+        ptr::write(ptr::addr_of_mut!((*this).criticality), Cell::new(Criticality::Normal));
+        ptr::write(ptr::addr_of_mut!((*this).forbidden_scopes), Cell::new(0));
+        ptr::write(ptr::addr_of_mut!((*this).node_cache), Cell::new(0));
+        ptr::write(ptr::addr_of_mut!((*this).frame), Cell::new(None));
+        ptr::write(ptr::addr_of_mut!((*this).local_pages), mem::zeroed());
+        ptr::write(ptr::addr_of_mut!((*this).idle_cache), Cell::new(0));
+        ptr::write(ptr::addr_of_mut!((*this).foreign_allocations), Default::default());
     }
 
     /// Returns the owner.
     pub(crate) fn owner(&self) -> *mut () { self.owner }
 
+    /// Returns the criticality.
+    pub(crate) fn criticality(&self) -> Criticality { self.criticality.get() }
+
+    /// Sets the criticality.
+    pub(crate) fn set_criticality(&self, criticality: Criticality) { self.criticality.set(criticality); }
+
+    /// Returns the number of scopes forbidding allocations.
+    pub(crate) fn forbidden_scopes(&self) -> u32 { self.forbidden_scopes.get() }
+
+    /// Sets the number of scopes forbidding allocations.
+    pub(crate) fn set_forbidden_scopes(&self, scopes: u32) { self.forbidden_scopes.set(scopes); }
+
+    /// Returns the cached NUMA node.
+    pub(crate) fn node_cache(&self) -> u64 { self.node_cache.get() }
+
+    /// Sets the cached NUMA node.
+    pub(crate) fn set_node_cache(&self, node_cache: u64) { self.node_cache.set(node_cache); }
+
+    /// Returns the frame region.
+    pub(crate) fn frame(&self) -> Option<NonNull<u8>> { self.frame.get() }
+
+    /// Sets the frame region.
+    pub(crate) fn set_frame(&self, frame: Option<NonNull<u8>>) { self.frame.set(frame); }
+
+    /// Returns the recorded idleness.
+    pub(crate) fn idle_cache(&self) -> u64 { self.idle_cache.get() }
+
+    /// Sets the recorded idleness.
+    pub(crate) fn set_idle_cache(&self, idle_cache: u64) { self.idle_cache.set(idle_cache); }
+
+    /// Returns the statistics.
+    pub(crate) fn statistics(&self) -> &AtomicStatistics { &self.statistics }
+
+    /// Returns the histogram of the requested sizes.
+    #[cfg(feature = "histogram")]
+    pub(crate) fn histogram(&self) -> &AtomicSizeHistogram { &self.histogram }
+
+    /// Returns the offset of the histogram, from the start of the instance.
+    #[cfg(feature = "histogram")]
+    pub(crate) fn histogram_offset() -> usize {
+        let this = mem::MaybeUninit::<Self>::uninit();
+        let start = this.as_ptr() as usize;
+
+        //  Safety:
+        //  -   No reference is formed to uninitialized memory.
+        let histogram = unsafe { ptr::addr_of!((*this.as_ptr()).histogram) } as usize;
+
+        histogram - start
+    }
+
+    /// Returns the offset of the statistics, from the start of the instance.
+    ///
+    /// All memory prior to this offset may be overwritten while the instance is not in use.
+    pub(crate) fn statistics_offset() -> usize {
+        let this = mem::MaybeUninit::<Self>::uninit();
+        let start = this.as_ptr() as usize;
+
+        //  Safety:
+        //  -   No reference is formed to uninitialized memory.
+        let statistics = unsafe { ptr::addr_of!((*this.as_ptr()).statistics) } as usize;
+
+        statistics - start
+    }
+
     /// Flushes all the memory retained by the current instance.
-    pub(crate) fn flush<F>(&self, mut recycler: F)
+    ///
+    /// Calls `recycler` with any `LargePage` that was adrift and was caught, and `inbound` with the foreign allocations
+    /// belonging to the `LargePage` of another owner.
+    pub(crate) fn flush<F, I>(&self, mut recycler: F, mut inbound: I)
         where
-            F: FnMut(NonNull<LargePage>)
+            F: FnMut(NonNull<LargePage>),
+            I: FnMut(&LargePage, &BlockForeignList),
     {
         //  The order in which the pages and foreign allocations are returned is inconsequential as it is guaranteed
         //  that the foreign allocations do not belong to the local pages.
@@ -81,7 +228,7 @@ impl<C> ThreadLocal<C>
             //  -   The access to `foreign_list` is exclusive.
             //  -   `foreign_list` is not empty.
             //  -   `foreign_list` belongs to the page.
-            unsafe { large_page.refill_foreign(foreign_list, &mut recycler) };
+            unsafe { self.flush_foreign(foreign_list, large_page, &mut recycler, &mut inbound) };
         }
     }
 
@@ -93,6 +240,7 @@ impl<C> ThreadLocal<C>
     ///
     /// -   Assumes that `self` is not concurrently accessed by another thread.
     /// -   Assumes that `class_size` is within bounds.
+    #[inline(always)]
     pub(crate) unsafe fn allocate<F>(&self, class_size: ClassSize, provider: F) -> Option<NonNull<u8>>
         where
             F: FnOnce(ClassSize) -> Option<NonNull<LargePage>>
@@ -113,7 +261,7 @@ impl<C> ThreadLocal<C>
             //  -   It is assumed that this function is never called from multiple threads concurrently.
             let result = large_page.allocate();
 
-            if !result.is_none() {
+            if result.is_some() {
                 return result;
             }
 
@@ -125,18 +273,103 @@ impl<C> ThreadLocal<C>
         self.slow_allocate(page, class_size, provider)
     }
 
+    /// Ensures that the locally cached page of the specified size holds at least `count` available cells, so that the
+    /// next `count` allocations of this size take the fast path.
+    ///
+    /// If necessary, queries `provider` to require a new LargePage of the appropriate class-size, and calls `recycler`
+    /// with whichever of the current and new pages holds the fewer cells, unless exhausted.
+    ///
+    /// Returns the minimum of the number of cells available in the locally cached page and `count`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `self` is not concurrently accessed by another thread.
+    /// -   Assumes that `class_size` is within bounds.
+    pub(crate) unsafe fn reserve_ahead<F, R>(&self, class_size: ClassSize, count: usize, provider: F, recycler: R)
+        -> usize
+        where
+            F: FnOnce(ClassSize) -> Option<NonNull<LargePage>>,
+            R: FnOnce(NonNull<LargePage>),
+    {
+        debug_assert!(class_size.value() < self.local_pages.len());
+
+        //  Safety:
+        //  -   `class_size` is assumed to be within bounds.
+        let page = self.local_pages.get_unchecked(class_size.value());
+
+        let mut available = 0;
+
+        if let Some(large_page) = page.get() {
+            //  Safety:
+            //  -   `page` is not null.
+            let large_page = large_page.as_ref();
+
+            //  Safety:
+            //  -   It is assumed that this function is never called from multiple threads concurrently.
+            available = large_page.available(count);
+
+            //  An exhausted page is either refilled by its freed cells, or cast adrift, as on allocation.
+            if available == 0 {
+                match large_page.allocate() {
+                    Some(cell) => {
+                        large_page.deallocate(cell);
+                        available = large_page.available(count);
+                    },
+                    None => {
+                        page.replace_with_null();
+                    },
+                }
+            }
+
+            if available >= count {
+                return count;
+            }
+        }
+
+        let fresh = match provider(class_size) {
+            Some(fresh) => fresh,
+            None => return available,
+        };
+
+        //  Safety:
+        //  -   `fresh` is not null.
+        let large_page = fresh.as_ref();
+
+        self.refill_foreign_allocations(large_page);
+
+        //  Safety:
+        //  -   It is assumed that this function is never called from multiple threads concurrently.
+        let fresh_available = large_page.available(count);
+
+        if fresh_available <= available {
+            recycler(fresh);
+            return available;
+        }
+
+        if let Some(previous) = page.replace_with_null() {
+            recycler(previous);
+        }
+
+        page.set(Some(fresh));
+
+        fresh_available
+    }
+
     /// Deallocates a cell.
     ///
-    /// Calls `recycler` with any `LargePage` that was adrift and was caught.
+    /// Calls `recycler` with any `LargePage` that was adrift and was caught, and `inbound` with the batches of cells
+    /// belonging to the `LargePage` of another owner, in lieu of returning them to their `LargePage`.
     ///
     /// #   Safety
     ///
     /// -   Assumes that `self` is not concurrently accessed by another thread.
     /// -   Assumes that the `ptr` points to memory that is no longer in use.
     /// -   Assumes that `ptr` belongs to a `LargePage`.
-    pub(crate) unsafe fn deallocate<F>(&self, ptr: NonNull<u8>, recycler: F)
+    #[inline(always)]
+    pub(crate) unsafe fn deallocate<F, I>(&self, ptr: NonNull<u8>, recycler: F, inbound: I)
         where
-            F: FnMut(NonNull<LargePage>)
+            F: FnMut(NonNull<LargePage>),
+            I: FnMut(&LargePage, &BlockForeignList),
     {
         //  Safety:
         //  -   `ptr` is assumed to belong to a `LargePage`.
@@ -164,7 +397,7 @@ impl<C> ThreadLocal<C>
             //  -   A single thread is assumed to be calling `deallocate` at a time.
             //  -   `ptr` is assumed to point to memory that is no longer in use.
             //  -   `ptr` is assumed to belong to `page`.
-            self.foreign_deallocate(ptr, page, recycler)
+            self.foreign_deallocate(ptr, page, recycler, inbound)
         }
     }
 
@@ -188,6 +421,19 @@ impl<C> ThreadLocal<C>
         //  -   `page` is not null.
         let large_page = page.as_ref();
 
+        self.refill_foreign_allocations(large_page);
+
+        //  Safety:
+        //  -   It is assumed that this function is never called from multiple threads concurrently.
+        large_page.allocate()
+    }
+
+    //  Internal; Refills `large_page`, newly cached, from the `foreign_allocations` which belong to it.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `self` is not concurrently accessed by another thread.
+    unsafe fn refill_foreign_allocations(&self, large_page: &LargePage) {
         //  Some of the so-called `foreign_allocations` may actually be local allocations now!
         for foreign_list in &self.foreign_allocations {
             if foreign_list.is_empty() {
@@ -198,24 +444,29 @@ impl<C> ThreadLocal<C>
             //  -   It is assumed that this function is never called from multiple threads concurrently.
             large_page.refill_local(foreign_list);
         }
-
-        //  Safety:
-        //  -   It is assumed that this function is never called from multiple threads concurrently.
-        large_page.allocate()
     }
 
     //  Internal; Deallocates a cell from the specified foreign page.
     //
-    //  Calls `recycler` with any `LargePage` that was adrift and was caught.
+    //  Calls `recycler` with any `LargePage` that was adrift and was caught, and `inbound` with the batches of cells of
+    //  the `LargePage` of another owner.
     // 
     //  #   Safety
     // 
     /// -   Assumes that `self` is not concurrently accessed by another thread.
     //  -   Assumes that the `ptr` points to memory that is no longer in use.
     //  -   Assumes that `ptr` belongs to `page`.
-    unsafe fn foreign_deallocate<F>(&self, ptr: NonNull<u8>, page: NonNull<LargePage>, mut recycler: F)
+    #[inline(never)]
+    unsafe fn foreign_deallocate<F, I>(
+        &self,
+        ptr: NonNull<u8>,
+        page: NonNull<LargePage>,
+        mut recycler: F,
+        mut inbound: I,
+    )
         where
-            F: FnMut(NonNull<LargePage>)
+            F: FnMut(NonNull<LargePage>),
+            I: FnMut(&LargePage, &BlockForeignList),
     {
         debug_assert!(C::LARGE_PAGE_SIZE.round_down(ptr.as_ptr() as usize) == C::LARGE_PAGE_SIZE.round_down(page.as_ptr() as usize));
 
@@ -236,7 +487,7 @@ impl<C> ThreadLocal<C>
         //  -   Evict a list to another page prematurely, it'll be a waste.
         if large_page.flush_threshold() == 1 {
             let foreign_list = BlockForeignList::default();
-            Self::push(block, &foreign_list, large_page, &mut recycler);
+            self.push(block, &foreign_list, large_page, &mut recycler, &mut inbound);
             return;
         }
 
@@ -253,7 +504,7 @@ impl<C> ThreadLocal<C>
                 continue;
             }
 
-            Self::push(block, foreign_list, large_page, &mut recycler);
+            self.push(block, foreign_list, large_page, &mut recycler, &mut inbound);
             return;
         }
 
@@ -286,16 +537,16 @@ impl<C> ThreadLocal<C>
 
                 //  Safety:
                 //  -   `page` is not null.
-                page.as_ref().refill_foreign(foreign_list, &mut recycler);
+                self.flush_foreign(foreign_list, page.as_ref(), &mut recycler, &mut inbound);
             } else {
                 debug_assert!(false, "How is the selected list empty if its score is not MAX?")
             }
         }
 
-        Self::push(block, foreign_list, large_page, &mut recycler);
+        self.push(block, foreign_list, large_page, &mut recycler, &mut inbound);
     }
 
-    //  Internal; Pushes a cell into a foreign-list, possibly refilling the page.
+    //  Internal; Pushes a cell into a foreign-list, possibly flushing it.
     // 
     //  Returns the page if it was adrift and has been caught, null otherwise.
     // 
@@ -305,14 +556,17 @@ impl<C> ThreadLocal<C>
     //  -   Assumes that `cell` points to memory that is no longer in use.
     //  -   Assumes that `cell` is compatible with `foreign_list`.
     //  -   Assumes that `cell` belongs to `large_page`.
-    unsafe fn push<F>(
+    unsafe fn push<F, I>(
+        &self,
         cell: NonNull<BlockForeign>,
         foreign_list: &BlockForeignList,
         large_page: &LargePage,
         recycler: F,
+        inbound: I,
     )
         where
-            F: FnMut(NonNull<LargePage>)
+            F: FnMut(NonNull<LargePage>),
+            I: FnMut(&LargePage, &BlockForeignList),
     {
         debug_assert!(foreign_list.is_compatible::<C>(cell));
         debug_assert!(C::LARGE_PAGE_SIZE.round_down(cell.as_ptr() as usize)
@@ -326,7 +580,36 @@ impl<C> ThreadLocal<C>
             return;
         }
 
-        large_page.refill_foreign(foreign_list, recycler);
+        self.flush_foreign(foreign_list, large_page, recycler, inbound);
+    }
+
+    //  Internal; Flushes a foreign-list, refilling its page, or handing it to `inbound` if the page has another owner.
+    //
+    //  The cells of the pages of another owner are thus batched as those of the pages of `self`, so that their owner is
+    //  touched once per batch, rather than once per cell.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes that `self` is not concurrently accessed by another thread.
+    //  -   Assumes that `foreign_list` is not empty.
+    //  -   Assumes that the cells of `foreign_list` belong to `large_page`.
+    unsafe fn flush_foreign<F, I>(
+        &self,
+        foreign_list: &BlockForeignList,
+        large_page: &LargePage,
+        recycler: F,
+        mut inbound: I,
+    )
+        where
+            F: FnMut(NonNull<LargePage>),
+            I: FnMut(&LargePage, &BlockForeignList),
+    {
+        if large_page.owner() == self.owner {
+            large_page.refill_foreign(foreign_list, recycler);
+        } else {
+            inbound(large_page, foreign_list);
+        }
+
         debug_assert!(foreign_list.is_empty());
     }
 }
@@ -365,7 +648,7 @@ type TestThreadLocal = ThreadLocal<TestConfiguration>;
 
 #[derive(Clone, Copy)]
 #[repr(align(131072))]
-struct HugePageStore([usize; 16384]);
+struct HugePageStore(#[allow(dead_code)] [usize; 16384]);
 
 impl HugePageStore {
     /// Creates a Recycler, which will memorize the recycled pages.
@@ -402,7 +685,7 @@ impl HugePageStore {
             "{} >= {}", page.as_ptr() as usize, address + Self::huge_page_size());
 
         let offset = page.as_ptr() as usize - self.address() as usize;
-        assert!(offset % Self::large_page_size() == 0, "{} % {} != 0", offset, Self::large_page_size());
+        assert!(offset.is_multiple_of(Self::large_page_size()), "{} % {} != 0", offset, Self::large_page_size());
 
         let owner = page.as_ref().owner() as usize;
         assert!(owner == self.address() as usize, "{} != {}", owner, self.address() as usize);
@@ -432,7 +715,7 @@ impl HugePageStore {
     /// Casts a page adrift, by exhausting it. Returns the number of allocations performed.
     unsafe fn cast_adrift(&self, page: &LargePage) -> usize {
         let mut number_cells = 0;
-        while !page.allocate().is_none() { number_cells += 1 }
+        while page.allocate().is_some() { number_cells += 1 }
         number_cells
     }
 
@@ -491,6 +774,7 @@ impl HugePageStore {
     }
 
     //  Internal; creates a `place` to initialize a `LargePage` in.
+    #[allow(clippy::mut_from_ref)]
     unsafe fn place(&self, index: usize) -> &mut [u8] {
         let place = self.get_large_page(index).as_ptr() as *mut u8;
 
@@ -514,12 +798,47 @@ impl Default for HugePageStore {
     }
 }
 
+//  Inbound of a ThreadLocal owning all the pages it deallocates to.
+fn no_inbound(_: &LargePage, _: &BlockForeignList) { panic!("No inbound!") }
+
 #[cfg(target_pointer_width = "64")]
 #[test]
 fn size() {
     const CACHE_LINE_SIZE: usize = 64;
 
-    assert_eq!(9 * CACHE_LINE_SIZE, mem::size_of::<ThreadLocal<TestConfiguration>>());
+    #[cfg(not(feature = "histogram"))]
+    assert_eq!(11 * CACHE_LINE_SIZE, mem::size_of::<ThreadLocal<TestConfiguration>>());
+    assert_eq!(32, TestThreadLocal::statistics_offset());
+
+    #[cfg(feature = "histogram")]
+    {
+        assert_eq!(19 * CACHE_LINE_SIZE, mem::size_of::<ThreadLocal<TestConfiguration>>());
+        assert_eq!(11 * CACHE_LINE_SIZE, TestThreadLocal::histogram_offset());
+    }
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn hot_cache_line() {
+    const CACHE_LINE_SIZE: usize = 64;
+
+    let thread_local = TestThreadLocal::default();
+
+    let start = &thread_local as *const _ as usize;
+    let owner = &thread_local.owner as *const _ as usize;
+    let normal = &thread_local.statistics as *const _ as usize;
+    let local_pages = &thread_local.local_pages as *const _ as usize;
+    let idle_cache = &thread_local.idle_cache as *const _ as usize;
+
+    //  The owner and the counters of Normal allocations share the first cache line.
+    assert_eq!(0, owner - start);
+    assert!(normal - start + 32 <= CACHE_LINE_SIZE);
+
+    //  The locally cached pages start on a cache line boundary.
+    assert_eq!(0, (local_pages - start) % CACHE_LINE_SIZE);
+
+    //  The idleness fills the last cache line of the locally cached pages.
+    assert_eq!(0, (idle_cache - start + 8) % CACHE_LINE_SIZE);
 }
 
 #[test]
@@ -527,6 +846,25 @@ fn new() {
     TestThreadLocal::default();
 }
 
+#[test]
+fn reinitialize() {
+    let mut thread_local = TestThreadLocal::default();
+
+    thread_local.set_criticality(Criticality::Critical);
+    thread_local.set_forbidden_scopes(2);
+    thread_local.set_node_cache(3);
+    thread_local.set_frame(Some(NonNull::dangling()));
+    thread_local.set_idle_cache(4);
+
+    unsafe { TestThreadLocal::reinitialize(NonNull::from(&mut thread_local), ptr::null_mut()) };
+
+    assert_eq!(Criticality::Normal, thread_local.criticality());
+    assert_eq!(0, thread_local.forbidden_scopes());
+    assert_eq!(0, thread_local.node_cache());
+    assert_eq!(None, thread_local.frame());
+    assert_eq!(0, thread_local.idle_cache());
+}
+
 #[test]
 fn flush() {
     const LOCAL_PAGE: usize = 1;
@@ -535,7 +873,7 @@ fn flush() {
 
     let store = HugePageStore::default();
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
 
     thread_local.local_pages[CLASS_SIZE.value()] =
         LargePagePtr::new(Some(unsafe { store.provide(LOCAL_PAGE, CLASS_SIZE) }));
@@ -544,7 +882,7 @@ fn flush() {
     thread_local.foreign_allocations[2] = unsafe { store.create_foreign_list(FOREIGN_PAGE, 3) };
 
     let mut recycled = [0; 1];
-    thread_local.flush(store.recycler(&mut recycled));
+    thread_local.flush(store.recycler(&mut recycled), no_inbound);
 
     assert_eq!(1, recycled[0]);
 
@@ -558,6 +896,38 @@ fn flush() {
     }
 }
 
+#[test]
+fn flush_inbound() {
+    const FOREIGN_PAGE: usize = 2;
+
+    let store = HugePageStore::default();
+
+    //  The pages of the store are owned by another owner.
+    let mut thread_local = TestThreadLocal::default();
+
+    unsafe { store.provide(FOREIGN_PAGE, ClassSize::new(5)) };
+    thread_local.foreign_allocations[2] = unsafe { store.create_foreign_list(FOREIGN_PAGE, 3) };
+
+    let mut inbound = Vec::new();
+
+    let mut recycled = [0; 1];
+    thread_local.flush(store.recycler(&mut recycled), |page, list| {
+        inbound.push((page as *const LargePage, list.len()));
+
+        //  Safety:
+        //  -   `list` is not empty, as it is flushed.
+        unsafe { list.steal() };
+    });
+
+    assert_eq!(0, recycled[0]);
+
+    //  The 3 allocations are handed over at once.
+    let page = store.get_large_page(FOREIGN_PAGE).as_ptr() as *const LargePage;
+    assert_eq!(vec![(page, 3)], inbound);
+
+    assert!(thread_local.foreign_allocations[2].is_empty());
+}
+
 #[test]
 fn allocate_fast() {
     const LOCAL_PAGE: usize = 1;
@@ -566,7 +936,7 @@ fn allocate_fast() {
     let store = HugePageStore::default();
     let local_page = unsafe { store.provide(LOCAL_PAGE, CLASS_SIZE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
 
     thread_local.local_pages[CLASS_SIZE.value()] = LargePagePtr::new(Some(local_page));
 
@@ -585,7 +955,7 @@ fn allocate_slow_initial() {
     let store = HugePageStore::default();
     let local_page = unsafe { store.provide(LOCAL_PAGE, CLASS_SIZE) };
 
-    let thread_local = TestThreadLocal::default();
+    let thread_local = TestThreadLocal::new(store.address());
 
     let p = unsafe {
         thread_local.allocate(CLASS_SIZE, |class_size| {
@@ -602,7 +972,7 @@ fn allocate_slow_initial() {
         let page = page.get();
 
         if index == CLASS_SIZE.value() {
-            assert!(!page.is_none(), "Page at {} is null!", index);
+            assert!(page.is_some(), "Page at {} is null!", index);
             assert_eq!(local_page.as_ptr() as usize, page.unwrap().as_ptr() as usize);
         } else {
             assert!(page.is_none(), "Page at {} is not null!", index);
@@ -641,7 +1011,7 @@ fn allocate_slow_exhausted() {
     //  Exaused `second_page`, stopping short of triggering the null allocation.
     unsafe { store.exhaust(second_page.as_ref(), FIRST_PAGE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
     thread_local.local_pages[CLASS_SIZE.value()] = LargePagePtr::new(Some(second_page));
 
     //  Attempt to allocate from an empty page, triggering a change of page.
@@ -660,7 +1030,7 @@ fn allocate_slow_exhausted() {
         let page = page.get();
 
         if index == CLASS_SIZE.value() {
-            assert!(!page.is_none(), "Page at {} is null!", index);
+            assert!(page.is_some(), "Page at {} is null!", index);
             assert_eq!(third_page.as_ptr() as usize, page.unwrap().as_ptr() as usize);
         } else {
             assert!(page.is_none(), "Page at {} is not null!", index);
@@ -680,7 +1050,7 @@ fn allocate_slow_exhausted_provider_exhausted() {
     //  Exaused `second_page`, stopping short of triggering the null allocation.
     unsafe { store.exhaust(second_page.as_ref(), FIRST_PAGE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
     thread_local.local_pages[CLASS_SIZE.value()] = LargePagePtr::new(Some(second_page));
 
     //  Attempt to allocate from an empty page, triggering a change of page.
@@ -710,12 +1080,21 @@ fn allocate_slow_exhausted_recover_foreign() {
     //  Exaused `second_page`, stopping short of triggering the null allocation.
     unsafe { store.exhaust(second_page.as_ref(), FIRST_PAGE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
     thread_local.local_pages[CLASS_SIZE.value()] = LargePagePtr::new(Some(second_page));
 
     thread_local.foreign_allocations[FOREIGN_LIST] = unsafe { store.create_foreign_list(THIRD_PAGE, 3) };
     let head = thread_local.foreign_allocations[FOREIGN_LIST].head().map(NonNull::cast);
 
+    //  The list is made of consecutive cells, the head being the last; the bitmap hands out the first instead.
+    let expected = if cfg!(feature = "bitmap") {
+        head.map(|head: NonNull<u8>| unsafe {
+            NonNull::new_unchecked(head.as_ptr().sub(2 * CLASS_SIZE.layout().size()))
+        })
+    } else {
+        head
+    };
+
     unsafe {
         let bound = FOREIGN_LIST + 1;
 
@@ -732,7 +1111,7 @@ fn allocate_slow_exhausted_recover_foreign() {
             Some(third_page)
         })
     };
-    assert_eq!(head, p);
+    assert_eq!(expected, p);
 
     //  In debug, throws if `p` doesn't belong to `third_page`.
     unsafe { third_page.as_ref().deallocate(p.unwrap()) };
@@ -741,7 +1120,7 @@ fn allocate_slow_exhausted_recover_foreign() {
         let page = page.get();
 
         if index == CLASS_SIZE.value() {
-            assert!(!page.is_none(), "Page at {} is null!", index);
+            assert!(page.is_some(), "Page at {} is null!", index);
             assert_eq!(third_page.as_ptr() as usize, page.unwrap().as_ptr() as usize);
         } else {
             assert!(page.is_none(), "Page at {} is not null!", index);
@@ -757,6 +1136,58 @@ fn allocate_slow_exhausted_recover_foreign() {
     }
 }
 
+#[test]
+fn reserve_ahead() {
+    const FIRST_PAGE: usize = 1;
+    const SECOND_PAGE: usize = 2;
+    const SCRATCH_PAGE: usize = 3;
+    const CLASS_SIZE: ClassSize = ClassSize::new(3);
+
+    let store = HugePageStore::default();
+    let first_page = unsafe { store.provide(FIRST_PAGE, CLASS_SIZE) };
+
+    let number_cells = unsafe { store.cast_adrift(store.provide(SCRATCH_PAGE, CLASS_SIZE).as_ref()) };
+
+    let thread_local = TestThreadLocal::new(store.address());
+    thread_local.local_pages[CLASS_SIZE.value()].set(Some(first_page));
+
+    //  The local page suffices.
+    let reserved = unsafe {
+        thread_local.reserve_ahead(CLASS_SIZE, number_cells, |_| panic!("No provider!"), |_| panic!("No recycler!"))
+    };
+    assert_eq!(number_cells, reserved);
+
+    //  No page suffices, and the fresh page holds no more cells than the local one.
+    let mut recycled = [0; 1];
+
+    let reserved = unsafe {
+        thread_local.reserve_ahead(CLASS_SIZE, number_cells + 1, |_| Some(store.provide(SECOND_PAGE, CLASS_SIZE)),
+            store.recycler(&mut recycled))
+    };
+    assert_eq!(number_cells, reserved);
+    assert_eq!(SECOND_PAGE, recycled[0]);
+    assert_eq!(Some(first_page), thread_local.local_pages[CLASS_SIZE.value()].get());
+
+    //  The local page no longer suffices, and is replaced.
+    for _ in 0..3 {
+        assert_ne!(None, unsafe { thread_local.allocate(CLASS_SIZE, |_| panic!("No provider!")) });
+    }
+
+    let second_page = unsafe { store.provide(SECOND_PAGE, CLASS_SIZE) };
+
+    let reserved = unsafe {
+        thread_local.reserve_ahead(CLASS_SIZE, number_cells, |_| Some(second_page), store.recycler(&mut recycled))
+    };
+    assert_eq!(number_cells, reserved);
+    assert_eq!(FIRST_PAGE, recycled[0]);
+    assert_eq!(Some(second_page), thread_local.local_pages[CLASS_SIZE.value()].get());
+
+    //  The reserved cells are allocated from the local page.
+    for _ in 0..number_cells {
+        assert_ne!(None, unsafe { thread_local.allocate(CLASS_SIZE, |_| panic!("No provider!")) });
+    }
+}
+
 #[test]
 fn deallocate_local() {
     const LOCAL_PAGE: usize = 1;
@@ -765,13 +1196,13 @@ fn deallocate_local() {
     let store = HugePageStore::default();
     let local_page = unsafe { store.provide(LOCAL_PAGE, CLASS_SIZE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
     thread_local.local_pages[CLASS_SIZE.value()] = LargePagePtr::new(Some(local_page));
 
     let p = unsafe { thread_local.allocate(CLASS_SIZE, |_| panic!("No provider!")) };
     assert_ne!(None, p);
 
-    unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!")) };
+    unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!"), no_inbound) };
 
     //  Immediately reusable!
     let q = unsafe { thread_local.allocate(CLASS_SIZE, |_| panic!("No provider!")) };
@@ -789,7 +1220,7 @@ fn deallocate_foreign_no_local() {
     let flush_threshold = unsafe { foreign_page.as_ref().flush_threshold() };
     assert!(flush_threshold > 5, "{} <= 5", flush_threshold);
 
-    let thread_local = TestThreadLocal::default();
+    let thread_local = TestThreadLocal::new(store.address());
 
     let mut allocated = [None; 5];
     for p in &mut allocated {
@@ -797,7 +1228,7 @@ fn deallocate_foreign_no_local() {
     }
 
     for p in &allocated {
-        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!")) };
+        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!"), no_inbound) };
     }
 
     assert_eq!(allocated.len(), thread_local.foreign_allocations[0].len());
@@ -814,7 +1245,7 @@ fn deallocate_foreign_with_local() {
     let local_page = unsafe { store.provide(LOCAL_PAGE, CLASS_SIZE) };
     let foreign_page = unsafe { store.provide(FOREIGN_PAGE, CLASS_SIZE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
     thread_local.local_pages[CLASS_SIZE.value()] = LargePagePtr::new(Some(local_page));
 
     let mut allocated = [None; 5];
@@ -823,7 +1254,7 @@ fn deallocate_foreign_with_local() {
     }
 
     for p in &allocated {
-        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!")) };
+        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!"), no_inbound) };
     }
 
     assert_eq!(allocated.len(), thread_local.foreign_allocations[0].len());
@@ -858,12 +1289,13 @@ fn deallocate_foreign_recycle_immediate() {
         foreign_page.as_ref().refill_foreign(&foreign_list, |_| panic!("No recycler!"));
     }
 
-    let thread_local = TestThreadLocal::default();
+    let thread_local = TestThreadLocal::new(store.address());
 
     //  Provokes flush, which provokes a catch!
     let mut recycled = [0; 1];
 
-    unsafe { thread_local.deallocate(allocated[FLUSH_TRESHOLD - 1].unwrap(), store.recycler(&mut recycled[..])) };
+    let last = allocated[FLUSH_TRESHOLD - 1].unwrap();
+    unsafe { thread_local.deallocate(last, store.recycler(&mut recycled[..]), no_inbound) };
 
     assert_eq!(FOREIGN_PAGE, recycled[0]);
 
@@ -900,16 +1332,17 @@ fn deallocate_foreign_recycle_foreign() {
         foreign_page.as_ref().refill_foreign(&foreign_list, |_| panic!("No recycler!"));
     }
 
-    let thread_local = TestThreadLocal::default();
+    let thread_local = TestThreadLocal::new(store.address());
 
     for p in &allocated[..(FLUSH_TRESHOLD - 1)] {
-        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!")) };
+        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!"), no_inbound) };
     }
 
     //  Provokes flush, which provokes a catch!
     let mut recycled = [0; 1];
 
-    unsafe { thread_local.deallocate(allocated[FLUSH_TRESHOLD - 1].unwrap(), store.recycler(&mut recycled[..])) };
+    let last = allocated[FLUSH_TRESHOLD - 1].unwrap();
+    unsafe { thread_local.deallocate(last, store.recycler(&mut recycled[..]), no_inbound) };
 
     assert_eq!(FOREIGN_PAGE, recycled[0]);
 
@@ -932,7 +1365,7 @@ fn deallocate_foreign_last_list() {
     unsafe { store.provide(THIRD_PAGE, CLASS_SIZE) };
     unsafe { store.provide(FOURTH_PAGE, CLASS_SIZE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
     thread_local.foreign_allocations[0] = unsafe { store.create_foreign_list(SECOND_PAGE, 3) };
     thread_local.foreign_allocations[1] = unsafe { store.create_foreign_list(THIRD_PAGE, 3) };
     thread_local.foreign_allocations[3] = unsafe { store.create_foreign_list(FOURTH_PAGE, 3) };
@@ -943,7 +1376,7 @@ fn deallocate_foreign_last_list() {
     }
 
     for p in &allocated {
-        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!")) };
+        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!"), no_inbound) };
     }
 
     assert_eq!(allocated.len(), thread_local.foreign_allocations[2].len());
@@ -961,7 +1394,7 @@ fn deallocate_foreign_kick_out_longest_list() {
     let other = HugePageStore::default();
     let foreign_page = unsafe { store.provide(FOREIGN_PAGE, CLASS_SIZE) };
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
 
     unsafe {
         let bound = LONGEST_LIST + 1;
@@ -979,7 +1412,7 @@ fn deallocate_foreign_kick_out_longest_list() {
 
     //  Kicks out the longest list (at 2), to store those cells instead.
     for p in &allocated {
-        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!")) };
+        unsafe { thread_local.deallocate(p.unwrap(), |_| panic!("No recycler!"), no_inbound) };
     }
 
     assert_eq!(allocated.len(), thread_local.foreign_allocations[LONGEST_LIST].len());
@@ -1000,7 +1433,7 @@ fn deallocate_foreign_recycle_longest_list() {
     let catch_threshold = unsafe { foreign_page.as_ref().catch_threshold() };
     assert!(catch_threshold > 3, "{} <= 3", catch_threshold);
 
-    let mut thread_local = TestThreadLocal::default();
+    let mut thread_local = TestThreadLocal::new(store.address());
 
     unsafe {
         let bound = LONGEST_LIST + 1;
@@ -1023,7 +1456,7 @@ fn deallocate_foreign_recycle_longest_list() {
     //  Kicks out the longest list (at 2), recycling its page, to store those cells instead.
     let mut recycled = [0; 1];
     for p in &allocated {
-        unsafe { thread_local.deallocate(p.unwrap(), store.recycler(&mut recycled[..])) };
+        unsafe { thread_local.deallocate(p.unwrap(), store.recycler(&mut recycled[..]), no_inbound) };
     }
 
     assert_eq!(KICKED_PAGE, recycled[0]);
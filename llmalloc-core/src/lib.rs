@@ -2,6 +2,10 @@
 
 #![deny(missing_docs)]
 
+#![cfg_attr(all(feature = "panic-free", not(test)), deny(
+    clippy::expect_used, clippy::panic, clippy::todo, clippy::unimplemented, clippy::unreachable, clippy::unwrap_used
+))]
+
 //! Building blocks for a low-latency allocator.
 //!
 //! llmalloc-core is a set of building blocks to build a custom low-latency malloc replacement with ease. It contains:
@@ -15,7 +15,7 @@ pub(crate) fn is_sufficiently_aligned_for(ptr: NonNull<u8>, alignment: PowerOf2)
 //  the thread currently using the LargePage.
 #[repr(align(128))]
 #[derive(Default)]
-pub(crate) struct PrefetchGuard(u8);
+pub(crate) struct PrefetchGuard(#[allow(dead_code)] u8);
 
 #[cfg(test)]
 mod tests {
@@ -60,7 +60,7 @@ impl<Global, Local> Bursty<Global, Local> {
     /// #   Warning
     ///
     /// Access is provided _without_ joining the threads first.
-    pub fn global(&self) -> &Global { &*self.global }
+    pub fn global(&self) -> &Global { &self.global }
 
     /// Returns a clone of the Local state.
     ///
@@ -112,7 +112,7 @@ impl<Global, Local> Drop for Bursty<Global, Local> {
 pub struct BurstyBuilder<Global, Local> {
     global: Arc<Global>,
     locals: Vec<Local>,
-    steps: Vec<Vec<Box<dyn FnMut(&Global, &mut Local) + Send + 'static>>>,
+    steps: Vec<Vec<Step<Global, Local>>>,
     rendez_vous: Vec<RendezVous>,
 }
 
@@ -126,7 +126,7 @@ impl<Global, Local> BurstyBuilder<Global, Local>
         let global = Arc::new(global);
         let steps = {
             let mut steps = vec!();
-            steps.resize_with(locals.len(), || vec!());
+            steps.resize_with(locals.len(), Vec::new);
             steps
         };
         let rendez_vous = vec!(RendezVous::new(locals.len()));
@@ -204,9 +204,9 @@ impl<Global, Local> BurstyBuilder<Global, Local>
     ///
     /// The threads start immediately.
     pub fn launch(mut self, iterations: usize) -> Bursty<Global, Local> {
-        assert!(self.steps.len() > 0,
+        assert!(!self.steps.is_empty(),
             "Cannot launch a burst test without a single thread");
-        assert!(self.steps[0].len() > 0,
+        assert!(!self.steps[0].is_empty(),
             "Cannot launch a burst test without a single step");
 
         //  The algorithm used for lock-step only works with a minimum of 3 steps, including the last step added below.
@@ -236,7 +236,7 @@ impl<Global, Local> BurstyBuilder<Global, Local>
         let mut threads = vec!();
         let rendez_vous = Arc::new(self.rendez_vous);
 
-        for (mut local, mut serie) in self.locals.into_iter().zip(self.steps.into_iter()) {
+        for (mut local, mut serie) in self.locals.into_iter().zip(self.steps) {
             let global = self.global.clone();
             let rendez_vous = rendez_vous.clone();
 
@@ -266,6 +266,9 @@ impl<Global, Local> BurstyBuilder<Global, Local>
 //  Implementation details
 //
 
+//  A single step of a single thread.
+type Step<Global, Local> = Box<dyn FnMut(&Global, &mut Local) + Send + 'static>;
+
 //  If a single thread panics, then we need to abort the execution of all threads.
 struct PoisonGuard(Arc<Vec<RendezVous>>);
 
@@ -303,7 +306,7 @@ impl RendezVous {
     fn reset(&self) {
         let mut count = self.load();
 
-        while let Err(_) = self.0.0.compare_exchange(count as isize, self.0.1, Ordering::Relaxed, Ordering::Relaxed) {
+        while self.0.0.compare_exchange(count as isize, self.0.1, Ordering::Relaxed, Ordering::Relaxed).is_err() {
             count = self.load();
         }
     }
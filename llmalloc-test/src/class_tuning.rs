@@ -0,0 +1,271 @@
+//! A tuner of size classes, guided by a profile of the requested allocation sizes.
+//!
+//! The tuner accumulates a trace of requested sizes, and derives the table of class sizes minimizing the memory wasted
+//! by rounding each request up to its class size, for a given number of classes.
+//!
+//! The class sizes of llmalloc are currently derived geometrically, by `ClassSize`, rather than read from a table: the
+//! emitted table is a const array ready to be plugged into a table-driven `Configuration`, and in the meantime allows
+//! measuring how far the built-in geometry is from the optimum for the workload, with `ClassTuner::waste`.
+
+use std::{collections::BTreeMap, fmt};
+
+use llmalloc_core::{ClassSize, Configuration, Properties};
+
+/// A tuner of size classes.
+///
+/// The requested sizes are rounded up to a multiple of the minimum alignment of class sizes before being recorded.
+#[derive(Clone, Debug, Default)]
+pub struct ClassTuner {
+    //  Number of requests, per rounded up size.
+    counts: BTreeMap<usize, u64>,
+}
+
+impl ClassTuner {
+    /// The granularity of class sizes, in bytes.
+    pub const GRANULARITY: usize = 16;
+
+    /// Creates an empty instance.
+    pub fn new() -> Self { Self::default() }
+
+    /// Records one request of `size` bytes.
+    pub fn record(&mut self, size: usize) { self.record_many(size, 1); }
+
+    /// Records `count` requests of `size` bytes.
+    pub fn record_many(&mut self, size: usize, count: u64) {
+        if count == 0 {
+            return;
+        }
+
+        let size = size.max(1).div_ceil(Self::GRANULARITY) * Self::GRANULARITY;
+
+        *self.counts.entry(size).or_insert(0) += count;
+    }
+
+    /// Records all the requests of `trace`.
+    pub fn record_trace<I>(&mut self, trace: I)
+        where
+            I: IntoIterator<Item = usize>,
+    {
+        for size in trace {
+            self.record(size);
+        }
+    }
+
+    /// Returns the number of requests recorded.
+    pub fn total(&self) -> u64 { self.counts.values().sum() }
+
+    /// Returns the number of distinct sizes recorded, after rounding.
+    pub fn distinct(&self) -> usize { self.counts.len() }
+
+    /// Returns the table of at most `number_classes` class sizes minimizing the waste, for the requests recorded.
+    ///
+    /// The largest class size is always the largest size recorded, so that every request fits.
+    ///
+    /// The complexity is quadratic in the number of distinct sizes recorded, and linear in `number_classes`.
+    ///
+    /// #   Panics
+    ///
+    /// If `number_classes` is 0.
+    pub fn optimize(&self, number_classes: usize) -> ClassTable {
+        assert!(number_classes > 0);
+
+        let sizes: Vec<_> = self.counts.keys().copied().collect();
+        let counts: Vec<_> = self.counts.values().copied().collect();
+        let n = sizes.len();
+
+        if n <= number_classes {
+            return ClassTable { sizes };
+        }
+
+        //  `requests[i]` and `bytes[i]` are the number and total size of the requests of the `i` smallest sizes.
+        let mut requests = vec![0u128; n + 1];
+        let mut bytes = vec![0u128; n + 1];
+
+        for i in 0..n {
+            requests[i + 1] = requests[i] + counts[i] as u128;
+            bytes[i + 1] = bytes[i] + counts[i] as u128 * sizes[i] as u128;
+        }
+
+        //  Waste of serving the sizes of indexes `j..=i` with a class of `sizes[i]`.
+        let cost = |j: usize, i: usize| (requests[i + 1] - requests[j]) * sizes[i] as u128 - (bytes[i + 1] - bytes[j]);
+
+        //  `waste[i]` is the minimum waste of serving the sizes of indexes `0..=i` with the classes so far, the last
+        //  being `sizes[i]`.
+        //
+        //  `previous[k][i]` is the index of the preceding class in the optimal solution with `k + 1` classes, or None
+        //  if there is no preceding class (`k == 0`) or the optimal solution uses no more than `k` classes.
+        let mut waste: Vec<_> = (0..n).map(|i| cost(0, i)).collect();
+        let mut previous = vec![vec![None; n]];
+
+        for _ in 1..number_classes {
+            let mut next = waste.clone();
+            let mut links = vec![None; n];
+
+            for i in 1..n {
+                for (j, prior) in waste.iter().enumerate().take(i) {
+                    let candidate = prior + cost(j + 1, i);
+
+                    if candidate < next[i] {
+                        next[i] = candidate;
+                        links[i] = Some(j);
+                    }
+                }
+            }
+
+            waste = next;
+            previous.push(links);
+        }
+
+        let mut sizes_chosen = Vec::with_capacity(number_classes);
+        let mut current = n - 1;
+
+        for (k, links) in previous.iter().enumerate().rev() {
+            match links[current] {
+                Some(index) => {
+                    sizes_chosen.push(sizes[current]);
+                    current = index;
+                },
+                None if k == 0 => sizes_chosen.push(sizes[current]),
+                None => (),
+            }
+        }
+
+        sizes_chosen.reverse();
+
+        ClassTable { sizes: sizes_chosen }
+    }
+
+    /// Returns the number of bytes wasted by serving the requests recorded with the class sizes of `table`.
+    ///
+    /// Requests larger than the largest class size of `table` are not accounted for.
+    pub fn waste(&self, table: &ClassTable) -> u128 {
+        self.counts.iter()
+            .filter_map(|(size, count)| table.class_of(*size).map(|class| (class - size) as u128 * *count as u128))
+            .sum()
+    }
+}
+
+/// A table of class sizes, in increasing order.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ClassTable {
+    sizes: Vec<usize>,
+}
+
+impl ClassTable {
+    /// Returns the table of the built-in class sizes of Normal allocations, for a given `Configuration`.
+    pub fn builtin<C>() -> Self
+        where
+            C: Configuration,
+    {
+        let threshold = Properties::<C>::normal_threshold().value();
+
+        let sizes = (0..ClassSize::number_classes(C::LARGE_PAGE_SIZE))
+            .map(|index| ClassSize::new(index).layout().size())
+            .take_while(|size| *size <= threshold)
+            .collect();
+
+        Self { sizes }
+    }
+
+    /// Returns the class sizes, in increasing order.
+    pub fn sizes(&self) -> &[usize] { &self.sizes }
+
+    /// Returns the smallest class size fitting `size`, if any.
+    pub fn class_of(&self, size: usize) -> Option<usize> {
+        let index = self.sizes.partition_point(|class| *class < size);
+
+        self.sizes.get(index).copied()
+    }
+}
+
+/// Emits the table as a const array, `CLASS_SIZES`.
+impl fmt::Display for ClassTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "pub const CLASS_SIZES: [usize; {}] = [", self.sizes.len())?;
+
+        for size in &self.sizes {
+            writeln!(f, "    {},", size)?;
+        }
+
+        write!(f, "];")
+    }
+}
+
+#[test]
+fn class_tuner_record() {
+    let mut tuner = ClassTuner::new();
+    tuner.record_trace(vec![0, 1, 16, 17, 32]);
+    tuner.record_many(100, 3);
+    tuner.record_many(200, 0);
+
+    assert_eq!(8, tuner.total());
+    assert_eq!(3, tuner.distinct());
+}
+
+#[test]
+fn class_tuner_optimize_few_sizes() {
+    let mut tuner = ClassTuner::new();
+    tuner.record_trace(vec![24, 64, 64]);
+
+    let table = tuner.optimize(4);
+
+    assert_eq!(&[32, 64], table.sizes());
+    assert_eq!(0, tuner.waste(&table));
+}
+
+#[test]
+fn class_tuner_optimize() {
+    let mut tuner = ClassTuner::new();
+    tuner.record_many(16, 100);
+    tuner.record_many(32, 1);
+    tuner.record_many(48, 100);
+    tuner.record_many(64, 1);
+
+    let table = tuner.optimize(2);
+
+    assert_eq!(&[16, 64], table.sizes());
+    assert_eq!(32 + 16 * 100, tuner.waste(&table));
+
+    let table = tuner.optimize(3);
+
+    assert_eq!(&[16, 48, 64], table.sizes());
+    assert_eq!(16, tuner.waste(&table));
+}
+
+#[test]
+fn class_tuner_optimize_beats_builtin() {
+    struct TestConfiguration;
+
+    impl Configuration for TestConfiguration {
+        const LARGE_PAGE_SIZE: llmalloc_core::PowerOf2 = unsafe { llmalloc_core::PowerOf2::new_unchecked(1 << 16) };
+        const HUGE_PAGE_SIZE: llmalloc_core::PowerOf2 = unsafe { llmalloc_core::PowerOf2::new_unchecked(1 << 21) };
+    }
+
+    let builtin = ClassTable::builtin::<TestConfiguration>();
+    assert!(builtin.sizes().windows(2).all(|pair| pair[0] < pair[1]), "{:?}", builtin);
+
+    let mut tuner = ClassTuner::new();
+    tuner.record_trace((1..2000).map(|i| i * 7 % 3000 + 1));
+
+    let table = tuner.optimize(builtin.sizes().len());
+
+    assert!(tuner.waste(&table) <= tuner.waste(&builtin));
+}
+
+#[test]
+fn class_table_class_of() {
+    let table = ClassTable { sizes: vec![16, 48, 64] };
+
+    assert_eq!(Some(16), table.class_of(1));
+    assert_eq!(Some(48), table.class_of(17));
+    assert_eq!(Some(48), table.class_of(48));
+    assert_eq!(Some(64), table.class_of(64));
+    assert_eq!(None, table.class_of(65));
+}
+
+#[test]
+fn class_table_display() {
+    let table = ClassTable { sizes: vec![16, 48] };
+
+    assert_eq!("pub const CLASS_SIZES: [usize; 2] = [\n    16,\n    48,\n];", table.to_string());
+}
@@ -1,5 +1,7 @@
 //! A test-support library.
 
 mod bursty;
+mod class_tuning;
 
 pub use bursty::{Bursty, BurstyBuilder};
+pub use class_tuning::{ClassTable, ClassTuner};
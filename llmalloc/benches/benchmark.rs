@@ -350,9 +350,9 @@ fn bench_function_worst_of_parallel<F, S, T, V, Z>(
 
         bursty.join();
 
-        let duration = bursty.global().into_iter()
+        let duration = bursty.global().iter()
             .map(|measurement| measurement.load(Ordering::Relaxed))
-            .map(|nanos| Duration::from_nanos(nanos))
+            .map(Duration::from_nanos)
             .max()
             .expect("At least one element");
 
@@ -401,7 +401,3 @@ unsafe impl Send for LLVec {}
 fn layout(size: usize, alignment: usize) -> Layout {
     Layout::from_size_align(size, alignment).expect("Valid Layout")
 }
-
-//  FIXME: use sys crates... properly configured for system libraries.
-#[link(name = "numa")]
-extern "C" {}
@@ -2,115 +2,2608 @@
 
 use core::{
     alloc::GlobalAlloc,
+    fmt,
+    marker::PhantomData,
     ptr::{self, NonNull},
+    slice,
+    time::Duration,
 };
 
-use llmalloc_core::{self, Configuration, Layout, PowerOf2};
+#[cfg(feature = "bare-metal")]
+use core::sync::atomic::AtomicPtr;
 
-use crate::{LLConfiguration, Platform, LLPlatform, ThreadLocal, LLThreadLocal};
+#[cfg(feature = "test-platform")]
+use crate::MockPlatform;
+
+use llmalloc_core::{
+    self, Category, ClassSize, Configuration, Criticality, Layout, PowerOf2, Properties, SizeHistogram, Statistics,
+    StatisticsEpoch,
+};
+
+use crate::{
+    print, AddressRange, AllocationError, Arena, AtomicInitMetrics, ALLOCATED_POISON, DEALLOCATED_POISON, CodeMapping,
+    CodeRegion, CollapseReport, CompactionPlan, CompactionReport, EpochTracker, Frame, FrameRegions, Hardening,
+    HostCapabilities, HugePageReport, HugeTlbPools, Capabilities, Fallback, FallbackMetrics, InitMetrics, InitStage,
+    LatencyCriticalReport, LLConfiguration, MapOptions, NodeStatistics, NumaNodeIndex, PhysicalBuffer, PhysicalSegment,
+    PinningReport, Platform, PrivilegeError, LLPlatform, Quarantine, Reclamation, Relocatable, Reservation,
+    ResidencyReport, Retry, RetryPolicy, SharedBacking, SharedHeap, SurvivingAllocation, Tag, TagCallback, Tags,
+    ThreadLocal, LLThreadLocal, ThreadProfile, ThreadStack, UnmapFailureCallback, UnmapFailurePolicy, WatermarkCallback,
+    WatermarkId, Watermarks,
+};
+
+use crate::{
+    armed::ARMED, background::BACKGROUND, bounds::ADDRESS_BOUNDS, clustering::CLUSTERING, decay::DECAY,
+    decommit::DECOMMIT, fork::FORK, guard::GUARDS, hotplug::TOPOLOGY_REFRESH, idle::{IdleCache, IDLE_TRIM},
+    locality::{NodeCache, FOREIGN_ALLOCATIONS}, mapping::MAP_OPTIONS, pinning::PINNING, prefault::PREFAULT,
+    registration::REGISTRATION, rehoming::REHOMING, reservation::ADDRESS_SPACE, shared::SHARED, tiering::TIERING,
+    unmapping::UNMAPPING,
+};
 
 /// Low-Latency Allocator.
-#[derive(Default)]
-pub struct LLAllocator;
+///
+/// All instances share the same underlying memory, only their settings are per-instance.
+pub struct LLAllocator {
+    maximum_size: usize,
+    direct_threshold: usize,
+    interleaved: bool,
+}
 
 impl LLAllocator {
-    /// Creates an instance.
-    pub const fn new() -> Self { Self }
+    /// Creates an instance, without maximum allocation size.
+    pub const fn new() -> Self { Self::with_maximum_size(usize::MAX) }
 
-    /// Prepares the socket-local and thread-local structures for allocation.
+    /// Creates an instance, with a maximum allocation size of `maximum_size` bytes.
     ///
-    /// Returns Ok if the attempt succeeded, Err otherwise.
+    /// Allocations of a greater size fail without requesting any memory from the OS, protecting against pathological
+    /// sizes, such as those derived from untrusted length fields.
+    pub const fn with_maximum_size(maximum_size: usize) -> Self {
+        Self { maximum_size, direct_threshold: usize::MAX, interleaved: false }
+    }
+
+    /// Returns the maximum allocation size, in bytes.
+    pub const fn maximum_size(&self) -> usize { self.maximum_size }
+
+    /// Returns a copy of the instance, mapping allocations of a size greater than `direct_threshold` bytes directly.
     ///
-    /// Failure to warm up the current thread may occur if:
+    /// Directly mapped allocations are served by dedicated extents of whole `HugePage`, rather than carved from the
+    /// `HugePage` shared by the Large allocations of the socket. A lower threshold trades memory, as each allocation is
+    /// rounded up to a whole number of `HugePage`, for isolation from the fragmentation of the socket.
     ///
-    /// -   The socket-local structure is not ready, and the underlying `Platform` cannot allocate one.
-    /// -   The socket-local structure cannot allocate a thread-local structure.
+    /// Normal allocations are never directly mapped, whereas allocations too large to be carved from a `HugePage`
+    /// always are, whatever the threshold.
+    ///
+    /// Whether directly mapped allocations are retained for reuse on deallocation is a process-wide setting; see
+    /// `set_direct_retained`.
+    pub const fn with_direct_threshold(self, direct_threshold: usize) -> Self {
+        Self { maximum_size: self.maximum_size, direct_threshold, interleaved: self.interleaved }
+    }
+
+    /// Returns the threshold above which allocations are mapped directly, in bytes.
+    pub const fn direct_threshold(&self) -> usize { self.direct_threshold }
+
+    /// Returns a copy of the instance, interleaving the pages of its directly mapped allocations across all NUMA nodes
+    /// if `interleaved`, with `mbind(MPOL_INTERLEAVE)`.
+    ///
+    /// Interleaving suits the large structures shared by the threads of all nodes, such as a giant hash table, whose
+    /// pages would otherwise all land on the node of the allocating thread: it trades the locality of their memory for
+    /// the aggregate bandwidth of all nodes. The pages already faulted in, as those of a retained allocation reused,
+    /// are migrated.
+    ///
+    /// Only the directly mapped allocations, see `with_direct_threshold`, are interleaved, as the others share their
+    /// `HugePage` with other allocations; the allocations of `allocate_on_node` are placed on their node instead.
+    /// Interleaving is honored on Linux, when NUMA is available; to interleave the whole heap, see
+    /// `MapOptions::with_interleaved`.
+    pub const fn with_interleaved(self, interleaved: bool) -> Self {
+        Self { maximum_size: self.maximum_size, direct_threshold: self.direct_threshold, interleaved }
+    }
+
+    /// Returns whether the pages of the directly mapped allocations are interleaved across all NUMA nodes.
+    pub const fn is_interleaved(&self) -> bool { self.interleaved }
+
+    /// Returns whether directly mapped allocations are retained for reuse on deallocation, which they are by default.
+    pub fn is_direct_retained(&self) -> bool { DOMAIN.is_retaining() }
+
+    /// Sets, process-wide, whether directly mapped allocations are retained for reuse on deallocation.
+    ///
+    /// When retained, they are cached, coalesced with their neighbours, and carved to serve further directly mapped
+    /// allocations, sparing the system calls. Otherwise, they are unmapped on deallocation, returning the memory to the
+    /// OS immediately, except for those carved from the allocations retained beforehand.
     #[cold]
-    pub fn warm_up(&self) -> Result<(), ()> {
-        Thread::get().or_else(Thread::initialize).map(|_| ()).ok_or(())
+    pub fn set_direct_retained(&self, retained: bool) { DOMAIN.set_retaining(retained) }
+
+    /// Returns whether the hardened mode is enabled.
+    ///
+    /// The hardened mode is process-wide, shared by all instances; see `set_hardened`.
+    pub fn is_hardened(&self) -> bool { HARDENING.is_enabled(DOMAIN.platform()) }
+
+    /// Enables or disables the hardened mode, process-wide, overriding the `LLMALLOC_HARDENED` environment variable.
+    ///
+    /// In hardened mode, newly allocated memory is poisoned with `ALLOCATED_POISON`, and deallocated memory with
+    /// `DEALLOCATED_POISON`; Normal allocations are padded with a canary, checked on deallocation, and quarantined
+    /// once deallocated, their poison being checked once evicted from the quarantine. A clobbered canary, or poison,
+    /// is counted as a violation by `hardening_violations`, and the block leaked.
+    ///
+    /// The mode is best selected before the first allocation, as memory allocated before the selection is not
+    /// retroactively poisoned, nor padded with a canary: the canaries are only enabled if the mode is selected before
+    /// the first allocation, and remain so until disabled.
+    #[cold]
+    pub fn set_hardened(&self, enabled: bool) { HARDENING.set(enabled) }
+
+    /// Returns the number of violations detected in hardened mode, that is clobbered canaries, or writes to quarantined
+    /// memory, since the start of the process.
+    pub fn hardening_violations(&self) -> u64 { HARDENING.violations() }
+
+    /// Returns whether guard pages are enabled.
+    ///
+    /// Guard pages are process-wide, shared by all instances; see `set_guarded`.
+    pub fn is_guarded(&self) -> bool { GUARDS.is_enabled(DOMAIN.platform()) }
+
+    /// Enables or disables guard pages, process-wide, overriding the `LLMALLOC_GUARD_PAGES` environment variable.
+    ///
+    /// With guard pages, each `HugePage` mapped, and thus each Huge allocation, is flanked by an inaccessible OS page
+    /// on either side, so that a linear overflow past the end of the mapping, or underflow before its start, faults
+    /// immediately. As Huge allocations are rounded up to a whole number of `HugePage`, an overflow is only caught past
+    /// the rounded up size; the Normal and Large allocations are only guarded by the guards of their `HugePage`.
+    ///
+    /// The selection is latched by the first mapping, hence Err is returned if it differs from the latched one.
+    ///
+    /// Guard pages are honored on Linux.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn set_guarded(&self, enabled: bool) -> Result<(), ()> { GUARDS.set(enabled) }
+
+    /// Returns the backing of the shared heap, if selected.
+    ///
+    /// The shared heap is process-wide, shared by all instances; see `set_shared_heap`.
+    pub fn shared_heap_backing(&self) -> Option<SharedBacking> { SHARED.backing(DOMAIN.platform()) }
+
+    /// Selects the backing of the shared heap, or none, process-wide, overriding the `LLMALLOC_MEMFD` and
+    /// `LLMALLOC_MEMFD_HUGETLB` environment variables.
+    ///
+    /// With a shared heap, each `HugePage` mapped, and thus each allocation, is backed by a single anonymous file,
+    /// created by `memfd_create`, whose descriptor, as returned by `shared_heap`, may be mapped by a cooperating
+    /// process, so as to access the memory allocated without copying it. A Huge allocation which cannot be resized in
+    /// place is moved by copy, rather than by `mremap`.
+    ///
+    /// The selection is latched by the first mapping, hence Err is returned if it differs from the latched one.
+    ///
+    /// A shared heap is honored on Linux.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn set_shared_heap(&self, backing: Option<SharedBacking>) -> Result<(), ()> { SHARED.set(backing) }
+
+    /// Returns the shared heap, or None if the heap is not shared, or its file is not yet created, or could not be.
+    ///
+    /// The file is created on the first mapping, once the selection is latched; see `set_shared_heap`.
+    pub fn shared_heap(&self) -> Option<SharedHeap> { SHARED.heap() }
+
+    /// Returns the size of the reservation of the address space selected, if any.
+    ///
+    /// The reservation is process-wide, shared by all instances; see `set_reservation`.
+    pub fn reservation_size(&self) -> Option<usize> { ADDRESS_SPACE.size(DOMAIN.platform()) }
+
+    /// Selects the size of the reservation of the address space, or none, process-wide, overriding the
+    /// `LLMALLOC_RESERVE` environment variable.
+    ///
+    /// With a reservation, a single contiguous range of the address space is reserved by the first mapping, without
+    /// committing any memory, then each `HugePage` mapped, and each Huge allocation, is committed out of it on demand,
+    /// so that all the memory of the heap lies within the range returned by `reservation`. Once the reservation is
+    /// exhausted, mappings fail.
+    ///
+    /// The size is rounded up to a whole number of Huge Pages, of which there may be at most 1024. The selection is
+    /// latched by the first mapping, hence Err is returned if it differs from the latched one, as well as if the size
+    /// is 0 or too large.
+    ///
+    /// A reservation is honored on Linux, where its memory is backed by neither HugeTLB pages, nor guard pages.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn set_reservation(&self, size: Option<usize>) -> Result<(), ()> { ADDRESS_SPACE.set(size) }
+
+    /// Returns the reservation of the address space, or None if not reserved, not yet, or if it could not be.
+    ///
+    /// The address space is reserved on the first mapping, once the selection is latched; see `set_reservation`.
+    pub fn reservation(&self) -> Option<Reservation> { ADDRESS_SPACE.reservation() }
+
+    /// Returns the options of the mappings of the heap.
+    ///
+    /// The options are process-wide, shared by all instances; see `set_map_options`.
+    pub fn map_options(&self) -> MapOptions { MAP_OPTIONS.get() }
+
+    /// Selects, process-wide, the options of the mappings of the heap: `MAP_NORESERVE`, `MAP_LOCKED`, the interleaving
+    /// of the pages across all NUMA nodes or their binding to their node, an address hint, and the advice on Kernel
+    /// Same-page Merging.
+    ///
+    /// Only the `HugePage`s, and the Huge allocations, mapped after the selection are affected.
+    ///
+    /// The options are honored on Linux, bar the memory committed out of a reservation.
+    #[cold]
+    pub fn set_map_options(&self, options: MapOptions) { MAP_OPTIONS.set(options) }
+
+    /// Returns the distance within which NUMA nodes are clustered, or None if clustering is disabled.
+    ///
+    /// The distance is process-wide, shared by all instances; see `set_clustering_distance`.
+    pub fn clustering_distance(&self) -> Option<u8> { CLUSTERING.distance() }
+
+    /// Selects, process-wide, the distance within which NUMA nodes are clustered, 11 by default, or disables
+    /// clustering if None.
+    ///
+    /// A node is served by the socket of the lowest node within the distance of it, as reported by the kernel, a node
+    /// being at a distance of 10 from itself; with clustering disabled, each node is served by its own socket. Only
+    /// the threads selecting their socket after the selection are affected, as are the placements of
+    /// `allocate_on_node`.
+    ///
+    /// Clustering is honored on Linux.
+    #[cold]
+    pub fn set_clustering_distance(&self, distance: Option<u8>) { CLUSTERING.set(distance) }
+
+    /// Re-scans the NUMA topology, returning the number of nodes found, or None if the topology of the platform is
+    /// fixed.
+    ///
+    /// On virtual machines, nodes may be hot-added after startup; once re-scanned, their threads are served by sockets
+    /// of their own as they first allocate, and the threads already served by another socket are moved over if
+    /// rehoming, see `set_rehoming`. The topology is re-scanned on Linux.
+    #[cold]
+    pub fn refresh_topology(&self) -> Option<u32> { DOMAIN.platform().refresh_topology() }
+
+    /// Returns the period of the refresh of the NUMA topology by the background thread, if enabled.
+    ///
+    /// The period is process-wide, shared by all instances; see `set_topology_refresh`.
+    pub fn topology_refresh(&self) -> Option<Duration> { TOPOLOGY_REFRESH.period() }
+
+    /// Selects, process-wide, the period of the refresh of the NUMA topology, as by `refresh_topology`, or disables it
+    /// if None, as by default.
+    ///
+    /// The refresh is run by the background thread, on its first wake-up past each period, hence only if it is
+    /// enabled, see `set_background_thread`.
+    #[cold]
+    pub fn set_topology_refresh(&self, period: Option<Duration>) { TOPOLOGY_REFRESH.set(period) }
+
+    /// Returns whether the placements of the heap may spill onto the NUMA nodes of far memory, false by default.
+    ///
+    /// The selection is process-wide, shared by all instances; see `set_far_memory`.
+    pub fn far_memory(&self) -> bool { TIERING.spills() }
+
+    /// Selects, process-wide, whether the placements of the heap may spill onto the NUMA nodes of far memory, such as
+    /// CXL.mem expanders, once near memory is exhausted.
+    ///
+    /// By default, the pages of the heap are restricted to the nodes of near memory, DRAM, bar the preferred
+    /// placements, and the interleaved pages are interleaved across them only; see `HostCapabilities::far_numa_nodes`.
+    /// Only the `HugePage`s, and the Huge allocations, mapped after the selection are affected.
+    ///
+    /// The tiers are honored on Linux.
+    #[cold]
+    pub fn set_far_memory(&self, far_memory: bool) { TIERING.set(far_memory) }
+
+    /// Returns the address range selected, if any, shrunk to the whole Huge Pages it covers.
+    ///
+    /// The address range is process-wide, shared by all instances; see `set_address_range`.
+    pub fn address_range(&self) -> Option<AddressRange> { ADDRESS_BOUNDS.range() }
+
+    /// Selects, process-wide, the address range within which all the memory of the heap is to lie, or none.
+    ///
+    /// With an address range, each mapping of the heap is hinted within the range, then unmapped, failing the
+    /// allocation, should it land outside of it, so that all the pointers handed over lie within the range; for
+    /// example, below 2^47, so as to pack metadata into their upper bits. The lack of memory within the range is thus
+    /// handled as the lack of memory, for example retried as per `set_retry_policy`.
+    ///
+    /// The range is shrunk to the whole Huge Pages it covers, bar the first Huge Page of the address space. The
+    /// selection is latched by the first mapping, hence Err is returned if it differs from the latched one, as well
+    /// as if the range holds no Huge Page once shrunk.
+    ///
+    /// An address range is honored on Linux, where the reservation, if any, is reserved within the range, or not at
+    /// all.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn set_address_range(&self, range: Option<AddressRange>) -> Result<(), ()> { ADDRESS_BOUNDS.set(range) }
+
+    /// Returns whether prefaulting is enabled.
+    ///
+    /// Prefaulting is process-wide, shared by all instances; see `set_prefault`.
+    pub fn is_prefaulting(&self) -> bool { PREFAULT.is_enabled(DOMAIN.platform()) }
+
+    /// Enables or disables prefaulting, process-wide, overriding the `LLMALLOC_PREFAULT` environment variable.
+    ///
+    /// When prefaulting, every OS page of each `HugePage` mapped, and of each Huge allocation grown, is touched before
+    /// being handed over, so that no soft page fault occurs on first access, at the cost of slower mappings and of
+    /// committing the memory immediately. Only the memory mapped after the selection is affected.
+    ///
+    /// Prefaulting is honored on Unix, where memory is mapped on demand; unlike `init_latency_critical`, it does not
+    /// lock the memory in RAM.
+    #[cold]
+    pub fn set_prefault(&self, enabled: bool) { PREFAULT.set(enabled) }
+
+    /// Returns whether pinning is enabled.
+    ///
+    /// Pinning is process-wide, shared by all instances; see `set_pinned`.
+    pub fn is_pinned(&self) -> bool { PINNING.is_enabled(DOMAIN.platform()) }
+
+    /// Enables or disables pinning, process-wide, overriding the `LLMALLOC_MLOCK` environment variable.
+    ///
+    /// When pinning, each `HugePage` mapped, and each Huge allocation grown, is locked in RAM with `mlock` before being
+    /// handed over, so that the memory of the heap is never paged out, and never incurs a major page fault. On
+    /// enabling, the `HugePage`s already mapped by the sockets are locked too, though not the Huge allocations already
+    /// mapped; on disabling, the memory already locked remains locked until unmapped.
+    ///
+    /// Returns Err if any of the `HugePage`s already mapped cannot be locked, typically as the limit of locked memory,
+    /// `RLIMIT_MEMLOCK`, is too low. The memory mapped afterwards is handed over even if it cannot be locked, the
+    /// failures being reported by `pinning_report`, and counted by `fallback_metrics`.
+    ///
+    /// Pinning is honored on Unix. Locked memory is not released by decommit, nor by decay.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn set_pinned(&self, enabled: bool) -> Result<(), ()> {
+        PINNING.set(enabled);
+
+        if !enabled {
+            return Ok(());
+        }
+
+        let huge_page_size = LLConfiguration::HUGE_PAGE_SIZE.value();
+        let mut result = Ok(());
+
+        SOCKETS.for_each_socket_handle(|_, socket| {
+            socket.for_each_huge_page(|page| {
+                if !PINNING.pin(DOMAIN.platform(), page, huge_page_size) {
+                    result = Err(());
+                }
+            });
+        });
+
+        result
     }
 
-    /// Ensures that at least `target` `HugePage` are allocated on the socket.
+    /// Returns the report of pinning, alongside the limit of locked memory of the process; see `set_pinned`.
+    #[cold]
+    pub fn pinning_report(&self) -> PinningReport {
+        PINNING.report(DOMAIN.platform(), DOMAIN.platform().host_capabilities().mlock_limit)
+    }
+
+    /// Returns whether decommit is enabled.
     ///
-    /// Returns the minimum of the currently allocated number of pages and `target`.
+    /// Decommit is process-wide, shared by all instances; see `set_decommit`.
+    pub fn is_decommitting(&self) -> bool { DECOMMIT.is_enabled(DOMAIN.platform()) }
+
+    /// Enables or disables decommit, process-wide, overriding the `LLMALLOC_DECOMMIT` environment variable.
     ///
-    /// Failure to meet the `target` may occur if:
+    /// When decommitting, the `LargePage`s freed by each Large deallocation have their physical memory released to the
+    /// OS, with `madvise(MADV_DONTNEED)`, while their address range is kept for future allocations, at the cost of a
+    /// system call per Large deallocation, and of page faults on their next use.
     ///
-    /// -   The maximum number of `HugePage` a `socket` may contain has been reached.
-    /// -   The underlying `Platform` is failing to allocate more `HugePage`.
+    /// Decommit is honored on Linux, and by the POSIX platform; the Normal allocations, and their `LargePage`s, are not
+    /// affected.
     #[cold]
-    pub fn reserve(&self, target: usize) -> usize {
-        if let Some(socket) = Sockets::socket_handle() {
-            socket.reserve(target)
-        } else {
-            0
+    pub fn set_decommit(&self, enabled: bool) { DECOMMIT.set(enabled) }
+
+    /// Returns the window of decay of the retained Huge blocks, if enabled.
+    ///
+    /// Decay is process-wide, shared by all instances; see `set_decay`.
+    pub fn decay_window(&self) -> Option<Duration> { DECAY.window(DOMAIN.platform()) }
+
+    /// Enables decay of the retained Huge blocks with the given `window`, or disables it if None, process-wide,
+    /// overriding the `LLMALLOC_DECAY` environment variable.
+    ///
+    /// When decaying, each Huge block retained for reuse, see `set_direct_retained`, is lazily purged on retention,
+    /// with `madvise(MADV_FREE)`, so that the OS may reclaim its memory under memory pressure while its reuse remains
+    /// cheap, and is forcibly purged, with `madvise(MADV_DONTNEED)`, once retained for one to two windows. The passes
+    /// run on the first deallocation of a Huge allocation following the expiry of the window, or on calls to `decay`.
+    ///
+    /// Decay is honored on Linux, and by the POSIX platform.
+    #[cold]
+    pub fn set_decay(&self, window: Option<Duration>) { DECAY.set(window) }
+
+    /// Runs a decay pass immediately, regardless of the window, and returns the number of bytes purged.
+    ///
+    /// The retained Huge blocks are aged, and those aged by a previous pass are forcibly purged; see `set_decay`. The
+    /// pass is intended for processes which seldom deallocate Huge allocations, to be invoked periodically.
+    #[cold]
+    pub fn decay(&self) -> usize { DOMAIN.decay() }
+
+    /// Returns the policy of retry of the allocations for which no memory could be mapped, if any.
+    ///
+    /// The policy is process-wide, shared by all instances; see `set_retry_policy`.
+    pub fn retry_policy(&self) -> Option<RetryPolicy> { RETRY.policy(DOMAIN.platform()) }
+
+    /// Selects the policy of retry of the allocations for which no memory could be mapped, or none, process-wide,
+    /// overriding the `LLMALLOC_RETRY` environment variable.
+    ///
+    /// By default, an allocation fails as soon as no memory can be mapped. With a policy, it is instead retried up to
+    /// `retries` times, each retry preceded by a backoff doubling from `backoff`, and the first preceded by a forced
+    /// purge of the Huge blocks retained for reuse if `purge`, so that a transient failure, such as a pool of HugeTLB
+    /// pages momentarily exhausted, does not fail the allocation. A policy without retry is no policy.
+    ///
+    /// The retries are recorded in the `fallback_metrics`. The allocations bounded by a deadline are never retried.
+    #[cold]
+    pub fn set_retry_policy(&self, policy: Option<RetryPolicy>) { RETRY.set(policy) }
+
+    /// Returns the policy applied to the memory which could not be unmapped.
+    ///
+    /// The policy is process-wide, shared by all instances; see `set_unmap_failure_policy`.
+    pub fn unmap_failure_policy(&self) -> UnmapFailurePolicy { UNMAPPING.policy() }
+
+    /// Selects, process-wide, the policy applied to the memory which could not be unmapped: leaked, as by default, or
+    /// the process aborted.
+    ///
+    /// Either way, the failure is first recorded in the `fallback_metrics`, then reported to the callback registered
+    /// with `set_unmap_failure_callback`, if any.
+    #[cold]
+    pub fn set_unmap_failure_policy(&self, policy: UnmapFailurePolicy) { UNMAPPING.set_policy(policy) }
+
+    /// Registers, process-wide, the callback reporting the failures to unmap memory, or none, replacing the previous
+    /// one, if any.
+    #[cold]
+    pub fn set_unmap_failure_callback(&self, callback: Option<UnmapFailureCallback>) {
+        UNMAPPING.set_callback(callback)
+    }
+
+    /// Returns the period of the background thread, if enabled, which it is not by default.
+    ///
+    /// The background thread performs the maintenance of the allocator off the critical path of the allocating and
+    /// deallocating threads; see `set_background_thread`.
+    pub fn background_thread_period(&self) -> Option<Duration> { BACKGROUND.period(DOMAIN.platform()) }
+
+    /// Returns whether the background thread is running.
+    pub fn is_background_thread_running(&self) -> bool { BACKGROUND.is_running() }
+
+    /// Returns the number of passes run by the background thread, since the start of the process.
+    pub fn background_thread_passes(&self) -> u64 { BACKGROUND.passes() }
+
+    /// Enables the background thread, waking up every `period`, spawning it if not yet running, or disables it if
+    /// `period` is None, overriding the `LLMALLOC_BACKGROUND_THREAD` environment variable.
+    ///
+    /// On each wake-up, the background thread returns the allocations deallocated by the threads of other sockets to
+    /// their `LargePage`, runs the passes of decay in lieu of the deallocating threads, re-scans the NUMA topology when
+    /// due, see `set_topology_refresh`, and frees the retired memory which no alive guard may still access. Disabled,
+    /// the thread exits on its next wake-up.
+    ///
+    /// Returns Err if the thread cannot be spawned, for example if the platform cannot spawn threads, in which case the
+    /// background thread is disabled.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn set_background_thread(&self, period: Option<Duration>) -> Result<(), ()> {
+        BACKGROUND.set(DOMAIN.platform(), period, background_thread)
+    }
+
+    /// Returns whether the handlers around fork are registered, which they are on the first use of the allocator, on
+    /// the platforms able to fork.
+    ///
+    /// The handlers let the child of a fork recover from the threads of its parent it does not inherit, the background
+    /// thread included; see the `fork` module.
+    pub fn is_fork_safe(&self) -> bool { FORK.is_registered() }
+
+    /// Provides the `size` bytes of memory located at `pointer` as the memory of the allocator, on bare metal.
+    ///
+    /// Without an OS to map memory from, all the Huge Pages are carved out of this region, such as a range reserved
+    /// by a bootloader, or a DPDK pool of huge pages; only the part aligned on Huge Pages is used. The region is to be
+    /// provided once, prior to the first allocation, and is never returned.
+    ///
+    /// Returns Err if a region was already provided, or if the region does not span a single aligned Huge Page.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the memory is valid for reads and writes, and not otherwise in use, for the remainder of the
+    ///     program.
+    #[cfg(feature = "bare-metal")]
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub unsafe fn provide_region(&self, pointer: NonNull<u8>, size: usize) -> Result<(), ()> {
+        if DOMAIN.platform().provide_region(pointer, size) { Ok(()) } else { Err(()) }
+    }
+
+    /// Provides the function returning the slot of the current thread, or CPU, holding its thread-local state, on
+    /// bare metal.
+    ///
+    /// By default, a single slot is used, suitable for a single thread of execution; a kernel would rather provide a
+    /// per-CPU slot, with preemption disabled around the calls to the allocator. The function is to be provided prior
+    /// to the first allocation.
+    ///
+    /// Returns Err if a function was already provided, or if the single slot is already in use.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `slot` returns a distinct slot for each thread of execution calling into the allocator,
+    ///     initially null, and only ever modified by the allocator.
+    #[cfg(feature = "bare-metal")]
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub unsafe fn provide_thread_slot(&self, slot: fn() -> &'static AtomicPtr<u8>) -> Result<(), ()> {
+        if THREAD_LOCAL.provide_slot(slot) { Ok(()) } else { Err(()) }
+    }
+
+    /// Creates an instance, without maximum allocation size, over the `platform` and `thread_local` storage supplied
+    /// by the embedder, for the OSes llmalloc does not support, such as QNX or VxWorks, or for memory which is not
+    /// mapped anonymously, such as a region pre-registered for RDMA.
+    ///
+    /// All instances share the same underlying memory, hence the platform is registered once, process-wide, prior to
+    /// the first allocation, and is then used by all instances, including those created beforehand with `new`, such
+    /// as the global allocator. Until then, allocations fail. The platform is requested multiples of the Huge Pages
+    /// of `LLConfiguration`, aligned as such.
+    ///
+    /// Returns Err if a platform was already registered.
+    #[cfg(feature = "custom-platform")]
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn with_platform<P, T>(platform: &'static P, thread_local: &'static T) -> Result<Self, ()>
+        where
+            P: Platform,
+            T: ThreadLocal<u8> + Sync,
+    {
+        if DOMAIN.platform().register(platform, thread_local) { Ok(Self::new()) } else { Err(()) }
+    }
+
+    /// Returns the mock platform, to inspect the calls made to it, inject failures, or advance its clock.
+    ///
+    /// The platform, and its arena, are shared by all instances, hence tests relying on its journal are best run
+    /// sequentially.
+    #[cfg(feature = "test-platform")]
+    #[cold]
+    pub fn mock_platform(&self) -> &'static MockPlatform { DOMAIN.platform() }
+
+    /// Releases the thread-local state of the current thread, with a platform supplied by the embedder, or without
+    /// libc.
+    ///
+    /// Neither the thread-local storage of the embedder, nor `#[thread_local]` statics, know of the exit of threads,
+    /// hence each thread which allocated is to call this function prior to exiting, lest the memory it caches be
+    /// leaked.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the current thread no longer calls into the allocator, until it exits.
+    #[cfg(any(feature = "custom-platform", feature = "no-libc"))]
+    #[cold]
+    pub unsafe fn release_thread(&self) {
+        if let Some(handle) = THREAD_LOCAL.get() {
+            drop_handle(handle.as_ptr());
         }
     }
 
-    /// Allocates `size` bytes of memory, aligned on at least an `alignment` boundary.
+    /// Flushes the cache of the current thread, returning the memory it caches to the heap of its node, for the other
+    /// threads to use.
     ///
-    /// If allocation fails, the returned pointer may be NULL.
-    pub fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
-        debug_assert!(layout.align().count_ones() == 1);
+    /// The cache is otherwise only flushed as the thread exits, hence a thread pool may flush the cache of a worker
+    /// about to go idle for a long time, lest the memory it caches be stranded meanwhile; see also `flush_on_drop`.
+    /// The thread remains usable, its next allocations filling its cache anew, and thus being slower.
+    ///
+    /// Does nothing if the current thread never allocated.
+    #[cold]
+    pub fn flush_thread_cache(&self) {
+        if let Some(thread) = Thread::get() {
+            thread.flush();
+        }
+    }
 
-        if layout.align() > LLConfiguration::HUGE_PAGE_SIZE.value() {
-            return None;
+    /// Returns a guard flushing the cache of the current thread once dropped, see `flush_thread_cache`.
+    ///
+    /// A worker of a thread pool may thus hold the guard for the duration of each task, flushing its cache as it goes
+    /// idle.
+    pub fn flush_on_drop(&self) -> FlushGuard { FlushGuard { _thread: PhantomData } }
+
+    /// Returns the threshold of inactivity past which the cache of a thread is trimmed, if enabled.
+    ///
+    /// The threshold is process-wide, shared by all instances; see `set_idle_trim`.
+    pub fn idle_trim(&self) -> Option<Duration> { IDLE_TRIM.threshold() }
+
+    /// Selects, process-wide, the threshold of inactivity past which the cache of a thread is trimmed, by
+    /// `trim_idle_thread_cache`, or disables the trimming if None, as by default.
+    #[cold]
+    pub fn set_idle_trim(&self, threshold: Option<Duration>) { IDLE_TRIM.set(threshold) }
+
+    /// Trims the cache of the current thread, as by `flush_thread_cache`, if it has neither allocated nor deallocated
+    /// for the threshold selected by `set_idle_trim`, returning whether it was trimmed.
+    ///
+    /// A parked thread runs no code of the allocator, hence this is to be called by the runtime as it parks the thread,
+    /// and as it wakes it up on a timeout: the first call following any activity of the thread records it, and the
+    /// first call past the threshold of inactivity trims the cache, once per idle period.
+    ///
+    /// Does nothing if the trimming is disabled, or if the current thread never allocated.
+    #[cold]
+    pub fn trim_idle_thread_cache(&self) -> bool {
+        match (Thread::get(), IDLE_TRIM.threshold_nanos()) {
+            (Some(thread), Some(threshold)) => thread.trim_if_idle(threshold),
+            _ => false,
         }
+    }
 
-        //  Safety:
-        //  -   `layout.align()` is a power of 2.
-        let align = unsafe { PowerOf2::new_unchecked(layout.align()) };
+    /// Registers the current thread, creating its thread-local state, if not already created.
+    ///
+    /// The threads whose exit is not known of the platform, such as those created by raw `clone`, or by some foreign
+    /// runtimes, are to register, and to unregister prior to exiting, see `unregister_thread`; once registration is
+    /// required, see `set_thread_registration`, the unregistered threads are served from a shared slow path.
+    ///
+    /// Returns Err if the thread-local state could not be created, as for `warm_up`.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn register_thread(&self) -> Result<(), ()> { Thread::get().or_else(Thread::register).map(|_| ()).ok_or(()) }
 
-        //  Round up size to a multiple of `align`, if not already.
-        let layout = if layout.size() % align == 0 {
-            layout
-        } else {
-            let size = align.round_up(layout.size());
+    /// Registers the current thread, as by `register_thread`, and declares its profile.
+    ///
+    /// A latency-critical thread is declared Critical, and its cache is pre-filled with a `LargePage` for each Normal
+    /// class size, whereas a background thread is declared Background, its cache being flushed periodically so as to
+    /// remain minimal. A thread already registered has its profile declared anew.
+    ///
+    /// Returns Err if the thread-local state could not be created, as for `warm_up`.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn register_thread_with(&self, profile: ThreadProfile) -> Result<(), ()> {
+        let thread = Thread::get().or_else(Thread::register).ok_or(())?;
 
-            //  Safety:
-            //  -   `align` is not 0.
-            //  -   `align` is a power of 2.
-            //  -   `size` is rounded up to a multiple of `align`, without overflow.
-            unsafe { Layout::from_size_align_unchecked(size, align.value()) }
-        };
+        thread.set_criticality(profile.criticality());
 
-        if let Some(thread_local) = Thread::get().or_else(Thread::initialize) {
-            return thread_local.allocate(layout);
+        if profile.is_prefilled() {
+            Self::prefill(&thread);
         }
 
-        None
+        Ok(())
     }
 
-    /// Deallocates the memory located at `pointer`.
+    /// Unregisters the current thread, releasing its thread-local state, and the memory it caches.
+    ///
+    /// The thread may register anew, or be served as an unregistered thread, afterwards. If the thread-local storage
+    /// cannot clear the state of the thread, as may that of a custom platform, the memory it caches is flushed instead,
+    /// see `flush_thread_cache`, the state itself remaining registered.
     ///
     /// #   Safety
     ///
-    /// -   Assumes `pointer` has been returned by a prior call to `allocate`.
-    /// -   Assumes `pointer` has not been deallocated since its allocation.
-    /// -   Assumes the memory pointed by `pointer` is no longer in use.
-    pub unsafe fn deallocate(&self, pointer: NonNull<u8>) {
-        if let Some(thread_local) = Thread::get().or_else(Thread::initialize) {
-            return thread_local.deallocate(pointer);
+    /// -   Assumes that the allocations of the frame region of the thread, if in frame mode, are no longer in use.
+    #[cold]
+    pub unsafe fn unregister_thread(&self) {
+        let handle = match THREAD_LOCAL.get() {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        if THREAD_LOCAL.clear() {
+            drop_handle(handle.as_ptr());
+        } else {
+            self.flush_thread_cache();
         }
+    }
+
+    /// Returns whether the registration of threads is required, which it is not by default.
+    pub fn is_thread_registration_required(&self) -> bool { REGISTRATION.is_required() }
+
+    /// Selects, process-wide, whether the registration of threads is required, see `register_thread`.
+    ///
+    /// Once required, the thread-local state of the unregistered threads is no longer created implicitly: their
+    /// allocations are served from a single state, shared by all unregistered threads under a lock, and their
+    /// deallocations are returned directly to the heap. Hence no memory is stranded by the threads exiting without
+    /// unregistering, at the cost of serializing the unregistered threads, whose allocations are counted, see
+    /// `unregistered_allocations`. The unregistered threads can neither `allocate_on_node`, nor `warm_up`, nor use
+    /// the facilities of the thread-local state, such as frame mode.
+    #[cold]
+    pub fn set_thread_registration(&self, required: bool) { REGISTRATION.set(required) }
+
+    /// Returns the number of allocations attempted by the unregistered threads, since the start of the process, once
+    /// the registration of threads is required.
+    pub fn unregistered_allocations(&self) -> usize { REGISTRATION.unregistered() }
+
+    /// Prepares the socket-local and thread-local structures for allocation.
+    ///
+    /// Returns Ok if the attempt succeeded, Err otherwise.
+    ///
+    /// Failure to warm up the current thread may occur if:
+    ///
+    /// -   The socket-local structure is not ready, and the underlying `Platform` cannot allocate one.
+    /// -   The socket-local structure cannot allocate a thread-local structure.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn warm_up(&self) -> Result<(), ()> {
+        Thread::get().or_else(Thread::initialize).map(|_| ()).ok_or(())
+    }
+
+    /// Performs all stages of the initialization, in order, for the current thread.
+    ///
+    /// Returns the metrics of the initialization if all stages succeeded, or the first stage which failed otherwise.
+    ///
+    /// Stages already performed, explicitly or lazily, are skipped. Calling `init` from each thread prior to entering a
+    /// latency-critical phase ensures no initialization occurs during that phase.
+    #[cold]
+    pub fn init(&self) -> Result<InitMetrics, InitStage> {
+        for stage in &InitStage::ALL {
+            self.init_stage(*stage).map_err(|_| *stage)?;
+        }
+
+        Ok(self.init_metrics())
+    }
+
+    /// Performs a single stage of the initialization, unless already performed.
+    ///
+    /// Returns Ok if the stage is performed, Err otherwise.
+    ///
+    /// Stages may be performed in any order, though each stage also performs the prior stages it depends on, if not
+    /// already performed.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn init_stage(&self, stage: InitStage) -> Result<(), ()> {
+        match stage {
+            InitStage::Key => {
+                Thread::prepare();
+                Ok(())
+            },
+            InitStage::Socket => Sockets::socket_handle().map(|_| ()).ok_or(()),
+            InitStage::Thread => self.warm_up(),
+        }
+    }
+
+    /// Prepares the current thread for a latency-critical phase, allocating up to `budget` bytes within it.
+    ///
+    /// In order:
+    ///
+    /// 1.  Performs all stages of the initialization, as `init`.
+    /// 2.  Reserves `budget` bytes, rounded up to a whole number of `HugePage`, on the socket of the current thread.
+    /// 3.  Prefaults and locks in RAM the reserved `HugePage`, so that no page fault occurs on first access.
+    /// 4.  Warms up the cache of the current thread, so that it holds a `LargePage` for each Normal class size.
+    ///
+    /// Returns a report of what was prepared; `LatencyCriticalReport::is_complete` verifies that no further system
+    /// call should be required by allocations within the budget.
+    ///
+    /// The thread-local cache of a thread may only be warmed up by the thread itself, hence each thread taking part in
+    /// the latency-critical phase should call `init_latency_critical`; steps already performed are cheap to repeat.
+    #[cold]
+    pub fn init_latency_critical(&self, budget: usize) -> LatencyCriticalReport {
+        let huge_page_size = LLConfiguration::HUGE_PAGE_SIZE.value();
+
+        let mut report = LatencyCriticalReport {
+            budget,
+            total_classes: Self::normal_classes().count(),
+            ..LatencyCriticalReport::default()
+        };
+
+        if let Err(stage) = self.init() {
+            report.failed_stage = Some(stage);
+            report.metrics = self.init_metrics();
+            return report;
+        }
+
+        self.reserve(budget.div_ceil(huge_page_size).max(1));
+
+        if let Some(socket) = Sockets::socket_handle() {
+            socket.for_each_huge_page(|page| {
+                report.reserved += huge_page_size;
+
+                if DOMAIN.platform().lock(page, huge_page_size) {
+                    report.locked += huge_page_size;
+                }
+            });
+        }
+
+        if let Some(thread_local) = Thread::get() {
+            report.warmed_classes = Self::prefill(&thread_local);
+        }
+
+        report.metrics = self.init_metrics();
+        report
+    }
+
+    /// Returns the metrics of the initialization, so far.
+    #[cold]
+    pub fn init_metrics(&self) -> InitMetrics { INIT_METRICS.snapshot() }
+
+    /// Ensures that at least `target` `HugePage` are allocated on the socket.
+    ///
+    /// Returns the minimum of the currently allocated number of pages and `target`.
+    ///
+    /// Failure to meet the `target` may occur if:
+    ///
+    /// -   The maximum number of `HugePage` a `socket` may contain has been reached.
+    /// -   The underlying `Platform` is failing to allocate more `HugePage`.
+    #[cold]
+    pub fn reserve(&self, target: usize) -> usize {
+        if let Some(socket) = Sockets::socket_handle() {
+            socket.reserve(target)
+        } else {
+            0
+        }
+    }
+
+    /// Attempts to ensure that `pages_per_class` `LargePage` of each Normal class size are reserved, on the socket, for
+    /// the threads declared Critical.
+    ///
+    /// Returns the minimum, across class sizes, of the number of reserved pages and `pages_per_class`.
+    ///
+    /// The reserved pages are only handed out to Critical threads once no other page is available, so that background
+    /// threads exhausting the memory cannot starve the Critical ones.
+    #[cold]
+    pub fn reserve_critical(&self, pages_per_class: usize) -> usize {
+        if let Some(socket) = Sockets::socket_handle() {
+            socket.reserve_critical(pages_per_class)
+        } else {
+            0
+        }
+    }
+
+    /// Attempts to ensure that the next `count` allocations of `layout`, by the current thread, take the fast path,
+    /// warming up the current thread if necessary.
+    ///
+    /// Returns the minimum of the number of such allocations and `count`.
+    ///
+    /// The blocks are pulled into the cache of the current thread, which holds a single `LargePage` per class size,
+    /// hence the number of blocks is limited by the number of blocks of a `LargePage`. Only Normal allocations are
+    /// cached, hence 0 is returned for larger layouts, as well as for layouts which cannot be allocated.
+    ///
+    /// Intended to be called right before entering a jitter-sensitive code region; the guarantee holds as long as the
+    /// current thread allocates no more than `count` blocks of the class size of `layout` in the meantime.
+    #[cold]
+    pub fn reserve_ahead(&self, count: usize, layout: Layout) -> usize {
+        let layout = layout.pad_to_align();
+
+        if layout.size() > self.maximum_size || !SocketHandle::is_valid_layout(layout) {
+            return 0;
+        }
+
+        match Thread::get().or_else(Thread::initialize) {
+            Some(thread_local) => thread_local.reserve_ahead(layout, count),
+            None => 0,
+        }
+    }
+
+    /// Returns the criticality of the current thread, Normal unless declared otherwise.
+    #[cold]
+    pub fn criticality(&self) -> Criticality { Thread::get().map(|thread| thread.0.criticality()).unwrap_or_default() }
+
+    /// Declares the criticality of the current thread, warming it up if necessary.
+    ///
+    /// The cache of a Background thread is flushed periodically, so as to remain minimal, see `ThreadProfile`.
+    ///
+    /// Returns Ok if the criticality is declared, Err if the current thread could not be warmed up.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn set_criticality(&self, criticality: Criticality) -> Result<(), ()> {
+        let thread = Thread::get().or_else(Thread::initialize).ok_or(())?;
+
+        thread.set_criticality(criticality);
+
+        Ok(())
+    }
+
+    /// Returns whether rehoming is enabled.
+    ///
+    /// Rehoming is process-wide, shared by all instances; see `set_rehoming`.
+    pub fn is_rehoming(&self) -> bool { REHOMING.is_enabled(DOMAIN.platform()) }
+
+    /// Enables or disables rehoming, process-wide, overriding the `LLMALLOC_REHOME` environment variable.
+    ///
+    /// When rehoming, each thread checks the NUMA node it runs on every 4096 allocations, and, once rescheduled onto a
+    /// node served by another socket, retires its thread cache to its former socket and acquires one from the socket
+    /// of its new node, so that a long-lived thread does not keep allocating remote memory. The allocations already
+    /// made are not migrated.
+    ///
+    /// Each check costs a query of the current node; threads in frame mode, or within a scope forbidding allocations,
+    /// are not rehomed, nor are the threads of a platform supplied by the embedder.
+    #[cold]
+    pub fn set_rehoming(&self, enabled: bool) { REHOMING.set(enabled) }
+
+    /// Rehomes the current thread onto the socket of the NUMA node it runs on, if served by another socket, whether
+    /// rehoming is enabled or not, as a thread just pinned to the CPUs of another node may.
+    ///
+    /// Returns true if the thread was rehomed, and false if it was already served by the socket of its node, if it is
+    /// in frame mode, or within a scope forbidding allocations, or if it could not be rehomed; see `set_rehoming`.
+    #[cold]
+    pub fn rehome_thread(&self) -> bool { Thread::get().is_some_and(Thread::rehome) }
+
+    /// Forbids allocations on the current thread, until the returned guard is dropped, warming up the current thread
+    /// if necessary.
+    ///
+    /// Returns Err if the current thread could not be warmed up.
+    ///
+    /// Within the scope, any allocation, or reallocation, attempted by the current thread fails with
+    /// `AllocationError::Forbidden`, in all builds, whereupon `GlobalAlloc` users invoke the allocation error handler,
+    /// which by default aborts the process. The attempt never panics, as `GlobalAlloc` cannot unwind.
+    ///
+    /// Deallocations remain permitted, and scopes may be nested, allocations being permitted again once all guards of
+    /// the thread are dropped.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn forbid_allocation(&self) -> Result<ForbidAllocationGuard, ()> {
+        let thread = Thread::get().or_else(Thread::initialize).ok_or(())?;
+
+        thread.0.set_forbidden_scopes(thread.0.forbidden_scopes().saturating_add(1));
+
+        Ok(ForbidAllocationGuard { _thread: PhantomData })
+    }
+
+    /// Enters frame mode on the current thread, with a frame region of `capacity` bytes.
+    ///
+    /// In frame mode, the allocations of the thread are served from the frame region, and freed wholesale by
+    /// `end_frame`; deallocating them is a no-op. The allocations which must outlive the frame are to be made by
+    /// `allocate_persistent`, and once the frame region is exhausted, the allocations of the frame are made from the
+    /// heap, as usual. The frame allocations are not recorded in the statistics.
+    ///
+    /// Returns an error if the thread is already in frame mode, if the frame region cannot be allocated, or if too
+    /// many threads are already in frame mode.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn enter_frame_mode(&self, capacity: usize) -> Result<(), ()> {
+        let thread = Thread::get().or_else(Thread::initialize).ok_or(())?;
+
+        if thread.frame().is_some() {
+            return Err(());
+        }
+
+        let size = capacity.checked_add(Frame::HEADER_SIZE).ok_or(())?;
+        let layout = Layout::from_size_align(size, Frame::ALIGNMENT).map_err(|_| ())?;
+
+        let region = self.allocate_impl(layout, false, false, ARMED.is_armed()).map_err(|_| ())?;
+
+        let slot = match FRAMES.register(region, size) {
+            Some(slot) => slot,
+            None => {
+                //  Safety:
+                //  -   `region` was just allocated, and is not in use.
+                unsafe { self.deallocate(region) };
+                return Err(());
+            }
+        };
+
+        //  Safety:
+        //  -   `region` is valid for writes of `size` bytes, aligned on `Frame::ALIGNMENT`, as it was just allocated.
+        let frame = unsafe { Frame::initialize(region, size, slot) };
+
+        thread.0.set_frame(Some(frame.region()));
+
+        Ok(())
+    }
+
+    /// Returns whether the current thread is in frame mode.
+    pub fn is_frame_mode(&self) -> bool { Thread::get().is_some_and(|thread| thread.frame().is_some()) }
+
+    /// Ends the current frame of the current thread, if in frame mode, freeing all its frame allocations at once.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the frame allocations of the current thread are no longer in use.
+    pub unsafe fn end_frame(&self) {
+        if let Some(frame) = Thread::get().and_then(|thread| thread.frame()) {
+            frame.reset();
+        }
+    }
+
+    /// Leaves frame mode on the current thread, if in frame mode, freeing all its frame allocations, and returning its
+    /// frame region to the heap.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the frame allocations of the current thread are no longer in use.
+    #[cold]
+    pub unsafe fn exit_frame_mode(&self) {
+        if let Some(thread) = Thread::get() {
+            thread.release_frame();
+        }
+    }
+
+    /// Pins the current reclamation epoch, until the guard is dropped.
+    ///
+    /// While pinned, the memory retired by `defer_free` is not freed, hence the readers of a lock-free data structure
+    /// may safely access the nodes they reached, even if concurrently unlinked and retired.
+    ///
+    /// Returns an error if too many guards are alive.
+    #[allow(clippy::result_unit_err)]
+    pub fn pin(&self) -> Result<ReclamationGuard, ()> {
+        let slot = RECLAMATION.pin().ok_or(())?;
+
+        Ok(ReclamationGuard { slot })
+    }
+
+    /// Retires the memory located at `pointer`, freeing it once no guard pinned before its retirement is still alive.
+    ///
+    /// Each retirement is recorded in a small allocation of its own; if the record cannot be allocated, the memory is
+    /// leaked rather than freed unsafely. The retired memory is collected periodically, or by `reclaim`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` has been returned by a prior call to `allocate`.
+    /// -   Assumes `pointer` has not been deallocated, nor retired, since its allocation.
+    /// -   Assumes the memory pointed by `pointer` is no longer reachable, except by the holders of alive guards.
+    pub unsafe fn defer_free(&self, pointer: NonNull<u8>) {
+        let record = match self.allocate_persistent(Reclamation::RECORD_LAYOUT) {
+            Some(record) => record,
+            None => return,
+        };
+
+        if RECLAMATION.retire(record, pointer) {
+            self.reclaim();
+        }
+    }
+
+    /// Frees the retired memory which no alive guard may still access, returning the number of retirements freed.
+    #[cold]
+    pub fn reclaim(&self) -> usize {
+        //  Safety:
+        //  -   The pointers retired, and their records, were allocated and are no longer in use.
+        RECLAMATION.collect(|pointer| unsafe { self.deallocate(pointer) })
+    }
+
+    /// Seals the Large or Huge allocation located at `pointer`, of `size` bytes, making its pages read-only.
+    ///
+    /// The allocation remains in the heap, readable, but any write to it faults until it is unsealed, protecting
+    /// configuration snapshots or loaded assets from accidental modification. The pages of Normal allocations are
+    /// shared with other allocations, hence cannot be sealed; small objects, such as lookup tables built at start-up,
+    /// are instead carved out of an arena, sealed as a whole, see `allocate_arena`.
+    ///
+    /// Returns an error if the allocation is not a Large or Huge allocation of llmalloc, or if the OS fails to protect
+    /// its pages.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` has been returned by a prior call to `allocate`, for `size` bytes.
+    /// -   Assumes `pointer` has not been deallocated since its allocation.
+    /// -   Assumes the memory is unsealed before being written to, reallocated, remapped, or deallocated.
+    #[allow(clippy::result_unit_err)]
+    pub unsafe fn seal(&self, pointer: NonNull<u8>, size: usize) -> Result<(), ()> {
+        self.protect(pointer, size, false)
+    }
+
+    /// Unseals the allocation located at `pointer`, of `size` bytes, previously sealed by `seal`, making its pages
+    /// writable again.
+    ///
+    /// Returns an error if the allocation is not a Large or Huge allocation of llmalloc, or if the OS fails to protect
+    /// its pages.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` has been returned by a prior call to `allocate`, for `size` bytes.
+    /// -   Assumes `pointer` has not been deallocated since its allocation.
+    #[allow(clippy::result_unit_err)]
+    pub unsafe fn unseal(&self, pointer: NonNull<u8>, size: usize) -> Result<(), ()> {
+        self.protect(pointer, size, true)
+    }
+
+    /// Allocates an arena of at least `size` bytes, out of a single Large or Huge allocation.
+    ///
+    /// The objects carved out of the arena, see `Arena::allocate`, are sealed and unsealed as a whole, see
+    /// `seal_arena`, and deallocated along with it, see `deallocate_arena`.
+    pub fn allocate_arena(&self, size: usize) -> Result<Arena, AllocationError> {
+        //  Normal allocations share their pages, hence cannot be sealed.
+        let size = size.max(Properties::<LLConfiguration>::normal_threshold().value() + 1);
+
+        let layout = Layout::from_size_align(size, 1).map_err(|_| AllocationError::ExceedsMaximumSize)?;
+
+        //  The allocations of a frame region cannot be sealed either.
+        let base = self.try_allocate_impl(layout, false)?;
+
+        Ok(Arena::new(base, size))
+    }
+
+    /// Seals `arena`, making its pages read-only, as per `seal`; no object is carved out of it until it is unsealed.
+    ///
+    /// Returns an error if the OS fails to protect its pages, or if the arena was served by the system allocator.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the objects of `arena` are not written to until it is unsealed.
+    #[allow(clippy::result_unit_err)]
+    pub unsafe fn seal_arena(&self, arena: &mut Arena) -> Result<(), ()> {
+        self.seal(arena.base(), arena.size())?;
+
+        arena.set_sealed(true);
+
+        Ok(())
+    }
+
+    /// Unseals `arena`, previously sealed by `seal_arena`, making its pages writable again.
+    ///
+    /// Returns an error if the OS fails to protect its pages.
+    #[allow(clippy::result_unit_err)]
+    pub fn unseal_arena(&self, arena: &mut Arena) -> Result<(), ()> {
+        //  Safety:
+        //  -   `arena` was allocated by `allocate_arena`, and is only deallocated by `deallocate_arena`.
+        unsafe { self.unseal(arena.base(), arena.size())? };
+
+        arena.set_sealed(false);
+
+        Ok(())
+    }
+
+    /// Deallocates `arena`, along with all the objects carved out of it, unsealing it first if sealed.
+    ///
+    /// If the OS fails to unseal it, the arena is leaked rather than deallocated.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the objects of `arena` are no longer in use.
+    pub unsafe fn deallocate_arena(&self, mut arena: Arena) {
+        if arena.is_sealed() && self.unseal_arena(&mut arena).is_err() {
+            return;
+        }
+
+        self.deallocate(arena.base());
+    }
+
+    /// Allocates a region of at least `size` bytes for executable code, mapped as per `mapping`.
+    ///
+    /// The region is a dedicated mapping, outside of the heap, rounded up to a multiple of the OS page size. It is
+    /// initially writable through its writable view; a dual-mapped region is always executable through its executable
+    /// view, whilst a flip-protected region only becomes executable through `make_executable`.
+    pub fn allocate_code(&self, size: usize, mapping: CodeMapping) -> Result<CodeRegion, AllocationError> {
+        if size > self.maximum_size {
+            return Err(AllocationError::ExceedsMaximumSize);
+        }
+
+        DOMAIN.platform().map_code(size, mapping).ok_or(AllocationError::OutOfMemory)
+    }
+
+    /// Makes the flip-protected `region` read-execute, so that its code may be executed, but no longer written.
+    ///
+    /// A dual-mapped region is always executable through its executable view, hence left untouched.
+    ///
+    /// Returns an error if the OS fails to protect the region.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `region` is not being written to concurrently.
+    #[allow(clippy::result_unit_err)]
+    pub unsafe fn make_executable(&self, region: &CodeRegion) -> Result<(), ()> { Self::protect_code(region, true) }
+
+    /// Makes the flip-protected `region` read-write, so that its code may be written, but no longer executed.
+    ///
+    /// A dual-mapped region is always writable through its writable view, hence left untouched.
+    ///
+    /// Returns an error if the OS fails to protect the region.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the code of `region` is not being executed concurrently.
+    #[allow(clippy::result_unit_err)]
+    pub unsafe fn make_writable(&self, region: &CodeRegion) -> Result<(), ()> { Self::protect_code(region, false) }
+
+    /// Deallocates `region`, unmapping its views.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the memory of `region` is no longer in use, neither written nor executed.
+    pub unsafe fn deallocate_code(&self, region: CodeRegion) { DOMAIN.platform().unmap_code(region) }
+
+    /// Allocates a thread stack of at least `size` bytes, suitable for `pthread_attr_setstack`.
+    ///
+    /// The stack is a dedicated mapping, outside of the heap, rounded up to a multiple of the Large Page size, backed
+    /// by Transparent Huge Pages when available, and preceded by an inaccessible guard page. If `prefault`, its memory
+    /// is faulted in by the current thread, hence local to its NUMA node.
+    pub fn allocate_stack(&self, size: usize, prefault: bool) -> Result<ThreadStack, AllocationError> {
+        if size > self.maximum_size {
+            return Err(AllocationError::ExceedsMaximumSize);
+        }
+
+        DOMAIN.platform().map_stack(size, prefault).ok_or(AllocationError::OutOfMemory)
+    }
+
+    /// Deallocates `stack`, unmapping it along with its guard page.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the memory of `stack` is no longer in use, that is the thread running on it has exited.
+    pub unsafe fn deallocate_stack(&self, stack: ThreadStack) { DOMAIN.platform().unmap_stack(stack) }
+
+    /// Allocates a buffer of at least `size` bytes, backed by huge pages and locked in RAM, for devices addressing
+    /// memory physically.
+    ///
+    /// The buffer is a dedicated mapping, outside of the heap, rounded up to a multiple of 2 MB. It is backed by
+    /// HugeTLB pages if available, or by Transparent Huge Pages otherwise, and faulted in by the current thread.
+    ///
+    /// Returns an error if the memory cannot be mapped, or locked, for example once the limit of locked memory is
+    /// reached.
+    pub fn allocate_physical(&self, size: usize) -> Result<PhysicalBuffer, AllocationError> {
+        if size > self.maximum_size {
+            return Err(AllocationError::ExceedsMaximumSize);
+        }
+
+        DOMAIN.platform().map_physical(size).ok_or(AllocationError::OutOfMemory)
+    }
+
+    /// Invokes `report` on each physically contiguous segment of `buffer`, in order, returning their number.
+    ///
+    /// Returns an error if the physical addresses cannot be looked up, notably if the process lacks the privileges to
+    /// read them from `/proc/self/pagemap`; the segments reported before the error are then not to be relied upon.
+    ///
+    /// The physical addresses of HugeTLB pages are stable; those of Transparent Huge Pages may change should the
+    /// kernel split or migrate them, despite their being locked.
+    #[cold]
+    #[allow(clippy::result_unit_err)]
+    pub fn physical_segments<F>(&self, buffer: &PhysicalBuffer, mut report: F) -> Result<usize, ()>
+        where
+            F: FnMut(&PhysicalSegment),
+    {
+        DOMAIN.platform().physical_segments(buffer, &mut report).ok_or(())
+    }
+
+    /// Deallocates `buffer`, unlocking and unmapping it.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes the memory of `buffer` is no longer in use, neither by the process nor by any device.
+    pub unsafe fn deallocate_physical(&self, buffer: PhysicalBuffer) { DOMAIN.platform().unmap_physical(buffer) }
+
+    /// Compacts the sparse Large Pages, by relocating the `allocations` residing in them.
+    ///
+    /// A Large Page is sparse if the listed allocations residing in it occupy at most a quarter of it. Each of them is
+    /// copied into a new allocation outside of the sparse pages, its `pointer` is updated, `relocate` is invoked with
+    /// the old and new pointers whilst the old memory is still readable, and the old memory is then deallocated. The
+    /// Large Pages emptied by the pass return to their `HugePage`, available to any class size; the `HugePage`
+    /// themselves are retained by the sockets, rather than returned to the OS.
+    ///
+    /// Only Normal allocations are relocated; the allocations which cannot be relocated, for lack of memory, are left
+    /// in place.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes each of `allocations` has been returned by a prior call to `allocate`, with its `layout`.
+    /// -   Assumes none of `allocations` has been deallocated since its allocation, nor is listed twice.
+    /// -   Assumes none of `allocations` is accessed concurrently, and that all references to them are updated, through
+    ///     their `pointer` or `relocate`, before being accessed again.
+    #[cold]
+    pub unsafe fn compact<F>(&self, allocations: &mut [Relocatable], mut relocate: F) -> CompactionReport
+        where
+            F: FnMut(NonNull<u8>, NonNull<u8>),
+    {
+        let mut report = CompactionReport::default();
+
+        let layout = match Layout::array::<(usize, usize)>(allocations.len()) {
+            Ok(layout) if layout.size() > 0 => layout,
+            _ => return report,
+        };
+
+        let scratch = match self.allocate_persistent(layout) {
+            Some(scratch) => scratch.cast::<(usize, usize)>(),
+            None => return report,
+        };
+
+        for index in 0..allocations.len() {
+            scratch.as_ptr().add(index).write((0, 0));
+        }
+
+        //  Safety:
+        //  -   `scratch` is valid for `allocations.len()` elements, all initialized.
+        let entries = slice::from_raw_parts_mut(scratch.as_ptr(), allocations.len());
+
+        let plan = CompactionPlan::new(allocations, entries, Self::is_relocatable);
+
+        report.sparse_pages = plan.sparse_pages();
+
+        //  Allocations landing in a sparse page, held aside until the end of the pass, linked through their first word.
+        let mut held: *mut u8 = ptr::null_mut();
+
+        for (_, group) in plan.pages() {
+            let mut evacuated = true;
+
+            for (_, index) in group {
+                let allocation = &mut allocations[*index];
+
+                let target = match self.relocation_target(&plan, allocation.layout, &mut held) {
+                    Some(target) => target,
+                    None => {
+                        evacuated = false;
+                        continue;
+                    },
+                };
+
+                let old = allocation.pointer;
+
+                //  Safety:
+                //  -   Both `old` and `target` are valid for `allocation.layout.size()` bytes.
+                //  -   `target` is freshly allocated, hence does not overlap `old`.
+                ptr::copy_nonoverlapping(old.as_ptr(), target.as_ptr(), allocation.layout.size());
+
+                allocation.pointer = target;
+                relocate(old, target);
+
+                self.deallocate(old);
+
+                report.relocated += 1;
+                report.relocated_bytes += allocation.layout.size();
+            }
+
+            report.evacuated_pages += evacuated as usize;
+        }
+
+        while let Some(pointer) = NonNull::new(held) {
+            held = pointer.cast::<*mut u8>().as_ptr().read();
+            self.deallocate(pointer);
+        }
+
+        self.deallocate(scratch.cast());
+
+        report
+    }
+
+    /// Returns the capabilities of the environment, and thereby the configuration selected.
+    ///
+    /// The capabilities are detected on first use, and HugeTLB is downgraded on the first failure to map a `HugePage`
+    /// with it, hence those returned are only final once the first `HugePage` is allocated. `Capabilities::downgrades`
+    /// lists, in a form suitable for logging, the capabilities downgraded as compared to the most capable
+    /// configuration.
+    #[cold]
+    pub fn capabilities(&self) -> Capabilities { DOMAIN.platform().capabilities() }
+
+    /// Forgoes HugeTLB, process-wide, as the `LLMALLOC_TRANSPARENT_HUGE_PAGES` environment variable does: the
+    /// `HugePage` mapped from then on are backed by Transparent Huge Pages, requested through `madvise`, or failing
+    /// that by normal pages.
+    ///
+    /// Intended for hosts where no pool of HugeTLB pages is provisioned, sparing the failed mappings of the detection
+    /// by trial; the `HugePage` already mapped are unaffected, and HugeTLB cannot be selected anew.
+    ///
+    /// Returns false if the platform cannot forgo HugeTLB.
+    #[cold]
+    pub fn use_transparent_huge_pages(&self) -> bool { DOMAIN.platform().use_transparent_huge_pages() }
+
+    /// Returns the capabilities of the host, in details: the HugeTLB page sizes, the mode of Transparent Huge Pages,
+    /// the number of NUMA nodes, the availability of `rseq`, the limit of locked memory, and the configuration
+    /// selected, as per `capabilities`.
+    ///
+    /// The details are detected anew on each call, from `/sys` and system calls, and are intended for deployment
+    /// tooling to verify that an instance runs in the intended configuration; their `Display` lists them one per line.
+    #[cold]
+    pub fn host_capabilities(&self) -> HostCapabilities { DOMAIN.platform().host_capabilities() }
+
+    /// Returns the pools of HugeTLB pages, as probed from `/sys` on the detection of the capabilities, that is on the
+    /// initialization of the first socket.
+    ///
+    /// A pool without pages left, free or yet to be overcommitted, is passed over by the mappings from the start; the
+    /// counts are those of the probe, not updated afterwards, and no pool is returned if `/sys` is not accessible.
+    #[cold]
+    pub fn huge_tlb_pools(&self) -> HugeTlbPools { DOMAIN.platform().huge_tlb_pools() }
+
+    /// Acquires the privilege required to back Huge Pages by large pages, on the platforms requiring one.
+    ///
+    /// On Windows, large pages are only allocated by the processes holding the `SeLockMemoryPrivilege`, without which
+    /// the Huge Pages silently fall back to normal pages. The privilege is acquired on first use of the allocator, its
+    /// absence merely being reported as a downgrade by `capabilities`; this reports why it cannot be acquired, in a
+    /// form suitable for logging, with the remedy if any.
+    ///
+    /// Returns `PrivilegeError::Unsupported` on the platforms requiring no privilege.
+    #[cold]
+    pub fn acquire_large_page_privilege(&self) -> Result<(), PrivilegeError> {
+        DOMAIN.platform().acquire_large_page_privilege()
+    }
+
+    /// Returns the metrics of the fallbacks, since the start of the process.
+    ///
+    /// A deployment which works, but is slower than expected, typically exhibits fallbacks: `HugePage` backed by
+    /// Transparent Huge Pages or normal pages rather than HugeTLB pages, retried mappings, threads served by the socket
+    /// of node 0 as their own node could not be determined, etc...
+    #[cold]
+    pub fn fallback_metrics(&self) -> FallbackMetrics { DOMAIN.platform().fallbacks().snapshot() }
+
+    /// Reconciles the `HugePage` owned by the sockets against the view of the OS, invoking `report` for each.
+    ///
+    /// Returns the number of anomalous `HugePage`, that is split or migrated.
+    ///
+    /// The reconciliation parses OS statistics, and is therefore slow: it is intended for debugging purposes, not for
+    /// use on the critical path.
+    #[cold]
+    pub fn reconcile<F>(&self, mut report: F) -> usize
+        where
+            F: FnMut(&HugePageReport),
+    {
+        let mut anomalies = 0;
+
+        SOCKETS.for_each_socket_handle(|node, socket| {
+            socket.for_each_huge_page(|page| {
+                let page_report = DOMAIN.platform().reconcile(page, LLConfiguration::HUGE_PAGE_SIZE.value(), node);
+
+                if page_report.is_anomalous() {
+                    anomalies += 1;
+                }
+
+                report(&page_report);
+            });
+        });
+
+        anomalies
+    }
+
+    /// Reports the statistics of the socket of each NUMA node, invoking `report` for each, and returns the number of
+    /// nodes reported.
+    ///
+    /// Only the nodes whose socket has been allocated are reported, in increasing order. The `HugePage` of each socket
+    /// are walked to count them, hence the report is intended for monitoring purposes, not for use on the critical
+    /// path.
+    #[cold]
+    pub fn node_stats<F>(&self, mut report: F) -> usize
+        where
+            F: FnMut(&NodeStatistics),
+    {
+        let mut nodes = 0;
+
+        SOCKETS.for_each_socket_handle(|node, socket| {
+            let mut huge_pages = 0;
+            socket.for_each_huge_page(|_| huge_pages += 1);
+
+            let statistics = socket.statistics();
+            let hosted = statistics.category(Category::Normal).live_bytes()
+                .wrapping_add(statistics.category(Category::Large).live_bytes());
+
+            report(&NodeStatistics {
+                node: node.value(),
+                huge_pages,
+                in_use_bytes: statistics.total().live_bytes(),
+                cached_bytes: (huge_pages * LLConfiguration::HUGE_PAGE_SIZE.value()).saturating_sub(hosted),
+                foreign_allocations: FOREIGN_ALLOCATIONS.get(node.value() as usize),
+                remote_deallocations: socket.remote_deallocations(),
+            });
+
+            nodes += 1;
+        });
+
+        nodes
+    }
+
+    /// Reports the residency of the memory retained by the allocator, invoking `report` for each range, and returns
+    /// the total number of resident bytes.
+    ///
+    /// The ranges are the `HugePage` owned by the sockets, which host the Normal and Large allocations and their free
+    /// cells, followed by the Huge blocks retained for reuse, see `set_direct_retained`. The residency is looked up
+    /// with `mincore`: memory never touched since mapped, or reclaimed by the kernel, is not resident, whereas memory
+    /// advised with `MADV_FREE` is resident until the kernel actually reclaims it, under memory pressure.
+    ///
+    /// The ranges whose residency cannot be looked up are not reported. The lookup is slow: it is intended for
+    /// monitoring purposes, not for use on the critical path.
+    #[cold]
+    pub fn residency<F>(&self, mut report: F) -> usize
+        where
+            F: FnMut(&ResidencyReport),
+    {
+        let mut resident = 0;
+
+        let mut record = |pointer: NonNull<u8>, size: usize, node: Option<NumaNodeIndex>| {
+            if let Some(bytes) = DOMAIN.platform().resident(pointer, size) {
+                let address = pointer.as_ptr() as usize;
+                let node = node.map(|node| node.value());
+
+                resident += bytes;
+                report(&ResidencyReport { address, size, node, resident: bytes });
+            }
+        };
+
+        SOCKETS.for_each_socket_handle(|node, socket| {
+            socket.for_each_huge_page(|page| record(page, LLConfiguration::HUGE_PAGE_SIZE.value(), Some(node)));
+        });
+
+        DOMAIN.for_each_retained(|block, size| record(block, size, None));
+
+        resident
+    }
+
+    /// Collapses the hot regions of the `HugePage`s owned by the sockets into Transparent Huge Pages, with
+    /// `MADV_COLLAPSE`, and returns the report of the collapse.
+    ///
+    /// A `HugePage` which could not be backed by HugeTLB pages falls back on normal pages, which the kernel only
+    /// promotes lazily, if at all. Each `HugePage` is instead split into regions of 2 MB, and each region of which at
+    /// least `min_resident` bytes are resident is collapsed, the bytes which were not resident then being committed;
+    /// a `min_resident` of 2 MB thus only collapses the fully resident regions, at no extra memory cost.
+    ///
+    /// The collapse copies the memory, and is slow: it is intended to be invoked once the heap is warm, for example by
+    /// a maintenance thread, not on the critical path. The collapse is honored on Linux 6.1 and later; elsewhere, the
+    /// report is not `supported`, and no region is attempted.
+    #[cold]
+    pub fn collapse(&self, min_resident: usize) -> CollapseReport {
+        let region_size = crate::collapse::REGION_SIZE.min(LLConfiguration::HUGE_PAGE_SIZE.value());
+
+        let platform = DOMAIN.platform();
+        let mut report = CollapseReport { supported: true, ..CollapseReport::default() };
+
+        SOCKETS.for_each_socket_handle(|_, socket| {
+            socket.for_each_huge_page(|page| {
+                for offset in (0..LLConfiguration::HUGE_PAGE_SIZE.value()).step_by(region_size) {
+                    if !report.supported {
+                        return;
+                    }
+
+                    //  Safety:
+                    //  -   `offset` is within the `HugePage`.
+                    let region = unsafe { NonNull::new_unchecked(page.as_ptr().add(offset)) };
+
+                    if platform.resident(region, region_size).unwrap_or(0) < min_resident {
+                        report.cold += 1;
+                        continue;
+                    }
+
+                    report.record(platform.collapse(region, region_size));
+                }
+            });
+        });
+
+        if !report.supported {
+            return CollapseReport::default();
+        }
+
+        report
+    }
+
+    /// Returns the statistics of the allocations and deallocations performed since the last call to `stats_reset`, or
+    /// since the start of the process if it was never called.
+    ///
+    /// The counters of each thread are updated without synchronization, hence the statistics are approximate while
+    /// other threads are concurrently allocating or deallocating.
+    #[cold]
+    pub fn stats(&self) -> Statistics { RESET_EPOCH.since(&Sockets::statistics()) }
+
+    /// Resets the statistics, as returned by `stats` and `stats_interval`.
+    #[cold]
+    pub fn stats_reset(&self) {
+        let current = Sockets::statistics();
+
+        RESET_EPOCH.reset(&current);
+        INTERVAL_EPOCH.reset(&current);
+    }
+
+    /// Returns the statistics of the allocations and deallocations performed since the last call to `stats_interval`
+    /// or `stats_reset`, and starts a new interval.
+    ///
+    /// Concurrent calls partition the deltas between themselves: no allocation or deallocation is reported twice.
+    #[cold]
+    pub fn stats_interval(&self) -> Statistics { INTERVAL_EPOCH.advance(&Sockets::statistics()) }
+
+    /// Prints a human-readable report of the statistics to `writer`.
+    ///
+    /// The report is made of multiple sections: the configuration, capabilities, and pools of HugeTLB pages, the
+    /// statistics merged across all sockets then those of each socket, the histogram of the requested sizes, and the
+    /// fallbacks. All counters are since the start of the process, unaffected by `stats_reset`, so that reports of
+    /// different runs compare.
+    ///
+    /// The per-class view is provided by the histogram of the requested sizes, hence is empty unless the `histogram`
+    /// feature is enabled.
+    ///
+    /// The report is formatted directly into `writer`, hence a writer which does not allocate, or does not allocate
+    /// from this allocator, may be used from within any context.
+    #[cold]
+    pub fn stats_print<W>(&self, writer: &mut W) -> fmt::Result
+        where
+            W: fmt::Write,
+    {
+        let writer: &mut dyn fmt::Write = writer;
+
+        print::write_begin(writer)?;
+
+        let (large_page_size, huge_page_size) =
+            (LLConfiguration::LARGE_PAGE_SIZE.value(), LLConfiguration::HUGE_PAGE_SIZE.value());
+
+        print::write_configuration(writer, large_page_size, huge_page_size)?;
+        print::write_capabilities(writer, &self.capabilities())?;
+        print::write_huge_tlb_pools(writer, &self.huge_tlb_pools())?;
+        print::write_statistics(writer, format_args!("Merged"), &Sockets::statistics())?;
+
+        let mut result = Ok(());
+
+        SOCKETS.for_each_socket_handle(|node, socket| {
+            if result.is_ok() {
+                result = print::write_statistics(writer, format_args!("Socket {}", node.value()), &socket.statistics());
+            }
+        });
+
+        result?;
+
+        print::write_histogram(writer, &self.size_histogram())?;
+        print::write_fallbacks(writer, &self.fallback_metrics())?;
+        print::write_end(writer)
+    }
+
+    /// Registers a watermark, invoking `callback` whenever the live bytes reach `rising`, then drop below `falling`.
+    ///
+    /// The watermark starts below its thresholds, hence `callback` is invoked on the next evaluation if the live bytes
+    /// already reached `rising`.
+    ///
+    /// The live bytes are evaluated on each Large or Huge allocation or deallocation, and on every 1024th allocation or
+    /// deallocation of each thread otherwise, hence a crossing is detected late, and missed if crossed back meanwhile.
+    /// The callback is invoked from within the allocation or deallocation detecting the crossing.
+    ///
+    /// Returns None if `falling` exceeds `rising`, or if too many watermarks are already registered.
+    pub fn register_watermark(&self, rising: usize, falling: usize, callback: WatermarkCallback)
+        -> Option<WatermarkId>
+    {
+        WATERMARKS.register(rising, falling, callback)
+    }
+
+    /// Unregisters the watermark identified by `id`.
+    ///
+    /// Returns false if it was not registered. An evaluation racing with the unregistration may still invoke its
+    /// callback, one last time.
+    pub fn unregister_watermark(&self, id: WatermarkId) -> bool { WATERMARKS.unregister(id) }
+
+    /// Returns the current allocation epoch, see `advance_epoch`.
+    pub fn epoch(&self) -> u64 { EPOCHS.epoch() }
+
+    /// Advances the allocation epoch, returning the new epoch.
+    ///
+    /// The epoch is meant to be advanced at known points of the application, such as the end of a frame, or of a
+    /// request, so that the allocations surviving many epochs stand out, see `surviving_allocations`.
+    pub fn advance_epoch(&self) -> u64 { EPOCHS.advance() }
+
+    /// Returns whether allocations are stamped with their epoch.
+    pub fn is_epoch_tracking(&self) -> bool { EPOCHS.is_tracking() }
+
+    /// Enables, or disables, the stamping of allocations with their epoch.
+    ///
+    /// Only the allocations made while enabled are stamped; enabling clears the stamps of any prior tracking. The
+    /// stamps are kept in a fixed-capacity table, and an allocation which cannot be stamped is counted by
+    /// `untracked_allocations` instead.
+    pub fn set_epoch_tracking(&self, enabled: bool) { EPOCHS.set_tracking(enabled) }
+
+    /// Returns the number of allocations which could not be stamped, as the table was full, since tracking was last
+    /// enabled.
+    pub fn untracked_allocations(&self) -> u64 { EPOCHS.untracked() }
+
+    /// Invokes `report` for each stamped allocation surviving more than `epochs` epochs, that is stamped with an epoch
+    /// older than the current epoch minus `epochs`, and not yet deallocated.
+    ///
+    /// Returns the number of allocations reported. The stamps are read without synchronization, hence allocations
+    /// made or deallocated concurrently may or may not be reported.
+    #[cold]
+    pub fn surviving_allocations<F>(&self, epochs: u64, report: F) -> usize
+        where
+            F: FnMut(&SurvivingAllocation),
+    {
+        EPOCHS.survivors(epochs, report)
+    }
+
+    /// Registers a tag, invoking `callback` with the pointer and size of each block allocated with the tag, by
+    /// `allocate_tagged`, as it is deallocated.
+    ///
+    /// While any tag is registered, each deallocation probes the table of the tagged blocks; a reallocation, or remap,
+    /// of a tagged block carries its tag over to the resulting block, without invoking the callback.
+    ///
+    /// Returns None if too many tags are already registered.
+    #[cold]
+    pub fn register_tag(&self, callback: TagCallback) -> Option<Tag> { TAGS.register(callback) }
+
+    /// Unregisters `tag`, untagging the blocks allocated with it, so that their deallocations are no longer reported.
+    ///
+    /// Returns false if it was not registered. A deallocation racing with the unregistration may still invoke its
+    /// callback, one last time.
+    #[cold]
+    pub fn unregister_tag(&self, tag: Tag) -> bool { TAGS.unregister(tag) }
+
+    /// Returns the number of blocks allocated by `allocate_tagged` which could not be tagged, as the table was full,
+    /// since the start of the process.
+    pub fn untagged_allocations(&self) -> u64 { TAGS.untagged() }
+
+    /// Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, from the heap even in frame
+    /// mode, tagged with `tag`, so that its deallocation invokes the callback of the tag.
+    ///
+    /// The tags are kept in a fixed-capacity table, and a block which cannot be tagged is counted by
+    /// `untagged_allocations` instead.
+    ///
+    /// If allocation fails, the returned pointer may be NULL.
+    pub fn allocate_tagged(&self, layout: Layout, tag: Tag) -> Option<NonNull<u8>> {
+        let pointer = self.try_allocate_impl(layout, false).ok()?;
+
+        TAGS.record(pointer, layout.size(), tag);
+
+        Some(pointer)
+    }
+
+    /// Returns the histogram of the requested allocation sizes, since the start of the process.
+    ///
+    /// The histogram is always empty, unless the `histogram` feature is enabled.
+    ///
+    /// Requested sizes are recorded after being rounded up to a multiple of their alignment.
+    #[cold]
+    pub fn size_histogram(&self) -> SizeHistogram {
+        let mut histogram = SizeHistogram::default();
+
+        SOCKETS.for_each_socket_handle(|_, socket| histogram.merge(&socket.size_histogram()));
+
+        histogram
+    }
+
+    /// Allocates `size` bytes of memory, aligned on at least an `alignment` boundary.
+    ///
+    /// If allocation fails, the returned pointer may be NULL.
+    pub fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> { self.try_allocate_impl(layout, true).ok() }
+
+    /// Allocates `size` bytes of memory, aligned on at least an `alignment` boundary.
+    ///
+    /// Returns the reason of the failure, if the allocation fails.
+    ///
+    /// With the `system-fallback` feature, the requests llmalloc cannot serve, whether due to an unsupported alignment,
+    /// exhausted memory, or a thread which cannot be initialized, are delegated to the system allocator instead.
+    pub fn try_allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocationError> {
+        self.try_allocate_impl(layout, true)
+    }
+
+    /// Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, from the heap even in frame
+    /// mode, so that the allocation outlives the frame.
+    ///
+    /// If allocation fails, the returned pointer may be NULL.
+    pub fn allocate_persistent(&self, layout: Layout) -> Option<NonNull<u8>> {
+        self.try_allocate_impl(layout, false).ok()
+    }
+
+    /// Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, failing fast rather than
+    /// entering a slow path known to exceed `max_latency`.
+    ///
+    /// The slow paths are the mapping of memory from the OS, and the draining of the deallocations queued by the
+    /// threads of other sockets. The latency of a mapping is the worst observed so far, and is assumed to exceed any
+    /// deadline until the first mapping. Whenever it exceeds `max_latency`, the allocation is served from the memory
+    /// already acquired by the socket of the current thread, or fails with `AllocationError::DeadlineExceeded`.
+    ///
+    /// The initialization of a thread is itself a slow path, hence the allocation fails unless the current thread was
+    /// initialized beforehand, for example by `warm_up`. The system allocator is never delegated to, even with the
+    /// `system-fallback` feature.
+    pub fn allocate_with_deadline(&self, layout: Layout, max_latency: Duration)
+        -> Result<NonNull<u8>, AllocationError>
+    {
+        debug_assert!(layout.align().count_ones() == 1);
+
+        if layout.size() > self.maximum_size {
+            return Err(AllocationError::ExceedsMaximumSize);
+        }
+
+        //  The initialization of a thread is a slow path, of unknown latency, whichever the deadline.
+        if Thread::get().is_none() {
+            return Err(AllocationError::DeadlineExceeded);
+        }
+
+        let bounded = DOMAIN.platform().mapping_latency().is_none_or(|latency| latency > max_latency);
+
+        let armed = ARMED.is_armed();
+
+        let pointer = self.allocate_impl(layout, bounded, true, armed)?;
+
+        self.interleave_allocation(pointer, layout);
+        Self::record_allocation(pointer, layout, armed);
+
+        Ok(pointer)
+    }
+
+    /// Returns the NUMA node the current thread runs on, after clustering the nearby nodes, as served by its socket.
+    ///
+    /// A consumer thread may thus publish its node, for the threads producing the buffers it consumes to allocate them
+    /// on it, with `allocate_on_node`. As the thread may migrate to another node at the scheduler's whim, the node is
+    /// a hint, best taken by a thread pinned to the CPUs of a single node.
+    ///
+    /// The node is cached by the thread, and only looked up anew every 256 lookups, sparing a system call on each: a
+    /// thread rescheduled onto another node may thus report its former node for a while.
+    pub fn current_node(&self) -> NumaNodeIndex {
+        Thread::get().map_or_else(|| DOMAIN.platform().current_node(), |thread| thread.current_node())
+    }
+
+    /// Returns the NUMA node of the heap owning the allocation located at `pointer`, or None if unknown.
+    ///
+    /// The Normal and Large allocations are owned by the socket of a node, after clustering the nearby nodes, as for
+    /// `node_stats`; the Huge allocations are owned by no socket, hence their node is that backing their first page,
+    /// as for `resident_node_of`. The allocations of a frame region, and those delegated to the system allocator, are
+    /// of no known node.
+    ///
+    /// The sockets are scanned for the owner of the allocation, hence the lookup is meant for diagnostics, such as
+    /// checking the placement of `allocate_on_node`, not for use on the critical path.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` has been returned by a prior call to `allocate`, and not deallocated since.
+    #[cold]
+    pub unsafe fn node_of(&self, pointer: NonNull<u8>) -> Option<NumaNodeIndex> {
+        if FRAMES.contains(pointer.as_ptr() as usize) {
+            return None;
+        }
+
+        #[cfg(feature = "system-fallback")]
+        if !DOMAIN.platform().owns(pointer) {
+            return None;
+        }
+
+        if Properties::<LLConfiguration>::category_of_pointer(pointer) == Category::Huge {
+            return self.resident_node_of(pointer);
+        }
+
+        let mut owner = None;
+
+        //  Safety:
+        //  -   `pointer` is assumed to be a live allocation, not of a frame region, nor of the system allocator.
+        SOCKETS.for_each_socket_handle(|node, socket| {
+            if owner.is_none() && socket.owns(pointer) {
+                owner = Some(node);
+            }
+        });
+
+        owner
+    }
+
+    /// Returns the NUMA node backing the page located at `pointer`, as reported by the OS, or None if unknown.
+    ///
+    /// Unlike `node_of`, this is the node the memory actually resides on, which may differ from that of its heap once
+    /// the pages preferred on a node spill onto another, or if the mappings are left unbound, see
+    /// `MapOptions::with_binding`; it is thus meant to cross-check `node_of` in debugging sessions. The node is
+    /// looked up with `move_pages`, moving nothing, on Linux; it is unknown for pages not yet faulted in, and on the
+    /// other platforms.
+    #[cold]
+    pub fn resident_node_of(&self, pointer: NonNull<u8>) -> Option<NumaNodeIndex> {
+        DOMAIN.platform().node_of_page(pointer)
+    }
+
+    /// Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, from the heap of NUMA `node`,
+    /// rather than that of the node the current thread runs on, regardless of which it is, see `current_node`.
+    ///
+    /// The nodes are clustered as for the threads, hence `node` may share the heap of a nearby node. Unless this heap
+    /// is that of the current node, the allocation borrows a thread cache from the heap of `node` for its duration,
+    /// and is therefore much slower than `allocate`: it is meant for long-lived placements, such as those of `NodeBox`
+    /// and `NodeVec`. The allocation is never served from the frame region, nor delegated to the system allocator,
+    /// which cannot honour the placement.
+    ///
+    /// Returns `AllocationError::UnknownNode` if `node` is not a NUMA node of the machine, or not among the first 64.
+    pub fn allocate_on_node(&self, node: NumaNodeIndex, layout: Layout) -> Result<NonNull<u8>, AllocationError> {
+        debug_assert!(layout.align().count_ones() == 1);
+
+        if layout.size() > self.maximum_size {
+            return Err(AllocationError::ExceedsMaximumSize);
+        }
+
+        //  The clustering only ever selects a lower node, hence the selected node is within the storage as well.
+        let node = Some(node)
+            .filter(|node| (node.value() as usize) < SOCKETS.0.len())
+            .and_then(|node| DOMAIN.platform().numa_node(node.value()))
+            .ok_or(AllocationError::UnknownNode)?;
+
+        let current = Thread::get().map_or_else(Sockets::current_node, |thread| thread.current_node().value() as usize);
+
+        let armed = ARMED.is_armed();
+
+        let pointer = if node.value() as usize == current {
+            self.allocate_impl(layout, false, false, armed)?
+        } else {
+            let thread_local = Thread::get().or_else(Thread::initialize).ok_or(AllocationError::OutOfMemory)?;
+
+            if thread_local.is_allocation_forbidden() {
+                return Err(AllocationError::Forbidden);
+            }
+
+            let allocate = |layout| self.allocate_on_socket(node, layout);
+
+            let pointer = if armed { Self::allocate_guarded(layout, allocate)? } else { allocate(layout)? };
+
+            FOREIGN_ALLOCATIONS.record(node.value() as usize);
+
+            pointer
+        };
+
+        Self::record_allocation(pointer, layout, armed);
+
+        Ok(pointer)
+    }
+
+    /// Reallocates the memory located at `pointer`, allocated with `layout`, to `new_size` bytes, preserving its
+    /// content up to the lesser of both sizes.
+    ///
+    /// Returns the pointer to the reallocated memory, or None if the reallocation fails, in which case the memory at
+    /// `pointer` is left untouched.
+    ///
+    /// Huge allocations, which are directly mapped, are resized by remapping their pages, either in place or onto a
+    /// new range of addresses, without copying their content; other allocations are copied.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` has been returned by a prior call to `allocate`, with `layout`.
+    /// -   Assumes `pointer` has not been deallocated since its allocation.
+    /// -   Assumes `new_size`, rounded up to a multiple of `layout.align()`, does not overflow.
+    /// -   Assumes the memory pointed by `pointer` is no longer in use, on success, unless returned.
+    pub unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        let new_layout = Layout::from_size_align(new_size, layout.align()).ok()?;
+
+        if new_size > self.maximum_size {
+            return None;
+        }
+
+        if let Ok(result) = self.remap_impl(pointer, layout.size(), new_layout) {
+            return Some(result);
+        }
+
+        let result = self.allocate(new_layout)?;
+
+        //  Safety:
+        //  -   Both `pointer` and `result` are valid for the lesser of both sizes.
+        //  -   `result` is freshly allocated, hence does not overlap `pointer`.
+        ptr::copy_nonoverlapping(pointer.as_ptr(), result.as_ptr(), layout.size().min(new_size));
+
+        if TAGS.is_armed() {
+            TAGS.relocate(pointer, result, new_size);
+        }
+
+        self.dealloc(pointer.as_ptr(), layout);
+
+        Some(result)
+    }
+
+    /// Remaps the memory located at `pointer`, of `old_len` bytes, to `new_len` bytes, without copying its content.
+    ///
+    /// Only directly mapped allocations may be remapped, that is Huge allocations and Large allocations above the
+    /// direct threshold, and only to a length which is itself directly mapped. Their pages are moved, in place if the
+    /// adjacent address space is free, or onto a new range of addresses otherwise, preserving the content up to the
+    /// lesser of both lengths; the content beyond `old_len` is unspecified.
+    ///
+    /// #   Pointer Invalidation
+    ///
+    /// On success, if the returned pointer differs from `pointer`, all pointers into the memory are dangling, and must
+    /// be rebased onto the returned pointer; otherwise, only the pointers beyond `new_len` are dangling.
+    ///
+    /// On failure, the memory is left untouched, and `pointer` remains valid for `old_len` bytes.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` has been returned by a prior call to `allocate`, for `old_len` bytes.
+    /// -   Assumes `pointer` has not been deallocated since its allocation.
+    /// -   Assumes the memory pointed by `pointer` is not accessed concurrently.
+    pub unsafe fn remap(&self, pointer: NonNull<u8>, old_len: usize, new_len: usize)
+        -> Result<NonNull<u8>, AllocationError>
+    {
+        if new_len > self.maximum_size {
+            return Err(AllocationError::ExceedsMaximumSize);
+        }
+
+        let layout = Layout::from_size_align(new_len, 1).map_err(|_| AllocationError::ExceedsMaximumSize)?;
+
+        self.remap_impl(pointer, old_len, layout)
+    }
+
+    /// Deallocates the memory located at `pointer`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` has been returned by a prior call to `allocate`.
+    /// -   Assumes `pointer` has not been deallocated since its allocation.
+    /// -   Assumes the memory pointed by `pointer` is no longer in use.
+    pub unsafe fn deallocate(&self, pointer: NonNull<u8>) {
+        let armed = ARMED.is_armed();
+
+        //  The memory of a frame region is freed wholesale, at the end of the frame.
+        if armed && Self::forget_deallocation(pointer) {
+            return;
+        }
+
+        //  The memory not owned by llmalloc was delegated to the system allocator.
+        #[cfg(feature = "system-fallback")]
+        if !DOMAIN.platform().owns(pointer) {
+            return DOMAIN.platform().system_deallocate(pointer);
+        }
+
+        let pointer = if armed && HARDENING.is_enabled(DOMAIN.platform()) {
+            match Self::harden_deallocation(pointer) {
+                Some(pointer) => pointer,
+                None => return,
+            }
+        } else {
+            pointer
+        };
+
+        if let Some(thread_local) = Thread::get().or_else(Thread::initialize) {
+            let category = Properties::<LLConfiguration>::category_of_pointer(pointer);
+
+            thread_local.deallocate(pointer);
+
+            if armed && WATERMARKS.is_armed() {
+                thread_local.tick_watermarks(category != Category::Normal);
+            }
+
+            //  The passes of decay are run by the background thread, if running.
+            if category == Category::Huge && !BACKGROUND.is_running() && DECAY.is_due(DOMAIN.platform()) {
+                DOMAIN.decay();
+            }
+
+            return;
+        }
+
+        //  If a non-null pointer exists, it _must_ have been allocated, and therefore there should be at least one
+        //  non-null socket-handle, somewhere, through which the memory can be returned.
+        //
+        //  Otherwise, the pointer was not allocated by llmalloc, and is leaked rather than corrupting the heap.
+        if let Some(socket) = Sockets::any_socket_handle() {
+            DOMAIN.platform().fallbacks().record(Fallback::UncachedDeallocation);
+            socket.deallocate_uncached(pointer);
+        }
+    }
+}
+
+impl LLAllocator {
+    //  Protects the flip-protected `region`, leaving a dual-mapped region untouched.
+    //
+    //  Safety:
+    //  -   Assumes `region` is not accessed in a manner conflicting with the new protection.
+    unsafe fn protect_code(region: &CodeRegion, executable: bool) -> Result<(), ()> {
+        if region.mapping() == CodeMapping::Dual {
+            return Ok(());
+        }
+
+        if DOMAIN.platform().protect_code(region, executable) { Ok(()) } else { Err(()) }
+    }
+
+    //  Protects the pages of the Large or Huge allocation located at `pointer`, of `size` bytes.
+    //
+    //  Safety:
+    //  -   Assumes `pointer` is a live allocation, of `size` bytes.
+    #[cold]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> Result<(), ()> {
+        //  The allocations of a frame region may happen to be aligned as Large allocations.
+        if Properties::<LLConfiguration>::category_of_pointer(pointer) == Category::Normal ||
+            FRAMES.contains(pointer.as_ptr() as usize)
+        {
+            return Err(());
+        }
+
+        //  The memory not owned by llmalloc was delegated to the system allocator.
+        #[cfg(feature = "system-fallback")]
+        if !DOMAIN.platform().owns(pointer) {
+            return Err(());
+        }
+
+        //  Large and Huge allocations span whole Large Pages, shared with no other allocation.
+        let size = LLConfiguration::LARGE_PAGE_SIZE.round_up(size);
+
+        if DOMAIN.platform().protect(pointer, size, writable) { Ok(()) } else { Err(()) }
+    }
+
+    //  Returns whether the memory located at `pointer` may be relocated by a compaction pass.
+    fn is_relocatable(pointer: NonNull<u8>) -> bool {
+        //  The memory not owned by llmalloc was delegated to the system allocator.
+        #[cfg(feature = "system-fallback")]
+        if !DOMAIN.platform().owns(pointer) {
+            return false;
+        }
+
+        !FRAMES.contains(pointer.as_ptr() as usize)
+    }
+
+    //  Allocates `layout` outside of the sparse pages of `plan`, pushing the allocations landing in them onto `held`.
+    //
+    //  Safety:
+    //  -   Assumes `held` is null, or points to a list of allocations held aside.
+    unsafe fn relocation_target(&self, plan: &CompactionPlan<'_>, layout: Layout, held: &mut *mut u8)
+        -> Option<NonNull<u8>>
+    {
+        for _ in 0..plan.attempts(layout) {
+            let candidate = self.allocate_persistent(layout)?;
+
+            if !plan.is_sparse(candidate) {
+                return Some(candidate);
+            }
+
+            //  Safety:
+            //  -   `candidate` is valid for writes of at least a pointer, the minimum allocation size.
+            candidate.cast::<*mut u8>().as_ptr().write(*held);
+            *held = candidate.as_ptr();
+        }
+
+        None
+    }
+
+    //  Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, from the socket of `node`,
+    //  through a thread handle borrowed for the duration of the allocation.
+    #[cold]
+    #[inline(never)]
+    fn allocate_on_socket(&self, node: NumaNodeIndex, layout: Layout) -> Result<NonNull<u8>, AllocationError> {
+        let layout = Self::round_layout(layout)?;
+
+        let socket = Sockets::node_socket_handle(node).ok_or(AllocationError::OutOfMemory)?;
+        let thread = socket.acquire_thread_handle().ok_or(AllocationError::OutOfMemory)?;
+
+        let direct = layout.size() > self.direct_threshold && Self::is_large(layout);
+
+        //  Safety:
+        //  -   `layout` is valid.
+        //  -   `thread` belongs to `socket`.
+        //  -   `thread` is exclusively accessed from this thread.
+        let result = unsafe {
+            if direct { socket.allocate_direct(&thread, layout) } else { socket.allocate(&thread, layout) }
+        };
+
+        //  Safety:
+        //  -   `thread` was acquired from `socket`, just above, and was not shared.
+        unsafe { socket.release_thread_handle(thread) };
+
+        result.ok_or(AllocationError::OutOfMemory)
+    }
+
+    //  Allocates `layout` from the sockets of the other nodes, nearest to the node of the socket of `thread` first, as
+    //  per the distances reported by the platform.
+    //
+    //  Only the sockets already created are tried, whose `HugePage`s may have room to spare: the nodes at an unknown
+    //  distance last, and the lowest node first among those at the same distance.
+    #[cold]
+    #[inline(never)]
+    fn allocate_on_nearest(&self, thread: &Thread, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let own: SocketHandle = unsafe { thread.0.socket() };
+
+        let mut home = None;
+        let mut candidates = [(u8::MAX, NumaNodeIndex::new(0)); 64];
+        let mut count = 0;
+
+        SOCKETS.for_each_socket_handle(|node, socket| {
+            if socket == own {
+                home = Some(node);
+            } else if let Some(candidate) = candidates.get_mut(count) {
+                *candidate = (u8::MAX, node);
+                count += 1;
+            }
+        });
+
+        let home = home?;
+        let candidates = &mut candidates[..count];
+
+        for candidate in candidates.iter_mut() {
+            candidate.0 = DOMAIN.platform().distance(home, candidate.1).unwrap_or(u8::MAX);
+        }
+
+        candidates.sort_unstable();
+
+        let (node, pointer) = candidates.iter()
+            .find_map(|(_, node)| self.allocate_on_socket(*node, layout).ok().map(|pointer| (*node, pointer)))?;
+
+        DOMAIN.platform().fallbacks().record(Fallback::RemoteNodeAllocation);
+        FOREIGN_ALLOCATIONS.record(node.value() as usize);
+
+        Some(pointer)
+    }
+
+    //  Interleaves the pages of the freshly allocated `pointer`, of `layout`, across all NUMA nodes, if the instance
+    //  interleaves and the allocation is directly mapped.
+    #[inline(always)]
+    fn interleave_allocation(&self, pointer: NonNull<u8>, layout: Layout) {
+        if self.interleaved {
+            self.interleave_direct(pointer, layout);
+        }
+    }
+
+    //  Interleaves the pages of the freshly allocated `pointer`, of `layout`, across all NUMA nodes, if directly
+    //  mapped.
+    #[cold]
+    #[inline(never)]
+    fn interleave_direct(&self, pointer: NonNull<u8>, layout: Layout) {
+        //  The memory not owned by llmalloc was delegated to the system allocator.
+        #[cfg(feature = "system-fallback")]
+        if !DOMAIN.platform().owns(pointer) {
+            return;
+        }
+
+        if FRAMES.contains(pointer.as_ptr() as usize)
+            || Properties::<LLConfiguration>::category_of_pointer(pointer) != Category::Huge
+        {
+            return;
+        }
+
+        //  A directly mapped allocation spans whole `HugePage`s.
+        let size = LLConfiguration::HUGE_PAGE_SIZE.round_up(layout.size());
+
+        DOMAIN.platform().interleave(pointer, size);
+    }
+
+    //  Checks the canary of the block located at `pointer`, if any, then poisons it, and quarantines it if a Normal
+    //  allocation.
+    //
+    //  Returns the block to deallocate in its stead, if any: none if a violation is detected, the block being leaked,
+    //  nor while the quarantine is filling up.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes `pointer` is a live allocation, not of a frame region, nor of the system allocator.
+    #[cold]
+    #[inline(never)]
+    unsafe fn harden_deallocation(pointer: NonNull<u8>) -> Option<NonNull<u8>> {
+        //  If no socket exists, the pointer was not allocated by llmalloc.
+        let socket = match Sockets::any_socket_handle() {
+            Some(socket) => socket,
+            None => return Some(pointer),
+        };
+
+        let size = socket.size_of(pointer);
+        let normal = Properties::<LLConfiguration>::category_of_pointer(pointer) == Category::Normal;
+
+        if normal && HARDENING.has_canaries(DOMAIN.platform()) && !Hardening::check_canary(pointer, size) {
+            HARDENING.record_violation();
+            return None;
+        }
+
+        Hardening::poison(pointer, size, DEALLOCATED_POISON);
+
+        if !normal {
+            return Some(pointer);
+        }
+
+        let evicted = QUARANTINE.push(pointer)?;
+        let size = socket.size_of(evicted);
+
+        if !Hardening::is_poisoned(evicted, size, DEALLOCATED_POISON) {
+            HARDENING.record_violation();
+            return None;
+        }
+
+        Some(evicted)
+    }
+
+    //  Erases the epoch and the tag of the memory located at `pointer`, about to be deallocated, if any, returning
+    //  whether it belongs to a frame region, hence is freed wholesale instead.
+    #[cold]
+    #[inline(never)]
+    fn forget_deallocation(pointer: NonNull<u8>) -> bool {
+        if EPOCHS.is_tracking() {
+            EPOCHS.erase(pointer.as_ptr() as usize);
+        }
+
+        if TAGS.is_armed() {
+            TAGS.release(pointer);
+        }
+
+        FRAMES.contains(pointer.as_ptr() as usize)
+    }
+
+    //  Poisons the freshly allocated `pointer`, of `layout`, if hardened, and stamps its epoch, if tracking, unless no
+    //  feature is `armed`.
+    #[inline(always)]
+    fn record_allocation(pointer: NonNull<u8>, layout: Layout, armed: bool) {
+        if armed {
+            Self::record_armed_allocation(pointer, layout);
+        }
+    }
+
+    //  Poisons the freshly allocated `pointer`, of `layout`, if hardened, and stamps its epoch, if tracking.
+    #[cold]
+    #[inline(never)]
+    fn record_armed_allocation(pointer: NonNull<u8>, layout: Layout) {
+        if HARDENING.is_enabled(DOMAIN.platform()) {
+            //  Safety:
+            //  -   `pointer` is valid for writes of `layout.size()` bytes, as it was just allocated.
+            unsafe { Hardening::poison(pointer, layout.size(), ALLOCATED_POISON) };
+        }
+
+        if EPOCHS.is_tracking() && !FRAMES.contains(pointer.as_ptr() as usize) {
+            EPOCHS.stamp(pointer.as_ptr() as usize);
+        }
+    }
+
+    //  Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, from the frame region of the
+    //  thread if `frame` and the thread is in frame mode, or from the heap otherwise.
+    #[inline(always)]
+    fn try_allocate_impl(&self, layout: Layout, frame: bool) -> Result<NonNull<u8>, AllocationError> {
+        debug_assert!(layout.align().count_ones() == 1);
+
+        if layout.size() > self.maximum_size {
+            return Err(AllocationError::ExceedsMaximumSize);
+        }
+
+        let armed = ARMED.is_armed();
+
+        let result = self.allocate_impl(layout, false, frame, armed);
+
+        //  Forbidden allocations are not to be served by any allocator.
+        #[cfg(feature = "system-fallback")]
+        let result = result.or_else(|error| {
+            if error == AllocationError::Forbidden {
+                return Err(error);
+            }
+
+            DOMAIN.platform().fallbacks().record(Fallback::SystemAllocation);
+            DOMAIN.platform().system_allocate(layout).ok_or(error)
+        });
+
+        let pointer = result?;
+
+        self.interleave_allocation(pointer, layout);
+        Self::record_allocation(pointer, layout, armed);
+
+        Ok(pointer)
+    }
+
+    //  Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, from llmalloc itself.
+    //
+    //  If `bounded`, neither initializes the thread nor enters the slow paths of the socket, failing with
+    //  `DeadlineExceeded` instead. If `frame`, allocates from the frame region of the thread first, if any. Unless
+    //  `armed`, see `armed`, none of the features hooking into allocations is checked.
+    #[inline(always)]
+    fn allocate_impl(&self, layout: Layout, bounded: bool, frame: bool, armed: bool)
+        -> Result<NonNull<u8>, AllocationError>
+    {
+        if !armed {
+            return self.allocate_block(layout, bounded, frame, false);
+        }
+
+        Self::allocate_guarded(layout, |layout| self.allocate_block(layout, bounded, frame, true))
+    }
+
+    //  Allocates `layout` with `allocate`, padded with a canary if a Normal allocation, and hardened with canaries.
+    #[cold]
+    #[inline(never)]
+    fn allocate_guarded<F>(layout: Layout, allocate: F) -> Result<NonNull<u8>, AllocationError>
+        where
+            F: FnOnce(Layout) -> Result<NonNull<u8>, AllocationError>,
+    {
+        if !HARDENING.has_canaries(DOMAIN.platform())
+            || Properties::<LLConfiguration>::category_of_size(layout.size()) != Category::Normal
+        {
+            return allocate(layout);
+        }
+
+        let size = layout.size() + Hardening::CANARY_SIZE;
+        let padded = Layout::from_size_align(size, layout.align()).map_err(|_| AllocationError::ExceedsMaximumSize)?;
+
+        let pointer = allocate(padded)?;
+
+        //  The padding may tip the allocation over into a Large allocation, whose size is not padded, and the
+        //  allocations of the frame regions are freed wholesale, hence neither carries a canary.
+        if Properties::<LLConfiguration>::category_of_pointer(pointer) == Category::Normal
+            && !FRAMES.contains(pointer.as_ptr() as usize)
+        {
+            if let Some(socket) = Sockets::any_socket_handle() {
+                //  Safety:
+                //  -   `pointer` was just allocated, hence is valid for its usable size, at least `size` bytes.
+                unsafe { Hardening::write_canary(pointer, socket.size_of(pointer)) };
+            }
+        }
+
+        Ok(pointer)
+    }
+
+    //  Allocates `layout`, as per `allocate_impl`, without canary.
+    #[inline(always)]
+    fn allocate_block(&self, layout: Layout, bounded: bool, frame: bool, armed: bool)
+        -> Result<NonNull<u8>, AllocationError>
+    {
+        let layout = Self::round_layout(layout)?;
+
+        let error = if bounded { AllocationError::DeadlineExceeded } else { AllocationError::OutOfMemory };
+
+        let thread_local = if bounded { Thread::get() } else { Thread::get().or_else(Thread::initialize) };
+
+        let thread_local = match thread_local {
+            Some(thread_local) => thread_local,
+            //  The unregistered threads are served from a shared state, once the registration of threads is required.
+            None if !bounded && REGISTRATION.is_required() => return self.allocate_unregistered(layout),
+            None => return Err(error),
+        };
+
+        if thread_local.is_allocation_forbidden() {
+            return Err(AllocationError::Forbidden);
+        }
+
+        //  A thread in frame mode registers its region, arming the frame regions.
+        if frame && armed {
+            if let Some(pointer) = thread_local.frame().and_then(|frame| frame.allocate(layout)) {
+                return Ok(pointer);
+            }
+        }
+
+        let direct = layout.size() > self.direct_threshold && Self::is_large(layout);
+
+        let mut result = thread_local.allocate_as(layout, direct, bounded);
+
+        //  The retries back off, hence are only performed by unbounded allocations.
+        if result.is_none() && !bounded {
+            result = self.allocate_exhausted(&thread_local, layout, direct);
+        }
+
+        //  The callbacks of the watermarks are of unknown latency, hence only invoked from unbounded allocations.
+        //
+        //  The thread-local instance may be replaced, hence is no longer used past this point.
+        if result.is_some() && !bounded && armed {
+            thread_local.tick_allocation(layout);
+        }
+
+        result.ok_or(error)
+    }
+
+    //  Allocates `layout`, already rounded, once the socket of `thread_local` is exhausted: from the sockets of the
+    //  other nodes, nearest first, then anew as per the retry policy.
+    #[cold]
+    #[inline(never)]
+    fn allocate_exhausted(&self, thread_local: &Thread, layout: Layout, direct: bool) -> Option<NonNull<u8>> {
+        if let Some(pointer) = self.allocate_on_nearest(thread_local, layout) {
+            return Some(pointer);
+        }
+
+        //  The deallocations pending on the inbound queues may free up whole pages, which the purge then releases.
+        let purge = || {
+            SOCKETS.for_each_socket_handle(|_, socket| {
+                socket.drain_inbound();
+            });
+
+            DOMAIN.purge();
+        };
+
+        RETRY.retry(DOMAIN.platform(), purge, || thread_local.allocate_as(layout, direct, false))
+    }
+
+    //  Allocates `layout`, already rounded, from the thread-local instance shared by the unregistered threads.
+    #[cold]
+    #[inline(never)]
+    fn allocate_unregistered(&self, layout: Layout) -> Result<NonNull<u8>, AllocationError> {
+        let direct = layout.size() > self.direct_threshold && Self::is_large(layout);
+
+        let result = REGISTRATION.with_shared(Thread::create_shared, |pointer| {
+            //  Safety:
+            //  -   `pointer` was obtained from `into_pointer`, by `create_shared`, and is accessed under the lock.
+            let thread = Thread(unsafe { ThreadHandle::from_pointer(pointer) });
+
+            if direct { thread.allocate_direct(layout) } else { thread.allocate(layout) }
+        });
+
+        result.flatten().ok_or(AllocationError::OutOfMemory)
+    }
+
+    //  Reallocates the memory located at `pointer`, of `old_size` bytes, to `layout`, by remapping its pages, if both
+    //  the current and the new allocations are directly mapped.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes `pointer` has been returned by a prior call to `allocate`, and not deallocated since.
+    unsafe fn remap_impl(&self, pointer: NonNull<u8>, old_size: usize, layout: Layout)
+        -> Result<NonNull<u8>, AllocationError>
+    {
+        //  The memory not owned by llmalloc was delegated to the system allocator.
+        #[cfg(feature = "system-fallback")]
+        if !DOMAIN.platform().owns(pointer) {
+            return Err(AllocationError::NotRemappable);
+        }
+
+        if FRAMES.contains(pointer.as_ptr() as usize) {
+            return Err(AllocationError::NotRemappable);
+        }
+
+        let is_direct = match Properties::<LLConfiguration>::category_of_size(layout.size()) {
+            Category::Normal => false,
+            Category::Large => layout.size() > self.direct_threshold,
+            Category::Huge => true,
+        };
+
+        if !is_direct || Properties::<LLConfiguration>::category_of_pointer(pointer) != Category::Huge {
+            return Err(AllocationError::NotRemappable);
+        }
+
+        if layout.align() > LLConfiguration::HUGE_PAGE_SIZE.value() {
+            return Err(AllocationError::UnsupportedAlignment);
+        }
+
+        let thread_local = Thread::get().or_else(Thread::initialize).ok_or(AllocationError::OutOfMemory)?;
+
+        if thread_local.is_allocation_forbidden() {
+            return Err(AllocationError::Forbidden);
+        }
+
+        let result = thread_local.reallocate_huge(pointer, layout.pad_to_align()).ok_or(AllocationError::OutOfMemory)?;
+
+        if layout.size() > old_size && HARDENING.is_enabled(DOMAIN.platform()) {
+            //  Safety:
+            //  -   `result` is valid for writes of `layout.size()` bytes, as it was just reallocated.
+            let grown = NonNull::new_unchecked(result.as_ptr().add(old_size));
+
+            Hardening::poison(grown, layout.size() - old_size, ALLOCATED_POISON);
+        }
+
+        if result != pointer && EPOCHS.is_tracking() {
+            EPOCHS.erase(pointer.as_ptr() as usize);
+            EPOCHS.stamp(result.as_ptr() as usize);
+        }
+
+        if TAGS.is_armed() {
+            TAGS.relocate(pointer, result, layout.size());
+        }
+
+        Ok(result)
+    }
+
+    //  Rounds up the size of `layout` to a multiple of its alignment, if not already.
+    fn round_layout(layout: Layout) -> Result<Layout, AllocationError> {
+        if layout.align() > LLConfiguration::HUGE_PAGE_SIZE.value() {
+            return Err(AllocationError::UnsupportedAlignment);
+        }
+
+        //  Safety:
+        //  -   `layout.align()` is a power of 2.
+        let align = unsafe { PowerOf2::new_unchecked(layout.align()) };
+
+        if layout.size() % align == 0 {
+            return Ok(layout);
+        }
+
+        let size = align.round_up(layout.size());
+
+        //  Safety:
+        //  -   `align` is not 0.
+        //  -   `align` is a power of 2.
+        //  -   `size` is rounded up to a multiple of `align`, without overflow.
+        Ok(unsafe { Layout::from_size_align_unchecked(size, align.value()) })
+    }
+
+    //  Returns whether `layout` is a Large allocation.
+    fn is_large(layout: Layout) -> bool {
+        Properties::<LLConfiguration>::category_of_size(layout.size()) == Category::Large
+    }
+
+    //  Fills the cache of `thread_local` with a `LargePage` for each Normal class size, returning the number of class
+    //  sizes for which it holds one.
+    fn prefill(thread_local: &Thread) -> usize {
+        let mut prefilled = 0;
+
+        for layout in Self::normal_classes() {
+            if let Some(pointer) = thread_local.allocate(layout) {
+                //  Safety:
+                //  -   `pointer` was allocated by `thread_local`, just above, and is not in use.
+                unsafe { thread_local.deallocate(pointer) };
+
+                prefilled += 1;
+            }
+        }
+
+        prefilled
+    }
+
+    //  Returns the layouts of the Normal class sizes, in increasing order.
+    fn normal_classes() -> impl Iterator<Item = Layout> {
+        let threshold = Properties::<LLConfiguration>::normal_threshold().value();
+
+        (0..ClassSize::number_classes(LLConfiguration::LARGE_PAGE_SIZE))
+            .map(|index| ClassSize::new(index).layout())
+            .take_while(move |layout| layout.size() <= threshold)
+    }
+}
+
+impl Default for LLAllocator {
+    fn default() -> Self { Self::new() }
+}
+
+unsafe impl GlobalAlloc for LLAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocate(layout).map(|ptr| ptr.as_ptr()).unwrap_or(ptr::null_mut())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        debug_assert!(layout.align().count_ones() == 1);
+
+        if let Some(ptr) = NonNull::new(ptr) {
+            self.deallocate(ptr);
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let result = match NonNull::new(ptr) {
+            Some(pointer) => self.reallocate(pointer, layout, new_size),
+            None => self.allocate(Layout::from_size_align_unchecked(new_size, layout.align())),
+        };
 
-        //  If a non-null pointer exists, it _must_ have been allocated, and therefore there should be at least one
-        //  non-null socket-handle, somewhere, through which the memory can be returned.
-        Sockets::any_socket_handle().deallocate_uncached(pointer);
+        result.map(|ptr| ptr.as_ptr()).unwrap_or(ptr::null_mut())
     }
 }
 
-unsafe impl GlobalAlloc for LLAllocator {
-    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        self.allocate(layout).map(|ptr| ptr.as_ptr()).unwrap_or(ptr::null_mut())
-    }
+/// Guard of a scope forbidding allocations on the current thread, see `LLAllocator::forbid_allocation`.
+///
+/// The guard is bound to the thread which entered the scope, and cannot be sent to another.
+#[must_use = "allocations are permitted again as soon as the guard is dropped"]
+pub struct ForbidAllocationGuard {
+    _thread: PhantomData<*const ()>,
+}
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _: Layout) {
-        if let Some(ptr) = NonNull::new(ptr) {
-            self.deallocate(ptr);
+impl Drop for ForbidAllocationGuard {
+    fn drop(&mut self) {
+        if let Some(thread) = Thread::get() {
+            thread.0.set_forbidden_scopes(thread.0.forbidden_scopes().saturating_sub(1));
         }
     }
 }
 
+/// Flushes the cache of the current thread, see `LLAllocator::flush_thread_cache`.
+#[cold]
+pub fn flush_thread_cache() { LLAllocator::new().flush_thread_cache() }
+
+/// Trims the cache of the current thread, if idle, see `LLAllocator::trim_idle_thread_cache`.
+#[cold]
+pub fn trim_idle_thread_cache() -> bool { LLAllocator::new().trim_idle_thread_cache() }
+
+/// Guard flushing the cache of the current thread once dropped, see `LLAllocator::flush_on_drop`.
+///
+/// The guard is bound to the thread which created it, and cannot be sent to another.
+#[must_use = "the cache is flushed as soon as the guard is dropped"]
+pub struct FlushGuard {
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) { LLAllocator::new().flush_thread_cache() }
+}
+
+/// Guard of a pinned reclamation epoch, see `LLAllocator::pin`.
+#[must_use = "the epoch is unpinned as soon as the guard is dropped"]
+pub struct ReclamationGuard {
+    slot: usize,
+}
+
+impl Drop for ReclamationGuard {
+    fn drop(&mut self) { RECLAMATION.unpin(self.slot); }
+}
+
 //
 //  Integration test backdoors.
 //
@@ -146,19 +2639,99 @@ static DOMAIN: DomainHandle = DomainHandle::new(LLPlatform::new());
 //  Storage for up to 64 NUMA nodes; it should be vastly overkill.
 static SOCKETS: Sockets = Sockets::new();
 
+//  Baseline of the statistics, as of the last reset.
+static RESET_EPOCH: StatisticsEpoch = StatisticsEpoch::new();
+
+//  Baseline of the statistics, as of the last interval.
+static INTERVAL_EPOCH: StatisticsEpoch = StatisticsEpoch::new();
+
+//  Selection of the hardened mode.
+static HARDENING: Hardening = Hardening::new();
+
+//  Quarantine of the deallocated Normal allocations, in hardened mode.
+static QUARANTINE: Quarantine = Quarantine::new();
+
+//  The tags, and the tagged blocks.
+static TAGS: Tags = Tags::new();
+
+//  Selection of the retry policy.
+static RETRY: Retry = Retry::new();
+
+//  Metrics of the initialization.
+static INIT_METRICS: AtomicInitMetrics = AtomicInitMetrics::new();
+
+//  The watermarks.
+static WATERMARKS: Watermarks = Watermarks::new();
+
+//  The epochs of the allocations.
+static EPOCHS: EpochTracker = EpochTracker::new();
+
+//  The frame regions.
+static FRAMES: FrameRegions = FrameRegions::new();
+
+//  The deferred reclamation.
+static RECLAMATION: Reclamation = Reclamation::new();
+
 //  Thread-local.
 //
 //  Safety:
-//  -   `drop_handle` points to an `unsafe extern "C" fn(*mut u8)`.
+//  -   `drop_handle` points to an `unsafe extern "system" fn(*mut u8)`, the calling convention of the destructors of
+//      thread-local storage on all platforms.
 static THREAD_LOCAL: LLThreadLocal<u8> = unsafe { LLThreadLocal::new(drop_handle as *const u8) };
 
+//  Handler invoked prior to forking.
 #[cold]
-unsafe extern "C" fn drop_handle(handle: *mut u8) {
-    let handle = NonNull::new(handle).expect("Non-null handle");
+extern "C" fn prepare_fork() { FORK.settle(); }
 
-    let thread = ThreadHandle::from_pointer(handle);
-    let socket: SocketHandle = thread.socket();
-    socket.release_thread_handle(thread);
+//  Handler invoked in the child of a fork.
+#[cold]
+extern "C" fn recover_fork() {
+    FORK.recover();
+
+    //  The child is scheduled independently of its parent, hence the node of the forking thread is looked up anew.
+    if let Some(thread) = Thread::get() {
+        thread.0.set_node_cache(NodeCache::default().into_raw());
+    }
+}
+
+//  Entry of the background thread.
+#[cold]
+fn background_thread() {
+    let allocator = LLAllocator::new();
+
+    BACKGROUND.run(DOMAIN.platform(), || {
+        SOCKETS.for_each_socket_handle(|_, socket| {
+            socket.drain_inbound();
+        });
+
+        if DECAY.is_due(DOMAIN.platform()) {
+            DOMAIN.decay();
+        }
+
+        if TOPOLOGY_REFRESH.is_due(DOMAIN.platform()) {
+            DOMAIN.platform().refresh_topology();
+        }
+
+        allocator.reclaim();
+    });
+}
+
+#[cold]
+unsafe extern "system" fn drop_handle(handle: *mut u8) {
+    let handle = match NonNull::new(handle) {
+        Some(handle) => handle,
+        None => return,
+    };
+
+    //  A thread exiting in frame mode no longer uses its frame allocations.
+    let thread = Thread(ThreadHandle::from_pointer(handle));
+    thread.release_frame();
+
+    //  A thread exiting as a Background thread no longer arms, see `armed`.
+    thread.set_criticality(Criticality::Normal);
+
+    let socket: SocketHandle = thread.0.socket();
+    socket.release_thread_handle(thread.0);
 }
 
 struct Thread(ThreadHandle);
@@ -171,21 +2744,287 @@ impl Thread {
             .map(|pointer| unsafe { Self(ThreadHandle::from_pointer(pointer)) })
     }
 
+    //  Initializes the thread-local instance implicitly, unless the registration of threads is required.
+    #[cold]
+    #[inline(never)]
+    fn initialize() -> Option<Thread> {
+        if REGISTRATION.is_required() {
+            return None;
+        }
+
+        Self::register()
+    }
+
     //  Initializes the thread-local instance and attempts to return a reference to it.
     //
     //  Initialization may fail for any reason, in which case None is returned.
     #[cold]
     #[inline(never)]
-    fn initialize() -> Option<Thread> {
+    fn register() -> Option<Thread> {
+        Self::prepare();
+
         //  Get the handles, can't do anything without both!
-        let socket = Sockets::socket_handle()?;
+        let node = DOMAIN.platform().current_node();
+        let socket = Sockets::node_socket_handle(node)?;
+
+        //  The warm-up of the thread excludes the preparation of the key and the creation of the socket.
+        let start = DOMAIN.platform().now();
+
         let thread = socket.acquire_thread_handle()?;
 
-        THREAD_LOCAL.set(thread.into_pointer());
+        thread.set_node_cache(NodeCache::fresh(node).into_raw());
+
+        let pointer = thread.into_pointer();
+
+        if !THREAD_LOCAL.set(pointer) {
+            //  Safety:
+            //  -   `pointer` was obtained from `into_pointer`, just above, and was not shared.
+            unsafe { socket.release_thread_handle(ThreadHandle::from_pointer(pointer)) };
+            return None;
+        }
+
+        INIT_METRICS.record_thread_warm_up(DOMAIN.platform().now().saturating_sub(start));
+
+        //  The background thread, if enabled by the environment, is spawned on the first use of the allocator.
+        BACKGROUND.start(DOMAIN.platform(), background_thread);
 
         Self::get()
     }
 
+    //  Creates the thread-local instance shared by the unregistered threads, see `registration`.
+    #[cold]
+    fn create_shared() -> Option<NonNull<u8>> {
+        let socket = Sockets::node_socket_handle(DOMAIN.platform().current_node())?;
+
+        socket.acquire_thread_handle().map(ThreadHandle::into_pointer)
+    }
+
+    //  Prepares the thread-local key, if not already prepared.
+    #[cold]
+    fn prepare() {
+        let start = DOMAIN.platform().now();
+
+        if THREAD_LOCAL.prepare() {
+            INIT_METRICS.record_key_creation(DOMAIN.platform().now().saturating_sub(start));
+
+            //  The key is only created once per process, and so are the handlers registered.
+            FORK.register(DOMAIN.platform(), prepare_fork, recover_fork);
+        }
+    }
+
+    //  Returns the NUMA node the thread runs on, as cached, looking it up anew every `locality::REFRESH_PERIOD`
+    //  lookups.
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex {
+        match NodeCache::from_raw(self.0.node_cache()).lookup() {
+            Some((node, cache)) => {
+                self.0.set_node_cache(cache.into_raw());
+                node
+            },
+            None => self.refresh_node(),
+        }
+    }
+
+    //  Looks up the NUMA node the thread runs on anew, caching it.
+    #[cold]
+    #[inline(never)]
+    fn refresh_node(&self) -> NumaNodeIndex {
+        let node = DOMAIN.platform().current_node();
+
+        self.0.set_node_cache(NodeCache::fresh(node).into_raw());
+
+        node
+    }
+
+    //  Returns whether allocations are forbidden, within the current scope.
+    #[inline(always)]
+    fn is_allocation_forbidden(&self) -> bool { self.0.forbidden_scopes() != 0 }
+
+    //  Returns the frame region of the thread, if in frame mode.
+    #[inline(always)]
+    fn frame(&self) -> Option<Frame> {
+        //  Safety:
+        //  -   The frame region of the thread was initialized by `enter_frame_mode`.
+        self.0.frame().map(|region| unsafe { Frame::from_region(region) })
+    }
+
+    //  Leaves frame mode, if in frame mode, returning the region to the heap.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes the frame allocations are no longer in use.
+    #[cold]
+    unsafe fn release_frame(&self) {
+        let frame = match self.frame() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        self.0.set_frame(None);
+        FRAMES.unregister(frame.slot());
+
+        self.deallocate(frame.region());
+    }
+
+    //  Declares the criticality of the thread, the Background threads arming, see `armed`.
+    #[cold]
+    fn set_criticality(&self, criticality: Criticality) {
+        let former = self.0.criticality();
+
+        self.0.set_criticality(criticality);
+
+        match (former == Criticality::Background, criticality == Criticality::Background) {
+            (false, true) => ARMED.arm(),
+            (true, false) => ARMED.disarm(),
+            _ => (),
+        }
+    }
+
+    //  Evaluates the watermarks, flushes the cache of a Background thread, and rehomes the thread, as enabled, past an
+    //  unbounded allocation of `layout`.
+    //
+    //  The thread-local instance may be replaced, hence `self` is consumed.
+    #[cold]
+    #[inline(never)]
+    fn tick_allocation(self, layout: Layout) {
+        if WATERMARKS.is_armed() {
+            let category = Properties::<LLConfiguration>::category_of_size(layout.size());
+            self.tick_watermarks(category != Category::Normal);
+        }
+
+        //  The cache of Background threads is kept minimal.
+        if self.0.criticality() == Criticality::Background {
+            self.tick_background();
+        }
+
+        if REHOMING.is_enabled(DOMAIN.platform()) {
+            self.tick_rehoming();
+        }
+    }
+
+    //  Evaluates the watermarks if `always`, or on every `Watermarks::PERIOD`-th operation of the thread.
+    #[cold]
+    #[inline(never)]
+    fn tick_watermarks(&self, always: bool) {
+        let statistics = self.0.statistics().total();
+        let operations = statistics.allocations.wrapping_add(statistics.deallocations);
+
+        if always || operations.is_multiple_of(Watermarks::PERIOD) {
+            WATERMARKS.evaluate(Sockets::statistics().total().live_bytes());
+        }
+    }
+
+    //  Flushes the cache of the thread on every `profile::FLUSH_PERIOD`-th allocation of the thread.
+    #[cold]
+    #[inline(never)]
+    fn tick_background(&self) {
+        if self.0.statistics().total().allocations.is_multiple_of(crate::profile::FLUSH_PERIOD) {
+            self.flush();
+        }
+    }
+
+    //  Rehomes the thread on every `rehoming::PERIOD`-th allocation of the thread.
+    #[cold]
+    #[inline(never)]
+    fn tick_rehoming(self) {
+        if self.0.statistics().total().allocations.is_multiple_of(crate::rehoming::PERIOD) {
+            self.rehome();
+        }
+    }
+
+    //  Rehomes the thread onto the socket of the node it runs on, if served by another socket, returning whether it
+    //  was rehomed.
+    //
+    //  The thread-local instance is replaced by one acquired from the socket of the node, the current one being
+    //  released to its socket, hence `self` is consumed.
+    #[cold]
+    #[inline(never)]
+    fn rehome(self) -> bool {
+        //  The thread-local storage of the embedder is not to be set anew.
+        if cfg!(feature = "custom-platform") || self.is_allocation_forbidden() || self.0.frame().is_some() {
+            return false;
+        }
+
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let former: SocketHandle = unsafe { self.0.socket() };
+
+        //  The node is looked up anew, the thread having possibly been rescheduled since cached.
+        let node = self.refresh_node();
+
+        let socket = match Sockets::node_socket_handle(node) {
+            Some(socket) if socket != former => socket,
+            _ => return false,
+        };
+
+        let thread = match socket.acquire_thread_handle() {
+            Some(thread) => thread,
+            None => return false,
+        };
+
+        //  The criticality is handed over, hence neither arms nor disarms.
+        thread.set_criticality(self.0.criticality());
+        thread.set_node_cache(self.0.node_cache());
+
+        let pointer = thread.into_pointer();
+
+        if !THREAD_LOCAL.set(pointer) {
+            //  Safety:
+            //  -   `pointer` was obtained from `into_pointer`, just above, and was not shared.
+            unsafe { socket.release_thread_handle(ThreadHandle::from_pointer(pointer)) };
+            return false;
+        }
+
+        //  Safety:
+        //  -   `self.0` was acquired from `former`, and is no longer the thread-local instance.
+        unsafe { former.release_thread_handle(self.0) };
+
+        true
+    }
+
+    //  Flushes the cache of the thread, returning its memory to its socket.
+    #[cold]
+    fn flush(&self) {
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let socket: SocketHandle = unsafe { self.0.socket() };
+
+        //  Safety:
+        //  -   The handle came from `socket`, and is only accessed by the current thread.
+        unsafe { socket.flush_thread_handle(&self.0) };
+    }
+
+    //  Flushes the cache of the thread if it has been idle for `threshold` nanoseconds, as tracked by `IdleCache`,
+    //  returning whether it was flushed.
+    #[cold]
+    fn trim_if_idle(&self, threshold: u64) -> bool {
+        let statistics = self.0.statistics().total();
+        let operations = statistics.allocations.wrapping_add(statistics.deallocations) as u64;
+
+        let cache = IdleCache::from_raw(self.0.idle_cache());
+        let (idle, cache) = cache.check(operations, DOMAIN.platform().now(), threshold);
+
+        self.0.set_idle_cache(cache.into_raw());
+
+        if idle {
+            self.flush();
+        }
+
+        idle
+    }
+
+    //  Allocates `layout`, as a directly mapped allocation if `direct`, and from the memory already acquired by the
+    //  socket if `bounded`.
+    #[inline(always)]
+    fn allocate_as(&self, layout: Layout, direct: bool, bounded: bool) -> Option<NonNull<u8>> {
+        match (direct, bounded) {
+            (false, false) => self.allocate(layout),
+            (true, false) => self.allocate_direct(layout),
+            (false, true) => self.allocate_bounded(layout),
+            (true, true) => self.allocate_direct_bounded(layout),
+        }
+    }
+
     //  Allocates `size` bytes of memory, aligned on at least an `alignment` boundary.
     //
     //  If allocation fails, the returned pointer may be NULL.
@@ -202,6 +3041,89 @@ impl Thread {
         unsafe { socket.allocate(&self.0, layout) }
     }
 
+    //  Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, as a directly mapped allocation.
+    #[cold]
+    #[inline(never)]
+    fn allocate_direct(&self, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let socket: SocketHandle = unsafe { self.0.socket() };
+
+        //  Safety:
+        //  -   `layout` is valid.
+        //  -   `self.0` belongs `socket`.
+        //  -   `self.0` is exclusively accessed from this thread.
+        unsafe { socket.allocate_direct(&self.0, layout) }
+    }
+
+    //  Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, from the memory already acquired
+    //  by the socket.
+    #[inline(never)]
+    fn allocate_bounded(&self, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let socket: SocketHandle = unsafe { self.0.socket() };
+
+        //  Safety:
+        //  -   `layout` is valid.
+        //  -   `self.0` belongs `socket`.
+        //  -   `self.0` is exclusively accessed from this thread.
+        unsafe { socket.allocate_bounded(&self.0, layout) }
+    }
+
+    //  Allocates `size` bytes of memory, aligned on at least an `alignment` boundary, as a directly mapped allocation,
+    //  from the Huge allocations retained by the socket.
+    #[cold]
+    #[inline(never)]
+    fn allocate_direct_bounded(&self, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let socket: SocketHandle = unsafe { self.0.socket() };
+
+        //  Safety:
+        //  -   `layout` is valid.
+        //  -   `self.0` belongs `socket`.
+        //  -   `self.0` is exclusively accessed from this thread.
+        unsafe { socket.allocate_direct_bounded(&self.0, layout) }
+    }
+
+    //  Attempts to ensure that the next `count` allocations of `layout` take the fast path.
+    //
+    //  `layout` is assumed to be valid.
+    #[cold]
+    #[inline(never)]
+    fn reserve_ahead(&self, layout: Layout, count: usize) -> usize {
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let socket: SocketHandle = unsafe { self.0.socket() };
+
+        //  Safety:
+        //  -   `layout` is valid.
+        //  -   `self.0` belongs `socket`.
+        //  -   `self.0` is exclusively accessed from this thread.
+        unsafe { socket.reserve_ahead(&self.0, layout, count) }
+    }
+
+    //  Reallocates the Huge allocation located at `pointer` to `layout`, without copying its content.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes `pointer` is a Huge allocation, returned by a prior call to `allocate`, and not deallocated since.
+    #[cold]
+    #[inline(never)]
+    unsafe fn reallocate_huge(&self, pointer: NonNull<u8>, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   Only uses SocketHandle type.
+        let socket: SocketHandle = self.0.socket();
+
+        //  Safety:
+        //  -   `layout` is valid.
+        //  -   `self.0` belongs `socket`.
+        //  -   `self.0` is exclusively accessed from this thread.
+        //  -   `pointer` is assumed to be a Huge allocation.
+        socket.reallocate_huge(&self.0, pointer, layout)
+    }
+
     //  Deallocates the memory located at `pointer`.
     //
     //  #   Safety
@@ -243,30 +3165,37 @@ impl Sockets {
     //  Returns a SocketHandle for this particular NUMA Node.
     #[cold]
     #[inline(never)]
-    fn socket_handle() -> Option<SocketHandle> { SOCKETS.socket_handle_impl() }
+    fn socket_handle() -> Option<SocketHandle> { SOCKETS.socket_handle_impl(Self::current_node()) }
 
-    //  Returns the first SocketHandle it finds.
-    //
-    //  #   Panics
-    //
-    //  If no handle has been allocated.
+    //  Returns a SocketHandle for `node`, as returned by `Platform::numa_node`.
+    #[cold]
+    #[inline(never)]
+    fn node_socket_handle(node: NumaNodeIndex) -> Option<SocketHandle> {
+        SOCKETS.socket_handle_impl(node.value() as usize)
+    }
+
+    //  Returns the first SocketHandle it finds, if any handle has been allocated.
     #[cold]
     #[inline(never)]
-    fn any_socket_handle() -> SocketHandle { SOCKETS.any_socket_handle_impl() }
+    fn any_socket_handle() -> Option<SocketHandle> { SOCKETS.any_socket_handle_impl() }
 
-    //  Internal; returns a SocketHandle, initialized if need be.
+    //  Internal; returns the SocketHandle of `node`, initialized if need be.
     #[cold]
-    fn socket_handle_impl(&self) -> Option<SocketHandle> {
-        let index = Self::current_node();
-        let atomic_handle = &self.0[index];
+    fn socket_handle_impl(&self, node: usize) -> Option<SocketHandle> {
+        //  Nodes beyond the storage cannot be served.
+        let atomic_handle = self.0.get(node)?;
 
         if let Some(socket_handle) = atomic_handle.load() {
             return Some(socket_handle);
         }
 
+        let start = DOMAIN.platform().now();
+
         //  There may not be enough memory to allocate a new handle.
         let socket_handle = SocketHandle::new(&DOMAIN)?;
 
+        INIT_METRICS.record_mapping(DOMAIN.platform().now().saturating_sub(start));
+
         //  Let's race to see who gets to initialize the handle.
         //
         //  If this thread loses, free the superfluous handle.
@@ -280,16 +3209,33 @@ impl Sockets {
         atomic_handle.load()
     }
 
-    //  Internal; returns the first SocketHandle it finds, or panics if it finds none.
+    //  Internal; returns the first SocketHandle it finds, if any.
+    #[cold]
+    fn any_socket_handle_impl(&self) -> Option<SocketHandle> {
+        self.0.iter().find_map(|atomic_handle| atomic_handle.load())
+    }
+
+    //  Invokes `f` with each SocketHandle allocated, and the NUMA node it is associated to.
     #[cold]
-    fn any_socket_handle_impl(&self) -> SocketHandle {
-        for atomic_handle in &self.0[..] {
+    fn for_each_socket_handle<F>(&self, mut f: F)
+        where
+            F: FnMut(NumaNodeIndex, SocketHandle),
+    {
+        for (index, atomic_handle) in self.0.iter().enumerate() {
             if let Some(socket_handle) = atomic_handle.load() {
-                return socket_handle;
+                f(NumaNodeIndex::new(index as u32), socket_handle);
             }
         }
+    }
+
+    //  Returns the statistics of all SocketHandle allocated.
+    #[cold]
+    fn statistics() -> Statistics {
+        let mut statistics = Statistics::default();
+
+        SOCKETS.for_each_socket_handle(|_, socket| statistics.merge(&socket.statistics()));
 
-        unreachable!("How can memory need be deallocated, if no socket handle was ever allocated?");
+        statistics
     }
 
     #[cold]
@@ -0,0 +1,109 @@
+//! Arenas
+//!
+//! Lookup tables, and other immutable structures built at start-up, are made of many small objects, whose Normal
+//! allocations share their pages with other allocations, hence cannot be sealed. An arena carves such objects out of a
+//! single Large or Huge allocation instead, by bumping a cursor, so that all are sealed read-only at once, see
+//! `LLAllocator::seal_arena`, once the structure is complete.
+//!
+//! The objects of an arena are not deallocated individually, but all at once, along with the arena, see
+//! `LLAllocator::deallocate_arena`.
+
+use core::{alloc::Layout, ptr::NonNull};
+
+/// An arena of objects, carved out of a single Large or Huge allocation, allocated by `LLAllocator::allocate_arena`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Arena {
+    base: NonNull<u8>,
+    size: usize,
+    used: usize,
+    sealed: bool,
+}
+
+//  Safety:
+//  -   The arena only exposes the addresses of its objects, the accesses are up to the caller.
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    /// Creates an instance, unsealed and empty, spanning the `size` bytes located at `base`.
+    pub(crate) fn new(base: NonNull<u8>, size: usize) -> Self { Self { base, size, used: 0, sealed: false } }
+
+    /// Returns the address of the arena, that is of the allocation its objects are carved out of.
+    pub fn base(&self) -> NonNull<u8> { self.base }
+
+    /// Returns the size of the arena, in bytes.
+    pub fn size(&self) -> usize { self.size }
+
+    /// Returns the number of bytes carved out of the arena, padding included.
+    pub fn used(&self) -> usize { self.used }
+
+    /// Returns whether the arena is sealed, see `LLAllocator::seal_arena`.
+    pub fn is_sealed(&self) -> bool { self.sealed }
+
+    /// Carves an object of `layout` out of the arena.
+    ///
+    /// Returns None if the arena is sealed, or lacks the space for the object.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if self.sealed {
+            return None;
+        }
+
+        let base = self.base.as_ptr() as usize;
+
+        let start = (base + self.used).checked_add(layout.align() - 1)? & !(layout.align() - 1);
+        let end = start.checked_add(layout.size())?;
+
+        if end > base + self.size {
+            return None;
+        }
+
+        self.used = end - base;
+
+        NonNull::new(start as *mut u8)
+    }
+
+    /// Marks the arena as sealed, or not.
+    pub(crate) fn set_sealed(&mut self, sealed: bool) { self.sealed = sealed; }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+fn arena(size: usize) -> Arena { Arena::new(NonNull::new(0x10_0000 as *mut u8).unwrap(), size) }
+
+#[test]
+fn arena_allocate() {
+    let mut arena = arena(64);
+
+    let first = arena.allocate(Layout::from_size_align(3, 1).unwrap()).unwrap();
+    assert_eq!(0x10_0000, first.as_ptr() as usize);
+
+    //  Aligned, past the padding.
+    let second = arena.allocate(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!(0x10_0008, second.as_ptr() as usize);
+    assert_eq!(16, arena.used());
+
+    //  Exhausted.
+    assert_eq!(None, arena.allocate(Layout::from_size_align(49, 1).unwrap()));
+
+    let last = arena.allocate(Layout::from_size_align(48, 1).unwrap()).unwrap();
+    assert_eq!(0x10_0010, last.as_ptr() as usize);
+    assert_eq!(64, arena.used());
+}
+
+#[test]
+fn arena_sealed() {
+    let mut arena = arena(64);
+    assert!(!arena.is_sealed());
+
+    arena.set_sealed(true);
+    assert!(arena.is_sealed());
+    assert_eq!(None, arena.allocate(Layout::from_size_align(8, 8).unwrap()));
+
+    arena.set_sealed(false);
+    assert!(arena.allocate(Layout::from_size_align(8, 8).unwrap()).is_some());
+}
+
+} // mod tests
@@ -0,0 +1,61 @@
+//! Armed features
+//!
+//! The opt-in features hooking into each allocation and deallocation, such as the hardened mode, the watermarks, the
+//! tags, the epochs, the frame regions, rehoming and the Background threads, are each disabled by default. Rather than
+//! checking each of them in turn, on each operation, the allocator checks a single process-wide count of the armed
+//! features, and only checks each of them, on a cold path, while any is armed.
+//!
+//! Each feature arms the count when enabled, and disarms it when disabled, so that arming and disarming concurrently
+//! commute. The hardened mode and rehoming are armed until resolved from the environment, on the first operation.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Process-wide count of the armed features.
+pub(crate) struct Armed(AtomicUsize);
+
+impl Armed {
+    /// Creates an instance, with `armed` features armed.
+    pub(crate) const fn new(armed: usize) -> Self { Self(AtomicUsize::new(armed)) }
+
+    /// Returns whether any feature is armed.
+    #[inline(always)]
+    pub(crate) fn is_armed(&self) -> bool { self.0.load(Ordering::Relaxed) != 0 }
+
+    /// Arms a feature.
+    #[cold]
+    pub(crate) fn arm(&self) { self.0.fetch_add(1, Ordering::Relaxed); }
+
+    /// Disarms a feature, armed beforehand.
+    #[cold]
+    pub(crate) fn disarm(&self) {
+        let previous = self.0.fetch_sub(1, Ordering::Relaxed);
+
+        debug_assert!(previous != 0);
+    }
+}
+
+/// Count of the armed features, the hardened mode and rehoming being armed until resolved.
+pub(crate) static ARMED: Armed = Armed::new(2);
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn armed_count() {
+    let armed = Armed::new(1);
+    assert!(armed.is_armed());
+
+    armed.arm();
+    armed.disarm();
+    assert!(armed.is_armed());
+
+    armed.disarm();
+    assert!(!armed.is_armed());
+
+    armed.arm();
+    assert!(armed.is_armed());
+}
+
+} // mod tests
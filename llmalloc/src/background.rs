@@ -0,0 +1,191 @@
+//! Background Thread
+//!
+//! The maintenance of the allocator is otherwise performed by the allocating and deallocating threads, on their
+//! critical path. With the background thread, it is instead performed by a dedicated thread, woken up every period to:
+//!
+//! -   Return the allocations deallocated by the threads of other sockets, pending on the inbound queue of each
+//!     socket, to their `LargePage`, ahead of any allocation running out of pages.
+//! -   Run the passes of decay, when due, see `LLAllocator::set_decay`, in lieu of the threads deallocating Huge
+//!     allocations, which no longer run them.
+//! -   Re-scan the NUMA topology, when due, see `LLAllocator::set_topology_refresh`.
+//! -   Free the retired memory which no alive guard may still access, see `LLAllocator::reclaim`.
+//!
+//! The caches of the threads are accessed without synchronization, hence are only ever flushed by their own thread, on
+//! exit, or through `LLAllocator::release_thread` where the platform does not know of the exit of threads.
+//!
+//! The period is resolved from the `LLMALLOC_BACKGROUND_THREAD` environment variable, enabled with a period of 1 second
+//! if set to any value other than an empty string or `0`, unless set explicitly beforehand by
+//! `LLAllocator::set_background_thread`. Enabled by the environment, the thread is spawned on the first use of the
+//! allocator, that is on the initialization of the first thread.
+//!
+//! The background thread is spawned by the platforms of Linux, and by the POSIX platform, and by custom platforms
+//! implementing `Platform::spawn_thread`.
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{Platform, LLPlatform};
+
+/// Name of the environment variable selecting the background thread, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_BACKGROUND_THREAD\0";
+
+/// Period of the background thread selected by the environment variable.
+pub(crate) const DEFAULT_PERIOD: Duration = Duration::from_secs(1);
+
+/// Process-wide state of the background thread.
+pub(crate) struct Background {
+    //  Period, in nanoseconds, or DISABLED, or UNRESOLVED.
+    period: AtomicU64,
+    //  Whether the thread is running, or being spawned.
+    running: AtomicBool,
+    //  Number of passes run.
+    passes: AtomicU64,
+}
+
+impl Background {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self {
+        Self { period: AtomicU64::new(UNRESOLVED), running: AtomicBool::new(false), passes: AtomicU64::new(0) }
+    }
+
+    /// Returns the period of the background thread, if enabled, resolving it from `platform` if not yet resolved.
+    pub(crate) fn period(&self, platform: &LLPlatform) -> Option<Duration> {
+        match self.period_nanos(platform) {
+            DISABLED => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Returns whether the background thread is running.
+    #[inline(always)]
+    pub(crate) fn is_running(&self) -> bool { self.running.load(Ordering::Relaxed) }
+
+    /// Returns the number of passes run by the background thread, since the start of the process.
+    pub(crate) fn passes(&self) -> u64 { self.passes.load(Ordering::Relaxed) }
+
+    /// Enables the background thread with the given period, or disables it, overriding the environment.
+    ///
+    /// If enabled, and not yet running, the thread is spawned to run `entry`, which is to call `run`. Returns Err if
+    /// the thread cannot be spawned, in which case the background thread is disabled.
+    ///
+    /// Once disabled, the thread exits on its next wake-up.
+    pub(crate) fn set(&self, platform: &LLPlatform, period: Option<Duration>, entry: fn()) -> Result<(), ()> {
+        self.period.store(Self::encode(period), Ordering::SeqCst);
+
+        if period.is_none() || self.spawn(platform, entry) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    /// Spawns the background thread, running `entry`, if enabled by the environment and not yet running.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn start(&self, platform: &LLPlatform, entry: fn()) {
+        if self.is_running() || self.period_nanos(platform) == DISABLED {
+            return;
+        }
+
+        self.spawn(platform, entry);
+    }
+
+    /// Runs `pass` every period, until the background thread is disabled.
+    ///
+    /// Only to be called by the background thread, from the entry with which it was spawned.
+    pub(crate) fn run<F>(&self, platform: &LLPlatform, mut pass: F)
+        where
+            F: FnMut(),
+    {
+        loop {
+            while let Some(period) = self.period(platform) {
+                platform.sleep(period);
+
+                //  Disabled while asleep.
+                if self.period_nanos(platform) == DISABLED {
+                    break;
+                }
+
+                pass();
+
+                self.passes.fetch_add(1, Ordering::Relaxed);
+            }
+
+            self.running.store(false, Ordering::SeqCst);
+
+            //  Enabled anew while exiting, the thread carries on, unless another was spawned in the meantime.
+            if self.period_nanos(platform) == DISABLED
+                || self.running.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Records the background thread as no longer running, its period being kept.
+    ///
+    /// To be called in the child of a fork, which does not inherit the background thread; the maintenance is then
+    /// performed by the allocating and deallocating threads, unless the thread is spawned anew by `set`.
+    #[cold]
+    pub(crate) fn forget(&self) { self.running.store(false, Ordering::SeqCst); }
+
+    //  Spawns the thread, unless already running, returning whether it is running.
+    //
+    //  On failure to spawn, the background thread is disabled, lest each further thread attempt to spawn it anew.
+    #[cold]
+    fn spawn(&self, platform: &LLPlatform, entry: fn()) -> bool {
+        if self.running.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+            return true;
+        }
+
+        if platform.spawn_thread(entry) {
+            return true;
+        }
+
+        self.period.store(DISABLED, Ordering::SeqCst);
+        self.running.store(false, Ordering::SeqCst);
+
+        false
+    }
+
+    #[inline(always)]
+    fn period_nanos(&self, platform: &LLPlatform) -> u64 {
+        match self.period.load(Ordering::SeqCst) {
+            UNRESOLVED => self.resolve(platform),
+            nanos => nanos,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self, platform: &LLPlatform) -> u64 {
+        let enabled = platform.environment_flag(ENVIRONMENT_VARIABLE);
+        let resolved = Self::encode(if enabled { Some(DEFAULT_PERIOD) } else { None });
+
+        //  A period set by `LLAllocator::set_background_thread` while the environment is read is kept over the default.
+        match self.period.compare_exchange(UNRESOLVED, resolved, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => resolved,
+            Err(current) => current,
+        }
+    }
+
+    //  A period of 0 is encoded as the shortest period, 1 nanosecond, rather than as disabled.
+    fn encode(period: Option<Duration>) -> u64 {
+        match period {
+            Some(period) => (period.as_nanos().min(u128::from(UNRESOLVED - 1)) as u64).max(1),
+            None => DISABLED,
+        }
+    }
+}
+
+/// State of the background thread, shared by the allocator and its thread.
+pub(crate) static BACKGROUND: Background = Background::new();
+
+//
+//  Implementation Details
+//
+
+const DISABLED: u64 = 0;
+const UNRESOLVED: u64 = u64::MAX;
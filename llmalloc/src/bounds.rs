@@ -0,0 +1,293 @@
+//! Address Range
+//!
+//! Memory mapped from the OS lands wherever the kernel sees fit, hence a process packing metadata into the upper bits
+//! of its pointers, or relying on its heap lying within a range of its own, has no guarantee about where it lives.
+//! With an address range, the platform instead hints each mapping of the heap within the range, then verifies that the
+//! mapping landed within it, unmapping it and failing otherwise.
+//!
+//! The hints walk the range, each mapping being hinted just past the previous one, wrapping around to the start of the
+//! range once its end is reached, and skipping past a hint which was not honored, so that a range partly occupied by
+//! other mappings is skipped rather than tried anew. Each mapping which landed outside of the range is recorded as a
+//! `FallbackMetrics::out_of_range_mappings`, whereas the allocation it was made for fails, unless retried, see
+//! `LLAllocator::set_retry_policy`.
+//!
+//! As the guarantee covers all of the heap, the selection is latched by the first mapping, after which it can no
+//! longer change, see `LLAllocator::set_address_range`.
+//!
+//! An address range is honored by the platform of Linux, for the `HugePage`s, the Huge allocations, and the
+//! reservation, if any; the thread stacks, code regions, and physical buffers are not part of the heap, and may land
+//! anywhere.
+
+#![cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform", feature = "no-libc")))), allow(dead_code))]
+
+use core::{
+    hint,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use llmalloc_core::{Configuration, PowerOf2};
+
+use crate::LLConfiguration;
+
+/// Range of the address space, as passed to `LLAllocator::set_address_range`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AddressRange {
+    /// The start of the range, inclusive.
+    pub start: usize,
+    /// The end of the range, exclusive.
+    pub end: usize,
+}
+
+impl AddressRange {
+    /// Returns whether `pointer` lies within the range.
+    pub fn contains(&self, pointer: *const u8) -> bool { (self.start..self.end).contains(&(pointer as usize)) }
+
+    /// Returns whether the `size` bytes at `pointer` lie within the range.
+    pub fn contains_all(&self, pointer: *const u8, size: usize) -> bool {
+        self.start <= pointer as usize && (pointer as usize).checked_add(size).is_some_and(|end| end <= self.end)
+    }
+}
+
+/// Process-wide selection of an address range, and the cursor of its hints.
+pub(crate) struct Bounds {
+    //  One of the STATE_ values, with the LATCHED bit.
+    state: AtomicU8,
+    start: AtomicUsize,
+    end: AtomicUsize,
+    //  The next hint, if within the range.
+    cursor: AtomicUsize,
+}
+
+impl Bounds {
+    /// Creates an instance, without range.
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(STATE_NONE),
+            start: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the address range selected, if any.
+    pub(crate) fn range(&self) -> Option<AddressRange> { self.read(self.wait()) }
+
+    /// Selects the address range, or none.
+    ///
+    /// The range is shrunk to the whole Huge Pages it covers, short of the first, so that no hint is null.
+    ///
+    /// Returns Err if the range holds no Huge Page once shrunk, or if the selection differs from the one already
+    /// latched.
+    pub(crate) fn set(&self, range: Option<AddressRange>) -> Result<(), ()> {
+        let range = match range {
+            Some(range) => Some(Self::shrink(range).ok_or(())?),
+            None => None,
+        };
+
+        loop {
+            let current = self.wait();
+
+            if current & LATCHED != 0 {
+                return if self.read(current) == range { Ok(()) } else { Err(()) };
+            }
+
+            if self.state.compare_exchange(current, STATE_WRITING, Ordering::Acquire, Ordering::Relaxed).is_err() {
+                continue;
+            }
+
+            let (start, end) = range.map_or((0, 0), |range| (range.start, range.end));
+
+            self.start.store(start, Ordering::Relaxed);
+            self.end.store(end, Ordering::Relaxed);
+            self.cursor.store(start, Ordering::Relaxed);
+
+            let state = if range.is_some() { STATE_SOME } else { STATE_NONE };
+
+            self.state.store(state, Ordering::Release);
+
+            return Ok(());
+        }
+    }
+
+    /// Returns the address range, if any, latching the selection.
+    ///
+    /// To be called by the platform on each mapping of the heap.
+    #[inline(always)]
+    pub(crate) fn latch(&self) -> Option<AddressRange> {
+        match self.state.load(Ordering::Acquire) {
+            LATCHED_NONE => None,
+            LATCHED_SOME => self.read(LATCHED_SOME),
+            _ => self.latch_slow(),
+        }
+    }
+
+    /// Returns the hint of the next mapping of `size` bytes within `range`, as latched.
+    ///
+    /// The hint leaves a Huge Page of slack, as a mapping may be over-allocated so as to be aligned.
+    pub(crate) fn hint(&self, range: AddressRange, size: usize) -> usize {
+        let cursor = self.cursor.load(Ordering::Relaxed);
+
+        let fits = |hint: usize| {
+            hint >= range.start && hint.checked_add(size).and_then(|end| end.checked_add(HUGE_PAGE_SIZE.value()))
+                .is_some_and(|end| end <= range.end)
+        };
+
+        if fits(cursor) { cursor } else { range.start }
+    }
+
+    /// Advances the cursor past the `size` bytes at `address`.
+    ///
+    /// To be called by the platform once a mapping landed, whether within the range, or not, so that the hint of the
+    /// next mapping differs.
+    pub(crate) fn advance(&self, address: usize, size: usize) {
+        self.cursor.store(address.saturating_add(size), Ordering::Relaxed);
+    }
+
+    /// Waits until the range is no longer being selected by another thread.
+    ///
+    /// To be called prior to forking, for the child to inherit the selection fully written.
+    #[cold]
+    pub(crate) fn settle(&self) { self.wait(); }
+
+    /// Resets the selection being written, if any, to no range.
+    ///
+    /// To be called in the child of a fork, where the thread writing it no longer exists.
+    #[cold]
+    pub(crate) fn recover(&self) {
+        let _ = self.state.compare_exchange(STATE_WRITING, STATE_NONE, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn latch_slow(&self) -> Option<AddressRange> {
+        loop {
+            let current = self.wait();
+
+            //  An explicit selection, racing with the latch, is latched instead.
+            if self.state.compare_exchange(current, current | LATCHED, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                return self.read(current);
+            }
+        }
+    }
+
+    //  Returns the state, once no selection is being written.
+    fn wait(&self) -> u8 {
+        loop {
+            match self.state.load(Ordering::Acquire) {
+                STATE_WRITING => hint::spin_loop(),
+                current => return current,
+            }
+        }
+    }
+
+    //  Returns the range as per `state`, which is not STATE_WRITING.
+    fn read(&self, state: u8) -> Option<AddressRange> {
+        if state & !LATCHED == STATE_NONE {
+            return None;
+        }
+
+        Some(AddressRange { start: self.start.load(Ordering::Relaxed), end: self.end.load(Ordering::Relaxed) })
+    }
+
+    //  Shrinks `range` to the whole Huge Pages it covers, short of the first.
+    fn shrink(range: AddressRange) -> Option<AddressRange> {
+        let mask = HUGE_PAGE_SIZE.value() - 1;
+
+        let start = range.start.max(HUGE_PAGE_SIZE.value()).checked_add(mask)? & !mask;
+        let end = range.end & !mask;
+
+        if start < end { Some(AddressRange { start, end }) } else { None }
+    }
+}
+
+/// Selection of an address range, shared by the allocator and the platforms.
+pub(crate) static ADDRESS_BOUNDS: Bounds = Bounds::new();
+
+//
+//  Implementation Details
+//
+
+const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+const STATE_NONE: u8 = 0;
+const STATE_SOME: u8 = 1;
+const STATE_WRITING: u8 = 2;
+const LATCHED: u8 = 4;
+const LATCHED_NONE: u8 = STATE_NONE | LATCHED;
+const LATCHED_SOME: u8 = STATE_SOME | LATCHED;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+const PAGE: usize = HUGE_PAGE_SIZE.value();
+
+#[test]
+fn address_range_contains() {
+    let range = AddressRange { start: 0x1000, end: 0x3000 };
+
+    assert!(!range.contains(0xfff as *const u8));
+    assert!(range.contains(0x1000 as *const u8));
+    assert!(range.contains(0x2fff as *const u8));
+    assert!(!range.contains(0x3000 as *const u8));
+
+    assert!(range.contains_all(0x1000 as *const u8, 0x2000));
+    assert!(!range.contains_all(0x1000 as *const u8, 0x2001));
+    assert!(!range.contains_all(0x2000 as *const u8, usize::MAX));
+}
+
+#[test]
+fn bounds_set_latch() {
+    let bounds = Bounds::new();
+
+    assert_eq!(None, bounds.range());
+
+    //  Shrunk, short of the first Huge Page.
+    assert_eq!(Err(()), bounds.set(Some(AddressRange { start: 1, end: PAGE + 1 })));
+    assert_eq!(Err(()), bounds.set(Some(AddressRange { start: 3 * PAGE, end: 2 * PAGE })));
+
+    assert_eq!(Ok(()), bounds.set(Some(AddressRange { start: 1, end: 4 * PAGE + 1 })));
+    assert_eq!(Some(AddressRange { start: PAGE, end: 4 * PAGE }), bounds.range());
+
+    assert_eq!(Ok(()), bounds.set(None));
+    assert_eq!(None, bounds.range());
+
+    let range = AddressRange { start: 2 * PAGE, end: 8 * PAGE };
+
+    assert_eq!(Ok(()), bounds.set(Some(range)));
+    assert_eq!(Some(range), bounds.latch());
+
+    //  Latched.
+    assert_eq!(Ok(()), bounds.set(Some(range)));
+    assert_eq!(Err(()), bounds.set(None));
+    assert_eq!(Some(range), bounds.latch());
+    assert_eq!(Some(range), bounds.range());
+}
+
+#[test]
+fn bounds_hint() {
+    let bounds = Bounds::new();
+    let range = AddressRange { start: 2 * PAGE, end: 8 * PAGE };
+
+    assert_eq!(Ok(()), bounds.set(Some(range)));
+
+    assert_eq!(2 * PAGE, bounds.hint(range, 2 * PAGE));
+
+    bounds.advance(2 * PAGE, 2 * PAGE);
+    assert_eq!(4 * PAGE, bounds.hint(range, 2 * PAGE));
+
+    //  Wrapped around, short of the slack.
+    assert_eq!(2 * PAGE, bounds.hint(range, 4 * PAGE));
+
+    //  Wrapped around, past the end.
+    bounds.advance(7 * PAGE, 2 * PAGE);
+    assert_eq!(2 * PAGE, bounds.hint(range, PAGE));
+
+    //  Wrapped around, before the start, as a hint was not honored.
+    bounds.advance(0, PAGE);
+    assert_eq!(2 * PAGE, bounds.hint(range, PAGE));
+}
+
+} // mod tests
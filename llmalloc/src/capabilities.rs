@@ -0,0 +1,427 @@
+//! Capabilities
+//!
+//! Containers commonly lack some of the facilities llmalloc relies on, such as a pool of HugeTLB pages, access to
+//! `/sys`, or NUMA support. Rather than failing, llmalloc detects the capabilities of its environment, and selects the
+//! most capable configuration available:
+//!
+//! -   Huge Pages are backed by HugeTLB pages of their own size, or failing that by 2 MB HugeTLB pages, or failing that
+//!     by Transparent Huge Pages, or failing that by normal pages.
+//! -   Sockets are per NUMA node, or failing that a single socket is shared by all threads.
+//!
+//! On hosts where no pool of HugeTLB pages is provisioned, HugeTLB may be forgone altogether, in favor of Transparent
+//! Huge Pages, by setting the `LLMALLOC_TRANSPARENT_HUGE_PAGES` environment variable to any value other than an empty
+//! string or `0`, or by calling `LLAllocator::use_transparent_huge_pages`.
+//!
+//! The pools of HugeTLB pages are probed alongside, on the detection of the capabilities, so that a pool with no page
+//! left is passed over from the start, rather than discovered by a failed mapping; the pages found are reported by
+//! `LLAllocator::huge_tlb_pools`.
+//!
+//! The selected configuration is reported by `LLAllocator::capabilities`, whose downgrades are suitable for logging,
+//! and the host itself, in more details, by `LLAllocator::host_capabilities`, for deployment tooling to verify that an
+//! instance runs in the intended configuration.
+
+use core::fmt;
+
+/// Name of the environment variable forgoing HugeTLB in favor of Transparent Huge Pages, NUL-terminated.
+#[cfg_attr(not(any(feature = "no-libc", all(any(target_os = "linux", target_os = "android"), not(any(
+    feature = "posix", feature = "bare-metal", feature = "custom-platform", feature = "test-platform"))))),
+    allow(dead_code))]
+pub(crate) const TRANSPARENT_HUGE_PAGES_VARIABLE: &[u8] = b"LLMALLOC_TRANSPARENT_HUGE_PAGES\0";
+
+/// Capabilities of the environment, as detected by llmalloc.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// Whether Huge Pages can be backed by HugeTLB pages.
+    ///
+    /// HugeTLB is assumed available unless `/sys` reports an empty pool, or it is forgone, and is downgraded on the
+    /// first failure to map a Huge Page with it.
+    pub huge_tlb: bool,
+    /// Whether Huge Pages can be backed by 2 MB HugeTLB pages, should HugeTLB pages of their own size be unavailable.
+    ///
+    /// Detected and downgraded as `huge_tlb`; always false if the Huge Pages are 2 MB already, as with the
+    /// `small-heap` feature.
+    pub huge_tlb_2mb: bool,
+    /// Whether Transparent Huge Pages are enabled, either always or on request.
+    pub transparent_huge_pages: bool,
+    /// Whether the NUMA topology is available, both from the kernel and from `/sys`.
+    pub numa: bool,
+    /// Whether `/sys` is accessible.
+    pub sysfs: bool,
+}
+
+impl Capabilities {
+    /// Returns whether any capability is downgraded, as compared to the most capable configuration.
+    pub fn is_degraded(&self) -> bool { self.downgrades().next().is_some() }
+
+    /// Returns the downgrades of the configuration, as compared to the most capable configuration.
+    pub fn downgrades(&self) -> impl Iterator<Item = Downgrade> {
+        let pages = match (self.huge_tlb, self.huge_tlb_2mb, self.transparent_huge_pages) {
+            (true, _, _) => None,
+            (false, true, _) => Some(Downgrade::HugeTlb2MbPages),
+            (false, false, true) => Some(Downgrade::TransparentHugePages),
+            (false, false, false) => Some(Downgrade::NormalPages),
+        };
+
+        let nodes = if self.numa { None } else { Some(Downgrade::SingleNode) };
+        let sysfs = if self.sysfs { None } else { Some(Downgrade::NoSysfs) };
+
+        pages.into_iter().chain(nodes).chain(sysfs)
+    }
+}
+
+/// Lists the downgrades, one per line, or states that there is none.
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_degraded() {
+            return write!(f, "llmalloc: no capability downgraded");
+        }
+
+        for (index, downgrade) in self.downgrades().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "llmalloc: {}", downgrade)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A downgrade from the most capable configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Downgrade {
+    /// HugeTLB pages of the Huge Page size are not available, hence Huge Pages are backed by 2 MB HugeTLB pages.
+    HugeTlb2MbPages,
+    /// HugeTLB is not available, hence Huge Pages are backed by Transparent Huge Pages.
+    TransparentHugePages,
+    /// Neither HugeTLB nor Transparent Huge Pages are available, hence Huge Pages are backed by normal pages.
+    NormalPages,
+    /// The NUMA topology is not available, hence a single socket is shared by all threads.
+    SingleNode,
+    /// `/sys` is not accessible, hence the capabilities it reports are assumed absent, or detected by trial.
+    NoSysfs,
+}
+
+impl fmt::Display for Downgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Downgrade::HugeTlb2MbPages =>
+                "HugeTLB pages of the Huge Page size unavailable, Huge Pages backed by 2 MB HugeTLB pages",
+            Downgrade::TransparentHugePages => "HugeTLB unavailable, Huge Pages backed by Transparent Huge Pages",
+            Downgrade::NormalPages =>
+                "HugeTLB and Transparent Huge Pages unavailable, Huge Pages backed by normal pages",
+            Downgrade::SingleNode => "NUMA topology unavailable, single socket shared by all threads",
+            Downgrade::NoSysfs => "/sys inaccessible, capabilities it reports assumed absent or detected by trial",
+        };
+
+        f.write_str(message)
+    }
+}
+
+/// A failure to acquire the privilege backing Huge Pages by large pages, see
+/// `LLAllocator::acquire_large_page_privilege`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PrivilegeError {
+    /// The platform offers no large pages, or needs no privilege to allocate them.
+    Unsupported,
+    /// The account does not hold the `SeLockMemoryPrivilege`.
+    NotHeld,
+    /// The token of the process could not be opened for adjusting its privileges, with the given OS error code.
+    TokenUnavailable(u32),
+    /// The `SeLockMemoryPrivilege` could not be looked up, with the given OS error code.
+    LookupFailed(u32),
+    /// The `SeLockMemoryPrivilege` could not be enabled, with the given OS error code.
+    AdjustFailed(u32),
+}
+
+impl fmt::Display for PrivilegeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivilegeError::Unsupported => f.write_str("large pages unsupported, or requiring no privilege"),
+            PrivilegeError::NotHeld => f.write_str(
+                "SeLockMemoryPrivilege not held: grant \"Lock pages in memory\" to the account, in Local Security \
+                 Policy > User Rights Assignment, then log off and on again"),
+            PrivilegeError::TokenUnavailable(code) =>
+                write!(f, "process token not opened to adjust privileges (error {}), check its access rights", code),
+            PrivilegeError::LookupFailed(code) => write!(f, "SeLockMemoryPrivilege not looked up (error {})", code),
+            PrivilegeError::AdjustFailed(code) => write!(f, "SeLockMemoryPrivilege not enabled (error {})", code),
+        }
+    }
+}
+
+/// Capabilities of the host, in details, as detected by `LLAllocator::host_capabilities`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HostCapabilities {
+    /// The configuration selected by llmalloc, see `LLAllocator::capabilities`.
+    pub capabilities: Capabilities,
+    /// The mode of Transparent Huge Pages.
+    pub transparent_huge_pages: TransparentHugePagesMode,
+    /// The number of NUMA nodes, 1 if the NUMA topology is not available.
+    pub numa_nodes: u32,
+    /// The number of NUMA nodes of far memory, such as CXL.mem expanders, 0 if none, or if not classified.
+    pub far_numa_nodes: u32,
+    /// Whether the kernel supports restartable sequences, `rseq`.
+    pub rseq: bool,
+    /// The limit of locked memory of the process, in bytes, or None if unlimited.
+    pub mlock_limit: Option<u64>,
+    /// The size of an OS page, in bytes.
+    pub os_page_size: usize,
+    //  The HugeTLB page sizes found, in ascending order, up to `MAX_HUGE_PAGE_SIZES`.
+    huge_page_sizes: [usize; MAX_HUGE_PAGE_SIZES],
+    huge_page_size_count: usize,
+}
+
+impl HostCapabilities {
+    /// Returns the HugeTLB page sizes supported by the kernel, in bytes and ascending order, whether or not a pool of
+    /// such pages is reserved.
+    pub fn huge_page_sizes(&self) -> &[usize] { &self.huge_page_sizes[..self.huge_page_size_count] }
+
+    /// Records a HugeTLB page size, keeping the sizes in ascending order, and ignoring duplicates and overflows.
+    pub fn add_huge_page_size(&mut self, size: usize) {
+        let sizes = &mut self.huge_page_sizes[..self.huge_page_size_count];
+
+        let index = match sizes.binary_search(&size) {
+            Ok(_) => return,
+            Err(index) => index,
+        };
+
+        if self.huge_page_size_count == MAX_HUGE_PAGE_SIZES {
+            return;
+        }
+
+        self.huge_page_size_count += 1;
+        self.huge_page_sizes[index..self.huge_page_size_count].rotate_right(1);
+        self.huge_page_sizes[index] = size;
+    }
+}
+
+/// Lists the capabilities of the host, one per line, followed by the downgrades.
+impl fmt::Display for HostCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "llmalloc: huge page sizes:")?;
+
+        if self.huge_page_sizes().is_empty() {
+            write!(f, " none")?;
+        }
+
+        for (index, size) in self.huge_page_sizes().iter().enumerate() {
+            let separator = if index == 0 { " " } else { ", " };
+            write!(f, "{}{} kB", separator, size / 1024)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "llmalloc: transparent huge pages: {}", self.transparent_huge_pages)?;
+        writeln!(f, "llmalloc: NUMA nodes: {}, of which far memory: {}", self.numa_nodes, self.far_numa_nodes)?;
+        writeln!(f, "llmalloc: rseq: {}", if self.rseq { "available" } else { "unavailable" })?;
+
+        match self.mlock_limit {
+            Some(limit) => writeln!(f, "llmalloc: mlock limit: {} kB", limit / 1024)?,
+            None => writeln!(f, "llmalloc: mlock limit: unlimited")?,
+        }
+
+        writeln!(f, "llmalloc: OS page size: {} kB", self.os_page_size / 1024)?;
+
+        write!(f, "{}", self.capabilities)
+    }
+}
+
+/// Mode of Transparent Huge Pages, as configured system-wide.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TransparentHugePagesMode {
+    /// The mode could not be determined, for example as `/sys` is not accessible.
+    #[default]
+    Unknown,
+    /// Transparent Huge Pages back all eligible mappings.
+    Always,
+    /// Transparent Huge Pages only back the mappings advised with `MADV_HUGEPAGE`, as llmalloc does.
+    Madvise,
+    /// Transparent Huge Pages are disabled.
+    Never,
+}
+
+impl fmt::Display for TransparentHugePagesMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            TransparentHugePagesMode::Unknown => "unknown",
+            TransparentHugePagesMode::Always => "always",
+            TransparentHugePagesMode::Madvise => "madvise",
+            TransparentHugePagesMode::Never => "never",
+        };
+
+        f.write_str(mode)
+    }
+}
+
+/// A pool of HugeTLB pages, as probed from `/sys/kernel/mm/hugepages`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HugeTlbPool {
+    /// The size of the pages of the pool, in bytes.
+    pub page_size: usize,
+    /// The number of pages reserved in the pool, as per `nr_hugepages`.
+    pub reserved: u64,
+    /// The number of pages reserved, and not in use, as per `free_hugepages`.
+    pub free: u64,
+    /// The number of pages which may be allocated beyond the reserved ones, as per `nr_overcommit_hugepages`.
+    pub overcommit: u64,
+    /// The number of pages allocated beyond the reserved ones, as per `surplus_hugepages`.
+    pub surplus: u64,
+}
+
+impl HugeTlbPool {
+    /// Returns the number of pages available, either free or yet to be overcommitted.
+    pub fn available(&self) -> u64 { self.free.saturating_add(self.overcommit.saturating_sub(self.surplus)) }
+}
+
+/// The pools of HugeTLB pages, as probed on the detection of the capabilities.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HugeTlbPools {
+    /// The pool of HugeTLB pages of the Huge Page size, if probed.
+    pub huge_pages: Option<HugeTlbPool>,
+    /// The pool of 2 MB HugeTLB pages, if probed, and distinct from `huge_pages`.
+    pub huge_pages_2mb: Option<HugeTlbPool>,
+}
+
+/// Lists the pools, one per line, or states that none was probed.
+impl fmt::Display for HugeTlbPools {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pools = self.huge_pages.iter().chain(self.huge_pages_2mb.iter()).peekable();
+
+        if pools.peek().is_none() {
+            return write!(f, "llmalloc: no huge tlb pool probed");
+        }
+
+        for (index, pool) in pools.enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "llmalloc: {} kB pages: {} reserved, {} free, {} overcommit, {} surplus, {} available",
+                pool.page_size / 1024, pool.reserved, pool.free, pool.overcommit, pool.surplus, pool.available())?;
+        }
+
+        Ok(())
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  Maximum number of HugeTLB page sizes recorded; x86_64 offers 2, and arm64 up to 4.
+const MAX_HUGE_PAGE_SIZES: usize = 8;
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use std::{string::ToString, vec, vec::Vec};
+
+use super::*;
+
+const FULL: Capabilities =
+    Capabilities { huge_tlb: true, huge_tlb_2mb: true, transparent_huge_pages: true, numa: true, sysfs: true };
+
+#[test]
+fn capabilities_full() {
+    assert!(!FULL.is_degraded());
+    assert_eq!(0, FULL.downgrades().count());
+    assert_eq!("llmalloc: no capability downgraded", FULL.to_string());
+}
+
+#[test]
+fn capabilities_downgrades() {
+    let huge_tlb_2mb = Capabilities { huge_tlb: false, ..FULL };
+    assert_eq!(vec![Downgrade::HugeTlb2MbPages], huge_tlb_2mb.downgrades().collect::<Vec<_>>());
+
+    let thp = Capabilities { huge_tlb: false, huge_tlb_2mb: false, ..FULL };
+    assert_eq!(vec![Downgrade::TransparentHugePages], thp.downgrades().collect::<Vec<_>>());
+
+    let none = Capabilities::default();
+    assert!(none.is_degraded());
+    assert_eq!(vec![Downgrade::NormalPages, Downgrade::SingleNode, Downgrade::NoSysfs],
+        none.downgrades().collect::<Vec<_>>());
+
+    assert_eq!(
+        "llmalloc: HugeTLB unavailable, Huge Pages backed by Transparent Huge Pages\n\
+         llmalloc: NUMA topology unavailable, single socket shared by all threads",
+        Capabilities { huge_tlb: false, huge_tlb_2mb: false, numa: false, ..FULL }.to_string());
+}
+
+#[test]
+fn privilege_error_display() {
+    assert_eq!(
+        "SeLockMemoryPrivilege not held: grant \"Lock pages in memory\" to the account, in Local Security Policy > \
+         User Rights Assignment, then log off and on again",
+        PrivilegeError::NotHeld.to_string());
+    assert_eq!("SeLockMemoryPrivilege not enabled (error 1300)", PrivilegeError::AdjustFailed(1300).to_string());
+}
+
+#[test]
+fn host_capabilities_huge_page_sizes() {
+    let mut host = HostCapabilities::default();
+    assert!(host.huge_page_sizes().is_empty());
+
+    for size in [1 << 30, 1 << 21, 1 << 30, 1 << 16] {
+        host.add_huge_page_size(size);
+    }
+
+    assert_eq!(&[1 << 16, 1 << 21, 1 << 30], host.huge_page_sizes());
+
+    for size in 0..2 * MAX_HUGE_PAGE_SIZES {
+        host.add_huge_page_size(size);
+    }
+
+    assert_eq!(MAX_HUGE_PAGE_SIZES, host.huge_page_sizes().len());
+}
+
+#[test]
+fn host_capabilities_display() {
+    let mut host = HostCapabilities {
+        capabilities: FULL,
+        transparent_huge_pages: TransparentHugePagesMode::Madvise,
+        numa_nodes: 2,
+        rseq: true,
+        mlock_limit: Some(8 << 20),
+        os_page_size: 4096,
+        ..HostCapabilities::default()
+    };
+
+    host.add_huge_page_size(1 << 30);
+    host.add_huge_page_size(1 << 21);
+
+    assert_eq!(
+        "llmalloc: huge page sizes: 2048 kB, 1048576 kB\n\
+         llmalloc: transparent huge pages: madvise\n\
+         llmalloc: NUMA nodes: 2, of which far memory: 0\n\
+         llmalloc: rseq: available\n\
+         llmalloc: mlock limit: 8192 kB\n\
+         llmalloc: OS page size: 4 kB\n\
+         llmalloc: no capability downgraded",
+        host.to_string());
+}
+
+#[test]
+fn huge_tlb_pool_available() {
+    let pool = HugeTlbPool { page_size: 1 << 30, reserved: 4, free: 1, overcommit: 3, surplus: 1 };
+    assert_eq!(3, pool.available());
+
+    assert_eq!(1, HugeTlbPool { surplus: 5, ..pool }.available());
+    assert_eq!(0, HugeTlbPool::default().available());
+}
+
+#[test]
+fn huge_tlb_pools_display() {
+    assert_eq!("llmalloc: no huge tlb pool probed", HugeTlbPools::default().to_string());
+
+    let huge = HugeTlbPool { page_size: 1 << 30, reserved: 2, free: 0, overcommit: 0, surplus: 0 };
+    let huge_2mb = HugeTlbPool { page_size: 1 << 21, reserved: 512, free: 500, overcommit: 8, surplus: 2 };
+
+    assert_eq!(
+        "llmalloc: 1048576 kB pages: 2 reserved, 0 free, 0 overcommit, 0 surplus, 0 available\n\
+         llmalloc: 2048 kB pages: 512 reserved, 500 free, 8 overcommit, 2 surplus, 506 available",
+        HugeTlbPools { huge_pages: Some(huge), huge_pages_2mb: Some(huge_2mb) }.to_string());
+}
+
+} // mod tests
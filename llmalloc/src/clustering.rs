@@ -0,0 +1,85 @@
+//! Clustering
+//!
+//! The kernel sometimes distinguishes NUMA nodes which are barely further from one another than from themselves, such
+//! as the sub-NUMA clusters of a single socket. Serving each of them from its own socket over-allocates, hence nearby
+//! nodes are clustered together: a node is served by the socket of the lowest node within the clustering distance of
+//! it, as per the distances reported by the kernel, a node being at a distance of 10 from itself.
+//!
+//! The clustering distance defaults to 11, which suits most machines; machines whose nearby nodes are at a distance of
+//! 12, as the CCDs of some EPYC processors, may raise it, and machines whose nodes at a distance of 11 are best kept
+//! apart may lower it, or disable clustering altogether. It is selected by `LLAllocator::set_clustering_distance`.
+//!
+//! Clustering is honored by the platform of Linux; other platforms do not expose the distances between their nodes,
+//! or their nodes are not clustered.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Clustering distance selected by default.
+pub(crate) const DEFAULT_DISTANCE: u8 = 11;
+
+/// Process-wide selection of the clustering distance.
+pub(crate) struct Clustering(AtomicU8);
+
+impl Clustering {
+    /// Creates an instance, selecting the default distance.
+    pub(crate) const fn new() -> Self { Self(AtomicU8::new(DEFAULT_DISTANCE)) }
+
+    /// Returns the clustering distance, or None if clustering is disabled.
+    pub(crate) fn distance(&self) -> Option<u8> {
+        match self.0.load(Ordering::Relaxed) {
+            DISABLED => None,
+            distance => Some(distance),
+        }
+    }
+
+    /// Returns whether two nodes at `distance` from one another are clustered together.
+    #[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+        feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc")))),
+        allow(dead_code))]
+    pub(crate) fn is_clustered(&self, distance: u8) -> bool { self.distance().is_some_and(|limit| distance <= limit) }
+
+    /// Selects the clustering distance, or disables clustering if None.
+    pub(crate) fn set(&self, distance: Option<u8>) { self.0.store(distance.unwrap_or(DISABLED), Ordering::Relaxed); }
+}
+
+/// Selection of the clustering distance, shared by the allocator and the platforms.
+pub(crate) static CLUSTERING: Clustering = Clustering::new();
+
+//
+//  Implementation Details
+//
+
+//  No distance reported by the kernel is 0, hence a distance of 0 clusters no node.
+const DISABLED: u8 = 0;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn clustering_set() {
+    let clustering = Clustering::new();
+
+    assert_eq!(Some(DEFAULT_DISTANCE), clustering.distance());
+    assert!(clustering.is_clustered(11));
+    assert!(!clustering.is_clustered(12));
+
+    clustering.set(Some(12));
+
+    assert_eq!(Some(12), clustering.distance());
+    assert!(clustering.is_clustered(12));
+    assert!(!clustering.is_clustered(21));
+
+    clustering.set(None);
+
+    assert_eq!(None, clustering.distance());
+    assert!(!clustering.is_clustered(10));
+
+    //  A distance of 0 clusters no node, as if disabled.
+    clustering.set(Some(0));
+
+    assert_eq!(None, clustering.distance());
+}
+
+} // mod tests
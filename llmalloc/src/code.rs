@@ -0,0 +1,63 @@
+//! Executable Memory
+//!
+//! JIT runtimes write code, then execute it, whilst W^X policies forbid any memory from being both writable and
+//! executable at once. The code regions are therefore dedicated mappings, never shared with the heap, in one of two
+//! modes:
+//!
+//! -   Dual-mapped: the same memory is mapped twice, read-write and read-execute, at distinct addresses, so that code
+//!     written through the writable view may be executed through the executable view without any protection change.
+//! -   Flip-protected: the memory is mapped once, and its protection flipped between read-write and read-execute, see
+//!     `LLAllocator::make_executable` and `LLAllocator::make_writable`.
+//!
+//! The code regions span whole OS pages, are not recorded in the statistics, and leave the coherence of the
+//! instruction cache, on the architectures requiring it, to the caller.
+
+use core::ptr::NonNull;
+
+/// Mapping of a code region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CodeMapping {
+    /// Two views of the same memory, one read-write and one read-execute.
+    Dual,
+    /// A single view, either read-write or read-execute, initially read-write.
+    Flip,
+}
+
+/// A region of memory for executable code, allocated by `LLAllocator::allocate_code`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct CodeRegion {
+    writable: NonNull<u8>,
+    executable: NonNull<u8>,
+    size: usize,
+    mapping: CodeMapping,
+}
+
+//  Safety:
+//  -   The region only exposes the addresses of its views, the accesses are up to the caller.
+unsafe impl Send for CodeRegion {}
+unsafe impl Sync for CodeRegion {}
+
+impl CodeRegion {
+    /// Creates an instance.
+    ///
+    /// With `CodeMapping::Flip`, `writable` and `executable` are expected to be equal.
+    pub fn new(writable: NonNull<u8>, executable: NonNull<u8>, size: usize, mapping: CodeMapping) -> Self {
+        debug_assert!(mapping == CodeMapping::Dual || writable == executable);
+
+        Self { writable, executable, size, mapping }
+    }
+
+    /// Returns the address of the writable view, through which the code is written.
+    pub fn writable(&self) -> NonNull<u8> { self.writable }
+
+    /// Returns the address of the executable view, through which the code is executed.
+    ///
+    /// With `CodeMapping::Flip`, it is the address of the writable view.
+    pub fn executable(&self) -> NonNull<u8> { self.executable }
+
+    /// Returns the size of the region, rounded up to a multiple of the OS page size.
+    pub fn size(&self) -> usize { self.size }
+
+    /// Returns the mapping of the region.
+    pub fn mapping(&self) -> CodeMapping { self.mapping }
+}
@@ -0,0 +1,88 @@
+//! Collapse
+//!
+//! A `HugePage` which could not be backed by HugeTLB pages falls back on normal pages, which are only promoted to
+//! Transparent Huge Pages lazily, if at all, by `khugepaged`, and forgo the TLB benefits of the size of the `HugePage`
+//! in the meantime. With `MADV_COLLAPSE`, available from Linux 6.1 onwards, the kernel instead promotes a range
+//! synchronously, copying its pages into freshly allocated huge pages.
+//!
+//! The collapse is explicit, see `LLAllocator::collapse`: the `HugePage`s owned by the sockets are split into regions
+//! of 2 MB, the size of a Transparent Huge Page, and the hot regions, those of which enough bytes are resident, are
+//! collapsed. As the bytes of a region which were not resident are committed by its collapse, only collapsing the fully
+//! resident regions comes at no extra memory cost.
+//!
+//! A collapse is honored by the platform of Linux.
+
+/// Outcome of the collapse of a region, as returned by `Platform::collapse`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Collapse {
+    /// The region is backed by huge pages, whether collapsed or already backed by huge pages.
+    Collapsed,
+    /// The region cannot be collapsed, for example if backed by HugeTLB pages, or if Transparent Huge Pages are not
+    /// supported by the kernel.
+    Ineligible,
+    /// The region could not be collapsed, for example if no huge page could be allocated, or if some of its pages are
+    /// pinned; it may be collapsed later.
+    Failed,
+    /// The platform does not support collapsing, as by default.
+    Unsupported,
+}
+
+/// Report of a collapse of the heap, as returned by `LLAllocator::collapse`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CollapseReport {
+    /// Whether the platform supports collapsing; if not, no region is attempted.
+    pub supported: bool,
+    /// The number of regions backed by huge pages.
+    pub collapsed: usize,
+    /// The number of regions which cannot be collapsed.
+    pub ineligible: usize,
+    /// The number of regions which could not be collapsed.
+    pub failed: usize,
+    /// The number of regions skipped, as not resident enough.
+    pub cold: usize,
+}
+
+impl CollapseReport {
+    /// Returns the number of regions examined.
+    pub fn regions(&self) -> usize { self.collapsed + self.ineligible + self.failed + self.cold }
+
+    /// Records the `outcome` of the collapse of a region.
+    pub(crate) fn record(&mut self, outcome: Collapse) {
+        match outcome {
+            Collapse::Collapsed => self.collapsed += 1,
+            Collapse::Ineligible => self.ineligible += 1,
+            Collapse::Failed => self.failed += 1,
+            Collapse::Unsupported => self.supported = false,
+        }
+    }
+}
+
+/// Size of the regions collapsed, that of a Transparent Huge Page.
+pub(crate) const REGION_SIZE: usize = 2 * 1024 * 1024;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn collapse_report_record() {
+    let mut report = CollapseReport { supported: true, ..CollapseReport::default() };
+
+    report.record(Collapse::Collapsed);
+    report.record(Collapse::Collapsed);
+    report.record(Collapse::Ineligible);
+    report.record(Collapse::Failed);
+    report.cold += 1;
+
+    assert!(report.supported);
+    assert_eq!((2, 1, 1, 1), (report.collapsed, report.ineligible, report.failed, report.cold));
+    assert_eq!(5, report.regions());
+
+    report.record(Collapse::Unsupported);
+
+    assert!(!report.supported);
+    assert_eq!(5, report.regions());
+}
+
+} // mod tests
@@ -0,0 +1,185 @@
+//! Compaction
+//!
+//! Long-running applications tend to leave Large Pages sparsely occupied: a handful of small objects pins each of
+//! them, preventing their return to their `HugePage`, whilst new pages get carved for new objects. A compaction pass
+//! relocates the relocatable objects out of the sparse Large Pages, so that, once empty, these pages return to their
+//! `HugePage`, available to any class size, rather than new `HugePage` being mapped from the OS.
+//!
+//! Only the application knows which objects may be relocated, and how to update the references to them, hence the
+//! compaction pass is opt-in, and operates on the objects listed by the application, either updating its handles in
+//! place, or notifying a user-provided callback of each move.
+//!
+//! The sockets retain their `HugePage` for the lifetime of the process, hence the compaction concentrates the live
+//! objects, but does not unmap memory by itself.
+
+use core::ptr::NonNull;
+
+use llmalloc_core::{Category, Configuration, Layout, Properties};
+
+use crate::LLConfiguration;
+
+/// An allocation which may be relocated by a compaction pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Relocatable {
+    /// The pointer to the allocation, updated on relocation.
+    pub pointer: NonNull<u8>,
+    /// The layout with which the allocation was allocated.
+    pub layout: Layout,
+}
+
+/// Outcome of a compaction pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CompactionReport {
+    /// Number of sparse Large Pages found, among those holding the relocatable allocations.
+    pub sparse_pages: usize,
+    /// Number of sparse Large Pages out of which all relocatable allocations were relocated.
+    pub evacuated_pages: usize,
+    /// Number of allocations relocated.
+    pub relocated: usize,
+    /// Number of bytes relocated.
+    pub relocated_bytes: usize,
+}
+
+/// Plan of a compaction pass: the relocatable allocations residing in sparse Large Pages, by page.
+pub(crate) struct CompactionPlan<'a> {
+    //  Pairs of Large Page and index of the allocation, sorted by Large Page, sparse pages only.
+    entries: &'a mut [(usize, usize)],
+    //  Number of sparse pages.
+    pages: usize,
+}
+
+impl<'a> CompactionPlan<'a> {
+    /// Denominator of the sparsity threshold: a Large Page is sparse if its relocatable allocations occupy at most
+    /// 1 / `SPARSITY` of its bytes.
+    pub(crate) const SPARSITY: usize = 4;
+
+    /// Plans the compaction of `allocations`, using `entries` as scratch space.
+    ///
+    /// Only the Normal allocations satisfying `eligible` are considered, the others occupying whole Large Pages, or
+    /// being directly mapped.
+    pub(crate) fn new<F>(allocations: &[Relocatable], entries: &'a mut [(usize, usize)], eligible: F) -> Self
+        where
+            F: Fn(NonNull<u8>) -> bool,
+    {
+        debug_assert!(entries.len() >= allocations.len());
+
+        let mut length = 0;
+
+        for (index, allocation) in allocations.iter().enumerate() {
+            let pointer = allocation.pointer;
+
+            if Properties::<LLConfiguration>::category_of_pointer(pointer) == Category::Normal && eligible(pointer) {
+                entries[length] = (Self::large_page_of(pointer), index);
+                length += 1;
+            }
+        }
+
+        let entries = &mut entries[..length];
+        entries.sort_unstable();
+
+        //  Retain the entries of sparse pages only.
+        let mut retained = 0;
+        let mut pages = 0;
+        let mut start = 0;
+
+        while start < entries.len() {
+            let page = entries[start].0;
+            let end = start + entries[start..].iter().take_while(|(other, _)| *other == page).count();
+
+            let occupied: usize = entries[start..end].iter().map(|(_, index)| allocations[*index].layout.size()).sum();
+
+            if occupied * Self::SPARSITY <= LLConfiguration::LARGE_PAGE_SIZE.value() {
+                entries.copy_within(start..end, retained);
+                retained += end - start;
+                pages += 1;
+            }
+
+            start = end;
+        }
+
+        CompactionPlan { entries: &mut entries[..retained], pages }
+    }
+
+    /// Returns the maximum number of allocations of `layout` attempted per relocation.
+    ///
+    /// The allocations landing in a sparse page are held aside until the end of the pass, so that the next attempts
+    /// land elsewhere, hence the free cells of all sparse pages, and one more page, bound the attempts.
+    pub(crate) fn attempts(&self, layout: Layout) -> usize {
+        (self.pages + 1).saturating_mul(LLConfiguration::LARGE_PAGE_SIZE.value() / layout.size().max(1) + 1)
+    }
+
+    /// Returns the Large Page of `pointer`.
+    pub(crate) fn large_page_of(pointer: NonNull<u8>) -> usize {
+        LLConfiguration::LARGE_PAGE_SIZE.round_down(pointer.as_ptr() as usize)
+    }
+
+    /// Returns the number of sparse pages.
+    pub(crate) fn sparse_pages(&self) -> usize { self.pages }
+
+    /// Returns whether `pointer` resides in a sparse page.
+    pub(crate) fn is_sparse(&self, pointer: NonNull<u8>) -> bool {
+        let page = Self::large_page_of(pointer);
+
+        self.entries.binary_search_by(|(other, _)| other.cmp(&page)).is_ok()
+    }
+
+    /// Returns the sparse pages, each with the indices of its allocations.
+    pub(crate) fn pages(&self) -> impl Iterator<Item = (usize, &[(usize, usize)])> + '_ {
+        let mut rest: &[(usize, usize)] = self.entries;
+
+        core::iter::from_fn(move || {
+            let page = rest.first()?.0;
+            let length = rest.iter().take_while(|(other, _)| *other == page).count();
+
+            let (group, tail) = rest.split_at(length);
+            rest = tail;
+
+            Some((page, group))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+const LARGE_PAGE_SIZE: usize = LLConfiguration::LARGE_PAGE_SIZE.value();
+
+fn relocatable(address: usize, size: usize) -> Relocatable {
+    let pointer = NonNull::new(address as *mut u8).unwrap();
+
+    Relocatable { pointer, layout: Layout::from_size_align(size, 8).unwrap() }
+}
+
+#[test]
+fn compaction_plan_sparse_pages() {
+    let dense = 4 * LARGE_PAGE_SIZE;
+    let sparse = 7 * LARGE_PAGE_SIZE;
+
+    let allocations = [
+        relocatable(sparse + 64, 32),
+        relocatable(dense + 64, LARGE_PAGE_SIZE / 2),
+        relocatable(sparse + 128, 32),
+        //  Large allocations are skipped.
+        relocatable(9 * LARGE_PAGE_SIZE, 32),
+        //  Ineligible allocations are skipped, and do not count towards the occupation of their page.
+        relocatable(sparse + 256, LARGE_PAGE_SIZE / 2),
+    ];
+
+    let ineligible = allocations[4].pointer;
+
+    let mut entries = [(0, 0); 5];
+    let plan = CompactionPlan::new(&allocations, &mut entries, |pointer| pointer != ineligible);
+
+    assert_eq!(1, plan.sparse_pages());
+
+    let (page, group) = plan.pages().next().unwrap();
+    assert_eq!(sparse, page);
+    assert_eq!([(sparse, 0), (sparse, 2)], group);
+
+    assert!(plan.is_sparse(allocations[0].pointer));
+    assert!(!plan.is_sparse(allocations[1].pointer));
+}
+
+} // mod tests
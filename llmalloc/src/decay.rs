@@ -0,0 +1,118 @@
+//! Decay
+//!
+//! The Huge blocks retained for reuse, see `LLAllocator::set_direct_retained`, keep their physical memory by default.
+//! With decay, a block is instead lazily purged as it is retained, with `madvise(MADV_FREE)`, so that the kernel may
+//! reclaim its memory under pressure while its reuse remains cheap, and forcibly purged, with `madvise(MADV_DONTNEED)`,
+//! once it has remained retained for one to two decay windows, so that the resident memory shrinks regardless.
+//!
+//! The decay passes are driven by the deallocations of Huge allocations: the first such deallocation following the
+//! expiry of the window runs a pass, unless the background thread is running, in which case it runs the passes instead.
+//! A process deallocating no Huge allocation may run the passes itself, through `LLAllocator::decay`.
+//!
+//! The window is resolved from the `LLMALLOC_DECAY` environment variable, enabled with a window of 10 seconds if set to
+//! any value other than an empty string or `0`, unless set explicitly beforehand by `LLAllocator::set_decay`.
+//!
+//! Decay is honored by the platforms of Linux, and by the POSIX platform.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{Platform, LLPlatform};
+
+/// Name of the environment variable selecting decay, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_DECAY\0";
+
+/// Window of decay selected by the environment variable.
+pub(crate) const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Process-wide selection of decay.
+pub(crate) struct Decay {
+    //  Window, in nanoseconds, or DISABLED, or UNRESOLVED.
+    window: AtomicU64,
+    //  Timestamp of the latest pass.
+    latest: AtomicU64,
+}
+
+impl Decay {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self { Self { window: AtomicU64::new(UNRESOLVED), latest: AtomicU64::new(0) } }
+
+    /// Returns whether decay is enabled, resolving it from `platform` if not yet resolved.
+    #[cfg_attr(not(all(any(target_os = "linux", feature = "posix"),
+        not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))), allow(dead_code))]
+    #[inline(always)]
+    pub(crate) fn is_enabled(&self, platform: &LLPlatform) -> bool { self.window_nanos(platform) != DISABLED }
+
+    /// Returns the window of decay, if enabled, resolving it from `platform` if not yet resolved.
+    pub(crate) fn window(&self, platform: &LLPlatform) -> Option<Duration> {
+        match self.window_nanos(platform) {
+            DISABLED => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Enables decay with the given window, or disables it, overriding the environment.
+    pub(crate) fn set(&self, window: Option<Duration>) { self.window.store(Self::encode(window), Ordering::Relaxed); }
+
+    /// Returns whether a pass is due, a window having elapsed since the latest, in which case the caller is to run it.
+    ///
+    /// Of concurrent callers, a single one is elected to run the pass.
+    #[inline(never)]
+    pub(crate) fn is_due(&self, platform: &LLPlatform) -> bool {
+        let window = self.window_nanos(platform);
+
+        if window == DISABLED {
+            return false;
+        }
+
+        let now = platform.now();
+        let latest = self.latest.load(Ordering::Relaxed);
+
+        if now.saturating_sub(latest) < window {
+            return false;
+        }
+
+        self.latest.compare_exchange(latest, now, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+    }
+
+    #[inline(always)]
+    fn window_nanos(&self, platform: &LLPlatform) -> u64 {
+        match self.window.load(Ordering::Relaxed) {
+            UNRESOLVED => self.resolve(platform),
+            nanos => nanos,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self, platform: &LLPlatform) -> u64 {
+        let enabled = platform.environment_flag(ENVIRONMENT_VARIABLE);
+        let resolved = Self::encode(if enabled { Some(DEFAULT_WINDOW) } else { None });
+
+        //  A window set by `LLAllocator::set_decay` while the environment is read is kept over the default one.
+        match self.window.compare_exchange(UNRESOLVED, resolved, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => resolved,
+            Err(current) => current,
+        }
+    }
+
+    //  A window of 0 is encoded as the shortest window, 1 nanosecond, rather than as disabled.
+    fn encode(window: Option<Duration>) -> u64 {
+        match window {
+            Some(window) => (window.as_nanos().min(u128::from(UNRESOLVED - 1)) as u64).max(1),
+            None => DISABLED,
+        }
+    }
+}
+
+/// Selection of decay, shared by the allocator and the platforms.
+pub(crate) static DECAY: Decay = Decay::new();
+
+//
+//  Implementation Details
+//
+
+const DISABLED: u64 = 0;
+const UNRESOLVED: u64 = u64::MAX;
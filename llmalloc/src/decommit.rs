@@ -0,0 +1,21 @@
+//! Decommit
+//!
+//! The physical memory backing a `HugePage` is retained by default, even once some of its `LargePage`s are free again,
+//! so that a later allocation hits memory already faulted in. With decommit, the platform instead advises the kernel
+//! that the `LargePage`s freed by a Large deallocation are no longer needed, with `madvise(MADV_DONTNEED)`, releasing
+//! their physical memory while keeping their address range, so that long-running services give back the memory of a
+//! load spike once it subsides. The pages are faulted in anew, zeroed, on their next use.
+//!
+//! The flag is resolved from the `LLMALLOC_DECOMMIT` environment variable, enabled if set to any value other than an
+//! empty string or `0`, unless set explicitly beforehand by `LLAllocator::set_decommit`.
+//!
+//! Decommit is honored by the platforms of Linux, and by the POSIX platform; bare metal memory is not backed lazily,
+//! and custom platforms decommit their memory themselves, if at all.
+
+use crate::toggle::Toggle;
+
+/// Name of the environment variable selecting decommit, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_DECOMMIT\0";
+
+/// Selection of decommit, shared by the allocator and the platforms.
+pub(crate) static DECOMMIT: Toggle = Toggle::new(ENVIRONMENT_VARIABLE);
@@ -0,0 +1,249 @@
+//! Allocation Epochs
+//!
+//! An epoch is a period delimited by calls to `LLAllocator::advance_epoch`, at known points of the application such as
+//! the end of a frame, or of a request. While tracking is enabled, each allocation is stamped with the current epoch,
+//! and the allocations surviving more than a given number of epochs may then be listed: in a game, an allocation
+//! surviving many frames, yet allocated on a per-frame path, is a likely leak.
+//!
+//! The stamps are kept in a fixed-capacity table, reserved in the BSS and only touched once tracking is enabled, with
+//! a bounded number of probes per allocation and deallocation. An allocation which cannot be stamped, as its probes
+//! are all occupied, is counted as untracked rather than evicting another.
+//!
+//! A reallocation, or remap, moving the memory is a new allocation, stamped with the current epoch.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate::armed::ARMED;
+
+/// An allocation surviving more than the requested number of epochs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SurvivingAllocation {
+    /// The address of the allocation.
+    pub address: usize,
+    /// The epoch during which the allocation was made.
+    pub epoch: u64,
+}
+
+/// Tracker of the epochs of the allocations.
+pub(crate) struct EpochTracker {
+    epoch: AtomicU64,
+    tracking: AtomicBool,
+    untracked: AtomicU64,
+    entries: [Entry; CAPACITY],
+}
+
+impl EpochTracker {
+    /// Creates an instance, at epoch 0, not tracking.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ENTRY: Entry = Entry::new();
+
+        Self {
+            epoch: AtomicU64::new(0),
+            tracking: AtomicBool::new(false),
+            untracked: AtomicU64::new(0),
+            entries: [ENTRY; CAPACITY],
+        }
+    }
+
+    /// Returns the current epoch.
+    pub(crate) fn epoch(&self) -> u64 { self.epoch.load(Ordering::Relaxed) }
+
+    /// Advances the current epoch, returning the new epoch.
+    pub(crate) fn advance(&self) -> u64 { self.epoch.fetch_add(1, Ordering::Relaxed) + 1 }
+
+    /// Returns whether allocations are stamped.
+    #[inline(always)]
+    pub(crate) fn is_tracking(&self) -> bool { self.tracking.load(Ordering::Relaxed) }
+
+    /// Enables, or disables, the stamping of allocations.
+    ///
+    /// Enabling clears the stamps, and the count of untracked allocations, left by any prior tracking.
+    #[cold]
+    pub(crate) fn set_tracking(&self, enabled: bool) {
+        if enabled && !self.is_tracking() {
+            for entry in &self.entries {
+                entry.clear();
+            }
+
+            self.untracked.store(0, Ordering::Relaxed);
+        }
+
+        match (self.tracking.swap(enabled, Ordering::Relaxed), enabled) {
+            (false, true) => ARMED.arm(),
+            (true, false) => ARMED.disarm(),
+            _ => (),
+        }
+    }
+
+    /// Returns the number of allocations which could not be stamped, since tracking was last enabled.
+    pub(crate) fn untracked(&self) -> u64 { self.untracked.load(Ordering::Relaxed) }
+
+    /// Stamps the allocation located at `address` with the current epoch.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn stamp(&self, address: usize) {
+        let stamp = self.epoch() + 1;
+
+        if !self.probes(address).any(|entry| entry.claim(address, stamp)) {
+            self.untracked.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Erases the stamp of the allocation located at `address`, if any.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn erase(&self, address: usize) {
+        if let Some(entry) = self.probes(address).find(|entry| entry.address.load(Ordering::Relaxed) == address) {
+            entry.clear();
+        }
+    }
+
+    /// Invokes `report` for each stamped allocation surviving more than `epochs` epochs, returning their number.
+    ///
+    /// The entries are read without synchronization, hence allocations made or deallocated concurrently may or may not
+    /// be reported.
+    #[cold]
+    pub(crate) fn survivors<F>(&self, epochs: u64, mut report: F) -> usize
+        where
+            F: FnMut(&SurvivingAllocation),
+    {
+        let current = self.epoch();
+        let mut survivors = 0;
+
+        for entry in &self.entries {
+            let address = entry.address.load(Ordering::Acquire);
+            let stamp = entry.stamp.load(Ordering::Acquire);
+
+            if address == 0 || stamp == 0 || current.saturating_sub(stamp - 1) <= epochs {
+                continue;
+            }
+
+            survivors += 1;
+            report(&SurvivingAllocation { address, epoch: stamp - 1 });
+        }
+
+        survivors
+    }
+
+    //  Returns the entries which may hold the stamp of `address`.
+    fn probes(&self, address: usize) -> impl Iterator<Item = &Entry> {
+        //  Fibonacci hashing, skipping the low bits which alignment mostly zeroes.
+        let hash = ((address >> 4) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - CAPACITY.trailing_zeros());
+
+        (0..PROBES).map(move |probe| &self.entries[(hash as usize + probe) % CAPACITY])
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  Number of entries of the table, a power of 2.
+const CAPACITY: usize = 1 << 18;
+
+//  Number of entries probed per allocation, or deallocation.
+const PROBES: usize = 8;
+
+//  An entry, free if `address` is 0, with `stamp` the epoch plus 1, or 0 if not yet stamped.
+struct Entry {
+    address: AtomicUsize,
+    stamp: AtomicU64,
+}
+
+impl Entry {
+    const fn new() -> Self { Self { address: AtomicUsize::new(0), stamp: AtomicU64::new(0) } }
+
+    //  Claims the entry for `address`, with `stamp`, if free.
+    fn claim(&self, address: usize, stamp: u64) -> bool {
+        if self.address.compare_exchange(0, address, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return false;
+        }
+
+        self.stamp.store(stamp, Ordering::Release);
+
+        true
+    }
+
+    //  Frees the entry.
+    fn clear(&self) {
+        self.stamp.store(0, Ordering::Relaxed);
+        self.address.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use std::vec::Vec;
+
+use super::*;
+
+#[test]
+fn epoch_tracker_survivors() {
+    static TRACKER: EpochTracker = EpochTracker::new();
+
+    let tracker = &TRACKER;
+    assert!(!tracker.is_tracking());
+
+    tracker.set_tracking(true);
+
+    tracker.stamp(0x1000);
+    tracker.stamp(0x2000);
+
+    assert_eq!(1, tracker.advance());
+
+    tracker.stamp(0x3000);
+    tracker.erase(0x2000);
+    tracker.erase(0x4000);
+
+    let mut survivors = Vec::new();
+    assert_eq!(1, tracker.survivors(0, |survivor| survivors.push(*survivor)));
+    assert_eq!([SurvivingAllocation { address: 0x1000, epoch: 0 }], &survivors[..]);
+
+    assert_eq!(2, tracker.advance());
+
+    assert_eq!(1, tracker.survivors(1, |_| ()));
+    assert_eq!(2, tracker.survivors(0, |_| ()));
+    assert_eq!(0, tracker.untracked());
+
+    //  Re-enabling clears the stamps.
+    tracker.set_tracking(false);
+    tracker.set_tracking(true);
+
+    assert_eq!(0, tracker.survivors(0, |_| ()));
+}
+
+#[test]
+fn epoch_tracker_untracked() {
+    static TRACKER: EpochTracker = EpochTracker::new();
+
+    let tracker = &TRACKER;
+    tracker.set_tracking(true);
+
+    //  Addresses differing in their low bits only share their probes.
+    let addresses: Vec<_> = (0..=PROBES).map(|index| 0x10000 + index).collect();
+
+    for address in &addresses {
+        tracker.stamp(*address);
+    }
+
+    assert_eq!(1, tracker.untracked());
+
+    tracker.erase(addresses[0]);
+    tracker.stamp(addresses[PROBES]);
+
+    assert_eq!(1, tracker.untracked());
+
+    tracker.advance();
+
+    let mut survivors = Vec::new();
+    assert_eq!(PROBES, tracker.survivors(0, |survivor| survivors.push(survivor.address)));
+
+    survivors.sort_unstable();
+    assert_eq!(&addresses[1..], &survivors[..]);
+}
+
+} // mod tests
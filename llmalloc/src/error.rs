@@ -0,0 +1,25 @@
+//! Errors
+//!
+//! The reasons for which an allocation may fail, as reported by `LLAllocator::try_allocate`, `LLAllocator::remap`,
+//! `LLAllocator::allocate_with_deadline`, and `LLAllocator::allocate_on_node`.
+
+/// Error of an allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AllocationError {
+    /// The requested size exceeds the maximum allocation size of the allocator.
+    ///
+    /// No memory is requested from the OS in this case, hence pathological sizes fail immediately.
+    ExceedsMaximumSize,
+    /// The requested alignment exceeds the maximum supported alignment, that of a `HugePage`.
+    UnsupportedAlignment,
+    /// The memory could not be obtained, either from the current pages or from the underlying `Platform`.
+    OutOfMemory,
+    /// The allocation cannot be remapped, as either its current or its requested size is not directly mapped.
+    NotRemappable,
+    /// The allocation cannot be served within its deadline, as it requires a slow path known to exceed it.
+    DeadlineExceeded,
+    /// The allocation was attempted within a scope forbidding allocations, see `LLAllocator::forbid_allocation`.
+    Forbidden,
+    /// The allocation targets a NUMA node which is not a node of the machine, or cannot be served by a socket.
+    UnknownNode,
+}
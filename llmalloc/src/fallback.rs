@@ -0,0 +1,227 @@
+//! Fallbacks
+//!
+//! Whenever the most capable path is unavailable, llmalloc falls back to a less capable one rather than failing: a
+//! `HugePage` is backed by Transparent Huge Pages, or normal pages, rather than HugeTLB pages, a misaligned mapping is
+//! retried, a thread is served by the socket of node 0, etc...
+//!
+//! Such a deployment works, but is slower than expected. The fallbacks are counted, so as to be diagnosable from the
+//! metrics alone.
+//!
+//! A `HugePage` which cannot be backed by HugeTLB pages of its own size is backed by 2 MB HugeTLB pages, if smaller and
+//! available, or otherwise by normal pages, which Transparent Huge Pages may then promote to 2 MB pages. A pool of
+//! HugeTLB pages merely exhausted is not downgraded, as its pages may be released, and is tried anew on the next
+//! mapping.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Metrics of the fallbacks, since the start of the process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FallbackMetrics {
+    /// Number of `HugePage` backed by HugeTLB pages, the most capable configuration.
+    pub huge_tlb_mappings: u64,
+    /// Number of failures to map a `HugePage` with HugeTLB pages, each downgrading HugeTLB pages of the size tried.
+    pub huge_tlb_failures: u64,
+    /// Number of failures to map a `HugePage` with HugeTLB pages as their pool was exhausted, downgrading nothing.
+    pub huge_tlb_exhaustions: u64,
+    /// Number of `HugePage` backed by 2 MB HugeTLB pages, as HugeTLB pages of their own size were unavailable.
+    pub huge_tlb_2mb_mappings: u64,
+    /// Number of `HugePage` backed by normal pages, advised to be promoted to Transparent Huge Pages.
+    pub transparent_huge_page_mappings: u64,
+    /// Number of `HugePage` backed by normal pages, without Transparent Huge Pages.
+    pub normal_page_mappings: u64,
+    /// Number of mappings retried by over-allocating, as the first attempt failed or was misaligned.
+    pub mmap_retries: u64,
+    /// Number of mappings which failed despite all fallbacks, each failing an allocation unless retried.
+    pub mmap_failures: u64,
+    /// Number of mappings unmapped as they landed outside of the address range, each failing an allocation unless
+    /// retried, see `LLAllocator::set_address_range`.
+    pub out_of_range_mappings: u64,
+    /// Number of allocations retried, as no memory could be mapped, see `LLAllocator::set_retry_policy`.
+    pub allocation_retries: u64,
+    /// Number of allocations which succeeded once retried.
+    pub recovered_allocations: u64,
+    /// Number of mappings which could not be unmapped, each leaked unless aborting, see
+    /// `LLAllocator::set_unmap_failure_policy`.
+    pub unmap_failures: u64,
+    /// Number of mappings which could not be locked in RAM while pinning, for example as the limit of locked memory
+    /// was reached, see `LLAllocator::set_pinned`.
+    pub lock_failures: u64,
+    /// Number of times the NUMA node of the current thread could not be determined, and node 0 was used instead.
+    pub unknown_nodes: u64,
+    /// Number of deallocations returned directly to the first socket found, as the thread could not be initialized.
+    pub uncached_deallocations: u64,
+    /// Number of allocations served by the socket of another node, nearest first, as that of the thread was exhausted.
+    pub remote_node_allocations: u64,
+    /// Number of allocations delegated to the system allocator.
+    ///
+    /// Always 0, unless the `system-fallback` feature is enabled.
+    pub system_allocations: u64,
+}
+
+impl FallbackMetrics {
+    /// Returns the total number of fallbacks, that is all counters but `huge_tlb_mappings` and `recovered_allocations`.
+    pub fn total(&self) -> u64 {
+        self.huge_tlb_failures +
+            self.huge_tlb_exhaustions +
+            self.huge_tlb_2mb_mappings +
+            self.transparent_huge_page_mappings +
+            self.normal_page_mappings +
+            self.mmap_retries +
+            self.mmap_failures +
+            self.out_of_range_mappings +
+            self.allocation_retries +
+            self.unmap_failures +
+            self.lock_failures +
+            self.unknown_nodes +
+            self.uncached_deallocations +
+            self.remote_node_allocations +
+            self.system_allocations
+    }
+}
+
+/// Event of a fallback, or of its absence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Fallback {
+    /// A `HugePage` was backed by HugeTLB pages.
+    HugeTlbMapping,
+    /// A `HugePage` failed to be backed by HugeTLB pages.
+    HugeTlbFailure,
+    /// A `HugePage` failed to be backed by HugeTLB pages, as their pool was exhausted.
+    #[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+        feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))), allow(dead_code))]
+    HugeTlbExhaustion,
+    /// A `HugePage` was backed by 2 MB HugeTLB pages.
+    #[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+        feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))), allow(dead_code))]
+    HugeTlb2MbMapping,
+    /// A `HugePage` was backed by Transparent Huge Pages.
+    TransparentHugePageMapping,
+    /// A `HugePage` was backed by normal pages.
+    NormalPageMapping,
+    /// A mapping was retried.
+    MmapRetry,
+    /// A mapping failed.
+    MmapFailure,
+    /// A mapping landed outside of the address range.
+    #[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+        feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc")))),
+        allow(dead_code))]
+    OutOfRangeMapping,
+    /// An allocation was retried.
+    AllocationRetry,
+    /// An allocation succeeded once retried.
+    RecoveredAllocation,
+    /// A mapping could not be unmapped.
+    UnmapFailure,
+    /// A mapping could not be locked in RAM.
+    LockFailure,
+    /// Node 0 was used, as the current node could not be determined.
+    UnknownNode,
+    /// A deallocation was returned directly to a socket.
+    UncachedDeallocation,
+    /// An allocation was served by the socket of another node.
+    RemoteNodeAllocation,
+    /// An allocation was delegated to the system allocator.
+    #[cfg_attr(not(feature = "system-fallback"), allow(dead_code))]
+    SystemAllocation,
+}
+
+/// Atomic Metrics of the fallbacks.
+///
+/// Held by the `Platform`, which the allocator records its own fallbacks in, alongside those of the platform.
+pub struct AtomicFallbackMetrics([AtomicU64; NUMBER_FALLBACKS]);
+
+impl AtomicFallbackMetrics {
+    /// Creates an instance, with nothing recorded.
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+
+        Self([ZERO; NUMBER_FALLBACKS])
+    }
+
+    /// Records one occurrence of `fallback`.
+    #[cold]
+    pub(crate) fn record(&self, fallback: Fallback) { self.0[fallback as usize].fetch_add(1, Ordering::Relaxed); }
+
+    /// Returns a snapshot of the metrics.
+    pub(crate) fn snapshot(&self) -> FallbackMetrics {
+        let count = |fallback: Fallback| self.0[fallback as usize].load(Ordering::Relaxed);
+
+        FallbackMetrics {
+            huge_tlb_mappings: count(Fallback::HugeTlbMapping),
+            huge_tlb_failures: count(Fallback::HugeTlbFailure),
+            huge_tlb_exhaustions: count(Fallback::HugeTlbExhaustion),
+            huge_tlb_2mb_mappings: count(Fallback::HugeTlb2MbMapping),
+            transparent_huge_page_mappings: count(Fallback::TransparentHugePageMapping),
+            normal_page_mappings: count(Fallback::NormalPageMapping),
+            mmap_retries: count(Fallback::MmapRetry),
+            mmap_failures: count(Fallback::MmapFailure),
+            out_of_range_mappings: count(Fallback::OutOfRangeMapping),
+            allocation_retries: count(Fallback::AllocationRetry),
+            recovered_allocations: count(Fallback::RecoveredAllocation),
+            unmap_failures: count(Fallback::UnmapFailure),
+            lock_failures: count(Fallback::LockFailure),
+            unknown_nodes: count(Fallback::UnknownNode),
+            uncached_deallocations: count(Fallback::UncachedDeallocation),
+            remote_node_allocations: count(Fallback::RemoteNodeAllocation),
+            system_allocations: count(Fallback::SystemAllocation),
+        }
+    }
+}
+
+impl Default for AtomicFallbackMetrics {
+    fn default() -> Self { Self::new() }
+}
+
+//
+//  Implementation Details
+//
+
+const NUMBER_FALLBACKS: usize = Fallback::SystemAllocation as usize + 1;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn fallback_metrics_total() {
+    let metrics = FallbackMetrics::default();
+    assert_eq!(0, metrics.total());
+
+    let metrics = FallbackMetrics { huge_tlb_mappings: 5, normal_page_mappings: 2, mmap_retries: 1, ..metrics };
+    assert_eq!(3, metrics.total());
+}
+
+#[test]
+fn atomic_fallback_metrics_record() {
+    let metrics = AtomicFallbackMetrics::new();
+    assert_eq!(FallbackMetrics::default(), metrics.snapshot());
+
+    metrics.record(Fallback::HugeTlbFailure);
+    metrics.record(Fallback::HugeTlbExhaustion);
+    metrics.record(Fallback::HugeTlb2MbMapping);
+    metrics.record(Fallback::NormalPageMapping);
+    metrics.record(Fallback::NormalPageMapping);
+    metrics.record(Fallback::SystemAllocation);
+    metrics.record(Fallback::AllocationRetry);
+    metrics.record(Fallback::RecoveredAllocation);
+    metrics.record(Fallback::OutOfRangeMapping);
+    metrics.record(Fallback::RemoteNodeAllocation);
+
+    let snapshot = metrics.snapshot();
+
+    assert_eq!(1, snapshot.huge_tlb_failures);
+    assert_eq!(1, snapshot.huge_tlb_exhaustions);
+    assert_eq!(1, snapshot.huge_tlb_2mb_mappings);
+    assert_eq!(2, snapshot.normal_page_mappings);
+    assert_eq!(1, snapshot.system_allocations);
+    assert_eq!(1, snapshot.allocation_retries);
+    assert_eq!(1, snapshot.recovered_allocations);
+    assert_eq!(1, snapshot.out_of_range_mappings);
+    assert_eq!(1, snapshot.remote_node_allocations);
+    assert_eq!(9, snapshot.total());
+}
+
+} // mod tests
@@ -0,0 +1,80 @@
+//! Fork
+//!
+//! The child of a fork only inherits the forking thread, whereas the allocator is shared by all threads: the heap is
+//! inherited as it stood at the time of the fork, whatever the other threads were doing being frozen mid-way. The
+//! structures of the heap are lock-free, hence consistent at any point, yet a few latches are spun upon by the threads
+//! waiting for another to publish what it creates, such as the reservation of the address space, and the background
+//! thread is not inherited either.
+//!
+//! Hence handlers are registered around fork, with `pthread_atfork`, on the first use of the allocator:
+//!
+//! -   Prior to forking, the latches being created by other threads are waited upon, for the child to inherit them
+//!     fully created.
+//! -   In the child, the latches still being created, by threads which no longer exist, are reset, to be created anew
+//!     on first use, as is the state shared by the unregistered threads if in use, see `LLAllocator::register_thread`,
+//!     and the background thread is recorded as no longer running: the maintenance is then performed by the threads
+//!     of the child, unless it spawns the background thread anew, see `LLAllocator::set_background_thread`.
+//!     The NUMA node of the forking thread is looked up anew, the child being scheduled independently of its parent.
+//!
+//! The caches of the other threads are not inherited, their memory being leaked by the child, and neither are their
+//! pinned epochs, past which the memory retired in the child is never reclaimed, see `LLAllocator::pin`.
+//!
+//! The handlers are registered by the platforms of Linux, and by the POSIX platform, and by custom platforms
+//! implementing `Platform::at_fork`.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    background::BACKGROUND, bounds::ADDRESS_BOUNDS, registration::REGISTRATION, reservation::ADDRESS_SPACE,
+    shared::SHARED, Platform, LLPlatform,
+};
+
+/// Process-wide registration of the handlers around fork.
+pub(crate) struct Fork(AtomicBool);
+
+impl Fork {
+    /// Creates an instance, without handlers.
+    pub(crate) const fn new() -> Self { Self(AtomicBool::new(false)) }
+
+    /// Returns whether the handlers are registered.
+    pub(crate) fn is_registered(&self) -> bool { self.0.load(Ordering::Relaxed) }
+
+    /// Registers `prepare` and `child` with `platform`, returning whether they were registered.
+    ///
+    /// To be called once per process, the handlers being invoked as many times as they are registered.
+    #[cold]
+    pub(crate) fn register(&self, platform: &LLPlatform, prepare: extern "C" fn(), child: extern "C" fn()) -> bool {
+        let registered = platform.at_fork(prepare, child);
+
+        self.0.store(registered, Ordering::Relaxed);
+
+        registered
+    }
+
+    /// Waits until the latches are no longer being created by other threads.
+    ///
+    /// To be called prior to forking.
+    #[cold]
+    pub(crate) fn settle(&self) {
+        ADDRESS_SPACE.settle();
+        ADDRESS_BOUNDS.settle();
+        SHARED.settle();
+    }
+
+    /// Resets the latches being created by threads which no longer exist, abandons the state shared by the unregistered
+    /// threads if in use, and records the background thread as no longer running.
+    ///
+    /// To be called in the child of a fork.
+    #[cold]
+    pub(crate) fn recover(&self) {
+        ADDRESS_SPACE.recover();
+        ADDRESS_BOUNDS.recover();
+        SHARED.recover();
+        REGISTRATION.recover();
+
+        BACKGROUND.forget();
+    }
+}
+
+/// Registration of the handlers around fork, shared by the allocator and its handlers.
+pub(crate) static FORK: Fork = Fork::new();
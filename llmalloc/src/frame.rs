@@ -0,0 +1,242 @@
+//! Frame Mode
+//!
+//! A thread in frame mode serves its allocations from a frame region, by bumping a cursor, and frees them wholesale by
+//! resetting the cursor at the end of each frame, as befits the per-frame allocations of games. The allocations which
+//! must outlive the frame are made persistent, from the heap, as are the allocations of a frame once its region is
+//! exhausted.
+//!
+//! The deallocation of frame memory is a no-op, whichever the thread deallocating it. To recognize such memory, the
+//! frame regions are registered in a process-wide registry, only scanned while at least one region is registered.
+//!
+//! The frame allocations are not recorded in the statistics, nor stamped with their epoch.
+
+use core::{
+    alloc::Layout,
+    cell::Cell,
+    mem,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::armed::ARMED;
+
+/// Frame region of a thread, starting with its header.
+#[derive(Clone, Copy)]
+pub(crate) struct Frame(NonNull<FrameHeader>);
+
+impl Frame {
+    /// Size of the header, at the start of the region.
+    pub(crate) const HEADER_SIZE: usize = mem::size_of::<FrameHeader>();
+
+    /// Alignment of the region.
+    pub(crate) const ALIGNMENT: usize = mem::align_of::<FrameHeader>();
+
+    /// Initializes a frame over the `size` bytes of `region`, registered in `slot`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `region` is valid for writes of `size` bytes, aligned on `ALIGNMENT`.
+    /// -   Assumes `size` is at least `HEADER_SIZE`.
+    pub(crate) unsafe fn initialize(region: NonNull<u8>, size: usize, slot: usize) -> Frame {
+        debug_assert!(size >= Self::HEADER_SIZE);
+        debug_assert!((region.as_ptr() as usize).is_multiple_of(Self::ALIGNMENT));
+
+        let start = region.as_ptr() as usize + Self::HEADER_SIZE;
+        let end = region.as_ptr() as usize + size;
+        let header = region.cast::<FrameHeader>();
+
+        header.as_ptr().write(FrameHeader { slot, start, cursor: Cell::new(start), end });
+
+        Frame(header)
+    }
+
+    /// Creates a frame from the pointer to its region.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `region` was passed to `initialize`, and is still in use.
+    pub(crate) unsafe fn from_region(region: NonNull<u8>) -> Frame { Frame(region.cast()) }
+
+    /// Returns the pointer to the region.
+    pub(crate) fn region(&self) -> NonNull<u8> { self.0.cast() }
+
+    /// Returns the slot of the region, in the registry.
+    pub(crate) fn slot(&self) -> usize { self.header().slot }
+
+    /// Allocates `layout` from the region, if it fits.
+    #[inline(always)]
+    pub(crate) fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let header = self.header();
+
+        let start = header.cursor.get().checked_add(layout.align() - 1)? & !(layout.align() - 1);
+        let end = start.checked_add(layout.size())?;
+
+        if end > header.end {
+            return None;
+        }
+
+        header.cursor.set(end);
+
+        NonNull::new(start as *mut u8)
+    }
+
+    /// Frees all the allocations of the region.
+    pub(crate) fn reset(&self) {
+        let header = self.header();
+
+        header.cursor.set(header.start);
+    }
+
+    fn header(&self) -> &FrameHeader {
+        //  Safety:
+        //  -   The header was initialized by `initialize`, and is only accessed by the owning thread.
+        unsafe { self.0.as_ref() }
+    }
+}
+
+/// Registry of the frame regions.
+pub(crate) struct FrameRegions {
+    //  Number of registered regions.
+    registered: AtomicUsize,
+    slots: [Slot; CAPACITY],
+}
+
+impl FrameRegions {
+    /// Creates an instance, with no region registered.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const SLOT: Slot = Slot::new();
+
+        Self { registered: AtomicUsize::new(0), slots: [SLOT; CAPACITY] }
+    }
+
+    /// Registers the `size` bytes located at `region`, returning its slot.
+    ///
+    /// Returns None if all slots are already registered.
+    #[cold]
+    pub(crate) fn register(&self, region: NonNull<u8>, size: usize) -> Option<usize> {
+        let start = region.as_ptr() as usize;
+
+        let slot = self.slots.iter()
+            .position(|slot| slot.start.compare_exchange(0, start, Ordering::Relaxed, Ordering::Relaxed).is_ok())?;
+
+        self.slots[slot].end.store(start + size, Ordering::Relaxed);
+        self.registered.fetch_add(1, Ordering::Relaxed);
+        ARMED.arm();
+
+        Some(slot)
+    }
+
+    /// Unregisters the region registered in `slot`.
+    #[cold]
+    pub(crate) fn unregister(&self, slot: usize) {
+        let slot = &self.slots[slot];
+
+        slot.end.store(0, Ordering::Relaxed);
+        slot.start.store(0, Ordering::Relaxed);
+
+        self.registered.fetch_sub(1, Ordering::Relaxed);
+        ARMED.disarm();
+    }
+
+    /// Returns whether `address` lies within a registered region.
+    #[inline(always)]
+    pub(crate) fn contains(&self, address: usize) -> bool {
+        self.registered.load(Ordering::Relaxed) != 0 && self.contains_impl(address)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn contains_impl(&self, address: usize) -> bool {
+        self.slots.iter().any(|slot| {
+            let start = slot.start.load(Ordering::Relaxed);
+
+            start != 0 && start <= address && address < slot.end.load(Ordering::Relaxed)
+        })
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  Maximum number of regions registered at any time, hence of threads in frame mode.
+const CAPACITY: usize = 64;
+
+//  Header of a region, accessed by the owning thread only.
+struct FrameHeader {
+    slot: usize,
+    start: usize,
+    cursor: Cell<usize>,
+    end: usize,
+}
+
+//  Slot of the registry, free if `start` is 0.
+struct Slot {
+    start: AtomicUsize,
+    end: AtomicUsize,
+}
+
+impl Slot {
+    const fn new() -> Self { Self { start: AtomicUsize::new(0), end: AtomicUsize::new(0) } }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[repr(align(64))]
+struct Region([u8; 256]);
+
+#[test]
+fn frame_allocate_reset() {
+    let mut region = Region([0; 256]);
+    let pointer = NonNull::from(&mut region.0).cast::<u8>();
+
+    let frame = unsafe { Frame::initialize(pointer, 256, 3) };
+    assert_eq!(3, frame.slot());
+    assert_eq!(pointer, frame.region());
+
+    let start = pointer.as_ptr() as usize + Frame::HEADER_SIZE;
+
+    let first = frame.allocate(Layout::from_size_align(3, 1).unwrap()).unwrap();
+    assert_eq!(start, first.as_ptr() as usize);
+
+    let second = frame.allocate(Layout::from_size_align(8, 8).unwrap()).unwrap();
+    assert_eq!((start + 3 + 7) & !7, second.as_ptr() as usize);
+
+    assert_eq!(None, frame.allocate(Layout::from_size_align(256, 1).unwrap()));
+
+    frame.reset();
+
+    let third = frame.allocate(Layout::from_size_align(256 - Frame::HEADER_SIZE, 1).unwrap()).unwrap();
+    assert_eq!(first, third);
+}
+
+#[test]
+fn frame_regions_register_unregister() {
+    let regions = FrameRegions::new();
+    let region = NonNull::new(0x1000 as *mut u8).unwrap();
+
+    assert!(!regions.contains(0x1000));
+
+    let slot = regions.register(region, 0x100).unwrap();
+
+    assert!(!regions.contains(0xFFF));
+    assert!(regions.contains(0x1000));
+    assert!(regions.contains(0x10FF));
+    assert!(!regions.contains(0x1100));
+
+    regions.unregister(slot);
+
+    assert!(!regions.contains(0x1000));
+
+    for _ in 0..CAPACITY {
+        regions.register(region, 0x100).unwrap();
+    }
+
+    assert_eq!(None, regions.register(region, 0x100));
+}
+
+} // mod tests
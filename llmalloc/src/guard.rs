@@ -0,0 +1,114 @@
+//! Guard Pages
+//!
+//! A linear overflow past the end of an allocation, or underflow before its start, silently corrupts whatever memory
+//! lies there. With guard pages, the platform instead flanks each `HugePage` it maps, and thus each Huge allocation,
+//! with an inaccessible, `PROT_NONE`, OS page on either side, so that such an overflow faults immediately.
+//!
+//! The guard pages are carved out of a reservation of the address space, which the mapping then replaces, hence they
+//! cost neither physical memory nor alignment. A guarded Huge allocation cannot be resized in place, and is moved
+//! instead, each mapping keeping its own guards.
+//!
+//! As a mapping is unmapped alongside its guards, the selection is latched by the first mapping, after which it can no
+//! longer change. The flag is resolved from the `LLMALLOC_GUARD_PAGES` environment variable, enabled if set to any
+//! value other than an empty string or `0`, unless set explicitly beforehand by `LLAllocator::set_guarded`.
+//!
+//! Guard pages are honored by the platform of Linux; the Normal and Large allocations, carved out of a `HugePage`, are
+//! only guarded by the guards of the `HugePage` as a whole.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::{Platform, LLPlatform};
+
+/// Name of the environment variable selecting guard pages, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_GUARD_PAGES\0";
+
+/// Process-wide selection of guard pages.
+pub(crate) struct Guards(AtomicU8);
+
+impl Guards {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self { Self(AtomicU8::new(UNRESOLVED)) }
+
+    /// Returns whether guard pages are enabled, resolving them from `platform` if not yet resolved.
+    pub(crate) fn is_enabled(&self, platform: &LLPlatform) -> bool { (self.resolve(platform) & ENABLED) != 0 }
+
+    /// Returns whether guard pages are enabled, resolving them from `platform` if not yet resolved, and latching the
+    /// selection.
+    ///
+    /// To be called by the platform on each mapping, and unmapping.
+    #[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+        feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc")))),
+        allow(dead_code))]
+    #[inline(always)]
+    pub(crate) fn latch(&self, platform: &LLPlatform) -> bool {
+        match self.0.load(Ordering::Relaxed) {
+            LATCHED_ENABLED => true,
+            LATCHED_DISABLED => false,
+            _ => self.latch_slow(platform),
+        }
+    }
+
+    /// Enables or disables guard pages, overriding the environment.
+    ///
+    /// Returns Err if the selection differs from the one already latched.
+    pub(crate) fn set(&self, enabled: bool) -> Result<(), ()> {
+        let selected = if enabled { ENABLED } else { DISABLED };
+
+        let mut current = self.0.load(Ordering::Relaxed);
+
+        loop {
+            if current & LATCHED != 0 {
+                return if current & !LATCHED == selected { Ok(()) } else { Err(()) };
+            }
+
+            match self.0.compare_exchange(current, selected, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn latch_slow(&self, platform: &LLPlatform) -> bool {
+        self.resolve(platform);
+
+        //  An explicit selection, racing with the latch, is latched instead.
+        (self.0.fetch_or(LATCHED, Ordering::Relaxed) | LATCHED) == LATCHED_ENABLED
+    }
+
+    //  Returns the state, resolving it if unresolved.
+    #[inline(always)]
+    fn resolve(&self, platform: &LLPlatform) -> u8 {
+        match self.0.load(Ordering::Relaxed) {
+            UNRESOLVED => self.resolve_slow(platform),
+            current => current,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve_slow(&self, platform: &LLPlatform) -> u8 {
+        let resolved = if platform.environment_flag(ENVIRONMENT_VARIABLE) { ENABLED } else { DISABLED };
+
+        //  Guard pages selected by `LLAllocator::set_guarded` while the environment is read stay selected.
+        match self.0.compare_exchange(UNRESOLVED, resolved, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => resolved,
+            Err(current) => current,
+        }
+    }
+}
+
+/// Selection of guard pages, shared by the allocator and the platforms.
+pub(crate) static GUARDS: Guards = Guards::new();
+
+//
+//  Implementation Details
+//
+
+const UNRESOLVED: u8 = 0;
+const DISABLED: u8 = 1;
+const ENABLED: u8 = 2;
+const LATCHED: u8 = 4;
+const LATCHED_DISABLED: u8 = DISABLED | LATCHED;
+const LATCHED_ENABLED: u8 = ENABLED | LATCHED;
@@ -0,0 +1,248 @@
+//! Hardened mode
+//!
+//! The hardened mode trades performance for the early detection of memory errors, such as use of uninitialized memory
+//! or use after free. Both the regular and hardened paths are compiled in, and the allocator branches on a flag
+//! resolved once, at process start, so that a single binary may run hardened in staging and fast in production.
+//!
+//! The flag is resolved from the `LLMALLOC_HARDENED` environment variable, enabled if set to any value other than an
+//! empty string or `0`, unless set explicitly beforehand by `LLAllocator::set_hardened`.
+//!
+//! In hardened mode:
+//!
+//! -   Newly allocated memory is poisoned with `ALLOCATED_POISON`.
+//! -   Deallocated memory is poisoned with `DEALLOCATED_POISON`, over the whole usable size of the block.
+//! -   Normal allocations are padded with a canary, occupying the last `CANARY_SIZE` bytes of the block, which is
+//!     checked on deallocation to detect buffer overflows.
+//! -   Deallocated Normal allocations are quarantined, rather than freed immediately, until evicted by `CAPACITY`
+//!     further deallocations; their poison is checked on eviction to detect writes after free.
+//!
+//! A violation, that is a clobbered canary or poison, is counted, and the block leaked rather than reused, see
+//! `LLAllocator::hardening_violations`.
+//!
+//! The canaries are only padded if the hardened mode is enabled from the start, before the first allocation, as a
+//! block allocated without one cannot be told apart from a block whose canary was clobbered.
+
+use core::{
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+};
+
+use crate::{armed::ARMED, Platform, LLPlatform};
+
+/// Byte with which newly allocated memory is poisoned, in hardened mode.
+pub const ALLOCATED_POISON: u8 = 0xAA;
+
+/// Byte with which deallocated memory is poisoned, in hardened mode.
+pub const DEALLOCATED_POISON: u8 = 0xDD;
+
+/// Name of the environment variable selecting the hardened mode, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_HARDENED\0";
+
+/// Process-wide selection of the hardened mode.
+pub(crate) struct Hardening {
+    mode: AtomicU8,
+    violations: AtomicU64,
+}
+
+impl Hardening {
+    /// Size of a canary, in bytes.
+    pub(crate) const CANARY_SIZE: usize = 8;
+
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self { Self { mode: AtomicU8::new(UNRESOLVED), violations: AtomicU64::new(0) } }
+
+    /// Returns whether the hardened mode is enabled, resolving it from `platform` if not yet resolved.
+    #[inline(always)]
+    pub(crate) fn is_enabled(&self, platform: &LLPlatform) -> bool { self.mode(platform) != DISABLED }
+
+    /// Returns whether the Normal allocations are padded with a canary, resolving the mode from `platform` if not yet
+    /// resolved.
+    #[inline(always)]
+    pub(crate) fn has_canaries(&self, platform: &LLPlatform) -> bool { self.mode(platform) == CANARIES }
+
+    /// Enables or disables the hardened mode, overriding the environment.
+    ///
+    /// The canaries are only enabled if the mode is not yet resolved, and remain so only as long as it is enabled.
+    pub(crate) fn set(&self, enabled: bool) {
+        //  The mode is armed unless disabled, including while unresolved, see `armed`.
+        if !enabled {
+            if self.mode.swap(DISABLED, Ordering::Relaxed) != DISABLED {
+                ARMED.disarm();
+            }
+
+            return;
+        }
+
+        //  An enabling racing with the resolution is not retroactive, hence leaves the canaries disabled.
+        if let Err(DISABLED) = self.mode.compare_exchange(UNRESOLVED, CANARIES, Ordering::Relaxed, Ordering::Relaxed) {
+            if self.mode.compare_exchange(DISABLED, ENABLED, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+                ARMED.arm();
+            }
+        }
+    }
+
+    /// Returns the number of violations detected, since the start of the process.
+    pub(crate) fn violations(&self) -> u64 { self.violations.load(Ordering::Relaxed) }
+
+    /// Records a violation.
+    #[cold]
+    pub(crate) fn record_violation(&self) { self.violations.fetch_add(1, Ordering::Relaxed); }
+
+    /// Poisons the `size` bytes located at `pointer` with `poison`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` is valid for writes of `size` bytes.
+    #[cold]
+    #[inline(never)]
+    pub(crate) unsafe fn poison(pointer: NonNull<u8>, size: usize, poison: u8) {
+        ptr::write_bytes(pointer.as_ptr(), poison, size);
+    }
+
+    /// Returns whether the `size` bytes located at `pointer` are all poisoned with `poison`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` is valid for reads of `size` bytes.
+    #[cold]
+    #[inline(never)]
+    pub(crate) unsafe fn is_poisoned(pointer: NonNull<u8>, size: usize, poison: u8) -> bool {
+        core::slice::from_raw_parts(pointer.as_ptr(), size).iter().all(|byte| *byte == poison)
+    }
+
+    /// Writes the canary of the block located at `pointer`, of `size` usable bytes, into its last bytes.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` is valid for writes of `size` bytes.
+    /// -   Assumes `size` is at least `CANARY_SIZE`.
+    #[cold]
+    #[inline(never)]
+    pub(crate) unsafe fn write_canary(pointer: NonNull<u8>, size: usize) {
+        let canary = pointer.as_ptr().add(size - Self::CANARY_SIZE) as *mut u64;
+
+        ptr::write_unaligned(canary, Self::canary_of(pointer));
+    }
+
+    /// Returns whether the canary of the block located at `pointer`, of `size` usable bytes, is intact.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` is valid for reads of `size` bytes.
+    /// -   Assumes `size` is at least `CANARY_SIZE`.
+    #[cold]
+    #[inline(never)]
+    pub(crate) unsafe fn check_canary(pointer: NonNull<u8>, size: usize) -> bool {
+        let canary = pointer.as_ptr().add(size - Self::CANARY_SIZE) as *const u64;
+
+        ptr::read_unaligned(canary) == Self::canary_of(pointer)
+    }
+
+    //  Returns the canary of the block located at `pointer`, differing from block to block, lest a block copied over
+    //  another carry a valid canary along.
+    fn canary_of(pointer: NonNull<u8>) -> u64 { CANARY ^ (pointer.as_ptr() as u64) }
+
+    #[inline(always)]
+    fn mode(&self, platform: &LLPlatform) -> u8 {
+        match self.mode.load(Ordering::Relaxed) {
+            UNRESOLVED => self.resolve(platform),
+            mode => mode,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self, platform: &LLPlatform) -> u8 {
+        let resolved = if platform.environment_flag(ENVIRONMENT_VARIABLE) { CANARIES } else { DISABLED };
+
+        //  A mode set by `LLAllocator::set_hardened` while the environment is read is kept over the environment's.
+        match self.mode.compare_exchange(UNRESOLVED, resolved, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => {
+                if resolved == DISABLED {
+                    ARMED.disarm();
+                }
+
+                resolved
+            },
+            Err(current) => current,
+        }
+    }
+}
+
+/// Quarantine of the deallocated Normal allocations, in hardened mode.
+///
+/// The quarantine is a ring of `CAPACITY` blocks, each deallocation evicting the oldest block in the ring, so that
+/// a block is only reused once `CAPACITY` further blocks were deallocated.
+pub(crate) struct Quarantine {
+    cursor: AtomicUsize,
+    blocks: [AtomicPtr<u8>; CAPACITY],
+}
+
+impl Quarantine {
+    /// Creates an instance, empty.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+
+        Self { cursor: AtomicUsize::new(0), blocks: [EMPTY; CAPACITY] }
+    }
+
+    /// Quarantines the block located at `pointer`, returning the block it evicts, if any.
+    pub(crate) fn push(&self, pointer: NonNull<u8>) -> Option<NonNull<u8>> {
+        let index = self.cursor.fetch_add(1, Ordering::Relaxed) % CAPACITY;
+
+        NonNull::new(self.blocks[index].swap(pointer.as_ptr(), Ordering::AcqRel))
+    }
+}
+
+//
+//  Implementation Details
+//
+
+const UNRESOLVED: u8 = 0;
+const DISABLED: u8 = 1;
+const ENABLED: u8 = 2;
+const CANARIES: u8 = 3;
+
+//  Base value of the canaries.
+const CANARY: u64 = 0xC4A7_A21E_5EA1_ED00;
+
+//  Number of blocks held in quarantine.
+const CAPACITY: usize = 1024;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn hardening_canary() {
+    let mut block = [0u64; 4];
+    let pointer = NonNull::new(block.as_mut_ptr() as *mut u8).unwrap();
+
+    unsafe { Hardening::write_canary(pointer, 32) };
+    assert!(unsafe { Hardening::check_canary(pointer, 32) });
+
+    block[3] ^= 1;
+    assert!(!unsafe { Hardening::check_canary(NonNull::new(block.as_mut_ptr() as *mut u8).unwrap(), 32) });
+}
+
+#[test]
+fn quarantine_push() {
+    static QUARANTINE: Quarantine = Quarantine::new();
+
+    let mut blocks = [0u8; 2];
+    let pointers: [NonNull<u8>; 2] = [NonNull::from(&mut blocks[0]), NonNull::from(&mut blocks[1])];
+
+    assert_eq!(None, QUARANTINE.push(pointers[0]));
+
+    for _ in 1..CAPACITY {
+        assert_eq!(None, QUARANTINE.push(pointers[1]));
+    }
+
+    //  The oldest block is evicted first.
+    assert_eq!(Some(pointers[0]), QUARANTINE.push(pointers[1]));
+    assert_eq!(Some(pointers[1]), QUARANTINE.push(pointers[1]));
+}
+
+} // mod tests
@@ -0,0 +1,106 @@
+//! Hot-plug
+//!
+//! The NUMA topology is discovered once, on first use. On virtual machines, memory may be hot-added as new nodes, and
+//! the cpuset of a process may be changed by its orchestrator, past which the topology captured at startup is stale:
+//! the distances to the new nodes are unknown, hence they are neither clustered nor tiered, and the nodes newly
+//! allowed are not bound to.
+//!
+//! The topology is re-scanned on demand, by `LLAllocator::refresh_topology`, or periodically, by the background thread,
+//! see `LLAllocator::set_topology_refresh`. The table of sockets holds a slot per node, up to 64, each socket being
+//! created on first use, hence the sockets of the new nodes are created as their threads first allocate; the threads
+//! already served by the socket of another node are moved over by rehoming, see `LLAllocator::set_rehoming`. The
+//! sockets of the nodes removed are kept, their memory remaining in use.
+//!
+//! The topology is re-scanned by the platform of Linux; the topology of the other platforms is fixed.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{Platform, LLPlatform};
+
+/// Process-wide selection of the periodic refresh of the NUMA topology.
+pub(crate) struct TopologyRefresh {
+    //  Period, in nanoseconds, or DISABLED.
+    period: AtomicU64,
+    //  Timestamp of the latest refresh, in nanoseconds.
+    latest: AtomicU64,
+}
+
+impl TopologyRefresh {
+    /// Creates an instance, disabled.
+    pub(crate) const fn new() -> Self { Self { period: AtomicU64::new(DISABLED), latest: AtomicU64::new(0) } }
+
+    /// Returns the period of the refresh, if enabled.
+    pub(crate) fn period(&self) -> Option<Duration> {
+        match self.period.load(Ordering::Relaxed) {
+            DISABLED => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Enables the refresh with the given period, or disables it.
+    pub(crate) fn set(&self, period: Option<Duration>) { self.period.store(Self::encode(period), Ordering::Relaxed); }
+
+    /// Returns whether a refresh is due, as per `platform`, electing the caller to run it if so.
+    ///
+    /// Of concurrent callers, a single one is elected to run the refresh.
+    pub(crate) fn is_due(&self, platform: &LLPlatform) -> bool {
+        let period = self.period.load(Ordering::Relaxed);
+
+        if period == DISABLED {
+            return false;
+        }
+
+        let now = platform.now();
+        let latest = self.latest.load(Ordering::Relaxed);
+
+        if now.saturating_sub(latest) < period {
+            return false;
+        }
+
+        self.latest.compare_exchange(latest, now, Ordering::Relaxed, Ordering::Relaxed).is_ok()
+    }
+
+    //  A period of 0 is encoded as the shortest period, 1 nanosecond, rather than as disabled.
+    fn encode(period: Option<Duration>) -> u64 {
+        match period {
+            Some(period) => (period.as_nanos().min(u128::from(u64::MAX)) as u64).max(1),
+            None => DISABLED,
+        }
+    }
+}
+
+/// Selection of the periodic refresh of the NUMA topology, shared by the allocator and its background thread.
+pub(crate) static TOPOLOGY_REFRESH: TopologyRefresh = TopologyRefresh::new();
+
+//
+//  Implementation Details
+//
+
+const DISABLED: u64 = 0;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn topology_refresh_period() {
+    let refresh = TopologyRefresh::new();
+
+    assert_eq!(None, refresh.period());
+
+    refresh.set(Some(Duration::from_secs(5)));
+    assert_eq!(Some(Duration::from_secs(5)), refresh.period());
+
+    //  A period of 0 is the shortest period, rather than disabled.
+    refresh.set(Some(Duration::ZERO));
+    assert_eq!(Some(Duration::from_nanos(1)), refresh.period());
+
+    refresh.set(None);
+    assert_eq!(None, refresh.period());
+}
+
+} // mod tests
@@ -0,0 +1,169 @@
+//! Idle
+//!
+//! The cache of a thread grows with its bursts of allocations, and is only flushed on demand, see
+//! `LLAllocator::flush_thread_cache`: an event-driven thread, bursting once in a while and parked in between, thus pins
+//! the memory of its peak burst for as long as it lives.
+//!
+//! With idle trimming, see `LLAllocator::set_idle_trim`, the cache of a thread is flushed once the thread has neither
+//! allocated nor deallocated for the idle threshold. A parked thread runs no code of the allocator, hence the check is
+//! run by the thread itself, through `LLAllocator::trim_idle_thread_cache`, which the runtime is to call as it parks
+//! the thread, and as it wakes it up on a timeout: the first call following any activity records it, and the first
+//! call past the threshold of inactivity flushes the cache, once per idle period.
+//!
+//! The activity of a thread is tracked from its statistics, so that its allocations and deallocations pay nothing for
+//! it. The threshold is measured by the clock of the platform, hence no cache is ever trimmed on platforms without one.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Process-wide selection of the trimming of the caches of idle threads.
+pub(crate) struct IdleTrim {
+    //  Threshold, in nanoseconds, or DISABLED.
+    threshold: AtomicU64,
+}
+
+impl IdleTrim {
+    /// Creates an instance, disabled.
+    pub(crate) const fn new() -> Self { Self { threshold: AtomicU64::new(DISABLED) } }
+
+    /// Returns the threshold of inactivity, if enabled.
+    pub(crate) fn threshold(&self) -> Option<Duration> { self.threshold_nanos().map(Duration::from_nanos) }
+
+    /// Returns the threshold of inactivity, in nanoseconds, if enabled.
+    pub(crate) fn threshold_nanos(&self) -> Option<u64> {
+        match self.threshold.load(Ordering::Relaxed) {
+            DISABLED => None,
+            nanos => Some(nanos),
+        }
+    }
+
+    /// Enables the trimming with the given threshold, or disables it.
+    pub(crate) fn set(&self, threshold: Option<Duration>) {
+        self.threshold.store(Self::encode(threshold), Ordering::Relaxed);
+    }
+
+    //  A threshold of 0 is encoded as the shortest threshold, 1 nanosecond, rather than as disabled.
+    fn encode(threshold: Option<Duration>) -> u64 {
+        match threshold {
+            Some(threshold) => (threshold.as_nanos().min(u128::from(u64::MAX)) as u64).max(1),
+            None => DISABLED,
+        }
+    }
+}
+
+/// Selection of the trimming of the caches of idle threads, shared by the allocator and its threads.
+pub(crate) static IDLE_TRIM: IdleTrim = IdleTrim::new();
+
+/// Idleness of a thread, packed in the opaque word of its thread-local instance.
+///
+/// The low 24 bits hold the number of operations of the thread, wrapping, as of its latest recorded activity, the next
+/// bit whether its cache was trimmed since, and the high 39 bits the timestamp of its latest recorded activity, in
+/// milliseconds. A value of 0 stands for no recorded activity.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct IdleCache(u64);
+
+impl IdleCache {
+    /// Creates an instance from its packed representation.
+    pub(crate) const fn from_raw(raw: u64) -> Self { Self(raw) }
+
+    /// Returns the packed representation of the instance.
+    pub(crate) const fn into_raw(self) -> u64 { self.0 }
+
+    /// Creates an instance recording the activity of a thread, having performed `operations`, as of `now`, in
+    /// nanoseconds.
+    pub(crate) fn fresh(operations: u64, now: u64) -> Self {
+        Self((Self::millis(now) << TIMESTAMP_SHIFT) | (operations & OPERATIONS_MASK))
+    }
+
+    /// Checks the activity of a thread, having performed `operations`, as of `now`, in nanoseconds.
+    ///
+    /// Returns whether the cache of the thread is to be trimmed, having been idle for `threshold` nanoseconds and not
+    /// trimmed since, along with the instance to record in its stead.
+    pub(crate) fn check(self, operations: u64, now: u64, threshold: u64) -> (bool, Self) {
+        if self.0 == 0 || (self.0 & OPERATIONS_MASK) != (operations & OPERATIONS_MASK) {
+            return (false, Self::fresh(operations, now));
+        }
+
+        if (self.0 & TRIMMED) != 0 {
+            return (false, self);
+        }
+
+        //  The timestamps wrap around, once every 17 years or so.
+        let idle = (Self::millis(now).wrapping_sub(self.0 >> TIMESTAMP_SHIFT) & TIMESTAMP_MASK) * NANOS_PER_MILLI;
+
+        if idle < threshold {
+            return (false, self);
+        }
+
+        (true, Self(self.0 | TRIMMED))
+    }
+
+    fn millis(now: u64) -> u64 { (now / NANOS_PER_MILLI) & TIMESTAMP_MASK }
+}
+
+//
+//  Implementation Details
+//
+
+const DISABLED: u64 = 0;
+
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+const OPERATIONS_MASK: u64 = (1 << 24) - 1;
+const TRIMMED: u64 = 1 << 24;
+const TIMESTAMP_SHIFT: u32 = 25;
+const TIMESTAMP_MASK: u64 = (1 << (64 - TIMESTAMP_SHIFT)) - 1;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn idle_trim_threshold() {
+    let trim = IdleTrim::new();
+
+    assert_eq!(None, trim.threshold());
+
+    trim.set(Some(Duration::from_secs(5)));
+    assert_eq!(Some(Duration::from_secs(5)), trim.threshold());
+
+    //  A threshold of 0 is the shortest threshold, rather than disabled.
+    trim.set(Some(Duration::ZERO));
+    assert_eq!(Some(1), trim.threshold_nanos());
+
+    trim.set(None);
+    assert_eq!(None, trim.threshold());
+}
+
+#[test]
+fn idle_cache_check() {
+    const SECOND: u64 = 1_000_000_000;
+
+    //  The first check records the activity.
+    let (trimmed, cache) = IdleCache::default().check(7, 10 * SECOND, SECOND);
+    assert!(!trimmed);
+    assert_eq!(IdleCache::fresh(7, 10 * SECOND), cache);
+
+    //  The thread is trimmed once idle for the threshold, and only once.
+    let (trimmed, cache) = cache.check(7, 10 * SECOND + SECOND / 2, SECOND);
+    assert!(!trimmed);
+
+    let (trimmed, cache) = cache.check(7, 11 * SECOND, SECOND);
+    assert!(trimmed);
+
+    let (trimmed, cache) = cache.check(7, 20 * SECOND, SECOND);
+    assert!(!trimmed);
+
+    //  Any activity is recorded anew, for the thread to be trimmed once idle anew.
+    let (trimmed, cache) = cache.check(9, 20 * SECOND, SECOND);
+    assert!(!trimmed);
+    assert_eq!(IdleCache::fresh(9, 20 * SECOND), cache);
+
+    let (trimmed, _) = cache.check(9, 21 * SECOND, SECOND);
+    assert!(trimmed);
+}
+
+} // mod tests
@@ -0,0 +1,206 @@
+//! Initialization
+//!
+//! The allocator initializes itself lazily, on first use, which may introduce latency spikes at inconvenient times.
+//!
+//! Applications sensitive to such spikes may instead front-load the initialization, stage by stage, and check how long
+//! each stage took.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Stage of the initialization.
+///
+/// Stages are listed in the order in which they are performed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InitStage {
+    /// Creation of the process-wide thread-local key.
+    Key,
+    /// Creation of the socket-local structures of the current NUMA node, which maps its first `HugePage`.
+    Socket,
+    /// Creation of the thread-local structures of the current thread.
+    Thread,
+}
+
+impl InitStage {
+    /// All stages, in order.
+    pub const ALL: [InitStage; 3] = [InitStage::Key, InitStage::Socket, InitStage::Thread];
+}
+
+/// Metrics of the initialization.
+///
+/// The metrics are recorded whether the initialization is performed explicitly or lazily, on first use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct InitMetrics {
+    /// Duration of the creation of the thread-local key, if created.
+    pub key_creation: Option<Duration>,
+    /// Duration of the creation of the first socket-local structures, including the mapping of the first `HugePage`,
+    /// if created.
+    pub first_mapping: Option<Duration>,
+    /// Number of threads warmed up.
+    ///
+    /// The warm-up of a thread excludes the creation of the thread-local key and of the socket-local structures, which
+    /// are accounted for separately.
+    pub thread_warm_ups: u64,
+    /// Total duration of the warm-up of all threads.
+    pub thread_warm_up_total: Duration,
+    /// Maximum duration of the warm-up of a single thread.
+    pub thread_warm_up_max: Duration,
+}
+
+impl InitMetrics {
+    /// Returns the average duration of the warm-up of a single thread, if any thread was warmed up.
+    pub fn thread_warm_up_average(&self) -> Option<Duration> {
+        if self.thread_warm_ups == 0 {
+            return None;
+        }
+
+        Some(Duration::from_nanos((self.thread_warm_up_total.as_nanos() / self.thread_warm_ups as u128) as u64))
+    }
+}
+
+/// Report of the latency-critical initialization, as performed by `LLAllocator::init_latency_critical`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LatencyCriticalReport {
+    /// Budget requested, in bytes.
+    pub budget: usize,
+    /// Memory reserved on the socket of the current thread, in bytes, as a whole number of `HugePage`.
+    pub reserved: usize,
+    /// Memory prefaulted and locked in RAM, in bytes, out of the memory reserved.
+    pub locked: usize,
+    /// Number of class sizes for which the current thread caches a `LargePage`.
+    pub warmed_classes: usize,
+    /// Number of class sizes of the Normal category.
+    pub total_classes: usize,
+    /// First stage of the initialization which failed, if any.
+    pub failed_stage: Option<InitStage>,
+    /// Metrics of the initialization, so far.
+    pub metrics: InitMetrics,
+}
+
+impl LatencyCriticalReport {
+    /// Returns whether the preparation is complete, that is whether allocations within the budget should not require
+    /// any further system call from the current thread.
+    ///
+    /// Huge allocations, and allocations beyond the budget, may still require system calls.
+    pub fn is_complete(&self) -> bool {
+        self.failed_stage.is_none() &&
+            self.reserved >= self.budget &&
+            self.locked == self.reserved &&
+            self.warmed_classes == self.total_classes
+    }
+}
+
+/// Atomic Metrics of the initialization, in nanoseconds.
+pub(crate) struct AtomicInitMetrics {
+    //  0 if not recorded, otherwise 1 + duration.
+    key_creation: AtomicU64,
+    //  0 if not recorded, otherwise 1 + duration.
+    first_mapping: AtomicU64,
+    thread_warm_ups: AtomicU64,
+    thread_warm_up_total: AtomicU64,
+    thread_warm_up_max: AtomicU64,
+}
+
+impl AtomicInitMetrics {
+    /// Creates an instance, with nothing recorded.
+    pub(crate) const fn new() -> Self {
+        Self {
+            key_creation: AtomicU64::new(0),
+            first_mapping: AtomicU64::new(0),
+            thread_warm_ups: AtomicU64::new(0),
+            thread_warm_up_total: AtomicU64::new(0),
+            thread_warm_up_max: AtomicU64::new(0),
+        }
+    }
+
+    /// Records the creation of the thread-local key.
+    pub(crate) fn record_key_creation(&self, nanos: u64) { Self::record_once(&self.key_creation, nanos); }
+
+    /// Records the creation of socket-local structures; only the first is retained.
+    pub(crate) fn record_mapping(&self, nanos: u64) { Self::record_once(&self.first_mapping, nanos); }
+
+    /// Records the warm-up of a thread.
+    pub(crate) fn record_thread_warm_up(&self, nanos: u64) {
+        self.thread_warm_ups.fetch_add(1, Ordering::Relaxed);
+        self.thread_warm_up_total.fetch_add(nanos, Ordering::Relaxed);
+        self.thread_warm_up_max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns a snapshot of the metrics.
+    pub(crate) fn snapshot(&self) -> InitMetrics {
+        let once = |value: &AtomicU64| {
+            let value = value.load(Ordering::Relaxed);
+            if value == 0 { None } else { Some(Duration::from_nanos(value - 1)) }
+        };
+
+        InitMetrics {
+            key_creation: once(&self.key_creation),
+            first_mapping: once(&self.first_mapping),
+            thread_warm_ups: self.thread_warm_ups.load(Ordering::Relaxed),
+            thread_warm_up_total: Duration::from_nanos(self.thread_warm_up_total.load(Ordering::Relaxed)),
+            thread_warm_up_max: Duration::from_nanos(self.thread_warm_up_max.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn record_once(value: &AtomicU64, nanos: u64) {
+        let _ = value.compare_exchange(0, nanos.saturating_add(1), Ordering::Relaxed, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn init_metrics_thread_warm_up_average() {
+    let metrics = InitMetrics::default();
+    assert_eq!(None, metrics.thread_warm_up_average());
+
+    let total = Duration::from_micros(30);
+    let metrics = InitMetrics { thread_warm_ups: 3, thread_warm_up_total: total, ..metrics };
+    assert_eq!(Some(Duration::from_micros(10)), metrics.thread_warm_up_average());
+}
+
+#[test]
+fn latency_critical_report_is_complete() {
+    let report = LatencyCriticalReport {
+        budget: 3,
+        reserved: 4,
+        locked: 4,
+        warmed_classes: 2,
+        total_classes: 2,
+        ..LatencyCriticalReport::default()
+    };
+    assert!(report.is_complete());
+
+    assert!(!LatencyCriticalReport { budget: 5, ..report }.is_complete());
+    assert!(!LatencyCriticalReport { locked: 3, ..report }.is_complete());
+    assert!(!LatencyCriticalReport { warmed_classes: 1, ..report }.is_complete());
+    assert!(!LatencyCriticalReport { failed_stage: Some(InitStage::Thread), ..report }.is_complete());
+}
+
+#[test]
+fn atomic_init_metrics_record() {
+    let metrics = AtomicInitMetrics::new();
+    assert_eq!(InitMetrics::default(), metrics.snapshot());
+
+    metrics.record_key_creation(0);
+    metrics.record_key_creation(5);
+    metrics.record_mapping(7);
+    metrics.record_mapping(3);
+    metrics.record_thread_warm_up(2);
+    metrics.record_thread_warm_up(4);
+
+    let snapshot = metrics.snapshot();
+
+    assert_eq!(Some(Duration::from_nanos(0)), snapshot.key_creation);
+    assert_eq!(Some(Duration::from_nanos(7)), snapshot.first_mapping);
+    assert_eq!(2, snapshot.thread_warm_ups);
+    assert_eq!(Duration::from_nanos(6), snapshot.thread_warm_up_total);
+    assert_eq!(Duration::from_nanos(4), snapshot.thread_warm_up_max);
+}
+
+} // mod tests
@@ -1,5 +1,9 @@
 #![no_std]
 #![deny(missing_docs)]
+#![cfg_attr(any(feature = "no-libc", feature = "initial-exec"), feature(thread_local))]
+#![cfg_attr(all(feature = "panic-free", not(test)), deny(
+    clippy::expect_used, clippy::panic, clippy::todo, clippy::unimplemented, clippy::unreachable, clippy::unwrap_used
+))]
 
 //! A Low-Latency Memory Allocator library.
 //!
@@ -10,10 +14,123 @@
 //! This low-latency memory allocator is not suitable for all applications.
 //!
 //! See the README.md file for the limitations and trade-offs made.
+//!
+//! #   Panics
+//!
+//! Allocation and deallocation never panic: platform failures result in a failed allocation, that is a null pointer
+//! returned to the caller, and memory which cannot be returned to the OS is leaked, unless the process is to be aborted
+//! instead, see `LLAllocator::set_unmap_failure_policy`. The `panic-free` feature denies, outside of tests, the
+//! constructs which may panic.
+//!
+//! #   Custom Platforms
+//!
+//! With the `custom-platform` feature, llmalloc relies on no OS service of its own: the embedder supplies them
+//! instead, by implementing:
+//!
+//! -   `CorePlatform`, which maps and unmaps the Huge Pages, for example by carving them out of a memory region
+//!     pre-registered for RDMA, rather than mapping anonymous memory.
+//! -   `Platform`, which supplies the remaining services: NUMA nodes, time, protection, metrics, etc... Any service
+//!     the embedder cannot supply is reported as failing, or absent, and llmalloc degrades accordingly.
+//! -   `ThreadLocal<u8>`, which stores the pointer to the thread-local state of each thread.
+//!
+//! The implementations are then registered, once and for all, with `LLAllocator::with_platform`, prior to the first
+//! allocation.
 
 mod allocator;
+mod arena;
+mod armed;
+mod background;
+mod bounds;
+mod capabilities;
+mod clustering;
+mod code;
+mod collapse;
+mod compaction;
+mod decay;
+mod decommit;
+mod epochs;
+mod error;
+mod fallback;
+mod fork;
+mod frame;
+mod guard;
+mod hardened;
+mod hotplug;
+mod idle;
+mod init;
+mod locality;
+mod mapping;
+mod node;
+mod physical;
+mod pinning;
 mod platform;
+mod prefault;
+mod print;
+mod profile;
+mod reclamation;
+mod registration;
+mod rehoming;
+mod report;
+mod reservation;
+mod retry;
+mod shared;
+mod stack;
+mod tagging;
+mod tiering;
+mod toggle;
+mod unmapping;
+mod watermark;
 
-pub use allocator::LLAllocator;
+pub use allocator::{
+    flush_thread_cache, trim_idle_thread_cache, FlushGuard, ForbidAllocationGuard, LLAllocator, ReclamationGuard,
+};
+pub use arena::Arena;
+pub use bounds::AddressRange;
+pub use capabilities::{
+    Capabilities, Downgrade, HostCapabilities, HugeTlbPool, HugeTlbPools, PrivilegeError, TransparentHugePagesMode,
+};
+pub use code::{CodeMapping, CodeRegion};
+pub use collapse::{Collapse, CollapseReport};
+pub use compaction::{CompactionReport, Relocatable};
+pub use epochs::SurvivingAllocation;
+pub use error::AllocationError;
+pub use fallback::{AtomicFallbackMetrics, FallbackMetrics};
+pub use hardened::{ALLOCATED_POISON, DEALLOCATED_POISON};
+pub use init::{InitMetrics, InitStage, LatencyCriticalReport};
+pub use locality::NodeStatistics;
+pub use mapping::{MapOptions, Merging, NodeBinding};
+pub use node::{node_box, NodeBox, NodeVec};
+pub use physical::{PhysicalBuffer, PhysicalSegment};
+pub use pinning::PinningReport;
+pub use platform::{Configuration, NumaNodeIndex, Platform, ThreadLocal};
+#[cfg(any(feature = "custom-platform", feature = "test-platform"))]
+pub use platform::LLConfiguration;
+#[cfg(feature = "test-platform")]
+pub use platform::{MockCall, MockPlatform};
+pub use llmalloc_core::{CategoryStatistics, Criticality, Platform as CorePlatform, SizeHistogram, Statistics};
+pub use profile::ThreadProfile;
+pub use report::{HugePageReport, ResidencyReport};
+pub use reservation::Reservation;
+pub use retry::RetryPolicy;
+pub use shared::{SharedBacking, SharedHeap};
+pub use stack::ThreadStack;
+pub use tagging::{Tag, TagCallback};
+pub use unmapping::{UnmapFailure, UnmapFailureCallback, UnmapFailurePolicy};
+pub use watermark::{Crossing, WatermarkCallback, WatermarkEvent, WatermarkId};
 
-use platform::{LLConfiguration, Platform, LLPlatform, ThreadLocal, LLThreadLocal};
+use compaction::CompactionPlan;
+use epochs::EpochTracker;
+use fallback::Fallback;
+use frame::{Frame, FrameRegions};
+use hardened::{Hardening, Quarantine};
+use init::AtomicInitMetrics;
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform", feature = "no-libc"))))]
+use physical::SegmentBuilder;
+use reclamation::Reclamation;
+use retry::Retry;
+use tagging::Tags;
+use watermark::Watermarks;
+#[cfg(not(any(feature = "custom-platform", feature = "test-platform")))]
+use platform::LLConfiguration;
+use platform::{LLPlatform, LLThreadLocal};
@@ -0,0 +1,142 @@
+//! Locality
+//!
+//! The sockets serve the threads of their NUMA node from the memory of their node, yet whether the traffic actually
+//! stays local depends on the threads: memory allocated on a node on behalf of another, through
+//! `LLAllocator::allocate_on_node`, or deallocated by a thread of another node, crosses the interconnect. The
+//! statistics of each node tell how much memory its socket holds, and how much of its traffic crosses nodes.
+//!
+//! Looking up the node a thread runs on takes a system call, hence each thread caches its node, looking it up anew
+//! every `REFRESH_PERIOD` lookups only: a thread rescheduled onto another node is thus noticed after a while, rather
+//! than at once, which is immaterial as the node is merely a hint, the thread being free to migrate at any time.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::NumaNodeIndex;
+
+/// Number of lookups of the node of a thread served by its cache, between two lookups from the platform.
+pub(crate) const REFRESH_PERIOD: u32 = 256;
+
+/// Statistics of the socket of a NUMA node, see `LLAllocator::node_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NodeStatistics {
+    /// The NUMA node of the socket.
+    pub node: u32,
+    /// The number of `HugePage`s mapped by the socket, to host its Normal and Large allocations.
+    pub huge_pages: usize,
+    /// The number of bytes in use by the live allocations of the socket, of all categories.
+    pub in_use_bytes: usize,
+    /// The number of bytes of the `HugePage`s of the socket not in use by Normal or Large allocations, including those
+    /// cached by its threads, and the metadata of the socket.
+    pub cached_bytes: usize,
+    /// The number of allocations of the socket made on behalf of the threads of other nodes, by `allocate_on_node`, or
+    /// as the sockets of their own nodes were exhausted.
+    pub foreign_allocations: usize,
+    /// The number of Normal allocations of the socket deallocated by the threads of other sockets.
+    pub remote_deallocations: usize,
+}
+
+impl NodeStatistics {
+    /// Returns the number of operations of the socket crossing nodes, whether allocations or deallocations.
+    pub fn cross_node_operations(&self) -> usize { self.foreign_allocations.wrapping_add(self.remote_deallocations) }
+}
+
+/// Cache of the NUMA node of a thread, packed in the opaque word of its thread-local instance.
+///
+/// The low half holds the node plus one, 0 standing for an empty cache, and the high half the number of lookups left
+/// until the node is looked up anew.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct NodeCache(u64);
+
+impl NodeCache {
+    /// Creates an instance from its packed representation.
+    pub(crate) const fn from_raw(raw: u64) -> Self { Self(raw) }
+
+    /// Returns the packed representation of the instance.
+    pub(crate) const fn into_raw(self) -> u64 { self.0 }
+
+    /// Creates an instance caching `node`, just looked up.
+    pub(crate) fn fresh(node: NumaNodeIndex) -> Self {
+        Self(((REFRESH_PERIOD as u64) << 32) | (node.value() as u64).wrapping_add(1))
+    }
+
+    /// Returns the cached node, along with the instance having served the lookup, or None if the node is to be looked
+    /// up anew.
+    pub(crate) fn lookup(self) -> Option<(NumaNodeIndex, Self)> {
+        let node = (self.0 as u32).checked_sub(1)?;
+        let remaining = (self.0 >> 32) as u32;
+
+        if remaining == 0 {
+            return None;
+        }
+
+        Some((NumaNodeIndex::new(node), Self(self.0 - (1 << 32))))
+    }
+}
+
+/// Process-wide counters of the allocations made on behalf of the threads of other nodes, indexed by node.
+pub(crate) struct ForeignAllocations([AtomicUsize; 64]);
+
+impl ForeignAllocations {
+    /// Creates an instance, with all counters zeroed.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+        Self([ZERO; 64])
+    }
+
+    /// Returns the number of allocations made on `node` on behalf of the threads of another node.
+    pub(crate) fn get(&self, node: usize) -> usize { self.0.get(node).map_or(0, |count| count.load(Ordering::Relaxed)) }
+
+    /// Records an allocation made on `node` on behalf of a thread of another node.
+    pub(crate) fn record(&self, node: usize) {
+        if let Some(count) = self.0.get(node) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Counters of the allocations made on behalf of the threads of other nodes.
+pub(crate) static FOREIGN_ALLOCATIONS: ForeignAllocations = ForeignAllocations::new();
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn foreign_allocations_record() {
+    let foreign = ForeignAllocations::new();
+
+    foreign.record(1);
+    foreign.record(1);
+    foreign.record(64);
+
+    assert_eq!((0, 2, 0), (foreign.get(0), foreign.get(1), foreign.get(64)));
+}
+
+#[test]
+fn node_cache_lookup() {
+    assert_eq!(None, NodeCache::default().lookup());
+
+    let mut cache = NodeCache::fresh(NumaNodeIndex::new(3));
+
+    for _ in 0..REFRESH_PERIOD {
+        let (node, next) = cache.lookup().expect("Cached");
+
+        assert_eq!(NumaNodeIndex::new(3), node);
+        cache = NodeCache::from_raw(next.into_raw());
+    }
+
+    //  Once stale, the node is to be looked up anew.
+    assert_eq!(None, cache.lookup());
+}
+
+#[test]
+fn node_statistics_cross_node_operations() {
+    let statistics = NodeStatistics { foreign_allocations: 2, remote_deallocations: 3, ..NodeStatistics::default() };
+
+    assert_eq!(5, statistics.cross_node_operations());
+}
+
+} // mod tests
@@ -0,0 +1,281 @@
+//! Map Options
+//!
+//! The platform maps the `HugePage`s of the heap with a fixed set of flags, which suits most processes. Some need more:
+//! a process overcommitting on purpose may forgo the accounting of its heap, with `MAP_NORESERVE`; a latency critical
+//! process may lock its heap in memory, with `MAP_LOCKED`; a process bound by memory bandwidth rather than latency may
+//! interleave the pages of its heap across all NUMA nodes, with `mbind(MPOL_INTERLEAVE)`, whereas a process bound by
+//! latency may bind them to their node, with `mbind(MPOL_BIND)` or `mbind(MPOL_PREFERRED)`; a process coordinating its
+//! address space may hint where its heap is to be mapped; and a process may opt its heap in or out of Kernel Same-page
+//! Merging, with `MADV_MERGEABLE` or `MADV_UNMERGEABLE`.
+//!
+//! The socket of a NUMA node merely serves the threads of the node, the kernel placing the pages of its memory where
+//! it sees fit: by default, on the node of the thread first touching them, falling back to the nearest nodes with free
+//! memory. Binding pins the pages of each mapping to the node of the thread mapping it: the node of the socket a
+//! `HugePage` is mapped for, bar those mapped by `LLAllocator::allocate_on_node` on behalf of another node, and the
+//! node of the thread requesting a Huge allocation.
+//!
+//! The placement is selected by `NodeBinding`, from the loosest to the strictest:
+//!
+//! -   `Unbound`, by default, places each page on the node first touching it, falling back to other nodes.
+//! -   `Preferred` places each page on the node of the mapping, falling back to other nodes, whichever thread first
+//!     touches it.
+//! -   `Bind` places each page on the node of the mapping, or the nodes clustered with it, and never on any other. A
+//!     mapping whose binding cannot be applied, as its node has no memory or lies outside the cpuset of the process,
+//!     fails, and so does the allocation requiring it; a fault which the node cannot serve later on raises `SIGBUS`,
+//!     or invokes the OOM killer. A machine with a single node honors the binding trivially.
+//!
+//! On machines with far memory, such as CXL.mem expanders, the unbound and bound placements, and the interleaving, are
+//! moreover restricted to the nodes of near memory, unless spilling onto far memory is enabled, see
+//! `LLAllocator::set_far_memory`.
+//!
+//! A strict binding suits capacity planning, surfacing the exhaustion of a node rather than silently spilling onto its
+//! neighbours; a preferred placement suits the processes favouring availability over locality.
+//!
+//! Same-page merging deduplicates identical pages across processes, which suits fleets of virtual machines
+//! overcommitting memory, yet its scanning of a hot heap, and the copies on write of the pages merged, introduce
+//! jitter. Opting out matters notably when merging is enabled process-wide, as by `prctl(PR_SET_MEMORY_MERGE)`.
+//!
+//! The options are selected by `LLAllocator::set_map_options`, and apply to the mappings which follow, the mappings
+//! already made being left untouched. An address hint is passed to each mapping, yet is only honored by the kernel if
+//! the range at the hint is free, hence mostly steers the first mappings; it is best aligned on the Huge Page size.
+//!
+//! The options are honored by the platform of Linux, bar the memory committed out of a reservation, which is mapped as
+//! a whole ahead of time, see `LLAllocator::set_reservation`; the interleaving of the pages is moreover only honored
+//! when NUMA is available, as is the preferred placement, and the merging when the kernel supports it; a strict
+//! binding on a machine with several nodes fails the mappings when NUMA is not available. Interleaving takes
+//! precedence over binding.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Options of the mappings of the heap, see `LLAllocator::set_map_options`.
+///
+/// The options are built from `MapOptions::new`, which selects none, as by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MapOptions {
+    no_reserve: bool,
+    locked: bool,
+    interleaved: bool,
+    binding: NodeBinding,
+    merging: Merging,
+    address_hint: usize,
+}
+
+/// Advice on the Kernel Same-page Merging of the mappings of the heap, see `MapOptions::with_merging`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Merging {
+    /// No advice, the mappings being merged as the kernel sees fit, as by default.
+    #[default]
+    Unadvised,
+    /// The mappings are advised to be merged, with `MADV_MERGEABLE`.
+    Mergeable,
+    /// The mappings are advised not to be merged, with `MADV_UNMERGEABLE`.
+    Unmergeable,
+}
+
+/// Binding of the pages of the mappings of the heap to their NUMA node, see `MapOptions::with_binding`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NodeBinding {
+    /// No binding, the pages being placed on the node first touching them, falling back to other nodes, as by default.
+    #[default]
+    Unbound,
+    /// The pages are strictly bound to the node, with `MPOL_BIND`, the mapping failing if the binding cannot be
+    /// applied, and a fault which cannot be served by the node failing.
+    Bind,
+    /// The pages are preferably placed on the node, with `MPOL_PREFERRED`, falling back to other nodes otherwise.
+    Preferred,
+}
+
+impl MapOptions {
+    /// Creates an instance, selecting none of the options.
+    pub const fn new() -> Self {
+        Self {
+            no_reserve: false,
+            locked: false,
+            interleaved: false,
+            binding: NodeBinding::Unbound,
+            merging: Merging::Unadvised,
+            address_hint: 0,
+        }
+    }
+
+    /// Returns a copy of the instance, mapping with `MAP_NORESERVE` if `no_reserve`.
+    ///
+    /// The memory mapped is then not accounted for by the kernel, as per `vm.overcommit_memory`, so that a process may
+    /// reserve more memory than is available; touching memory which cannot be provided then raises `SIGSEGV`.
+    pub const fn with_no_reserve(self, no_reserve: bool) -> Self { Self { no_reserve, ..self } }
+
+    /// Returns a copy of the instance, mapping with `MAP_LOCKED` if `locked`.
+    ///
+    /// The memory mapped is then locked in memory, as by `mlock`, and faulted in as it is mapped; a mapping exceeding
+    /// `RLIMIT_MEMLOCK` fails, for an unprivileged process.
+    pub const fn with_locked(self, locked: bool) -> Self { Self { locked, ..self } }
+
+    /// Returns a copy of the instance, interleaving the pages of each mapping across all NUMA nodes if `interleaved`.
+    ///
+    /// Interleaving trades the locality of the memory of each socket for the aggregate bandwidth of all nodes.
+    pub const fn with_interleaved(self, interleaved: bool) -> Self { Self { interleaved, ..self } }
+
+    /// Returns a copy of the instance, binding the pages of each mapping to its NUMA node as per `binding`.
+    ///
+    /// With `NodeBinding::Bind`, a mapping whose binding cannot be applied fails, and a fault which cannot be served by
+    /// the node raises `SIGBUS`, or invokes the OOM killer.
+    pub const fn with_binding(self, binding: NodeBinding) -> Self { Self { binding, ..self } }
+
+    /// Returns a copy of the instance, advising each mapping on its same-page merging as per `merging`.
+    pub const fn with_merging(self, merging: Merging) -> Self { Self { merging, ..self } }
+
+    /// Returns a copy of the instance, hinting each mapping at `address_hint`, or at none if 0.
+    ///
+    /// The hint is rounded down to a multiple of 4 KB.
+    pub const fn with_address_hint(self, address_hint: usize) -> Self {
+        Self { address_hint: address_hint & !(HINT_ALIGNMENT - 1), ..self }
+    }
+
+    /// Returns whether the memory is mapped with `MAP_NORESERVE`.
+    pub const fn no_reserve(&self) -> bool { self.no_reserve }
+
+    /// Returns whether the memory is mapped with `MAP_LOCKED`.
+    pub const fn locked(&self) -> bool { self.locked }
+
+    /// Returns whether the pages are interleaved across all NUMA nodes.
+    pub const fn interleaved(&self) -> bool { self.interleaved }
+
+    /// Returns the binding of the pages to their NUMA node.
+    pub const fn binding(&self) -> NodeBinding { self.binding }
+
+    /// Returns the advice on same-page merging.
+    pub const fn merging(&self) -> Merging { self.merging }
+
+    /// Returns the address hint, if any.
+    pub const fn address_hint(&self) -> Option<usize> {
+        if self.address_hint == 0 { None } else { Some(self.address_hint) }
+    }
+}
+
+/// Process-wide selection of the map options.
+pub(crate) struct Mapping(AtomicUsize);
+
+impl Mapping {
+    /// Creates an instance, selecting none of the options.
+    pub(crate) const fn new() -> Self { Self(AtomicUsize::new(0)) }
+
+    /// Returns the options.
+    pub(crate) fn get(&self) -> MapOptions { decode(self.0.load(Ordering::Relaxed)) }
+
+    /// Selects the options.
+    pub(crate) fn set(&self, options: MapOptions) { self.0.store(encode(options), Ordering::Relaxed); }
+}
+
+/// Selection of the map options, shared by the allocator and the platforms.
+pub(crate) static MAP_OPTIONS: Mapping = Mapping::new();
+
+//
+//  Implementation Details
+//
+
+//  The options are encoded as the address hint, a multiple of HINT_ALIGNMENT, or'ed with their flags.
+const HINT_ALIGNMENT: usize = 4096;
+
+const NO_RESERVE: usize = 1;
+const LOCKED: usize = 2;
+const INTERLEAVED: usize = 4;
+const MERGEABLE: usize = 8;
+const UNMERGEABLE: usize = 16;
+const BIND: usize = 32;
+const PREFERRED: usize = 64;
+
+fn encode(options: MapOptions) -> usize {
+    let flag = |selected: bool, flag: usize| if selected { flag } else { 0 };
+
+    options.address_hint
+        | flag(options.no_reserve, NO_RESERVE)
+        | flag(options.locked, LOCKED)
+        | flag(options.interleaved, INTERLEAVED)
+        | flag(options.binding == NodeBinding::Bind, BIND)
+        | flag(options.binding == NodeBinding::Preferred, PREFERRED)
+        | flag(options.merging == Merging::Mergeable, MERGEABLE)
+        | flag(options.merging == Merging::Unmergeable, UNMERGEABLE)
+}
+
+fn decode(state: usize) -> MapOptions {
+    MapOptions {
+        no_reserve: state & NO_RESERVE != 0,
+        locked: state & LOCKED != 0,
+        interleaved: state & INTERLEAVED != 0,
+        binding: match state & (BIND | PREFERRED) {
+            BIND => NodeBinding::Bind,
+            PREFERRED => NodeBinding::Preferred,
+            _ => NodeBinding::Unbound,
+        },
+        merging: match state & (MERGEABLE | UNMERGEABLE) {
+            MERGEABLE => Merging::Mergeable,
+            UNMERGEABLE => Merging::Unmergeable,
+            _ => Merging::Unadvised,
+        },
+        address_hint: state & !(HINT_ALIGNMENT - 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn map_options_builder() {
+    let options = MapOptions::new();
+
+    assert_eq!(MapOptions::default(), options);
+    assert_eq!((false, false, false, None),
+        (options.no_reserve(), options.locked(), options.interleaved(), options.address_hint()));
+
+    let options = options.with_no_reserve(true).with_locked(true).with_interleaved(true).with_address_hint(0x4000_1234);
+
+    assert_eq!((true, true, true, Some(0x4000_1000)),
+        (options.no_reserve(), options.locked(), options.interleaved(), options.address_hint()));
+
+    let options = options.with_locked(false).with_address_hint(0);
+
+    assert_eq!((true, false, true, None),
+        (options.no_reserve(), options.locked(), options.interleaved(), options.address_hint()));
+
+    assert_eq!(Merging::Unadvised, options.merging());
+    assert_eq!(Merging::Unmergeable, options.with_merging(Merging::Unmergeable).merging());
+
+    assert_eq!(NodeBinding::Unbound, options.binding());
+    assert_eq!(NodeBinding::Preferred, options.with_binding(NodeBinding::Preferred).binding());
+}
+
+#[test]
+fn mapping_set() {
+    let mapping = Mapping::new();
+
+    assert_eq!(MapOptions::new(), mapping.get());
+
+    let options = MapOptions::new().with_no_reserve(true).with_address_hint(0x7000_0000);
+
+    mapping.set(options);
+    assert_eq!(options, mapping.get());
+
+    let options = MapOptions::new().with_locked(true).with_interleaved(true).with_merging(Merging::Mergeable);
+
+    mapping.set(options);
+    assert_eq!(options, mapping.get());
+
+    let options = options.with_merging(Merging::Unmergeable);
+
+    mapping.set(options);
+    assert_eq!(options, mapping.get());
+
+    let options = options.with_binding(NodeBinding::Bind).with_address_hint(0x7000_0000);
+
+    mapping.set(options);
+    assert_eq!(options, mapping.get());
+
+    let options = options.with_binding(NodeBinding::Preferred);
+
+    mapping.set(options);
+    assert_eq!(options, mapping.get());
+}
+
+} // mod tests
@@ -0,0 +1,283 @@
+//! NUMA Node Containers
+//!
+//! The standard containers can only be parameterized by an allocator through the unstable `allocator_api`, hence
+//! llmalloc provides thin containers of its own, whose memory is allocated from the heap of a chosen NUMA node, as by
+//! `LLAllocator::allocate_on_node`:
+//!
+//! -   `NodeBox`, a single value, as created by `node_box`.
+//! -   `NodeVec`, a growable array, which remains on its node as it grows.
+//!
+//! The containers report allocation failures to their caller, rather than panicking, and may be dropped from any
+//! thread, as any other allocation.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+
+use llmalloc_core::Layout;
+
+use crate::{AllocationError, LLAllocator, NumaNodeIndex};
+
+/// Allocates `value` on the heap of NUMA `node`.
+///
+/// Returns an error, having dropped `value`, if the allocation fails.
+pub fn node_box<T>(node: NumaNodeIndex, value: T) -> Result<NodeBox<T>, AllocationError> { NodeBox::new(node, value) }
+
+/// A value allocated on the heap of a NUMA node.
+pub struct NodeBox<T> {
+    pointer: NonNull<T>,
+    node: NumaNodeIndex,
+    _marker: PhantomData<T>,
+}
+
+//  Safety:
+//  -   The box owns its value, as `Box` does.
+unsafe impl<T: Send> Send for NodeBox<T> {}
+unsafe impl<T: Sync> Sync for NodeBox<T> {}
+
+impl<T> NodeBox<T> {
+    /// Allocates `value` on the heap of NUMA `node`.
+    ///
+    /// Returns an error, having dropped `value`, if the allocation fails.
+    pub fn new(node: NumaNodeIndex, value: T) -> Result<Self, AllocationError> {
+        let pointer = allocate::<T>(node, Layout::new::<T>())?;
+
+        //  Safety:
+        //  -   `pointer` is valid for writes of a `T`, as it was just allocated.
+        unsafe { ptr::write(pointer.as_ptr(), value) };
+
+        Ok(Self { pointer, node, _marker: PhantomData })
+    }
+
+    /// Returns the NUMA node `this` was allocated on, as requested.
+    ///
+    /// An associated function, rather than a method, so as not to shadow the methods of `T`.
+    pub fn node(this: &Self) -> NumaNodeIndex { this.node }
+}
+
+impl<T> Deref for NodeBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        //  Safety:
+        //  -   `self.pointer` points to a valid `T`, owned by `self`.
+        unsafe { self.pointer.as_ref() }
+    }
+}
+
+impl<T> DerefMut for NodeBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        //  Safety:
+        //  -   `self.pointer` points to a valid `T`, exclusively owned by `self`.
+        unsafe { self.pointer.as_mut() }
+    }
+}
+
+impl<T> Drop for NodeBox<T> {
+    fn drop(&mut self) {
+        //  Safety:
+        //  -   `self.pointer` points to a valid `T`, owned by `self`, and no longer in use.
+        unsafe {
+            ptr::drop_in_place(self.pointer.as_ptr());
+            deallocate(self.pointer, mem::size_of::<T>());
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for NodeBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { fmt::Debug::fmt(&**self, f) }
+}
+
+/// A growable array allocated on the heap of a NUMA node.
+pub struct NodeVec<T> {
+    pointer: NonNull<T>,
+    capacity: usize,
+    length: usize,
+    node: NumaNodeIndex,
+    _marker: PhantomData<T>,
+}
+
+//  Safety:
+//  -   The array owns its elements, as `Vec` does.
+unsafe impl<T: Send> Send for NodeVec<T> {}
+unsafe impl<T: Sync> Sync for NodeVec<T> {}
+
+impl<T> NodeVec<T> {
+    /// Creates an empty array, for NUMA `node`.
+    ///
+    /// No memory is allocated until the first element is pushed, hence `node` is only validated then.
+    pub const fn new(node: NumaNodeIndex) -> Self {
+        //  Zero-sized elements never require any memory.
+        let capacity = if mem::size_of::<T>() == 0 { usize::MAX } else { 0 };
+
+        Self { pointer: NonNull::dangling(), capacity, length: 0, node, _marker: PhantomData }
+    }
+
+    /// Creates an empty array, for NUMA `node`, with room for at least `capacity` elements.
+    pub fn with_capacity(node: NumaNodeIndex, capacity: usize) -> Result<Self, AllocationError> {
+        let mut result = Self::new(node);
+        result.reserve(capacity)?;
+
+        Ok(result)
+    }
+
+    /// Returns the NUMA node the array is allocated on, as requested.
+    pub fn node(&self) -> NumaNodeIndex { self.node }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize { self.length }
+
+    /// Returns whether the array has no element.
+    pub fn is_empty(&self) -> bool { self.length == 0 }
+
+    /// Returns the number of elements the array can hold without reallocating.
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    /// Reserves room for at least `additional` more elements, reallocating on the NUMA node of the array if need be.
+    ///
+    /// Returns an error, leaving the array untouched, if the reallocation fails.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), AllocationError> {
+        let required = self.length.checked_add(additional).ok_or(AllocationError::ExceedsMaximumSize)?;
+
+        if required <= self.capacity {
+            return Ok(());
+        }
+
+        let capacity = required.max(self.capacity.saturating_mul(2)).max(4);
+        let layout = Layout::array::<T>(capacity).map_err(|_| AllocationError::ExceedsMaximumSize)?;
+
+        let pointer = allocate::<T>(self.node, layout)?;
+
+        //  Safety:
+        //  -   `self.pointer` is valid for reads of `self.length` elements.
+        //  -   `pointer` is valid for writes of `capacity` elements, as it was just allocated.
+        //  -   The buffers do not overlap.
+        //  -   The previous buffer is no longer in use, its elements having been moved.
+        unsafe {
+            ptr::copy_nonoverlapping(self.pointer.as_ptr(), pointer.as_ptr(), self.length);
+            self.release();
+        }
+
+        self.pointer = pointer;
+        self.capacity = capacity;
+
+        Ok(())
+    }
+
+    /// Appends `value`, reallocating on the NUMA node of the array if need be.
+    ///
+    /// Returns `value` back if the reallocation fails.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.length == self.capacity && self.reserve(1).is_err() {
+            return Err(value);
+        }
+
+        //  Safety:
+        //  -   `self.length` is less than `self.capacity`, hence within the buffer.
+        unsafe { ptr::write(self.pointer.as_ptr().add(self.length), value) };
+
+        self.length += 1;
+
+        Ok(())
+    }
+
+    /// Removes the last element, and returns it, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.length == 0 {
+            return None;
+        }
+
+        self.length -= 1;
+
+        //  Safety:
+        //  -   The element at `self.length` is initialized, and no longer considered part of the array.
+        Some(unsafe { ptr::read(self.pointer.as_ptr().add(self.length)) })
+    }
+
+    /// Drops all elements, retaining the buffer.
+    pub fn clear(&mut self) {
+        let elements: *mut [T] = &mut **self;
+
+        //  Set first, so that a panicking destructor leaks, rather than double drops, the remaining elements.
+        self.length = 0;
+
+        //  Safety:
+        //  -   `elements` were initialized, and are no longer considered part of the array.
+        unsafe { ptr::drop_in_place(elements) };
+    }
+
+    //  Deallocates the buffer, without dropping its elements.
+    //
+    //  #   Safety
+    //
+    //  -   Assumes the buffer is no longer in use.
+    unsafe fn release(&mut self) {
+        if self.capacity != 0 {
+            //  `capacity` elements were successfully allocated, hence their size does not overflow.
+            deallocate(self.pointer, self.capacity.wrapping_mul(mem::size_of::<T>()));
+        }
+    }
+}
+
+impl<T> Deref for NodeVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        //  Safety:
+        //  -   The first `self.length` elements are initialized, and owned by `self`.
+        unsafe { slice::from_raw_parts(self.pointer.as_ptr(), self.length) }
+    }
+}
+
+impl<T> DerefMut for NodeVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        //  Safety:
+        //  -   The first `self.length` elements are initialized, and exclusively owned by `self`.
+        unsafe { slice::from_raw_parts_mut(self.pointer.as_ptr(), self.length) }
+    }
+}
+
+impl<T> Drop for NodeVec<T> {
+    fn drop(&mut self) {
+        self.clear();
+
+        //  Safety:
+        //  -   The buffer is no longer in use, its elements having been dropped.
+        unsafe { self.release() };
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for NodeVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { fmt::Debug::fmt(&**self, f) }
+}
+
+//
+//  Implementation Details
+//
+
+static ALLOCATOR: LLAllocator = LLAllocator::new();
+
+//  Allocates memory for `layout` on the heap of `node`, or returns a dangling pointer if `layout` is zero-sized.
+fn allocate<T>(node: NumaNodeIndex, layout: Layout) -> Result<NonNull<T>, AllocationError> {
+    if layout.size() == 0 {
+        return Ok(NonNull::dangling());
+    }
+
+    ALLOCATOR.allocate_on_node(node, layout).map(NonNull::cast)
+}
+
+//  Deallocates the memory of `size` bytes at `pointer`, unless zero-sized.
+//
+//  #   Safety
+//
+//  -   Assumes `pointer` was returned by `allocate`, with a layout of `size` bytes, and is no longer in use.
+unsafe fn deallocate<T>(pointer: NonNull<T>, size: usize) {
+    if size != 0 {
+        ALLOCATOR.deallocate(pointer.cast());
+    }
+}
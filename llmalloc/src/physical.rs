@@ -0,0 +1,146 @@
+//! Physically-Backed Buffers
+//!
+//! Kernel-bypass frameworks, in the style of DPDK, hand buffers to devices by physical address, and therefore require
+//! memory which neither moves nor is swapped out. A physical buffer is a dedicated mapping, outside of the heap:
+//!
+//! -   Backed by 2 MB HugeTLB pages, if available, or otherwise aligned on 2 MB and advised to be backed by
+//!     Transparent Huge Pages, which the kernel may still split or migrate.
+//! -   Locked in RAM, and thereby faulted in, on allocation.
+//!
+//! The physical addresses are looked up in `/proc/self/pagemap`, which only reports them to sufficiently privileged
+//! processes, `CAP_SYS_ADMIN` on most kernels, and are reported as segments of physically contiguous memory.
+
+use core::ptr::NonNull;
+
+/// A buffer of locked memory, allocated by `LLAllocator::allocate_physical`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct PhysicalBuffer {
+    pointer: NonNull<u8>,
+    size: usize,
+    huge_tlb: bool,
+}
+
+//  Safety:
+//  -   The buffer only exposes the address of its memory, the accesses are up to the caller.
+unsafe impl Send for PhysicalBuffer {}
+unsafe impl Sync for PhysicalBuffer {}
+
+impl PhysicalBuffer {
+    /// Creates an instance, of `size` bytes located at `pointer`.
+    pub fn new(pointer: NonNull<u8>, size: usize, huge_tlb: bool) -> Self { Self { pointer, size, huge_tlb } }
+
+    /// Returns the address of the buffer.
+    pub fn pointer(&self) -> NonNull<u8> { self.pointer }
+
+    /// Returns the size of the buffer, rounded up to a multiple of 2 MB.
+    pub fn size(&self) -> usize { self.size }
+
+    /// Returns whether the buffer is backed by HugeTLB pages, rather than Transparent Huge Pages.
+    pub fn is_huge_tlb(&self) -> bool { self.huge_tlb }
+}
+
+/// A physically contiguous segment of a buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PhysicalSegment {
+    /// The offset of the segment, from the start of the buffer.
+    pub offset: usize,
+    /// The physical address of the segment.
+    pub physical_address: u64,
+    /// The size of the segment, in bytes.
+    pub size: usize,
+}
+
+/// Builder of the physically contiguous segments, from the frames of consecutive pages.
+//  Only Linux exposes the frames of the pages.
+#[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+    feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc")))),
+    allow(dead_code))]
+pub(crate) struct SegmentBuilder {
+    page_size: usize,
+    current: Option<PhysicalSegment>,
+    segments: usize,
+}
+
+#[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+    feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc")))),
+    allow(dead_code))]
+impl SegmentBuilder {
+    /// Creates an instance, for pages of `page_size` bytes.
+    pub(crate) fn new(page_size: usize) -> Self { Self { page_size, current: None, segments: 0 } }
+
+    /// Pushes the frame of the next page, invoking `report` on the segment it ends, if any.
+    pub(crate) fn push<F>(&mut self, frame: u64, report: &mut F)
+        where
+            F: FnMut(&PhysicalSegment) + ?Sized,
+    {
+        let physical_address = frame * self.page_size as u64;
+
+        match &mut self.current {
+            Some(segment) if segment.physical_address + segment.size as u64 == physical_address => {
+                segment.size += self.page_size;
+            },
+            current => {
+                let offset = current.map_or(0, |segment| segment.offset + segment.size);
+                let next = PhysicalSegment { offset, physical_address, size: self.page_size };
+
+                if let Some(segment) = current.replace(next) {
+                    self.segments += 1;
+                    report(&segment);
+                }
+            },
+        }
+    }
+
+    /// Invokes `report` on the last segment, if any, returning the number of segments.
+    pub(crate) fn finish<F>(mut self, report: &mut F) -> usize
+        where
+            F: FnMut(&PhysicalSegment) + ?Sized,
+    {
+        if let Some(segment) = self.current.take() {
+            self.segments += 1;
+            report(&segment);
+        }
+
+        self.segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use std::vec::Vec;
+
+use super::*;
+
+fn segment(offset: usize, physical_address: u64, size: usize) -> PhysicalSegment {
+    PhysicalSegment { offset, physical_address, size }
+}
+
+#[test]
+fn segment_builder_contiguity() {
+    let mut segments = Vec::new();
+    let mut report = |segment: &PhysicalSegment| segments.push(*segment);
+
+    let mut builder = SegmentBuilder::new(0x1000);
+
+    for frame in [7, 8, 9, 3, 12, 13] {
+        builder.push(frame, &mut report);
+    }
+
+    assert_eq!(3, builder.finish(&mut report));
+
+    assert_eq!(
+        [segment(0, 0x7000, 0x3000), segment(0x3000, 0x3000, 0x1000), segment(0x4000, 0xC000, 0x2000)],
+        &segments[..]);
+}
+
+#[test]
+fn segment_builder_empty() {
+    let builder = SegmentBuilder::new(0x1000);
+
+    assert_eq!(0, builder.finish(&mut |_: &PhysicalSegment| panic!("No segment")));
+}
+
+} // mod tests
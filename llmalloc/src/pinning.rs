@@ -0,0 +1,112 @@
+//! Pinning
+//!
+//! Memory which is mapped is only guaranteed to remain resident while it is not paged out, a major page fault then
+//! bringing it back on access. With pinning, the platform instead locks in RAM, with `mlock`, each `HugePage` it maps,
+//! and each Huge allocation it grows, before handing them over, so that the memory of the heap is never paged out.
+//!
+//! Locking counts against the limit of locked memory of the process, `RLIMIT_MEMLOCK`, past which locking fails. The
+//! memory which cannot be locked is still handed over, rather than failing the allocation, and each failure is
+//! recorded, both in `PinningReport::unlocked` and as a `FallbackMetrics::lock_failures`.
+//!
+//! The flag is resolved from the `LLMALLOC_MLOCK` environment variable, enabled if set to any value other than an empty
+//! string or `0`, unless set explicitly beforehand by `LLAllocator::set_pinned`.
+//!
+//! Pinning is honored by the platforms mapping memory from the OS on Unix; bare metal memory is never paged out, and
+//! custom platforms map their memory themselves.
+
+use core::{
+    ptr::NonNull,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{toggle::Toggle, Fallback, Platform, LLPlatform};
+
+/// Name of the environment variable selecting pinning, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_MLOCK\0";
+
+/// Report of the pinning of the heap, as returned by `LLAllocator::pinning_report`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PinningReport {
+    /// Whether pinning is enabled.
+    pub enabled: bool,
+    /// Memory locked in RAM, in bytes, since the start of the process, whether or not it was unmapped since.
+    pub locked: u64,
+    /// Memory which could not be locked in RAM, in bytes, since the start of the process.
+    pub unlocked: u64,
+    /// The limit of locked memory of the process, in bytes, or None if unlimited or unknown.
+    pub limit: Option<u64>,
+}
+
+impl PinningReport {
+    /// Returns whether all the memory mapped while pinning was locked in RAM.
+    pub fn is_complete(&self) -> bool { self.unlocked == 0 }
+}
+
+/// Process-wide selection of pinning, and its metrics.
+pub(crate) struct Pinning {
+    state: Toggle,
+    locked: AtomicU64,
+    unlocked: AtomicU64,
+}
+
+impl Pinning {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self {
+        Self { state: Toggle::new(ENVIRONMENT_VARIABLE), locked: AtomicU64::new(0), unlocked: AtomicU64::new(0) }
+    }
+
+    /// Returns whether pinning is enabled, resolving it from `platform` if not yet resolved.
+    #[inline(always)]
+    pub(crate) fn is_enabled(&self, platform: &LLPlatform) -> bool { self.state.is_enabled(platform) }
+
+    /// Enables or disables pinning, overriding the environment.
+    pub(crate) fn set(&self, enabled: bool) { self.state.set(enabled) }
+
+    /// Locks in RAM the `size` bytes located at `pointer`, if pinning is enabled, returning false if they could not be
+    /// locked.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn pin(&self, platform: &LLPlatform, pointer: NonNull<u8>, size: usize) -> bool {
+        if !self.is_enabled(platform) {
+            return true;
+        }
+
+        if platform.lock(pointer, size) {
+            self.locked.fetch_add(size as u64, Ordering::Relaxed);
+            return true;
+        }
+
+        self.unlocked.fetch_add(size as u64, Ordering::Relaxed);
+        platform.fallbacks().record(Fallback::LockFailure);
+
+        false
+    }
+
+    /// Returns the report of the pinning, with the given `limit` of locked memory.
+    pub(crate) fn report(&self, platform: &LLPlatform, limit: Option<u64>) -> PinningReport {
+        PinningReport {
+            enabled: self.is_enabled(platform),
+            locked: self.locked.load(Ordering::Relaxed),
+            unlocked: self.unlocked.load(Ordering::Relaxed),
+            limit,
+        }
+    }
+}
+
+/// Selection of pinning, shared by the allocator and the platforms.
+pub(crate) static PINNING: Pinning = Pinning::new();
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn pinning_report_is_complete() {
+    let report = PinningReport { enabled: true, locked: 4096, ..PinningReport::default() };
+    assert!(report.is_complete());
+
+    assert!(!PinningReport { unlocked: 4096, ..report }.is_complete());
+}
+
+} // mod tests
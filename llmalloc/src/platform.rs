@@ -2,10 +2,150 @@
 
 mod api;
 
-pub(crate) use api::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+#[cfg(all(feature = "bare-metal", feature = "custom-platform"))]
+compile_error!("The `bare-metal` and `custom-platform` features are mutually exclusive.");
 
-#[cfg(target_os = "linux")]
+#[cfg(all(feature = "test-platform", any(feature = "bare-metal", feature = "custom-platform")))]
+compile_error!("The `test-platform` feature is mutually exclusive with the `bare-metal` and `custom-platform` ones.");
+
+#[cfg(all(feature = "test-platform", not(unix)))]
+compile_error!("The `test-platform` feature relies on the thread-local storage of Unix systems.");
+
+#[cfg(all(feature = "no-libc", any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform")))]
+compile_error!("The `no-libc` feature is mutually exclusive with the `posix`, `bare-metal`, `custom-platform`, and \
+    `test-platform` ones.");
+
+#[cfg(all(feature = "no-libc", not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))))]
+compile_error!("The `no-libc` feature relies on the raw system calls of Linux, on x86_64 or aarch64.");
+
+#[cfg(all(feature = "system-fallback", not(any(target_arch = "wasm32", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform", feature = "no-libc"))))]
+mod ownership;
+
+pub use api::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+
+#[cfg(all(unix, not(any(target_os = "illumos", target_os = "solaris", feature = "bare-metal",
+    feature = "custom-platform", feature = "no-libc"))))]
+mod pthread;
+
+#[cfg(all(unix, not(any(target_os = "illumos", target_os = "solaris", feature = "bare-metal",
+    feature = "custom-platform", feature = "no-libc"))))]
+pub(crate) use pthread::LLThreadLocal;
+
+#[cfg(all(any(target_os = "illumos", target_os = "solaris"), not(any(feature = "bare-metal",
+    feature = "custom-platform"))))]
+mod thr;
+
+#[cfg(all(any(target_os = "illumos", target_os = "solaris"), not(any(feature = "bare-metal",
+    feature = "custom-platform"))))]
+pub(crate) use thr::LLThreadLocal;
+
+#[cfg(all(unix, not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")),
+    any(feature = "posix", not(any(target_os = "linux", target_os = "android", target_os = "macos",
+    target_os = "freebsd", target_os = "fuchsia")))))]
+mod shm;
+
+#[cfg(all(unix, not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")),
+    any(feature = "posix", not(any(target_os = "linux", target_os = "android")))))]
+mod unix;
+
+#[cfg(all(unix, not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")),
+    any(feature = "posix", not(any(target_os = "linux", target_os = "android", target_os = "fuchsia")))))]
+mod mmap;
+
+#[cfg(all(any(target_os = "macos", target_os = "freebsd", target_os = "illumos", target_os = "solaris"),
+    not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform", feature = "test-platform"))))]
+mod detector;
+
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform", feature = "no-libc"))))]
 mod linux;
 
-#[cfg(target_os = "linux")]
-pub(crate) use linux::{LLConfiguration, LLPlatform, LLThreadLocal};
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform", feature = "no-libc"))))]
+pub(crate) use linux::{LLConfiguration, LLPlatform};
+
+#[cfg(all(target_os = "macos", not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+mod macos;
+
+#[cfg(all(target_os = "macos", not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+pub(crate) use macos::{LLConfiguration, LLPlatform};
+
+#[cfg(all(target_os = "freebsd", not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+mod freebsd;
+
+#[cfg(all(target_os = "freebsd", not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+pub(crate) use freebsd::{LLConfiguration, LLPlatform};
+
+#[cfg(all(any(target_os = "illumos", target_os = "solaris"), not(any(feature = "posix", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform"))))]
+mod illumos;
+
+#[cfg(all(any(target_os = "illumos", target_os = "solaris"), not(any(feature = "posix", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform"))))]
+pub(crate) use illumos::{LLConfiguration, LLPlatform};
+
+#[cfg(all(target_os = "fuchsia", not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+mod fuchsia;
+
+#[cfg(all(target_os = "fuchsia", not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+pub(crate) use fuchsia::{LLConfiguration, LLPlatform};
+
+#[cfg(all(unix, not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")),
+    any(feature = "posix", not(any(target_os = "linux", target_os = "android", target_os = "macos",
+    target_os = "freebsd", target_os = "illumos", target_os = "solaris", target_os = "fuchsia")))))]
+mod posix;
+
+#[cfg(all(unix, not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")),
+    any(feature = "posix", not(any(target_os = "linux", target_os = "android", target_os = "macos",
+    target_os = "freebsd", target_os = "illumos", target_os = "solaris", target_os = "fuchsia")))))]
+pub(crate) use posix::{LLConfiguration, LLPlatform};
+
+#[cfg(all(target_os = "windows", not(any(feature = "bare-metal", feature = "custom-platform"))))]
+mod windows;
+
+#[cfg(all(target_os = "windows", not(any(feature = "bare-metal", feature = "custom-platform"))))]
+pub(crate) use windows::{LLConfiguration, LLPlatform, LLThreadLocal};
+
+#[cfg(all(target_arch = "wasm32", not(any(feature = "bare-metal", feature = "custom-platform"))))]
+mod wasm;
+
+#[cfg(all(target_arch = "wasm32", not(any(feature = "bare-metal", feature = "custom-platform"))))]
+pub(crate) use wasm::{LLConfiguration, LLPlatform, LLThreadLocal};
+
+#[cfg(feature = "bare-metal")]
+mod bare_metal;
+
+#[cfg(feature = "bare-metal")]
+pub(crate) use bare_metal::{LLConfiguration, LLPlatform, LLThreadLocal};
+
+#[cfg(feature = "custom-platform")]
+mod custom;
+
+#[cfg(feature = "custom-platform")]
+pub use custom::LLConfiguration;
+
+#[cfg(feature = "custom-platform")]
+pub(crate) use custom::{LLPlatform, LLThreadLocal};
+
+#[cfg(feature = "test-platform")]
+mod mock;
+
+#[cfg(feature = "test-platform")]
+pub use mock::{LLConfiguration, MockCall, MockPlatform};
+
+#[cfg(feature = "test-platform")]
+pub(crate) use mock::LLPlatform;
+
+#[cfg(feature = "no-libc")]
+mod nolibc;
+
+#[cfg(feature = "no-libc")]
+pub(crate) use nolibc::{LLConfiguration, LLPlatform, LLThreadLocal};
@@ -1,21 +1,228 @@
 //! API of OS required services.
 
-use core::ptr::NonNull;
+#[cfg(feature = "system-fallback")]
+use core::alloc::Layout;
+use core::{ptr::NonNull, time::Duration};
 
 pub use llmalloc_core::Configuration;
 
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Collapse, HostCapabilities, HugePageReport,
+    HugeTlbPools, PhysicalBuffer, PhysicalSegment, PrivilegeError, ThreadStack,
+};
+
 /// Abstraction over OS services.
-pub(crate) trait Platform : llmalloc_core::Platform + Send + Sync {
+///
+/// Implemented by the embedder, with the `custom-platform` feature, for the OSes llmalloc does not support, see
+/// `LLAllocator::with_platform`; the Huge Pages themselves are mapped through its supertrait, `CorePlatform`.
+pub trait Platform : llmalloc_core::Platform + Send + Sync {
     /// Returns the current NUMA node on which the thread is running.
     ///
     /// As the thread may migrate to another node at the scheduler's whim, the actual result has no impact on
     /// correctness. It does, however, impact performance: it is better for a node's thread to access memory
     /// stored in the node's memory banks, rather than another node.
     fn current_node(&self) -> NumaNodeIndex;
+
+    /// Returns the NUMA node serving the memory of `node`, after clustering the nearby nodes, as `current_node` does.
+    ///
+    /// Returns None if `node` is not a NUMA node of the machine.
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex>;
+
+    /// Returns a monotonic timestamp, in nanoseconds.
+    ///
+    /// The origin of the timestamps is unspecified, hence only differences between timestamps are meaningful.
+    fn now(&self) -> u64;
+
+    /// Reconciles the `HugePage` of `size` bytes located at `page`, owned by the socket of `node`, against the view of
+    /// the OS.
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport;
+
+    /// Returns the number of resident bytes among the `size` bytes located at `pointer`, or None if the residency
+    /// cannot be looked up, for example if the memory is not mapped.
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize>;
+
+    /// Prefaults and locks in RAM the `size` bytes located at `pointer`, so that no page fault occurs on access.
+    ///
+    /// Returns true if the memory is locked, false otherwise, for example if the limit of locked memory is reached.
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool;
+
+    /// Protects the `size` bytes located at `pointer`, making them read-only, or read-write if `writable`.
+    ///
+    /// Returns true if the protection is changed, false otherwise.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` is aligned on an OS page, and that the `size` bytes are mapped, and not otherwise in use.
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool;
+
+    /// Maps a region of at least `size` bytes for executable code, outside of the heap.
+    ///
+    /// The region is initially writable, through the writable view, and not executable unless dual-mapped.
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion>;
+
+    /// Unmaps a region mapped by `map_code`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `region` was mapped by `map_code`, and is no longer in use.
+    unsafe fn unmap_code(&self, region: CodeRegion);
+
+    /// Protects the flip-protected `region`, making it read-execute if `executable`, or read-write otherwise.
+    ///
+    /// Returns true if the protection is changed, false otherwise.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `region` was mapped by `map_code`, with `CodeMapping::Flip`.
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool;
+
+    /// Maps a thread stack of at least `size` bytes, preceded by a guard page, prefaulting it if `prefault`.
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack>;
+
+    /// Unmaps a stack mapped by `map_stack`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `stack` was mapped by `map_stack`, and is no longer in use.
+    unsafe fn unmap_stack(&self, stack: ThreadStack);
+
+    /// Maps a buffer of at least `size` bytes, backed by huge pages, and locked in RAM.
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer>;
+
+    /// Unmaps a buffer mapped by `map_physical`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `buffer` was mapped by `map_physical`, and is no longer in use.
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer);
+
+    /// Invokes `report` on each physically contiguous segment of `buffer`, in order, returning their number.
+    ///
+    /// Returns None if the physical addresses cannot be looked up.
+    fn physical_segments(&self, buffer: &PhysicalBuffer, report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize>;
+
+    /// Returns whether the environment variable `name`, NUL-terminated, is set to a value other than an empty string
+    /// or `0`.
+    fn environment_flag(&self, name: &[u8]) -> bool;
+
+    /// Returns the capabilities of the environment, as detected, and downgraded, so far.
+    fn capabilities(&self) -> Capabilities;
+
+    /// Forgoes HugeTLB, so that the Huge Pages mapped from then on are backed by Transparent Huge Pages, requested
+    /// through `madvise`, or failing that by normal pages.
+    ///
+    /// Returns false if the platform cannot forgo HugeTLB, as by default.
+    fn use_transparent_huge_pages(&self) -> bool { false }
+
+    /// Returns the pools of HugeTLB pages, as probed on the detection of the capabilities.
+    ///
+    /// Returns no pool if the platform does not probe them, as by default.
+    fn huge_tlb_pools(&self) -> HugeTlbPools { HugeTlbPools::default() }
+
+    /// Collapses the `size` bytes located at `pointer` into huge pages, preserving their content.
+    ///
+    /// Returns `Collapse::Unsupported` if the platform cannot collapse memory, as by default.
+    fn collapse(&self, pointer: NonNull<u8>, size: usize) -> Collapse {
+        let _ = (pointer, size);
+        Collapse::Unsupported
+    }
+
+    /// Interleaves the pages of the `size` bytes located at `pointer` across all NUMA nodes, migrating those already
+    /// faulted in.
+    ///
+    /// Returns false if the pages cannot be interleaved, or if the platform cannot interleave memory, as by default.
+    fn interleave(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        let _ = (pointer, size);
+        false
+    }
+
+    /// Returns the distance between the NUMA nodes `from` and `to`, as reported by the OS, a node being at a distance
+    /// of 10 from itself, or None if unknown, as by default.
+    ///
+    /// Once the socket of a thread is exhausted, its allocations fall back onto the sockets of the other nodes, in the
+    /// order of their distance; the nodes at an unknown distance are tried last.
+    fn distance(&self, from: NumaNodeIndex, to: NumaNodeIndex) -> Option<u8> {
+        let _ = (from, to);
+        None
+    }
+
+    /// Returns the NUMA node backing the page located at `pointer`, as reported by the OS, or None if unknown, such as
+    /// if the page was never faulted in, or if the platform cannot look it up, as by default.
+    fn node_of_page(&self, pointer: NonNull<u8>) -> Option<NumaNodeIndex> {
+        let _ = pointer;
+        None
+    }
+
+    /// Re-scans the NUMA topology, such as after nodes were hot-added, returning the number of nodes found.
+    ///
+    /// Returns None if the topology of the platform is fixed, as by default.
+    fn refresh_topology(&self) -> Option<u32> { None }
+
+    /// Spawns a detached thread running `entry`, for the background thread of the allocator.
+    ///
+    /// Returns false if the thread cannot be spawned, or if the platform cannot spawn threads, as by default.
+    fn spawn_thread(&self, entry: fn()) -> bool {
+        let _ = entry;
+        false
+    }
+
+    /// Suspends the current thread for at least `duration`.
+    ///
+    /// Only called by the threads spawned by `spawn_thread`, hence a platform spawning threads is to implement it too;
+    /// by default, does not suspend the thread.
+    fn sleep(&self, duration: Duration) { let _ = duration; }
+
+    /// Registers `prepare` to be invoked prior to each fork of the process, and `child` to be invoked in the child,
+    /// for the allocator to recover from the threads which the child does not inherit.
+    ///
+    /// Returns false if the handlers cannot be registered, or if the platform cannot fork, as by default.
+    fn at_fork(&self, prepare: extern "C" fn(), child: extern "C" fn()) -> bool {
+        let _ = (prepare, child);
+        false
+    }
+
+    /// Returns the capabilities of the host, in details, including the capabilities selected so far.
+    fn host_capabilities(&self) -> HostCapabilities;
+
+    /// Acquires the privilege required to back Huge Pages by large pages, if any, reporting why it cannot otherwise.
+    ///
+    /// By default, no privilege is required.
+    fn acquire_large_page_privilege(&self) -> Result<(), PrivilegeError> { Err(PrivilegeError::Unsupported) }
+
+    /// Returns the worst latency observed mapping memory, through `llmalloc_core::Platform::allocate`, if any memory
+    /// was mapped.
+    fn mapping_latency(&self) -> Option<Duration>;
+
+    /// Returns the metrics of the fallbacks, recorded by both the platform and the allocator.
+    fn fallbacks(&self) -> &AtomicFallbackMetrics;
+
+    /// Allocates memory from the system allocator, for the requests llmalloc cannot serve.
+    #[cfg(feature = "system-fallback")]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    /// Deallocates memory allocated by `system_allocate`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `pointer` was allocated by `system_allocate`, and is no longer in use.
+    #[cfg(feature = "system-fallback")]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>);
+
+    /// Returns whether `pointer` lies within the memory allocated by `llmalloc_core::Platform::allocate`, as opposed
+    /// to the memory allocated by `system_allocate`.
+    #[cfg(feature = "system-fallback")]
+    fn owns(&self, pointer: NonNull<u8>) -> bool;
 }
 
 /// Abstraction over thread-local storage.
-pub(crate) trait ThreadLocal<T> {
+///
+/// Implemented by the embedder, with the `custom-platform` feature, alongside `Platform`.
+pub trait ThreadLocal<T> {
+    /// Prepares the instance, so that the first call to `set` is as cheap as possible.
+    ///
+    /// Returns true if this call performed the preparation, and false if it was already performed.
+    fn prepare(&self) -> bool;
+
     /// Returns a pointer to the thread-local value associated to this instance.
     ///
     /// May return a null pointer if no prior value was set, or it was already destructed.
@@ -23,20 +230,27 @@ pub(crate) trait ThreadLocal<T> {
 
     /// Sets the pointer to the thread-local value associated to this instance.
     ///
+    /// Returns true if the value is set, false otherwise.
+    ///
     /// #   Safety
     ///
     /// -   Assumes that the value is not already set.
-    fn set(&self, value: NonNull<T>);
+    fn set(&self, value: NonNull<T>) -> bool;
+
+    /// Clears the pointer to the thread-local value associated to this instance, without invoking its destructor.
+    ///
+    /// Returns true if the value is cleared, false otherwise, such as if the instance cannot clear it, as by default.
+    fn clear(&self) -> bool { false }
 }
 
 /// Index of a NUMA node.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
-pub(crate) struct NumaNodeIndex(u32);
+pub struct NumaNodeIndex(u32);
 
 impl NumaNodeIndex {
     /// Creates a NumaNodeIndex.
-    pub(crate) fn new(value: u32) -> Self { Self(value) }
+    pub const fn new(value: u32) -> Self { Self(value) }
 
     /// Retrieves the index.
-    pub(crate) fn value(&self) -> u32 { self.0 }
+    pub fn value(&self) -> u32 { self.0 }
 }
@@ -0,0 +1,538 @@
+//! Implementation of a bare-metal platform, over a region of memory handed in by the embedder.
+//!
+//! Without an OS, there is neither libc, nor pthread, nor `mmap`: the memory is a fixed region, such as a range
+//! reserved by a bootloader or a DPDK pool of huge pages, handed in once with `LLAllocator::provide_region`, and carved
+//! into Huge Pages, with a map of those in use. Neither code, stacks, nor physical buffers can be mapped, and the
+//! region is never returned.
+//!
+//! The thread-local storage is a single slot, suitable for a single thread of execution, unless the embedder provides
+//! its own per-thread, or per-CPU, slots with `LLAllocator::provide_thread_slot`.
+
+use core::{
+    alloc::Layout,
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    sync::atomic::{self, AtomicBool, AtomicPtr, AtomicU64, AtomicUsize},
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, HostCapabilities, HugePageReport, PhysicalBuffer,
+    PhysicalSegment, ThreadStack,
+};
+
+use super::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+
+/// Implementation of the Configuration trait, for bare metal.
+///
+/// The pages are sized as on Linux, see the Linux configuration for the consequences of the `small-heap` feature; the
+/// region must therefore span at least one aligned Huge Page.
+#[derive(Default)]
+pub(crate) struct LLConfiguration;
+
+#[cfg(not(feature = "small-heap"))]
+impl Configuration for LLConfiguration {
+    //  2 MB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+
+    //  1 GB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(1024 * 1024 * 1024) };
+}
+
+#[cfg(feature = "small-heap")]
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
+/// Implementation of the Platform trait, for bare metal.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+
+    /// Provides the `size` bytes of memory located at `pointer` as the region to carve Huge Pages from.
+    ///
+    /// Returns false if a region was already provided, or if the region does not span a single aligned Huge Page.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that the memory is valid for reads and writes, and not otherwise in use, forever.
+    #[cold]
+    pub(crate) unsafe fn provide_region(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        REGION.provide(pointer.as_ptr() as usize, size)
+    }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        const HUGE_PAGE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        debug_assert!(layout.size() % HUGE_PAGE == 0,
+            "Incorrect size: {} % {} != 0", layout.size(), HUGE_PAGE_SIZE);
+        debug_assert!(layout.align() <= HUGE_PAGE_SIZE,
+            "Incorrect alignment: {} > {}", layout.align(), HUGE_PAGE_SIZE);
+
+        if layout.size() % HUGE_PAGE != 0 || layout.align() > HUGE_PAGE_SIZE {
+            return None;
+        }
+
+        let address = REGION.allocate(layout.size() / HUGE_PAGE_SIZE)?;
+
+        NonNull::new(address as *mut u8)
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        REGION.release(pointer.as_ptr() as usize, layout.size() / HUGE_PAGE_SIZE);
+    }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        const HUGE_PAGE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        if new_size % HUGE_PAGE != 0 || layout.align() > HUGE_PAGE_SIZE {
+            return None;
+        }
+
+        let address = pointer.as_ptr() as usize;
+        let (pages, new_pages) = (layout.size() / HUGE_PAGE_SIZE, new_size / HUGE_PAGE_SIZE);
+
+        //  Shrink in place, by releasing the tail.
+        if new_pages <= pages {
+            REGION.release(address + new_size, pages - new_pages);
+            return Some(pointer);
+        }
+
+        //  Grow in place, if the following Huge Pages are free; the pages cannot be moved.
+        if REGION.extend(address, pages, new_pages - pages) { Some(pointer) } else { None }
+    }
+}
+
+impl Platform for LLPlatform {
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex { NumaNodeIndex::new(0) }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node == 0 { Some(NumaNodeIndex::new(0)) } else { None }
+    }
+
+    //  There is no clock to read, hence the metrics relying on timestamps are meaningless.
+    #[inline(always)]
+    fn now(&self) -> u64 { 0 }
+
+    //  The region is neither paged, nor split, nor migrated, by any kernel.
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: size,
+            resident: size,
+            anonymous_huge: 0,
+            local_pages: 1,
+            foreign_pages: 0,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, _pointer: NonNull<u8>, size: usize) -> Option<usize> { Some(size) }
+
+    //  The region is never paged out, hence always locked.
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, _pointer: NonNull<u8>, _size: usize) -> bool { true }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, _pointer: NonNull<u8>, _size: usize, _writable: bool) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, _size: usize, _mapping: CodeMapping) -> Option<CodeRegion> { None }
+
+    //  No code region is ever mapped.
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, _region: CodeRegion) {}
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, _region: &CodeRegion, _executable: bool) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, _size: usize, _prefault: bool) -> Option<ThreadStack> { None }
+
+    //  No stack is ever mapped.
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, _stack: ThreadStack) {}
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, _size: usize) -> Option<PhysicalBuffer> { None }
+
+    //  No physical buffer is ever mapped.
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, _buffer: PhysicalBuffer) {}
+
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    //  There is no environment to read.
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, _name: &[u8]) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities {
+        let mut host = HostCapabilities::default();
+        host.capabilities = CAPABILITIES;
+
+        //  Without paging, the region is managed at the granularity of the Huge Pages.
+        host.numa_nodes = 1;
+        host.os_page_size = HUGE_PAGE_SIZE;
+
+        host
+    }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { None }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    //  There is no system allocator to delegate to.
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, _layout: Layout) -> Option<NonNull<u8>> { None }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, _pointer: NonNull<u8>) {}
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { REGION.contains(pointer.as_ptr() as usize) }
+}
+
+/// Implementation of the ThreadLocal trait, for bare metal.
+pub(crate) struct LLThreadLocal<T> {
+    //  The function returning the slot of the current thread, or CPU, as a `ThreadSlot`, or 0 if none was provided.
+    provider: AtomicUsize,
+    //  The single slot, used unless a provider was provided.
+    slot: AtomicPtr<u8>,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> LLThreadLocal<T> {
+    /// Creates an uninitialized instance.
+    ///
+    /// Without threads, no thread ever exits, hence `destructor` is never invoked.
+    ///
+    /// #   Safety
+    ///
+    /// -   Has no requirement, `destructor` being unused.
+    pub(crate) const unsafe fn new(_destructor: *const u8) -> Self {
+        let provider = AtomicUsize::new(0);
+        let slot = AtomicPtr::new(ptr::null_mut());
+        let _marker = PhantomData;
+
+        LLThreadLocal { provider, slot, _marker }
+    }
+
+    /// Provides the function returning the slot of the current thread, or CPU, in place of the single slot.
+    ///
+    /// Returns false if a provider was already provided, or the single slot is already set.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `provider` returns a distinct slot for each thread of execution using the allocator, initially
+    ///     null, and only ever modified by the allocator.
+    #[cold]
+    pub(crate) unsafe fn provide_slot(&self, provider: ThreadSlot) -> bool {
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        if !self.slot.load(RELAXED).is_null() {
+            return false;
+        }
+
+        self.provider.compare_exchange(0, provider as usize, RELAXED, RELAXED).is_ok()
+    }
+
+    #[inline(always)]
+    fn current_slot(&self) -> &AtomicPtr<u8> {
+        match self.provider.load(atomic::Ordering::Relaxed) {
+            0 => &self.slot,
+            provider => {
+                //  Safety:
+                //  -   `provider` was stored from a `ThreadSlot`, and fn pointers are just pointers.
+                let provider = unsafe { mem::transmute::<usize, ThreadSlot>(provider) };
+                provider()
+            },
+        }
+    }
+}
+
+impl<T> ThreadLocal<T> for LLThreadLocal<T> {
+    //  There is no key to create.
+    #[inline(always)]
+    fn prepare(&self) -> bool { false }
+
+    #[inline(always)]
+    fn get(&self) -> Option<NonNull<T>> {
+        NonNull::new(self.current_slot().load(atomic::Ordering::Relaxed) as *mut T)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn set(&self, value: NonNull<T>) -> bool {
+        self.current_slot().store(value.as_ptr() as *mut u8, atomic::Ordering::Relaxed);
+        true
+    }
+}
+
+unsafe impl<T> Sync for LLThreadLocal<T> {}
+
+/// Function returning the slot of the current thread, or CPU.
+pub(crate) type ThreadSlot = fn() -> &'static AtomicPtr<u8>;
+
+//
+//  Implementation Details
+//
+
+//  Capabilities of the environment, which are not detected: the backing of the region is up to the embedder.
+const CAPABILITIES: Capabilities =
+    Capabilities { huge_tlb: false, huge_tlb_2mb: false, transparent_huge_pages: false, numa: false, sysfs: true };
+
+const HUGE_PAGE_SIZE: usize = LLConfiguration::HUGE_PAGE_SIZE.value();
+
+//  Maximum number of Huge Pages of the region: 1 TB with 1 GB Huge Pages, and 2 GB with those of `small-heap`.
+const MAXIMUM_PAGES: usize = WORDS * 64;
+
+const WORDS: usize = 16;
+
+//  Metrics of the fallbacks.
+static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+//  The region of memory.
+static REGION: Region = Region::new();
+
+//  Region of memory, carved into Huge Pages.
+//
+//  The Huge Pages are rarely allocated, hence a lock suffices, sparing the search of free runs of pages from racing.
+struct Region {
+    lock: AtomicBool,
+    start: AtomicUsize,
+    pages: AtomicUsize,
+    used: [AtomicU64; WORDS],
+}
+
+impl Region {
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+
+        let (lock, start, pages) = (AtomicBool::new(false), AtomicUsize::new(0), AtomicUsize::new(0));
+
+        Self { lock, start, pages, used: [ZERO; WORDS] }
+    }
+
+    //  Provides the `size` bytes located at `address`, returning false if already provided, or too small.
+    //
+    //  Only the aligned Huge Pages are used, up to `MAXIMUM_PAGES`.
+    fn provide(&self, address: usize, size: usize) -> bool {
+        let aligned = match address.checked_add(HUGE_PAGE_SIZE - 1) {
+            Some(aligned) => aligned & !(HUGE_PAGE_SIZE - 1),
+            None => return false,
+        };
+
+        let end = address.saturating_add(size);
+        let pages = (end.saturating_sub(aligned) / HUGE_PAGE_SIZE).min(MAXIMUM_PAGES);
+
+        if pages == 0 {
+            return false;
+        }
+
+        self.locked(|| {
+            if self.pages.load(atomic::Ordering::Relaxed) != 0 {
+                return false;
+            }
+
+            self.start.store(aligned, atomic::Ordering::Relaxed);
+            self.pages.store(pages, atomic::Ordering::Release);
+
+            true
+        })
+    }
+
+    //  Allocates `count` consecutive Huge Pages, returning the address of the first.
+    fn allocate(&self, count: usize) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+
+        self.locked(|| {
+            let pages = self.pages.load(atomic::Ordering::Relaxed);
+            let mut first = 0;
+
+            while first + count <= pages {
+                match (first..first + count).find(|index| self.is_used(*index)) {
+                    Some(used) => first = used + 1,
+                    None => {
+                        (first..first + count).for_each(|index| self.set_used(index, true));
+                        return Some(self.start.load(atomic::Ordering::Relaxed) + first * HUGE_PAGE_SIZE);
+                    },
+                }
+            }
+
+            None
+        })
+    }
+
+    //  Extends the `count` Huge Pages located at `address` by the `extra` following ones, if free.
+    fn extend(&self, address: usize, count: usize, extra: usize) -> bool {
+        let first = match self.index_of(address) {
+            Some(first) => first + count,
+            None => return false,
+        };
+
+        self.locked(|| {
+            let pages = self.pages.load(atomic::Ordering::Relaxed);
+
+            if first + extra > pages || (first..first + extra).any(|index| self.is_used(index)) {
+                return false;
+            }
+
+            (first..first + extra).for_each(|index| self.set_used(index, true));
+
+            true
+        })
+    }
+
+    //  Releases the `count` Huge Pages located at `address`.
+    fn release(&self, address: usize, count: usize) {
+        let first = match self.index_of(address) {
+            Some(first) => first,
+            None => return,
+        };
+
+        self.locked(|| (first..first + count).for_each(|index| self.set_used(index, false)));
+    }
+
+    //  Returns whether `address` lies within the region.
+    #[cfg_attr(not(feature = "system-fallback"), allow(dead_code))]
+    fn contains(&self, address: usize) -> bool { self.index_of(address).is_some() }
+
+    //  Returns the index of the Huge Page containing `address`, if within the region.
+    fn index_of(&self, address: usize) -> Option<usize> {
+        let pages = self.pages.load(atomic::Ordering::Acquire);
+        let start = self.start.load(atomic::Ordering::Relaxed);
+
+        let index = address.checked_sub(start)? / HUGE_PAGE_SIZE;
+
+        if index < pages { Some(index) } else { None }
+    }
+
+    fn is_used(&self, index: usize) -> bool {
+        self.used[index / 64].load(atomic::Ordering::Relaxed) & (1 << (index % 64)) != 0
+    }
+
+    fn set_used(&self, index: usize, used: bool) {
+        let word = &self.used[index / 64];
+        let bit = 1 << (index % 64);
+
+        if used {
+            word.fetch_or(bit, atomic::Ordering::Relaxed);
+        } else {
+            word.fetch_and(!bit, atomic::Ordering::Relaxed);
+        }
+    }
+
+    //  Invokes `f` with the lock held.
+    fn locked<R, F>(&self, f: F) -> R
+        where
+            F: FnOnce() -> R,
+    {
+        const ACQUIRE: atomic::Ordering = atomic::Ordering::Acquire;
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        while self.lock.compare_exchange_weak(false, true, ACQUIRE, RELAXED).is_err() {
+            core::hint::spin_loop();
+        }
+
+        let result = f();
+
+        self.lock.store(false, atomic::Ordering::Release);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn region_provide() {
+    let region = Region::new();
+
+    assert!(!region.provide(HUGE_PAGE_SIZE + 1, HUGE_PAGE_SIZE));
+    assert!(region.provide(HUGE_PAGE_SIZE + 1, 3 * HUGE_PAGE_SIZE));
+    assert!(!region.provide(8 * HUGE_PAGE_SIZE, HUGE_PAGE_SIZE));
+
+    assert!(!region.contains(HUGE_PAGE_SIZE + 1));
+    assert!(region.contains(2 * HUGE_PAGE_SIZE));
+    assert!(region.contains(4 * HUGE_PAGE_SIZE - 1));
+    assert!(!region.contains(4 * HUGE_PAGE_SIZE));
+}
+
+#[test]
+fn region_allocate_release() {
+    let region = Region::new();
+    assert_eq!(None, region.allocate(1));
+
+    assert!(region.provide(4 * HUGE_PAGE_SIZE, 4 * HUGE_PAGE_SIZE));
+
+    assert_eq!(Some(4 * HUGE_PAGE_SIZE), region.allocate(1));
+    assert_eq!(Some(5 * HUGE_PAGE_SIZE), region.allocate(2));
+    assert_eq!(None, region.allocate(2));
+
+    region.release(4 * HUGE_PAGE_SIZE, 1);
+
+    assert!(!region.extend(5 * HUGE_PAGE_SIZE, 2, 2));
+    assert!(region.extend(5 * HUGE_PAGE_SIZE, 2, 1));
+    assert_eq!(Some(4 * HUGE_PAGE_SIZE), region.allocate(1));
+    assert_eq!(None, region.allocate(1));
+
+    region.release(6 * HUGE_PAGE_SIZE, 2);
+
+    assert_eq!(Some(6 * HUGE_PAGE_SIZE), region.allocate(2));
+}
+
+} // mod tests
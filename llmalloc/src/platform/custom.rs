@@ -0,0 +1,441 @@
+//! Implementation of a platform supplied by the embedder, for the OSes llmalloc does not support.
+//!
+//! RTOSes such as QNX or VxWorks offer their own primitives to map memory and store thread-local values: the embedder
+//! implements the `Platform` and `ThreadLocal` traits over those, and registers them once, prior to the first
+//! allocation, with `LLAllocator::with_platform`. Until then, there is no memory, and every allocation fails.
+//!
+//! A single registration is ever accepted, as memory is to be returned to the platform it was allocated from.
+//!
+//! The thread-local storage of the embedder knows nothing of the destructor of the allocator, hence the threads
+//! release their thread-local state with `LLAllocator::release_thread` prior to exiting.
+
+use core::{
+    alloc::Layout,
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ptr::NonNull,
+    sync::atomic::{self, AtomicU8},
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Collapse, HostCapabilities, HugePageReport,
+    HugeTlbPools, PhysicalBuffer, PhysicalSegment, ThreadStack,
+};
+
+use super::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+
+/// Implementation of the Configuration trait, for a platform supplied by the embedder.
+///
+/// The pages are sized as on Linux: the platform is requested multiples of 1 GB Huge Pages, aligned as such, or, with
+/// the `small-heap` feature, of 2 MB Huge Pages, carved into 64 KB Large Pages.
+#[derive(Default)]
+pub struct LLConfiguration;
+
+#[cfg(not(feature = "small-heap"))]
+impl Configuration for LLConfiguration {
+    //  2 MB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+
+    //  1 GB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(1024 * 1024 * 1024) };
+}
+
+#[cfg(feature = "small-heap")]
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
+/// Implementation of the Platform trait, forwarding to the platform registered by the embedder.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+
+    /// Registers the `platform` and `thread_local` storage supplied by the embedder.
+    ///
+    /// Returns false if a platform was already registered.
+    #[cold]
+    pub(crate) fn register(&self, platform: &'static dyn Platform, thread_local: &'static SyncThreadLocal) -> bool {
+        REGISTRATION.register(platform, thread_local)
+    }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> { platform()?.allocate(layout) }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        //  Memory is only ever allocated once registered.
+        if let Some(platform) = platform() {
+            platform.deallocate(pointer, layout);
+        }
+    }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        platform()?.reallocate(pointer, layout, new_size)
+    }
+
+    unsafe fn name_huge(&self, pointer: NonNull<u8>, size: usize) {
+        //  Memory is only ever allocated once registered.
+        if let Some(platform) = platform() {
+            platform.name_huge(pointer, size);
+        }
+    }
+
+    unsafe fn decommit(&self, pointer: NonNull<u8>, size: usize) {
+        //  Memory is only ever allocated once registered.
+        if let Some(platform) = platform() {
+            platform.decommit(pointer, size);
+        }
+    }
+
+    unsafe fn purge_lazy(&self, pointer: NonNull<u8>, size: usize) {
+        //  Memory is only ever allocated once registered.
+        if let Some(platform) = platform() {
+            platform.purge_lazy(pointer, size);
+        }
+    }
+
+    unsafe fn purge_forced(&self, pointer: NonNull<u8>, size: usize) {
+        //  Memory is only ever allocated once registered.
+        if let Some(platform) = platform() {
+            platform.purge_forced(pointer, size);
+        }
+    }
+
+    unsafe fn purge_forced_batch(&self, ranges: &[(NonNull<u8>, usize)]) {
+        //  Memory is only ever allocated once registered.
+        if let Some(platform) = platform() {
+            platform.purge_forced_batch(ranges);
+        }
+    }
+}
+
+impl Platform for LLPlatform {
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex {
+        platform().map_or(NumaNodeIndex::new(0), |platform| platform.current_node())
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        match platform() {
+            Some(platform) => platform.numa_node(node),
+            None if node == 0 => Some(NumaNodeIndex::new(0)),
+            None => None,
+        }
+    }
+
+    #[inline(always)]
+    fn now(&self) -> u64 { platform().map_or(0, |platform| platform.now()) }
+
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        match platform() {
+            Some(platform) => platform.reconcile(page, size, node),
+            None => HugePageReport { address: page.as_ptr() as usize, size, node: node.value(), ..Default::default() },
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> { platform()?.resident(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        platform().is_some_and(|platform| platform.lock(pointer, size))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        platform().is_some_and(|platform| platform.protect(pointer, size, writable))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> { platform()?.map_code(size, mapping) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) {
+        if let Some(platform) = platform() {
+            platform.unmap_code(region);
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        platform().is_some_and(|platform| platform.protect_code(region, executable))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> { platform()?.map_stack(size, prefault) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        if let Some(platform) = platform() {
+            platform.unmap_stack(stack);
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> { platform()?.map_physical(size) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) {
+        if let Some(platform) = platform() {
+            platform.unmap_physical(buffer);
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, buffer: &PhysicalBuffer, report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        platform()?.physical_segments(buffer, report)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool {
+        platform().is_some_and(|platform| platform.environment_flag(name))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { platform().map_or(CAPABILITIES, |platform| platform.capabilities()) }
+
+    #[cold]
+    #[inline(never)]
+    fn use_transparent_huge_pages(&self) -> bool {
+        platform().is_some_and(|platform| platform.use_transparent_huge_pages())
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn huge_tlb_pools(&self) -> HugeTlbPools { platform().map_or_else(HugeTlbPools::default, |p| p.huge_tlb_pools()) }
+
+    #[cold]
+    #[inline(never)]
+    fn collapse(&self, pointer: NonNull<u8>, size: usize) -> Collapse {
+        platform().map_or(Collapse::Unsupported, |platform| platform.collapse(pointer, size))
+    }
+
+    fn interleave(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        platform().is_some_and(|platform| platform.interleave(pointer, size))
+    }
+
+    fn distance(&self, from: NumaNodeIndex, to: NumaNodeIndex) -> Option<u8> {
+        platform().and_then(|platform| platform.distance(from, to))
+    }
+
+    #[cold]
+    fn node_of_page(&self, pointer: NonNull<u8>) -> Option<NumaNodeIndex> {
+        platform().and_then(|platform| platform.node_of_page(pointer))
+    }
+
+    #[cold]
+    fn refresh_topology(&self) -> Option<u32> { platform().and_then(|platform| platform.refresh_topology()) }
+
+    #[cold]
+    #[inline(never)]
+    fn spawn_thread(&self, entry: fn()) -> bool { platform().is_some_and(|platform| platform.spawn_thread(entry)) }
+
+    fn sleep(&self, duration: Duration) {
+        if let Some(platform) = platform() {
+            platform.sleep(duration);
+        }
+    }
+
+    #[cold]
+    fn at_fork(&self, prepare: extern "C" fn(), child: extern "C" fn()) -> bool {
+        platform().is_some_and(|platform| platform.at_fork(prepare, child))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities {
+        if let Some(platform) = platform() {
+            return platform.host_capabilities();
+        }
+
+        let mut host = HostCapabilities::default();
+        host.capabilities = CAPABILITIES;
+        host.numa_nodes = 1;
+
+        host
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn mapping_latency(&self) -> Option<Duration> { platform()?.mapping_latency() }
+
+    //  The fallbacks recorded prior to the registration are kept apart.
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { platform().map_or(&FALLBACKS, |platform| platform.fallbacks()) }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> { platform()?.system_allocate(layout) }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) {
+        //  Memory is only ever allocated once registered.
+        if let Some(platform) = platform() {
+            platform.system_deallocate(pointer);
+        }
+    }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { platform().is_some_and(|platform| platform.owns(pointer)) }
+}
+
+/// Implementation of the ThreadLocal trait, forwarding to the thread-local storage registered by the embedder.
+pub(crate) struct LLThreadLocal<T>(PhantomData<*const T>);
+
+impl<T> LLThreadLocal<T> {
+    /// Creates an uninitialized instance.
+    ///
+    /// The thread-local storage of the embedder has no destructor, hence `destructor` is never invoked, the threads
+    /// rather calling `LLAllocator::release_thread`.
+    ///
+    /// #   Safety
+    ///
+    /// -   Has no requirement, `destructor` being unused.
+    pub(crate) const unsafe fn new(_destructor: *const u8) -> Self { Self(PhantomData) }
+}
+
+impl ThreadLocal<u8> for LLThreadLocal<u8> {
+    #[cold]
+    #[inline(never)]
+    fn prepare(&self) -> bool { thread_local().is_some_and(|thread_local| thread_local.prepare()) }
+
+    #[inline(always)]
+    fn get(&self) -> Option<NonNull<u8>> { thread_local()?.get() }
+
+    #[cold]
+    #[inline(never)]
+    fn set(&self, value: NonNull<u8>) -> bool { thread_local().is_some_and(|thread_local| thread_local.set(value)) }
+
+    #[cold]
+    #[inline(never)]
+    fn clear(&self) -> bool { thread_local().is_some_and(|thread_local| thread_local.clear()) }
+}
+
+unsafe impl<T> Sync for LLThreadLocal<T> {}
+
+/// Thread-local storage supplied by the embedder, shared by all threads.
+pub(crate) type SyncThreadLocal = dyn ThreadLocal<u8> + Sync;
+
+//
+//  Implementation Details
+//
+
+//  Capabilities of the environment, prior to the registration.
+const CAPABILITIES: Capabilities =
+    Capabilities { huge_tlb: false, huge_tlb_2mb: false, transparent_huge_pages: false, numa: false, sysfs: true };
+
+const UNREGISTERED: u8 = 0;
+const REGISTERING: u8 = 1;
+const REGISTERED: u8 = 2;
+
+//  Metrics of the fallbacks, prior to the registration.
+static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+//  The registration of the embedder.
+static REGISTRATION: Registration = Registration::new();
+
+//  Returns the registered platform, if any.
+#[inline(always)]
+fn platform() -> Option<&'static dyn Platform> { REGISTRATION.get().map(|(platform, _)| platform) }
+
+//  Returns the registered thread-local storage, if any.
+#[inline(always)]
+fn thread_local() -> Option<&'static SyncThreadLocal> { REGISTRATION.get().map(|(_, thread_local)| thread_local) }
+
+//  Registration of the platform, and thread-local storage, supplied by the embedder.
+//
+//  The registration is written once, prior to being published, and never modified afterwards.
+struct Registration {
+    state: AtomicU8,
+    registered: UnsafeCell<Option<(&'static dyn Platform, &'static SyncThreadLocal)>>,
+}
+
+impl Registration {
+    const fn new() -> Self { Self { state: AtomicU8::new(UNREGISTERED), registered: UnsafeCell::new(None) } }
+
+    //  Registers `platform` and `thread_local`, returning false if already registered, or being registered.
+    fn register(&self, platform: &'static dyn Platform, thread_local: &'static SyncThreadLocal) -> bool {
+        const ACQUIRE: atomic::Ordering = atomic::Ordering::Acquire;
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        if self.state.compare_exchange(UNREGISTERED, REGISTERING, ACQUIRE, RELAXED).is_err() {
+            return false;
+        }
+
+        //  Safety:
+        //  -   Exclusive, as only the winner of the transition to REGISTERING writes, and none reads until REGISTERED.
+        unsafe { *self.registered.get() = Some((platform, thread_local)) };
+
+        self.state.store(REGISTERED, atomic::Ordering::Release);
+
+        true
+    }
+
+    //  Returns the registration, if published.
+    #[inline(always)]
+    fn get(&self) -> Option<(&'static dyn Platform, &'static SyncThreadLocal)> {
+        if self.state.load(atomic::Ordering::Acquire) != REGISTERED {
+            return None;
+        }
+
+        //  Safety:
+        //  -   The registration is no longer written to, once REGISTERED.
+        unsafe { *self.registered.get() }
+    }
+}
+
+//  Safety:
+//  -   The registration is written once, prior to its publication, see `register`.
+unsafe impl Sync for Registration {}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn registration_once() {
+    static PLATFORM: LLPlatform = LLPlatform::new();
+    static THREAD_LOCAL: LLThreadLocal<u8> = unsafe { LLThreadLocal::new(core::ptr::null()) };
+
+    let registration = Registration::new();
+    assert!(registration.get().is_none());
+
+    assert!(registration.register(&PLATFORM, &THREAD_LOCAL));
+    assert!(registration.get().is_some());
+
+    assert!(!registration.register(&PLATFORM, &THREAD_LOCAL));
+}
+
+} // mod tests
@@ -0,0 +1,116 @@
+//! Detection of the capabilities of the environment, on first use, for the Unix platforms detecting them.
+//!
+//! The platforms only differ in how their capabilities are detected, the detection being resolved once, and possibly
+//! downgraded thereafter, as implemented here. None of them has a `/sys` to be missed, nor a HugeTLB pool sized in
+//! 2 MB pages.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::Capabilities;
+
+/// Capabilities of the environment, detected on first use.
+pub(super) struct Detector {
+    bits: AtomicU8,
+    detect: fn() -> Capabilities,
+}
+
+impl Detector {
+    /// Creates an instance, undetected, to be detected by `detect`.
+    pub(super) const fn new(detect: fn() -> Capabilities) -> Self { Self { bits: AtomicU8::new(0), detect } }
+
+    /// Returns the capabilities, detecting them if not yet detected.
+    #[inline(always)]
+    pub(super) fn get(&self) -> Capabilities {
+        let bits = match self.bits.load(Ordering::Relaxed) {
+            0 => self.resolve(),
+            bits => bits,
+        };
+
+        Capabilities {
+            huge_tlb: bits & HUGE_TLB != 0,
+            huge_tlb_2mb: false,
+            transparent_huge_pages: bits & TRANSPARENT_HUGE_PAGES != 0,
+            numa: bits & NUMA != 0,
+            sysfs: true,
+        }
+    }
+
+    /// Downgrades the HugeTLB pages, after a failure to map a Huge Page with them.
+    #[cold]
+    pub(super) fn downgrade_huge_tlb(&self) {
+        if self.bits.load(Ordering::Relaxed) == 0 {
+            self.resolve();
+        }
+
+        self.bits.fetch_and(!HUGE_TLB, Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self) -> u8 {
+        let detected = encode((self.detect)());
+
+        //  A concurrent resolution, or downgrade, takes precedence.
+        match self.bits.compare_exchange(0, detected, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => detected,
+            Err(current) => current,
+        }
+    }
+}
+
+//
+//  Implementation Details
+//
+
+const DETECTED: u8 = 1;
+const HUGE_TLB: u8 = 2;
+const TRANSPARENT_HUGE_PAGES: u8 = 4;
+const NUMA: u8 = 8;
+
+fn encode(capabilities: Capabilities) -> u8 {
+    let mut bits = DETECTED;
+
+    if capabilities.huge_tlb {
+        bits |= HUGE_TLB;
+    }
+
+    if capabilities.transparent_huge_pages {
+        bits |= TRANSPARENT_HUGE_PAGES;
+    }
+
+    if capabilities.numa {
+        bits |= NUMA;
+    }
+
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+fn detect() -> Capabilities {
+    Capabilities { huge_tlb: true, huge_tlb_2mb: false, transparent_huge_pages: false, numa: true, sysfs: true }
+}
+
+#[test]
+fn detector_stable() {
+    let detector = Detector::new(detect);
+
+    let detected = detector.get();
+
+    assert_eq!(detect(), detected);
+    assert_eq!(detected, detector.get());
+}
+
+#[test]
+fn detector_downgrade_huge_tlb() {
+    let detector = Detector::new(detect);
+
+    detector.downgrade_huge_tlb();
+
+    assert_eq!(Capabilities { huge_tlb: false, ..detect() }, detector.get());
+}
+
+} // mod tests
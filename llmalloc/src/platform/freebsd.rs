@@ -0,0 +1,350 @@
+//! Implementation of FreeBSD specific calls.
+
+mod capabilities;
+
+use core::{
+    alloc::Layout,
+    mem,
+    ptr::{self, NonNull},
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Fallback, HostCapabilities, HugePageReport,
+    PhysicalBuffer, PhysicalSegment, ThreadStack,
+};
+
+use super::{detector::Detector, mmap, unix, NumaNodeIndex, Configuration, Platform};
+
+use super::unix::FALLBACKS;
+
+pub(crate) use super::unix::LLConfiguration;
+
+/// Implementation of the Platform trait, for FreeBSD.
+///
+/// FreeBSD has no HugeTLB pool; instead its kernel transparently promotes suitably aligned, fully populated, ranges of
+/// memory to superpages, hence the memory is mapped aligned, with `MAP_ALIGNED`, and its promotion is left to the
+/// kernel. The NUMA domains are read from the CPU sets of the kernel, no libnuma being available.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> { mmap::allocate(self, layout, mmap_normal) }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) { mmap::deallocate(pointer, layout) }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        //  `MAP_EXCL` fails rather than replace a mapping.
+        mmap::reallocate(self, pointer, layout, new_size, libc::MAP_FIXED | libc::MAP_EXCL)
+    }
+}
+
+impl Platform for LLPlatform {
+    #[cold]
+    #[inline(never)]
+    fn current_node(&self) -> NumaNodeIndex {
+        //  Without NUMA, a single socket is shared by all threads.
+        if !CAPABILITIES.get().numa {
+            return NumaNodeIndex::new(0);
+        }
+
+        //  Safety:
+        //  -   `sched_getcpu` has no precondition.
+        let cpu = unsafe { libc::sched_getcpu() };
+
+        let node = if cpu >= 0 { domain_of_cpu(cpu as usize) } else { None };
+
+        match node {
+            Some(node) => NumaNodeIndex::new(node),
+            None => {
+                FALLBACKS.record(Fallback::UnknownNode);
+                NumaNodeIndex::new(0)
+            },
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        //  Without NUMA, the single node is node 0.
+        if !CAPABILITIES.get().numa {
+            return if node == 0 { Some(NumaNodeIndex::new(0)) } else { None };
+        }
+
+        if node >= number_of_domains()? {
+            return None;
+        }
+
+        Some(NumaNodeIndex::new(node))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 { unix::now() }
+
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        let page_size = mmap::os_page_size().value();
+
+        let mut report = HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: page_size,
+            ..HugePageReport::default()
+        };
+
+        let mut small_pages = false;
+
+        let queried = mmap::for_each_page(page.as_ptr() as usize, size, |state| {
+            if state & MINCORE_INCORE == 0 {
+                return;
+            }
+
+            report.resident += page_size;
+
+            if state & MINCORE_SUPER != 0 {
+                report.anonymous_huge += page_size;
+            } else {
+                small_pages = true;
+            }
+        });
+
+        if queried.is_none() {
+            return HugePageReport { resident: 0, anonymous_huge: 0, ..report };
+        }
+
+        if !small_pages && report.anonymous_huge > 0 {
+            report.kernel_page_size = SUPERPAGE_SIZE;
+        }
+
+        //  The domain of a page is not exposed to user space, hence the pages are reported as local.
+        report.local_pages = report.resident / page_size;
+        report.resident = report.resident.min(size);
+
+        report
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> { mmap::resident(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool { mmap::lock(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        mmap::protect(pointer, size, writable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        mmap::map_code(size, mapping, mmap_dual)
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) { mmap::unmap_code(region) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        mmap::protect_code(region, executable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> { mmap::map_stack(size, prefault) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, size) = stack.mapping();
+
+        mmap::munmap_deallocate(pointer.as_ptr(), size);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> {
+        let size = size.max(1).checked_add(SUPERPAGE_SIZE - 1)? & !(SUPERPAGE_SIZE - 1);
+
+        //  Locking the memory populates it fully, which promotes it to superpages if enabled.
+        let pointer = mmap::mmap_allocate(size, libc::MAP_ALIGNED_SUPER)
+            .or_else(|| mmap::mmap_aligned(size, SUPERPAGE_SIZE, 0))?;
+
+        //  Safety:
+        //  -   `pointer` points to a `mmap`ed area of `size` bytes, not in use.
+        unsafe { mmap::lock_physical(pointer, size, false) }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) {
+        mmap::munmap_deallocate(buffer.pointer().as_ptr(), buffer.size());
+    }
+
+    //  FreeBSD does not expose the physical addresses to user space.
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool { unix::environment_flag(name) }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES.get() }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities { capabilities::detect_host(CAPABILITIES.get()) }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { unix::mapping_latency() }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> { unix::system_allocate(layout) }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) { unix::system_deallocate(pointer) }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { unix::owns(pointer) }
+}
+
+//  Capabilities of the environment.
+static CAPABILITIES: Detector = Detector::new(capabilities::detect);
+
+const SUPERPAGE_SIZE: usize = 2 * 1024 * 1024;
+
+//  The `which` selector of `cpuset_getaffinity` designating a NUMA domain, missing from `libc`.
+const CPU_WHICH_DOMAIN: libc::cpuwhich_t = 6;
+
+const MINCORE_INCORE: u8 = libc::MINCORE_INCORE as u8;
+
+//  The index of the superpage size backing the page, a single bit until FreeBSD 13, and two bits since.
+const MINCORE_SUPER: u8 = 0x60;
+
+//  Returns the number of NUMA domains, or None if unknown.
+fn number_of_domains() -> Option<u32> {
+    const NDOMAINS: &[u8] = b"vm.ndomains\0";
+
+    let mut domains: libc::c_int = 0;
+    let mut length = mem::size_of::<libc::c_int>();
+
+    //  Safety:
+    //  -   `NDOMAINS` is NUL-terminated.
+    //  -   `domains` is valid for writes of `length` bytes.
+    let result = unsafe {
+        libc::sysctlbyname(
+            NDOMAINS.as_ptr() as *const libc::c_char,
+            &mut domains as *mut _ as *mut libc::c_void,
+            &mut length,
+            ptr::null(),
+            0,
+        )
+    };
+
+    if result == 0 && domains > 0 { Some(domains as u32) } else { None }
+}
+
+//  Returns the NUMA domain of `cpu`, or None if unknown.
+//
+//  The CPU set of each domain is looked up in turn, there being few domains, and this being a cold path.
+fn domain_of_cpu(cpu: usize) -> Option<u32> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return None;
+    }
+
+    (0..number_of_domains()?).find(|domain| {
+        //  Safety:
+        //  -   `cpuset_t` is plain old data.
+        let mut set: libc::cpuset_t = unsafe { mem::zeroed() };
+
+        //  Safety:
+        //  -   `set` is valid for writes of `size_of::<cpuset_t>()` bytes.
+        let result = unsafe {
+            libc::cpuset_getaffinity(
+                libc::CPU_LEVEL_WHICH,
+                CPU_WHICH_DOMAIN,
+                *domain as libc::id_t,
+                mem::size_of::<libc::cpuset_t>(),
+                &mut set,
+            )
+        };
+
+        //  Safety:
+        //  -   `cpu` is less than `CPU_SETSIZE`.
+        result == 0 && unsafe { libc::CPU_ISSET(cpu, &set) }
+    })
+}
+
+//  Attempts to allocate the required size, aligned, requesting superpages if enabled.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_normal(size: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    let aligned = |size| mmap::mmap_allocate(size, mmap_alignment(ALIGNMENT.value()));
+
+    //  Being aligned on `HUGE_PAGE_SIZE`, the memory is also aligned on superpages.
+    let backing = if CAPABILITIES.get().transparent_huge_pages {
+        Fallback::TransparentHugePageMapping
+    } else {
+        Fallback::NormalPageMapping
+    };
+
+    mmap::mmap_huge_page(size, aligned, backing)
+}
+
+//  Returns the flag requesting `mmap` to align the mapping on `alignment`, a power of 2.
+//
+//  `MAP_ALIGNED_SUPER` is preferred for the size of superpages, as it also aligns sizes which are not a multiple.
+fn mmap_alignment(alignment: usize) -> libc::c_int {
+    debug_assert!(alignment.is_power_of_two());
+
+    if alignment == SUPERPAGE_SIZE {
+        libc::MAP_ALIGNED_SUPER
+    } else {
+        libc::MAP_ALIGNED(alignment.trailing_zeros() as libc::c_int)
+    }
+}
+
+//  Maps `size` bytes of shared memory twice, read-write then read-execute, returning both views.
+//
+//  The memory is backed by an anonymous shared memory object, closed once mapped, the mappings keeping it alive.
+fn mmap_dual(size: usize) -> Option<(NonNull<u8>, NonNull<u8>)> {
+    //  Safety:
+    //  -   `SHM_ANON` designates an anonymous object, rather than a name.
+    let fd = unsafe { libc::shm_open(libc::SHM_ANON, libc::O_RDWR | libc::O_CLOEXEC, 0o600) };
+
+    if fd < 0 {
+        return None;
+    }
+
+    mmap::mmap_dual(fd, size)
+}
@@ -0,0 +1,90 @@
+//! Detection of the capabilities of the environment.
+//!
+//! The capabilities are detected once, on first use, from `sysctl`: FreeBSD offers no HugeTLB pool, its superpages
+//! being promoted transparently by the kernel if `vm.pmap.pg_ps_enabled`, and NUMA is available if the kernel reports
+//! several memory domains, as per `vm.ndomains`.
+
+use core::{
+    mem,
+    ptr,
+};
+
+use crate::{Capabilities, HostCapabilities, TransparentHugePagesMode};
+
+use super::{mmap, number_of_domains, unix, SUPERPAGE_SIZE};
+
+/// Detects the capabilities of the environment.
+#[cold]
+pub(super) fn detect() -> Capabilities {
+    //  FreeBSD has no HugeTLB pool.
+    Capabilities {
+        huge_tlb: false,
+        huge_tlb_2mb: false,
+        transparent_huge_pages: superpages_enabled().unwrap_or(false),
+        numa: number_of_domains().unwrap_or(1) > 1,
+        sysfs: true,
+    }
+}
+
+/// Detects the capabilities of the host, in details, given the `capabilities` selected.
+///
+/// Unlike the selected capabilities, the details are detected anew on each call.
+#[cold]
+pub(super) fn detect_host(capabilities: Capabilities) -> HostCapabilities {
+    let mut host = unix::host_capabilities(capabilities, mmap::os_page_size().value(), mmap::mlock_limit());
+
+    host.transparent_huge_pages = match superpages_enabled() {
+        Some(true) => {
+            host.add_huge_page_size(SUPERPAGE_SIZE);
+            TransparentHugePagesMode::Always
+        },
+        Some(false) => TransparentHugePagesMode::Never,
+        None => TransparentHugePagesMode::Unknown,
+    };
+
+    host.numa_nodes = if capabilities.numa { number_of_domains().unwrap_or(1) } else { 1 };
+
+    host
+}
+
+//
+//  Implementation Details
+//
+
+const PG_PS_ENABLED: &[u8] = b"vm.pmap.pg_ps_enabled\0";
+
+//  Returns whether the kernel promotes memory to superpages, or None if unknown.
+fn superpages_enabled() -> Option<bool> {
+    let mut enabled: libc::c_int = 0;
+    let mut length = mem::size_of::<libc::c_int>();
+
+    //  Safety:
+    //  -   `PG_PS_ENABLED` is NUL-terminated.
+    //  -   `enabled` is valid for writes of `length` bytes.
+    let result = unsafe {
+        libc::sysctlbyname(
+            PG_PS_ENABLED.as_ptr() as *const libc::c_char,
+            &mut enabled as *mut _ as *mut libc::c_void,
+            &mut length,
+            ptr::null(),
+            0,
+        )
+    };
+
+    if result == 0 { Some(enabled != 0) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn detect_stable() {
+    let detected = detect();
+
+    assert!(!detected.huge_tlb);
+    assert_eq!(detected, detect());
+}
+
+} // mod tests
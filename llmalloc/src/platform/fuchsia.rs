@@ -0,0 +1,525 @@
+//! Implementation of Fuchsia specific calls, over the Zircon kernel.
+//!
+//! Zircon offers no `mmap` of its own: the memory is held by Virtual Memory Objects, VMOs, created with
+//! `zx_vmo_create`, then mapped into the root Virtual Memory Address Region, VMAR, of the process, which aligns the
+//! mappings as requested. The handle of each VMO is closed as soon as the VMO is mapped, the mapping keeping it alive.
+//!
+//! Zircon backs the VMOs with normal pages, and exposes the NUMA topology to no component: `zx_system_get_num_cpus`
+//! is all there is, and a single node serves all the CPUs it reports.
+
+use core::{
+    alloc::Layout,
+    ffi::c_void,
+    ptr::{self, NonNull},
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Fallback, HostCapabilities, HugePageReport,
+    PhysicalBuffer, PhysicalSegment, ThreadStack, TransparentHugePagesMode, UnmapFailure,
+};
+
+use crate::unmapping::UNMAPPING;
+
+use super::{unix, NumaNodeIndex, Configuration, Platform};
+
+use super::unix::FALLBACKS;
+
+pub(crate) use super::unix::LLConfiguration;
+
+/// Implementation of the Platform trait, for Fuchsia.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        unix::map_huge_page(layout, vmo_map_huge_page, vmar_unmap)
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        unix::release(pointer.as_ptr(), layout.size());
+
+        vmar_unmap(pointer.as_ptr(), layout.size());
+    }
+
+    //  Only shrinks in place: a VMO mapped anew next to the existing one could only be placed relative to the base of
+    //  the root VMAR, and a VMO cannot be moved.
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        if new_size % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() || new_size > layout.size() {
+            return None;
+        }
+
+        let size = layout.size();
+
+        if new_size < size {
+            unix::release(pointer.as_ptr().add(new_size), size - new_size);
+
+            vmar_unmap(pointer.as_ptr().add(new_size), size - new_size);
+        }
+
+        Some(pointer)
+    }
+}
+
+impl Platform for LLPlatform {
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex { NumaNodeIndex::new(0) }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node == 0 { Some(NumaNodeIndex::new(0)) } else { None }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 {
+        //  Safety:
+        //  -   `zx_clock_get_monotonic` has no precondition.
+        let now = unsafe { zx_clock_get_monotonic() };
+
+        now.max(0) as u64
+    }
+
+    //  Zircon exposes neither the residency, nor the backing, of the pages of a range, and all pages reside on the
+    //  single node.
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: os_page_size().value(),
+            resident: 0,
+            anonymous_huge: 0,
+            local_pages: 0,
+            foreign_pages: 0,
+        }
+    }
+
+    //  Zircon only reports the committed bytes of whole VMOs, and the VMOs are not kept.
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, _pointer: NonNull<u8>, _size: usize) -> Option<usize> { None }
+
+    //  Zircon never pages out the committed pages of a VMO, hence committing them suffices.
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        let address = pointer.as_ptr() as usize;
+
+        //  Safety:
+        //  -   Committing the pages does not access their content.
+        let result =
+            unsafe { zx_vmar_op_range(zx_vmar_root_self(), ZX_VMAR_OP_COMMIT, address, size, ptr::null_mut(), 0) };
+
+        result == ZX_OK
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        let options = if writable { ZX_VM_PERM_READ | ZX_VM_PERM_WRITE } else { ZX_VM_PERM_READ };
+
+        zx_vmar_protect(zx_vmar_root_self(), options, pointer.as_ptr() as usize, size) == ZX_OK
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        let page_size = os_page_size().value();
+        let size = size.max(1).checked_add(page_size - 1)? & !(page_size - 1);
+
+        let (writable, executable) = vmo_map_code(size, mapping)?;
+
+        Some(CodeRegion::new(writable, executable, size, mapping))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) {
+        vmar_unmap(region.writable().as_ptr(), region.size());
+
+        if region.mapping() == CodeMapping::Dual {
+            vmar_unmap(region.executable().as_ptr(), region.size());
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        debug_assert!(region.mapping() == CodeMapping::Flip);
+
+        let options =
+            if executable { ZX_VM_PERM_READ | ZX_VM_PERM_EXECUTE } else { ZX_VM_PERM_READ | ZX_VM_PERM_WRITE };
+
+        zx_vmar_protect(zx_vmar_root_self(), options, region.writable().as_ptr() as usize, region.size()) == ZX_OK
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> {
+        const ALIGNMENT: PowerOf2 = LLConfiguration::LARGE_PAGE_SIZE;
+
+        //  The guard spans a whole Large Page, so that the usable area of the aligned child VMAR is aligned too; only
+        //  address space is reserved for it.
+        let guard_size = ALIGNMENT.value();
+        let size = size.max(1).checked_add(ALIGNMENT.value() - 1)? & !(ALIGNMENT.value() - 1);
+
+        let guard = vmar_map_stack(guard_size, size)?;
+        let bottom = guard.as_ptr() as usize + guard_size;
+
+        if prefault {
+            for offset in (0..size).step_by(guard_size) {
+                //  Safety:
+                //  -   `bottom + offset` is within the usable area, writable and not in use.
+                unsafe { ptr::write_volatile((bottom + offset) as *mut u8, 0) };
+            }
+        }
+
+        Some(ThreadStack::new(guard, guard_size, size))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, size) = stack.mapping();
+
+        //  Unmapping the range also destroys the child VMAR reserving the guard page.
+        vmar_unmap(pointer.as_ptr(), size);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> {
+        const ALIGNMENT: usize = 2 * 1024 * 1024;
+
+        let size = size.max(1).checked_add(ALIGNMENT - 1)? & !(ALIGNMENT - 1);
+
+        //  `ZX_VM_MAP_RANGE` commits the pages immediately.
+        let pointer = vmo_map(size, ALIGNMENT, ZX_VM_MAP_RANGE)?;
+
+        if !self.lock(pointer, size) {
+            //  Safety:
+            //  -   `pointer` points to a mapped area of `size` bytes, not in use.
+            unsafe { vmar_unmap(pointer.as_ptr(), size) };
+            return None;
+        }
+
+        Some(PhysicalBuffer::new(pointer, size, false))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) {
+        vmar_unmap(buffer.pointer().as_ptr(), buffer.size());
+    }
+
+    //  The physical addresses are only exposed to the drivers pinning VMOs through a Bus Transaction Initiator.
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool { unix::environment_flag(name) }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities {
+        //  Zircon never pages out committed pages, hence there is no limit to locking them.
+        let mut host = unix::host_capabilities(CAPABILITIES, os_page_size().value(), None);
+
+        host.transparent_huge_pages = TransparentHugePagesMode::Never;
+
+        host
+    }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { unix::mapping_latency() }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> { unix::system_allocate(layout) }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) { unix::system_deallocate(pointer) }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { unix::owns(pointer) }
+}
+
+//
+//  Implementation Details
+//
+
+type Handle = u32;
+type Status = i32;
+
+const ZX_OK: Status = 0;
+const ZX_HANDLE_INVALID: Handle = 0;
+
+const ZX_VM_PERM_READ: u32 = 1 << 0;
+const ZX_VM_PERM_WRITE: u32 = 1 << 1;
+const ZX_VM_PERM_EXECUTE: u32 = 1 << 2;
+const ZX_VM_SPECIFIC: u32 = 1 << 4;
+const ZX_VM_CAN_MAP_SPECIFIC: u32 = 1 << 6;
+const ZX_VM_CAN_MAP_READ: u32 = 1 << 7;
+const ZX_VM_CAN_MAP_WRITE: u32 = 1 << 8;
+const ZX_VM_MAP_RANGE: u32 = 1 << 10;
+
+//  The alignment of a mapping is encoded as its base 2 logarithm, from 1 KB to 4 GB, shifted by `ZX_VM_ALIGN_BASE`.
+const ZX_VM_ALIGN_BASE: u32 = 24;
+const ZX_VM_ALIGN_MIN: u32 = 10;
+const ZX_VM_ALIGN_MAX: u32 = 32;
+
+const ZX_VMAR_OP_COMMIT: u32 = 1;
+
+#[link(name = "zircon")]
+extern "C" {
+    fn zx_vmar_root_self() -> Handle;
+
+    fn zx_vmo_create(size: u64, options: u32, out: *mut Handle) -> Status;
+
+    fn zx_vmo_replace_as_executable(handle: Handle, vmex: Handle, out: *mut Handle) -> Status;
+
+    fn zx_vmar_allocate(
+        parent_vmar: Handle,
+        options: u32,
+        offset: usize,
+        size: usize,
+        child_vmar: *mut Handle,
+        child_addr: *mut usize,
+    ) -> Status;
+
+    fn zx_vmar_map(
+        handle: Handle,
+        options: u32,
+        vmar_offset: usize,
+        vmo: Handle,
+        vmo_offset: u64,
+        len: usize,
+        mapped_addr: *mut usize,
+    ) -> Status;
+
+    fn zx_vmar_unmap(handle: Handle, addr: usize, len: usize) -> Status;
+
+    fn zx_vmar_protect(handle: Handle, options: u32, addr: usize, len: usize) -> Status;
+
+    fn zx_vmar_op_range(handle: Handle, op: u32, addr: usize, len: usize, buffer: *mut c_void, buffer_size: usize)
+        -> Status;
+
+    fn zx_handle_close(handle: Handle) -> Status;
+
+    fn zx_clock_get_monotonic() -> i64;
+
+    fn zx_system_get_page_size() -> u32;
+}
+
+//  Capabilities of the environment, which are not detected: no `/sys` is expected, hence none is missed.
+const CAPABILITIES: Capabilities =
+    Capabilities { huge_tlb: false, huge_tlb_2mb: false, transparent_huge_pages: false, numa: false, sysfs: true };
+
+//  Maps a Huge Page of `size` bytes, with normal pages, aligned on its size.
+fn vmo_map_huge_page(size: usize) -> Option<NonNull<u8>> {
+    let candidate = vmo_map(size, LLConfiguration::HUGE_PAGE_SIZE.value(), 0);
+
+    FALLBACKS.record(if candidate.is_some() { Fallback::NormalPageMapping } else { Fallback::MmapFailure });
+
+    candidate
+}
+
+//  Returns the option aligning a mapping on `alignment`, a power of 2, or None if Zircon cannot align on it.
+fn align_option(alignment: usize) -> Option<u32> {
+    debug_assert!(alignment.is_power_of_two());
+
+    match alignment.trailing_zeros() {
+        //  Any mapping is aligned on a page, at least.
+        shift if alignment <= os_page_size().value() || shift < ZX_VM_ALIGN_MIN => Some(0),
+        shift if shift <= ZX_VM_ALIGN_MAX => Some(shift << ZX_VM_ALIGN_BASE),
+        _ => None,
+    }
+}
+
+//  Creates a VMO of `size` bytes, and maps it read-write in the root VMAR, aligned on `alignment`, with the additional
+//  `options`.
+fn vmo_map(size: usize, alignment: usize, options: u32) -> Option<NonNull<u8>> {
+    let vmo = vmo_create(size)?;
+
+    let result = vmar_map(vmo, ZX_VM_PERM_READ | ZX_VM_PERM_WRITE | align_option(alignment)? | options, size);
+
+    //  Safety:
+    //  -   `vmo` is a valid handle, owned by this function, and the mapping keeps the VMO alive.
+    unsafe { zx_handle_close(vmo) };
+
+    result
+}
+
+//  Maps `size` bytes for executable code, returning both views, identical unless `CodeMapping::Dual`.
+//
+//  The VMO must be executable to be mapped as such, which requires the process to be allowed to mark VMOs as
+//  executable, as per its job policy.
+fn vmo_map_code(size: usize, mapping: CodeMapping) -> Option<(NonNull<u8>, NonNull<u8>)> {
+    let vmo = vmo_create(size)?;
+    let mut executable = ZX_HANDLE_INVALID;
+
+    //  Safety:
+    //  -   `vmo` is a valid handle, consumed by the call, whether it succeeds or not.
+    //  -   `executable` is valid for writes.
+    if unsafe { zx_vmo_replace_as_executable(vmo, ZX_HANDLE_INVALID, &mut executable) } != ZX_OK {
+        return None;
+    }
+
+    let views = match mapping {
+        CodeMapping::Dual => vmar_map(executable, ZX_VM_PERM_READ | ZX_VM_PERM_WRITE, size).and_then(|writable| {
+            match vmar_map(executable, ZX_VM_PERM_READ | ZX_VM_PERM_EXECUTE, size) {
+                Some(view) => Some((writable, view)),
+                None => {
+                    //  Safety:
+                    //  -   `writable` points to a mapped area of `size` bytes, not in use.
+                    unsafe { vmar_unmap(writable.as_ptr(), size) };
+                    None
+                },
+            }
+        }),
+        CodeMapping::Flip => vmar_map(executable, ZX_VM_PERM_READ | ZX_VM_PERM_WRITE, size).map(|view| (view, view)),
+    };
+
+    //  Safety:
+    //  -   `executable` is a valid handle, owned by this function, and the mappings keep the VMO alive.
+    unsafe { zx_handle_close(executable) };
+
+    views
+}
+
+//  Maps a stack of `size` bytes, preceded by a guard of `guard_size` bytes, returning the start of the guard.
+//
+//  The stack is mapped within a child VMAR spanning both and aligned on `guard_size`, in which the guard stays
+//  unmapped, so that no other mapping is ever placed right below the stack.
+fn vmar_map_stack(guard_size: usize, size: usize) -> Option<NonNull<u8>> {
+    let total = guard_size.checked_add(size)?;
+    let options = ZX_VM_CAN_MAP_READ | ZX_VM_CAN_MAP_WRITE | ZX_VM_CAN_MAP_SPECIFIC | align_option(guard_size)?;
+
+    let mut child = ZX_HANDLE_INVALID;
+    let mut base = 0;
+
+    //  Safety:
+    //  -   `child` and `base` are valid for writes.
+    if unsafe { zx_vmar_allocate(zx_vmar_root_self(), options, 0, total, &mut child, &mut base) } != ZX_OK {
+        return None;
+    }
+
+    let vmo = vmo_create(size);
+
+    let mapped = vmo.map(|vmo| {
+        let mut address = 0;
+        let options = ZX_VM_PERM_READ | ZX_VM_PERM_WRITE | ZX_VM_SPECIFIC;
+
+        //  Safety:
+        //  -   `child` and `vmo` are valid handles.
+        //  -   `[guard_size, total)` lies within the child VMAR, and is not mapped yet.
+        let result = unsafe { zx_vmar_map(child, options, guard_size, vmo, 0, size, &mut address) };
+
+        //  Safety:
+        //  -   `vmo` is a valid handle, owned by this function, and the mapping keeps the VMO alive.
+        unsafe { zx_handle_close(vmo) };
+
+        result == ZX_OK
+    });
+
+    //  The child VMAR is kept alive by its parent, until destroyed by unmapping its range from the root VMAR.
+    //
+    //  Safety:
+    //  -   `child` is a valid handle, owned by this function.
+    unsafe { zx_handle_close(child) };
+
+    if mapped != Some(true) {
+        //  Safety:
+        //  -   `[base, base + total)` is the range of the child VMAR, not in use.
+        unsafe { vmar_unmap(base as *mut u8, total) };
+        return None;
+    }
+
+    NonNull::new(base as *mut u8)
+}
+
+//  Wrapper around `zx_vmo_create`.
+fn vmo_create(size: usize) -> Option<Handle> {
+    let mut vmo = ZX_HANDLE_INVALID;
+
+    //  Safety:
+    //  -   `vmo` is valid for writes.
+    if unsafe { zx_vmo_create(size as u64, 0, &mut vmo) } != ZX_OK {
+        return None;
+    }
+
+    Some(vmo)
+}
+
+//  Wrapper around `zx_vmar_map`, mapping the first `size` bytes of `vmo` anywhere in the root VMAR.
+fn vmar_map(vmo: Handle, options: u32, size: usize) -> Option<NonNull<u8>> {
+    let mut address = 0;
+
+    //  Safety:
+    //  -   Without `ZX_VM_SPECIFIC`, the mapping never replaces an existing one.
+    //  -   `address` is valid for writes.
+    let result = unsafe { zx_vmar_map(zx_vmar_root_self(), options, 0, vmo, 0, size, &mut address) };
+
+    if result != ZX_OK {
+        return None;
+    }
+
+    NonNull::new(address as *mut u8)
+}
+
+//  Returns the size of the OS pages.
+fn os_page_size() -> PowerOf2 {
+    const DEFAULT: PowerOf2 = unsafe { PowerOf2::new_unchecked(4096) };
+
+    //  Safety:
+    //  -   `zx_system_get_page_size` has no precondition.
+    let size = unsafe { zx_system_get_page_size() };
+
+    PowerOf2::new(size as usize).unwrap_or(DEFAULT)
+}
+
+//  Wrapper around `zx_vmar_unmap`.
+//
+//  #   Safety
+//
+//  -   Assumes that `addr` points to a mapped area of at least `size` bytes.
+//  -   Assumes that the range `[addr, addr + size)` is no longer in use.
+unsafe fn vmar_unmap(addr: *mut u8, size: usize) {
+    let result = zx_vmar_unmap(zx_vmar_root_self(), addr as usize, size);
+
+    if result != ZX_OK {
+        let failure = UnmapFailure { address: addr as usize, size, error: result };
+
+        //  Should the memory fail to be unmapped, it is leaked, unless the process is aborted.
+        UNMAPPING.fail(&FALLBACKS, failure, || unsafe { libc::abort() });
+    }
+}
@@ -0,0 +1,369 @@
+//! Implementation of illumos, and Solaris, specific calls.
+//!
+//! The NUMA topology is exposed as locality groups, queried with the `lgrp_*` functions rather than libnuma, and the
+//! large pages are applied by the kernel to suitably aligned mappings, hence the memory is mapped with `MAP_ALIGN`.
+
+mod capabilities;
+
+use core::{
+    alloc::Layout,
+    ptr::NonNull,
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Fallback, HostCapabilities, HugePageReport,
+    PhysicalBuffer, PhysicalSegment, ThreadStack,
+};
+
+use super::{detector::Detector, mmap, shm, unix, NumaNodeIndex, Configuration, Platform};
+
+use super::unix::FALLBACKS;
+
+pub(crate) use super::unix::LLConfiguration;
+
+/// Implementation of the Platform trait, for illumos.
+///
+/// illumos has no HugeTLB pool; instead its kernel backs suitably aligned anonymous mappings with large pages, as
+/// per Multiple Page Size Support, hence the memory is mapped aligned, with `MAP_ALIGN`. The NUMA nodes are the leaf
+/// locality groups, numbered from 1, the root being 0; node `n` is therefore locality group `n + 1`.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> { mmap::allocate(self, layout, mmap_normal) }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) { mmap::deallocate(pointer, layout) }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        //  Without `MAP_EXCL`, the address is only a hint.
+        mmap::reallocate(self, pointer, layout, new_size, 0)
+    }
+}
+
+impl Platform for LLPlatform {
+    #[cold]
+    #[inline(never)]
+    fn current_node(&self) -> NumaNodeIndex {
+        //  Without NUMA, a single socket is shared by all threads.
+        if !CAPABILITIES.get().numa {
+            return NumaNodeIndex::new(0);
+        }
+
+        match home_lgroup().and_then(node_of_lgroup) {
+            Some(node) => NumaNodeIndex::new(node),
+            None => {
+                FALLBACKS.record(Fallback::UnknownNode);
+                NumaNodeIndex::new(0)
+            },
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        //  Without NUMA, the single node is node 0.
+        if !CAPABILITIES.get().numa {
+            return if node == 0 { Some(NumaNodeIndex::new(0)) } else { None };
+        }
+
+        if node >= number_of_nodes() {
+            return None;
+        }
+
+        Some(NumaNodeIndex::new(node))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 { unix::now() }
+
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        let page_size = mmap::os_page_size().value();
+        let numa = CAPABILITIES.get().numa;
+
+        let mut report = HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: page_size,
+            ..HugePageReport::default()
+        };
+
+        let mut smallest_backing = usize::MAX;
+
+        let queried = for_each_page(page.as_ptr() as usize, size, |lgroup, backing| {
+            report.resident += page_size;
+            smallest_backing = smallest_backing.min(backing);
+
+            if backing > page_size {
+                report.anonymous_huge += page_size;
+            }
+
+            //  Without NUMA, all pages reside on the single node.
+            if !numa || node_of_lgroup(lgroup) == Some(node.value()) {
+                report.local_pages += 1;
+            } else {
+                report.foreign_pages += 1;
+            }
+        });
+
+        if queried.is_none() {
+            return HugePageReport { resident: 0, anonymous_huge: 0, local_pages: 0, foreign_pages: 0, ..report };
+        }
+
+        if report.resident > 0 {
+            report.kernel_page_size = smallest_backing.max(page_size);
+        }
+
+        report.resident = report.resident.min(size);
+
+        report
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> { mmap::resident(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool { mmap::lock(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        mmap::protect(pointer, size, writable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        mmap::map_code(size, mapping, |size| mmap::mmap_dual(shm::open_shared_memory()?, size))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) { mmap::unmap_code(region) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        mmap::protect_code(region, executable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> { mmap::map_stack(size, prefault) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, size) = stack.mapping();
+
+        mmap::munmap_deallocate(pointer.as_ptr(), size);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> {
+        let size = size.max(1).checked_add(SUPERPAGE_SIZE - 1)? & !(SUPERPAGE_SIZE - 1);
+
+        //  Locking the memory populates it fully, backing it with large pages if available.
+        let pointer = mmap_allocate_aligned(size, SUPERPAGE_SIZE)
+            .or_else(|| mmap::mmap_aligned(size, SUPERPAGE_SIZE, 0))?;
+
+        //  Safety:
+        //  -   `pointer` points to a `mmap`ed area of `size` bytes, not in use.
+        unsafe { mmap::lock_physical(pointer, size, false) }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) {
+        mmap::munmap_deallocate(buffer.pointer().as_ptr(), buffer.size());
+    }
+
+    //  The physical addresses, although exposed by `meminfo`, are left unreported.
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool { unix::environment_flag(name) }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES.get() }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities { capabilities::detect_host(CAPABILITIES.get()) }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { unix::mapping_latency() }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> { unix::system_allocate(layout) }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) { unix::system_deallocate(pointer) }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { unix::owns(pointer) }
+}
+
+//  Capabilities of the environment.
+static CAPABILITIES: Detector = Detector::new(capabilities::detect);
+
+const SUPERPAGE_SIZE: usize = 2 * 1024 * 1024;
+
+//  The `id` designating the calling LWP, or process, missing from `libc`.
+const P_MYID: libc::id_t = -1;
+
+//  The `meminfo` requests of the locality group, and page size, backing a virtual address, missing from `libc`.
+const MEMINFO_VLGRP: libc::c_uint = 2;
+const MEMINFO_VPAGESIZE: libc::c_uint = 3;
+
+//  Maximum number of addresses queried by `meminfo` at once.
+const MAX_MEMINFO_CNT: usize = 256;
+
+//  Returns the home locality group of the calling thread, or None if unknown.
+fn home_lgroup() -> Option<libc::lgrp_id_t> {
+    //  Safety:
+    //  -   `lgrp_home` has no precondition.
+    let lgroup = unsafe { libc::lgrp_home(libc::P_LWPID, P_MYID) };
+
+    if lgroup >= 0 { Some(lgroup) } else { None }
+}
+
+//  Returns the NUMA node of the leaf locality group `lgroup`, or None if the root.
+fn node_of_lgroup(lgroup: libc::lgrp_id_t) -> Option<u32> {
+    if lgroup > 0 { Some(lgroup as u32 - 1) } else { None }
+}
+
+//  Returns whether `lgroup` is a locality group of the machine.
+//
+//  The affinity is queried, rather than a snapshot of the hierarchy taken with `lgrp_init`, as the latter allocates.
+fn lgroup_exists(lgroup: libc::lgrp_id_t) -> bool {
+    //  Safety:
+    //  -   `lgrp_affinity_get` has no precondition, it reports an invalid `lgroup` as an error.
+    unsafe { libc::lgrp_affinity_get(libc::P_LWPID, P_MYID, lgroup) >= 0 }
+}
+
+//  Returns the number of NUMA nodes, that is of leaf locality groups, at least 1.
+fn number_of_nodes() -> u32 {
+    //  The number of sockets supported is bounded, hence so is the search.
+    const MAXIMUM: u32 = 64;
+
+    (1..=MAXIMUM).take_while(|lgroup| lgroup_exists(*lgroup as libc::lgrp_id_t)).count().max(1) as u32
+}
+
+//  Attempts to allocate the required size, aligned, for the kernel to back it with large pages if available.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_normal(size: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    let aligned = |size| mmap_allocate_aligned(size, ALIGNMENT.value());
+
+    //  Being aligned on `HUGE_PAGE_SIZE`, the memory is also aligned on large pages.
+    let backing = if CAPABILITIES.get().transparent_huge_pages {
+        Fallback::TransparentHugePageMapping
+    } else {
+        Fallback::NormalPageMapping
+    };
+
+    mmap::mmap_huge_page(size, aligned, backing)
+}
+
+//  Invokes `f` with the locality group, and size of the backing page, of each resident OS page covering the `size`
+//  bytes located at `address`, as per `meminfo`.
+//
+//  Returns None if the pages cannot be looked up.
+fn for_each_page<F>(address: usize, size: usize, mut f: F) -> Option<()>
+    where
+        F: FnMut(libc::lgrp_id_t, usize),
+{
+    const REQUESTS: [libc::c_uint; 2] = [MEMINFO_VLGRP, MEMINFO_VPAGESIZE];
+
+    //  The validity of the address, then of each request in turn.
+    const VALID: libc::c_uint = 0b111;
+
+    let page_size = mmap::os_page_size();
+
+    let mut addresses = [0u64; MAX_MEMINFO_CNT];
+    let mut outputs = [0u64; MAX_MEMINFO_CNT * REQUESTS.len()];
+    let mut validity = [0 as libc::c_uint; MAX_MEMINFO_CNT];
+
+    let end = address.checked_add(size)?;
+    let mut current = page_size.round_down(address);
+
+    while current < end {
+        let pages = (end - current).div_ceil(page_size.value()).min(MAX_MEMINFO_CNT);
+
+        for (index, address) in addresses[..pages].iter_mut().enumerate() {
+            *address = (current + index * page_size.value()) as u64;
+        }
+
+        //  Safety:
+        //  -   `addresses` and `validity` hold `pages` elements.
+        //  -   `outputs` holds `REQUESTS.len()` elements per address.
+        let result = unsafe {
+            libc::meminfo(
+                addresses.as_ptr(),
+                pages as libc::c_int,
+                REQUESTS.as_ptr(),
+                REQUESTS.len() as libc::c_int,
+                outputs.as_mut_ptr(),
+                validity.as_mut_ptr(),
+            )
+        };
+
+        if result != 0 {
+            return None;
+        }
+
+        //  The locality group, and backing page, of a page are only valid if the page is resident.
+        for (index, valid) in validity[..pages].iter().enumerate() {
+            if *valid & VALID == VALID {
+                let outputs = &outputs[index * REQUESTS.len()..];
+                f(outputs[0] as libc::lgrp_id_t, outputs[1] as usize);
+            }
+        }
+
+        current += pages * page_size.value();
+    }
+
+    Some(())
+}
+
+//  Wrapper around `mmap`, with `MAP_ALIGN`.
+//
+//  Returns a pointer to `size` bytes of memory, aligned on `alignment`, a power of 2 multiple of the OS page size.
+fn mmap_allocate_aligned(size: usize, alignment: usize) -> Option<NonNull<u8>> {
+    debug_assert!(alignment.is_power_of_two());
+
+    //  With `MAP_ALIGN`, the address is the alignment.
+    mmap::mmap_allocate_at(alignment as *mut u8, size, libc::MAP_ALIGN)
+}
@@ -0,0 +1,87 @@
+//! Detection of the capabilities of the environment.
+//!
+//! The capabilities are detected once, on first use: illumos offers no HugeTLB pool, its large pages being applied
+//! transparently by the kernel to aligned mappings if supported by the hardware, as per `getpagesizes`, and NUMA is
+//! available if the calling thread has a home locality group other than the root.
+
+use crate::{Capabilities, HostCapabilities, TransparentHugePagesMode};
+
+use super::{home_lgroup, mmap, number_of_nodes, unix, SUPERPAGE_SIZE};
+
+/// Detects the capabilities of the environment.
+#[cold]
+pub(super) fn detect() -> Capabilities {
+    let transparent_huge_pages = page_sizes().is_some_and(|(sizes, count)| sizes[..count].contains(&SUPERPAGE_SIZE));
+
+    //  illumos has no HugeTLB pool; without NUMA, the root locality group is the only one, and therefore the home of
+    //  all threads.
+    Capabilities {
+        huge_tlb: false,
+        huge_tlb_2mb: false,
+        transparent_huge_pages,
+        numa: home_lgroup().unwrap_or(0) > 0,
+        sysfs: true,
+    }
+}
+
+/// Detects the capabilities of the host, in details, given the `capabilities` selected.
+///
+/// Unlike the selected capabilities, the details are detected anew on each call.
+#[cold]
+pub(super) fn detect_host(capabilities: Capabilities) -> HostCapabilities {
+    let os_page_size = mmap::os_page_size().value();
+
+    let mut host = unix::host_capabilities(capabilities, os_page_size, mmap::mlock_limit());
+
+    host.transparent_huge_pages = match page_sizes() {
+        Some((sizes, count)) => {
+            for size in sizes[..count].iter().filter(|size| **size > os_page_size) {
+                host.add_huge_page_size(*size);
+            }
+
+            if sizes[..count].contains(&SUPERPAGE_SIZE) {
+                TransparentHugePagesMode::Always
+            } else {
+                TransparentHugePagesMode::Never
+            }
+        },
+        None => TransparentHugePagesMode::Unknown,
+    };
+
+    host.numa_nodes = if capabilities.numa { number_of_nodes() } else { 1 };
+
+    host
+}
+
+//
+//  Implementation Details
+//
+
+//  Maximum number of page sizes looked up.
+const MAXIMUM_PAGE_SIZES: usize = 8;
+
+//  Returns the page sizes supported, and their number, or None if unknown.
+fn page_sizes() -> Option<([usize; MAXIMUM_PAGE_SIZES], usize)> {
+    let mut sizes = [0usize; MAXIMUM_PAGE_SIZES];
+
+    //  Safety:
+    //  -   `sizes` is valid for writes of `MAXIMUM_PAGE_SIZES` elements.
+    let count = unsafe { libc::getpagesizes(sizes.as_mut_ptr(), MAXIMUM_PAGE_SIZES as libc::c_int) };
+
+    if count > 0 { Some((sizes, count as usize)) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn detect_stable() {
+    let detected = detect();
+
+    assert!(!detected.huge_tlb);
+    assert_eq!(detected, detect());
+}
+
+} // mod tests
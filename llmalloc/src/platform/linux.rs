@@ -1,21 +1,53 @@
 //! Implementation of Linux specific calls.
+//!
+//! The implementation also covers Android, whose C library, Bionic, lacks the `mremap` binding of the `libc` crate,
+//! which is replaced by its closest equivalent, and musl. The NUMA topology is discovered from `/sys`, and the node of
+//! the current thread queried with `getcpu`, rather than through libnuma, which is not installed everywhere.
+
+mod capabilities;
+mod pagemap;
+mod procfs;
+mod topology;
 
 use core::{
     alloc::Layout,
-    marker::PhantomData,
     mem,
     ptr::{self, NonNull},
     sync::atomic,
+    time::Duration,
 };
 
 use llmalloc_core::{self, PowerOf2};
 
-use super::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+use crate::{
+    AddressRange, AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Collapse, Fallback, HostCapabilities,
+    HugePageReport, HugeTlbPools, MapOptions, Merging, NodeBinding, PhysicalBuffer, PhysicalSegment, SharedBacking,
+    SharedHeap, ThreadStack, UnmapFailure,
+};
+
+use crate::{
+    bounds::ADDRESS_BOUNDS, clustering::CLUSTERING, decay::DECAY, decommit::DECOMMIT, guard::GUARDS,
+    mapping::MAP_OPTIONS, pinning::PINNING, prefault::PREFAULT, reservation::ADDRESS_SPACE, shared::SHARED,
+    tiering::TIERING, unmapping::UNMAPPING,
+};
+
+use super::{NumaNodeIndex, Configuration, Platform};
+
+#[cfg(feature = "system-fallback")]
+use super::ownership::OWNERSHIP;
 
 /// Implementation of the Configuration trait, for Linux.
+///
+/// With the `small-heap` feature, the pages are scaled down so that small heaps are not dominated by the reservations
+/// of the allocator: the Huge Pages shrink from 1 GB to 2 MB, and the Large Pages from 2 MB to 64 KB, which in turn
+/// caps Normal allocations at 28 KB, reducing the number of class sizes and the size of each thread cache.
+///
+/// The thread caches are hosted within the first Large Page of each socket, hence the `small-heap` feature also limits
+/// the number of threads concurrently using the allocator to about 70 per NUMA node.
 #[derive(Default)]
 pub(crate) struct LLConfiguration;
 
+#[cfg(not(feature = "small-heap"))]
 impl Configuration for LLConfiguration {
     //  2 MB
     const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
@@ -24,7 +56,19 @@ impl Configuration for LLConfiguration {
     const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(1024 * 1024 * 1024) };
 }
 
+#[cfg(feature = "small-heap")]
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
 /// Implementation of the Platform trait, for Linux.
+///
+/// The mappings of the heap are named, as listed in `/proc/<pid>/maps`: `llmalloc:node<N>` for the `HugePage`s mapped
+/// by the threads of node N, and `llmalloc:huge` for the Huge allocations, on the kernels which support it.
 #[derive(Default)]
 pub(crate) struct LLPlatform;
 
@@ -37,23 +81,250 @@ impl llmalloc_core::Platform for LLPlatform {
     unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
         const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
 
-        assert!(layout.size() % HUGE_PAGE_SIZE == 0,
+        debug_assert!(layout.size() % HUGE_PAGE_SIZE == 0,
             "Incorrect size: {} % {} != 0", layout.size(), HUGE_PAGE_SIZE.value());
-        assert!(layout.align() <= HUGE_PAGE_SIZE.value(),
+        debug_assert!(layout.align() <= HUGE_PAGE_SIZE.value(),
             "Incorrect alignment: {} > {}", layout.align(), HUGE_PAGE_SIZE.value());
 
-        let candidate = mmap_huge(layout.size())
-            .or_else(|| mmap_exact(layout.size()))
-            .or_else(|| mmap_over(layout.size()))?;
+        if layout.size() % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() {
+            return None;
+        }
+
+        let start = self.now();
+
+        let guarded = GUARDS.latch(self);
+        let shared = SHARED.latch(self, memfd_heap);
+        let reserved = ADDRESS_SPACE.latch(self, reserve_address_space);
+        let bounds = ADDRESS_BOUNDS.latch();
+        let options = hinted(MAP_OPTIONS.get(), bounds, layout.size());
+
+        let candidate = if reserved {
+            mmap_reserved(layout.size())
+        } else {
+            match shared {
+                Some(heap) => mmap_shared(layout.size(), guarded, heap, options),
+                None => mmap_huge(layout.size(), guarded, options)
+                    .or_else(|| mmap_huge_2mb(layout.size(), guarded, options))
+                    .or_else(|| mmap_normal(layout.size(), guarded, options)),
+            }
+        };
+
+        //  Failed mappings count too, as a deadline must also cover the paths which end up failing.
+        MAPPING_LATENCY.fetch_max(self.now().saturating_sub(start).saturating_add(1), atomic::Ordering::Relaxed);
+
+        let candidate = candidate?;
+
+        //  The memory of the reservation lies within the bounds, as the reservation does.
+        if !reserved && !within_bounds(bounds, options.address_hint().unwrap_or(0), candidate, layout.size()) {
+            munmap_heap(candidate, layout.size(), guarded, shared);
+            return None;
+        }
 
         debug_assert!(candidate.as_ptr() as usize % HUGE_PAGE_SIZE == 0,
             "Incorrect alignment of allocation: {:x} % {:x} != 0", candidate.as_ptr() as usize, HUGE_PAGE_SIZE.value());
 
+        #[cfg(feature = "system-fallback")]
+        if !OWNERSHIP.mark(candidate.as_ptr() as usize, layout.size()) {
+            munmap_heap(candidate, layout.size(), guarded, shared);
+            return None;
+        }
+
+        //  The node of the thread mapping it, on which its pages are first touched, and that of its socket.
+        let node = self.current_node();
+
+        //  A strict binding which cannot be honored fails the mapping, rather than letting the pages stray.
+        if !reserved && !advise(options, candidate, layout.size(), node) {
+            #[cfg(feature = "system-fallback")]
+            OWNERSHIP.clear(candidate.as_ptr() as usize, layout.size());
+
+            munmap_heap(candidate, layout.size(), guarded, shared);
+            return None;
+        }
+
+        //  A Huge allocation is renamed once allocated, see `name_huge`.
+        name(candidate, layout.size(), &node_name(node));
+
+        PREFAULT.prefault(self, candidate, layout.size(), os_page_size().value());
+        PINNING.pin(self, candidate, layout.size());
+
         Some(candidate)
     }
 
     unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
-        munmap_deallocate(pointer.as_ptr(), layout.size());
+        #[cfg(feature = "system-fallback")]
+        OWNERSHIP.clear(pointer.as_ptr() as usize, layout.size());
+
+        munmap_heap(pointer, layout.size(), GUARDS.latch(self), SHARED.latch(self, memfd_heap));
+    }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        if new_size % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() {
+            return None;
+        }
+
+        let size = layout.size();
+
+        //  A guarded mapping is always moved, onto a fresh range flanked by guards of its own.
+        let guarded = GUARDS.latch(self);
+        let shared = SHARED.latch(self, memfd_heap);
+        let bounds = ADDRESS_BOUNDS.latch();
+
+        //  The memory of the reservation is resized within it.
+        if ADDRESS_SPACE.contains(pointer) {
+            return reallocate_reserved(self, pointer, size, new_size, shared);
+        }
+
+        //  Shrink in place, by unmapping the tail.
+        if new_size <= size && !guarded {
+            if new_size < size {
+                #[cfg(feature = "system-fallback")]
+                OWNERSHIP.clear(pointer.as_ptr() as usize + new_size, size - new_size);
+
+                if let Some(heap) = shared {
+                    punch_hole(heap, pointer.as_ptr().add(new_size), size - new_size);
+                }
+
+                munmap_deallocate(pointer.as_ptr().add(new_size), size - new_size);
+            }
+
+            return Some(pointer);
+        }
+
+        //  Grow in place, if the adjacent address space is free, and the grown area still within the shared file, and
+        //  the bounds.
+        let in_place = !guarded
+            && (shared.is_none() || within_shared_file(pointer.as_ptr() as usize, new_size))
+            && bounds.is_none_or(|range| range.contains_all(pointer.as_ptr(), new_size));
+        let grown = if in_place { mremap_resize(pointer, size, new_size, 0, ptr::null_mut()) } else { None };
+
+        if let Some(result) = grown {
+            debug_assert!(result == pointer);
+
+            #[cfg(feature = "system-fallback")]
+            if !OWNERSHIP.mark(pointer.as_ptr() as usize + size, new_size - size) {
+                munmap_deallocate(pointer.as_ptr().add(size), new_size - size);
+                return None;
+            }
+
+            let tail = NonNull::new_unchecked(result.as_ptr().add(size));
+            PREFAULT.prefault(self, tail, new_size - size, os_page_size().value());
+            PINNING.pin(self, tail, new_size - size);
+
+            return Some(result);
+        }
+
+        //  The memory of the shared heap lives at the offset of its address, hence cannot be moved by `mremap`.
+        if let Some(heap) = shared {
+            return move_shared(self, pointer, size, new_size, guarded, heap, bounds);
+        }
+
+        //  Otherwise, move the pages onto a fresh, suitably aligned, range of the address space; the pages moved keep
+        //  the flags, and the NUMA policy, they were mapped with.
+        let hint = bounds.map_or(0, |range| ADDRESS_BOUNDS.hint(range, new_size));
+        let target = if guarded { mmap_reserve(new_size, hint)? } else { mmap_over(new_size, 0, hint)? };
+
+        if !within_bounds(bounds, hint, target, new_size) {
+            munmap_release(target, new_size, guarded);
+            return None;
+        }
+
+        #[cfg(feature = "system-fallback")]
+        if !OWNERSHIP.mark(target.as_ptr() as usize, new_size) {
+            munmap_release(target, new_size, guarded);
+            return None;
+        }
+
+        let flags = MREMAP_MAYMOVE | MREMAP_FIXED;
+
+        match mremap_resize(pointer, size, new_size, flags, target.as_ptr()) {
+            Some(result) => {
+                debug_assert!(result == target);
+
+                #[cfg(feature = "system-fallback")]
+                OWNERSHIP.clear(pointer.as_ptr() as usize, size);
+
+                //  The pages moved, only the former guards remain to be unmapped.
+                if guarded {
+                    munmap_release(pointer, size, true);
+                }
+
+                if new_size > size {
+                    let tail = NonNull::new_unchecked(result.as_ptr().add(size));
+                    PREFAULT.prefault(self, tail, new_size - size, os_page_size().value());
+                    PINNING.pin(self, tail, new_size - size);
+                }
+
+                Some(result)
+            },
+            //  Notably, HugeTLB mappings cannot be grown by `mremap`.
+            None => {
+                #[cfg(feature = "system-fallback")]
+                OWNERSHIP.clear(target.as_ptr() as usize, new_size);
+
+                munmap_release(target, new_size, guarded);
+                None
+            },
+        }
+    }
+
+    unsafe fn name_huge(&self, pointer: NonNull<u8>, size: usize) { name(pointer, size, HUGE_NAME) }
+
+    unsafe fn decommit(&self, pointer: NonNull<u8>, size: usize) {
+        if !DECOMMIT.is_enabled(self) {
+            return;
+        }
+
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        //
+        //  Failures are benign, the memory remaining committed; notably, HugeTLB pages cannot be partially released.
+        match SHARED.latch(self, memfd_heap) {
+            Some(heap) => punch_hole(heap, pointer.as_ptr(), size),
+            None => {
+                libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_DONTNEED);
+            },
+        }
+    }
+
+    unsafe fn purge_lazy(&self, pointer: NonNull<u8>, size: usize) {
+        if !DECAY.is_enabled(self) {
+            return;
+        }
+
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        //
+        //  Failures are benign, the memory remaining committed until forcibly purged; notably, `MADV_FREE` requires
+        //  Linux 4.5, and applies neither to HugeTLB pages, nor to the memory of a shared heap.
+        libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_FREE);
+    }
+
+    unsafe fn purge_forced(&self, pointer: NonNull<u8>, size: usize) {
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        match SHARED.latch(self, memfd_heap) {
+            Some(heap) => punch_hole(heap, pointer.as_ptr(), size),
+            None => {
+                libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_DONTNEED);
+            },
+        }
+    }
+
+    unsafe fn purge_forced_batch(&self, ranges: &[(NonNull<u8>, usize)]) {
+        //  The memory of a shared heap is punched out of its file, one range at a time.
+        if SHARED.latch(self, memfd_heap).is_some() {
+            for &(pointer, size) in ranges {
+                self.purge_forced(pointer, size);
+            }
+
+            return;
+        }
+
+        for batch in ranges.chunks(MAXIMUM_PURGE_BATCH) {
+            purge_batch(batch);
+        }
     }
 }
 
@@ -61,259 +332,1746 @@ impl Platform for LLPlatform {
     #[cold]
     #[inline(never)]
     fn current_node(&self) -> NumaNodeIndex {
-        let cpu = unsafe { libc::sched_getcpu() };
-        assert!(cpu >= 0, "Expected CPU index, got {}", cpu);
+        //  Without NUMA, a single socket is shared by all threads.
+        if !CAPABILITIES.get().numa {
+            return NumaNodeIndex::new(0);
+        }
+
+        //  If the node is unknown, then use 0 as fallback.
+        match current_numa_node() {
+            Some(node) => select_node(NumaNodeIndex::new(node)),
+            None => {
+                FALLBACKS.record(Fallback::UnknownNode);
+                NumaNodeIndex::new(0)
+            },
+        }
+    }
 
-        let node = unsafe { numa_node_of_cpu(cpu) };
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        //  Without NUMA, the single node is node 0.
+        if !CAPABILITIES.get().numa {
+            return if node == 0 { Some(NumaNodeIndex::new(0)) } else { None };
+        }
 
-        //  If libnuma cannot find the appropriate node (such as under WSL), then use 0 as fallback.
-        if node < 0 {
-            return NumaNodeIndex::new(0);
+        if node >= TOPOLOGY.nodes() {
+            return None;
         }
 
-        select_node(NumaNodeIndex::new(node as u32))
+        Some(select_node(NumaNodeIndex::new(node)))
     }
-}
 
-/// Implementation of the ThreadLocal trait, for Linux.
-pub(crate) struct LLThreadLocal<T> {
-    key: atomic::AtomicI64,
-    destructor: *const u8,
-    _marker: PhantomData<*const T>,
-}
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 {
+        let mut timespec = libc::timespec { tv_sec: 0, tv_nsec: 0 };
 
-impl<T> LLThreadLocal<T> {
-    const UNINITIALIZED: i64 = -1;
-    const UNDER_INITIALIZATION: i64 = -2;
+        //  Safety:
+        //  -   `timespec` is valid for writes.
+        let result = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut timespec as *mut _) };
 
-    /// Creates an uninitialized instance.
-    ///
-    /// #   Safety
-    ///
-    /// -   Assumes that `destructor` points to an `unsafe extern "C" fn(*mut c_void)` function, or compatible.
-    pub(crate) const unsafe fn new(destructor: *const u8) -> Self {
-        let key = atomic::AtomicI64::new(-1);
-        let _marker = PhantomData;
+        //  The timestamps are only used for metrics, an unreadable clock merely makes them meaningless.
+        if result != 0 {
+            return 0;
+        }
 
-        LLThreadLocal { key, destructor, _marker }
+        (timespec.tv_sec as u64) * 1_000_000_000 + (timespec.tv_nsec as u64)
     }
 
-    #[inline(always)]
-    fn get_key(&self) -> libc::pthread_key_t {
-        let key = self.key.load(atomic::Ordering::Relaxed);
-        if key >= 0 { key as libc::pthread_key_t} else { unsafe { self.initialize() } }
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        let is_local = |other: u32| select_node(NumaNodeIndex::new(other)) == node;
+
+        procfs::reconcile(page.as_ptr() as usize, size, node.value(), is_local)
     }
 
     #[cold]
     #[inline(never)]
-    unsafe fn initialize(&self) -> libc::pthread_key_t {
-        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> {
+        //  Number of pages looked up at once.
+        const BATCH: usize = 4096;
 
-        let mut key = self.key.load(RELAXED);
+        let page_size = os_page_size();
+        let mut vector = [0u8; BATCH];
 
-        if let Ok(_) = self.key.compare_exchange(Self::UNINITIALIZED, Self::UNDER_INITIALIZATION, RELAXED, RELAXED)
-        {
-            key = self.create_key();
-            self.key.store(key, RELAXED);
-        }
+        let address = page_size.round_down(pointer.as_ptr() as usize);
+        let end = (pointer.as_ptr() as usize).checked_add(size)?;
+
+        let mut resident = 0;
+        let mut current = address;
+
+        while current < end {
+            let length = (end - current).min(BATCH * page_size.value());
+
+            //  Safety:
+            //  -   `current` is aligned on an OS page.
+            //  -   `vector` holds one byte per page of the `length` bytes.
+            let result = unsafe { libc::mincore(current as *mut libc::c_void, length, vector.as_mut_ptr()) };
 
-        while key < 0 {
-            libc::sched_yield();
-            key = self.key.load(RELAXED);
+            if result != 0 {
+                return None;
+            }
+
+            let pages = length.div_ceil(page_size.value());
+            resident += vector[..pages].iter().filter(|entry| **entry & 1 != 0).count() * page_size.value();
+
+            current += length;
         }
 
-        key as libc::pthread_key_t
+        Some(resident.min(size))
     }
 
     #[cold]
-    unsafe fn create_key(&self) -> i64 {
-        let mut key: libc::pthread_key_t = 0;
-
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool {
         //  Safety:
-        //  -   fn pointers are just pointers.
-        let destructor = mem::transmute::<_, Destructor>(self.destructor);
-        let result = libc::pthread_key_create(&mut key as *mut _, Some(destructor));
-        assert!(result == 0, "Could not create thread-local key: {}", result);
+        //  -   `mlock` does not access the memory it locks, it only faults it in.
+        let result = unsafe { libc::mlock(pointer.as_ptr() as *const libc::c_void, size) };
 
-        key as i64
+        result == 0
     }
-}
 
-impl<T> ThreadLocal<T> for LLThreadLocal<T> {
-    fn get(&self) -> Option<NonNull<T>> {
-        let key = self.key.load(atomic::Ordering::Relaxed);
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        let prot = if writable { libc::PROT_READ | libc::PROT_WRITE } else { libc::PROT_READ };
 
-        //  If key is not initialized, then a null pointer is returned.
-        NonNull::new(unsafe { libc::pthread_getspecific(key as libc::pthread_key_t) as *mut T })
+        libc::mprotect(pointer.as_ptr() as *mut libc::c_void, size, prot) == 0
     }
 
     #[cold]
     #[inline(never)]
-    fn set(&self, value: NonNull<T>) {
-        let key = self.get_key();
-
-        let result = unsafe { libc::pthread_setspecific(key, value.as_ptr() as *mut libc::c_void) };
-        assert!(result == 0, "Could not set thread-local value for {}: {}", key, result);
-    }
-}
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        let page_size = os_page_size().value();
+        let size = size.max(1).checked_add(page_size - 1)? & !(page_size - 1);
 
-unsafe impl<T> Sync for LLThreadLocal<T> {}
+        let (writable, executable) = match mapping {
+            CodeMapping::Dual => mmap_dual(size)?,
+            CodeMapping::Flip => {
+                let pointer = mmap_allocate(size, 0)?;
+                (pointer, pointer)
+            },
+        };
 
-type Destructor = unsafe extern "C" fn(*mut libc::c_void);
+        Some(CodeRegion::new(writable, executable, size, mapping))
+    }
 
-//  Selects the "best" node.
-//
-//  The Linux kernel sometimes distinguishes nodes even though their distance is 11, when the distance to self is 10.
-//  This may lead to over-allocation, hence it is judged best to "cluster" the nodes together.
-//
-//  This function will therefore return the smallest node number whose distance to the `original` is less than or
-//  equal to 11.
-fn select_node(original: NumaNodeIndex) -> NumaNodeIndex {
-    let original = original.value() as i32;
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) {
+        munmap_deallocate(region.writable().as_ptr(), region.size());
 
-    for current in 0..original {
-        if unsafe { numa_distance(current, original) } <= 11 {
-            return NumaNodeIndex::new(current as u32);
+        if region.mapping() == CodeMapping::Dual {
+            munmap_deallocate(region.executable().as_ptr(), region.size());
         }
     }
 
-    NumaNodeIndex::new(original as u32)
-}
-
-//  Attempts to allocate the required size in Huge Pages.
-//
-//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
-fn mmap_huge(size: usize) -> Option<NonNull<u8>> {
-    const MAP_HUGE_SHIFT: u8 = 26;
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        debug_assert!(region.mapping() == CodeMapping::Flip);
 
-    const MAP_HUGE_1GB: libc::c_int = 30 << MAP_HUGE_SHIFT;
+        let prot = if executable { libc::PROT_READ | libc::PROT_EXEC } else { libc::PROT_READ | libc::PROT_WRITE };
 
-    mmap_allocate(size, libc::MAP_HUGETLB | MAP_HUGE_1GB)
-        .and_then(|pointer| unsafe { mmap_check(pointer, size) })
-}
+        libc::mprotect(region.writable().as_ptr() as *mut libc::c_void, region.size(), prot) == 0
+    }
 
-//  Attempts to allocate the required size in Normal (or Large) Pages.
-//
-//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
-fn mmap_exact(size: usize) -> Option<NonNull<u8>> {
-    mmap_allocate(size, 0)
-        .and_then(|pointer| unsafe { mmap_check(pointer, size) })
-}
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> {
+        const ALIGNMENT: PowerOf2 = LLConfiguration::LARGE_PAGE_SIZE;
 
-//  Attempts to allocate the required size in Normal (or Large) Pages.
-//
-//  Ensures the alignment is met by over-allocated then trimming front and back.
-fn mmap_over(size: usize) -> Option<NonNull<u8>> {
-    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+        let guard_size = os_page_size().value();
+        let size = size.max(1).checked_add(ALIGNMENT.value() - 1)? & !(ALIGNMENT.value() - 1);
 
-    let over_size = size + ALIGNMENT.value();
-    let front_pointer = mmap_allocate(over_size, 0)?;
+        //  Over-allocate, so that the usable area may be aligned, with the guard page immediately below.
+        let over_size = size.checked_add(ALIGNMENT.value())?.checked_add(guard_size)?;
+        let front_pointer = mmap_allocate(over_size, libc::MAP_STACK)?;
 
-    let back_size = (front_pointer.as_ptr() as usize) % ALIGNMENT;
-    let front_size = ALIGNMENT.value() - back_size;
+        let start = front_pointer.as_ptr() as usize;
+        let bottom = ALIGNMENT.round_up(start + guard_size);
 
-    debug_assert!(front_size <= ALIGNMENT.value(), "{} > {}", front_size, ALIGNMENT.value());
-    debug_assert!(back_size < ALIGNMENT.value(), "{} >= {}", back_size, ALIGNMENT.value());
-    debug_assert!(front_size + size + back_size == over_size,
-        "{} + {} + {} != {}", front_size, size, back_size, over_size);
+        let front_size = bottom - guard_size - start;
+        let back_size = over_size - front_size - guard_size - size;
 
-    //  Safety:
-    //  -   `front_size` is less than `over_size`, hence the result is within the allocated block.
-    let aligned_pointer = unsafe { front_pointer.as_ptr().add(front_size) };
+        if front_size > 0 {
+            //  Safety:
+            //  -   `[start, start + front_size)` is within the mapped area, and not in use.
+            unsafe { munmap_deallocate(front_pointer.as_ptr(), front_size) };
+        }
 
-    debug_assert!(aligned_pointer as usize % ALIGNMENT == 0,
-        "{:x} not {:x}-aligned!", aligned_pointer as usize, ALIGNMENT.value());
+        if back_size > 0 {
+            //  Safety:
+            //  -   `[bottom + size, bottom + size + back_size)` is within the mapped area, and not in use.
+            unsafe { munmap_deallocate((bottom + size) as *mut u8, back_size) };
+        }
 
-    //  Safety:
-    //  -   `front_size + size` is less than `over_size`, hence the result is within the allocated block,
-    //      or pointing to its end.
-    let back_pointer = unsafe { aligned_pointer.add(size) };
+        let guard = (bottom - guard_size) as *mut u8;
 
-    if front_size > 0 {
         //  Safety:
-        //  -   `front_pointer` points to a `mmap`ed area of at least `front_size` bytes.
-        //  -   `[front_pointer, front_pointer + front_size)` is no longer in use.
-        unsafe { munmap_deallocate(front_pointer.as_ptr(), front_size) };
+        //  -   `[guard, guard + guard_size)` is within the mapped area, and not in use.
+        if unsafe { libc::mprotect(guard as *mut libc::c_void, guard_size, libc::PROT_NONE) } != 0 {
+            //  Safety:
+            //  -   `[guard, guard + guard_size + size)` is the remainder of the mapped area, and not in use.
+            unsafe { munmap_deallocate(guard, guard_size + size) };
+            return None;
+        }
+
+        if CAPABILITIES.get().transparent_huge_pages {
+            //  Safety:
+            //  -   `[bottom, bottom + size)` is within the mapped area.
+            //  -   The advice is merely a hint, hence its failure is inconsequential.
+            unsafe { libc::madvise(bottom as *mut libc::c_void, size, libc::MADV_HUGEPAGE) };
+        }
+
+        if prefault {
+            for offset in (0..size).step_by(guard_size) {
+                //  Safety:
+                //  -   `bottom + offset` is within the usable area, writable and not in use.
+                unsafe { ptr::write_volatile((bottom + offset) as *mut u8, 0) };
+            }
+        }
+
+        NonNull::new(guard).map(|guard| ThreadStack::new(guard, guard_size, size))
     }
 
-    if back_size > 0 {
-        //  Safety:
-        //  -   `back_pointer` points to a `mmap`ed area of at least `back_size` bytes.
-        //  -   `[back_pointer, back_pointer + back_size)` is no longer in use.
-        unsafe { munmap_deallocate(back_pointer, back_size) };
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, size) = stack.mapping();
+
+        munmap_deallocate(pointer.as_ptr(), size);
     }
 
-    NonNull::new(aligned_pointer)
-}
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> {
+        const ALIGNMENT: usize = 2 * 1024 * 1024;
 
-//  `mmap` alignment checker.
-//
-//  Returns a non-null pointer if suitably aligned, and None otherwise.
-//  If none is returned, the memory has been unmapped.
-//
-//  #   Safety
-//
-//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes.
-//  -   Assumes that `pointer` is no longer in use, unless returned.
-unsafe fn mmap_check(pointer: NonNull<u8>, size: usize) -> Option<NonNull<u8>> {
-    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+        let size = size.max(1).checked_add(ALIGNMENT - 1)? & !(ALIGNMENT - 1);
 
-    if pointer.as_ptr() as usize % ALIGNMENT == 0 {
-        Some(pointer)
-    } else {
-        //  Safety:
-        //  -   `pointer` points to a `mmap`ed area of at least `size` bytes.
-        //  -   `[pointer, pointer + size)` is no longer in use.
-        munmap_deallocate(pointer.as_ptr(), size);
-        None
+        let (pointer, huge_tlb) = match mmap_allocate(size, libc::MAP_HUGETLB | MAP_HUGE_2MB) {
+            Some(pointer) => (pointer, true),
+            None => (mmap_aligned(size, ALIGNMENT)?, false),
+        };
+
+        if !huge_tlb && CAPABILITIES.get().transparent_huge_pages {
+            //  Safety:
+            //  -   `pointer` points to a `mmap`ed area of `size` bytes.
+            //  -   The advice is merely a hint, hence its failure is inconsequential.
+            unsafe { libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_HUGEPAGE) };
+        }
+
+        if !self.lock(pointer, size) {
+            //  Safety:
+            //  -   `pointer` points to a `mmap`ed area of `size` bytes, not in use.
+            unsafe { munmap_deallocate(pointer.as_ptr(), size) };
+            return None;
+        }
+
+        Some(PhysicalBuffer::new(pointer, size, huge_tlb))
     }
-}
 
-//  Wrapper around `mmap`.
-//
-//  Returns a pointer to `size` bytes of memory; does not guarantee any alignment.
-fn mmap_allocate(size: usize, extra_flags: i32) -> Option<NonNull<u8>> {
-    let length = size;
-    let prot = libc::PROT_READ | libc::PROT_WRITE;
-    let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | extra_flags;
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) {
+        munmap_deallocate(buffer.pointer().as_ptr(), buffer.size());
+    }
 
-    //  No specific address hint.
-    let addr = ptr::null_mut();
-    //  When used in conjunction with MAP_ANONYMOUS, fd is mandated to be -1 on some implementations.
-    let fd = -1;
-    //  When used in conjunction with MAP_ANONYMOUS, offset is mandated to be 0 on some implementations.
-    let offset = 0;
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, buffer: &PhysicalBuffer, report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        let address = buffer.pointer().as_ptr() as usize;
 
-    //  Safety:
-    //  -   `addr`, `fd`, and `offset` are suitable for MAP_ANONYMOUS.
-    let result = unsafe { libc::mmap(addr, length, prot, flags, fd, offset) };
+        pagemap::physical_segments(address, buffer.size(), os_page_size().value(), report)
+    }
 
-    let result = if result != libc::MAP_FAILED { result as *mut u8 } else { ptr::null_mut() };
-    NonNull::new(result)
-}
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool { environment_flag(name) }
 
-//  Wrapper around `munmap`.
-//
-//  #   Panics
-//
-//  If `munmap` returns a non-0 result.
-//
-//  #   Safety
-//
-//  -   Assumes that `addr` points to a `mmap`ed area of at least `size` bytes.
-//  -   Assumes that the range `[addr, addr + size)` is no longer in use.
-unsafe fn munmap_deallocate(addr: *mut u8, size: usize) {
-    let result = libc::munmap(addr as *mut libc::c_void, size);
-    assert!(result == 0, "Could not munmap {:x}, {}: {}", addr as usize, size, result);
-}
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES.get() }
 
-#[link(name = "numa")]
-extern "C" {
-    //  Returns the NUMA node corresponding to a CPU, or -1 if the CPU is invalid.
-    fn numa_node_of_cpu(cpu: i32) -> i32;
+    #[cold]
+    #[inline(never)]
+    fn use_transparent_huge_pages(&self) -> bool {
+        CAPABILITIES.forgo_huge_tlb();
+        true
+    }
 
-    //  Returns the distance between two NUMA nodes.
-    //
-    //  A node has a distance 10 to itself; factors should be multiples of 10, although 11 and 21 has been observed.
-    fn numa_distance(left: i32, right: i32) -> i32;
-}
+    #[cold]
+    #[inline(never)]
+    fn huge_tlb_pools(&self) -> HugeTlbPools { CAPABILITIES.pools() }
+
+    #[cold]
+    #[inline(never)]
+    fn interleave(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        set_policy(pointer, size, MPOL_INTERLEAVE, MPOL_MF_MOVE, is_tiered)
+    }
+
+    fn distance(&self, from: NumaNodeIndex, to: NumaNodeIndex) -> Option<u8> {
+        TOPOLOGY.distance(from.value(), to.value())
+    }
+
+    #[cold]
+    fn node_of_page(&self, pointer: NonNull<u8>) -> Option<NumaNodeIndex> {
+        if !CAPABILITIES.get().numa {
+            return None;
+        }
+
+        page_numa_node(pointer).map(NumaNodeIndex::new)
+    }
+
+    #[cold]
+    fn refresh_topology(&self) -> Option<u32> {
+        //  Without NUMA, the single node is node 0, whichever the topology.
+        let nodes = TOPOLOGY.refresh();
+
+        Some(if CAPABILITIES.get().numa { nodes } else { 1 })
+    }
+
+    fn collapse(&self, pointer: NonNull<u8>, size: usize) -> Collapse {
+        if !collapse_supported() {
+            return Collapse::Unsupported;
+        }
+
+        //  Safety:
+        //  -   `MADV_COLLAPSE` preserves the content of the memory, copying it into the huge pages.
+        let result = unsafe { libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, MADV_COLLAPSE) };
+
+        if result == 0 {
+            return Collapse::Collapsed;
+        }
+
+        //  `EINVAL` is reported for memory backed by HugeTLB pages, or for a kernel without Transparent Huge Pages,
+        //  whereas `EAGAIN` or `ENOMEM` are transient.
+        if capabilities::errno() == libc::EINVAL { Collapse::Ineligible } else { Collapse::Failed }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn spawn_thread(&self, entry: fn()) -> bool {
+        extern "C" fn start(entry: *mut libc::c_void) -> *mut libc::c_void {
+            //  Safety:
+            //  -   `entry` was converted from a `fn()`, by `spawn_thread`.
+            let entry: fn() = unsafe { mem::transmute(entry) };
+
+            entry();
+
+            ptr::null_mut()
+        }
+
+        let mut thread = mem::MaybeUninit::<libc::pthread_t>::uninit();
+
+        let argument = entry as *mut libc::c_void;
+
+        //  Safety:
+        //  -   `thread` is valid for writes.
+        //  -   `start` is a start routine, expecting `entry` as argument.
+        let result = unsafe { libc::pthread_create(thread.as_mut_ptr(), ptr::null(), start, argument) };
+
+        if result != 0 {
+            return false;
+        }
+
+        //  Safety:
+        //  -   `thread` was initialized by `pthread_create`, on success.
+        unsafe { libc::pthread_detach(thread.assume_init()) };
+
+        true
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut request = libc::timespec {
+            tv_sec: duration.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_nsec: duration.subsec_nanos() as _,
+        };
+
+        //  Interrupted by a signal, the sleep resumes for the remaining duration.
+        loop {
+            let mut remaining = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+
+            //  Safety:
+            //  -   `request` and `remaining` are valid, for reads and writes respectively.
+            let result = unsafe { libc::nanosleep(&request as *const _, &mut remaining as *mut _) };
+
+            if result == 0 || (remaining.tv_sec == 0 && remaining.tv_nsec == 0) {
+                return;
+            }
+
+            request = remaining;
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn at_fork(&self, prepare: extern "C" fn(), child: extern "C" fn()) -> bool {
+        //  Safety:
+        //  -   `prepare` and `child` are handlers expecting no argument.
+        let result = unsafe { libc::pthread_atfork(Some(prepare), None, Some(child)) };
+
+        result == 0
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities { capabilities::detect_host(CAPABILITIES.get()) }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> {
+        match MAPPING_LATENCY.load(atomic::Ordering::Relaxed) {
+            0 => None,
+            latency => Some(Duration::from_nanos(latency - 1)),
+        }
+    }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let mut pointer = ptr::null_mut();
+
+        //  `posix_memalign` requires an alignment which is a multiple of the size of a pointer.
+        let align = layout.align().max(core::mem::size_of::<*mut libc::c_void>());
+
+        //  Safety:
+        //  -   `align` is a power of 2, and a multiple of the size of a pointer.
+        let result = unsafe { libc::posix_memalign(&mut pointer as *mut _, align, layout.size().max(1)) };
+
+        if result == 0 { NonNull::new(pointer as *mut u8) } else { None }
+    }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) { libc::free(pointer.as_ptr() as *mut libc::c_void) }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { OWNERSHIP.contains(pointer.as_ptr() as usize) }
+}
+
+//  The flag of `mmap` selecting 2 MB HugeTLB pages, the log2 of their size shifted by `MAP_HUGE_SHIFT`.
+const MAP_HUGE_2MB: libc::c_int = 21 << 26;
+
+//  The advice of `madvise` collapsing memory into Transparent Huge Pages, missing from the `libc` bindings of musl and
+//  Bionic.
+const MADV_COLLAPSE: libc::c_int = 25;
+
+//  The NUMA policies of `mbind`, and the flag migrating the pages already faulted in to the nodes of the policy.
+const MPOL_PREFERRED: libc::c_long = 1;
+const MPOL_BIND: libc::c_long = 2;
+const MPOL_INTERLEAVE: libc::c_long = 3;
+const MPOL_MF_MOVE: libc::c_ulong = 1 << 1;
+
+//  The name of the mappings of the Huge allocations, NUL-terminated.
+const HUGE_NAME: &[u8] = b"llmalloc:huge\0";
+
+//  The prefix of the name of the mappings of the `HugePage`s, followed by the index of their node, at most 10 digits,
+//  and NUL.
+const NODE_NAME_PREFIX: &[u8] = b"llmalloc:node";
+const NODE_NAME_LENGTH: usize = NODE_NAME_PREFIX.len() + 10 + 1;
+
+//  The number of ranges purged by a single `process_madvise`, at most.
+const MAXIMUM_PURGE_BATCH: usize = 16;
+
+//  Capabilities of the environment.
+static CAPABILITIES: capabilities::Detector = capabilities::Detector::new();
+
+//  NUMA topology of the machine.
+static TOPOLOGY: topology::Topology = topology::Topology::new();
+
+//  Metrics of the fallbacks.
+static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+//  Worst latency observed mapping memory, in nanoseconds: 0 if none was observed, otherwise 1 + latency.
+static MAPPING_LATENCY: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+//  Selects the "best" node.
+//
+//  The Linux kernel sometimes distinguishes nodes even though their distance is 11, when the distance to self is 10.
+//  This may lead to over-allocation, hence it is judged best to "cluster" the nodes together.
+//
+//  This function will therefore return the smallest node number whose distance to the `original` is less than or
+//  equal to the clustering distance, 11 by default, see `CLUSTERING`.
+//
+//  Within a container, the `original` node may be denied to the memory of the process by its cpuset, in which case the
+//  nearest allowed node stands in for it, and only the allowed nodes are clustered; the nodes of different tiers are
+//  never clustered together.
+fn select_node(original: NumaNodeIndex) -> NumaNodeIndex {
+    let original = TOPOLOGY.nearest_allowed(original.value());
+
+    if CLUSTERING.distance().is_none() {
+        return NumaNodeIndex::new(original);
+    }
+
+    //  The nodes of far memory are never clustered with those of near memory, lest their socket be served by either.
+    let far = TOPOLOGY.is_far(original);
+
+    for current in (0..original).filter(|current| TOPOLOGY.is_allowed(*current) && TOPOLOGY.is_far(*current) == far) {
+        if TOPOLOGY.distance(current, original).is_some_and(|distance| CLUSTERING.is_clustered(distance)) {
+            return NumaNodeIndex::new(current);
+        }
+    }
+
+    NumaNodeIndex::new(original)
+}
+
+//  Returns whether the environment variable `name`, NUL-terminated, is set to a value other than an empty string or
+//  `0`.
+fn environment_flag(name: &[u8]) -> bool {
+    debug_assert_eq!(Some(&0), name.last());
+
+    //  Safety:
+    //  -   `name` is NUL-terminated.
+    //  -   `getenv` does not allocate.
+    let value = unsafe { libc::getenv(name.as_ptr() as *const libc::c_char) };
+
+    if value.is_null() {
+        return false;
+    }
+
+    //  Safety:
+    //  -   `value` is non-null, and NUL-terminated.
+    let value = unsafe { core::ffi::CStr::from_ptr(value) };
+
+    !matches!(value.to_bytes(), b"" | b"0")
+}
+
+//  Attempts to allocate the required size in Huge Pages, unless HugeTLB is known to be unavailable.
+//
+//  A pool merely exhausted, as reported by `ENOMEM`, is not downgraded: HugeTLB pages may be released, or added to
+//  the pool, in the meantime, hence the next mapping tries anew.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`, flanked by guard pages if `guarded`, and mapped as per
+//  `options`.
+fn mmap_huge(size: usize, guarded: bool, options: MapOptions) -> Option<NonNull<u8>> {
+    const MAP_HUGE_SHIFT: u8 = 26;
+
+    //  The log2 of the page size, as expected by `MAP_HUGETLB`: 30 for 1 GB, 21 for 2 MB.
+    const MAP_HUGE_SIZE: libc::c_int =
+        (LLConfiguration::HUGE_PAGE_SIZE.value().trailing_zeros() as libc::c_int) << MAP_HUGE_SHIFT;
+
+    if !CAPABILITIES.get().huge_tlb {
+        return None;
+    }
+
+    let flags = libc::MAP_HUGETLB | MAP_HUGE_SIZE | map_flags(options);
+    let hint = options.address_hint().unwrap_or(0);
+
+    let mapped = if guarded { mmap_guarded(size, flags, hint) } else { mmap_allocate_at(size, flags, hint) };
+
+    //  A guarded mapping is always aligned, hence never unmapped by `mmap_check`, which would leave its guards behind.
+    let result = match mapped {
+        Some(pointer) => unsafe { mmap_check(pointer, size) },
+        None if capabilities::errno() == libc::ENOMEM => {
+            FALLBACKS.record(Fallback::HugeTlbExhaustion);
+            return None;
+        },
+        None => None,
+    };
+
+    match result {
+        Some(_) => FALLBACKS.record(Fallback::HugeTlbMapping),
+        None => {
+            FALLBACKS.record(Fallback::HugeTlbFailure);
+            CAPABILITIES.downgrade_huge_tlb();
+        },
+    }
+
+    result
+}
+
+//  Attempts to allocate the required size in 2 MB HugeTLB pages, unless they are known to be unavailable, as a fallback
+//  for HugeTLB pages of the Huge Page size.
+//
+//  The 2 MB HugeTLB pages are only aligned on 2 MB, hence a suitably aligned area is reserved first, then replaced. As
+//  for `mmap_huge`, a pool merely exhausted is not downgraded.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`, flanked by guard pages if `guarded`, and mapped as per
+//  `options`.
+fn mmap_huge_2mb(size: usize, guarded: bool, options: MapOptions) -> Option<NonNull<u8>> {
+    if !CAPABILITIES.get().huge_tlb_2mb {
+        return None;
+    }
+
+    let hint = options.address_hint().unwrap_or(0);
+    let reserved = if guarded { mmap_reserve(size, hint)? } else { mmap_over(size, 0, hint)? };
+
+    //  Safety:
+    //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use.
+    if unsafe { !mmap_replace(reserved, size, libc::MAP_HUGETLB | MAP_HUGE_2MB | map_flags(options)) } {
+        //  Read prior to `munmap`, which may overwrite it.
+        let exhausted = capabilities::errno() == libc::ENOMEM;
+
+        //  Safety:
+        //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use, flanked by guard pages if `guarded`.
+        unsafe { munmap_release(reserved, size, guarded) };
+
+        if exhausted {
+            FALLBACKS.record(Fallback::HugeTlbExhaustion);
+        } else {
+            FALLBACKS.record(Fallback::HugeTlbFailure);
+            CAPABILITIES.downgrade_huge_tlb_2mb();
+        }
+
+        return None;
+    }
+
+    FALLBACKS.record(Fallback::HugeTlb2MbMapping);
+
+    Some(reserved)
+}
+
+//  Attempts to allocate the required size in Normal (or Large) Pages, requesting Transparent Huge Pages if available.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`, flanked by guard pages if `guarded`, and mapped as per
+//  `options`.
+fn mmap_normal(size: usize, guarded: bool, options: MapOptions) -> Option<NonNull<u8>> {
+    let flags = map_flags(options);
+    let hint = options.address_hint().unwrap_or(0);
+
+    let result = if guarded {
+        mmap_guarded(size, flags, hint)
+    } else {
+        mmap_exact(size, flags, hint).or_else(|| {
+            FALLBACKS.record(Fallback::MmapRetry);
+            mmap_over(size, flags, hint)
+        })
+    };
+
+    let result = match result {
+        Some(result) => result,
+        None => {
+            FALLBACKS.record(Fallback::MmapFailure);
+            return None;
+        },
+    };
+
+    if CAPABILITIES.get().transparent_huge_pages {
+        FALLBACKS.record(Fallback::TransparentHugePageMapping);
+
+        //  Safety:
+        //  -   `result` points to a `mmap`ed area of at least `size` bytes.
+        //  -   The advice is merely a hint, hence its failure is inconsequential.
+        unsafe { libc::madvise(result.as_ptr() as *mut libc::c_void, size, libc::MADV_HUGEPAGE) };
+    } else {
+        FALLBACKS.record(Fallback::NormalPageMapping);
+    }
+
+    Some(result)
+}
+
+//  Attempts to allocate the required size from the file of the shared `heap`, at the offset equal to its address.
+//
+//  For HugeTLB pages, a pool exhausted fails the mapping, there being no fallback within the file.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`, flanked by guard pages if `guarded`, and mapped as per
+//  `options`.
+fn mmap_shared(size: usize, guarded: bool, heap: SharedHeap, options: MapOptions) -> Option<NonNull<u8>> {
+    let hint = options.address_hint().unwrap_or(0);
+    let reserved = if guarded { mmap_reserve(size, hint) } else { mmap_over(size, 0, hint) };
+
+    let reserved = match reserved {
+        Some(reserved) => reserved,
+        None => {
+            FALLBACKS.record(Fallback::MmapFailure);
+            return None;
+        },
+    };
+
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+
+    //  Safety:
+    //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use.
+    if unsafe { !mmap_replace_shared(reserved, size, heap.fd, prot, map_flags(options)) } {
+        //  Read prior to `munmap`, which may overwrite it.
+        let exhausted = capabilities::errno() == libc::ENOMEM;
+
+        //  Safety:
+        //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use, flanked by guard pages if `guarded`.
+        //  -   The memory was never touched, hence holds no page of the file.
+        unsafe { munmap_release(reserved, size, guarded) };
+
+        if exhausted && heap.backing == SharedBacking::HugeTlb {
+            FALLBACKS.record(Fallback::HugeTlbExhaustion);
+        } else {
+            FALLBACKS.record(Fallback::MmapFailure);
+        }
+
+        return None;
+    }
+
+    match heap.backing {
+        SharedBacking::HugeTlb => FALLBACKS.record(Fallback::HugeTlb2MbMapping),
+        SharedBacking::Normal if CAPABILITIES.get().transparent_huge_pages => {
+            FALLBACKS.record(Fallback::TransparentHugePageMapping);
+
+            //  Safety:
+            //  -   `reserved` points to a `mmap`ed area of `size` bytes.
+            //  -   The advice is merely a hint, hence its failure is inconsequential; notably, the Transparent Huge
+            //      Pages of shared memory are governed by `shmem_enabled`.
+            unsafe { libc::madvise(reserved.as_ptr() as *mut libc::c_void, size, libc::MADV_HUGEPAGE) };
+        },
+        SharedBacking::Normal => FALLBACKS.record(Fallback::NormalPageMapping),
+    }
+
+    Some(reserved)
+}
+
+//  Attempts to commit the required size out of the reservation.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_reserved(size: usize) -> Option<NonNull<u8>> {
+    let pointer = match ADDRESS_SPACE.claim(size) {
+        Some(pointer) => pointer,
+        None => {
+            FALLBACKS.record(Fallback::MmapFailure);
+            return None;
+        },
+    };
+
+    //  Safety:
+    //  -   `pointer` points to `size` bytes claimed out of the reservation, not in use.
+    if unsafe { !commit_reserved(pointer, size) } {
+        //  Safety:
+        //  -   `pointer` points to `size` bytes claimed out of the reservation, never used.
+        unsafe { munmap_heap(pointer, size, false, None) };
+
+        FALLBACKS.record(Fallback::MmapFailure);
+        return None;
+    }
+
+    Some(pointer)
+}
+
+//  Attempts to allocate the required size in Normal (or Large) Pages, with `extra_flags`, at `hint` if non-zero.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_exact(size: usize, extra_flags: i32, hint: usize) -> Option<NonNull<u8>> {
+    mmap_allocate_at(size, extra_flags, hint)
+        .and_then(|pointer| unsafe { mmap_check(pointer, size) })
+}
+
+//  Attempts to allocate the required size in Normal (or Large) Pages, with `extra_flags`, near `hint` if non-zero.
+//
+//  Ensures the alignment is met by over-allocated then trimming front and back.
+fn mmap_over(size: usize, extra_flags: i32, hint: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    let over_size = size + ALIGNMENT.value();
+    let front_pointer = mmap_allocate_at(over_size, extra_flags, hint)?;
+
+    let back_size = (front_pointer.as_ptr() as usize) % ALIGNMENT;
+    let front_size = ALIGNMENT.value() - back_size;
+
+    debug_assert!(front_size <= ALIGNMENT.value(), "{} > {}", front_size, ALIGNMENT.value());
+    debug_assert!(back_size < ALIGNMENT.value(), "{} >= {}", back_size, ALIGNMENT.value());
+    debug_assert!(front_size + size + back_size == over_size,
+        "{} + {} + {} != {}", front_size, size, back_size, over_size);
+
+    //  Safety:
+    //  -   `front_size` is less than `over_size`, hence the result is within the allocated block.
+    let aligned_pointer = unsafe { front_pointer.as_ptr().add(front_size) };
+
+    debug_assert!(aligned_pointer as usize % ALIGNMENT == 0,
+        "{:x} not {:x}-aligned!", aligned_pointer as usize, ALIGNMENT.value());
+
+    //  Safety:
+    //  -   `front_size + size` is less than `over_size`, hence the result is within the allocated block,
+    //      or pointing to its end.
+    let back_pointer = unsafe { aligned_pointer.add(size) };
+
+    if front_size > 0 {
+        //  Safety:
+        //  -   `front_pointer` points to a `mmap`ed area of at least `front_size` bytes.
+        //  -   `[front_pointer, front_pointer + front_size)` is no longer in use.
+        unsafe { munmap_deallocate(front_pointer.as_ptr(), front_size) };
+    }
+
+    if back_size > 0 {
+        //  Safety:
+        //  -   `back_pointer` points to a `mmap`ed area of at least `back_size` bytes.
+        //  -   `[back_pointer, back_pointer + back_size)` is no longer in use.
+        unsafe { munmap_deallocate(back_pointer, back_size) };
+    }
+
+    NonNull::new(aligned_pointer)
+}
+
+//  Attempts to allocate the required size, with `extra_flags`, near `hint` if non-zero, flanked by guard pages.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_guarded(size: usize, extra_flags: i32, hint: usize) -> Option<NonNull<u8>> {
+    let reserved = mmap_reserve(size, hint)?;
+
+    //  Safety:
+    //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use.
+    if unsafe { mmap_replace(reserved, size, extra_flags) } {
+        return Some(reserved);
+    }
+
+    //  Read prior to `munmap`, which may overwrite it, as the callers inspect it.
+    let error = capabilities::errno();
+
+    //  Safety:
+    //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use, flanked by guard pages.
+    unsafe { munmap_release(reserved, size, true) };
+
+    capabilities::set_errno(error);
+
+    None
+}
+
+//  Reserves the required size of address space, near `hint` if non-zero, flanked by a guard page on either side.
+//
+//  The reservation is inaccessible, and meant to be replaced by `mmap_replace`, whereas the guard pages remain so
+//  until released by `munmap_release`.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_reserve(size: usize, hint: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    let guard = os_page_size().value();
+
+    let over_size = size.checked_add(ALIGNMENT.value() + 2 * guard)?;
+    let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE;
+
+    //  The hint is that of the usable area, which is preceded by the guard page.
+    let hint = hint.saturating_sub(guard) as *mut libc::c_void;
+
+    //  Safety:
+    //  -   The file descriptor, and offset, are suitable for MAP_ANONYMOUS.
+    //  -   The address hint is merely a hint, in the absence of MAP_FIXED.
+    let start = unsafe { libc::mmap(hint, over_size, libc::PROT_NONE, flags, -1, 0) };
+
+    if start == libc::MAP_FAILED {
+        return None;
+    }
+
+    let start = start as usize;
+    let aligned = (start + guard + ALIGNMENT.value() - 1) & !(ALIGNMENT.value() - 1);
+
+    let front_size = aligned - guard - start;
+    let back_size = over_size - front_size - size - 2 * guard;
+
+    if front_size > 0 {
+        //  Safety:
+        //  -   `[start, start + front_size)` is within the reserved area, and not in use.
+        unsafe { munmap_deallocate(start as *mut u8, front_size) };
+    }
+
+    if back_size > 0 {
+        //  Safety:
+        //  -   `[aligned + size + guard, aligned + size + guard + back_size)` is within the reserved area, and not in
+        //      use.
+        unsafe { munmap_deallocate((aligned + size + guard) as *mut u8, back_size) };
+    }
+
+    NonNull::new(aligned as *mut u8)
+}
+
+//  Replaces the required size of memory at `pointer` with a fresh read-write mapping, with `extra_flags`.
+//
+//  Returns whether the mapping succeeded; on failure, the area may have been unmapped.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes, no longer in use.
+unsafe fn mmap_replace(pointer: NonNull<u8>, size: usize, extra_flags: i32) -> bool {
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+    let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED | extra_flags;
+
+    let result = libc::mmap(pointer.as_ptr() as *mut libc::c_void, size, prot, flags, -1, 0);
+
+    debug_assert!(result == libc::MAP_FAILED || result as *mut u8 == pointer.as_ptr());
+
+    result != libc::MAP_FAILED
+}
+
+//  Replaces the required size of memory at `pointer` with a mapping of the file `fd`, with `prot` and `extra_flags`, at
+//  the offset equal to `pointer`.
+//
+//  Returns whether the mapping succeeded; on failure, the area may have been unmapped.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes, no longer in use.
+unsafe fn mmap_replace_shared(pointer: NonNull<u8>, size: usize, fd: i32, prot: i32, extra_flags: i32) -> bool {
+    let offset = pointer.as_ptr() as usize;
+
+    //  Past the end of the file, the memory would fault on access.
+    if !within_shared_file(offset, size) {
+        capabilities::set_errno(libc::EOVERFLOW);
+        return false;
+    }
+
+    let flags = libc::MAP_SHARED | libc::MAP_FIXED | extra_flags;
+
+    let result = libc::mmap(pointer.as_ptr() as *mut libc::c_void, size, prot, flags, fd, offset as libc::off_t);
+
+    debug_assert!(result == libc::MAP_FAILED || result as *mut u8 == pointer.as_ptr());
+
+    result != libc::MAP_FAILED
+}
+
+//  `mmap` alignment checker.
+//
+//  Returns a non-null pointer if suitably aligned, and None otherwise.
+//  If none is returned, the memory has been unmapped.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes.
+//  -   Assumes that `pointer` is no longer in use, unless returned.
+unsafe fn mmap_check(pointer: NonNull<u8>, size: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    if pointer.as_ptr() as usize % ALIGNMENT == 0 {
+        Some(pointer)
+    } else {
+        //  Safety:
+        //  -   `pointer` points to a `mmap`ed area of at least `size` bytes.
+        //  -   `[pointer, pointer + size)` is no longer in use.
+        munmap_deallocate(pointer.as_ptr(), size);
+        None
+    }
+}
+
+//  Returns `options`, hinted within `bounds`, if any, for a mapping of `size` bytes.
+fn hinted(options: MapOptions, bounds: Option<AddressRange>, size: usize) -> MapOptions {
+    match bounds {
+        Some(range) => options.with_address_hint(ADDRESS_BOUNDS.hint(range, size)),
+        None => options,
+    }
+}
+
+//  Returns whether the `size` bytes at `pointer`, mapped at `hint`, lie within `bounds`, if any, advancing the hints
+//  past them, or past `hint` if they do not.
+//
+//  A mapping outside of the bounds is recorded, and is to be unmapped.
+fn within_bounds(bounds: Option<AddressRange>, hint: usize, pointer: NonNull<u8>, size: usize) -> bool {
+    let range = match bounds {
+        Some(range) => range,
+        None => return true,
+    };
+
+    if range.contains_all(pointer.as_ptr(), size) {
+        ADDRESS_BOUNDS.advance(pointer.as_ptr() as usize, size);
+        return true;
+    }
+
+    ADDRESS_BOUNDS.advance(hint, size);
+    FALLBACKS.record(Fallback::OutOfRangeMapping);
+
+    false
+}
+
+//  Returns the flags of the mappings of the heap selected by `options`.
+fn map_flags(options: MapOptions) -> i32 {
+    let mut flags = 0;
+
+    if options.no_reserve() {
+        flags |= libc::MAP_NORESERVE;
+    }
+
+    if options.locked() {
+        flags |= libc::MAP_LOCKED;
+    }
+
+    flags
+}
+
+//  Applies the NUMA policy, and the advice on same-page merging, selected by `options`, to the `size` bytes at
+//  `pointer`, mapped for `node`, returning whether the placement selected is honored.
+//
+//  The placement is only required under a strict binding: the other policies, and the advice, are merely hints, hence
+//  their failure is inconsequential. A machine with a single node honors any binding, whether NUMA is available or not.
+fn advise(options: MapOptions, pointer: NonNull<u8>, size: usize, node: NumaNodeIndex) -> bool {
+    let applied = match (options.interleaved(), options.binding()) {
+        (true, _) => set_policy(pointer, size, MPOL_INTERLEAVE, 0, is_tiered),
+        //  Near memory is first touched locally, as with the default policy, yet never spills onto far memory.
+        (false, NodeBinding::Unbound) if TOPOLOGY.far_nodes() > 0 && !TIERING.spills() =>
+            set_policy(pointer, size, MPOL_BIND, 0, |other| !TOPOLOGY.is_far(other.value())),
+        (false, NodeBinding::Unbound) => true,
+        //  The nodes clustered with `node` are served by its socket, hence its memory may live on either.
+        (false, NodeBinding::Bind) => set_policy(pointer, size, MPOL_BIND, 0, |other| {
+            select_node(other) == node || (TIERING.spills() && TOPOLOGY.is_far(other.value()))
+        }),
+        (false, NodeBinding::Preferred) => set_policy(pointer, size, MPOL_PREFERRED, 0, |other| other == node),
+    };
+
+    //  The kernel refuses to bind to nodes without memory, or denied by the cpuset, as the mask is then empty.
+    let strict = !options.interleaved() && options.binding() == NodeBinding::Bind;
+
+    if strict && !applied && TOPOLOGY.nodes() > 1 {
+        return false;
+    }
+
+    let advice = match options.merging() {
+        Merging::Unadvised => return true,
+        Merging::Mergeable => libc::MADV_MERGEABLE,
+        Merging::Unmergeable => libc::MADV_UNMERGEABLE,
+    };
+
+    //  Safety:
+    //  -   `pointer` points to a `mmap`ed area of `size` bytes.
+    unsafe { libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, advice) };
+
+    true
+}
+
+//  Applies the NUMA policy `mode` to the pages of the `size` bytes at `pointer`, over the nodes `selected`, if NUMA is
+//  available, returning whether it was applied.
+//
+//  The pages already faulted in are left where they are, unless `flags` holds `MPOL_MF_MOVE`, hence the policy is best
+//  applied before the memory is touched.
+fn set_policy<F>(pointer: NonNull<u8>, size: usize, mode: libc::c_long, flags: libc::c_ulong, selected: F) -> bool
+    where
+        F: Fn(NumaNodeIndex) -> bool,
+{
+    //  The nodes beyond the mask, if any, are left out.
+    const WORDS: usize = 16;
+    const BITS: usize = libc::c_ulong::BITS as usize;
+
+    if !CAPABILITIES.get().numa {
+        return false;
+    }
+
+    let nodes = (TOPOLOGY.nodes() as usize).min(WORDS * BITS);
+    let mut mask: [libc::c_ulong; WORDS] = [0; WORDS];
+
+    //  The nodes denied by the cpuset of the process would fail the policy.
+    let allowed = |node: &usize| TOPOLOGY.is_allowed(*node as u32) && selected(NumaNodeIndex::new(*node as u32));
+
+    for node in (0..nodes).filter(allowed) {
+        mask[node / BITS] |= 1 << (node % BITS);
+    }
+
+    if mask.iter().all(|word| *word == 0) {
+        return false;
+    }
+
+    //  Safety:
+    //  -   `pointer` points to a `mmap`ed area of `size` bytes.
+    //  -   `mask` holds `nodes` bits, the kernel reading one less than its `maxnode` argument.
+    //  -   The policy is merely a hint, hence its failure is inconsequential.
+    let result = unsafe {
+        let maximum_nodes = nodes as libc::c_ulong + 1;
+
+        libc::syscall(libc::SYS_mbind, pointer.as_ptr(), size, mode, mask.as_ptr(), maximum_nodes, flags)
+    };
+
+    result == 0
+}
+
+//  Returns whether the pages of the heap may be placed on `node`, as per its tier: the nodes of far memory are only
+//  selected once spilling onto them is enabled.
+fn is_tiered(node: NumaNodeIndex) -> bool { TIERING.spills() || !TOPOLOGY.is_far(node.value()) }
+
+//  Names the `size` bytes at `pointer` after `name`, NUL-terminated, as listed in `/proc/<pid>/maps`, where they
+//  appear as `[anon:<name>]`.
+//
+//  Naming requires Linux 5.17, built with `CONFIG_ANON_VMA_NAME`, and only applies to anonymous memory, hence neither
+//  to HugeTLB pages, nor to the memory of a shared heap; the name is merely a label, hence its failure is
+//  inconsequential.
+fn name(pointer: NonNull<u8>, size: usize, name: &[u8]) {
+    const PR_SET_VMA: libc::c_int = 0x53564d41;
+    const PR_SET_VMA_ANON_NAME: libc::c_ulong = 0;
+
+    debug_assert_eq!(Some(&0), name.last());
+
+    //  Safety:
+    //  -   `pointer` points to a `mmap`ed area of `size` bytes.
+    //  -   `name` is NUL-terminated, and copied by the kernel.
+    unsafe {
+        let (address, size) = (pointer.as_ptr() as libc::c_ulong, size as libc::c_ulong);
+
+        libc::prctl(PR_SET_VMA, PR_SET_VMA_ANON_NAME, address, size, name.as_ptr() as libc::c_ulong)
+    };
+}
+
+//  Returns the name of the mappings of the `HugePage`s of `node`, as in `llmalloc:node0`, NUL-terminated.
+fn node_name(node: NumaNodeIndex) -> [u8; NODE_NAME_LENGTH] {
+    let mut name = [0; NODE_NAME_LENGTH];
+
+    name[..NODE_NAME_PREFIX.len()].copy_from_slice(NODE_NAME_PREFIX);
+
+    //  The digits, least significant first.
+    let (mut digits, mut count, mut value) = ([0u8; 10], 0, node.value());
+
+    loop {
+        digits[count] = b'0' + (value % 10) as u8;
+        count += 1;
+        value /= 10;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    for (index, digit) in digits[..count].iter().rev().enumerate() {
+        name[NODE_NAME_PREFIX.len() + index] = *digit;
+    }
+
+    name
+}
+
+//  Wrapper around `mmap`.
+//
+//  Returns a pointer to `size` bytes of memory; does not guarantee any alignment.
+fn mmap_allocate(size: usize, extra_flags: i32) -> Option<NonNull<u8>> { mmap_allocate_at(size, extra_flags, 0) }
+
+//  Wrapper around `mmap`, at `hint` if non-zero and free.
+//
+//  Returns a pointer to `size` bytes of memory; does not guarantee any alignment.
+fn mmap_allocate_at(size: usize, extra_flags: i32, hint: usize) -> Option<NonNull<u8>> {
+    let length = size;
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+    let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | extra_flags;
+
+    //  Merely a hint, in the absence of MAP_FIXED.
+    let addr = hint as *mut libc::c_void;
+    //  When used in conjunction with MAP_ANONYMOUS, fd is mandated to be -1 on some implementations.
+    let fd = -1;
+    //  When used in conjunction with MAP_ANONYMOUS, offset is mandated to be 0 on some implementations.
+    let offset = 0;
+
+    //  Safety:
+    //  -   `addr`, `fd`, and `offset` are suitable for MAP_ANONYMOUS.
+    let result = unsafe { libc::mmap(addr, length, prot, flags, fd, offset) };
+
+    let result = if result != libc::MAP_FAILED { result as *mut u8 } else { ptr::null_mut() };
+    NonNull::new(result)
+}
+
+//  Returns the size of the OS pages.
+fn os_page_size() -> PowerOf2 {
+    const DEFAULT: PowerOf2 = unsafe { PowerOf2::new_unchecked(4096) };
+
+    //  Safety:
+    //  -   `sysconf` has no precondition.
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+
+    if size <= 0 { DEFAULT } else { PowerOf2::new(size as usize).unwrap_or(DEFAULT) }
+}
+
+//  Maps `size` bytes of memory, aligned on `alignment`, a power of 2, by over-allocating then trimming front and back.
+fn mmap_aligned(size: usize, alignment: usize) -> Option<NonNull<u8>> {
+    debug_assert!(alignment.is_power_of_two());
+
+    let over_size = size.checked_add(alignment)?;
+    let front_pointer = mmap_allocate(over_size, 0)?;
+
+    let start = front_pointer.as_ptr() as usize;
+    let aligned = (start + alignment - 1) & !(alignment - 1);
+
+    let front_size = aligned - start;
+    let back_size = over_size - front_size - size;
+
+    if front_size > 0 {
+        //  Safety:
+        //  -   `[start, start + front_size)` is within the mapped area, and not in use.
+        unsafe { munmap_deallocate(front_pointer.as_ptr(), front_size) };
+    }
+
+    if back_size > 0 {
+        //  Safety:
+        //  -   `[aligned + size, aligned + size + back_size)` is within the mapped area, and not in use.
+        unsafe { munmap_deallocate((aligned + size) as *mut u8, back_size) };
+    }
+
+    NonNull::new(aligned as *mut u8)
+}
+
+//  Maps `size` bytes of shared memory twice, read-write then read-execute, returning both views.
+//
+//  The memory is backed by an anonymous file, closed once mapped, the mappings keeping it alive.
+fn mmap_dual(size: usize) -> Option<(NonNull<u8>, NonNull<u8>)> {
+    const NAME: &[u8] = b"llmalloc-code\0";
+
+    //  Safety:
+    //  -   `NAME` is NUL-terminated.
+    let fd = unsafe { libc::memfd_create(NAME.as_ptr() as *const libc::c_char, libc::MFD_CLOEXEC) };
+
+    if fd < 0 {
+        return None;
+    }
+
+    let map = |prot| {
+        //  Safety:
+        //  -   `fd` is a valid file descriptor, of at least `size` bytes once truncated.
+        let result = unsafe { libc::mmap(ptr::null_mut(), size, prot, libc::MAP_SHARED, fd, 0) };
+
+        let result = if result != libc::MAP_FAILED { result as *mut u8 } else { ptr::null_mut() };
+        NonNull::new(result)
+    };
+
+    //  Safety:
+    //  -   `fd` is a valid file descriptor.
+    let truncated = unsafe { libc::ftruncate(fd, size as libc::off_t) } == 0;
+
+    let writable = if truncated { map(libc::PROT_READ | libc::PROT_WRITE) } else { None };
+
+    let result = writable.and_then(|writable| {
+        match map(libc::PROT_READ | libc::PROT_EXEC) {
+            Some(executable) => Some((writable, executable)),
+            None => {
+                //  Safety:
+                //  -   `writable` points to a `mmap`ed area of `size` bytes, not yet in use.
+                unsafe { munmap_deallocate(writable.as_ptr(), size) };
+                None
+            },
+        }
+    });
+
+    //  Safety:
+    //  -   `fd` is a valid file descriptor, no longer needed once mapped.
+    unsafe { libc::close(fd) };
+
+    result
+}
+
+//  Creates the anonymous file of a shared heap, backed by `backing`, returning its descriptor.
+//
+//  The file spans the address space, sparsely, so that the memory at any address may live at the offset equal to it.
+fn memfd_heap(backing: SharedBacking) -> Option<i32> {
+    const NAME: &[u8] = b"llmalloc-heap\0";
+
+    let flags = match backing {
+        SharedBacking::Normal => libc::MFD_CLOEXEC,
+        SharedBacking::HugeTlb => libc::MFD_CLOEXEC | libc::MFD_HUGETLB | libc::MFD_HUGE_2MB,
+    };
+
+    //  Safety:
+    //  -   `NAME` is NUL-terminated.
+    let fd = unsafe { libc::memfd_create(NAME.as_ptr() as *const libc::c_char, flags) };
+
+    if fd < 0 {
+        return None;
+    }
+
+    //  Safety:
+    //  -   `fd` is a valid file descriptor.
+    if unsafe { libc::ftruncate(fd, shared_file_size() as libc::off_t) } != 0 {
+        //  Safety:
+        //  -   `fd` is a valid file descriptor, not yet published.
+        unsafe { libc::close(fd) };
+        return None;
+    }
+
+    Some(fd)
+}
+
+//  Returns whether the kernel supports `MADV_COLLAPSE`, introduced in Linux 6.1, as looked up once.
+//
+//  The support cannot be probed by `madvise` itself, which reports `EINVAL` both for an unknown advice and for memory
+//  which cannot be collapsed.
+fn collapse_supported() -> bool {
+    const UNKNOWN: u8 = 0;
+    const UNSUPPORTED: u8 = 1;
+    const SUPPORTED: u8 = 2;
+
+    static SUPPORT: atomic::AtomicU8 = atomic::AtomicU8::new(UNKNOWN);
+
+    match SUPPORT.load(atomic::Ordering::Relaxed) {
+        UNSUPPORTED => false,
+        SUPPORTED => true,
+        _ => {
+            let supported = kernel_version().is_some_and(|version| version >= (6, 1));
+
+            SUPPORT.store(if supported { SUPPORTED } else { UNSUPPORTED }, atomic::Ordering::Relaxed);
+
+            supported
+        },
+    }
+}
+
+//  Forcibly purges the `ranges`, at most `MAXIMUM_PURGE_BATCH`, with a single `process_madvise`, or with one `madvise`
+//  per range if the kernel does not support it, as looked up once.
+//
+//  `process_madvise` was introduced in Linux 5.10, yet only accepts `MADV_DONTNEED` on more recent kernels.
+//
+//  #   Safety
+//
+//  -   Assumes that the memory of `ranges` is no longer in use, hence its content may be discarded.
+unsafe fn purge_batch(ranges: &[(NonNull<u8>, usize)]) {
+    const UNKNOWN: u8 = 0;
+    const UNSUPPORTED: u8 = 1;
+    const SUPPORTED: u8 = 2;
+
+    static SUPPORT: atomic::AtomicU8 = atomic::AtomicU8::new(UNKNOWN);
+
+    debug_assert!(ranges.len() <= MAXIMUM_PURGE_BATCH);
+
+    let purge_each = || {
+        for &(pointer, size) in ranges {
+            libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_DONTNEED);
+        }
+    };
+
+    if SUPPORT.load(atomic::Ordering::Relaxed) == UNSUPPORTED {
+        return purge_each();
+    }
+
+    //  A descriptor of the process is opened anew for each batch, as one inherited across `fork` would designate the
+    //  parent.
+    let pidfd = libc::syscall(libc::SYS_pidfd_open, libc::getpid(), 0 as libc::c_uint);
+
+    if pidfd < 0 {
+        if capabilities::errno() == libc::ENOSYS {
+            SUPPORT.store(UNSUPPORTED, atomic::Ordering::Relaxed);
+        }
+
+        return purge_each();
+    }
+
+    let mut vectors = [libc::iovec { iov_base: ptr::null_mut(), iov_len: 0 }; MAXIMUM_PURGE_BATCH];
+
+    for (vector, &(pointer, size)) in vectors.iter_mut().zip(ranges) {
+        *vector = libc::iovec { iov_base: pointer.as_ptr() as *mut libc::c_void, iov_len: size };
+    }
+
+    let (count, flags) = (ranges.len() as libc::size_t, 0 as libc::c_uint);
+    let result = libc::syscall(libc::SYS_process_madvise, pidfd as libc::c_int, vectors.as_ptr(), count,
+        libc::MADV_DONTNEED, flags);
+    let error = capabilities::errno();
+
+    libc::close(pidfd as libc::c_int);
+
+    let total: usize = ranges.iter().map(|range| range.1).sum();
+
+    if result >= 0 && result as usize == total {
+        SUPPORT.store(SUPPORTED, atomic::Ordering::Relaxed);
+        return;
+    }
+
+    //  An advice which is not accepted is reported as `EINVAL`; other failures, and partial purges, are deemed
+    //  transient.
+    if result < 0 && matches!(error, libc::EINVAL | libc::ENOSYS | libc::EPERM) {
+        SUPPORT.store(UNSUPPORTED, atomic::Ordering::Relaxed);
+    }
+
+    //  Purging anew the ranges already purged is harmless.
+    purge_each();
+}
+
+//  Returns the major and minor versions of the running kernel, as per `uname`, or None if unknown.
+fn kernel_version() -> Option<(u32, u32)> {
+    //  Safety:
+    //  -   `utsname` is only made of arrays of `c_char`, for which all zeroes is a valid value.
+    let mut name: libc::utsname = unsafe { mem::zeroed() };
+
+    //  Safety:
+    //  -   `name` is valid for writes.
+    if unsafe { libc::uname(&mut name) } != 0 {
+        return None;
+    }
+
+    //  The release is of the form "6.1.0-13-amd64", terminated by a NUL.
+    let mut release = name.release.iter().map(|c| *c as u8);
+
+    let mut number = || {
+        let mut value: Option<u32> = None;
+
+        for byte in release.by_ref() {
+            if !byte.is_ascii_digit() {
+                break;
+            }
+
+            value = Some(value.unwrap_or(0).saturating_mul(10).saturating_add((byte - b'0') as u32));
+        }
+
+        value
+    };
+
+    let major = number()?;
+    let minor = number()?;
+
+    Some((major, minor))
+}
+
+//  Returns the size of the file of a shared heap, spanning the address space, bar any address exceeding 48 bits.
+fn shared_file_size() -> u64 {
+    const SIZE: u64 = 1 << 48;
+
+    SIZE.min(libc::off_t::MAX as u64)
+}
+
+//  Returns whether the `size` bytes at `address` live within the file of a shared heap.
+fn within_shared_file(address: usize, size: usize) -> bool {
+    (address as u64).checked_add(size as u64).is_some_and(|end| end <= shared_file_size())
+}
+
+//  Reserves `size` bytes of address space, inaccessible, and flanked by guard pages, mapping the file of the shared
+//  heap over them, if any, at the offset equal to their address.
+//
+//  A shared heap backed by HugeTLB pages is not reserved, as a reservation of its file would commit its pages.
+fn reserve_address_space(size: usize) -> Option<NonNull<u8>> {
+    let shared = SHARED.latch(&LLPlatform::new(), memfd_heap);
+
+    if shared.is_some_and(|heap| heap.backing == SharedBacking::HugeTlb) {
+        return None;
+    }
+
+    let bounds = ADDRESS_BOUNDS.latch();
+    let hint = bounds.map_or(0, |range| ADDRESS_BOUNDS.hint(range, size));
+
+    let reserved = mmap_reserve(size, hint)?;
+
+    if !within_bounds(bounds, hint, reserved, size) {
+        //  Safety:
+        //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use, flanked by guard pages.
+        unsafe { munmap_release(reserved, size, true) };
+        return None;
+    }
+
+    if let Some(heap) = shared {
+        //  Safety:
+        //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use.
+        if unsafe { !mmap_replace_shared(reserved, size, heap.fd, libc::PROT_NONE, 0) } {
+            //  Safety:
+            //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use, flanked by guard pages.
+            unsafe { munmap_release(reserved, size, true) };
+            return None;
+        }
+    }
+
+    Some(reserved)
+}
+
+//  Commits the `size` bytes of the reservation at `pointer`, making them accessible, and requesting Transparent Huge
+//  Pages if available.
+//
+//  Returns whether the memory was committed.
+//
+//  #   Safety
+//
+//  -   Assumes that the `size` bytes at `pointer` are claimed out of the reservation, and not in use.
+unsafe fn commit_reserved(pointer: NonNull<u8>, size: usize) -> bool {
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+
+    //  As the reservation is not accounted for, neither is the memory committed out of it, until touched.
+    if libc::mprotect(pointer.as_ptr() as *mut libc::c_void, size, prot) != 0 {
+        return false;
+    }
+
+    if CAPABILITIES.get().transparent_huge_pages {
+        FALLBACKS.record(Fallback::TransparentHugePageMapping);
+
+        //  The advice is merely a hint, hence its failure is inconsequential.
+        libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_HUGEPAGE);
+    } else {
+        FALLBACKS.record(Fallback::NormalPageMapping);
+    }
+
+    true
+}
+
+//  Decommits the `size` bytes of the reservation at `pointer`, releasing their memory, punching them out of the file of
+//  the `shared` heap if any, and making them inaccessible.
+//
+//  Unlike unmapping, decommitting never leaves a hole in the reservation, which another mapping could then occupy.
+//
+//  #   Safety
+//
+//  -   Assumes that the `size` bytes at `pointer` are claimed out of the reservation, and no longer in use.
+unsafe fn decommit_reserved(pointer: NonNull<u8>, size: usize, shared: Option<SharedHeap>) {
+    //  Failures are benign, the memory remaining committed, or accessible, until committed anew.
+    match shared {
+        Some(heap) => punch_hole(heap, pointer.as_ptr(), size),
+        None => {
+            libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_DONTNEED);
+        },
+    }
+
+    libc::mprotect(pointer.as_ptr() as *mut libc::c_void, size, libc::PROT_NONE);
+}
+
+//  Resizes the `size` bytes at `pointer`, committed out of the reservation, to `new_size` bytes, in place if the chunks
+//  allow, or by copy onto other chunks otherwise.
+//
+//  Returns the resized area on success, and None otherwise, in which case the area is left untouched.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to `size` bytes committed out of the reservation, backed by the `shared` heap if
+//      any.
+unsafe fn reallocate_reserved(
+    platform: &LLPlatform,
+    pointer: NonNull<u8>,
+    size: usize,
+    new_size: usize,
+    shared: Option<SharedHeap>
+)
+    -> Option<NonNull<u8>>
+{
+    //  Shrink in place, by decommitting the tail.
+    if new_size <= size {
+        if new_size < size {
+            #[cfg(feature = "system-fallback")]
+            OWNERSHIP.clear(pointer.as_ptr() as usize + new_size, size - new_size);
+
+            munmap_heap(NonNull::new_unchecked(pointer.as_ptr().add(new_size)), size - new_size, false, shared);
+        }
+
+        return Some(pointer);
+    }
+
+    let (tail, extra) = (NonNull::new_unchecked(pointer.as_ptr().add(size)), new_size - size);
+
+    //  Grow in place, if the adjacent chunks are free.
+    if ADDRESS_SPACE.claim_at(tail, extra) {
+        if !commit_reserved(tail, extra) {
+            munmap_heap(tail, extra, false, shared);
+            return None;
+        }
+
+        #[cfg(feature = "system-fallback")]
+        if !OWNERSHIP.mark(tail.as_ptr() as usize, extra) {
+            munmap_heap(tail, extra, false, shared);
+            return None;
+        }
+
+        //  Only Huge allocations are ever reallocated.
+        name(tail, extra, HUGE_NAME);
+
+        PREFAULT.prefault(platform, tail, extra, os_page_size().value());
+        PINNING.pin(platform, tail, extra);
+
+        return Some(pointer);
+    }
+
+    //  Otherwise, move by copy onto other chunks, the reservation not being remappable.
+    let target = mmap_reserved(new_size)?;
+
+    #[cfg(feature = "system-fallback")]
+    if !OWNERSHIP.mark(target.as_ptr() as usize, new_size) {
+        munmap_heap(target, new_size, false, shared);
+        return None;
+    }
+
+    ptr::copy_nonoverlapping(pointer.as_ptr(), target.as_ptr(), size);
+
+    #[cfg(feature = "system-fallback")]
+    OWNERSHIP.clear(pointer.as_ptr() as usize, size);
+
+    munmap_heap(pointer, size, false, shared);
+
+    PREFAULT.prefault(platform, NonNull::new_unchecked(target.as_ptr().add(size)), extra, os_page_size().value());
+
+    //  Unlike `mremap`, a copy does not carry over the locks, nor the name, of the pages.
+    PINNING.pin(platform, target, new_size);
+    name(target, new_size, HUGE_NAME);
+
+    Some(target)
+}
+
+//  Moves the `size` bytes at `pointer` onto a fresh mapping of `new_size` bytes of the shared `heap`, within `bounds`
+//  if any, by copy.
+//
+//  Returns the moved area on success, and None otherwise, in which case the area is left untouched.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a mapping of at least `size` bytes of the shared `heap`, flanked by guard pages
+//      if `guarded`.
+unsafe fn move_shared(
+    platform: &LLPlatform,
+    pointer: NonNull<u8>,
+    size: usize,
+    new_size: usize,
+    guarded: bool,
+    heap: SharedHeap,
+    bounds: Option<AddressRange>
+)
+    -> Option<NonNull<u8>>
+{
+    let options = hinted(MAP_OPTIONS.get(), bounds, new_size);
+    let target = mmap_shared(new_size, guarded, heap, options)?;
+
+    if !within_bounds(bounds, options.address_hint().unwrap_or(0), target, new_size) {
+        munmap_release(target, new_size, guarded);
+        return None;
+    }
+
+    #[cfg(feature = "system-fallback")]
+    if !OWNERSHIP.mark(target.as_ptr() as usize, new_size) {
+        munmap_release(target, new_size, guarded);
+        return None;
+    }
+
+    if !advise(options, target, new_size, platform.current_node()) {
+        #[cfg(feature = "system-fallback")]
+        OWNERSHIP.clear(target.as_ptr() as usize, new_size);
+
+        munmap_release(target, new_size, guarded);
+        return None;
+    }
+
+    ptr::copy_nonoverlapping(pointer.as_ptr(), target.as_ptr(), size.min(new_size));
+
+    #[cfg(feature = "system-fallback")]
+    OWNERSHIP.clear(pointer.as_ptr() as usize, size);
+
+    munmap_heap(pointer, size, guarded, Some(heap));
+
+    if new_size > size {
+        let tail = NonNull::new_unchecked(target.as_ptr().add(size));
+        PREFAULT.prefault(platform, tail, new_size - size, os_page_size().value());
+    }
+
+    //  Unlike `mremap`, a copy does not carry over the locks of the pages.
+    PINNING.pin(platform, target, new_size);
+
+    Some(target)
+}
+
+//  Wrapper around `mremap`.
+//
+//  Returns the resized area on success, and None otherwise, in which case the area is left untouched.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes.
+//  -   Assumes that `target`, if `flags` include `MREMAP_FIXED`, points to a `mmap`ed area of at least `new_size` bytes
+//      no longer in use.
+unsafe fn mremap_resize(pointer: NonNull<u8>, size: usize, new_size: usize, flags: i32, target: *mut u8)
+    -> Option<NonNull<u8>>
+{
+    let (pointer, target) = (pointer.as_ptr() as *mut libc::c_void, target as *mut libc::c_void);
+
+    #[cfg(not(target_os = "android"))]
+    let result = libc::mremap(pointer, size, new_size, flags, target);
+
+    #[cfg(target_os = "android")]
+    let result = libc::syscall(libc::SYS_mremap, pointer, size, new_size, flags, target) as *mut libc::c_void;
+
+    let result = if result != libc::MAP_FAILED { result as *mut u8 } else { ptr::null_mut() };
+    NonNull::new(result)
+}
+
+//  Returns the NUMA node backing the page at `pointer`, as reported by `move_pages`, or None if unknown.
+//
+//  Without a target node, `move_pages` moves nothing, merely reporting the node of each page, or a negative error
+//  for pages not faulted in, or not mapped.
+fn page_numa_node(pointer: NonNull<u8>) -> Option<u32> {
+    let page = pointer.as_ptr() as *mut libc::c_void;
+    let mut status: libc::c_int = -1;
+
+    //  Safety:
+    //  -   `page` and `status` are valid for a single page, the nodes being optional.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            0 as libc::pid_t,
+            1 as libc::c_ulong,
+            &page as *const *mut libc::c_void,
+            ptr::null::<libc::c_int>(),
+            &mut status as *mut libc::c_int,
+            0 as libc::c_int,
+        )
+    };
+
+    if result == 0 && status >= 0 { Some(status as u32) } else { None }
+}
+
+//  Returns the NUMA node the current thread is running on, as reported by `getcpu`, or None if unknown.
+//
+//  The system call is invoked directly, as older versions of Bionic and musl lack its wrapper.
+fn current_numa_node() -> Option<u32> {
+    let mut node: libc::c_uint = 0;
+
+    //  Safety:
+    //  -   `node` is valid for writes, the CPU and cache being optional.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_getcpu,
+            ptr::null_mut::<libc::c_uint>(),
+            &mut node as *mut libc::c_uint,
+            ptr::null_mut::<libc::c_void>(),
+        )
+    };
+
+    if result == 0 { Some(node) } else { None }
+}
+
+//  Wrapper around `munmap`.
+//
+//  #   Safety
+//
+//  -   Assumes that `addr` points to a `mmap`ed area of at least `size` bytes.
+//  -   Assumes that the range `[addr, addr + size)` is no longer in use.
+unsafe fn munmap_deallocate(addr: *mut u8, size: usize) {
+    if libc::munmap(addr as *mut libc::c_void, size) != 0 {
+        let failure = UnmapFailure { address: addr as usize, size, error: capabilities::errno() };
+
+        //  Should the memory fail to be unmapped, it is leaked, unless the process is aborted.
+        UNMAPPING.fail(&FALLBACKS, failure, || unsafe { libc::abort() });
+    }
+}
+
+//  Unmaps the `size` bytes at `pointer`, alongside their guard pages if `guarded`.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes, flanked by guard pages if `guarded`.
+//  -   Assumes that the area is no longer in use.
+unsafe fn munmap_release(pointer: NonNull<u8>, size: usize, guarded: bool) {
+    let guard = if guarded { os_page_size().value() } else { 0 };
+
+    munmap_deallocate(pointer.as_ptr().sub(guard), size + 2 * guard);
+}
+
+//  Unmaps the `size` bytes at `pointer`, as `munmap_release` does, punching them out of the file of the `shared` heap
+//  beforehand, if any, or decommits them back into the reservation, if within.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes, flanked by guard pages if `guarded`
+//      and outside the reservation, and backed by the `shared` heap if any.
+//  -   Assumes that the area is no longer in use.
+unsafe fn munmap_heap(pointer: NonNull<u8>, size: usize, guarded: bool, shared: Option<SharedHeap>) {
+    if ADDRESS_SPACE.contains(pointer) {
+        decommit_reserved(pointer, size, shared);
+        ADDRESS_SPACE.release(pointer, size);
+        return;
+    }
+
+    //  Once unmapped, the addresses, and thus the offsets, may be reused by another mapping at any time.
+    if let Some(heap) = shared {
+        punch_hole(heap, pointer.as_ptr(), size);
+    }
+
+    munmap_release(pointer, size, guarded);
+}
+
+//  Punches the `size` bytes at `pointer` out of the file of the shared `heap`, releasing, and zeroing, their memory.
+//
+//  Failures are benign, the memory remaining within the file; notably, HugeTLB pages cannot be partially released.
+//
+//  #   Safety
+//
+//  -   Assumes that the `size` bytes at `pointer` are backed by the shared `heap`, and no longer in use.
+unsafe fn punch_hole(heap: SharedHeap, pointer: *mut u8, size: usize) {
+    let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+
+    libc::fallocate(heap.fd, mode, pointer as usize as libc::off_t, size as libc::off_t);
+}
+
+#[cfg(not(target_os = "android"))]
+use libc::{MREMAP_FIXED, MREMAP_MAYMOVE};
+
+//  The flags of `mremap`, missing from the `libc` bindings of Bionic.
+#[cfg(target_os = "android")]
+const MREMAP_MAYMOVE: libc::c_int = 1;
+
+#[cfg(target_os = "android")]
+const MREMAP_FIXED: libc::c_int = 2;
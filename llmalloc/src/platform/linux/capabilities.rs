@@ -0,0 +1,554 @@
+//! Detection of the capabilities of the environment.
+//!
+//! The capabilities are detected once, on first use, from `/sys` and `/proc`; HugeTLB, and its fallback on 2 MB
+//! HugeTLB pages, are additionally downgraded on the first failure to map a Huge Page with them, sparing the futile
+//! system calls of further attempts, or altogether when forgone in favor of Transparent Huge Pages.
+//!
+//! The pools of HugeTLB pages are probed on detection, and only deemed available if they have pages left, whether free
+//! or yet to be overcommitted.
+
+use core::{
+    mem,
+    ptr,
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+};
+
+use crate::{
+    capabilities::TRANSPARENT_HUGE_PAGES_VARIABLE, Capabilities, HostCapabilities, HugeTlbPool, HugeTlbPools,
+    TransparentHugePagesMode,
+};
+
+use super::{
+    environment_flag, os_page_size, procfs::LineReader, Configuration, LLConfiguration, TOPOLOGY,
+};
+
+#[cfg(not(target_os = "android"))]
+use libc::SYS_rseq as SYS_RSEQ;
+
+/// Capabilities of the environment, detected on first use.
+pub(super) struct Detector {
+    bits: AtomicU8,
+    //  The reserved, free, overcommit and surplus counts of each pool probed, written prior to publishing `bits`.
+    pools: [AtomicU64; 8],
+}
+
+impl Detector {
+    /// Creates an instance, undetected.
+    pub(super) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+
+        Self { bits: AtomicU8::new(0), pools: [ZERO; 8] }
+    }
+
+    /// Returns the capabilities, detecting them if not yet detected.
+    #[inline(always)]
+    pub(super) fn get(&self) -> Capabilities {
+        let bits = match self.bits.load(Ordering::Relaxed) {
+            0 => self.resolve(),
+            bits => bits,
+        };
+
+        Capabilities {
+            huge_tlb: bits & HUGE_TLB != 0,
+            huge_tlb_2mb: bits & HUGE_TLB_2MB != 0,
+            transparent_huge_pages: bits & TRANSPARENT_HUGE_PAGES != 0,
+            numa: bits & NUMA != 0,
+            sysfs: bits & SYSFS != 0,
+        }
+    }
+
+    /// Downgrades HugeTLB, after a failure to map a Huge Page with it.
+    #[cold]
+    pub(super) fn downgrade_huge_tlb(&self) { self.downgrade(HUGE_TLB) }
+
+    /// Downgrades 2 MB HugeTLB pages, after a failure to map a Huge Page with them.
+    #[cold]
+    pub(super) fn downgrade_huge_tlb_2mb(&self) { self.downgrade(HUGE_TLB_2MB) }
+
+    /// Downgrades HugeTLB, and its fallback on 2 MB HugeTLB pages, in favor of Transparent Huge Pages.
+    #[cold]
+    pub(super) fn forgo_huge_tlb(&self) { self.downgrade(HUGE_TLB | HUGE_TLB_2MB) }
+
+    /// Returns the pools of HugeTLB pages, as probed on detection, detecting the capabilities if not yet detected.
+    #[cold]
+    pub(super) fn pools(&self) -> HugeTlbPools {
+        let bits = match self.bits.load(Ordering::Acquire) {
+            0 => self.resolve(),
+            bits => bits,
+        };
+
+        let pool = |bit, index: usize, page_size| {
+            if bits & bit == 0 {
+                return None;
+            }
+
+            let [reserved, free, overcommit, surplus] = [0, 1, 2, 3].map(|offset| &self.pools[4 * index + offset]);
+            let load = |count: &AtomicU64| count.load(Ordering::Relaxed);
+
+            Some(HugeTlbPool {
+                page_size,
+                reserved: load(reserved),
+                free: load(free),
+                overcommit: load(overcommit),
+                surplus: load(surplus),
+            })
+        };
+
+        HugeTlbPools {
+            huge_pages: pool(POOL, 0, LLConfiguration::HUGE_PAGE_SIZE.value()),
+            huge_pages_2mb: pool(POOL_2MB, 1, HUGE_PAGE_SIZE_2MB),
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn downgrade(&self, bit: u8) {
+        if self.bits.load(Ordering::Relaxed) == 0 {
+            self.resolve();
+        }
+
+        self.bits.fetch_and(!bit, Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self) -> u8 {
+        let detected = detect(|index, pool| {
+            let counts = [pool.reserved, pool.free, pool.overcommit, pool.surplus];
+
+            for (count, value) in self.pools[4 * index..].iter().zip(counts) {
+                count.store(value, Ordering::Relaxed);
+            }
+        });
+
+        //  A concurrent resolution, or downgrade, takes precedence; their pools were probed likewise.
+        match self.bits.compare_exchange(0, detected, Ordering::Release, Ordering::Acquire) {
+            Ok(_) => detected,
+            Err(current) => current,
+        }
+    }
+}
+
+/// Detects the capabilities of the host, in details, given the `capabilities` selected.
+///
+/// Unlike the selected capabilities, the details are detected anew on each call.
+#[cold]
+pub(super) fn detect_host(capabilities: Capabilities) -> HostCapabilities {
+    let mut host = HostCapabilities::default();
+    host.capabilities = capabilities;
+
+    for_each_huge_page_size(|size| host.add_huge_page_size(size));
+
+    host.transparent_huge_pages =
+        read_first_line(TRANSPARENT_HUGE_PAGES_ENABLED, parse_mode).flatten().unwrap_or_default();
+
+    host.numa_nodes = if capabilities.numa { TOPOLOGY.nodes() } else { 1 };
+    host.far_numa_nodes = if capabilities.numa { TOPOLOGY.far_nodes() } else { 0 };
+
+    host.rseq = has_rseq();
+    host.mlock_limit = mlock_limit();
+    host.os_page_size = os_page_size().value();
+
+    host
+}
+
+//
+//  Implementation Details
+//
+
+const DETECTED: u8 = 1;
+const HUGE_TLB: u8 = 2;
+const TRANSPARENT_HUGE_PAGES: u8 = 4;
+const NUMA: u8 = 8;
+const SYSFS: u8 = 16;
+const HUGE_TLB_2MB: u8 = 32;
+const POOL: u8 = 64;
+const POOL_2MB: u8 = 128;
+
+const HUGE_PAGE_SIZE_2MB: usize = 2 * 1024 * 1024;
+
+const SYS: &[u8] = b"/sys/kernel\0";
+const NODES: &[u8] = b"/sys/devices/system/node\0";
+const TRANSPARENT_HUGE_PAGES_ENABLED: &[u8] = b"/sys/kernel/mm/transparent_hugepage/enabled\0";
+
+//  The directories of the pools, without NUL-terminator, as the name of the file read is appended.
+const POOL_2MB_DIRECTORY: &[u8] = b"/sys/kernel/mm/hugepages/hugepages-2048kB/";
+
+#[cfg(not(feature = "small-heap"))]
+const POOL_DIRECTORY: &[u8] = b"/sys/kernel/mm/hugepages/hugepages-1048576kB/";
+
+#[cfg(feature = "small-heap")]
+const POOL_DIRECTORY: &[u8] = POOL_2MB_DIRECTORY;
+
+const HUGE_PAGES: &[u8] = b"/sys/kernel/mm/hugepages\0";
+
+//  The number of the `rseq` system call, missing from the `libc` bindings of Bionic.
+#[cfg(all(target_os = "android", target_arch = "x86_64"))]
+const SYS_RSEQ: libc::c_long = 334;
+
+#[cfg(all(target_os = "android", target_arch = "x86"))]
+const SYS_RSEQ: libc::c_long = 386;
+
+#[cfg(all(target_os = "android", target_arch = "arm"))]
+const SYS_RSEQ: libc::c_long = 398;
+
+#[cfg(all(target_os = "android", any(target_arch = "aarch64", target_arch = "riscv64")))]
+const SYS_RSEQ: libc::c_long = 293;
+
+//  Detects the capabilities, invoking `record` with the index, 0 or 1 for 2 MB pages, of each pool probed.
+fn detect<F>(mut record: F) -> u8
+    where
+        F: FnMut(usize, &HugeTlbPool),
+{
+    let mut bits = DETECTED;
+
+    let sysfs = is_accessible(SYS);
+
+    if sysfs {
+        bits |= SYSFS;
+    }
+
+    let forgone = environment_flag(TRANSPARENT_HUGE_PAGES_VARIABLE);
+
+    //  Without `/sys`, HugeTLB is detected by trial, unless forgone; with it, a pool without pages left is passed over.
+    let mut available = |bit, index, directory, page_size| {
+        if !sysfs {
+            return true;
+        }
+
+        match probe_pool(directory, page_size) {
+            Some(pool) => {
+                bits |= bit;
+                record(index, &pool);
+                pool.available() > 0
+            },
+            None => false,
+        }
+    };
+
+    let huge_tlb = available(POOL, 0, POOL_DIRECTORY, LLConfiguration::HUGE_PAGE_SIZE.value());
+
+    //  2 MB HugeTLB pages are only a fallback if the Huge Pages are larger.
+    let huge_tlb_2mb = LLConfiguration::HUGE_PAGE_SIZE.value() > HUGE_PAGE_SIZE_2MB &&
+        available(POOL_2MB, 1, POOL_2MB_DIRECTORY, HUGE_PAGE_SIZE_2MB);
+
+    if huge_tlb && !forgone {
+        bits |= HUGE_TLB;
+    }
+
+    if huge_tlb_2mb && !forgone {
+        bits |= HUGE_TLB_2MB;
+    }
+
+    //  The mode in use is bracketed, as in `always [madvise] never`.
+    let transparent_huge_pages = read_first_line(TRANSPARENT_HUGE_PAGES_ENABLED, |line| {
+        contains(line, b"[always]") || contains(line, b"[madvise]")
+    });
+
+    if transparent_huge_pages.unwrap_or(false) {
+        bits |= TRANSPARENT_HUGE_PAGES;
+    }
+
+    if has_mempolicy() && is_accessible(NODES) {
+        bits |= NUMA;
+    }
+
+    bits
+}
+
+//  Returns whether the kernel supports NUMA policies, as probed by `get_mempolicy`, as libnuma does.
+//
+//  The probe only fails if the system call is unavailable, or forbidden, as by a seccomp filter.
+fn has_mempolicy() -> bool {
+    //  Safety:
+    //  -   Without a mode, nor a mask, nor an address, the call only queries the policy of the thread.
+    let result = unsafe {
+        libc::syscall(libc::SYS_get_mempolicy, ptr::null_mut::<libc::c_int>(), ptr::null_mut::<libc::c_ulong>(),
+            0 as libc::c_ulong, ptr::null_mut::<libc::c_void>(), 0 as libc::c_ulong)
+    };
+
+    result == 0 || !matches!(errno(), libc::ENOSYS | libc::EPERM)
+}
+
+//  Returns whether the file, or directory, located at `path`, NUL-terminated, is readable.
+pub(super) fn is_accessible(path: &[u8]) -> bool {
+    debug_assert!(path.last() == Some(&0));
+
+    //  Safety:
+    //  -   `path` is NUL-terminated.
+    unsafe { libc::access(path.as_ptr() as *const libc::c_char, libc::R_OK) == 0 }
+}
+
+//  Probes the pool of HugeTLB pages of `page_size` bytes located at `directory`, or returns None if there is none.
+fn probe_pool(directory: &[u8], page_size: usize) -> Option<HugeTlbPool> {
+    //  The path is NUL-terminated by the trailing zeroes of the buffer.
+    let read = |file: &[u8]| {
+        let mut path = [0u8; 96];
+
+        let (prefix, suffix) = path.get_mut(..directory.len() + file.len() + 1)?.split_at_mut(directory.len());
+        prefix.copy_from_slice(directory);
+        suffix[..file.len()].copy_from_slice(file);
+
+        read_number(&path[..=directory.len() + file.len()])
+    };
+
+    Some(HugeTlbPool {
+        page_size,
+        reserved: read(b"nr_hugepages")?,
+        free: read(b"free_hugepages").unwrap_or(0),
+        overcommit: read(b"nr_overcommit_hugepages").unwrap_or(0),
+        surplus: read(b"surplus_hugepages").unwrap_or(0),
+    })
+}
+
+//  Reads the number on the first line of the file located at `path`, NUL-terminated.
+fn read_number(path: &[u8]) -> Option<u64> {
+    read_first_line(path, parse_number).flatten()
+}
+
+//  Applies `f` to the first line of the file located at `path`, NUL-terminated, if any.
+fn read_first_line<F, R>(path: &[u8], f: F) -> Option<R>
+    where
+        F: FnOnce(&[u8]) -> R,
+{
+    let mut reader = LineReader::open(path)?;
+
+    reader.next_line().map(f)
+}
+
+fn parse_number(line: &[u8]) -> Option<u64> {
+    let digits = line.iter().take_while(|byte| byte.is_ascii_whitespace()).count();
+    let line = &line[digits..];
+
+    let length = line.iter().take_while(|byte| byte.is_ascii_digit()).count();
+
+    if length == 0 {
+        return None;
+    }
+
+    line[..length].iter().try_fold(0u64, |number, digit| number.checked_mul(10)?.checked_add((digit - b'0') as u64))
+}
+
+//  Invokes `f` with the size, in bytes, of each HugeTLB page size listed in `/sys/kernel/mm/hugepages`.
+fn for_each_huge_page_size<F>(mut f: F)
+    where
+        F: FnMut(usize),
+{
+    //  Safety:
+    //  -   `HUGE_PAGES` is NUL-terminated.
+    let directory = unsafe { libc::opendir(HUGE_PAGES.as_ptr() as *const libc::c_char) };
+
+    if directory.is_null() {
+        return;
+    }
+
+    loop {
+        //  Safety:
+        //  -   `directory` is a valid, open, directory stream.
+        let entry = unsafe { libc::readdir(directory) };
+
+        if entry.is_null() {
+            break;
+        }
+
+        //  Safety:
+        //  -   `entry` is valid until the next call to `readdir`, and its name is NUL-terminated.
+        let name = unsafe { (*entry).d_name.as_ptr() };
+        let length = unsafe { libc::strlen(name) };
+        let name = unsafe { core::slice::from_raw_parts(name as *const u8, length) };
+
+        if let Some(size) = parse_huge_page_size(name) {
+            f(size);
+        }
+    }
+
+    //  Safety:
+    //  -   `directory` is a valid, open, directory stream, not used afterwards.
+    unsafe { libc::closedir(directory) };
+}
+
+//  Parses the size of a HugeTLB page from the name of its directory, as in `hugepages-2048kB`.
+fn parse_huge_page_size(name: &[u8]) -> Option<usize> {
+    let kilobytes = name.strip_prefix(b"hugepages-")?.strip_suffix(b"kB")?;
+
+    if kilobytes.is_empty() || !kilobytes.iter().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    parse_number(kilobytes)?.checked_mul(1024).map(|bytes| bytes as usize)
+}
+
+//  Parses the mode of Transparent Huge Pages, bracketed, as in `always [madvise] never`.
+fn parse_mode(line: &[u8]) -> Option<TransparentHugePagesMode> {
+    if contains(line, b"[always]") {
+        Some(TransparentHugePagesMode::Always)
+    } else if contains(line, b"[madvise]") {
+        Some(TransparentHugePagesMode::Madvise)
+    } else if contains(line, b"[never]") {
+        Some(TransparentHugePagesMode::Never)
+    } else {
+        None
+    }
+}
+
+//  Returns whether the kernel supports `rseq`.
+//
+//  Registering a null area is invalid, and rejected with `EINVAL` by the kernels supporting `rseq`, or with `EPERM` or
+//  `EBUSY` if the C library registered one already, whereas the kernels not supporting it reject the call with
+//  `ENOSYS`.
+fn has_rseq() -> bool {
+    //  Safety:
+    //  -   A null area of length 0 is never accessed.
+    let result = unsafe { libc::syscall(SYS_RSEQ, ptr::null_mut::<libc::c_void>(), 0u32, 0i32, 0u32) };
+
+    result == 0 || errno() != libc::ENOSYS
+}
+
+/// Returns the errno of the current thread.
+pub(super) fn errno() -> libc::c_int {
+    #[cfg(not(target_os = "android"))]
+    let location = libc::__errno_location;
+
+    #[cfg(target_os = "android")]
+    let location = libc::__errno;
+
+    //  Safety:
+    //  -   `location` always returns a valid pointer, to the errno of the current thread.
+    unsafe { *location() }
+}
+
+/// Sets the errno of the current thread.
+pub(super) fn set_errno(value: libc::c_int) {
+    #[cfg(not(target_os = "android"))]
+    let location = libc::__errno_location;
+
+    #[cfg(target_os = "android")]
+    let location = libc::__errno;
+
+    //  Safety:
+    //  -   `location` always returns a valid pointer, to the errno of the current thread.
+    unsafe { *location() = value };
+}
+
+//  Returns the soft limit of locked memory, in bytes, or None if unlimited or unknown.
+fn mlock_limit() -> Option<u64> {
+    //  Safety:
+    //  -   `rlimit` is plain old data.
+    let mut limit: libc::rlimit = unsafe { mem::zeroed() };
+
+    //  Safety:
+    //  -   `limit` is valid for writes.
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+
+    Some(limit.rlim_cur as u64)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool { haystack.windows(needle.len()).any(|window| window == needle) }
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn detector_downgrade_huge_tlb() {
+    let detector = Detector::new();
+
+    let detected = detector.get();
+    assert_eq!(detected, detector.get());
+
+    detector.downgrade_huge_tlb();
+
+    assert_eq!(Capabilities { huge_tlb: false, ..detected }, detector.get());
+
+    detector.downgrade_huge_tlb_2mb();
+
+    assert_eq!(Capabilities { huge_tlb: false, huge_tlb_2mb: false, ..detected }, detector.get());
+}
+
+#[test]
+fn detector_forgo_huge_tlb() {
+    let detector = Detector::new();
+
+    let detected = detector.get();
+
+    detector.forgo_huge_tlb();
+
+    assert_eq!(Capabilities { huge_tlb: false, huge_tlb_2mb: false, ..detected }, detector.get());
+}
+
+#[test]
+fn detector_pools() {
+    let detector = Detector::new();
+
+    let pools = detector.pools();
+    let capabilities = detector.get();
+
+    //  A pool without pages left is passed over.
+    if let Some(pool) = pools.huge_pages {
+        assert_eq!(LLConfiguration::HUGE_PAGE_SIZE.value(), pool.page_size);
+        assert!(pool.available() > 0 || !capabilities.huge_tlb, "{:?} {:?}", pools, capabilities);
+    }
+
+    if let Some(pool) = pools.huge_pages_2mb {
+        assert_eq!(HUGE_PAGE_SIZE_2MB, pool.page_size);
+        assert!(pool.available() > 0 || !capabilities.huge_tlb_2mb, "{:?} {:?}", pools, capabilities);
+    }
+
+    assert_eq!(pools, detector.pools());
+}
+
+#[test]
+fn probe_pool_directory() {
+    if let Some(pool) = probe_pool(POOL_2MB_DIRECTORY, HUGE_PAGE_SIZE_2MB) {
+        assert_eq!(HUGE_PAGE_SIZE_2MB, pool.page_size);
+        assert!(pool.free <= pool.reserved, "{:?}", pool);
+    }
+
+    assert_eq!(None, probe_pool(b"/sys/kernel/mm/hugepages/hugepages-3kB/", 3 * 1024));
+    assert_eq!(None, probe_pool(&[b'/'; 96], HUGE_PAGE_SIZE_2MB));
+}
+
+#[cfg(feature = "small-heap")]
+#[test]
+fn detector_huge_tlb_2mb_small_heap() {
+    //  The Huge Pages are 2 MB already, hence there is nothing to fall back on.
+    assert!(!Detector::new().get().huge_tlb_2mb);
+}
+
+#[test]
+fn parse_number_line() {
+    assert_eq!(Some(0), parse_number(b"0"));
+    assert_eq!(Some(42), parse_number(b" 42 kB"));
+    assert_eq!(None, parse_number(b"never"));
+    assert_eq!(None, parse_number(b"99999999999999999999999"));
+}
+
+#[test]
+fn parse_huge_page_size_name() {
+    assert_eq!(Some(2 << 20), parse_huge_page_size(b"hugepages-2048kB"));
+    assert_eq!(Some(1 << 30), parse_huge_page_size(b"hugepages-1048576kB"));
+    assert_eq!(None, parse_huge_page_size(b"hugepages-kB"));
+    assert_eq!(None, parse_huge_page_size(b"hugepages-2048"));
+    assert_eq!(None, parse_huge_page_size(b"."));
+}
+
+#[test]
+fn parse_mode_line() {
+    assert_eq!(Some(TransparentHugePagesMode::Always), parse_mode(b"[always] madvise never"));
+    assert_eq!(Some(TransparentHugePagesMode::Madvise), parse_mode(b"always [madvise] never"));
+    assert_eq!(Some(TransparentHugePagesMode::Never), parse_mode(b"always madvise [never]"));
+    assert_eq!(None, parse_mode(b"always madvise never"));
+}
+
+#[test]
+fn contains_needle() {
+    assert!(contains(b"always [madvise] never", b"[madvise]"));
+    assert!(!contains(b"always madvise [never]", b"[madvise]"));
+    assert!(!contains(b"[m", b"[madvise]"));
+}
+
+} // mod tests
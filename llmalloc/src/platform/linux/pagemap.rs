@@ -0,0 +1,92 @@
+//! Lookup of the physical addresses in `/proc/self/pagemap`.
+//!
+//! The pagemap holds one 64-bit entry per virtual page, in which bit 63 indicates whether the page is present, and
+//! bits 0 to 54 are its page frame number. The frame numbers are zeroed for insufficiently privileged processes.
+
+use crate::{PhysicalSegment, SegmentBuilder};
+
+/// Reports the physically contiguous segments of the `size` bytes located at `address`, in pages of `page_size`
+/// bytes, returning their number.
+///
+/// Returns None if the pagemap cannot be read, or if any page is not present or its frame number is withheld, in
+/// which case the segments reported so far are not to be relied upon.
+pub(super) fn physical_segments<F>(address: usize, size: usize, page_size: usize, report: &mut F) -> Option<usize>
+    where
+        F: FnMut(&PhysicalSegment) + ?Sized,
+{
+    debug_assert!(address.is_multiple_of(page_size));
+
+    let pagemap = Pagemap::open()?;
+    let mut builder = SegmentBuilder::new(page_size);
+    let mut entries = [0u64; BATCH];
+
+    let first = address / page_size;
+    let count = size.div_ceil(page_size);
+
+    let mut index = 0;
+
+    while index < count {
+        let batch = &mut entries[..(count - index).min(BATCH)];
+
+        pagemap.read(first + index, batch)?;
+
+        for entry in batch.iter() {
+            let frame = entry & FRAME_MASK;
+
+            if entry & PRESENT == 0 || frame == 0 {
+                return None;
+            }
+
+            builder.push(frame, report);
+        }
+
+        index += batch.len();
+    }
+
+    Some(builder.finish(report))
+}
+
+//
+//  Implementation Details
+//
+
+const PAGEMAP: &[u8] = b"/proc/self/pagemap\0";
+
+//  Number of entries read at once.
+const BATCH: usize = 512;
+
+const PRESENT: u64 = 1 << 63;
+const FRAME_MASK: u64 = (1 << 55) - 1;
+
+//  The pagemap file.
+struct Pagemap(libc::c_int);
+
+impl Pagemap {
+    fn open() -> Option<Self> {
+        //  Safety:
+        //  -   `PAGEMAP` is NUL-terminated.
+        let fd = unsafe { libc::open(PAGEMAP.as_ptr() as *const libc::c_char, libc::O_RDONLY | libc::O_CLOEXEC) };
+
+        if fd < 0 { None } else { Some(Self(fd)) }
+    }
+
+    //  Reads the entries of the pages starting from the `first` page, filling `entries`.
+    fn read(&self, first: usize, entries: &mut [u64]) -> Option<()> {
+        let length = entries.len() * 8;
+        let offset = (first * 8) as libc::off_t;
+
+        //  Safety:
+        //  -   `entries` is valid for writes of `length` bytes.
+        let read = unsafe { libc::pread(self.0, entries.as_mut_ptr() as *mut libc::c_void, length, offset) };
+
+        if read == length as isize { Some(()) } else { None }
+    }
+}
+
+impl Drop for Pagemap {
+    fn drop(&mut self) {
+        //  Safety:
+        //  -   `self.0` is a valid file descriptor, owned by `self`.
+        unsafe { libc::close(self.0) };
+    }
+}
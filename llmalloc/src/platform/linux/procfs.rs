@@ -0,0 +1,306 @@
+//! Reconciliation against `/proc/self/smaps` and `/proc/self/numa_maps`.
+//!
+//! Neither memory allocation nor formatting is available, hence the files are read line by line, within a fixed-size
+//! buffer, and parsed by hand.
+
+use core::{
+    cmp,
+    ops::Range,
+};
+
+use crate::HugePageReport;
+
+/// Reconciles the memory area `[address, address + size)` owned by a socket on `node` against the kernel's view.
+///
+/// `is_local` determines whether a given NUMA node is considered local to `node`.
+pub(super) fn reconcile<F>(address: usize, size: usize, node: u32, is_local: F) -> HugePageReport
+    where
+        F: Fn(u32) -> bool,
+{
+    let mut report = HugePageReport { address, size, node, ..HugePageReport::default() };
+    let mut mappings = Mappings::default();
+
+    if let Some(mut smaps) = LineReader::open(SMAPS) {
+        scan_smaps(&mut smaps, &mut report, &mut mappings);
+    }
+
+    if let Some(mut numa_maps) = LineReader::open(NUMA_MAPS) {
+        scan_numa_maps(&mut numa_maps, &mut report, &mappings, is_local);
+    }
+
+    report
+}
+
+//
+//  Implementation Details
+//
+
+const SMAPS: &[u8] = b"/proc/self/smaps\0";
+const NUMA_MAPS: &[u8] = b"/proc/self/numa_maps\0";
+
+//  Scans `/proc/self/smaps` for mappings overlapping the area of the `report`, and accumulates their statistics.
+fn scan_smaps(reader: &mut LineReader, report: &mut HugePageReport, mappings: &mut Mappings) {
+    let area = report.address..(report.address + report.size);
+
+    //  The mapping whose fields are being parsed, if it overlaps the area.
+    let mut current: Option<Mapping> = None;
+
+    while let Some(line) = reader.next_line() {
+        if let Some(range) = parse_range(line) {
+            current = Mapping::overlapping(range, &area);
+
+            if let Some(mapping) = current {
+                report.mappings += 1;
+                mappings.push(mapping);
+            }
+
+            continue;
+        }
+
+        let mapping = match current {
+            Some(mapping) => mapping,
+            None => continue,
+        };
+
+        //  All fields of interest are expressed in kB.
+        match parse_field(line) {
+            Some((b"KernelPageSize", kb)) => {
+                let page_size = kb * 1024;
+
+                if report.kernel_page_size == 0 || page_size < report.kernel_page_size {
+                    report.kernel_page_size = page_size;
+                }
+            },
+            Some((b"Rss", kb)) => report.resident += mapping.apportion(kb * 1024),
+            Some((b"AnonHugePages", kb)) => report.anonymous_huge += mapping.apportion(kb * 1024),
+            _ => (),
+        }
+    }
+}
+
+//  Scans `/proc/self/numa_maps` for the `mappings` previously found, and accumulates the location of their pages.
+fn scan_numa_maps<F>(reader: &mut LineReader, report: &mut HugePageReport, mappings: &Mappings, is_local: F)
+    where
+        F: Fn(u32) -> bool,
+{
+    while let Some(line) = reader.next_line() {
+        let mut tokens = line.split(|&byte| byte == b' ');
+
+        let mapping = match tokens.next().and_then(parse_hex).and_then(|start| mappings.find(start)) {
+            Some(mapping) => mapping,
+            None => continue,
+        };
+
+        for (node, pages) in tokens.filter_map(parse_node_pages) {
+            let pages = mapping.apportion(pages);
+
+            if is_local(node) {
+                report.local_pages += pages;
+            } else {
+                report.foreign_pages += pages;
+            }
+        }
+    }
+}
+
+//  A kernel mapping, overlapping the area being reconciled.
+#[derive(Clone, Copy, Default)]
+struct Mapping {
+    start: usize,
+    size: usize,
+    overlap: usize,
+}
+
+impl Mapping {
+    //  Returns a mapping, if `range` overlaps `area`.
+    fn overlapping(range: Range<usize>, area: &Range<usize>) -> Option<Mapping> {
+        let begin = cmp::max(range.start, area.start);
+        let end = cmp::min(range.end, area.end);
+
+        if begin >= end {
+            return None;
+        }
+
+        Some(Mapping { start: range.start, size: range.end - range.start, overlap: end - begin })
+    }
+
+    //  Returns the fraction of `value` corresponding to the overlap.
+    fn apportion(&self, value: usize) -> usize {
+        if self.overlap == self.size {
+            return value;
+        }
+
+        (value as u128 * self.overlap as u128 / self.size as u128) as usize
+    }
+}
+
+//  A fixed-capacity set of mappings.
+//
+//  A `HugePage` is normally covered by a single mapping, should it be split further only the first mappings are
+//  tracked.
+#[derive(Default)]
+struct Mappings {
+    mappings: [Mapping; 16],
+    length: usize,
+}
+
+impl Mappings {
+    //  Pushes a mapping, if there is room.
+    fn push(&mut self, mapping: Mapping) {
+        if let Some(slot) = self.mappings.get_mut(self.length) {
+            *slot = mapping;
+            self.length += 1;
+        }
+    }
+
+    //  Finds the mapping starting at `start`, if any.
+    fn find(&self, start: usize) -> Option<Mapping> {
+        self.mappings[..self.length].iter().find(|mapping| mapping.start == start).copied()
+    }
+}
+
+//  A reader of lines, using a fixed-size buffer.
+pub(super) struct LineReader {
+    fd: libc::c_int,
+    //  Whether the remainder of a line too long for the buffer is being skipped.
+    skipping: bool,
+    begin: usize,
+    end: usize,
+    buffer: [u8; 4096],
+}
+
+impl LineReader {
+    //  Opens the file located at `path`, which must be NUL-terminated.
+    pub(super) fn open(path: &[u8]) -> Option<Self> {
+        debug_assert!(path.last() == Some(&0));
+
+        //  Safety:
+        //  -   `path` is NUL-terminated.
+        let fd = unsafe { libc::open(path.as_ptr() as *const libc::c_char, libc::O_RDONLY | libc::O_CLOEXEC) };
+
+        if fd < 0 {
+            return None;
+        }
+
+        Some(Self { fd, skipping: false, begin: 0, end: 0, buffer: [0; 4096] })
+    }
+
+    //  Returns the next line, without its trailing newline.
+    //
+    //  Lines longer than the buffer are truncated.
+    pub(super) fn next_line(&mut self) -> Option<&[u8]> {
+        loop {
+            let pending = &self.buffer[self.begin..self.end];
+
+            if let Some(length) = pending.iter().position(|&byte| byte == b'\n') {
+                let begin = self.begin;
+                let skipped = self.skipping;
+
+                self.begin += length + 1;
+                self.skipping = false;
+
+                if skipped {
+                    continue;
+                }
+
+                return Some(&self.buffer[begin..(begin + length)]);
+            }
+
+            if self.skipping {
+                self.begin = self.end;
+            }
+
+            //  The buffer is full, yet contains no newline: truncate the line, and skip its remainder.
+            if self.begin == 0 && self.end == self.buffer.len() {
+                self.begin = self.end;
+                self.skipping = true;
+
+                return Some(&self.buffer[..]);
+            }
+
+            if !self.refill() {
+                //  The last line may lack a trailing newline.
+                if self.begin == self.end {
+                    return None;
+                }
+
+                let begin = self.begin;
+                self.begin = self.end;
+
+                return Some(&self.buffer[begin..self.end]);
+            }
+        }
+    }
+
+    //  Moves the pending bytes to the front of the buffer, and reads more.
+    //
+    //  Returns false on end of file, or error.
+    fn refill(&mut self) -> bool {
+        self.buffer.copy_within(self.begin..self.end, 0);
+        self.end -= self.begin;
+        self.begin = 0;
+
+        let available = &mut self.buffer[self.end..];
+
+        //  Safety:
+        //  -   `available` is valid for writes of `available.len()` bytes.
+        let read = unsafe { libc::read(self.fd, available.as_mut_ptr() as *mut libc::c_void, available.len()) };
+
+        if read <= 0 {
+            return false;
+        }
+
+        self.end += read as usize;
+        true
+    }
+}
+
+impl Drop for LineReader {
+    fn drop(&mut self) {
+        //  Safety:
+        //  -   `self.fd` is a valid file descriptor, owned by `self`.
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+//  Parses the range of a smaps mapping header, such as `7f0000000000-7f0040000000 rw-p 00000000 00:00 0`.
+fn parse_range(line: &[u8]) -> Option<Range<usize>> {
+    let token = line.split(|&byte| byte == b' ').next()?;
+    let dash = token.iter().position(|&byte| byte == b'-')?;
+
+    Some(parse_hex(&token[..dash])?..parse_hex(&token[(dash + 1)..])?)
+}
+
+//  Parses a numeric smaps field, such as `Rss:                 123 kB`.
+fn parse_field(line: &[u8]) -> Option<(&[u8], usize)> {
+    let colon = line.iter().position(|&byte| byte == b':')?;
+    let value = line[(colon + 1)..].split(|&byte| byte == b' ').find(|token| !token.is_empty())?;
+
+    Some((&line[..colon], parse_decimal(value)?))
+}
+
+//  Parses a per-node numa_maps token, such as `N0=262144`.
+fn parse_node_pages(token: &[u8]) -> Option<(u32, usize)> {
+    let token = token.strip_prefix(b"N")?;
+    let equal = token.iter().position(|&byte| byte == b'=')?;
+
+    let node = parse_decimal(&token[..equal])?;
+    let pages = parse_decimal(&token[(equal + 1)..])?;
+
+    Some((node as u32, pages))
+}
+
+fn parse_hex(token: &[u8]) -> Option<usize> { parse_radix(token, 16) }
+
+fn parse_decimal(token: &[u8]) -> Option<usize> { parse_radix(token, 10) }
+
+fn parse_radix(token: &[u8], radix: u32) -> Option<usize> {
+    if token.is_empty() {
+        return None;
+    }
+
+    token.iter().try_fold(0usize, |accumulator, &byte| {
+        let digit = (byte as char).to_digit(radix)?;
+        accumulator.checked_mul(radix as usize)?.checked_add(digit as usize)
+    })
+}
@@ -0,0 +1,440 @@
+//! Discovery of the NUMA topology.
+//!
+//! The topology is discovered once, on first use, from `/sys/devices/system/node`, rather than from libnuma, which is
+//! not installed everywhere: the highest possible node is read from `possible`, as in `0-3`, and the distances from
+//! each node to the others from `node<N>/distance`, as in `10 21 21 21`, into a matrix.
+//!
+//! The matrix only holds the first `MAXIMUM_NODES` nodes; the distances to, and from, the nodes beyond are unknown.
+//!
+//! The nodes the process is allowed to allocate memory from are read alongside, from the `Mems_allowed_list` of
+//! `/proc/self/status`, which reflects the effective `cpuset.mems` of its cgroup: within a container, a thread may
+//! run on a node whose memory is denied to it. The nodes beyond the first `MAXIMUM_NODES` are deemed allowed.
+//!
+//! The nodes are classified in tiers: a node without CPUs, as per `has_cpu`, is far memory, such as a CXL.mem expander
+//! or persistent memory exposed as a node, if it lies further than `FAR_DISTANCE` from every node with CPUs, or if its
+//! memory is fronted by a memory-side cache, as per `node<N>/memory_side_cache`; the others are near memory, DRAM.
+//! Should `has_cpu` be unavailable, all nodes are deemed near.
+//!
+//! The topology may be re-scanned, as nodes are hot-added, or the cpuset of the process changed, in which case the
+//! readers may briefly observe a mix of both topologies, all of whose nodes are valid.
+//!
+//! No library is loaded, whether at link time or lazily: should `/sys` be unavailable, as within some sandboxes, the
+//! topology degrades to a single node, whose distances are unknown, and the process starts regardless.
+
+use core::{
+    convert::TryFrom,
+    sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering},
+};
+
+use super::{capabilities::is_accessible, procfs::LineReader};
+
+/// The number of nodes whose distances are held, at most.
+pub(super) const MAXIMUM_NODES: usize = 64;
+
+/// The distance beyond which a node without CPUs is far memory, as reported by the kernel: the nodes of another socket
+/// are at 20, or 21, whereas CXL.mem expanders are further still.
+pub(super) const FAR_DISTANCE: u8 = 20;
+
+/// NUMA topology, discovered on first use.
+pub(super) struct Topology {
+    //  The highest possible node plus one, or 0 if not yet discovered.
+    nodes: AtomicU32,
+    //  The distances between the nodes, row by row, written prior to publishing `nodes`; 0 if unknown.
+    distances: [AtomicU8; MAXIMUM_NODES * MAXIMUM_NODES],
+    //  The mask of the nodes allowed, written prior to publishing `nodes`.
+    allowed: AtomicU64,
+    //  The mask of the nodes of far memory, written prior to publishing `nodes`.
+    far: AtomicU64,
+}
+
+impl Topology {
+    /// Creates an instance, undiscovered.
+    pub(super) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU8 = AtomicU8::new(0);
+
+        Self {
+            nodes: AtomicU32::new(0),
+            distances: [ZERO; MAXIMUM_NODES * MAXIMUM_NODES],
+            allowed: AtomicU64::new(0),
+            far: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the number of nodes, the highest possible node plus one, at least 1, discovering them if need be.
+    pub(super) fn nodes(&self) -> u32 {
+        match self.nodes.load(Ordering::Acquire) {
+            0 => self.discover(),
+            nodes => nodes,
+        }
+    }
+
+    /// Returns the distance between the nodes `from` and `to`, as reported by the kernel, or None if unknown.
+    ///
+    /// A node is at a distance of 10 from itself; the other distances are relative to it.
+    pub(super) fn distance(&self, from: u32, to: u32) -> Option<u8> {
+        let nodes = self.nodes();
+
+        if from >= nodes || to >= nodes {
+            return None;
+        }
+
+        let (from, to) = (from as usize, to as usize);
+
+        if from >= MAXIMUM_NODES || to >= MAXIMUM_NODES {
+            return None;
+        }
+
+        match self.distances[from * MAXIMUM_NODES + to].load(Ordering::Relaxed) {
+            0 => None,
+            distance => Some(distance),
+        }
+    }
+
+    /// Returns whether the process is allowed to allocate memory from `node`, as per its cpuset.
+    pub(super) fn is_allowed(&self, node: u32) -> bool {
+        let nodes = self.nodes();
+
+        if node >= nodes {
+            return false;
+        }
+
+        (node as usize) >= MAXIMUM_NODES || self.allowed.load(Ordering::Relaxed) & (1 << node) != 0
+    }
+
+    /// Returns whether `node` is far memory, rather than near memory.
+    ///
+    /// The nodes beyond the first `MAXIMUM_NODES` are deemed near.
+    pub(super) fn is_far(&self, node: u32) -> bool {
+        let nodes = self.nodes();
+
+        node < nodes && (node as usize) < MAXIMUM_NODES && self.far.load(Ordering::Relaxed) & (1 << node) != 0
+    }
+
+    /// Returns the number of nodes of far memory.
+    pub(super) fn far_nodes(&self) -> u32 {
+        self.nodes();
+
+        self.far.load(Ordering::Relaxed).count_ones()
+    }
+
+    /// Returns the allowed node nearest to `node`, the lowest of the nearest if several, or `node` itself if allowed.
+    ///
+    /// The nodes of near memory are preferred over those of far memory, and the nodes whose distance from `node` is
+    /// unknown are deemed furthest.
+    pub(super) fn nearest_allowed(&self, node: u32) -> u32 {
+        if self.is_allowed(node) {
+            return node;
+        }
+
+        let candidates = (0..self.nodes().min(MAXIMUM_NODES as u32)).filter(|other| self.is_allowed(*other));
+
+        candidates.min_by_key(|other| (self.is_far(*other), self.distance(node, *other).unwrap_or(u8::MAX)))
+            .unwrap_or(node)
+    }
+
+    /// Re-scans the topology, returning the number of nodes.
+    ///
+    /// The distances of the nodes no longer present are kept.
+    pub(super) fn refresh(&self) -> u32 { self.discover() }
+
+    //  Discovers the topology, returning the number of nodes.
+    //
+    //  Concurrent discoveries write the same values, hence are benign.
+    #[cold]
+    #[inline(never)]
+    fn discover(&self) -> u32 {
+        let nodes = read_possible().unwrap_or(1).max(1);
+
+        for from in 0..(nodes as usize).min(MAXIMUM_NODES) {
+            let row = &self.distances[from * MAXIMUM_NODES..(from + 1) * MAXIMUM_NODES];
+
+            read_distances(from, |to, distance| {
+                if let Some(cell) = row.get(to) {
+                    cell.store(distance, Ordering::Relaxed);
+                }
+            });
+        }
+
+        //  The cpuset of the process allows at least one node, lest it could not allocate at all.
+        let all = if nodes as usize >= MAXIMUM_NODES { u64::MAX } else { (1 << nodes) - 1 };
+        let allowed = read_allowed().map_or(all, |allowed| allowed & all);
+
+        self.allowed.store(if allowed == 0 { all } else { allowed }, Ordering::Relaxed);
+
+        let far = read_list(HAS_CPU).map_or(0, |cpus| self.classify(nodes, cpus & all));
+
+        self.far.store(far, Ordering::Relaxed);
+
+        self.nodes.store(nodes, Ordering::Release);
+
+        nodes
+    }
+
+    //  Returns the mask of the nodes of far memory, among the `nodes` first, given the mask of the nodes with `cpus`.
+    //
+    //  Without a node with CPUs, the classification is meaningless, and all nodes are deemed near.
+    fn classify(&self, nodes: u32, cpus: u64) -> u64 {
+        if cpus == 0 {
+            return 0;
+        }
+
+        let nodes = (nodes as usize).min(MAXIMUM_NODES);
+        let mut far = 0u64;
+
+        for node in (0..nodes).filter(|node| cpus & (1 << node) == 0) {
+            if self.is_distant(node, cpus) || has_memory_side_cache(node) {
+                far |= 1 << node;
+            }
+        }
+
+        far
+    }
+
+    //  Returns whether `node` lies further than `FAR_DISTANCE` from every node with `cpus`; an unknown distance is not
+    //  deemed far.
+    fn is_distant(&self, node: usize, cpus: u64) -> bool {
+        (0..MAXIMUM_NODES).filter(|other| cpus & (1 << other) != 0)
+            .all(|other| self.distances[other * MAXIMUM_NODES + node].load(Ordering::Relaxed) > FAR_DISTANCE)
+    }
+}
+
+//
+//  Implementation Details
+//
+
+const POSSIBLE: &[u8] = b"/sys/devices/system/node/possible\0";
+const HAS_CPU: &[u8] = b"/sys/devices/system/node/has_cpu\0";
+const STATUS: &[u8] = b"/proc/self/status\0";
+
+const MEMS_ALLOWED_LIST: &[u8] = b"Mems_allowed_list:";
+
+const NODE_PREFIX: &[u8] = b"/sys/devices/system/node/node";
+const DISTANCE_SUFFIX: &[u8] = b"/distance\0";
+const MEMORY_SIDE_CACHE_SUFFIX: &[u8] = b"/memory_side_cache\0";
+
+//  The longest path of a node, NUL-terminated.
+const NODE_PATH_LENGTH: usize = NODE_PREFIX.len() + 20 + MEMORY_SIDE_CACHE_SUFFIX.len();
+
+//  Returns the highest possible node plus one, as per the list of `possible`, as in `0-3`, or `0,2-3`.
+fn read_possible() -> Option<u32> {
+    let mut reader = LineReader::open(POSSIBLE)?;
+    let line = reader.next_line()?;
+
+    let last = line.rsplit(|byte| *byte == b'-' || *byte == b',').next()?;
+
+    parse_decimal(last)?.checked_add(1)
+}
+
+//  Returns the mask of the nodes allowed, among the first `MAXIMUM_NODES`, as per the `Mems_allowed_list` of `status`.
+fn read_allowed() -> Option<u64> {
+    let mut reader = LineReader::open(STATUS)?;
+
+    while let Some(line) = reader.next_line() {
+        if let Some(list) = line.strip_prefix(MEMS_ALLOWED_LIST) {
+            return parse_list(list);
+        }
+    }
+
+    None
+}
+
+//  Returns the mask of the nodes listed by `path`, NUL-terminated, among the first `MAXIMUM_NODES`, as in `0-1,3`.
+fn read_list(path: &[u8]) -> Option<u64> {
+    let mut reader = LineReader::open(path)?;
+    let line = reader.next_line()?;
+
+    parse_list(line)
+}
+
+//  Parses a list of nodes, as in `0-1,3`, surrounded by whitespace, into the mask of the first `MAXIMUM_NODES`.
+fn parse_list(list: &[u8]) -> Option<u64> {
+    let list = list.trim_ascii();
+
+    if list.is_empty() {
+        return None;
+    }
+
+    let mut mask = 0u64;
+
+    for range in list.split(|byte| *byte == b',') {
+        let mut bounds = range.splitn(2, |byte| *byte == b'-');
+
+        let low = parse_decimal(bounds.next()?)?;
+        let high = bounds.next().map_or(Some(low), parse_decimal)?;
+
+        for node in low..=high.min(MAXIMUM_NODES as u32 - 1) {
+            mask |= 1 << node;
+        }
+    }
+
+    Some(mask)
+}
+
+//  Invokes `f` with each node, and its distance from `from`, as per `node<from>/distance`.
+fn read_distances<F>(from: usize, mut f: F)
+    where
+        F: FnMut(usize, u8),
+{
+    let mut path = [0u8; NODE_PATH_LENGTH];
+
+    let mut reader = match LineReader::open(node_path(from, DISTANCE_SUFFIX, &mut path)) {
+        Some(reader) => reader,
+        None => return,
+    };
+
+    let line = match reader.next_line() {
+        Some(line) => line,
+        None => return,
+    };
+
+    let distances = line.split(|byte| *byte == b' ').filter(|field| !field.is_empty());
+
+    for (to, field) in distances.enumerate() {
+        if let Some(distance) = parse_decimal(field).and_then(|distance| u8::try_from(distance).ok()) {
+            f(to, distance);
+        }
+    }
+}
+
+//  Returns whether the memory of `node` is fronted by a memory-side cache, as per `node<node>/memory_side_cache`.
+fn has_memory_side_cache(node: usize) -> bool {
+    let mut path = [0u8; NODE_PATH_LENGTH];
+
+    is_accessible(node_path(node, MEMORY_SIDE_CACHE_SUFFIX, &mut path))
+}
+
+//  Writes the path of `suffix`, NUL-terminated, within the directory of `node` into `buffer`, returning the path.
+fn node_path<'a>(node: usize, suffix: &[u8], buffer: &'a mut [u8; NODE_PATH_LENGTH]) -> &'a [u8] {
+    debug_assert!(suffix.len() <= MEMORY_SIDE_CACHE_SUFFIX.len());
+
+    let mut length = NODE_PREFIX.len();
+    buffer[..length].copy_from_slice(NODE_PREFIX);
+
+    length += write_decimal(node, &mut buffer[length..]);
+    buffer[length..length + suffix.len()].copy_from_slice(suffix);
+
+    &buffer[..length + suffix.len()]
+}
+
+//  Parses `digits`, as in `42`, or returns None if it is not a decimal number.
+fn parse_decimal(digits: &[u8]) -> Option<u32> {
+    if digits.is_empty() {
+        return None;
+    }
+
+    digits.iter().try_fold(0u32, |number, digit| {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+
+        number.checked_mul(10)?.checked_add((digit - b'0') as u32)
+    })
+}
+
+//  Writes `value` in decimal into `buffer`, returning the number of digits written.
+fn write_decimal(value: usize, buffer: &mut [u8]) -> usize {
+    //  The digits, least significant first.
+    let (mut digits, mut count, mut value) = ([0u8; 20], 0, value);
+
+    loop {
+        digits[count] = b'0' + (value % 10) as u8;
+        count += 1;
+        value /= 10;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    for (index, digit) in digits[..count].iter().rev().enumerate() {
+        buffer[index] = *digit;
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn topology_decimal() {
+    assert_eq!(Some(0), parse_decimal(b"0"));
+    assert_eq!(Some(21), parse_decimal(b"21"));
+    assert_eq!(None, parse_decimal(b""));
+    assert_eq!(None, parse_decimal(b"2a"));
+    assert_eq!(None, parse_decimal(b"99999999999"));
+
+    let mut buffer = [0u8; 20];
+
+    assert_eq!(1, write_decimal(0, &mut buffer));
+    assert_eq!(b"0", &buffer[..1]);
+
+    assert_eq!(3, write_decimal(105, &mut buffer));
+    assert_eq!(b"105", &buffer[..3]);
+}
+
+#[test]
+fn topology_list() {
+    assert_eq!(Some(0b1), parse_list(b"\t0"));
+    assert_eq!(Some(0b1011), parse_list(b"0-1,3\n"));
+    assert_eq!(Some(0b1111_0100), parse_list(b"2,4-7"));
+    assert_eq!(Some(1 << 63), parse_list(b"63-127"));
+    assert_eq!(None, parse_list(b""));
+    assert_eq!(None, parse_list(b"0-"));
+    assert_eq!(None, parse_list(b"a"));
+}
+
+#[test]
+fn topology_discover() {
+    let topology = Topology::new();
+
+    let nodes = topology.nodes();
+    assert!(nodes >= 1);
+
+    //  A node is at a distance of 10 from itself, if known.
+    assert!(topology.distance(0, 0).is_none_or(|distance| distance == 10));
+    assert_eq!(None, topology.distance(nodes, 0));
+    assert_eq!(None, topology.distance(0, nodes));
+
+    //  At least one node is allowed, which is its own nearest.
+    let allowed = topology.nearest_allowed(0);
+
+    assert!(topology.is_allowed(allowed));
+    assert_eq!(allowed, topology.nearest_allowed(allowed));
+    assert!(!topology.is_allowed(nodes));
+
+    //  Not all nodes are far memory, lest no node would run threads.
+    assert!(topology.far_nodes() < nodes);
+    assert!(!topology.is_far(nodes));
+}
+
+#[test]
+fn topology_classify() {
+    let topology = Topology::new();
+
+    //  Nodes 0 and 1 have CPUs, node 2 is a distant expander, and node 3 a nearby node without CPUs.
+    let distances = [[10, 21, 30, 12], [21, 10, 30, 21], [30, 30, 10, 30], [12, 21, 30, 10]];
+
+    for (from, row) in distances.iter().enumerate() {
+        for (to, distance) in row.iter().enumerate() {
+            topology.distances[from * MAXIMUM_NODES + to].store(*distance, Ordering::Relaxed);
+        }
+    }
+
+    assert_eq!(0b0100, topology.classify(4, 0b0011));
+
+    //  Without a node with CPUs, all nodes are deemed near.
+    assert_eq!(0, topology.classify(4, 0));
+}
+
+#[test]
+fn topology_node_path() {
+    let mut buffer = [0u8; NODE_PATH_LENGTH];
+
+    assert_eq!(&b"/sys/devices/system/node/node12/distance\0"[..], node_path(12, DISTANCE_SUFFIX, &mut buffer));
+}
+
+} // mod tests
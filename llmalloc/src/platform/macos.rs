@@ -0,0 +1,338 @@
+//! Implementation of macOS specific calls.
+
+mod capabilities;
+
+use core::{
+    alloc::Layout,
+    ptr::NonNull,
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Fallback, HostCapabilities, HugePageReport,
+    PhysicalBuffer, PhysicalSegment, ThreadStack,
+};
+
+use super::{detector::Detector, mmap, unix, NumaNodeIndex, Configuration, Platform};
+
+use super::unix::FALLBACKS;
+
+pub(crate) use super::unix::LLConfiguration;
+
+/// Implementation of the Platform trait, for macOS.
+///
+/// macOS has a single NUMA node, backs the Huge Pages with superpages, of 2 MB, on x86_64 only, and exposes neither
+/// the physical addresses nor the kernel mappings of a process, hence the corresponding reports are limited to what can
+/// be observed: the residency of the memory.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        mmap::allocate(self, layout, |size| vm_allocate_super(size).or_else(|| mmap_normal(size)))
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) { mmap::deallocate(pointer, layout) }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        mmap::reallocate(self, pointer, layout, new_size, 0)
+    }
+}
+
+impl Platform for LLPlatform {
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex { NumaNodeIndex::new(0) }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node == 0 { Some(NumaNodeIndex::new(0)) } else { None }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 { unix::now() }
+
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        let page_size = mmap::os_page_size().value();
+        let resident = self.resident(page, size).unwrap_or(0);
+
+        //  Superpages cannot be told apart from normal pages, and all pages reside on the single node.
+        HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: page_size,
+            resident,
+            anonymous_huge: 0,
+            local_pages: resident / page_size,
+            foreign_pages: 0,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> { mmap::resident(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool { mmap::lock(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        mmap::protect(pointer, size, writable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        mmap::map_code(size, mapping, vm_remap_dual)
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) { mmap::unmap_code(region) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        mmap::protect_code(region, executable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> { mmap::map_stack(size, prefault) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, size) = stack.mapping();
+
+        mmap::munmap_deallocate(pointer.as_ptr(), size);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> {
+        const ALIGNMENT: usize = SUPERPAGE_SIZE;
+
+        let size = size.max(1).checked_add(ALIGNMENT - 1)? & !(ALIGNMENT - 1);
+
+        //  Superpages are always aligned on their size.
+        let superpages = Some(size)
+            .filter(|_| CAPABILITIES.get().huge_tlb)
+            .and_then(|size| vm_allocate(size, VM_FLAGS_SUPERPAGE_SIZE_2MB));
+
+        let (pointer, huge_tlb) = match superpages {
+            Some(pointer) => (pointer, true),
+            None => (mmap::mmap_aligned(size, ALIGNMENT, 0)?, false),
+        };
+
+        //  Safety:
+        //  -   `pointer` points to a mapped area of `size` bytes, not in use.
+        unsafe { mmap::lock_physical(pointer, size, huge_tlb) }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) {
+        mmap::munmap_deallocate(buffer.pointer().as_ptr(), buffer.size());
+    }
+
+    //  macOS does not expose the physical addresses to user space.
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool { unix::environment_flag(name) }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES.get() }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities { capabilities::detect_host(CAPABILITIES.get()) }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { unix::mapping_latency() }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> { unix::system_allocate(layout) }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) { unix::system_deallocate(pointer) }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { unix::owns(pointer) }
+}
+
+//  Capabilities of the environment.
+static CAPABILITIES: Detector = Detector::new(capabilities::detect);
+
+const SUPERPAGE_SIZE: usize = 2 * 1024 * 1024;
+
+//  Attempts to allocate the required size in superpages, unless known to be unavailable.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn vm_allocate_super(size: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    if !CAPABILITIES.get().huge_tlb {
+        return None;
+    }
+
+    //  Superpages are only aligned on their own size, hence the Huge Pages are aligned by trimming front and back.
+    let result = size.checked_add(ALIGNMENT.value())
+        .and_then(|over_size| Some((vm_allocate(over_size, VM_FLAGS_SUPERPAGE_SIZE_2MB)?, over_size)))
+        .and_then(|(pointer, over_size)| unsafe { mmap::trim(pointer, over_size, size, ALIGNMENT.value(), 0) });
+
+    match result {
+        Some(_) => FALLBACKS.record(Fallback::HugeTlbMapping),
+        None => {
+            FALLBACKS.record(Fallback::HugeTlbFailure);
+            CAPABILITIES.downgrade_huge_tlb();
+        },
+    }
+
+    result
+}
+
+//  Attempts to allocate the required size in normal pages.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_normal(size: usize) -> Option<NonNull<u8>> {
+    let exact = |size| mmap::mmap_exact(size, LLConfiguration::HUGE_PAGE_SIZE);
+
+    mmap::mmap_huge_page(size, exact, Fallback::NormalPageMapping)
+}
+
+//  Wrapper around `mach_vm_allocate`, `flags` being added to `VM_FLAGS_ANYWHERE`.
+//
+//  Returns a pointer to `size` bytes of memory, which may be deallocated with `munmap`.
+fn vm_allocate(size: usize, flags: i32) -> Option<NonNull<u8>> {
+    let mut address: u64 = 0;
+
+    //  Safety:
+    //  -   `address` is valid for writes.
+    //  -   `mach_task_self_` is initialized by the C library before `main`.
+    let result = unsafe { mach_vm_allocate(mach_task_self_, &mut address, size as u64, VM_FLAGS_ANYWHERE | flags) };
+
+    if result == KERN_SUCCESS { NonNull::new(address as *mut u8) } else { None }
+}
+
+//  Maps `size` bytes of memory twice, read-write then read-execute, returning both views.
+//
+//  The read-execute view is a remapping of the read-write one, sharing its pages.
+fn vm_remap_dual(size: usize) -> Option<(NonNull<u8>, NonNull<u8>)> {
+    let writable = mmap::mmap_allocate(size, 0)?;
+
+    let mut executable: u64 = 0;
+    let (mut current, mut maximum) = (0, 0);
+
+    //  Safety:
+    //  -   `executable`, `current`, and `maximum` are valid for writes.
+    //  -   `writable` points to a mapped area of `size` bytes.
+    //  -   `mach_task_self_` is initialized by the C library before `main`.
+    let result = unsafe {
+        mach_vm_remap(
+            mach_task_self_,
+            &mut executable,
+            size as u64,
+            0,
+            VM_FLAGS_ANYWHERE,
+            mach_task_self_,
+            writable.as_ptr() as u64,
+            0,
+            &mut current,
+            &mut maximum,
+            VM_INHERIT_NONE,
+        )
+    };
+
+    let executable = if result == KERN_SUCCESS { NonNull::new(executable as *mut u8) } else { None };
+
+    let protected = executable.filter(|executable| {
+        //  Safety:
+        //  -   `executable` points to a mapped area of `size` bytes, not yet in use.
+        let protected = unsafe {
+            libc::mprotect(executable.as_ptr() as *mut libc::c_void, size, libc::PROT_READ | libc::PROT_EXEC) == 0
+        };
+
+        if !protected {
+            //  Safety:
+            //  -   `executable` points to a mapped area of `size` bytes, not yet in use.
+            unsafe { mmap::munmap_deallocate(executable.as_ptr(), size) };
+        }
+
+        protected
+    });
+
+    match protected {
+        Some(executable) => Some((writable, executable)),
+        None => {
+            //  Safety:
+            //  -   `writable` points to a `mmap`ed area of `size` bytes, not yet in use.
+            unsafe { mmap::munmap_deallocate(writable.as_ptr(), size) };
+            None
+        },
+    }
+}
+
+const KERN_SUCCESS: i32 = 0;
+const VM_FLAGS_ANYWHERE: i32 = 0x0001;
+const VM_FLAGS_SUPERPAGE_SIZE_2MB: i32 = 2 << 16;
+const VM_INHERIT_NONE: u32 = 2;
+
+//  The Mach VM interface, part of libSystem.
+//
+//  The bindings of the `libc` crate are deprecated, in favor of a dedicated crate, hence the few calls needed are
+//  declared here.
+extern "C" {
+    //  The port of the current task, as returned by the `mach_task_self()` macro.
+    static mach_task_self_: u32;
+
+    //  Allocates `size` bytes of zeroed memory, anywhere if `flags` include `VM_FLAGS_ANYWHERE`, storing the address
+    //  in `address`.
+    fn mach_vm_allocate(target: u32, address: *mut u64, size: u64, flags: i32) -> i32;
+
+    //  Maps the `size` bytes located at `source_address` in `source_task` into `target`, sharing the memory unless
+    //  `copy`, storing the address in `target_address`, and the protections of the mapping in `current_protection`
+    //  and `maximum_protection`.
+    fn mach_vm_remap(
+        target: u32,
+        target_address: *mut u64,
+        size: u64,
+        mask: u64,
+        flags: i32,
+        source_task: u32,
+        source_address: u64,
+        copy: u32,
+        current_protection: *mut i32,
+        maximum_protection: *mut i32,
+        inheritance: u32,
+    ) -> i32;
+}
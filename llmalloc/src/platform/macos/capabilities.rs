@@ -0,0 +1,43 @@
+//! Detection of the capabilities of the environment.
+//!
+//! macOS offers neither NUMA nor Transparent Huge Pages, and only offers superpages, of 2 MB, on x86_64. Superpages
+//! are assumed available there, and downgraded on the first failure to map a Huge Page with them, sparing the futile
+//! system calls of further attempts.
+
+use crate::{Capabilities, HostCapabilities, TransparentHugePagesMode};
+
+use super::{mmap, unix};
+
+/// Detects the capabilities of the environment.
+#[cold]
+pub(super) fn detect() -> Capabilities {
+    Capabilities {
+        huge_tlb: cfg!(target_arch = "x86_64"),
+        huge_tlb_2mb: false,
+        transparent_huge_pages: false,
+        numa: false,
+        sysfs: true,
+    }
+}
+
+/// Detects the capabilities of the host, in details, given the `capabilities` selected.
+///
+/// Unlike the selected capabilities, the details are detected anew on each call.
+#[cold]
+pub(super) fn detect_host(capabilities: Capabilities) -> HostCapabilities {
+    let mut host = unix::host_capabilities(capabilities, mmap::os_page_size().value(), mmap::mlock_limit());
+
+    if cfg!(target_arch = "x86_64") {
+        host.add_huge_page_size(SUPERPAGE_SIZE);
+    }
+
+    host.transparent_huge_pages = TransparentHugePagesMode::Never;
+
+    host
+}
+
+//
+//  Implementation Details
+//
+
+const SUPERPAGE_SIZE: usize = 2 * 1024 * 1024;
@@ -0,0 +1,476 @@
+//! Implementation of the calls shared by the Unix platforms mapping their memory with `mmap`.
+//!
+//! The platforms only select how the Huge Pages are mapped and aligned, as well as the flags of `mmap` needed to grow
+//! them in place, the remaining calls being implemented once here, on top of the `unix` module.
+
+use core::{
+    alloc::Layout,
+    mem,
+    ptr::{self, NonNull},
+};
+
+use llmalloc_core::PowerOf2;
+
+use crate::{CodeMapping, CodeRegion, Fallback, PhysicalBuffer, ThreadStack, UnmapFailure};
+
+use crate::{pinning::PINNING, prefault::PREFAULT, unmapping::UNMAPPING};
+
+use super::{unix::{self, LLConfiguration, FALLBACKS}, Configuration, LLPlatform};
+
+/// Maps a Huge Page of `layout` with `map`, then prefaults and pins it as selected.
+///
+/// #   Safety
+///
+/// -   Assumes that `map` maps its memory with `mmap`.
+pub(super) unsafe fn allocate<F>(platform: &LLPlatform, layout: Layout, map: F) -> Option<NonNull<u8>>
+    where
+        F: FnOnce(usize) -> Option<NonNull<u8>>,
+{
+    let candidate = unix::map_huge_page(layout, map, munmap_deallocate)?;
+
+    PREFAULT.prefault(platform, candidate, layout.size(), os_page_size().value());
+    PINNING.pin(platform, candidate, layout.size());
+
+    Some(candidate)
+}
+
+/// Unmaps the Huge Page of `layout` located at `pointer`.
+///
+/// #   Safety
+///
+/// -   Assumes that `pointer` was allocated by `allocate`, with `layout`, and is no longer in use.
+pub(super) unsafe fn deallocate(pointer: NonNull<u8>, layout: Layout) {
+    unix::release(pointer.as_ptr(), layout.size());
+
+    munmap_deallocate(pointer.as_ptr(), layout.size());
+}
+
+/// Shrinks or grows, in place, the Huge Page of `layout` located at `pointer` to `new_size` bytes, growing by mapping
+/// the adjacent address space with the `extra_flags` of `mmap`.
+///
+/// Returns None if the Huge Page cannot be resized in place.
+///
+/// #   Safety
+///
+/// -   Assumes that `pointer` was allocated by `allocate`, with `layout`.
+/// -   Assumes that `extra_flags` never replace an existing mapping.
+pub(super) unsafe fn reallocate(platform: &LLPlatform, pointer: NonNull<u8>, layout: Layout, new_size: usize,
+    extra_flags: i32) -> Option<NonNull<u8>>
+{
+    const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    if new_size % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() {
+        return None;
+    }
+
+    let size = layout.size();
+
+    //  Shrink in place, by unmapping the tail.
+    if new_size <= size {
+        if new_size < size {
+            unix::release(pointer.as_ptr().add(new_size), size - new_size);
+
+            munmap_deallocate(pointer.as_ptr().add(new_size), size - new_size);
+        }
+
+        return Some(pointer);
+    }
+
+    //  Grow in place, if the adjacent address space is free; without `mremap`, the pages cannot be moved.
+    let tail = pointer.as_ptr().add(size);
+    let extension = mmap_allocate_at(tail, new_size - size, extra_flags)?;
+
+    if extension.as_ptr() != tail {
+        munmap_deallocate(extension.as_ptr(), new_size - size);
+        return None;
+    }
+
+    if !unix::claim(tail, new_size - size, munmap_deallocate) {
+        return None;
+    }
+
+    PREFAULT.prefault(platform, extension, new_size - size, os_page_size().value());
+    PINNING.pin(platform, extension, new_size - size);
+
+    Some(pointer)
+}
+
+/// Attempts to map `size` bytes aligned on `HUGE_PAGE_SIZE` with `attempt`, then by over-allocating, recording the
+/// mapping as `backing`, or as a failure.
+pub(super) fn mmap_huge_page<F>(size: usize, attempt: F, backing: Fallback) -> Option<NonNull<u8>>
+    where
+        F: FnOnce(usize) -> Option<NonNull<u8>>,
+{
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    let result = attempt(size).or_else(|| {
+        FALLBACKS.record(Fallback::MmapRetry);
+        mmap_aligned(size, ALIGNMENT.value(), 0)
+    });
+
+    match result {
+        Some(_) => FALLBACKS.record(backing),
+        None => FALLBACKS.record(Fallback::MmapFailure),
+    }
+
+    result
+}
+
+/// Maps `size` bytes of memory, returning them only if they happen to be aligned on `alignment`.
+pub(super) fn mmap_exact(size: usize, alignment: PowerOf2) -> Option<NonNull<u8>> {
+    let pointer = mmap_allocate(size, 0)?;
+
+    if pointer.as_ptr() as usize % alignment == 0 {
+        return Some(pointer);
+    }
+
+    //  Safety:
+    //  -   `pointer` points to a `mmap`ed area of `size` bytes, not in use.
+    unsafe { munmap_deallocate(pointer.as_ptr(), size) };
+    None
+}
+
+/// Maps `size` bytes of memory, such that the address `offset` bytes past the start of the memory is aligned on
+/// `alignment`, a power of 2, by over-allocating then trimming front and back.
+pub(super) fn mmap_aligned(size: usize, alignment: usize, offset: usize) -> Option<NonNull<u8>> {
+    debug_assert!(alignment.is_power_of_two());
+
+    let over_size = size.checked_add(alignment)?;
+    let front_pointer = mmap_allocate(over_size, 0)?;
+
+    //  Safety:
+    //  -   `front_pointer` points to a `mmap`ed area of `over_size` bytes, not in use.
+    unsafe { trim(front_pointer, over_size, size, alignment, offset) }
+}
+
+/// Trims the `over_size` bytes located at `pointer` down to `size` bytes, such that the address `offset` bytes past
+/// the start of the result is aligned on `alignment`, a power of 2.
+///
+/// #   Safety
+///
+/// -   Assumes that `pointer` points to a mapped area of `over_size` bytes, not in use.
+/// -   Assumes that `over_size` is at least `size + alignment`, and `offset` less than `alignment`.
+pub(super) unsafe fn trim(pointer: NonNull<u8>, over_size: usize, size: usize, alignment: usize, offset: usize)
+    -> Option<NonNull<u8>>
+{
+    debug_assert!(over_size >= size + alignment);
+    debug_assert!(offset < alignment);
+
+    let start = pointer.as_ptr() as usize;
+    let aligned = ((start + offset + alignment - 1) & !(alignment - 1)) - offset;
+    let aligned = if aligned < start { aligned + alignment } else { aligned };
+
+    let front_size = aligned - start;
+    let back_size = over_size - front_size - size;
+
+    if front_size > 0 {
+        //  Safety:
+        //  -   `[start, start + front_size)` is within the mapped area, and not in use.
+        munmap_deallocate(pointer.as_ptr(), front_size);
+    }
+
+    if back_size > 0 {
+        //  Safety:
+        //  -   `[aligned + size, aligned + size + back_size)` is within the mapped area, and not in use.
+        munmap_deallocate((aligned + size) as *mut u8, back_size);
+    }
+
+    NonNull::new(aligned as *mut u8)
+}
+
+/// Wrapper around `mmap`.
+///
+/// Returns a pointer to `size` bytes of memory; does not guarantee any alignment, unless requested by `extra_flags`.
+pub(super) fn mmap_allocate(size: usize, extra_flags: i32) -> Option<NonNull<u8>> {
+    mmap_allocate_at(ptr::null_mut(), size, extra_flags)
+}
+
+/// Wrapper around `mmap`, with `address` as hint if not null, or as whatever `extra_flags` make of it.
+///
+/// Returns a pointer to `size` bytes of memory, at `address` only if available.
+pub(super) fn mmap_allocate_at(address: *mut u8, size: usize, extra_flags: i32) -> Option<NonNull<u8>> {
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+    let flags = libc::MAP_PRIVATE | libc::MAP_ANON | extra_flags;
+
+    //  Safety:
+    //  -   The platforms never pass `MAP_FIXED` without a flag failing rather than replace an existing mapping.
+    //  -   `fd` and `offset` are suitable for `MAP_ANON`.
+    let result = unsafe { libc::mmap(address as *mut libc::c_void, size, prot, flags, -1, 0) };
+
+    let result = if result != libc::MAP_FAILED { result as *mut u8 } else { ptr::null_mut() };
+    NonNull::new(result)
+}
+
+/// Maps `size` bytes of the shared memory object `fd` twice, read-write then read-execute, returning both views.
+///
+/// The object is closed once mapped, the mappings keeping it alive.
+#[cfg(any(feature = "posix", not(target_os = "macos")))]
+pub(super) fn mmap_dual(fd: libc::c_int, size: usize) -> Option<(NonNull<u8>, NonNull<u8>)> {
+    let map = |prot| {
+        //  Safety:
+        //  -   `fd` is a valid file descriptor, of at least `size` bytes once truncated.
+        let result = unsafe { libc::mmap(ptr::null_mut(), size, prot, libc::MAP_SHARED, fd, 0) };
+
+        if result != libc::MAP_FAILED { NonNull::new(result as *mut u8) } else { None }
+    };
+
+    //  Safety:
+    //  -   `fd` is a valid file descriptor.
+    let truncated = unsafe { libc::ftruncate(fd, size as libc::off_t) } == 0;
+
+    let writable = if truncated { map(libc::PROT_READ | libc::PROT_WRITE) } else { None };
+    let executable = writable.and_then(|_| map(libc::PROT_READ | libc::PROT_EXEC));
+
+    //  Safety:
+    //  -   `fd` is a valid file descriptor, no longer needed.
+    unsafe { libc::close(fd) };
+
+    match (writable, executable) {
+        (Some(writable), Some(executable)) => Some((writable, executable)),
+        (Some(writable), None) => {
+            //  Safety:
+            //  -   `writable` points to a `mmap`ed area of `size` bytes, not yet in use.
+            unsafe { munmap_deallocate(writable.as_ptr(), size) };
+            None
+        },
+        _ => None,
+    }
+}
+
+/// Maps `size` bytes for executable code, rounded up to OS pages, the dual views being mapped by `dual`.
+pub(super) fn map_code<F>(size: usize, mapping: CodeMapping, dual: F) -> Option<CodeRegion>
+    where
+        F: FnOnce(usize) -> Option<(NonNull<u8>, NonNull<u8>)>,
+{
+    let page_size = os_page_size().value();
+    let size = size.max(1).checked_add(page_size - 1)? & !(page_size - 1);
+
+    let (writable, executable) = match mapping {
+        CodeMapping::Dual => dual(size)?,
+        CodeMapping::Flip => {
+            let pointer = mmap_allocate(size, 0)?;
+            (pointer, pointer)
+        },
+    };
+
+    Some(CodeRegion::new(writable, executable, size, mapping))
+}
+
+/// Unmaps `region`, and its executable view if distinct.
+///
+/// #   Safety
+///
+/// -   Assumes that `region` was mapped by `map_code`, and is no longer in use.
+pub(super) unsafe fn unmap_code(region: CodeRegion) {
+    munmap_deallocate(region.writable().as_ptr(), region.size());
+
+    if region.mapping() == CodeMapping::Dual {
+        munmap_deallocate(region.executable().as_ptr(), region.size());
+    }
+}
+
+/// Flips `region` to executable, or back to writable.
+///
+/// #   Safety
+///
+/// -   Assumes that `region` was mapped by `map_code`, with `CodeMapping::Flip`.
+pub(super) unsafe fn protect_code(region: &CodeRegion, executable: bool) -> bool {
+    debug_assert!(region.mapping() == CodeMapping::Flip);
+
+    let prot = if executable { libc::PROT_READ | libc::PROT_EXEC } else { libc::PROT_READ | libc::PROT_WRITE };
+
+    libc::mprotect(region.writable().as_ptr() as *mut libc::c_void, region.size(), prot) == 0
+}
+
+/// Protects the `size` bytes located at `pointer` as read-only, or read-write if `writable`.
+///
+/// #   Safety
+///
+/// -   Assumes that `[pointer, pointer + size)` is mapped.
+pub(super) unsafe fn protect(pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+    let prot = if writable { libc::PROT_READ | libc::PROT_WRITE } else { libc::PROT_READ };
+
+    libc::mprotect(pointer.as_ptr() as *mut libc::c_void, size, prot) == 0
+}
+
+/// Locks the `size` bytes located at `pointer` in memory.
+pub(super) fn lock(pointer: NonNull<u8>, size: usize) -> bool {
+    //  Safety:
+    //  -   `mlock` does not access the memory it locks, it only faults it in.
+    let result = unsafe { libc::mlock(pointer.as_ptr() as *const libc::c_void, size) };
+
+    result == 0
+}
+
+/// Maps a stack of `size` bytes, rounded up to Large Pages and aligned on them, with a guard page right below.
+pub(super) fn map_stack(size: usize, prefault: bool) -> Option<ThreadStack> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::LARGE_PAGE_SIZE;
+
+    let guard_size = os_page_size().value();
+    let size = size.max(1).checked_add(ALIGNMENT.value() - 1)? & !(ALIGNMENT.value() - 1);
+
+    //  The usable area is aligned, hence eligible for large pages where the kernel promotes them, with the guard page
+    //  immediately below.
+    let guard = mmap_aligned(guard_size.checked_add(size)?, ALIGNMENT.value(), guard_size)?;
+    let bottom = guard.as_ptr() as usize + guard_size;
+
+    //  Safety:
+    //  -   `[guard, guard + guard_size)` is within the mapped area, and not in use.
+    if unsafe { libc::mprotect(guard.as_ptr() as *mut libc::c_void, guard_size, libc::PROT_NONE) } != 0 {
+        //  Safety:
+        //  -   `[guard, guard + guard_size + size)` is the mapped area, and not in use.
+        unsafe { munmap_deallocate(guard.as_ptr(), guard_size + size) };
+        return None;
+    }
+
+    if prefault {
+        for offset in (0..size).step_by(guard_size) {
+            //  Safety:
+            //  -   `bottom + offset` is within the usable area, writable and not in use.
+            unsafe { ptr::write_volatile((bottom + offset) as *mut u8, 0) };
+        }
+    }
+
+    Some(ThreadStack::new(guard, guard_size, size))
+}
+
+/// Locks the `size` bytes located at `pointer`, freshly mapped, into a physical buffer, backed by HugeTLB pages if
+/// `huge_tlb`, unmapping them on failure.
+///
+/// #   Safety
+///
+/// -   Assumes that `pointer` points to a mapped area of `size` bytes, not in use, which `munmap` may unmap.
+pub(super) unsafe fn lock_physical(pointer: NonNull<u8>, size: usize, huge_tlb: bool) -> Option<PhysicalBuffer> {
+    if !lock(pointer, size) {
+        munmap_deallocate(pointer.as_ptr(), size);
+        return None;
+    }
+
+    Some(PhysicalBuffer::new(pointer, size, huge_tlb))
+}
+
+/// Invokes `f` with the state of each OS page covering the `size` bytes located at `address`, as per `mincore`.
+///
+/// Returns None if the pages cannot be looked up.
+pub(super) fn for_each_page<F>(address: usize, size: usize, mut f: F) -> Option<()>
+    where
+        F: FnMut(u8),
+{
+    //  Number of pages looked up at once.
+    const BATCH: usize = 4096;
+
+    let page_size = os_page_size();
+    let mut vector = [0u8; BATCH];
+
+    let end = address.checked_add(size)?;
+    let mut current = page_size.round_down(address);
+
+    while current < end {
+        let length = (end - current).min(BATCH * page_size.value());
+
+        //  `mincore` is not part of POSIX proper, yet is offered by all Unix systems, with either signed or unsigned
+        //  bytes, and either a constant or mutable address.
+        //
+        //  Safety:
+        //  -   `current` is aligned on an OS page.
+        //  -   `vector` holds one byte per page of the `length` bytes.
+        let result = unsafe { libc::mincore(current as *mut _, length, vector.as_mut_ptr() as *mut _) };
+
+        if result != 0 {
+            return None;
+        }
+
+        let pages = length.div_ceil(page_size.value());
+        vector[..pages].iter().for_each(|state| f(*state));
+
+        current += length;
+    }
+
+    Some(())
+}
+
+/// Returns the number of bytes of the `size` bytes located at `pointer` which are resident, as per `mincore`.
+pub(super) fn resident(pointer: NonNull<u8>, size: usize) -> Option<usize> {
+    let page_size = os_page_size().value();
+    let mut resident = 0;
+
+    for_each_page(pointer.as_ptr() as usize, size, |state| {
+        if state & 1 != 0 {
+            resident += page_size;
+        }
+    })?;
+
+    Some(resident.min(size))
+}
+
+/// Returns the soft limit of locked memory, in bytes, or None if unlimited or unknown.
+pub(super) fn mlock_limit() -> Option<u64> {
+    //  Safety:
+    //  -   `rlimit` is plain old data.
+    let mut limit: libc::rlimit = unsafe { mem::zeroed() };
+
+    //  Safety:
+    //  -   `limit` is valid for writes.
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } != 0 || limit.rlim_cur == libc::RLIM_INFINITY {
+        return None;
+    }
+
+    Some(limit.rlim_cur as u64)
+}
+
+/// Returns the size of the OS pages.
+pub(super) fn os_page_size() -> PowerOf2 {
+    const DEFAULT: PowerOf2 = unsafe { PowerOf2::new_unchecked(4096) };
+
+    //  Safety:
+    //  -   `sysconf` has no precondition.
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+
+    if size <= 0 { DEFAULT } else { PowerOf2::new(size as usize).unwrap_or(DEFAULT) }
+}
+
+/// Wrapper around `munmap`.
+///
+/// #   Safety
+///
+/// -   Assumes that `addr` points to a mapped area of at least `size` bytes.
+/// -   Assumes that the range `[addr, addr + size)` is no longer in use.
+pub(super) unsafe fn munmap_deallocate(addr: *mut u8, size: usize) {
+    if libc::munmap(addr as *mut libc::c_void, size) != 0 {
+        let failure = UnmapFailure { address: addr as usize, size, error: errno() };
+
+        //  Should the memory fail to be unmapped, it is leaked, unless the process is aborted.
+        UNMAPPING.fail(&FALLBACKS, failure, || unsafe { libc::abort() });
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  Returns the errno of the current thread, or 0 on the Unixes whose location of the errno is unknown.
+fn errno() -> i32 {
+    #[cfg(any(target_os = "linux", target_os = "fuchsia", target_os = "redox"))]
+    let location = libc::__errno_location;
+
+    #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+    let location = libc::__errno;
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+    let location = libc::__error;
+
+    #[cfg(any(target_os = "illumos", target_os = "solaris"))]
+    let location = libc::___errno;
+
+    #[cfg(not(any(target_os = "linux", target_os = "fuchsia", target_os = "redox", target_os = "android",
+        target_os = "netbsd", target_os = "openbsd", target_os = "macos", target_os = "ios", target_os = "freebsd",
+        target_os = "dragonfly", target_os = "illumos", target_os = "solaris")))]
+    return 0;
+
+    //  Safety:
+    //  -   `location` always returns a valid pointer, to the errno of the current thread.
+    #[cfg(any(target_os = "linux", target_os = "fuchsia", target_os = "redox", target_os = "android",
+        target_os = "netbsd", target_os = "openbsd", target_os = "macos", target_os = "ios", target_os = "freebsd",
+        target_os = "dragonfly", target_os = "illumos", target_os = "solaris"))]
+    unsafe { *location() }
+}
@@ -0,0 +1,565 @@
+//! Implementation of a deterministic mock platform, for the tests of the crates built upon llmalloc.
+//!
+//! The Huge Pages are carved out of a static arena, first fit, rather than mapped: the machine running the tests needs
+//! neither Huge Pages, nor NUMA, nor any privilege, and a given sequence of calls always yields the same offsets within
+//! the arena. Every call to allocate, reallocate, or deallocate Huge Pages is journaled, for the tests to check, and
+//! failures may be injected, to exercise the paths handling them.
+//!
+//! The clock only moves when advanced by the test, and the thread-local storage is that of pthread; neither code,
+//! stacks, nor physical buffers can be mapped.
+
+use core::{
+    alloc::Layout,
+    cell::UnsafeCell,
+    convert::TryFrom,
+    mem::MaybeUninit,
+    ptr::NonNull,
+    sync::atomic::{self, AtomicBool, AtomicU64},
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Fallback, HostCapabilities, HugePageReport,
+    PhysicalBuffer, PhysicalSegment, ThreadStack,
+};
+
+use super::{NumaNodeIndex, Configuration, Platform};
+
+/// Implementation of the Configuration trait, for the mock platform.
+///
+/// The pages are those of the `small-heap` feature, whether enabled or not, so as to fit within the static arena: 2 MB
+/// Huge Pages, carved into 64 KB Large Pages.
+#[derive(Default)]
+pub struct LLConfiguration;
+
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
+/// A call to the mock platform, as journaled.
+///
+/// The memory is designated by its offset within the arena, which, unlike its address, is deterministic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MockCall {
+    /// An allocation of Huge Pages.
+    Allocate {
+        /// Size requested, in bytes.
+        size: usize,
+        /// Offset of the memory allocated, or None if the allocation failed.
+        offset: Option<usize>,
+    },
+    /// A reallocation of Huge Pages, in place.
+    Reallocate {
+        /// Offset of the memory reallocated.
+        offset: usize,
+        /// Size of the memory, prior to the reallocation, in bytes.
+        size: usize,
+        /// Size requested, in bytes.
+        new_size: usize,
+        /// Whether the memory was resized.
+        resized: bool,
+    },
+    /// A deallocation of Huge Pages.
+    Deallocate {
+        /// Offset of the memory deallocated.
+        offset: usize,
+        /// Size of the memory, in bytes.
+        size: usize,
+    },
+}
+
+/// Implementation of the Platform trait, over a static arena, as returned by `LLAllocator::mock_platform`.
+pub struct MockPlatform(());
+
+pub(crate) type LLPlatform = MockPlatform;
+
+impl MockPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self(()) }
+
+    /// Returns the size of the arena, in bytes.
+    pub fn arena_size(&self) -> usize { ARENA_SIZE }
+
+    /// Returns the number of bytes of the arena currently allocated.
+    pub fn allocated(&self) -> usize { PAGES.used() * HUGE_PAGE_SIZE }
+
+    /// Returns the offset of `pointer` within the arena, or None if it does not point within the arena.
+    pub fn offset_of(&self, pointer: NonNull<u8>) -> Option<usize> {
+        let offset = (pointer.as_ptr() as usize).checked_sub(arena_start())?;
+
+        if offset < ARENA_SIZE { Some(offset) } else { None }
+    }
+
+    /// Makes the next `count` attempts to allocate, or grow, Huge Pages fail, as if the OS were out of memory.
+    ///
+    /// A `count` of 0 cancels the failures not yet injected.
+    pub fn fail_next(&self, count: usize) { PAGES.fail_next(count) }
+
+    /// Advances the clock, which only ever moves when advanced, by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+
+        let _ = CLOCK.fetch_update(atomic::Ordering::Relaxed, atomic::Ordering::Relaxed, |now| {
+            Some(now.saturating_add(nanos))
+        });
+    }
+
+    /// Invokes `f` with each journaled call, oldest first, then returns the number of calls made since the journal
+    /// was last cleared, whether journaled or not.
+    ///
+    /// The journal holds the first 1024 calls; the later ones are only counted.
+    pub fn calls<F>(&self, mut f: F) -> usize
+        where
+            F: FnMut(&MockCall),
+    {
+        //  The journal is not locked while `f` runs, as it may well allocate.
+        let mut index = 0;
+
+        while let Some(call) = PAGES.call(index) {
+            f(&call);
+            index += 1;
+        }
+
+        PAGES.calls()
+    }
+
+    /// Clears the journal.
+    pub fn clear_calls(&self) { PAGES.clear_calls() }
+}
+
+impl llmalloc_core::Platform for MockPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        debug_assert!(layout.size() % LLConfiguration::HUGE_PAGE_SIZE == 0,
+            "Incorrect size: {} % {} != 0", layout.size(), HUGE_PAGE_SIZE);
+        debug_assert!(layout.align() <= HUGE_PAGE_SIZE,
+            "Incorrect alignment: {} > {}", layout.align(), HUGE_PAGE_SIZE);
+
+        if layout.size() % LLConfiguration::HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE {
+            return None;
+        }
+
+        match PAGES.allocate(layout.size() / HUGE_PAGE_SIZE) {
+            Some(index) => {
+                FALLBACKS.record(Fallback::NormalPageMapping);
+                NonNull::new((arena_start() + index * HUGE_PAGE_SIZE) as *mut u8)
+            },
+            None => {
+                FALLBACKS.record(Fallback::MmapFailure);
+                None
+            },
+        }
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        if let Some(offset) = self.offset_of(pointer) {
+            PAGES.release(offset / HUGE_PAGE_SIZE, layout.size() / HUGE_PAGE_SIZE);
+        }
+    }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        if new_size % LLConfiguration::HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE {
+            return None;
+        }
+
+        let offset = self.offset_of(pointer)?;
+
+        //  The pages are resized in place, shrinking by releasing the tail, or growing if the following pages are free.
+        if PAGES.resize(offset / HUGE_PAGE_SIZE, layout.size() / HUGE_PAGE_SIZE, new_size / HUGE_PAGE_SIZE) {
+            Some(pointer)
+        } else {
+            None
+        }
+    }
+}
+
+impl Platform for MockPlatform {
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex { NumaNodeIndex::new(0) }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node == 0 { Some(NumaNodeIndex::new(0)) } else { None }
+    }
+
+    #[inline(always)]
+    fn now(&self) -> u64 { CLOCK.load(atomic::Ordering::Relaxed) }
+
+    //  The arena is deemed neither paged, nor split, nor migrated.
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: OS_PAGE_SIZE,
+            resident: size,
+            anonymous_huge: 0,
+            local_pages: size / OS_PAGE_SIZE,
+            foreign_pages: 0,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, _pointer: NonNull<u8>, size: usize) -> Option<usize> { Some(size) }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, _pointer: NonNull<u8>, _size: usize) -> bool { true }
+
+    //  The mock makes no system call, hence cannot protect the arena.
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, _pointer: NonNull<u8>, _size: usize, _writable: bool) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, _size: usize, _mapping: CodeMapping) -> Option<CodeRegion> { None }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, _region: CodeRegion) {}
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, _region: &CodeRegion, _executable: bool) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, _size: usize, _prefault: bool) -> Option<ThreadStack> { None }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, _stack: ThreadStack) {}
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, _size: usize) -> Option<PhysicalBuffer> { None }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, _buffer: PhysicalBuffer) {}
+
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    //  The environment of the tests is not to alter their outcome.
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, _name: &[u8]) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities {
+        let mut host = HostCapabilities::default();
+        host.capabilities = CAPABILITIES;
+
+        host.numa_nodes = 1;
+        host.os_page_size = OS_PAGE_SIZE;
+
+        host
+    }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { None }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    //  The system allocator is not deterministic, hence never delegated to.
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, _layout: Layout) -> Option<NonNull<u8>> { None }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, _pointer: NonNull<u8>) {}
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { self.offset_of(pointer).is_some() }
+}
+
+//
+//  Implementation Details
+//
+
+//  Capabilities of the environment, which are not detected: the arena is backed by whatever the host backs it with.
+const CAPABILITIES: Capabilities =
+    Capabilities { huge_tlb: false, huge_tlb_2mb: false, transparent_huge_pages: false, numa: false, sysfs: true };
+
+const HUGE_PAGE_SIZE: usize = LLConfiguration::HUGE_PAGE_SIZE.value();
+
+const OS_PAGE_SIZE: usize = 4096;
+
+//  Number of Huge Pages of the arena, that is 128 MB.
+const ARENA_PAGES: usize = 64;
+
+const ARENA_SIZE: usize = ARENA_PAGES * HUGE_PAGE_SIZE;
+
+//  Number of calls journaled.
+const JOURNAL_CAPACITY: usize = 1024;
+
+//  Metrics of the fallbacks.
+static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+//  The clock, in nanoseconds.
+static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+//  The arena, over-sized by a Huge Page so as to be aligned at run-time, as statics cannot be aligned on 2 MB on all
+//  platforms.
+static ARENA: Arena = Arena(UnsafeCell::new(MaybeUninit::uninit()));
+
+//  The Huge Pages of the arena in use, and the journal.
+static PAGES: Pages = Pages::new();
+
+struct Arena(UnsafeCell<MaybeUninit<[u8; ARENA_SIZE + HUGE_PAGE_SIZE]>>);
+
+//  Safety:
+//  -   The arena is only accessed through the pointers handed out, to distinct Huge Pages.
+unsafe impl Sync for Arena {}
+
+//  Returns the address of the first Huge Page of the arena.
+fn arena_start() -> usize {
+    let base = ARENA.0.get() as usize;
+
+    (base + HUGE_PAGE_SIZE - 1) & !(HUGE_PAGE_SIZE - 1)
+}
+
+//  The Huge Pages of the arena in use, the failures to inject, and the journal of the calls.
+//
+//  The Huge Pages are rarely allocated, hence a lock suffices, also ordering the calls journaled.
+struct Pages {
+    lock: AtomicBool,
+    state: UnsafeCell<State>,
+}
+
+struct State {
+    used: u64,
+    failures: usize,
+    calls: usize,
+    journal: [MockCall; JOURNAL_CAPACITY],
+}
+
+//  Safety:
+//  -   `state` is only ever accessed with `lock` held.
+unsafe impl Sync for Pages {}
+
+impl Pages {
+    const fn new() -> Self {
+        const EMPTY: MockCall = MockCall::Deallocate { offset: 0, size: 0 };
+
+        let state = State { used: 0, failures: 0, calls: 0, journal: [EMPTY; JOURNAL_CAPACITY] };
+
+        Self { lock: AtomicBool::new(false), state: UnsafeCell::new(state) }
+    }
+
+    //  Allocates `count` consecutive Huge Pages, first fit, returning the index of the first.
+    fn allocate(&self, count: usize) -> Option<usize> {
+        self.locked(|state| {
+            let result = if state.inject_failure() { None } else { state.find(count) };
+
+            if let Some(first) = result {
+                state.set(first, count, true);
+            }
+
+            let offset = result.map(|first| first * HUGE_PAGE_SIZE);
+            state.journal(MockCall::Allocate { size: count * HUGE_PAGE_SIZE, offset });
+
+            result
+        })
+    }
+
+    //  Resizes the `count` Huge Pages starting at `first` to `new_count`, in place, returning whether it succeeded.
+    fn resize(&self, first: usize, count: usize, new_count: usize) -> bool {
+        self.locked(|state| {
+            let resized = if new_count <= count {
+                state.set(first + new_count, count - new_count, false);
+                true
+            } else if !state.inject_failure() && state.is_free(first + count, new_count - count) {
+                state.set(first + count, new_count - count, true);
+                true
+            } else {
+                false
+            };
+
+            let (offset, size, new_size) = (first * HUGE_PAGE_SIZE, count * HUGE_PAGE_SIZE, new_count * HUGE_PAGE_SIZE);
+            state.journal(MockCall::Reallocate { offset, size, new_size, resized });
+
+            resized
+        })
+    }
+
+    //  Releases the `count` Huge Pages starting at `first`.
+    fn release(&self, first: usize, count: usize) {
+        self.locked(|state| {
+            state.set(first, count, false);
+            state.journal(MockCall::Deallocate { offset: first * HUGE_PAGE_SIZE, size: count * HUGE_PAGE_SIZE });
+        })
+    }
+
+    //  Returns the number of Huge Pages in use.
+    fn used(&self) -> usize { self.locked(|state| state.used.count_ones() as usize) }
+
+    fn fail_next(&self, count: usize) { self.locked(|state| state.failures = count) }
+
+    //  Returns the call journaled at `index`, if any.
+    fn call(&self, index: usize) -> Option<MockCall> {
+        self.locked(|state| state.journal[..state.calls.min(JOURNAL_CAPACITY)].get(index).copied())
+    }
+
+    //  Returns the number of calls, journaled or not.
+    fn calls(&self) -> usize { self.locked(|state| state.calls) }
+
+    fn clear_calls(&self) { self.locked(|state| state.calls = 0) }
+
+    //  Invokes `f` with the state, and the lock held.
+    fn locked<R, F>(&self, f: F) -> R
+        where
+            F: FnOnce(&mut State) -> R,
+    {
+        const ACQUIRE: atomic::Ordering = atomic::Ordering::Acquire;
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        while self.lock.compare_exchange_weak(false, true, ACQUIRE, RELAXED).is_err() {
+            core::hint::spin_loop();
+        }
+
+        //  Safety:
+        //  -   The lock is held, hence the access is exclusive.
+        let result = f(unsafe { &mut *self.state.get() });
+
+        self.lock.store(false, atomic::Ordering::Release);
+
+        result
+    }
+}
+
+impl State {
+    //  Consumes one injected failure, if any, returning whether it did.
+    fn inject_failure(&mut self) -> bool {
+        if self.failures == 0 {
+            return false;
+        }
+
+        self.failures -= 1;
+        true
+    }
+
+    //  Returns the index of the first run of `count` free Huge Pages, if any.
+    fn find(&self, count: usize) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+
+        (0..ARENA_PAGES).find(|first| self.is_free(*first, count))
+    }
+
+    //  Returns whether the `count` Huge Pages starting at `first` exist, and are free.
+    fn is_free(&self, first: usize, count: usize) -> bool {
+        first + count <= ARENA_PAGES && (first..first + count).all(|index| self.used & (1 << index) == 0)
+    }
+
+    fn set(&mut self, first: usize, count: usize, used: bool) {
+        for index in first..(first + count).min(ARENA_PAGES) {
+            if used {
+                self.used |= 1 << index;
+            } else {
+                self.used &= !(1 << index);
+            }
+        }
+    }
+
+    fn journal(&mut self, call: MockCall) {
+        if let Some(entry) = self.journal.get_mut(self.calls) {
+            *entry = call;
+        }
+
+        self.calls += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn pages_allocate_release() {
+    let pages = Pages::new();
+
+    assert_eq!(Some(0), pages.allocate(1));
+    assert_eq!(Some(1), pages.allocate(2));
+    assert_eq!(None, pages.allocate(ARENA_PAGES));
+    assert_eq!(3, pages.used());
+
+    pages.release(0, 1);
+
+    //  First fit.
+    assert_eq!(Some(0), pages.allocate(1));
+    assert_eq!(Some(3), pages.allocate(1));
+
+    assert!(!pages.resize(1, 2, 3));
+    assert!(pages.resize(3, 1, 2));
+    assert!(pages.resize(1, 2, 1));
+    assert_eq!(4, pages.used());
+
+    let mut calls = [None; 9];
+    let mut index = 0;
+
+    while let Some(call) = pages.call(index) {
+        calls[index] = Some(call);
+        index += 1;
+    }
+
+    assert_eq!(9, pages.calls());
+    assert_eq!(Some(MockCall::Allocate { size: ARENA_SIZE, offset: None }), calls[2]);
+    assert_eq!(Some(MockCall::Deallocate { offset: 0, size: HUGE_PAGE_SIZE }), calls[3]);
+
+    let resized = MockCall::Reallocate { offset: HUGE_PAGE_SIZE, size: 2 * HUGE_PAGE_SIZE, new_size: HUGE_PAGE_SIZE,
+        resized: true };
+    assert_eq!(Some(resized), calls[8]);
+}
+
+#[test]
+fn pages_fail_next() {
+    let pages = Pages::new();
+
+    pages.fail_next(2);
+
+    assert_eq!(None, pages.allocate(1));
+    assert_eq!(None, pages.allocate(1));
+    assert_eq!(Some(0), pages.allocate(1));
+
+    pages.fail_next(1);
+
+    //  Shrinking never fails, unlike growing.
+    assert!(!pages.resize(0, 1, 2));
+    assert!(pages.resize(0, 1, 2));
+    assert!(pages.resize(0, 2, 1));
+
+    pages.clear_calls();
+
+    assert_eq!(0, pages.calls());
+    assert_eq!(None, pages.call(0));
+}
+
+} // mod tests
@@ -0,0 +1,1205 @@
+//! Implementation of a Linux platform issuing raw system calls, without libc, for fully static, `-nostdlib`, binaries.
+//!
+//! The memory is mapped as on Linux, falling back from HugeTLB pages to Transparent Huge Pages, yet with `mmap`,
+//! `munmap`, and `getcpu` issued directly, and the capabilities read from `/sys` with `openat` and `read`. The NUMA
+//! node of the current thread is reported by `getcpu` itself, without libnuma, hence the nodes are not clustered by
+//! distance, and the kernel view of the Huge Pages is reduced to their residency. Neither physical buffers, nor the
+//! system allocator, are available.
+//!
+//! The thread-local storage is a `#[thread_local]` static, requiring a nightly compiler, and the ELF TLS to be set up
+//! by the embedder. There being no pthread key, hence no destructor, each thread which allocated is to call
+//! `LLAllocator::release_thread` prior to exiting, lest the memory it caches be leaked.
+
+mod syscall;
+
+use core::{
+    alloc::Layout,
+    cell::Cell,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    sync::atomic::{self, AtomicU8, AtomicUsize},
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    capabilities::TRANSPARENT_HUGE_PAGES_VARIABLE, AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion,
+    Fallback, HostCapabilities, HugePageReport, HugeTlbPool, HugeTlbPools, PhysicalBuffer, PhysicalSegment,
+    ThreadStack, UnmapFailure,
+};
+
+use crate::{decay::DECAY, decommit::DECOMMIT, pinning::PINNING, prefault::PREFAULT, unmapping::UNMAPPING};
+
+use super::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+
+use syscall::{
+    ENOMEM, MADV_DONTNEED, MADV_FREE, MADV_HUGEPAGE, MAP_FIXED, MAP_HUGETLB, MAP_HUGE_SHIFT, MAP_PRIVATE, MAP_SHARED,
+    MAP_STACK, MREMAP_FIXED, MREMAP_MAYMOVE, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE,
+};
+
+/// Implementation of the Configuration trait, for Linux without libc.
+///
+/// The pages are sized as on Linux, see the Linux configuration for the consequences of the `small-heap` feature.
+#[derive(Default)]
+pub(crate) struct LLConfiguration;
+
+#[cfg(not(feature = "small-heap"))]
+impl Configuration for LLConfiguration {
+    //  2 MB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+
+    //  1 GB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(1024 * 1024 * 1024) };
+}
+
+#[cfg(feature = "small-heap")]
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
+/// Implementation of the Platform trait, for Linux without libc.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        debug_assert!(layout.size() % HUGE_PAGE_SIZE == 0,
+            "Incorrect size: {} % {} != 0", layout.size(), HUGE_PAGE_SIZE.value());
+        debug_assert!(layout.align() <= HUGE_PAGE_SIZE.value(),
+            "Incorrect alignment: {} > {}", layout.align(), HUGE_PAGE_SIZE.value());
+
+        if layout.size() % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() {
+            return None;
+        }
+
+        let start = self.now();
+
+        let candidate = mmap_huge(layout.size())
+            .or_else(|| mmap_huge_2mb(layout.size()))
+            .or_else(|| mmap_normal(layout.size()));
+
+        //  Failed mappings count too, as a deadline must also cover the paths which end up failing.
+        MAPPING_LATENCY.fetch_max(self.now().saturating_sub(start).saturating_add(1), atomic::Ordering::Relaxed);
+
+        let candidate = candidate?;
+
+        PREFAULT.prefault(self, candidate, layout.size(), os_page_size().value());
+        PINNING.pin(self, candidate, layout.size());
+
+        Some(candidate)
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        munmap_deallocate(pointer.as_ptr(), layout.size());
+    }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        if new_size % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() {
+            return None;
+        }
+
+        let size = layout.size();
+
+        //  Shrink in place, by unmapping the tail.
+        if new_size <= size {
+            if new_size < size {
+                munmap_deallocate(pointer.as_ptr().add(new_size), size - new_size);
+            }
+
+            return Some(pointer);
+        }
+
+        //  Grow in place, if the adjacent address space is free.
+        if let Some(result) = syscall::mremap(pointer, size, new_size, 0, ptr::null_mut()) {
+            debug_assert!(result == pointer);
+
+            let tail = NonNull::new_unchecked(result.as_ptr().add(size));
+            PREFAULT.prefault(self, tail, new_size - size, os_page_size().value());
+            PINNING.pin(self, tail, new_size - size);
+
+            return Some(result);
+        }
+
+        //  Otherwise, move the pages onto a fresh, suitably aligned, range of the address space.
+        let target = mmap_over(new_size)?;
+
+        match syscall::mremap(pointer, size, new_size, MREMAP_MAYMOVE | MREMAP_FIXED, target.as_ptr()) {
+            Some(result) => {
+                debug_assert!(result == target);
+
+                let tail = NonNull::new_unchecked(result.as_ptr().add(size));
+                PREFAULT.prefault(self, tail, new_size - size, os_page_size().value());
+                PINNING.pin(self, tail, new_size - size);
+
+                Some(result)
+            },
+            //  Notably, HugeTLB mappings cannot be grown by `mremap`.
+            None => {
+                munmap_deallocate(target.as_ptr(), new_size);
+                None
+            },
+        }
+    }
+
+    unsafe fn decommit(&self, pointer: NonNull<u8>, size: usize) {
+        if !DECOMMIT.is_enabled(self) {
+            return;
+        }
+
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        //
+        //  Failures are benign, the memory remaining committed; notably, HugeTLB pages cannot be partially released.
+        syscall::madvise(pointer.as_ptr(), size, MADV_DONTNEED);
+    }
+
+    unsafe fn purge_lazy(&self, pointer: NonNull<u8>, size: usize) {
+        if !DECAY.is_enabled(self) {
+            return;
+        }
+
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        //
+        //  Failures are benign, the memory remaining committed until forcibly purged; notably, `MADV_FREE` requires
+        //  Linux 4.5, and does not apply to HugeTLB pages.
+        syscall::madvise(pointer.as_ptr(), size, MADV_FREE);
+    }
+
+    unsafe fn purge_forced(&self, pointer: NonNull<u8>, size: usize) {
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        syscall::madvise(pointer.as_ptr(), size, MADV_DONTNEED);
+    }
+}
+
+impl Platform for LLPlatform {
+    #[cold]
+    #[inline(never)]
+    fn current_node(&self) -> NumaNodeIndex {
+        //  Without NUMA, a single socket is shared by all threads.
+        if !CAPABILITIES.get().numa {
+            return NumaNodeIndex::new(0);
+        }
+
+        match syscall::getcpu() {
+            Some((_, node)) => NumaNodeIndex::new(node),
+            None => {
+                FALLBACKS.record(Fallback::UnknownNode);
+                NumaNodeIndex::new(0)
+            },
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node < numa_nodes() { Some(NumaNodeIndex::new(node)) } else { None }
+    }
+
+    //  The timestamps are only used for metrics, an unreadable clock merely makes them meaningless.
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 { syscall::clock_monotonic().unwrap_or(0) }
+
+    //  Without libnuma, nor formatting, the kernel view is reduced to the residency of the page, deemed local.
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        let page_size = os_page_size().value();
+        let resident = self.resident(page, size).unwrap_or(0);
+
+        HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: page_size,
+            resident,
+            anonymous_huge: 0,
+            local_pages: resident / page_size,
+            foreign_pages: 0,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> {
+        //  Number of pages looked up at once.
+        const BATCH: usize = 4096;
+
+        let page_size = os_page_size();
+        let mut vector = [0u8; BATCH];
+
+        let address = page_size.round_down(pointer.as_ptr() as usize);
+        let end = (pointer.as_ptr() as usize).checked_add(size)?;
+
+        let mut resident = 0;
+        let mut current = address;
+
+        while current < end {
+            let length = (end - current).min(BATCH * page_size.value());
+
+            if !syscall::mincore(current, length, page_size.value(), &mut vector) {
+                return None;
+            }
+
+            let pages = length.div_ceil(page_size.value());
+            resident += vector[..pages].iter().filter(|entry| **entry & 1 != 0).count() * page_size.value();
+
+            current += length;
+        }
+
+        Some(resident.min(size))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool { syscall::mlock(pointer.as_ptr(), size) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        let prot = if writable { PROT_READ | PROT_WRITE } else { PROT_READ };
+
+        syscall::mprotect(pointer.as_ptr(), size, prot)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        let page_size = os_page_size().value();
+        let size = size.max(1).checked_add(page_size - 1)? & !(page_size - 1);
+
+        let (writable, executable) = match mapping {
+            CodeMapping::Dual => mmap_dual(size)?,
+            CodeMapping::Flip => {
+                let pointer = mmap_allocate(size, 0).ok()?;
+                (pointer, pointer)
+            },
+        };
+
+        Some(CodeRegion::new(writable, executable, size, mapping))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) {
+        munmap_deallocate(region.writable().as_ptr(), region.size());
+
+        if region.mapping() == CodeMapping::Dual {
+            munmap_deallocate(region.executable().as_ptr(), region.size());
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        debug_assert!(region.mapping() == CodeMapping::Flip);
+
+        let prot = if executable { PROT_READ | PROT_EXEC } else { PROT_READ | PROT_WRITE };
+
+        syscall::mprotect(region.writable().as_ptr(), region.size(), prot)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> {
+        const ALIGNMENT: PowerOf2 = LLConfiguration::LARGE_PAGE_SIZE;
+
+        let guard_size = os_page_size().value();
+        let size = size.max(1).checked_add(ALIGNMENT.value() - 1)? & !(ALIGNMENT.value() - 1);
+
+        //  Over-allocate, so that the usable area may be aligned, with the guard page immediately below.
+        let over_size = size.checked_add(ALIGNMENT.value())?.checked_add(guard_size)?;
+        let front_pointer = mmap_allocate(over_size, MAP_STACK).ok()?;
+
+        let start = front_pointer.as_ptr() as usize;
+        let bottom = ALIGNMENT.round_up(start + guard_size);
+
+        let front_size = bottom - guard_size - start;
+        let back_size = over_size - front_size - guard_size - size;
+
+        if front_size > 0 {
+            //  Safety:
+            //  -   `[start, start + front_size)` is within the mapped area, and not in use.
+            unsafe { munmap_deallocate(front_pointer.as_ptr(), front_size) };
+        }
+
+        if back_size > 0 {
+            //  Safety:
+            //  -   `[bottom + size, bottom + size + back_size)` is within the mapped area, and not in use.
+            unsafe { munmap_deallocate((bottom + size) as *mut u8, back_size) };
+        }
+
+        let guard = (bottom - guard_size) as *mut u8;
+
+        //  Safety:
+        //  -   `[guard, guard + guard_size)` is within the mapped area, and not in use.
+        if !unsafe { syscall::mprotect(guard, guard_size, PROT_NONE) } {
+            //  Safety:
+            //  -   `[guard, guard + guard_size + size)` is the remainder of the mapped area, and not in use.
+            unsafe { munmap_deallocate(guard, guard_size + size) };
+            return None;
+        }
+
+        if CAPABILITIES.get().transparent_huge_pages {
+            //  Safety:
+            //  -   `[bottom, bottom + size)` is within the mapped area.
+            //  -   The advice is merely a hint, hence its failure is inconsequential.
+            unsafe { syscall::madvise(bottom as *mut u8, size, MADV_HUGEPAGE) };
+        }
+
+        if prefault {
+            for offset in (0..size).step_by(guard_size) {
+                //  Safety:
+                //  -   `bottom + offset` is within the usable area, writable and not in use.
+                unsafe { ptr::write_volatile((bottom + offset) as *mut u8, 0) };
+            }
+        }
+
+        NonNull::new(guard).map(|guard| ThreadStack::new(guard, guard_size, size))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, size) = stack.mapping();
+
+        munmap_deallocate(pointer.as_ptr(), size);
+    }
+
+    //  The physical addresses are only exposed through `/proc/self/pagemap`, which is not read without libc.
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, _size: usize) -> Option<PhysicalBuffer> { None }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, _buffer: PhysicalBuffer) {}
+
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool { environment_flag(name) }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES.get() }
+
+    #[cold]
+    #[inline(never)]
+    fn use_transparent_huge_pages(&self) -> bool {
+        CAPABILITIES.downgrade(Detector::HUGE_TLB | Detector::HUGE_TLB_2MB);
+        true
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn huge_tlb_pools(&self) -> HugeTlbPools { CAPABILITIES.pools() }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities {
+        let mut host = HostCapabilities::default();
+        host.capabilities = CAPABILITIES.get();
+
+        host.numa_nodes = numa_nodes();
+        host.os_page_size = os_page_size().value();
+
+        host
+    }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> {
+        match MAPPING_LATENCY.load(atomic::Ordering::Relaxed) {
+            0 => None,
+            latency => Some(Duration::from_nanos(latency - 1)),
+        }
+    }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    //  Without libc, there is no system allocator to delegate to.
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, _layout: Layout) -> Option<NonNull<u8>> { None }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, _pointer: NonNull<u8>) {}
+
+    //  All the memory is therefore llmalloc's.
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, _pointer: NonNull<u8>) -> bool { true }
+}
+
+/// Implementation of the ThreadLocal trait, over a `#[thread_local]` static.
+///
+/// The static being unique, so is the instance, which holds the thread-local state of the allocator.
+pub(crate) struct LLThreadLocal<T>(PhantomData<*const T>);
+
+impl<T> LLThreadLocal<T> {
+    /// Creates an instance.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that no other instance exists. The destructor is never invoked, there being no exit hook per thread.
+    pub(crate) const unsafe fn new(_destructor: *const u8) -> Self { Self(PhantomData) }
+}
+
+impl<T> ThreadLocal<T> for LLThreadLocal<T> {
+    //  The storage is set up with the thread, hence there is nothing to prepare.
+    #[inline(always)]
+    fn prepare(&self) -> bool { false }
+
+    #[inline(always)]
+    fn get(&self) -> Option<NonNull<T>> { NonNull::new(SLOT.get() as *mut T) }
+
+    #[inline(always)]
+    fn set(&self, value: NonNull<T>) -> bool {
+        SLOT.set(value.as_ptr() as *mut u8);
+        true
+    }
+
+    #[cold]
+    fn clear(&self) -> bool {
+        SLOT.set(ptr::null_mut());
+        true
+    }
+}
+
+unsafe impl<T> Sync for LLThreadLocal<T> {}
+
+//
+//  Implementation Details
+//
+
+const ENVIRON: &[u8] = b"/proc/self/environ\0";
+const AUXV: &[u8] = b"/proc/self/auxv\0";
+const NODES_POSSIBLE: &[u8] = b"/sys/devices/system/node/possible\0";
+const SYS: &[u8] = b"/sys/kernel\0";
+const TRANSPARENT_HUGE_PAGES_ENABLED: &[u8] = b"/sys/kernel/mm/transparent_hugepage/enabled\0";
+
+//  The directories of the pools, without NUL-terminator, as the name of the file read is appended.
+const POOL_2MB_DIRECTORY: &[u8] = b"/sys/kernel/mm/hugepages/hugepages-2048kB/";
+
+#[cfg(not(feature = "small-heap"))]
+const POOL_DIRECTORY: &[u8] = b"/sys/kernel/mm/hugepages/hugepages-1048576kB/";
+
+#[cfg(feature = "small-heap")]
+const POOL_DIRECTORY: &[u8] = POOL_2MB_DIRECTORY;
+
+const HUGE_PAGE_SIZE_2MB: usize = 2 * 1024 * 1024;
+
+//  The flag of `mmap` selecting 2 MB HugeTLB pages.
+const MAP_HUGE_2MB: usize = 21 << MAP_HUGE_SHIFT;
+
+//  The thread-local state of the allocator, for the current thread.
+#[thread_local]
+static SLOT: Cell<*mut u8> = Cell::new(ptr::null_mut());
+
+//  Capabilities of the environment.
+static CAPABILITIES: Detector = Detector::new();
+
+//  Metrics of the fallbacks.
+static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+//  Worst latency observed mapping memory, in nanoseconds: 0 if none was observed, otherwise 1 + latency.
+static MAPPING_LATENCY: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+//  Number of NUMA nodes, and size of the OS pages, read once: 0 if not yet read.
+static NUMA_NODES: AtomicUsize = AtomicUsize::new(0);
+static OS_PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+//  Capabilities of the environment, detected on first use from `/sys`, as on Linux.
+//
+//  HugeTLB, and its fallback on 2 MB HugeTLB pages, are additionally downgraded on the first failure to map a Huge Page
+//  with them, sparing the futile system calls of further attempts. The pools of HugeTLB pages are probed on detection,
+//  and only deemed available if they have pages left.
+struct Detector {
+    bits: AtomicU8,
+    //  The reserved, free, overcommit and surplus counts of each pool probed, written prior to publishing `bits`.
+    pools: [atomic::AtomicU64; 8],
+}
+
+impl Detector {
+    const DETECTED: u8 = 1;
+    const HUGE_TLB: u8 = 2;
+    const TRANSPARENT_HUGE_PAGES: u8 = 4;
+    const NUMA: u8 = 8;
+    const SYSFS: u8 = 16;
+    const HUGE_TLB_2MB: u8 = 32;
+    const POOL: u8 = 64;
+    const POOL_2MB: u8 = 128;
+
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+        Self { bits: AtomicU8::new(0), pools: [ZERO; 8] }
+    }
+
+    #[inline(always)]
+    fn get(&self) -> Capabilities {
+        let bits = match self.bits.load(atomic::Ordering::Relaxed) {
+            0 => self.resolve(),
+            bits => bits,
+        };
+
+        Capabilities {
+            huge_tlb: bits & Self::HUGE_TLB != 0,
+            huge_tlb_2mb: bits & Self::HUGE_TLB_2MB != 0,
+            transparent_huge_pages: bits & Self::TRANSPARENT_HUGE_PAGES != 0,
+            numa: bits & Self::NUMA != 0,
+            sysfs: bits & Self::SYSFS != 0,
+        }
+    }
+
+    #[cold]
+    fn pools(&self) -> HugeTlbPools {
+        let bits = match self.bits.load(atomic::Ordering::Acquire) {
+            0 => self.resolve(),
+            bits => bits,
+        };
+
+        let pool = |bit, index: usize, page_size| {
+            if bits & bit == 0 {
+                return None;
+            }
+
+            let [reserved, free, overcommit, surplus] =
+                [0, 1, 2, 3].map(|offset| self.pools[4 * index + offset].load(atomic::Ordering::Relaxed));
+
+            Some(HugeTlbPool { page_size, reserved, free, overcommit, surplus })
+        };
+
+        HugeTlbPools {
+            huge_pages: pool(Self::POOL, 0, LLConfiguration::HUGE_PAGE_SIZE.value()),
+            huge_pages_2mb: pool(Self::POOL_2MB, 1, HUGE_PAGE_SIZE_2MB),
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn downgrade(&self, bit: u8) {
+        if self.bits.load(atomic::Ordering::Relaxed) == 0 {
+            self.resolve();
+        }
+
+        self.bits.fetch_and(!bit, atomic::Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self) -> u8 {
+        let detected = self.detect();
+
+        //  A concurrent resolution, or downgrade, takes precedence; their pools were probed likewise.
+        match self.bits.compare_exchange(0, detected, atomic::Ordering::Release, atomic::Ordering::Acquire) {
+            Ok(_) => detected,
+            Err(current) => current,
+        }
+    }
+
+    fn detect(&self) -> u8 {
+        let mut bits = Self::DETECTED;
+
+        let sysfs = match syscall::open(SYS) {
+            Some(fd) => {
+                syscall::close(fd);
+                true
+            },
+            None => false,
+        };
+
+        if sysfs {
+            bits |= Self::SYSFS;
+        }
+
+        let forgone = environment_flag(TRANSPARENT_HUGE_PAGES_VARIABLE);
+
+        //  Without `/sys`, HugeTLB is detected by trial, unless forgone; with it, a pool without pages left is passed
+        //  over.
+        let mut available = |bit, index: usize, directory, page_size| {
+            if !sysfs {
+                return true;
+            }
+
+            match probe_pool(directory, page_size) {
+                Some(pool) => {
+                    let counts = [pool.reserved, pool.free, pool.overcommit, pool.surplus];
+
+                    for (count, value) in self.pools[4 * index..].iter().zip(counts) {
+                        count.store(value, atomic::Ordering::Relaxed);
+                    }
+
+                    bits |= bit;
+                    pool.available() > 0
+                },
+                None => false,
+            }
+        };
+
+        let huge_tlb = available(Self::POOL, 0, POOL_DIRECTORY, LLConfiguration::HUGE_PAGE_SIZE.value());
+
+        //  2 MB HugeTLB pages are only a fallback if the Huge Pages are larger.
+        let huge_tlb_2mb = LLConfiguration::HUGE_PAGE_SIZE.value() > HUGE_PAGE_SIZE_2MB &&
+            available(Self::POOL_2MB, 1, POOL_2MB_DIRECTORY, HUGE_PAGE_SIZE_2MB);
+
+        if huge_tlb && !forgone {
+            bits |= Self::HUGE_TLB;
+        }
+
+        if huge_tlb_2mb && !forgone {
+            bits |= Self::HUGE_TLB_2MB;
+        }
+
+        //  The mode in use is bracketed, as in `always [madvise] never`.
+        let mut buffer = [0u8; 64];
+        let enabled = read_file(TRANSPARENT_HUGE_PAGES_ENABLED, &mut buffer).unwrap_or(&[]);
+
+        if contains(enabled, b"[always]") || contains(enabled, b"[madvise]") {
+            bits |= Self::TRANSPARENT_HUGE_PAGES;
+        }
+
+        if numa_nodes() > 1 {
+            bits |= Self::NUMA;
+        }
+
+        bits
+    }
+}
+
+//  Attempts to allocate the required size in Huge Pages, unless HugeTLB is known to be unavailable.
+//
+//  A pool merely exhausted, as reported by `ENOMEM`, is not downgraded: the next mapping tries anew.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_huge(size: usize) -> Option<NonNull<u8>> {
+    //  The log2 of the page size, as expected by `MAP_HUGETLB`: 30 for 1 GB, 21 for 2 MB.
+    const MAP_HUGE_SIZE: usize = (LLConfiguration::HUGE_PAGE_SIZE.value().trailing_zeros() as usize) << MAP_HUGE_SHIFT;
+
+    if !CAPABILITIES.get().huge_tlb {
+        return None;
+    }
+
+    let result = match mmap_allocate(size, MAP_HUGETLB | MAP_HUGE_SIZE) {
+        Ok(pointer) => unsafe { mmap_check(pointer, size) },
+        Err(ENOMEM) => {
+            FALLBACKS.record(Fallback::HugeTlbExhaustion);
+            return None;
+        },
+        Err(_) => None,
+    };
+
+    match result {
+        Some(_) => FALLBACKS.record(Fallback::HugeTlbMapping),
+        None => {
+            FALLBACKS.record(Fallback::HugeTlbFailure);
+            CAPABILITIES.downgrade(Detector::HUGE_TLB);
+        },
+    }
+
+    result
+}
+
+//  Attempts to allocate the required size in 2 MB HugeTLB pages, unless they are known to be unavailable, as a fallback
+//  for HugeTLB pages of the Huge Page size.
+//
+//  The 2 MB HugeTLB pages are only aligned on 2 MB, hence a suitably aligned area is reserved first, then replaced. As
+//  for `mmap_huge`, a pool merely exhausted is not downgraded.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_huge_2mb(size: usize) -> Option<NonNull<u8>> {
+    if !CAPABILITIES.get().huge_tlb_2mb {
+        return None;
+    }
+
+    let reserved = mmap_over(size)?;
+
+    let flags = MAP_PRIVATE | MAP_FIXED | MAP_HUGETLB | MAP_HUGE_2MB;
+
+    //  Safety:
+    //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use, which `MAP_FIXED` replaces.
+    let result = unsafe { syscall::mmap(reserved.as_ptr(), size, PROT_READ | PROT_WRITE, flags, None) };
+
+    if let Err(error) = result {
+        //  Safety:
+        //  -   `reserved` points to a `mmap`ed area of `size` bytes, not in use.
+        unsafe { munmap_deallocate(reserved.as_ptr(), size) };
+
+        if error == ENOMEM {
+            FALLBACKS.record(Fallback::HugeTlbExhaustion);
+        } else {
+            FALLBACKS.record(Fallback::HugeTlbFailure);
+            CAPABILITIES.downgrade(Detector::HUGE_TLB_2MB);
+        }
+
+        return None;
+    }
+
+    debug_assert!(result == Ok(reserved));
+
+    FALLBACKS.record(Fallback::HugeTlb2MbMapping);
+
+    Some(reserved)
+}
+
+//  Attempts to allocate the required size in Normal (or Large) Pages, requesting Transparent Huge Pages if available.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_normal(size: usize) -> Option<NonNull<u8>> {
+    let result = mmap_allocate(size, 0).ok()
+        .and_then(|pointer| unsafe { mmap_check(pointer, size) })
+        .or_else(|| {
+            FALLBACKS.record(Fallback::MmapRetry);
+            mmap_over(size)
+        });
+
+    let result = match result {
+        Some(result) => result,
+        None => {
+            FALLBACKS.record(Fallback::MmapFailure);
+            return None;
+        },
+    };
+
+    if CAPABILITIES.get().transparent_huge_pages {
+        FALLBACKS.record(Fallback::TransparentHugePageMapping);
+
+        //  Safety:
+        //  -   `result` points to a `mmap`ed area of at least `size` bytes.
+        //  -   The advice is merely a hint, hence its failure is inconsequential.
+        unsafe { syscall::madvise(result.as_ptr(), size, MADV_HUGEPAGE) };
+    } else {
+        FALLBACKS.record(Fallback::NormalPageMapping);
+    }
+
+    Some(result)
+}
+
+//  Attempts to allocate the required size in Normal (or Large) Pages.
+//
+//  Ensures the alignment is met by over-allocating then trimming front and back.
+fn mmap_over(size: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    let over_size = size.checked_add(ALIGNMENT.value())?;
+    let front_pointer = mmap_allocate(over_size, 0).ok()?;
+
+    let start = front_pointer.as_ptr() as usize;
+    let aligned = ALIGNMENT.round_up(start);
+
+    let front_size = aligned - start;
+    let back_size = over_size - front_size - size;
+
+    if front_size > 0 {
+        //  Safety:
+        //  -   `[start, start + front_size)` is within the mapped area, and not in use.
+        unsafe { munmap_deallocate(front_pointer.as_ptr(), front_size) };
+    }
+
+    if back_size > 0 {
+        //  Safety:
+        //  -   `[aligned + size, aligned + size + back_size)` is within the mapped area, and not in use.
+        unsafe { munmap_deallocate((aligned + size) as *mut u8, back_size) };
+    }
+
+    NonNull::new(aligned as *mut u8)
+}
+
+//  `mmap` alignment checker.
+//
+//  Returns a non-null pointer if suitably aligned, and None otherwise, in which case the memory has been unmapped.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to a `mmap`ed area of at least `size` bytes.
+//  -   Assumes that `pointer` is no longer in use, unless returned.
+unsafe fn mmap_check(pointer: NonNull<u8>, size: usize) -> Option<NonNull<u8>> {
+    if pointer.as_ptr() as usize % LLConfiguration::HUGE_PAGE_SIZE == 0 {
+        Some(pointer)
+    } else {
+        munmap_deallocate(pointer.as_ptr(), size);
+        None
+    }
+}
+
+//  Maps `size` bytes of anonymous, read-write, memory, or returns the error number; does not guarantee any alignment.
+fn mmap_allocate(size: usize, extra_flags: usize) -> Result<NonNull<u8>, usize> {
+    //  Safety:
+    //  -   No specific address is requested.
+    unsafe { syscall::mmap(ptr::null_mut(), size, PROT_READ | PROT_WRITE, MAP_PRIVATE | extra_flags, None) }
+}
+
+//  Maps `size` bytes of shared memory twice, read-write then read-execute, returning both views.
+//
+//  The memory is backed by an anonymous file, closed once mapped, the mappings keeping it alive.
+fn mmap_dual(size: usize) -> Option<(NonNull<u8>, NonNull<u8>)> {
+    const NAME: &[u8] = b"llmalloc-code\0";
+
+    let fd = syscall::memfd(NAME, size)?;
+
+    //  Safety:
+    //  -   `fd` is a valid file descriptor, of `size` bytes, and no specific address is requested.
+    let map = |prot| unsafe { syscall::mmap(ptr::null_mut(), size, prot, MAP_SHARED, Some(fd)).ok() };
+
+    let result = map(PROT_READ | PROT_WRITE).and_then(|writable| {
+        match map(PROT_READ | PROT_EXEC) {
+            Some(executable) => Some((writable, executable)),
+            None => {
+                //  Safety:
+                //  -   `writable` points to a `mmap`ed area of `size` bytes, not yet in use.
+                unsafe { munmap_deallocate(writable.as_ptr(), size) };
+                None
+            },
+        }
+    });
+
+    syscall::close(fd);
+
+    result
+}
+
+//  Wrapper around `munmap`.
+//
+//  #   Safety
+//
+//  -   Assumes that `address` points to a `mmap`ed area of at least `size` bytes.
+//  -   Assumes that the range `[address, address + size)` is no longer in use.
+unsafe fn munmap_deallocate(address: *mut u8, size: usize) {
+    if let Err(error) = syscall::munmap(address, size) {
+        let failure = UnmapFailure { address: address as usize, size, error: error as i32 };
+
+        //  Should the memory fail to be unmapped, it is leaked, unless the process is aborted.
+        UNMAPPING.fail(&FALLBACKS, failure, syscall::abort);
+    }
+}
+
+//  Returns the size of the OS pages, as reported by the auxiliary vector.
+fn os_page_size() -> PowerOf2 {
+    const DEFAULT: PowerOf2 = unsafe { PowerOf2::new_unchecked(4096) };
+
+    //  The entries of the auxiliary vector are pairs of words, the type then the value.
+    const AT_PAGESZ: usize = 6;
+    const WORD: usize = core::mem::size_of::<usize>();
+
+    let size = cached(&OS_PAGE_SIZE, || {
+        let mut buffer = [0u8; 1024];
+        let auxv = read_file(AUXV, &mut buffer).unwrap_or(&[]);
+
+        let word = |bytes: &[u8]| bytes.iter().rev().fold(0usize, |word, byte| (word << 8) | *byte as usize);
+
+        auxv.chunks_exact(2 * WORD)
+            .find(|entry| word(&entry[..WORD]) == AT_PAGESZ)
+            .map(|entry| word(&entry[WORD..]))
+            .unwrap_or(0)
+    });
+
+    PowerOf2::new(size).unwrap_or(DEFAULT)
+}
+
+//  Returns the number of NUMA nodes, the highest possible node plus one, as in `0-3`.
+fn numa_nodes() -> u32 {
+    let nodes = cached(&NUMA_NODES, || {
+        let mut buffer = [0u8; 64];
+        let possible = read_file(NODES_POSSIBLE, &mut buffer).unwrap_or(&[]);
+
+        let last = possible.rsplit(|byte| *byte == b'-' || *byte == b',').next().unwrap_or(&[]);
+
+        read_decimal(last).map(|highest| highest as usize + 1).unwrap_or(1)
+    });
+
+    nodes.max(1) as u32
+}
+
+//  Returns the value of `cache`, computing it with `f` if not yet computed; 0 is never cached.
+fn cached<F>(cache: &AtomicUsize, f: F) -> usize
+    where
+        F: FnOnce() -> usize,
+{
+    match cache.load(atomic::Ordering::Relaxed) {
+        0 => {
+            let value = f();
+            cache.store(value, atomic::Ordering::Relaxed);
+            value
+        },
+        value => value,
+    }
+}
+
+//  Returns whether the environment variable `name`, NUL-terminated, is set to a value other than an empty string or
+//  `0`.
+//
+//  Without libc, there is no `environ`, hence the environment is read from `/proc/self/environ`, as it was on
+//  start-up.
+fn environment_flag(name: &[u8]) -> bool {
+    debug_assert_eq!(Some(&0), name.last());
+
+    let name = &name[..name.len().saturating_sub(1)];
+
+    let mut finder = VariableFinder::new(name);
+
+    for_each_chunk(ENVIRON, |chunk| finder.feed(chunk));
+
+    finder.flag()
+}
+
+//  Reads the file located at `path`, NUL-terminated, into `buffer`, and returns the part of it read.
+//
+//  Only the start of the file is read if it does not fit.
+fn read_file<'a>(path: &[u8], buffer: &'a mut [u8]) -> Option<&'a [u8]> {
+    let mut length = 0;
+
+    for_each_chunk(path, |chunk| {
+        let copied = chunk.len().min(buffer.len() - length);
+
+        buffer[length..length + copied].copy_from_slice(&chunk[..copied]);
+        length += copied;
+
+        length < buffer.len()
+    })?;
+
+    Some(&buffer[..length])
+}
+
+//  Invokes `f` with each chunk of the file located at `path`, NUL-terminated, until it returns false.
+//
+//  Returns None if the file cannot be opened.
+fn for_each_chunk<F>(path: &[u8], mut f: F) -> Option<()>
+    where
+        F: FnMut(&[u8]) -> bool,
+{
+    let mut chunk = [0u8; 512];
+
+    let fd = syscall::open(path)?;
+
+    while let Some(length) = syscall::read(fd, &mut chunk) {
+        if length == 0 || !f(&chunk[..length]) {
+            break;
+        }
+    }
+
+    syscall::close(fd);
+
+    Some(())
+}
+
+//  Reads the number on the first line of the file located at `path`, NUL-terminated.
+fn read_number(path: &[u8]) -> Option<u64> {
+    let mut buffer = [0u8; 32];
+
+    read_file(path, &mut buffer).and_then(read_decimal)
+}
+
+//  Probes the pool of HugeTLB pages of `page_size` bytes located at `directory`, or returns None if there is none.
+fn probe_pool(directory: &[u8], page_size: usize) -> Option<HugeTlbPool> {
+    //  The path is NUL-terminated by the trailing zeroes of the buffer.
+    let read = |file: &[u8]| {
+        let mut path = [0u8; 96];
+
+        let (prefix, suffix) = path.get_mut(..directory.len() + file.len() + 1)?.split_at_mut(directory.len());
+        prefix.copy_from_slice(directory);
+        suffix[..file.len()].copy_from_slice(file);
+
+        read_number(&path[..=directory.len() + file.len()])
+    };
+
+    Some(HugeTlbPool {
+        page_size,
+        reserved: read(b"nr_hugepages")?,
+        free: read(b"free_hugepages").unwrap_or(0),
+        overcommit: read(b"nr_overcommit_hugepages").unwrap_or(0),
+        surplus: read(b"surplus_hugepages").unwrap_or(0),
+    })
+}
+
+//  Parses the decimal number at the start of `bytes`, ignoring leading whitespace.
+fn read_decimal(bytes: &[u8]) -> Option<u64> {
+    let blanks = bytes.iter().take_while(|byte| byte.is_ascii_whitespace()).count();
+    let bytes = &bytes[blanks..];
+
+    let length = bytes.iter().take_while(|byte| byte.is_ascii_digit()).count();
+
+    if length == 0 {
+        return None;
+    }
+
+    bytes[..length].iter().try_fold(0u64, |number, digit| number.checked_mul(10)?.checked_add((digit - b'0') as u64))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool { haystack.windows(needle.len()).any(|window| window == needle) }
+
+//  Finds a variable within the NUL-separated `NAME=value` entries of the environment, fed chunk by chunk.
+//
+//  Only the start of the value is retained, which suffices to tell whether the variable is set as a flag.
+struct VariableFinder<'a> {
+    name: &'a [u8],
+    //  Position within the current entry.
+    position: usize,
+    //  Whether the current entry matches so far.
+    matching: bool,
+    //  The variable, once found: the length, and the start, of its value.
+    found: Option<(usize, u8)>,
+    //  The value of the current entry, if matching.
+    value: (usize, u8),
+}
+
+impl<'a> VariableFinder<'a> {
+    fn new(name: &'a [u8]) -> Self { Self { name, position: 0, matching: true, found: None, value: (0, 0) } }
+
+    //  Feeds a chunk of the environment, returns whether the search continues.
+    fn feed(&mut self, chunk: &[u8]) -> bool {
+        for byte in chunk {
+            if *byte == 0 {
+                if self.matching && self.position > self.name.len() {
+                    self.found = Some(self.value);
+                    return false;
+                }
+
+                *self = Self::new(self.name);
+                continue;
+            }
+
+            if self.matching {
+                match self.position.cmp(&self.name.len()) {
+                    core::cmp::Ordering::Less => self.matching = *byte == self.name[self.position],
+                    core::cmp::Ordering::Equal => self.matching = *byte == b'=',
+                    core::cmp::Ordering::Greater => {
+                        if self.value.0 == 0 {
+                            self.value.1 = *byte;
+                        }
+
+                        self.value.0 += 1;
+                    },
+                }
+            }
+
+            self.position += 1;
+        }
+
+        true
+    }
+
+    //  Returns whether the variable is set, to neither an empty value, nor "0".
+    fn flag(&self) -> bool { !matches!(self.found, None | Some((0, _)) | Some((1, b'0'))) }
+}
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use super::*;
+
+#[test]
+fn variable_finder_flag() {
+    let flag = |chunks: &[&[u8]]| {
+        let mut finder = VariableFinder::new(b"LLMALLOC_FLAG");
+
+        for chunk in chunks {
+            if !finder.feed(chunk) {
+                break;
+            }
+        }
+
+        finder.flag()
+    };
+
+    assert!(flag(&[b"HOME=/root\0LLMALLOC_FLAG=1\0"]));
+    assert!(flag(&[b"HOME=/root\0LLMALLOC_", b"FLAG=", b"yes\0"]));
+    assert!(flag(&[b"LLMALLOC_FLAG=00\0"]));
+
+    assert!(!flag(&[b"LLMALLOC_FLAG=0\0"]));
+    assert!(!flag(&[b"LLMALLOC_FLAG=\0"]));
+    assert!(!flag(&[b"LLMALLOC_FLAGS=1\0XLLMALLOC_FLAG=1\0"]));
+    assert!(!flag(&[b"LLMALLOC_FLAG=1"]));
+}
+
+#[test]
+fn detector_forgo_huge_tlb() {
+    let detector = Detector::new();
+
+    let detected = detector.get();
+
+    detector.downgrade(Detector::HUGE_TLB | Detector::HUGE_TLB_2MB);
+
+    assert_eq!(Capabilities { huge_tlb: false, huge_tlb_2mb: false, ..detected }, detector.get());
+}
+
+#[test]
+fn detector_pools() {
+    let detector = Detector::new();
+
+    let pools = detector.pools();
+    let capabilities = detector.get();
+
+    //  A pool without pages left is passed over.
+    if let Some(pool) = pools.huge_pages {
+        assert!(pool.available() > 0 || !capabilities.huge_tlb, "{:?} {:?}", pools, capabilities);
+    }
+
+    if let Some(pool) = pools.huge_pages_2mb {
+        assert!(pool.available() > 0 || !capabilities.huge_tlb_2mb, "{:?} {:?}", pools, capabilities);
+    }
+
+    assert_eq!(None, probe_pool(b"/sys/kernel/mm/hugepages/hugepages-3kB/", 3 * 1024));
+}
+
+#[test]
+fn read_decimal_bytes() {
+    assert_eq!(Some(0), read_decimal(b"0"));
+    assert_eq!(Some(3), read_decimal(b"3\n"));
+    assert_eq!(Some(42), read_decimal(b" 42 kB"));
+    assert_eq!(None, read_decimal(b"never"));
+    assert_eq!(None, read_decimal(b"99999999999999999999999"));
+}
+
+#[test]
+fn thread_local_slot() {
+    //  Safety:
+    //  -   The test shares the slot with the allocator, and restores it.
+    let thread_local = unsafe { LLThreadLocal::<u8>::new(ptr::null()) };
+
+    let previous = SLOT.get();
+    let mut value = 0u8;
+
+    assert!(thread_local.set(NonNull::from(&mut value)));
+    assert_eq!(Some(NonNull::from(&mut value)), thread_local.get());
+
+    //  Each thread has its own slot.
+    std::thread::spawn(|| assert!(SLOT.get().is_null())).join().unwrap();
+
+    SLOT.set(previous);
+}
+
+#[test]
+fn os_page_size_auxv() {
+    assert!(os_page_size().value() >= 4096);
+}
+
+} // mod tests
@@ -0,0 +1,319 @@
+//! Raw Linux system calls, and the constants they take, without libc.
+//!
+//! The system calls return the negated error number on failure, in the range `[-4095, -1]`, which the wrappers
+//! translate into None, or false, unless the error number matters.
+
+use core::{arch::asm, ptr::NonNull};
+
+pub(super) const PROT_NONE: usize = 0;
+pub(super) const PROT_READ: usize = 1;
+pub(super) const PROT_WRITE: usize = 2;
+pub(super) const PROT_EXEC: usize = 4;
+
+pub(super) const MAP_SHARED: usize = 0x01;
+pub(super) const MAP_PRIVATE: usize = 0x02;
+pub(super) const MAP_FIXED: usize = 0x10;
+pub(super) const MAP_ANONYMOUS: usize = 0x20;
+pub(super) const MAP_STACK: usize = 0x2_0000;
+pub(super) const MAP_HUGETLB: usize = 0x4_0000;
+
+//  The flag of `mmap` selecting HugeTLB pages of a given size, the log2 of their size shifted by `MAP_HUGE_SHIFT`.
+pub(super) const MAP_HUGE_SHIFT: u32 = 26;
+
+pub(super) const MADV_DONTNEED: usize = 4;
+pub(super) const MADV_FREE: usize = 8;
+pub(super) const MADV_HUGEPAGE: usize = 14;
+
+pub(super) const ENOMEM: usize = 12;
+
+pub(super) const MREMAP_MAYMOVE: usize = 1;
+pub(super) const MREMAP_FIXED: usize = 2;
+
+/// Wrapper around `mmap`, of anonymous memory unless `fd` is provided.
+///
+/// Returns a pointer to `size` bytes of memory, or the error number; does not guarantee any alignment.
+///
+/// #   Safety
+///
+/// -   Assumes that `address`, if `flags` include `MAP_FIXED`, points to a `mmap`ed area of at least `size` bytes no
+///     longer in use.
+pub(super) unsafe fn mmap(address: *mut u8, size: usize, prot: usize, flags: usize, fd: Option<usize>)
+    -> Result<NonNull<u8>, usize>
+{
+    let (flags, fd) = match fd {
+        Some(fd) => (flags, fd),
+        None => (flags | MAP_ANONYMOUS, usize::MAX),
+    };
+
+    let result = syscall6(SYS_MMAP, address as usize, size, prot, flags, fd, 0);
+
+    if is_error(result) {
+        return Err(result.wrapping_neg());
+    }
+
+    NonNull::new(result as *mut u8).ok_or(ENOMEM)
+}
+
+/// Wrapper around `munmap`.
+///
+/// Returns the error number on failure.
+///
+/// #   Safety
+///
+/// -   Assumes that `[address, address + size)` is a `mmap`ed area, no longer in use.
+pub(super) unsafe fn munmap(address: *mut u8, size: usize) -> Result<(), usize> {
+    let result = syscall6(SYS_MUNMAP, address as usize, size, 0, 0, 0, 0);
+
+    if is_error(result) { Err(result.wrapping_neg()) } else { Ok(()) }
+}
+
+/// Wrapper around `mremap`.
+///
+/// Returns the resized area on success, and None otherwise, in which case the area is left untouched.
+///
+/// #   Safety
+///
+/// -   Assumes that `address` points to a `mmap`ed area of at least `size` bytes.
+/// -   Assumes that `target`, if `flags` include `MREMAP_FIXED`, points to a `mmap`ed area of at least `new_size`
+///     bytes no longer in use.
+pub(super) unsafe fn mremap(address: NonNull<u8>, size: usize, new_size: usize, flags: usize, target: *mut u8)
+    -> Option<NonNull<u8>>
+{
+    let result = syscall6(SYS_MREMAP, address.as_ptr() as usize, size, new_size, flags, target as usize, 0);
+
+    if is_error(result) { None } else { NonNull::new(result as *mut u8) }
+}
+
+/// Wrapper around `mprotect`.
+///
+/// #   Safety
+///
+/// -   Assumes that the memory of `[address, address + size)` is not accessed in a manner incompatible with `prot`.
+pub(super) unsafe fn mprotect(address: *mut u8, size: usize, prot: usize) -> bool {
+    !is_error(syscall6(SYS_MPROTECT, address as usize, size, prot, 0, 0, 0))
+}
+
+/// Wrapper around `madvise`.
+///
+/// #   Safety
+///
+/// -   Assumes that `advice` does not alter the content of `[address, address + size)`, unless no longer in use.
+pub(super) unsafe fn madvise(address: *mut u8, size: usize, advice: usize) -> bool {
+    !is_error(syscall6(SYS_MADVISE, address as usize, size, advice, 0, 0, 0))
+}
+
+/// Wrapper around `mincore`, filling one byte of `vector` per OS page of `[address, address + size)`.
+///
+/// Returns false if `vector` is too short.
+pub(super) fn mincore(address: usize, size: usize, page_size: usize, vector: &mut [u8]) -> bool {
+    if size.div_ceil(page_size) > vector.len() {
+        return false;
+    }
+
+    //  Safety:
+    //  -   `vector` holds one byte per page of the `size` bytes.
+    !is_error(unsafe { syscall6(SYS_MINCORE, address, size, vector.as_mut_ptr() as usize, 0, 0, 0) })
+}
+
+/// Wrapper around `mlock`.
+pub(super) fn mlock(address: *const u8, size: usize) -> bool {
+    //  Safety:
+    //  -   `mlock` does not access the memory it locks, it only faults it in.
+    !is_error(unsafe { syscall6(SYS_MLOCK, address as usize, size, 0, 0, 0, 0) })
+}
+
+/// Wrapper around `getcpu`, returning the indexes of the CPU, and of the NUMA node, the current thread runs on.
+pub(super) fn getcpu() -> Option<(u32, u32)> {
+    let (mut cpu, mut node) = (0u32, 0u32);
+
+    //  Safety:
+    //  -   `cpu` and `node` are valid for writes, the cache being unused.
+    let result = unsafe {
+        syscall6(SYS_GETCPU, &mut cpu as *mut u32 as usize, &mut node as *mut u32 as usize, 0, 0, 0, 0)
+    };
+
+    if is_error(result) { None } else { Some((cpu, node)) }
+}
+
+/// Wrapper around `clock_gettime(CLOCK_MONOTONIC)`, returning a timestamp in nanoseconds.
+///
+/// Without libc, there is no vDSO to spare the system call.
+pub(super) fn clock_monotonic() -> Option<u64> {
+    const CLOCK_MONOTONIC: usize = 1;
+
+    let mut timespec = [0i64; 2];
+
+    //  Safety:
+    //  -   `timespec` is valid for writes, and laid out as a `struct timespec` on 64-bit targets.
+    let result = unsafe { syscall6(SYS_CLOCK_GETTIME, CLOCK_MONOTONIC, timespec.as_mut_ptr() as usize, 0, 0, 0, 0) };
+
+    if is_error(result) {
+        return None;
+    }
+
+    Some((timespec[0] as u64) * 1_000_000_000 + (timespec[1] as u64))
+}
+
+/// Wrapper around `openat`, opening the file located at `path`, NUL-terminated, read-only.
+pub(super) fn open(path: &[u8]) -> Option<usize> {
+    const AT_FDCWD: isize = -100;
+    const O_CLOEXEC: usize = 0o2000000;
+
+    debug_assert!(path.last() == Some(&0));
+
+    //  Safety:
+    //  -   `path` is NUL-terminated.
+    let result = unsafe { syscall6(SYS_OPENAT, AT_FDCWD as usize, path.as_ptr() as usize, O_CLOEXEC, 0, 0, 0) };
+
+    if is_error(result) { None } else { Some(result) }
+}
+
+/// Wrapper around `read`, returning the number of bytes read, 0 at the end of the file.
+pub(super) fn read(fd: usize, buffer: &mut [u8]) -> Option<usize> {
+    //  Safety:
+    //  -   `buffer` is valid for writes of its length.
+    let result = unsafe { syscall6(SYS_READ, fd, buffer.as_mut_ptr() as usize, buffer.len(), 0, 0, 0) };
+
+    if is_error(result) { None } else { Some(result) }
+}
+
+/// Wrapper around `close`.
+pub(super) fn close(fd: usize) {
+    //  Safety:
+    //  -   `close` has no precondition, closing an invalid descriptor merely fails.
+    unsafe { syscall6(SYS_CLOSE, fd, 0, 0, 0, 0, 0) };
+}
+
+/// Wrapper around `memfd_create`, creating an anonymous file of `size` bytes, named `name`, NUL-terminated.
+pub(super) fn memfd(name: &[u8], size: usize) -> Option<usize> {
+    const MFD_CLOEXEC: usize = 1;
+
+    debug_assert!(name.last() == Some(&0));
+
+    //  Safety:
+    //  -   `name` is NUL-terminated.
+    let fd = unsafe { syscall6(SYS_MEMFD_CREATE, name.as_ptr() as usize, MFD_CLOEXEC, 0, 0, 0, 0) };
+
+    if is_error(fd) {
+        return None;
+    }
+
+    //  Safety:
+    //  -   `fd` is a valid file descriptor.
+    if is_error(unsafe { syscall6(SYS_FTRUNCATE, fd, size, 0, 0, 0, 0) }) {
+        close(fd);
+        return None;
+    }
+
+    Some(fd)
+}
+
+/// Aborts the process, as `abort` would: raising `SIGABRT`, then exiting should the signal be handled.
+pub(super) fn abort() -> ! {
+    const SIGABRT: usize = 6;
+
+    //  Safety:
+    //  -   `getpid`, `kill`, and `exit_group` have no precondition.
+    unsafe {
+        let pid = syscall6(SYS_GETPID, 0, 0, 0, 0, 0, 0);
+
+        syscall6(SYS_KILL, pid, SIGABRT, 0, 0, 0, 0);
+        syscall6(SYS_EXIT_GROUP, 128 + SIGABRT, 0, 0, 0, 0, 0);
+    }
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+//
+//  Implementation Details
+//
+
+#[cfg(target_arch = "x86_64")]
+mod numbers {
+    pub(super) const SYS_READ: usize = 0;
+    pub(super) const SYS_CLOSE: usize = 3;
+    pub(super) const SYS_MMAP: usize = 9;
+    pub(super) const SYS_MPROTECT: usize = 10;
+    pub(super) const SYS_MUNMAP: usize = 11;
+    pub(super) const SYS_MREMAP: usize = 25;
+    pub(super) const SYS_MINCORE: usize = 27;
+    pub(super) const SYS_MADVISE: usize = 28;
+    pub(super) const SYS_GETPID: usize = 39;
+    pub(super) const SYS_KILL: usize = 62;
+    pub(super) const SYS_FTRUNCATE: usize = 77;
+    pub(super) const SYS_MLOCK: usize = 149;
+    pub(super) const SYS_CLOCK_GETTIME: usize = 228;
+    pub(super) const SYS_EXIT_GROUP: usize = 231;
+    pub(super) const SYS_OPENAT: usize = 257;
+    pub(super) const SYS_GETCPU: usize = 309;
+    pub(super) const SYS_MEMFD_CREATE: usize = 319;
+}
+
+#[cfg(target_arch = "aarch64")]
+mod numbers {
+    pub(super) const SYS_FTRUNCATE: usize = 46;
+    pub(super) const SYS_OPENAT: usize = 56;
+    pub(super) const SYS_CLOSE: usize = 57;
+    pub(super) const SYS_READ: usize = 63;
+    pub(super) const SYS_EXIT_GROUP: usize = 94;
+    pub(super) const SYS_CLOCK_GETTIME: usize = 113;
+    pub(super) const SYS_KILL: usize = 129;
+    pub(super) const SYS_GETCPU: usize = 168;
+    pub(super) const SYS_GETPID: usize = 172;
+    pub(super) const SYS_MUNMAP: usize = 215;
+    pub(super) const SYS_MREMAP: usize = 216;
+    pub(super) const SYS_MMAP: usize = 222;
+    pub(super) const SYS_MPROTECT: usize = 226;
+    pub(super) const SYS_MLOCK: usize = 228;
+    pub(super) const SYS_MINCORE: usize = 232;
+    pub(super) const SYS_MADVISE: usize = 233;
+    pub(super) const SYS_MEMFD_CREATE: usize = 279;
+}
+
+use numbers::*;
+
+//  Returns whether the result of a system call is an error number.
+fn is_error(result: usize) -> bool { result > (-4096isize) as usize }
+
+//  Issues the system call `number`, with up to 6 arguments, the unused ones being ignored by the kernel.
+//
+//  #   Safety
+//
+//  -   Assumes that the arguments are valid for the system call.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn syscall6(number: usize, a: usize, b: usize, c: usize, d: usize, e: usize, f: usize) -> usize {
+    let result;
+
+    //  Safety:
+    //  -   `syscall` clobbers `rcx` and `r11`, and nothing else but its result.
+    asm!(
+        "syscall",
+        inlateout("rax") number => result,
+        in("rdi") a, in("rsi") b, in("rdx") c, in("r10") d, in("r8") e, in("r9") f,
+        lateout("rcx") _, lateout("r11") _,
+        options(nostack),
+    );
+
+    result
+}
+
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+unsafe fn syscall6(number: usize, a: usize, b: usize, c: usize, d: usize, e: usize, f: usize) -> usize {
+    let result;
+
+    //  Safety:
+    //  -   `svc 0` clobbers nothing but its result.
+    asm!(
+        "svc 0",
+        in("x8") number,
+        inlateout("x0") a => result,
+        in("x1") b, in("x2") c, in("x3") d, in("x4") e, in("x5") f,
+        options(nostack),
+    );
+
+    result
+}
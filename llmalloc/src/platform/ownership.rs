@@ -0,0 +1,160 @@
+//! Map of the memory owned by llmalloc, at the granularity of Huge Pages.
+//!
+//! With the `system-fallback` feature, the requests llmalloc cannot serve are delegated to the system allocator, and
+//! the pointers must then be routed back to their allocator on deallocation. Since all the memory of llmalloc is
+//! allocated from the platform as Huge Pages, one bit per Huge Page of the user-space address space suffices to tell
+//! whether a pointer belongs to llmalloc, or to the system allocator.
+//!
+//! On x86_64, the map reserves 16 KB with 1 GB Huge Pages, and 8 MB with the 2 MB Huge Pages of the `small-heap`
+//! feature, twice as much on aarch64, of which only the parts covering the addresses actually used by llmalloc are ever
+//! touched.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use llmalloc_core::Configuration;
+
+use super::LLConfiguration;
+
+/// Map of the memory owned by llmalloc.
+pub(crate) struct OwnershipMap([AtomicU64; WORDS]);
+
+impl OwnershipMap {
+    /// Creates an empty instance.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+
+        Self([ZERO; WORDS])
+    }
+
+    /// Marks the `size` bytes located at `address` as owned.
+    ///
+    /// Returns false, marking nothing, if the memory lies beyond the range covered by the map: such memory cannot be
+    /// told apart from that of the system allocator, and must be released rather than used.
+    pub(crate) fn mark(&self, address: usize, size: usize) -> bool {
+        if address.checked_add(size).is_none_or(|end| end as u64 > ADDRESS_LIMIT) {
+            return false;
+        }
+
+        self.for_each_bit(address, size, |word, bit| { word.fetch_or(bit, Ordering::Relaxed); });
+
+        true
+    }
+
+    /// Clears the ownership of the `size` bytes located at `address`.
+    pub(crate) fn clear(&self, address: usize, size: usize) {
+        self.for_each_bit(address, size, |word, bit| { word.fetch_and(!bit, Ordering::Relaxed); });
+    }
+
+    /// Returns whether `address` lies within owned memory.
+    #[inline(always)]
+    pub(crate) fn contains(&self, address: usize) -> bool {
+        let index = address >> HUGE_PAGE_SHIFT;
+
+        self.0.get(index / 64).is_some_and(|word| word.load(Ordering::Relaxed) & (1 << (index % 64)) != 0)
+    }
+
+    //  Invokes `f` with the word and bit of each Huge Page covering the `size` bytes located at `address`.
+    fn for_each_bit<F>(&self, address: usize, size: usize, mut f: F)
+        where
+            F: FnMut(&AtomicU64, u64),
+    {
+        let first = address >> HUGE_PAGE_SHIFT;
+        let last = (address + size).div_ceil(1 << HUGE_PAGE_SHIFT);
+
+        for index in first..last {
+            if let Some(word) = self.0.get(index / 64) {
+                f(word, 1 << (index % 64));
+            }
+        }
+    }
+}
+
+/// Map of the memory allocated by `LLPlatform`, to tell it apart from that of the system allocator.
+pub(super) static OWNERSHIP: OwnershipMap = OwnershipMap::new();
+
+//
+//  Implementation Details
+//
+
+//  Number of bits of the user-space address space, as handed out by `mmap` absent a hint above it.
+//
+//  Linux only maps memory beyond 47 bits on x86_64 and riscv64, and beyond 48 bits on aarch64, when explicitly hinted
+//  to, which llmalloc never does, even with 5-level page tables or 52-bit virtual addresses. Other targets are assumed
+//  to hand out their whole address space, up to 48 bits.
+//
+//  Memory mapped beyond is rejected by `mark`, and released by the platform rather than used.
+#[cfg(any(target_arch = "x86_64", target_arch = "riscv64"))]
+const ADDRESS_BITS: u32 = 47;
+
+#[cfg(target_arch = "aarch64")]
+const ADDRESS_BITS: u32 = 48;
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "riscv64", target_arch = "aarch64")))]
+const ADDRESS_BITS: u32 = if usize::BITS < 48 { usize::BITS } else { 48 };
+
+//  End of the range covered by the map, which may not be representable as `usize` on 32-bit targets.
+const ADDRESS_LIMIT: u64 = 1 << ADDRESS_BITS;
+
+const HUGE_PAGE_SHIFT: u32 = LLConfiguration::HUGE_PAGE_SIZE.value().trailing_zeros();
+
+const WORDS: usize = (1usize << (ADDRESS_BITS - HUGE_PAGE_SHIFT)).div_ceil(64);
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn ownership_map_mark_clear() {
+    const HUGE_PAGE_SIZE: usize = 1 << HUGE_PAGE_SHIFT;
+
+    static MAP: OwnershipMap = OwnershipMap::new();
+
+    let map = &MAP;
+
+    assert!(!map.contains(3 * HUGE_PAGE_SIZE));
+
+    assert!(map.mark(3 * HUGE_PAGE_SIZE, 2 * HUGE_PAGE_SIZE));
+
+    assert!(!map.contains(3 * HUGE_PAGE_SIZE - 1));
+    assert!(map.contains(3 * HUGE_PAGE_SIZE));
+    assert!(map.contains(5 * HUGE_PAGE_SIZE - 1));
+    assert!(!map.contains(5 * HUGE_PAGE_SIZE));
+
+    map.clear(3 * HUGE_PAGE_SIZE, HUGE_PAGE_SIZE);
+
+    assert!(!map.contains(3 * HUGE_PAGE_SIZE));
+    assert!(map.contains(4 * HUGE_PAGE_SIZE));
+
+    assert!(!map.mark(usize::MAX - HUGE_PAGE_SIZE, HUGE_PAGE_SIZE));
+    assert!(!map.contains(usize::MAX));
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn ownership_map_mark_beyond_address_space() {
+    const HUGE_PAGE_SIZE: usize = 1 << HUGE_PAGE_SHIFT;
+    const LIMIT: usize = ADDRESS_LIMIT as usize;
+
+    static MAP: OwnershipMap = OwnershipMap::new();
+
+    let map = &MAP;
+
+    //  The last Huge Page of the address space is covered.
+    assert!(map.mark(LIMIT - HUGE_PAGE_SIZE, HUGE_PAGE_SIZE));
+    assert!(map.contains(LIMIT - 1));
+
+    //  Memory straddling, or lying beyond, the end of the address space is rejected, and nothing is marked.
+    assert!(!map.mark(LIMIT - HUGE_PAGE_SIZE, 2 * HUGE_PAGE_SIZE));
+    assert!(!map.mark(LIMIT, HUGE_PAGE_SIZE));
+
+    assert!(!map.contains(LIMIT));
+    assert!(!map.contains(LIMIT + HUGE_PAGE_SIZE));
+
+    map.clear(LIMIT - HUGE_PAGE_SIZE, HUGE_PAGE_SIZE);
+
+    assert!(!map.contains(LIMIT - 1));
+}
+
+} // mod tests
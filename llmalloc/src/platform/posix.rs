@@ -0,0 +1,288 @@
+//! Implementation of generic POSIX calls, for the systems lacking a dedicated implementation.
+//!
+//! The implementation only relies on the calls available on any POSIX system, such as `mmap`, hence neither Huge
+//! Pages nor NUMA are available: the Huge Pages are backed by normal pages, and a single socket is shared by all
+//! threads. It is selected on the Unix systems without a dedicated implementation, or with the `posix` feature, for
+//! example on Linux systems lacking Huge Pages.
+
+use core::{
+    alloc::Layout,
+    mem,
+    ptr::{self, NonNull},
+    time::Duration,
+};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Fallback, HostCapabilities, HugePageReport,
+    PhysicalBuffer, PhysicalSegment, ThreadStack,
+};
+
+use crate::{decay::DECAY, decommit::DECOMMIT};
+
+use super::{mmap, shm, unix, NumaNodeIndex, Configuration, Platform};
+
+pub(crate) use super::unix::LLConfiguration;
+
+/// Implementation of the Platform trait, for POSIX.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> { mmap::allocate(self, layout, mmap_normal) }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) { mmap::deallocate(pointer, layout) }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        mmap::reallocate(self, pointer, layout, new_size, 0)
+    }
+
+    unsafe fn decommit(&self, pointer: NonNull<u8>, size: usize) {
+        if !DECOMMIT.is_enabled(self) {
+            return;
+        }
+
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        //
+        //  Failures are benign, the memory remaining committed; outside of Linux, the advice may be a mere hint.
+        libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_DONTNEED);
+    }
+
+    unsafe fn purge_lazy(&self, pointer: NonNull<u8>, size: usize) {
+        if !DECAY.is_enabled(self) {
+            return;
+        }
+
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        //
+        //  Failures are benign, the memory remaining committed until forcibly purged.
+        libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_FREE);
+    }
+
+    unsafe fn purge_forced(&self, pointer: NonNull<u8>, size: usize) {
+        //  Safety:
+        //  -   The memory is assumed to be no longer in use, hence its content may be discarded.
+        libc::madvise(pointer.as_ptr() as *mut libc::c_void, size, libc::MADV_DONTNEED);
+    }
+}
+
+impl Platform for LLPlatform {
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex { NumaNodeIndex::new(0) }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node == 0 { Some(NumaNodeIndex::new(0)) } else { None }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 { unix::now() }
+
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        let page_size = mmap::os_page_size().value();
+        let resident = self.resident(page, size).unwrap_or(0);
+
+        //  POSIX exposes neither the mappings nor the backing of the pages, and all pages reside on the single node.
+        HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: page_size,
+            resident,
+            anonymous_huge: 0,
+            local_pages: resident / page_size,
+            foreign_pages: 0,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> { mmap::resident(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool { mmap::lock(pointer, size) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        mmap::protect(pointer, size, writable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        mmap::map_code(size, mapping, |size| mmap::mmap_dual(shm::open_shared_memory()?, size))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) { mmap::unmap_code(region) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        mmap::protect_code(region, executable)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> { mmap::map_stack(size, prefault) }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, size) = stack.mapping();
+
+        mmap::munmap_deallocate(pointer.as_ptr(), size);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> {
+        const ALIGNMENT: usize = 2 * 1024 * 1024;
+
+        let size = size.max(1).checked_add(ALIGNMENT - 1)? & !(ALIGNMENT - 1);
+        let pointer = mmap::mmap_aligned(size, ALIGNMENT, 0)?;
+
+        //  Safety:
+        //  -   `pointer` points to a `mmap`ed area of `size` bytes, not in use.
+        unsafe { mmap::lock_physical(pointer, size, false) }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) {
+        mmap::munmap_deallocate(buffer.pointer().as_ptr(), buffer.size());
+    }
+
+    //  POSIX does not expose the physical addresses.
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool { unix::environment_flag(name) }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES }
+
+    #[cold]
+    #[inline(never)]
+    fn spawn_thread(&self, entry: fn()) -> bool {
+        extern "C" fn start(entry: *mut libc::c_void) -> *mut libc::c_void {
+            //  Safety:
+            //  -   `entry` was converted from a `fn()`, by `spawn_thread`.
+            let entry: fn() = unsafe { mem::transmute(entry) };
+
+            entry();
+
+            ptr::null_mut()
+        }
+
+        let mut thread = mem::MaybeUninit::<libc::pthread_t>::uninit();
+
+        let argument = entry as *mut libc::c_void;
+
+        //  Safety:
+        //  -   `thread` is valid for writes.
+        //  -   `start` is a start routine, expecting `entry` as argument.
+        let result = unsafe { libc::pthread_create(thread.as_mut_ptr(), ptr::null(), start, argument) };
+
+        if result != 0 {
+            return false;
+        }
+
+        //  Safety:
+        //  -   `thread` was initialized by `pthread_create`, on success.
+        unsafe { libc::pthread_detach(thread.assume_init()) };
+
+        true
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut request = libc::timespec {
+            tv_sec: duration.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+            tv_nsec: duration.subsec_nanos() as _,
+        };
+
+        //  Interrupted by a signal, the sleep resumes for the remaining duration.
+        loop {
+            let mut remaining = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+
+            //  Safety:
+            //  -   `request` and `remaining` are valid, for reads and writes respectively.
+            let result = unsafe { libc::nanosleep(&request as *const _, &mut remaining as *mut _) };
+
+            if result == 0 || (remaining.tv_sec == 0 && remaining.tv_nsec == 0) {
+                return;
+            }
+
+            request = remaining;
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn at_fork(&self, prepare: extern "C" fn(), child: extern "C" fn()) -> bool {
+        //  Safety:
+        //  -   `prepare` and `child` are handlers expecting no argument.
+        let result = unsafe { libc::pthread_atfork(Some(prepare), None, Some(child)) };
+
+        result == 0
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities {
+        unix::host_capabilities(CAPABILITIES, mmap::os_page_size().value(), mmap::mlock_limit())
+    }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { unix::mapping_latency() }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &unix::FALLBACKS }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> { unix::system_allocate(layout) }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) { unix::system_deallocate(pointer) }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { unix::owns(pointer) }
+}
+
+//  Capabilities of the environment, which are not detected: no `/sys` is expected, hence none is missed.
+const CAPABILITIES: Capabilities =
+    Capabilities { huge_tlb: false, huge_tlb_2mb: false, transparent_huge_pages: false, numa: false, sysfs: true };
+
+//  Attempts to allocate the required size in normal pages.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn mmap_normal(size: usize) -> Option<NonNull<u8>> {
+    let exact = |size| mmap::mmap_exact(size, LLConfiguration::HUGE_PAGE_SIZE);
+
+    mmap::mmap_huge_page(size, exact, Fallback::NormalPageMapping)
+}
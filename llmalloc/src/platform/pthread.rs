@@ -0,0 +1,191 @@
+//! Implementation of thread-local storage on top of pthread keys, shared by the POSIX platforms.
+//!
+//! With the `initial-exec` feature, the value is read from a `#[thread_local]` static instead, sparing the indirect
+//! call to `pthread_getspecific` on each allocation; the pthread key is then only kept to run the destructor on thread
+//! exit. Within an executable, the static is accessed with a single load off the thread pointer; within a shared
+//! library, it is so accessed only if built with `-Z tls-model=initial-exec`.
+
+#[cfg(feature = "initial-exec")]
+use core::cell::Cell;
+
+use core::{
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    sync::atomic,
+};
+
+use super::ThreadLocal;
+
+/// Implementation of the ThreadLocal trait, for the POSIX platforms.
+pub(crate) struct LLThreadLocal<T> {
+    key: atomic::AtomicI64,
+    destructor: *const u8,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> LLThreadLocal<T> {
+    const UNINITIALIZED: i64 = -1;
+    const UNDER_INITIALIZATION: i64 = -2;
+    const FAILED: i64 = -3;
+
+    /// Creates an uninitialized instance.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `destructor` points to an `unsafe extern "C" fn(*mut c_void)` function, or compatible.
+    /// -   Assumes that no other instance exists, with the `initial-exec` feature, the `#[thread_local]` static being
+    ///     unique.
+    pub(crate) const unsafe fn new(destructor: *const u8) -> Self {
+        let key = atomic::AtomicI64::new(-1);
+        let _marker = PhantomData;
+
+        LLThreadLocal { key, destructor, _marker }
+    }
+
+    #[inline(always)]
+    fn get_key(&self) -> libc::pthread_key_t {
+        let key = self.key.load(atomic::Ordering::Relaxed);
+        if key >= 0 { key as libc::pthread_key_t} else { unsafe { self.initialize() } }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn initialize(&self) -> libc::pthread_key_t { self.initialize_impl().0 }
+
+    //  Returns the key, and whether this call created it.
+    #[cold]
+    unsafe fn initialize_impl(&self) -> (libc::pthread_key_t, bool) {
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        let mut key = self.key.load(RELAXED);
+        let mut created = false;
+
+        if self.key.compare_exchange(Self::UNINITIALIZED, Self::UNDER_INITIALIZATION, RELAXED, RELAXED).is_ok() {
+            key = self.create_key();
+            created = true;
+            self.key.store(key, RELAXED);
+        }
+
+        while key == Self::UNDER_INITIALIZATION {
+            libc::sched_yield();
+            key = self.key.load(RELAXED);
+        }
+
+        (key as libc::pthread_key_t, created)
+    }
+
+    #[cold]
+    unsafe fn create_key(&self) -> i64 {
+        let mut key: libc::pthread_key_t = 0;
+
+        //  Safety:
+        //  -   fn pointers are just pointers.
+        #[cfg(not(feature = "initial-exec"))]
+        let destructor = mem::transmute::<*const u8, Destructor>(self.destructor);
+
+        //  The static is cleared prior to invoking the destructor, lest a later destructor of another key allocating
+        //  on the exiting thread be handed the released value.
+        #[cfg(feature = "initial-exec")]
+        let destructor: Destructor = {
+            DESTRUCTOR.store(self.destructor as *mut u8, atomic::Ordering::Relaxed);
+            clear_slot
+        };
+
+        let result = libc::pthread_key_create(&mut key as *mut _, Some(destructor));
+
+        if result == 0 { key as i64 } else { Self::FAILED }
+    }
+}
+
+impl<T> ThreadLocal<T> for LLThreadLocal<T> {
+    #[cold]
+    #[inline(never)]
+    fn prepare(&self) -> bool {
+        if self.key.load(atomic::Ordering::Relaxed) >= 0 {
+            return false;
+        }
+
+        //  Safety:
+        //  -   The key is not yet initialized, or under initialization.
+        unsafe { self.initialize_impl().1 }
+    }
+
+    #[cfg(feature = "initial-exec")]
+    #[inline(always)]
+    fn get(&self) -> Option<NonNull<T>> { NonNull::new(SLOT.get() as *mut T) }
+
+    #[cfg(not(feature = "initial-exec"))]
+    fn get(&self) -> Option<NonNull<T>> {
+        let key = self.key.load(atomic::Ordering::Relaxed);
+
+        //  If key is not initialized, then a null pointer is returned.
+        NonNull::new(unsafe { libc::pthread_getspecific(key as libc::pthread_key_t) as *mut T })
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn set(&self, value: NonNull<T>) -> bool {
+        let key = self.get_key();
+
+        //  An invalid key, if its creation failed, is reported by `pthread_setspecific`.
+        let result = unsafe { libc::pthread_setspecific(key, value.as_ptr() as *mut libc::c_void) };
+
+        //  The value is only cached once the destructor is certain to run.
+        #[cfg(feature = "initial-exec")]
+        if result == 0 {
+            SLOT.set(value.as_ptr() as *mut u8);
+        }
+
+        result == 0
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn clear(&self) -> bool {
+        let key = self.key.load(atomic::Ordering::Relaxed);
+
+        if key < 0 {
+            return false;
+        }
+
+        //  A null value is not destructed on thread exit.
+        let result = unsafe { libc::pthread_setspecific(key as libc::pthread_key_t, ptr::null()) };
+
+        #[cfg(feature = "initial-exec")]
+        if result == 0 {
+            SLOT.set(ptr::null_mut());
+        }
+
+        result == 0
+    }
+}
+
+unsafe impl<T> Sync for LLThreadLocal<T> {}
+
+//
+//  Implementation Details
+//
+
+type Destructor = unsafe extern "C" fn(*mut libc::c_void);
+
+//  The value of the key, for the current thread.
+#[cfg(feature = "initial-exec")]
+#[thread_local]
+static SLOT: Cell<*mut u8> = Cell::new(ptr::null_mut());
+
+//  The destructor of the instance, invoked by `clear_slot`.
+#[cfg(feature = "initial-exec")]
+static DESTRUCTOR: atomic::AtomicPtr<u8> = atomic::AtomicPtr::new(ptr::null_mut());
+
+//  Clears the static, then invokes the destructor of the instance with `value`.
+#[cfg(feature = "initial-exec")]
+unsafe extern "C" fn clear_slot(value: *mut libc::c_void) {
+    SLOT.set(ptr::null_mut());
+
+    //  Safety:
+    //  -   `DESTRUCTOR` was set, prior to creating the key, to a destructor as per `new`.
+    let destructor = mem::transmute::<*mut u8, Destructor>(DESTRUCTOR.load(atomic::Ordering::Relaxed));
+
+    destructor(value)
+}
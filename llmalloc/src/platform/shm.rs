@@ -0,0 +1,100 @@
+//! Implementation of anonymous shared memory objects, on top of named POSIX shared memory objects.
+
+use core::sync::atomic;
+
+/// Opens a new, anonymous, shared memory object, returning its file descriptor.
+///
+/// POSIX only offers named shared memory objects, hence the object is created under a unique name, then unlinked.
+pub(super) fn open_shared_memory() -> Option<libc::c_int> {
+    //  Number of names attempted, should others have been created concurrently by other processes.
+    const ATTEMPTS: usize = 8;
+
+    //  Safety:
+    //  -   `getpid` has no precondition.
+    let pid = unsafe { libc::getpid() } as u32;
+
+    for _ in 0..ATTEMPTS {
+        let counter = SHARED_MEMORY_OBJECTS.fetch_add(1, atomic::Ordering::Relaxed);
+        let name = shared_memory_name(pid, counter);
+
+        let flags = libc::O_RDWR | libc::O_CREAT | libc::O_EXCL;
+
+        //  Safety:
+        //  -   `name` is NUL-terminated.
+        let fd = unsafe { libc::shm_open(name.as_ptr() as *const libc::c_char, flags, 0o600) };
+
+        if fd < 0 {
+            continue;
+        }
+
+        //  Safety:
+        //  -   `name` is NUL-terminated.
+        unsafe { libc::shm_unlink(name.as_ptr() as *const libc::c_char) };
+
+        return Some(fd);
+    }
+
+    None
+}
+
+//
+//  Implementation Details
+//
+
+//  Counter of the shared memory objects created, to name them uniquely.
+static SHARED_MEMORY_OBJECTS: atomic::AtomicU32 = atomic::AtomicU32::new(0);
+
+//  Returns the name of a shared memory object, as in `/llmalloc-<pid>-<counter>`, NUL-terminated.
+fn shared_memory_name(pid: u32, counter: u32) -> [u8; 32] {
+    const PREFIX: &[u8] = b"/llmalloc-";
+
+    let mut name = [0u8; 32];
+    name[..PREFIX.len()].copy_from_slice(PREFIX);
+
+    let length = write_decimal(&mut name[PREFIX.len()..], pid) + PREFIX.len();
+    name[length] = b'-';
+
+    write_decimal(&mut name[length + 1..], counter);
+
+    name
+}
+
+//  Writes `value`, in decimal, at the start of `buffer`, returning the number of digits written.
+//
+//  The buffer is assumed large enough, up to 10 digits.
+fn write_decimal(buffer: &mut [u8], mut value: u32) -> usize {
+    let mut digits = [0u8; 10];
+    let mut count = 0;
+
+    loop {
+        digits[count] = b'0' + (value % 10) as u8;
+        count += 1;
+        value /= 10;
+
+        if value == 0 {
+            break;
+        }
+    }
+
+    for (target, digit) in buffer.iter_mut().zip(digits[..count].iter().rev()) {
+        *target = *digit;
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn shared_memory_name_format() {
+    let name = shared_memory_name(4321, 0);
+    assert_eq!(&b"/llmalloc-4321-0\0"[..], &name[..17]);
+
+    let name = shared_memory_name(u32::MAX, u32::MAX);
+    assert_eq!(&b"/llmalloc-4294967295-4294967295\0"[..], &name[..]);
+}
+
+} // mod tests
@@ -0,0 +1,135 @@
+//! Implementation of thread-local storage on top of Solaris threads keys, for illumos and Solaris.
+//!
+//! The `thr_*` functions are part of the C library, yet missing from `libc`, hence are declared here.
+
+use core::{
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    sync::atomic,
+};
+
+use super::ThreadLocal;
+
+/// Implementation of the ThreadLocal trait, for illumos and Solaris.
+pub(crate) struct LLThreadLocal<T> {
+    key: atomic::AtomicI64,
+    destructor: *const u8,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> LLThreadLocal<T> {
+    const UNINITIALIZED: i64 = -1;
+    const UNDER_INITIALIZATION: i64 = -2;
+    const FAILED: i64 = -3;
+
+    /// Creates an uninitialized instance.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `destructor` points to an `unsafe extern "C" fn(*mut c_void)` function, or compatible.
+    pub(crate) const unsafe fn new(destructor: *const u8) -> Self {
+        let key = atomic::AtomicI64::new(-1);
+        let _marker = PhantomData;
+
+        LLThreadLocal { key, destructor, _marker }
+    }
+
+    #[inline(always)]
+    fn get_key(&self) -> ThreadKey {
+        let key = self.key.load(atomic::Ordering::Relaxed);
+        if key >= 0 { key as ThreadKey} else { unsafe { self.initialize() } }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn initialize(&self) -> ThreadKey { self.initialize_impl().0 }
+
+    //  Returns the key, and whether this call created it.
+    #[cold]
+    unsafe fn initialize_impl(&self) -> (ThreadKey, bool) {
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        let mut key = self.key.load(RELAXED);
+        let mut created = false;
+
+        if self.key.compare_exchange(Self::UNINITIALIZED, Self::UNDER_INITIALIZATION, RELAXED, RELAXED).is_ok() {
+            key = self.create_key();
+            created = true;
+            self.key.store(key, RELAXED);
+        }
+
+        while key == Self::UNDER_INITIALIZATION {
+            libc::sched_yield();
+            key = self.key.load(RELAXED);
+        }
+
+        (key as ThreadKey, created)
+    }
+
+    #[cold]
+    unsafe fn create_key(&self) -> i64 {
+        let mut key: ThreadKey = 0;
+
+        //  Safety:
+        //  -   fn pointers are just pointers.
+        let destructor = mem::transmute::<*const u8, Destructor>(self.destructor);
+        let result = thr_keycreate(&mut key as *mut _, Some(destructor));
+
+        if result == 0 { key as i64 } else { Self::FAILED }
+    }
+}
+
+impl<T> ThreadLocal<T> for LLThreadLocal<T> {
+    #[cold]
+    #[inline(never)]
+    fn prepare(&self) -> bool {
+        if self.key.load(atomic::Ordering::Relaxed) >= 0 {
+            return false;
+        }
+
+        //  Safety:
+        //  -   The key is not yet initialized, or under initialization.
+        unsafe { self.initialize_impl().1 }
+    }
+
+    fn get(&self) -> Option<NonNull<T>> {
+        let key = self.key.load(atomic::Ordering::Relaxed);
+
+        let mut value = ptr::null_mut();
+
+        //  If key is not initialized, then an error is returned, and `value` is left null.
+        unsafe { thr_getspecific(key as ThreadKey, &mut value as *mut _) };
+
+        NonNull::new(value as *mut T)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn set(&self, value: NonNull<T>) -> bool {
+        let key = self.get_key();
+
+        //  An invalid key, if its creation failed, is reported by `thr_setspecific`.
+        let result = unsafe { thr_setspecific(key, value.as_ptr() as *mut libc::c_void) };
+
+        result == 0
+    }
+}
+
+unsafe impl<T> Sync for LLThreadLocal<T> {}
+
+type Destructor = unsafe extern "C" fn(*mut libc::c_void);
+
+type ThreadKey = libc::c_uint;
+
+extern "C" {
+    //  Creates a key, whose value is initially null in all threads, and `destructor` is invoked on exit of a thread
+    //  whose value is non-null.
+    fn thr_keycreate(key: *mut ThreadKey, destructor: Option<Destructor>) -> libc::c_int;
+
+    //  Stores the value of `key` of the current thread in `value`.
+    fn thr_getspecific(key: ThreadKey, value: *mut *mut libc::c_void) -> libc::c_int;
+
+    //  Sets the value of `key` of the current thread.
+    fn thr_setspecific(key: ThreadKey, value: *mut libc::c_void) -> libc::c_int;
+}
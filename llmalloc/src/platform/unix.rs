@@ -0,0 +1,214 @@
+//! Implementation of the calls shared by the Unix platforms, other than Linux.
+//!
+//! These platforms only differ in how they map, back, and report on their memory: they share their configuration,
+//! the bookkeeping of their fallbacks and of their mapping latency, the ownership of their memory, and the calls of
+//! the C library, all implemented once here. The platforms mapping their memory with `mmap` further share the calls of
+//! the `mmap` module.
+
+use core::{
+    alloc::Layout,
+    ptr::NonNull,
+    sync::atomic,
+    time::Duration,
+};
+
+use llmalloc_core::PowerOf2;
+
+use crate::{AtomicFallbackMetrics, Capabilities, HostCapabilities, TransparentHugePagesMode};
+
+use super::Configuration;
+
+#[cfg(feature = "system-fallback")]
+use super::ownership::OWNERSHIP;
+
+/// Implementation of the Configuration trait, for the Unix platforms other than Linux.
+///
+/// The pages are sized as on Linux, see the Linux configuration for the consequences of the `small-heap` feature.
+#[derive(Default)]
+pub(crate) struct LLConfiguration;
+
+#[cfg(not(feature = "small-heap"))]
+impl Configuration for LLConfiguration {
+    //  2 MB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+
+    //  1 GB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(1024 * 1024 * 1024) };
+}
+
+#[cfg(feature = "small-heap")]
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
+/// Metrics of the fallbacks.
+pub(super) static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+/// Maps a Huge Page of `layout` with `map`, recording the latency of the mapping, then marks it as owned, unmapping it
+/// with `unmap` should it not be markable.
+///
+/// Returns None if `layout` is not that of Huge Pages, or if the mapping fails.
+///
+/// #   Safety
+///
+/// -   Assumes that `unmap` unmaps the memory mapped by `map`.
+pub(super) unsafe fn map_huge_page<F>(layout: Layout, map: F, unmap: unsafe fn(*mut u8, usize)) -> Option<NonNull<u8>>
+    where
+        F: FnOnce(usize) -> Option<NonNull<u8>>,
+{
+    const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    debug_assert!(layout.size() % HUGE_PAGE_SIZE == 0,
+        "Incorrect size: {} % {} != 0", layout.size(), HUGE_PAGE_SIZE.value());
+    debug_assert!(layout.align() <= HUGE_PAGE_SIZE.value(),
+        "Incorrect alignment: {} > {}", layout.align(), HUGE_PAGE_SIZE.value());
+
+    if layout.size() % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() {
+        return None;
+    }
+
+    let start = now();
+
+    let candidate = map(layout.size());
+
+    //  Failed mappings count too, as a deadline must also cover the paths which end up failing.
+    MAPPING_LATENCY.fetch_max(now().saturating_sub(start).saturating_add(1), atomic::Ordering::Relaxed);
+
+    let candidate = candidate?;
+
+    debug_assert!(candidate.as_ptr() as usize % HUGE_PAGE_SIZE == 0,
+        "Incorrect alignment of allocation: {:x} % {:x} != 0", candidate.as_ptr() as usize, HUGE_PAGE_SIZE.value());
+
+    if !claim(candidate.as_ptr(), layout.size(), unmap) {
+        return None;
+    }
+
+    Some(candidate)
+}
+
+/// Marks the `size` bytes located at `pointer` as owned, unmapping them with `unmap` should they not be markable.
+///
+/// Returns whether the memory is usable.
+///
+/// #   Safety
+///
+/// -   Assumes that `pointer` points to a mapped area of at least `size` bytes, not yet in use.
+#[cfg_attr(not(feature = "system-fallback"), allow(unused_variables))]
+pub(super) unsafe fn claim(pointer: *mut u8, size: usize, unmap: unsafe fn(*mut u8, usize)) -> bool {
+    #[cfg(feature = "system-fallback")]
+    if !OWNERSHIP.mark(pointer as usize, size) {
+        unmap(pointer, size);
+        return false;
+    }
+
+    true
+}
+
+/// Clears the ownership of the `size` bytes located at `pointer`, before they are unmapped.
+#[cfg_attr(not(feature = "system-fallback"), allow(unused_variables))]
+pub(super) fn release(pointer: *mut u8, size: usize) {
+    #[cfg(feature = "system-fallback")]
+    OWNERSHIP.clear(pointer as usize, size);
+}
+
+/// Returns the worst latency observed mapping memory, if any.
+#[inline(always)]
+pub(super) fn mapping_latency() -> Option<Duration> {
+    match MAPPING_LATENCY.load(atomic::Ordering::Relaxed) {
+        0 => None,
+        latency => Some(Duration::from_nanos(latency - 1)),
+    }
+}
+
+/// Returns the current time, in nanoseconds, from the monotonic clock.
+pub(super) fn now() -> u64 {
+    let mut timespec = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+
+    //  Safety:
+    //  -   `timespec` is valid for writes.
+    let result = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut timespec as *mut _) };
+
+    //  The timestamps are only used for metrics, an unreadable clock merely makes them meaningless.
+    if result != 0 {
+        return 0;
+    }
+
+    (timespec.tv_sec as u64) * 1_000_000_000 + (timespec.tv_nsec as u64)
+}
+
+/// Returns whether the environment variable `name`, NUL-terminated, is set to any value other than an empty string or
+/// `0`.
+pub(super) fn environment_flag(name: &[u8]) -> bool {
+    debug_assert_eq!(Some(&0), name.last());
+
+    //  Safety:
+    //  -   `name` is NUL-terminated.
+    //  -   `getenv` does not allocate.
+    let value = unsafe { libc::getenv(name.as_ptr() as *const libc::c_char) };
+
+    if value.is_null() {
+        return false;
+    }
+
+    //  Safety:
+    //  -   `value` is non-null, and NUL-terminated.
+    let value = unsafe { core::ffi::CStr::from_ptr(value) };
+
+    !matches!(value.to_bytes(), b"" | b"0")
+}
+
+/// Returns the details of an host with the `capabilities` selected, as far as they are common to the Unix platforms:
+/// its Transparent Huge Pages are unknown, and it has a single NUMA node, no `rseq`, and pages of `os_page_size`.
+pub(super) fn host_capabilities(capabilities: Capabilities, os_page_size: usize, mlock_limit: Option<u64>)
+    -> HostCapabilities
+{
+    let mut host = HostCapabilities::default();
+    host.capabilities = capabilities;
+
+    host.transparent_huge_pages = TransparentHugePagesMode::Unknown;
+    host.numa_nodes = 1;
+    host.rseq = false;
+    host.mlock_limit = mlock_limit;
+    host.os_page_size = os_page_size;
+
+    host
+}
+
+/// Allocates `layout` from the system allocator.
+#[cfg(feature = "system-fallback")]
+pub(super) fn system_allocate(layout: Layout) -> Option<NonNull<u8>> {
+    let mut pointer = core::ptr::null_mut();
+
+    //  `posix_memalign` requires an alignment which is a multiple of the size of a pointer.
+    let align = layout.align().max(core::mem::size_of::<*mut libc::c_void>());
+
+    //  Safety:
+    //  -   `align` is a power of 2, and a multiple of the size of a pointer.
+    let result = unsafe { libc::posix_memalign(&mut pointer as *mut _, align, layout.size().max(1)) };
+
+    if result == 0 { NonNull::new(pointer as *mut u8) } else { None }
+}
+
+/// Deallocates `pointer`, allocated by `system_allocate`.
+///
+/// #   Safety
+///
+/// -   Assumes that `pointer` was allocated by `system_allocate`, and is no longer in use.
+#[cfg(feature = "system-fallback")]
+pub(super) unsafe fn system_deallocate(pointer: NonNull<u8>) { libc::free(pointer.as_ptr() as *mut libc::c_void) }
+
+/// Returns whether `pointer` lies within memory mapped by the platform.
+#[cfg(feature = "system-fallback")]
+#[inline(always)]
+pub(super) fn owns(pointer: NonNull<u8>) -> bool { OWNERSHIP.contains(pointer.as_ptr() as usize) }
+
+//
+//  Implementation Details
+//
+
+//  Worst latency observed mapping memory, in nanoseconds: 0 if none was observed, otherwise 1 + latency.
+static MAPPING_LATENCY: atomic::AtomicU64 = atomic::AtomicU64::new(0);
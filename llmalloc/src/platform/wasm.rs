@@ -0,0 +1,492 @@
+//! Implementation of a WebAssembly platform, over the linear memory.
+//!
+//! The linear memory can only ever grow, with `memory.grow`, and never shrink: the Huge Pages are carved out of its
+//! extensions, aligned, and those deallocated are returned to a pool, serving the next allocations before the memory
+//! grows anew. There is a single pseudo-NUMA node, no clock, and neither code, stacks, nor physical buffers can be
+//! mapped.
+//!
+//! The thread-local storage is a single slot, suitable for the single thread of execution of WebAssembly, without the
+//! threads proposal.
+
+use core::{
+    alloc::Layout,
+    arch::wasm32,
+    marker::PhantomData,
+    ptr::{self, NonNull},
+    sync::atomic::{self, AtomicBool, AtomicPtr, AtomicU64},
+    time::Duration,
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, HostCapabilities, HugePageReport, PhysicalBuffer,
+    PhysicalSegment, ThreadStack,
+};
+
+use super::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+
+/// Implementation of the Configuration trait, for WebAssembly.
+///
+/// The linear memory spans at most 4 GB, hence the pages are always sized as with the `small-heap` feature: 2 MB Huge
+/// Pages, carved into 64 KB Large Pages, the size of the pages of the linear memory.
+#[derive(Default)]
+pub(crate) struct LLConfiguration;
+
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
+/// Implementation of the Platform trait, for WebAssembly.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        const HUGE_PAGE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        debug_assert!(layout.size() % HUGE_PAGE == 0,
+            "Incorrect size: {} % {} != 0", layout.size(), HUGE_PAGE_SIZE);
+        debug_assert!(layout.align() <= HUGE_PAGE_SIZE,
+            "Incorrect alignment: {} > {}", layout.align(), HUGE_PAGE_SIZE);
+
+        if layout.size() % HUGE_PAGE != 0 || layout.align() > HUGE_PAGE_SIZE {
+            return None;
+        }
+
+        let address = MEMORY.allocate(layout.size() / HUGE_PAGE_SIZE)?;
+
+        NonNull::new(address as *mut u8)
+    }
+
+    //  The linear memory cannot shrink, hence the Huge Pages are returned to the pool.
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        MEMORY.release(pointer.as_ptr() as usize, layout.size() / HUGE_PAGE_SIZE);
+    }
+
+    unsafe fn reallocate(&self, pointer: NonNull<u8>, layout: Layout, new_size: usize) -> Option<NonNull<u8>> {
+        const HUGE_PAGE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        if new_size % HUGE_PAGE != 0 || layout.align() > HUGE_PAGE_SIZE {
+            return None;
+        }
+
+        let address = pointer.as_ptr() as usize;
+        let (pages, new_pages) = (layout.size() / HUGE_PAGE_SIZE, new_size / HUGE_PAGE_SIZE);
+
+        //  Shrink in place, by returning the tail to the pool.
+        if new_pages <= pages {
+            MEMORY.release(address + new_size, pages - new_pages);
+            return Some(pointer);
+        }
+
+        //  Grow in place, if the following Huge Pages are pooled, or follow the end of the linear memory.
+        if MEMORY.extend(address, pages, new_pages - pages) { Some(pointer) } else { None }
+    }
+}
+
+impl Platform for LLPlatform {
+    #[inline(always)]
+    fn current_node(&self) -> NumaNodeIndex { NumaNodeIndex::new(0) }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node == 0 { Some(NumaNodeIndex::new(0)) } else { None }
+    }
+
+    //  There is no clock to read, hence the metrics relying on timestamps are meaningless.
+    #[inline(always)]
+    fn now(&self) -> u64 { 0 }
+
+    //  The linear memory is neither paged, nor split, nor migrated, as far as the module can tell.
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        HugePageReport {
+            address: page.as_ptr() as usize,
+            size,
+            node: node.value(),
+            mappings: 1,
+            kernel_page_size: size,
+            resident: size,
+            anonymous_huge: 0,
+            local_pages: 1,
+            foreign_pages: 0,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, _pointer: NonNull<u8>, size: usize) -> Option<usize> { Some(size) }
+
+    //  The linear memory is never paged out, as far as the module can tell, hence always locked.
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, _pointer: NonNull<u8>, _size: usize) -> bool { true }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, _pointer: NonNull<u8>, _size: usize, _writable: bool) -> bool { false }
+
+    //  The code of a module cannot be modified, nor generated, from within.
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, _size: usize, _mapping: CodeMapping) -> Option<CodeRegion> { None }
+
+    //  No code region is ever mapped.
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, _region: CodeRegion) {}
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, _region: &CodeRegion, _executable: bool) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, _size: usize, _prefault: bool) -> Option<ThreadStack> { None }
+
+    //  No stack is ever mapped.
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, _stack: ThreadStack) {}
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, _size: usize) -> Option<PhysicalBuffer> { None }
+
+    //  No physical buffer is ever mapped.
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, _buffer: PhysicalBuffer) {}
+
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    //  There is no environment to read, without WASI.
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, _name: &[u8]) -> bool { false }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities {
+        let mut host = HostCapabilities::default();
+        host.capabilities = CAPABILITIES;
+
+        host.numa_nodes = 1;
+        host.os_page_size = WASM_PAGE_SIZE;
+
+        host
+    }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> { None }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    //  There is no system allocator to delegate to.
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, _layout: Layout) -> Option<NonNull<u8>> { None }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, _pointer: NonNull<u8>) {}
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { MEMORY.owns(pointer.as_ptr() as usize) }
+}
+
+/// Implementation of the ThreadLocal trait, for WebAssembly.
+pub(crate) struct LLThreadLocal<T> {
+    slot: AtomicPtr<u8>,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> LLThreadLocal<T> {
+    /// Creates an uninitialized instance.
+    ///
+    /// The single thread of execution never exits, hence `destructor` is never invoked.
+    ///
+    /// #   Safety
+    ///
+    /// -   Has no requirement, `destructor` being unused.
+    pub(crate) const unsafe fn new(_destructor: *const u8) -> Self {
+        LLThreadLocal { slot: AtomicPtr::new(ptr::null_mut()), _marker: PhantomData }
+    }
+}
+
+impl<T> ThreadLocal<T> for LLThreadLocal<T> {
+    //  There is no key to create.
+    #[inline(always)]
+    fn prepare(&self) -> bool { false }
+
+    #[inline(always)]
+    fn get(&self) -> Option<NonNull<T>> { NonNull::new(self.slot.load(atomic::Ordering::Relaxed) as *mut T) }
+
+    #[cold]
+    #[inline(never)]
+    fn set(&self, value: NonNull<T>) -> bool {
+        self.slot.store(value.as_ptr() as *mut u8, atomic::Ordering::Relaxed);
+        true
+    }
+}
+
+unsafe impl<T> Sync for LLThreadLocal<T> {}
+
+//
+//  Implementation Details
+//
+
+//  Capabilities of the environment, which are not detected: the backing of the linear memory is up to the host.
+const CAPABILITIES: Capabilities =
+    Capabilities { huge_tlb: false, huge_tlb_2mb: false, transparent_huge_pages: false, numa: false, sysfs: true };
+
+const HUGE_PAGE_SIZE: usize = LLConfiguration::HUGE_PAGE_SIZE.value();
+
+//  The size of the pages of the linear memory, as grown by `memory.grow`.
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+const WASM_PAGES_PER_HUGE_PAGE: usize = HUGE_PAGE_SIZE / WASM_PAGE_SIZE;
+
+//  Number of Huge Pages of the 4 GB of linear memory.
+const MAXIMUM_PAGES: usize = WORDS * 64;
+
+const WORDS: usize = 32;
+
+//  Metrics of the fallbacks.
+static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+//  The linear memory.
+static MEMORY: LinearMemory = LinearMemory::new();
+
+//  Huge Pages carved out of the linear memory, indexed by address.
+//
+//  The Huge Pages are owned once carved, and pooled while not in use. The linear memory is rarely grown, hence a lock
+//  suffices, sparing the search of pooled runs of pages from racing.
+struct LinearMemory {
+    lock: AtomicBool,
+    owned: [AtomicU64; WORDS],
+    used: [AtomicU64; WORDS],
+}
+
+impl LinearMemory {
+    const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+
+        Self { lock: AtomicBool::new(false), owned: [ZERO; WORDS], used: [ZERO; WORDS] }
+    }
+
+    //  Allocates `count` consecutive Huge Pages, from the pool if possible, returning the address of the first.
+    fn allocate(&self, count: usize) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+
+        self.locked(|| {
+            let first = self.find_pooled(count).or_else(|| self.grow(count))?;
+
+            (first..first + count).for_each(|index| self.set(&self.used, index, true));
+
+            Some(first * HUGE_PAGE_SIZE)
+        })
+    }
+
+    //  Extends the `count` Huge Pages located at `address` by the `extra` following ones, if pooled, or if they follow
+    //  the end of the linear memory.
+    fn extend(&self, address: usize, count: usize, extra: usize) -> bool {
+        if !address.is_multiple_of(HUGE_PAGE_SIZE) || !self.owns(address) {
+            return false;
+        }
+
+        let first = address / HUGE_PAGE_SIZE + count;
+
+        if first + extra > MAXIMUM_PAGES {
+            return false;
+        }
+
+        self.locked(|| {
+            let pooled = (first..first + extra).all(|index| self.is_pooled(index));
+
+            if !pooled && !self.grow_at(first, extra) {
+                return false;
+            }
+
+            (first..first + extra).for_each(|index| self.set(&self.used, index, true));
+
+            true
+        })
+    }
+
+    //  Returns the `count` Huge Pages located at `address` to the pool.
+    fn release(&self, address: usize, count: usize) {
+        let first = address / HUGE_PAGE_SIZE;
+
+        if first + count > MAXIMUM_PAGES {
+            return;
+        }
+
+        self.locked(|| (first..first + count).for_each(|index| self.set(&self.used, index, false)));
+    }
+
+    //  Returns whether `address` lies within a Huge Page carved out of the linear memory.
+    fn owns(&self, address: usize) -> bool {
+        let index = address / HUGE_PAGE_SIZE;
+
+        index < MAXIMUM_PAGES && self.is(&self.owned, index)
+    }
+
+    //  Returns the index of the first of `count` consecutive pooled Huge Pages, if any.
+    fn find_pooled(&self, count: usize) -> Option<usize> {
+        let mut first = 0;
+
+        while first + count <= MAXIMUM_PAGES {
+            match (first..first + count).find(|index| !self.is_pooled(*index)) {
+                Some(unpooled) => first = unpooled + 1,
+                None => return Some(first),
+            }
+        }
+
+        None
+    }
+
+    //  Grows the linear memory by `count` Huge Pages, aligned, returning the index of the first.
+    //
+    //  The part of the linear memory skipped to align the Huge Pages is left to whichever else grows it.
+    fn grow(&self, count: usize) -> Option<usize> {
+        let first = wasm32::memory_size::<0>().div_ceil(WASM_PAGES_PER_HUGE_PAGE);
+
+        if first + count > MAXIMUM_PAGES || !self.grow_at(first, count) {
+            return None;
+        }
+
+        Some(first)
+    }
+
+    //  Grows the linear memory so as to carve the `count` Huge Pages starting at index `first`, returning whether
+    //  the linear memory was grown, which requires those Huge Pages to lie past its current end.
+    fn grow_at(&self, first: usize, count: usize) -> bool {
+        let current = wasm32::memory_size::<0>();
+        let target = (first + count) * WASM_PAGES_PER_HUGE_PAGE;
+
+        if first * WASM_PAGES_PER_HUGE_PAGE < current {
+            return false;
+        }
+
+        //  Should the linear memory be grown concurrently, by another allocator, then the Huge Pages may overlap its
+        //  extension, in which case the memory grown is lost.
+        let previous = wasm32::memory_grow::<0>(target - current);
+
+        if previous != current {
+            return false;
+        }
+
+        (first..first + count).for_each(|index| self.set(&self.owned, index, true));
+
+        true
+    }
+
+    fn is_pooled(&self, index: usize) -> bool { self.is(&self.owned, index) && !self.is(&self.used, index) }
+
+    fn is(&self, bits: &[AtomicU64; WORDS], index: usize) -> bool {
+        bits[index / 64].load(atomic::Ordering::Relaxed) & (1 << (index % 64)) != 0
+    }
+
+    fn set(&self, bits: &[AtomicU64; WORDS], index: usize, value: bool) {
+        let word = &bits[index / 64];
+        let bit = 1 << (index % 64);
+
+        if value {
+            word.fetch_or(bit, atomic::Ordering::Relaxed);
+        } else {
+            word.fetch_and(!bit, atomic::Ordering::Relaxed);
+        }
+    }
+
+    //  Invokes `f` with the lock held.
+    fn locked<R, F>(&self, f: F) -> R
+        where
+            F: FnOnce() -> R,
+    {
+        const ACQUIRE: atomic::Ordering = atomic::Ordering::Acquire;
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        while self.lock.compare_exchange_weak(false, true, ACQUIRE, RELAXED).is_err() {
+            core::hint::spin_loop();
+        }
+
+        let result = f();
+
+        self.lock.store(false, atomic::Ordering::Release);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn linear_memory_pool() {
+    let memory = LinearMemory::new();
+    assert_eq!(None, memory.allocate(0));
+
+    let first = memory.allocate(2).expect("Grown");
+    assert_eq!(0, first % HUGE_PAGE_SIZE);
+    assert!(memory.owns(first) && memory.owns(first + 2 * HUGE_PAGE_SIZE - 1));
+
+    //  Returned to the pool, and reused.
+    memory.release(first, 2);
+    assert!(memory.owns(first));
+
+    assert_eq!(Some(first), memory.allocate(1));
+    assert_eq!(Some(first + HUGE_PAGE_SIZE), memory.allocate(1));
+}
+
+#[test]
+fn linear_memory_extend() {
+    let memory = LinearMemory::new();
+
+    let first = memory.allocate(1).expect("Grown");
+
+    //  At the end of the linear memory, it grows.
+    assert!(memory.extend(first, 1, 1));
+    assert!(memory.owns(first + HUGE_PAGE_SIZE));
+
+    //  Otherwise, only into the pool.
+    memory.release(first + HUGE_PAGE_SIZE, 1);
+
+    let next = memory.allocate(1).expect("Grown");
+    assert_eq!(first + HUGE_PAGE_SIZE, next);
+    assert!(!memory.extend(first, 1, 1));
+
+    memory.release(next, 1);
+    assert!(memory.extend(first, 1, 1));
+}
+
+} // mod tests
@@ -0,0 +1,845 @@
+//! Implementation of Windows specific calls.
+
+mod capabilities;
+mod working_set;
+
+use core::{
+    alloc::Layout,
+    marker::PhantomData,
+    mem,
+    ptr::{self, NonNull},
+    sync::atomic,
+    time::Duration,
+};
+
+use winapi::{
+    shared::minwindef::{DWORD, FALSE},
+    um::{
+        errhandlingapi, fibersapi, handleapi, memoryapi, processenv, processthreadsapi, profileapi, sysinfoapi,
+        systemtopologyapi, winbase, winnt,
+    },
+};
+
+use llmalloc_core::{self, PowerOf2};
+
+use crate::{
+    AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Fallback, HostCapabilities, HugePageReport,
+    PhysicalBuffer, PhysicalSegment, PrivilegeError, ThreadStack, UnmapFailure,
+};
+
+use crate::unmapping::UNMAPPING;
+
+use super::{NumaNodeIndex, Configuration, Platform, ThreadLocal};
+
+#[cfg(feature = "system-fallback")]
+use super::ownership::OWNERSHIP;
+
+/// Implementation of the Configuration trait, for Windows.
+///
+/// The pages are sized as on Linux, the Huge Pages being backed by large pages, of 2 MB on x64, if the process holds
+/// the `SeLockMemoryPrivilege`.
+///
+/// With the `small-heap` feature, the Huge Pages shrink from 1 GB to 2 MB, and the Large Pages from 2 MB to 64 KB, see
+/// the Linux configuration for the consequences.
+#[derive(Default)]
+pub(crate) struct LLConfiguration;
+
+#[cfg(not(feature = "small-heap"))]
+impl Configuration for LLConfiguration {
+    //  2 MB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+
+    //  1 GB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(1024 * 1024 * 1024) };
+}
+
+#[cfg(feature = "small-heap")]
+impl Configuration for LLConfiguration {
+    //  64 KB
+    const LARGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(64 * 1024) };
+
+    //  2 MB
+    const HUGE_PAGE_SIZE: PowerOf2 = unsafe { PowerOf2::new_unchecked(2 * 1024 * 1024) };
+}
+
+/// Implementation of the Platform trait, for Windows.
+///
+/// A reservation of the address space can only be released as a whole, hence the Huge allocations are never resized
+/// in place, and the parts of a reservation deallocated separately are merely decommitted, their address space
+/// remaining reserved.
+#[derive(Default)]
+pub(crate) struct LLPlatform;
+
+impl LLPlatform {
+    /// Creates an instance.
+    pub(crate) const fn new() -> Self { Self }
+}
+
+impl llmalloc_core::Platform for LLPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+        debug_assert!(layout.size() % HUGE_PAGE_SIZE == 0,
+            "Incorrect size: {} % {} != 0", layout.size(), HUGE_PAGE_SIZE.value());
+        debug_assert!(layout.align() <= HUGE_PAGE_SIZE.value(),
+            "Incorrect alignment: {} > {}", layout.align(), HUGE_PAGE_SIZE.value());
+
+        if layout.size() % HUGE_PAGE_SIZE != 0 || layout.align() > HUGE_PAGE_SIZE.value() {
+            return None;
+        }
+
+        let start = self.now();
+
+        let candidate = virtual_alloc_huge(layout.size())
+            .or_else(|| virtual_alloc_normal(layout.size()));
+
+        //  Failed mappings count too, as a deadline must also cover the paths which end up failing.
+        MAPPING_LATENCY.fetch_max(self.now().saturating_sub(start).saturating_add(1), atomic::Ordering::Relaxed);
+
+        let candidate = candidate?;
+
+        debug_assert!(candidate.as_ptr() as usize % HUGE_PAGE_SIZE == 0,
+            "Incorrect alignment of allocation: {:x} % {:x} != 0", candidate.as_ptr() as usize, HUGE_PAGE_SIZE.value());
+
+        #[cfg(feature = "system-fallback")]
+        if !OWNERSHIP.mark(candidate.as_ptr() as usize, layout.size()) {
+            virtual_free(candidate.as_ptr(), layout.size());
+            return None;
+        }
+
+        Some(candidate)
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "system-fallback")]
+        OWNERSHIP.clear(pointer.as_ptr() as usize, layout.size());
+
+        virtual_free(pointer.as_ptr(), layout.size());
+    }
+}
+
+impl Platform for LLPlatform {
+    #[cold]
+    #[inline(never)]
+    fn current_node(&self) -> NumaNodeIndex {
+        //  Without NUMA, a single socket is shared by all threads.
+        if !CAPABILITIES.get().numa {
+            return NumaNodeIndex::new(0);
+        }
+
+        //  Safety:
+        //  -   `GetCurrentProcessorNumber` has no precondition.
+        let processor = unsafe { processthreadsapi::GetCurrentProcessorNumber() };
+
+        //  The processor number is relative to the group of the thread, of at most 64 processors.
+        let mut node = UNKNOWN_NODE;
+
+        //  Safety:
+        //  -   `node` is valid for writes.
+        let result = unsafe { winbase::GetNumaProcessorNode(processor as u8, &mut node) };
+
+        if result == FALSE || node == UNKNOWN_NODE {
+            FALLBACKS.record(Fallback::UnknownNode);
+            return NumaNodeIndex::new(0);
+        }
+
+        NumaNodeIndex::new(node as u32)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        //  Without NUMA, the single node is node 0.
+        if !CAPABILITIES.get().numa {
+            return if node == 0 { Some(NumaNodeIndex::new(0)) } else { None };
+        }
+
+        let mut highest: u32 = 0;
+
+        //  Safety:
+        //  -   `highest` is valid for writes.
+        let result = unsafe { systemtopologyapi::GetNumaHighestNodeNumber(&mut highest) };
+
+        //  Windows does not expose the distances between nodes, hence no node is clustered with another.
+        if result == FALSE || node > highest { None } else { Some(NumaNodeIndex::new(node)) }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn now(&self) -> u64 {
+        //  Safety:
+        //  -   `LARGE_INTEGER` is plain old data.
+        let (mut counter, mut frequency): (winnt::LARGE_INTEGER, winnt::LARGE_INTEGER) =
+            unsafe { (mem::zeroed(), mem::zeroed()) };
+
+        //  Safety:
+        //  -   `counter` and `frequency` are valid for writes.
+        let result = unsafe {
+            profileapi::QueryPerformanceCounter(&mut counter) != FALSE &&
+                profileapi::QueryPerformanceFrequency(&mut frequency) != FALSE
+        };
+
+        //  The timestamps are only used for metrics, an unreadable clock merely makes them meaningless.
+        if !result {
+            return 0;
+        }
+
+        //  Safety:
+        //  -   `QuadPart` is the only variant of `LARGE_INTEGER` in use.
+        let (counter, frequency) = unsafe { (*counter.QuadPart() as u64, (*frequency.QuadPart() as u64).max(1)) };
+
+        (counter / frequency) * 1_000_000_000 + (counter % frequency) * 1_000_000_000 / frequency
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        let is_local = |other: u32| other == node.value();
+
+        working_set::reconcile(page.as_ptr() as usize, size, node.value(), os_page_size().value(), large_page_size(),
+            is_local)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resident(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> {
+        working_set::resident(pointer.as_ptr() as usize, size, os_page_size().value())
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn lock(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        //  Safety:
+        //  -   `VirtualLock` does not access the memory it locks, it only faults it in.
+        let result = unsafe { memoryapi::VirtualLock(pointer.as_ptr() as winnt::PVOID, size) };
+
+        result != FALSE
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect(&self, pointer: NonNull<u8>, size: usize, writable: bool) -> bool {
+        let protection = if writable { winnt::PAGE_READWRITE } else { winnt::PAGE_READONLY };
+
+        virtual_protect(pointer.as_ptr(), size, protection)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_code(&self, size: usize, mapping: CodeMapping) -> Option<CodeRegion> {
+        let page_size = os_page_size().value();
+        let size = size.max(1).checked_add(page_size - 1)? & !(page_size - 1);
+
+        let (writable, executable) = match mapping {
+            CodeMapping::Dual => map_dual(size)?,
+            CodeMapping::Flip => {
+                let pointer = virtual_alloc(ptr::null_mut(), size, COMMITTED, winnt::PAGE_READWRITE)?;
+                (pointer, pointer)
+            },
+        };
+
+        Some(CodeRegion::new(writable, executable, size, mapping))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_code(&self, region: CodeRegion) {
+        match region.mapping() {
+            CodeMapping::Dual => {
+                memoryapi::UnmapViewOfFile(region.writable().as_ptr() as winnt::PVOID);
+                memoryapi::UnmapViewOfFile(region.executable().as_ptr() as winnt::PVOID);
+            },
+            CodeMapping::Flip => virtual_release(region.writable().as_ptr()),
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn protect_code(&self, region: &CodeRegion, executable: bool) -> bool {
+        debug_assert!(region.mapping() == CodeMapping::Flip);
+
+        let protection = if executable { winnt::PAGE_EXECUTE_READ } else { winnt::PAGE_READWRITE };
+
+        virtual_protect(region.writable().as_ptr(), region.size(), protection)
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_stack(&self, size: usize, prefault: bool) -> Option<ThreadStack> {
+        const ALIGNMENT: PowerOf2 = LLConfiguration::LARGE_PAGE_SIZE;
+
+        //  A reservation starts on the allocation granularity, hence the guard spans it, rather than a single page.
+        let guard_size = allocation_granularity();
+        let size = size.max(1).checked_add(ALIGNMENT.value() - 1)? & !(ALIGNMENT.value() - 1);
+
+        let guard = virtual_alloc_aligned(guard_size.checked_add(size)?, ALIGNMENT.value(), guard_size, 0)?;
+        let bottom = guard.as_ptr() as usize + guard_size;
+
+        //  Safety:
+        //  -   `[guard, guard + guard_size)` is within the allocated area, and not in use.
+        if !unsafe { virtual_protect(guard.as_ptr(), guard_size, winnt::PAGE_NOACCESS) } {
+            //  Safety:
+            //  -   `guard` points to the start of the allocated area, not in use.
+            unsafe { virtual_release(guard.as_ptr()) };
+            return None;
+        }
+
+        if prefault {
+            for offset in (0..size).step_by(os_page_size().value()) {
+                //  Safety:
+                //  -   `bottom + offset` is within the usable area, writable and not in use.
+                unsafe { ptr::write_volatile((bottom + offset) as *mut u8, 0) };
+            }
+        }
+
+        Some(ThreadStack::new(guard, guard_size, size))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_stack(&self, stack: ThreadStack) {
+        let (pointer, _) = stack.mapping();
+
+        virtual_release(pointer.as_ptr());
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn map_physical(&self, size: usize) -> Option<PhysicalBuffer> {
+        const ALIGNMENT: usize = 2 * 1024 * 1024;
+
+        let size = size.max(1).checked_add(ALIGNMENT - 1)? & !(ALIGNMENT - 1);
+
+        //  Large pages are never paged out, hence need not be locked.
+        if CAPABILITIES.get().huge_tlb && large_page_size() != 0 && ALIGNMENT % large_page_size() == 0 {
+            if let Some(pointer) = virtual_alloc_aligned(size, ALIGNMENT, 0, winnt::MEM_LARGE_PAGES) {
+                return Some(PhysicalBuffer::new(pointer, size, true));
+            }
+        }
+
+        let pointer = virtual_alloc_aligned(size, ALIGNMENT, 0, 0)?;
+
+        if !self.lock(pointer, size) {
+            //  Safety:
+            //  -   `pointer` points to the start of the allocated area, not in use.
+            unsafe { virtual_release(pointer.as_ptr()) };
+            return None;
+        }
+
+        Some(PhysicalBuffer::new(pointer, size, false))
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn unmap_physical(&self, buffer: PhysicalBuffer) { virtual_release(buffer.pointer().as_ptr()) }
+
+    //  Windows does not expose the physical addresses to user space.
+    #[cold]
+    #[inline(never)]
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn environment_flag(&self, name: &[u8]) -> bool {
+        debug_assert_eq!(Some(&0), name.last());
+
+        //  Room for a single character, and the NUL terminator, which suffices to tell `0` from any other value.
+        let mut value = [0u8; 2];
+
+        //  Safety:
+        //  -   `name` is NUL-terminated.
+        //  -   `value` is valid for writes of its length.
+        //  -   `GetEnvironmentVariableA` does not allocate.
+        let length = unsafe {
+            processenv::GetEnvironmentVariableA(
+                name.as_ptr() as winnt::LPCSTR,
+                value.as_mut_ptr() as winnt::LPSTR,
+                value.len() as DWORD,
+            )
+        };
+
+        //  The length is 0 if the variable is unset, or empty, and exceeds the buffer if the value does not fit.
+        match length {
+            0 => false,
+            1 => value[0] != b'0',
+            _ => true,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn capabilities(&self) -> Capabilities { CAPABILITIES.get() }
+
+    #[cold]
+    #[inline(never)]
+    fn host_capabilities(&self) -> HostCapabilities { capabilities::detect_host(CAPABILITIES.get()) }
+
+    #[cold]
+    fn acquire_large_page_privilege(&self) -> Result<(), PrivilegeError> {
+        capabilities::acquire_lock_memory_privilege()
+    }
+
+    #[inline(always)]
+    fn mapping_latency(&self) -> Option<Duration> {
+        match MAPPING_LATENCY.load(atomic::Ordering::Relaxed) {
+            0 => None,
+            latency => Some(Duration::from_nanos(latency - 1)),
+        }
+    }
+
+    #[inline(always)]
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &FALLBACKS }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    fn system_allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        //  Safety:
+        //  -   `layout.align()` is a power of 2.
+        let pointer = unsafe { _aligned_malloc(layout.size().max(1), layout.align()) };
+
+        NonNull::new(pointer as *mut u8)
+    }
+
+    #[cfg(feature = "system-fallback")]
+    #[cold]
+    #[inline(never)]
+    unsafe fn system_deallocate(&self, pointer: NonNull<u8>) { _aligned_free(pointer.as_ptr() as winnt::PVOID) }
+
+    #[cfg(feature = "system-fallback")]
+    #[inline(always)]
+    fn owns(&self, pointer: NonNull<u8>) -> bool { OWNERSHIP.contains(pointer.as_ptr() as usize) }
+}
+
+//  Capabilities of the environment.
+static CAPABILITIES: capabilities::Detector = capabilities::Detector::new();
+
+//  Metrics of the fallbacks.
+static FALLBACKS: AtomicFallbackMetrics = AtomicFallbackMetrics::new();
+
+//  Worst latency observed mapping memory, in nanoseconds: 0 if none was observed, otherwise 1 + latency.
+static MAPPING_LATENCY: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+/// Implementation of the ThreadLocal trait, for Windows.
+///
+/// The values are stored in fiber-local storage, whose destructors, unlike those of thread-local storage, are invoked
+/// as each thread exits.
+pub(crate) struct LLThreadLocal<T> {
+    index: atomic::AtomicI64,
+    destructor: *const u8,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> LLThreadLocal<T> {
+    const UNINITIALIZED: i64 = -1;
+    const UNDER_INITIALIZATION: i64 = -2;
+    const FAILED: i64 = -3;
+
+    /// Creates an uninitialized instance.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes that `destructor` points to an `unsafe extern "system" fn(*mut c_void)` function, or compatible.
+    pub(crate) const unsafe fn new(destructor: *const u8) -> Self {
+        let index = atomic::AtomicI64::new(-1);
+        let _marker = PhantomData;
+
+        LLThreadLocal { index, destructor, _marker }
+    }
+
+    #[inline(always)]
+    fn get_index(&self) -> i64 {
+        let index = self.index.load(atomic::Ordering::Relaxed);
+        if index >= 0 { index } else { unsafe { self.initialize() } }
+    }
+
+    #[cold]
+    #[inline(never)]
+    unsafe fn initialize(&self) -> i64 { self.initialize_impl().0 }
+
+    //  Returns the index, and whether this call created it.
+    #[cold]
+    unsafe fn initialize_impl(&self) -> (i64, bool) {
+        const RELAXED: atomic::Ordering = atomic::Ordering::Relaxed;
+
+        let mut index = self.index.load(RELAXED);
+        let mut created = false;
+
+        if self.index.compare_exchange(Self::UNINITIALIZED, Self::UNDER_INITIALIZATION, RELAXED, RELAXED).is_ok() {
+            index = self.create_index();
+            created = true;
+            self.index.store(index, RELAXED);
+        }
+
+        while index == Self::UNDER_INITIALIZATION {
+            processthreadsapi::SwitchToThread();
+            index = self.index.load(RELAXED);
+        }
+
+        (index, created)
+    }
+
+    #[cold]
+    unsafe fn create_index(&self) -> i64 {
+        //  Safety:
+        //  -   fn pointers are just pointers.
+        let destructor = mem::transmute::<*const u8, Destructor>(self.destructor);
+        let index = fibersapi::FlsAlloc(Some(destructor));
+
+        if index != FLS_OUT_OF_INDEXES { index as i64 } else { Self::FAILED }
+    }
+}
+
+impl<T> ThreadLocal<T> for LLThreadLocal<T> {
+    #[cold]
+    #[inline(never)]
+    fn prepare(&self) -> bool {
+        if self.index.load(atomic::Ordering::Relaxed) >= 0 {
+            return false;
+        }
+
+        //  Safety:
+        //  -   The index is not yet initialized, or under initialization.
+        unsafe { self.initialize_impl().1 }
+    }
+
+    fn get(&self) -> Option<NonNull<T>> {
+        let index = self.index.load(atomic::Ordering::Relaxed);
+
+        //  If the index is not initialized, then no value was set.
+        if index < 0 {
+            return None;
+        }
+
+        NonNull::new(unsafe { fibersapi::FlsGetValue(index as DWORD) as *mut T })
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn set(&self, value: NonNull<T>) -> bool {
+        let index = self.get_index();
+
+        if index < 0 {
+            return false;
+        }
+
+        let result = unsafe { fibersapi::FlsSetValue(index as DWORD, value.as_ptr() as winnt::PVOID) };
+
+        result != FALSE
+    }
+}
+
+unsafe impl<T> Sync for LLThreadLocal<T> {}
+
+type Destructor = unsafe extern "system" fn(winnt::PVOID);
+
+//  Returned by `FlsAlloc` on failure.
+const FLS_OUT_OF_INDEXES: DWORD = DWORD::MAX;
+
+//  Reported by `GetNumaProcessorNode` for an unknown processor.
+const UNKNOWN_NODE: u8 = u8::MAX;
+
+//  Reserved and committed at once.
+const COMMITTED: DWORD = winnt::MEM_RESERVE | winnt::MEM_COMMIT;
+
+//  Attempts to allocate the required size in large pages, unless known to be unavailable.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn virtual_alloc_huge(size: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    if !CAPABILITIES.get().huge_tlb || large_page_size() == 0 || ALIGNMENT.value() % large_page_size() != 0 {
+        return None;
+    }
+
+    let result = virtual_alloc_aligned(size, ALIGNMENT.value(), 0, winnt::MEM_LARGE_PAGES);
+
+    match result {
+        Some(_) => FALLBACKS.record(Fallback::HugeTlbMapping),
+        None => {
+            FALLBACKS.record(Fallback::HugeTlbFailure);
+            CAPABILITIES.downgrade_huge_tlb();
+        },
+    }
+
+    result
+}
+
+//  Attempts to allocate the required size in normal pages.
+//
+//  If non-null, the result is aligned on `HUGE_PAGE_SIZE`.
+fn virtual_alloc_normal(size: usize) -> Option<NonNull<u8>> {
+    const ALIGNMENT: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+    let exact = virtual_alloc(ptr::null_mut(), size, COMMITTED, winnt::PAGE_READWRITE).and_then(|pointer| {
+        if pointer.as_ptr() as usize % ALIGNMENT == 0 {
+            return Some(pointer);
+        }
+
+        //  Safety:
+        //  -   `pointer` points to the start of the allocated area, not in use.
+        unsafe { virtual_release(pointer.as_ptr()) };
+        None
+    });
+
+    let result = exact.or_else(|| {
+        FALLBACKS.record(Fallback::MmapRetry);
+        virtual_alloc_aligned(size, ALIGNMENT.value(), 0, 0)
+    });
+
+    match result {
+        Some(_) => FALLBACKS.record(Fallback::NormalPageMapping),
+        None => FALLBACKS.record(Fallback::MmapFailure),
+    }
+
+    result
+}
+
+//  Allocates `size` bytes of memory, such that the address `offset` bytes past the start of the memory is aligned on
+//  `alignment`, a power of 2, with the `extra_flags` in addition to reserving and committing.
+//
+//  A reservation cannot be trimmed, hence the address is found by reserving an over-sized area, then releasing it
+//  and allocating anew within its bounds, which fails should another thread race for the address space in between,
+//  and is then retried.
+fn virtual_alloc_aligned(size: usize, alignment: usize, offset: usize, extra_flags: DWORD) -> Option<NonNull<u8>> {
+    const ATTEMPTS: usize = 8;
+
+    debug_assert!(alignment.is_power_of_two());
+
+    let over_size = size.checked_add(alignment)?.checked_add(offset)?;
+
+    for _ in 0..ATTEMPTS {
+        let probe = virtual_alloc(ptr::null_mut(), over_size, winnt::MEM_RESERVE, winnt::PAGE_NOACCESS)?;
+
+        let start = probe.as_ptr() as usize + offset;
+        let address = ((start + alignment - 1) & !(alignment - 1)) - offset;
+
+        //  Safety:
+        //  -   `probe` points to the start of the reserved area, not in use.
+        unsafe { virtual_release(probe.as_ptr()) };
+
+        let flags = COMMITTED | extra_flags;
+
+        if let Some(result) = virtual_alloc(address as *mut u8, size, flags, winnt::PAGE_READWRITE) {
+            return Some(result);
+        }
+    }
+
+    None
+}
+
+//  Wrapper around `VirtualAlloc`.
+//
+//  Returns a pointer to `size` bytes of memory, at `address` if not null.
+fn virtual_alloc(address: *mut u8, size: usize, allocation_type: DWORD, protection: DWORD) -> Option<NonNull<u8>> {
+    //  Safety:
+    //  -   `VirtualAlloc` fails, rather than overlaps, if `address` is already in use.
+    let result = unsafe { memoryapi::VirtualAlloc(address as winnt::PVOID, size, allocation_type, protection) };
+
+    NonNull::new(result as *mut u8)
+}
+
+//  Wrapper around `VirtualProtect`.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to an allocated area of at least `size` bytes, not otherwise in use.
+unsafe fn virtual_protect(pointer: *mut u8, size: usize, protection: DWORD) -> bool {
+    let mut previous: DWORD = 0;
+
+    memoryapi::VirtualProtect(pointer as winnt::PVOID, size, protection, &mut previous) != FALSE
+}
+
+//  Frees the `size` bytes located at `pointer`, which may span several reservations.
+//
+//  Each reservation spanned whole is released, and the parts of the others merely decommitted, their address space
+//  remaining reserved until the whole reservation is freed at once.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to an allocated area of at least `size` bytes.
+//  -   Assumes that the range `[pointer, pointer + size)` is no longer in use.
+unsafe fn virtual_free(pointer: *mut u8, size: usize) {
+    let end = pointer as usize + size;
+    let mut current = pointer as usize;
+
+    while current < end {
+        let (base, reserved) = match reservation(current) {
+            Some(reservation) => reservation,
+            None => break,
+        };
+
+        let next = end.min(base + reserved);
+
+        if base == current && next == base + reserved {
+            virtual_release(current as *mut u8);
+        } else {
+            //  Should the memory fail to be decommitted, as large pages are not, it is leaked.
+            memoryapi::VirtualFree(current as winnt::PVOID, next - current, winnt::MEM_DECOMMIT);
+        }
+
+        current = next;
+    }
+}
+
+//  Wrapper around `VirtualFree`, releasing a whole reservation.
+//
+//  #   Safety
+//
+//  -   Assumes that `pointer` points to the start of a reservation.
+//  -   Assumes that the reservation is no longer in use.
+unsafe fn virtual_release(pointer: *mut u8) {
+    if memoryapi::VirtualFree(pointer as winnt::PVOID, 0, winnt::MEM_RELEASE) == FALSE {
+        let error = errhandlingapi::GetLastError() as i32;
+        let size = reservation(pointer as usize).map_or(0, |(_, size)| size);
+
+        //  Should the memory fail to be released, it is leaked, unless the process is aborted.
+        UNMAPPING.fail(&FALLBACKS, UnmapFailure { address: pointer as usize, size, error }, abort);
+    }
+}
+
+//  Terminates the process, with the exit code of `abort`.
+fn abort() -> ! {
+    //  Safety:
+    //  -   `TerminateProcess` has no precondition, and does not return when terminating the current process.
+    unsafe { processthreadsapi::TerminateProcess(processthreadsapi::GetCurrentProcess(), 3) };
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+//  Returns the start and size of the reservation containing `address`, or None if `address` is not reserved.
+fn reservation(address: usize) -> Option<(usize, usize)> {
+    let base = query(address)?.AllocationBase as usize;
+    let mut size = 0;
+
+    //  The regions of a reservation share its base, hence its end is reached at the first region which does not.
+    while let Some(information) = query(base.checked_add(size)?) {
+        if information.AllocationBase as usize != base {
+            break;
+        }
+
+        size += information.RegionSize;
+    }
+
+    if size == 0 { None } else { Some((base, size)) }
+}
+
+//  Wrapper around `VirtualQuery`.
+//
+//  Returns the region containing `address`, or None if `address` is not reserved.
+fn query(address: usize) -> Option<winnt::MEMORY_BASIC_INFORMATION> {
+    //  Safety:
+    //  -   `MEMORY_BASIC_INFORMATION` is plain old data.
+    let mut information: winnt::MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+    let length = mem::size_of::<winnt::MEMORY_BASIC_INFORMATION>();
+
+    //  Safety:
+    //  -   `information` is valid for writes of `length` bytes.
+    let result = unsafe { memoryapi::VirtualQuery(address as winnt::PVOID, &mut information, length) };
+
+    if result == 0 || information.State == winnt::MEM_FREE || information.RegionSize == 0 {
+        return None;
+    }
+
+    Some(information)
+}
+
+//  Maps `size` bytes of shared memory twice, read-write then read-execute, returning both views.
+//
+//  The memory is backed by an anonymous section, closed once mapped, the views keeping it alive.
+fn map_dual(size: usize) -> Option<(NonNull<u8>, NonNull<u8>)> {
+    let (high, low) = ((size as u64 >> 32) as DWORD, size as DWORD);
+
+    //  Safety:
+    //  -   `INVALID_HANDLE_VALUE` requests a section backed by the paging file.
+    let section = unsafe {
+        memoryapi::CreateFileMappingW(
+            handleapi::INVALID_HANDLE_VALUE,
+            ptr::null_mut(),
+            winnt::PAGE_EXECUTE_READWRITE,
+            high,
+            low,
+            ptr::null(),
+        )
+    };
+
+    if section.is_null() {
+        return None;
+    }
+
+    let map = |access| {
+        //  Safety:
+        //  -   `section` is a valid section handle, of `size` bytes.
+        let result = unsafe { memoryapi::MapViewOfFile(section, access, 0, 0, size) };
+
+        NonNull::new(result as *mut u8)
+    };
+
+    let result = map(memoryapi::FILE_MAP_WRITE).and_then(|writable| {
+        match map(memoryapi::FILE_MAP_READ | memoryapi::FILE_MAP_EXECUTE) {
+            Some(executable) => Some((writable, executable)),
+            None => {
+                //  Safety:
+                //  -   `writable` points to a view of `size` bytes, not yet in use.
+                unsafe { memoryapi::UnmapViewOfFile(writable.as_ptr() as winnt::PVOID) };
+                None
+            },
+        }
+    });
+
+    //  Safety:
+    //  -   `section` is a valid handle, no longer needed once mapped.
+    unsafe { handleapi::CloseHandle(section) };
+
+    result
+}
+
+//  Returns the size of the OS pages.
+fn os_page_size() -> PowerOf2 {
+    const DEFAULT: PowerOf2 = unsafe { PowerOf2::new_unchecked(4096) };
+
+    let size = system_info().dwPageSize as usize;
+
+    PowerOf2::new(size).unwrap_or(DEFAULT)
+}
+
+//  Returns the granularity of the start of reservations, typically 64 KB.
+fn allocation_granularity() -> usize {
+    const DEFAULT: usize = 64 * 1024;
+
+    let granularity = system_info().dwAllocationGranularity as usize;
+
+    if granularity.is_power_of_two() { granularity } else { DEFAULT }
+}
+
+//  Returns the size of the large pages, or 0 if not supported.
+fn large_page_size() -> usize {
+    //  Safety:
+    //  -   `GetLargePageMinimum` has no precondition.
+    unsafe { memoryapi::GetLargePageMinimum() }
+}
+
+fn system_info() -> sysinfoapi::SYSTEM_INFO {
+    //  Safety:
+    //  -   `SYSTEM_INFO` is plain old data.
+    let mut info: sysinfoapi::SYSTEM_INFO = unsafe { mem::zeroed() };
+
+    //  Safety:
+    //  -   `info` is valid for writes.
+    unsafe { sysinfoapi::GetSystemInfo(&mut info) };
+
+    info
+}
+
+#[cfg(feature = "system-fallback")]
+extern "C" {
+    //  Allocates `size` bytes aligned on `alignment`, a power of 2, from the C runtime, or returns a null pointer.
+    fn _aligned_malloc(size: usize, alignment: usize) -> winnt::PVOID;
+
+    //  Frees memory allocated by `_aligned_malloc`.
+    fn _aligned_free(pointer: winnt::PVOID);
+}
@@ -0,0 +1,252 @@
+//! Detection of the capabilities of the environment.
+//!
+//! The capabilities are detected once, on first use, from the system information APIs. Large pages, backing the Huge
+//! Pages, require the `SeLockMemoryPrivilege`, which the detection attempts to enable for the process, and are
+//! additionally downgraded on the first failure to map a Huge Page with them, sparing the futile system calls of
+//! further attempts. The reason for which the privilege cannot be enabled is reported by
+//! `acquire_lock_memory_privilege`, for the embedder to act upon.
+
+use core::{
+    mem,
+    ptr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use winapi::{
+    shared::minwindef::{DWORD, FALSE},
+    um::{errhandlingapi, handleapi, memoryapi, processthreadsapi, securitybaseapi, systemtopologyapi, winbase, winnt},
+};
+
+use crate::{Capabilities, HostCapabilities, PrivilegeError, TransparentHugePagesMode};
+
+use super::os_page_size;
+
+/// Capabilities of the environment, detected on first use.
+pub(super) struct Detector(AtomicU8);
+
+impl Detector {
+    /// Creates an instance, undetected.
+    pub(super) const fn new() -> Self { Self(AtomicU8::new(0)) }
+
+    /// Returns the capabilities, detecting them if not yet detected.
+    #[inline(always)]
+    pub(super) fn get(&self) -> Capabilities {
+        let bits = match self.0.load(Ordering::Relaxed) {
+            0 => self.resolve(),
+            bits => bits,
+        };
+
+        //  Windows offers no Transparent Huge Pages, and no `/sys` to be missed.
+        Capabilities {
+            huge_tlb: bits & HUGE_TLB != 0,
+            huge_tlb_2mb: false,
+            transparent_huge_pages: false,
+            numa: bits & NUMA != 0,
+            sysfs: true,
+        }
+    }
+
+    /// Downgrades large pages, after a failure to map a Huge Page with them.
+    #[cold]
+    pub(super) fn downgrade_huge_tlb(&self) {
+        if self.0.load(Ordering::Relaxed) == 0 {
+            self.resolve();
+        }
+
+        self.0.fetch_and(!HUGE_TLB, Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self) -> u8 {
+        let detected = detect();
+
+        //  A concurrent resolution, or downgrade, takes precedence.
+        match self.0.compare_exchange(0, detected, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => detected,
+            Err(current) => current,
+        }
+    }
+}
+
+/// Detects the capabilities of the host, in details, given the `capabilities` selected.
+///
+/// Unlike the selected capabilities, the details are detected anew on each call.
+#[cold]
+pub(super) fn detect_host(capabilities: Capabilities) -> HostCapabilities {
+    let mut host = HostCapabilities::default();
+    host.capabilities = capabilities;
+
+    //  Safety:
+    //  -   `GetLargePageMinimum` has no precondition.
+    let large_page_size = unsafe { memoryapi::GetLargePageMinimum() };
+
+    if large_page_size > 0 {
+        host.add_huge_page_size(large_page_size);
+    }
+
+    host.transparent_huge_pages = TransparentHugePagesMode::Never;
+    host.numa_nodes = highest_node().map_or(1, |highest| highest + 1);
+    host.rseq = false;
+    host.mlock_limit = mlock_limit();
+    host.os_page_size = os_page_size().value();
+
+    host
+}
+
+//
+//  Implementation Details
+//
+
+const DETECTED: u8 = 1;
+const HUGE_TLB: u8 = 2;
+const NUMA: u8 = 4;
+
+const SE_LOCK_MEMORY_NAME: &[u8] = b"SeLockMemoryPrivilege\0";
+
+//  Reported by `AdjustTokenPrivileges` when some of the privileges are not held.
+const ERROR_NOT_ALL_ASSIGNED: DWORD = 1300;
+
+fn detect() -> u8 {
+    let mut bits = DETECTED;
+
+    if acquire_lock_memory_privilege().is_ok() {
+        bits |= HUGE_TLB;
+    }
+
+    if highest_node().is_some() {
+        bits |= NUMA;
+    }
+
+    bits
+}
+
+/// Enables the `SeLockMemoryPrivilege` of the process, without which large pages cannot be allocated.
+///
+/// The privilege is only granted to the accounts configured so, by the administrator, in the "Lock pages in memory"
+/// local security policy.
+#[cold]
+pub(super) fn acquire_lock_memory_privilege() -> Result<(), PrivilegeError> {
+    //  Safety:
+    //  -   `GetLargePageMinimum` has no precondition.
+    if unsafe { memoryapi::GetLargePageMinimum() } == 0 {
+        return Err(PrivilegeError::Unsupported);
+    }
+
+    let mut token: winnt::HANDLE = ptr::null_mut();
+
+    //  Safety:
+    //  -   `token` is valid for writes.
+    let opened = unsafe {
+        processthreadsapi::OpenProcessToken(
+            processthreadsapi::GetCurrentProcess(),
+            winnt::TOKEN_ADJUST_PRIVILEGES | winnt::TOKEN_QUERY,
+            &mut token,
+        )
+    };
+
+    if opened == FALSE {
+        return Err(PrivilegeError::TokenUnavailable(last_error()));
+    }
+
+    let result = adjust_lock_memory_privilege(token);
+
+    //  Safety:
+    //  -   `token` is a valid handle, not used afterwards.
+    unsafe { handleapi::CloseHandle(token) };
+
+    result
+}
+
+//  Enables the `SeLockMemoryPrivilege` of `token`, opened for adjusting privileges.
+fn adjust_lock_memory_privilege(token: winnt::HANDLE) -> Result<(), PrivilegeError> {
+    //  Safety:
+    //  -   `TOKEN_PRIVILEGES` is plain old data.
+    let mut privileges: winnt::TOKEN_PRIVILEGES = unsafe { mem::zeroed() };
+    privileges.PrivilegeCount = 1;
+    privileges.Privileges[0].Attributes = winnt::SE_PRIVILEGE_ENABLED;
+
+    //  Safety:
+    //  -   `SE_LOCK_MEMORY_NAME` is NUL-terminated.
+    //  -   `privileges.Privileges[0].Luid` is valid for writes.
+    let found = unsafe {
+        winbase::LookupPrivilegeValueA(
+            ptr::null(),
+            SE_LOCK_MEMORY_NAME.as_ptr() as winnt::LPCSTR,
+            &mut privileges.Privileges[0].Luid,
+        )
+    };
+
+    if found == FALSE {
+        return Err(PrivilegeError::LookupFailed(last_error()));
+    }
+
+    //  Safety:
+    //  -   `token` is a valid token handle, opened for adjusting privileges.
+    //  -   `privileges` holds a single privilege, as declared.
+    let adjusted = unsafe {
+        securitybaseapi::AdjustTokenPrivileges(token, FALSE, &mut privileges, 0, ptr::null_mut(), ptr::null_mut())
+    };
+
+    if adjusted == FALSE {
+        return Err(PrivilegeError::AdjustFailed(last_error()));
+    }
+
+    //  `AdjustTokenPrivileges` succeeds even if the privilege is not held, reporting it as the last error only.
+    match last_error() {
+        ERROR_NOT_ALL_ASSIGNED => Err(PrivilegeError::NotHeld),
+        _ => Ok(()),
+    }
+}
+
+fn last_error() -> DWORD {
+    //  Safety:
+    //  -   `GetLastError` has no precondition.
+    unsafe { errhandlingapi::GetLastError() }
+}
+
+//  Returns the highest NUMA node number of the machine, or None if unknown.
+fn highest_node() -> Option<u32> {
+    let mut highest: u32 = 0;
+
+    //  Safety:
+    //  -   `highest` is valid for writes.
+    let result = unsafe { systemtopologyapi::GetNumaHighestNodeNumber(&mut highest) };
+
+    if result != FALSE { Some(highest) } else { None }
+}
+
+//  Returns the limit of locked memory, in bytes, or None if unknown.
+//
+//  The memory locked by `VirtualLock` is bounded by the minimum working set size of the process, less a few pages of
+//  overhead.
+fn mlock_limit() -> Option<u64> {
+    let (mut minimum, mut maximum): (usize, usize) = (0, 0);
+
+    //  Safety:
+    //  -   `minimum` and `maximum` are valid for writes.
+    let result = unsafe {
+        winbase::GetProcessWorkingSetSize(processthreadsapi::GetCurrentProcess(), &mut minimum, &mut maximum)
+    };
+
+    if result != FALSE { Some(minimum as u64) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn detector_downgrade_huge_tlb() {
+    let detector = Detector::new();
+
+    let detected = detector.get();
+    assert_eq!(detected, detector.get());
+
+    detector.downgrade_huge_tlb();
+
+    assert_eq!(Capabilities { huge_tlb: false, ..detected }, detector.get());
+}
+
+} // mod tests
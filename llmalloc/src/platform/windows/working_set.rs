@@ -0,0 +1,150 @@
+//! Lookup of the working set of the process, with `QueryWorkingSetEx`, and of its regions, with `VirtualQuery`.
+//!
+//! The working set reports, for each virtual page, whether it is resident, whether it is backed by a large page, and
+//! which NUMA node it resides on. The regions are the ranges of pages sharing the same state and protection, which the
+//! kernel splits as the state or protection of some of their pages changes.
+
+use core::mem;
+
+use winapi::{
+    shared::minwindef::{DWORD, FALSE},
+    um::{memoryapi, processthreadsapi, psapi, winnt},
+};
+
+use crate::HugePageReport;
+
+/// Reconciles the `size` bytes located at `address`, owned by the socket of `node`, against the working set, in pages
+/// of `page_size` bytes.
+///
+/// The resident bytes backed by large pages, of `large_page` bytes, are reported as `anonymous_huge`, and the
+/// local and foreign pages are counted in pages of `page_size` bytes.
+pub(super) fn reconcile<F>(address: usize, size: usize, node: u32, page_size: usize, large_page: usize, is_local: F)
+    -> HugePageReport
+    where
+        F: Fn(u32) -> bool,
+{
+    let mut report = HugePageReport { address, size, node, ..HugePageReport::default() };
+    let mut small_pages = false;
+
+    report.mappings = count_regions(address, size);
+
+    for_each_page(address, size, page_size, |attributes| {
+        if attributes.Valid() == 0 {
+            return;
+        }
+
+        report.resident += page_size;
+
+        if attributes.LargePage() != 0 {
+            report.anonymous_huge += page_size;
+        } else {
+            small_pages = true;
+        }
+
+        if is_local(attributes.Node() as u32) {
+            report.local_pages += 1;
+        } else {
+            report.foreign_pages += 1;
+        }
+    });
+
+    report.kernel_page_size = if small_pages || report.anonymous_huge == 0 { page_size } else { large_page };
+
+    report
+}
+
+/// Returns the number of resident bytes among the `size` bytes located at `address`, in pages of `page_size` bytes.
+///
+/// Returns None if the working set cannot be queried.
+pub(super) fn resident(address: usize, size: usize, page_size: usize) -> Option<usize> {
+    let mut resident = 0;
+
+    for_each_page(address, size, page_size, |attributes| {
+        if attributes.Valid() != 0 {
+            resident += page_size;
+        }
+    })?;
+
+    Some(resident.min(size))
+}
+
+//
+//  Implementation Details
+//
+
+//  Number of pages queried at once.
+const BATCH: usize = 512;
+
+//  Invokes `f` with the attributes of each page of `page_size` bytes covering the `size` bytes located at `address`.
+//
+//  Returns None if the working set cannot be queried, in which case the pages reported so far are not to be relied
+//  upon.
+fn for_each_page<F>(address: usize, size: usize, page_size: usize, mut f: F) -> Option<()>
+    where
+        F: FnMut(&psapi::PSAPI_WORKING_SET_EX_BLOCK),
+{
+    //  Safety:
+    //  -   `PSAPI_WORKING_SET_EX_INFORMATION` is plain old data.
+    let mut entries: [psapi::PSAPI_WORKING_SET_EX_INFORMATION; BATCH] = unsafe { mem::zeroed() };
+
+    let first = address / page_size * page_size;
+    let count = (address.checked_add(size)? - first).div_ceil(page_size);
+
+    let mut index = 0;
+
+    while index < count {
+        let batch = &mut entries[..(count - index).min(BATCH)];
+
+        for (offset, entry) in batch.iter_mut().enumerate() {
+            entry.VirtualAddress = (first + (index + offset) * page_size) as winnt::PVOID;
+        }
+
+        let length = (batch.len() * mem::size_of::<psapi::PSAPI_WORKING_SET_EX_INFORMATION>()) as DWORD;
+
+        //  Safety:
+        //  -   `batch` is valid for reads and writes of `length` bytes.
+        let result = unsafe {
+            psapi::QueryWorkingSetEx(processthreadsapi::GetCurrentProcess(), batch.as_mut_ptr() as winnt::PVOID, length)
+        };
+
+        if result == FALSE {
+            return None;
+        }
+
+        for entry in batch.iter() {
+            f(&entry.VirtualAttributes);
+        }
+
+        index += batch.len();
+    }
+
+    Some(())
+}
+
+//  Returns the number of regions overlapping the `size` bytes located at `address`.
+fn count_regions(address: usize, size: usize) -> usize {
+    let end = address.saturating_add(size);
+
+    let mut regions = 0;
+    let mut current = address;
+
+    while current < end {
+        //  Safety:
+        //  -   `MEMORY_BASIC_INFORMATION` is plain old data.
+        let mut information: winnt::MEMORY_BASIC_INFORMATION = unsafe { mem::zeroed() };
+        let length = mem::size_of::<winnt::MEMORY_BASIC_INFORMATION>();
+
+        //  Safety:
+        //  -   `information` is valid for writes of `length` bytes.
+        let result = unsafe { memoryapi::VirtualQuery(current as winnt::PVOID, &mut information, length) };
+
+        if result == 0 || information.RegionSize == 0 {
+            break;
+        }
+
+        regions += 1;
+        current = (information.BaseAddress as usize).saturating_add(information.RegionSize);
+    }
+
+    regions
+}
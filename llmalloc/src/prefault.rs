@@ -0,0 +1,58 @@
+//! Prefaulting
+//!
+//! Freshly mapped memory is only backed by physical pages on first touch, each soft page fault adding to the latency of
+//! the allocation, or access, which triggers it. With prefaulting, the platform instead touches every OS page of each
+//! `HugePage` it maps, and of each Huge allocation it grows, before handing them over, so that the page faults occur
+//! within the mapping rather than on the first access.
+//!
+//! The flag is resolved from the `LLMALLOC_PREFAULT` environment variable, enabled if set to any value other than an
+//! empty string or `0`, unless set explicitly beforehand by `LLAllocator::set_prefault`.
+//!
+//! Prefaulting is honored by the platforms mapping memory from the OS on Unix; bare metal memory does not fault, and
+//! custom platforms map their memory themselves.
+
+use core::ptr::{self, NonNull};
+
+use crate::{toggle::Toggle, LLPlatform};
+
+/// Name of the environment variable selecting prefaulting, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_PREFAULT\0";
+
+/// Process-wide selection of prefaulting.
+pub(crate) struct Prefault(Toggle);
+
+impl Prefault {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self { Self(Toggle::new(ENVIRONMENT_VARIABLE)) }
+
+    /// Returns whether prefaulting is enabled, resolving it from `platform` if not yet resolved.
+    #[inline(always)]
+    pub(crate) fn is_enabled(&self, platform: &LLPlatform) -> bool { self.0.is_enabled(platform) }
+
+    /// Enables or disables prefaulting, overriding the environment.
+    pub(crate) fn set(&self, enabled: bool) { self.0.set(enabled) }
+
+    /// Touches every page of `page_size` bytes of the `size` bytes located at `pointer`, if prefaulting is enabled.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `pointer` is valid for writes of `size` bytes, not yet in use.
+    #[cfg_attr(not(all(unix, not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))),
+        allow(dead_code))]
+    #[cold]
+    #[inline(never)]
+    pub(crate) unsafe fn prefault(&self, platform: &LLPlatform, pointer: NonNull<u8>, size: usize, page_size: usize) {
+        if !self.is_enabled(platform) {
+            return;
+        }
+
+        for offset in (0..size).step_by(page_size.max(1)) {
+            //  Safety:
+            //  -   `pointer + offset` is within the `size` bytes, writable and not yet in use.
+            ptr::write_volatile(pointer.as_ptr().add(offset), 0);
+        }
+    }
+}
+
+/// Selection of prefaulting, shared by the allocator and the platforms.
+pub(crate) static PREFAULT: Prefault = Prefault::new();
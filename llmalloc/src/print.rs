@@ -0,0 +1,237 @@
+//! Printing of statistics.
+//!
+//! The statistics are printed as a human-readable, multi-section, text report, in the spirit of jemalloc's
+//! `malloc_stats_print`, suitable for pasting into tickets and comparing across runs.
+//!
+//! Each section starts with an unindented title, ending with a colon, followed by its indented lines.
+
+use core::fmt::{self, Write};
+
+use crate::{Capabilities, CategoryStatistics, FallbackMetrics, HugeTlbPools, SizeHistogram, Statistics};
+
+/// Writes the opening line of the report.
+pub(crate) fn write_begin(writer: &mut dyn Write) -> fmt::Result {
+    writeln!(writer, "___ Begin llmalloc statistics ___")
+}
+
+/// Writes the closing line of the report.
+pub(crate) fn write_end(writer: &mut dyn Write) -> fmt::Result {
+    writeln!(writer, "--- End llmalloc statistics ---")
+}
+
+/// Writes the configuration section.
+pub(crate) fn write_configuration(writer: &mut dyn Write, large_page_size: usize, huge_page_size: usize)
+    -> fmt::Result
+{
+    writeln!(writer, "Configuration:")?;
+    writeln!(writer, "  large page size: {}", large_page_size)?;
+    writeln!(writer, "  huge page size: {}", huge_page_size)
+}
+
+/// Writes the capabilities section.
+pub(crate) fn write_capabilities(writer: &mut dyn Write, capabilities: &Capabilities) -> fmt::Result {
+    writeln!(writer, "Capabilities:")?;
+
+    let mut indented = Indented::new(writer);
+    writeln!(indented, "{}", capabilities)
+}
+
+/// Writes the section of the pools of HugeTLB pages.
+pub(crate) fn write_huge_tlb_pools(writer: &mut dyn Write, pools: &HugeTlbPools) -> fmt::Result {
+    writeln!(writer, "HugeTLB pools:")?;
+
+    let mut indented = Indented::new(writer);
+    writeln!(indented, "{}", pools)
+}
+
+/// Writes a section of statistics, per category, titled `title`.
+pub(crate) fn write_statistics(writer: &mut dyn Write, title: fmt::Arguments<'_>, statistics: &Statistics)
+    -> fmt::Result
+{
+    writeln!(writer, "{}:", title)?;
+    writeln!(writer, "  {:<8}{:>14}{:>14}{:>14}{:>18}{:>18}{:>18}",
+        "", "allocations", "deallocations", "live", "allocated bytes", "deallocated bytes", "live bytes")?;
+
+    let categories = [
+        ("normal", statistics.normal),
+        ("large", statistics.large),
+        ("huge", statistics.huge),
+        ("total", statistics.total()),
+    ];
+
+    for (name, category) in &categories {
+        write_category(writer, name, category)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the section of the histogram of the requested sizes, listing the non-empty buckets only.
+pub(crate) fn write_histogram(writer: &mut dyn Write, histogram: &SizeHistogram) -> fmt::Result {
+    writeln!(writer, "Requested sizes:")?;
+
+    if histogram.total() == 0 {
+        return writeln!(writer, "  none recorded");
+    }
+
+    writeln!(writer, "  {:>20}  {:>20}{:>14}", "from", "to", "requests")?;
+
+    for (range, count) in histogram.iter() {
+        writeln!(writer, "  {:>20}  {:>20}{:>14}", range.start(), range.end(), count)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the section of the fallbacks.
+pub(crate) fn write_fallbacks(writer: &mut dyn Write, fallbacks: &FallbackMetrics) -> fmt::Result {
+    let counters = [
+        ("huge tlb mappings", fallbacks.huge_tlb_mappings),
+        ("huge tlb failures", fallbacks.huge_tlb_failures),
+        ("huge tlb exhaustions", fallbacks.huge_tlb_exhaustions),
+        ("huge tlb 2mb mappings", fallbacks.huge_tlb_2mb_mappings),
+        ("transparent huge page mappings", fallbacks.transparent_huge_page_mappings),
+        ("normal page mappings", fallbacks.normal_page_mappings),
+        ("mmap retries", fallbacks.mmap_retries),
+        ("mmap failures", fallbacks.mmap_failures),
+        ("out of range mappings", fallbacks.out_of_range_mappings),
+        ("allocation retries", fallbacks.allocation_retries),
+        ("recovered allocations", fallbacks.recovered_allocations),
+        ("unmap failures", fallbacks.unmap_failures),
+        ("lock failures", fallbacks.lock_failures),
+        ("unknown nodes", fallbacks.unknown_nodes),
+        ("uncached deallocations", fallbacks.uncached_deallocations),
+        ("remote node allocations", fallbacks.remote_node_allocations),
+        ("system allocations", fallbacks.system_allocations),
+    ];
+
+    writeln!(writer, "Fallbacks:")?;
+
+    for (name, count) in &counters {
+        writeln!(writer, "  {:<32}{:>14}", name, count)?;
+    }
+
+    Ok(())
+}
+
+//
+//  Implementation Details
+//
+
+fn write_category(writer: &mut dyn Write, name: &str, category: &CategoryStatistics) -> fmt::Result {
+    writeln!(writer, "  {:<8}{:>14}{:>14}{:>14}{:>18}{:>18}{:>18}",
+        name,
+        category.allocations,
+        category.deallocations,
+        category.live(),
+        category.allocated_bytes,
+        category.deallocated_bytes,
+        category.live_bytes())
+}
+
+//  Indents each line written to the underlying writer by 2 spaces.
+struct Indented<'a> {
+    writer: &'a mut dyn Write,
+    //  Whether the next character written starts a line.
+    line_start: bool,
+}
+
+impl<'a> Indented<'a> {
+    fn new(writer: &'a mut dyn Write) -> Self { Self { writer, line_start: true } }
+}
+
+impl Write for Indented<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (index, line) in s.split('\n').enumerate() {
+            if index > 0 {
+                self.writer.write_char('\n')?;
+                self.line_start = true;
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if self.line_start {
+                self.writer.write_str("  ")?;
+                self.line_start = false;
+            }
+
+            self.writer.write_str(line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use std::{string::String, vec::Vec};
+
+use super::*;
+
+#[test]
+fn write_statistics_table() {
+    let normal = CategoryStatistics { allocations: 3, deallocations: 1, allocated_bytes: 48, deallocated_bytes: 16 };
+    let statistics = Statistics { normal, ..Statistics::default() };
+
+    let mut output = String::new();
+    write_statistics(&mut output, format_args!("Socket {}", 1), &statistics).unwrap();
+
+    let lines: Vec<_> = output.lines().collect();
+
+    assert_eq!(6, lines.len(), "{}", output);
+    assert_eq!("Socket 1:", lines[0]);
+    assert!(lines[1].contains("allocations") && lines[1].contains("live bytes"), "{}", lines[1]);
+
+    let normal: Vec<_> = lines[2].split_whitespace().collect();
+    assert_eq!(["normal", "3", "1", "2", "48", "16", "32"], &normal[..]);
+
+    let total: Vec<_> = lines[5].split_whitespace().collect();
+    assert_eq!(["total", "3", "1", "2", "48", "16", "32"], &total[..]);
+}
+
+#[test]
+fn write_histogram_buckets() {
+    let mut output = String::new();
+    write_histogram(&mut output, &SizeHistogram::default()).unwrap();
+
+    assert_eq!("Requested sizes:\n  none recorded\n", output);
+
+    let mut buckets = [0; SizeHistogram::BUCKETS];
+    buckets[4] = 7;
+
+    let mut output = String::new();
+    write_histogram(&mut output, &SizeHistogram::from_buckets(buckets)).unwrap();
+
+    let last: Vec<_> = output.lines().last().unwrap().split_whitespace().collect();
+    assert_eq!(["9", "16", "7"], &last[..]);
+}
+
+#[test]
+fn write_capabilities_indented() {
+    let capabilities =
+        Capabilities { huge_tlb: false, huge_tlb_2mb: false, transparent_huge_pages: true, numa: false, sysfs: true };
+
+    let mut output = String::new();
+    write_capabilities(&mut output, &capabilities).unwrap();
+
+    assert_eq!(
+        "Capabilities:\n  \
+         llmalloc: HugeTLB unavailable, Huge Pages backed by Transparent Huge Pages\n  \
+         llmalloc: NUMA topology unavailable, single socket shared by all threads\n",
+        output);
+}
+
+#[test]
+fn write_huge_tlb_pools_indented() {
+    let mut output = String::new();
+    write_huge_tlb_pools(&mut output, &HugeTlbPools::default()).unwrap();
+
+    assert_eq!("HugeTLB pools:\n  llmalloc: no huge tlb pool probed\n", output);
+}
+
+} // mod tests
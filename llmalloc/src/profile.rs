@@ -0,0 +1,72 @@
+//! Profile
+//!
+//! The cache of each thread holds a single `LargePage` per Normal class size, filled on demand as the thread allocates,
+//! and kept until flushed. This suits most threads, yet neither the latency-critical threads, whose first allocations
+//! of each class size take the slow path, nor the background threads, whose caches pin memory they rarely reuse.
+//!
+//! Hence the embedder may classify its threads as they register, see `LLAllocator::register_thread_with`:
+//!
+//! -   The latency-critical threads are declared Critical, dipping into the reserve of Critical threads once the
+//!     socket is exhausted, see `LLAllocator::reserve_critical`, and their caches are pre-filled with a `LargePage`
+//!     for each Normal class size, so that none of their Normal allocations takes the slow path until exhausted.
+//! -   The background threads are declared Background, and their caches are flushed every `FLUSH_PERIOD`-th
+//!     allocation, returning their memory to the socket, at the cost of refilling their caches more often.
+//!
+//! The profile is recorded as the criticality of the thread, hence a thread declaring its criticality afterwards, see
+//! `LLAllocator::set_criticality`, changes its profile, bar the pre-filling.
+
+use llmalloc_core::Criticality;
+
+/// Number of allocations of a Background thread between two flushes of its cache.
+pub(crate) const FLUSH_PERIOD: usize = 256;
+
+/// Profile of a thread, as declared when it registers, see `LLAllocator::register_thread_with`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ThreadProfile {
+    /// Normal.
+    ///
+    /// The cache of the thread is filled on demand, and kept until flushed.
+    #[default]
+    Normal,
+    /// Latency-critical.
+    ///
+    /// The thread is declared Critical, and its cache is pre-filled with a `LargePage` for each Normal class size.
+    LatencyCritical,
+    /// Background.
+    ///
+    /// The thread is declared Background, and its cache is flushed periodically, so as to remain minimal.
+    Background,
+}
+
+impl ThreadProfile {
+    /// Returns the criticality declared for the threads of this profile.
+    pub const fn criticality(&self) -> Criticality {
+        match self {
+            ThreadProfile::Normal => Criticality::Normal,
+            ThreadProfile::LatencyCritical => Criticality::Critical,
+            ThreadProfile::Background => Criticality::Background,
+        }
+    }
+
+    /// Returns whether the caches of the threads of this profile are pre-filled as they register.
+    pub const fn is_prefilled(&self) -> bool { matches!(self, ThreadProfile::LatencyCritical) }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn thread_profile_criticality() {
+    assert_eq!(ThreadProfile::Normal, ThreadProfile::default());
+
+    assert_eq!(Criticality::Normal, ThreadProfile::Normal.criticality());
+    assert_eq!(Criticality::Critical, ThreadProfile::LatencyCritical.criticality());
+    assert_eq!(Criticality::Background, ThreadProfile::Background.criticality());
+
+    assert!(ThreadProfile::LatencyCritical.is_prefilled());
+    assert!(!ThreadProfile::Background.is_prefilled());
+}
+
+} // mod tests
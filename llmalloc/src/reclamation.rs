@@ -0,0 +1,251 @@
+//! Deferred Reclamation
+//!
+//! Lock-free data structures cannot free a node as soon as it is unlinked, as concurrent readers may still be
+//! accessing it. The deferred reclamation retires such nodes instead, freeing them only once no reader may still
+//! access them, using epoch-based reclamation:
+//!
+//! -   A reader pins the current epoch for the duration of its accesses, see `LLAllocator::pin`.
+//! -   A retired node is stamped with the current epoch, see `LLAllocator::defer_free`.
+//! -   The epoch advances once all pinned readers have observed it, and a node is freed once the epoch advanced twice
+//!     past its stamp, at which point any reader which could have observed it has unpinned.
+//!
+//! The content of a retired node must remain intact for the pinned readers, hence each retirement is recorded in a
+//! small allocation of its own, `RECORD_LAYOUT`, freed alongside the node. The retired nodes are collected every
+//! `PERIOD` retirements, or on demand, by whichever thread retires or requests the collection.
+//!
+//! The readers pin one of a fixed number of slots, and pinning fails once all are in use.
+
+use core::{
+    alloc::Layout,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering},
+};
+
+/// Epoch-based reclamation of the retired allocations.
+pub(crate) struct Reclamation {
+    epoch: AtomicU64,
+    //  Pinned epoch of each slot, times 2, plus 1; 0 if unpinned.
+    pins: [AtomicU64; CAPACITY],
+    //  Stack of the records of the retired allocations.
+    retired: AtomicPtr<Record>,
+    //  Number of retirements, modulo `PERIOD`.
+    retirements: AtomicUsize,
+}
+
+impl Reclamation {
+    /// Layout of the record of a retirement.
+    pub(crate) const RECORD_LAYOUT: Layout = Layout::new::<Record>();
+
+    /// Creates an instance, with nothing pinned nor retired.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const UNPINNED: AtomicU64 = AtomicU64::new(0);
+
+        Self {
+            epoch: AtomicU64::new(0),
+            pins: [UNPINNED; CAPACITY],
+            retired: AtomicPtr::new(ptr::null_mut()),
+            retirements: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pins the current epoch, returning the pinned slot.
+    ///
+    /// Returns None if all slots are pinned.
+    pub(crate) fn pin(&self) -> Option<usize> {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+
+        self.pins.iter()
+            .position(|pin| pin.compare_exchange(0, epoch * 2 + 1, Ordering::SeqCst, Ordering::Relaxed).is_ok())
+    }
+
+    /// Unpins the slot `slot`.
+    pub(crate) fn unpin(&self, slot: usize) { self.pins[slot].store(0, Ordering::SeqCst); }
+
+    /// Retires the allocation at `pointer`, recorded in `record`, returning whether a collection is due.
+    ///
+    /// #   Safety
+    ///
+    /// -   Assumes `record` is valid for writes of `RECORD_LAYOUT`, and is not otherwise in use.
+    /// -   Assumes the memory at `pointer` is no longer reachable, except by the readers currently pinned.
+    pub(crate) unsafe fn retire(&self, record: NonNull<u8>, pointer: NonNull<u8>) -> bool {
+        let record = record.cast::<Record>();
+
+        record.as_ptr().write(Record { next: ptr::null_mut(), pointer, epoch: self.epoch.load(Ordering::SeqCst) });
+
+        self.push(record, record);
+
+        (self.retirements.fetch_add(1, Ordering::Relaxed) + 1).is_multiple_of(PERIOD)
+    }
+
+    /// Advances the epoch, if possible, then invokes `free` on each retired allocation no reader may still access, and
+    /// on its record.
+    ///
+    /// Returns the number of retired allocations freed.
+    #[cold]
+    pub(crate) fn collect<F>(&self, mut free: F) -> usize
+        where
+            F: FnMut(NonNull<u8>),
+    {
+        let epoch = self.try_advance();
+
+        let mut current = self.retired.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut kept: Option<(NonNull<Record>, NonNull<Record>)> = None;
+        let mut freed = 0;
+
+        while let Some(record) = NonNull::new(current) {
+            //  Safety:
+            //  -   `record` was written by `retire`, and is exclusively owned since the swap.
+            let Record { next, pointer, epoch: stamp } = unsafe { record.as_ptr().read() };
+
+            current = next;
+
+            if stamp + 2 <= epoch {
+                freed += 1;
+                free(pointer);
+                free(record.cast());
+                continue;
+            }
+
+            //  Safety:
+            //  -   `record` is exclusively owned since the swap.
+            unsafe { (*record.as_ptr()).next = kept.map_or(ptr::null_mut(), |(head, _)| head.as_ptr()) };
+
+            kept = Some((record, kept.map_or(record, |(_, tail)| tail)));
+        }
+
+        if let Some((head, tail)) = kept {
+            self.push(head, tail);
+        }
+
+        freed
+    }
+
+    //  Advances the epoch if all pinned slots observed it, returning the current epoch.
+    fn try_advance(&self) -> u64 {
+        let epoch = self.epoch.load(Ordering::SeqCst);
+
+        let observed = self.pins.iter().all(|pin| {
+            let pin = pin.load(Ordering::SeqCst);
+
+            pin == 0 || pin / 2 == epoch
+        });
+
+        if !observed {
+            return epoch;
+        }
+
+        match self.epoch.compare_exchange(epoch, epoch + 1, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => epoch + 1,
+            Err(current) => current,
+        }
+    }
+
+    //  Pushes the chain from `head` to `tail` onto the retired stack.
+    fn push(&self, head: NonNull<Record>, tail: NonNull<Record>) {
+        let mut current = self.retired.load(Ordering::Relaxed);
+
+        loop {
+            //  Safety:
+            //  -   `tail` is exclusively owned until pushed.
+            unsafe { (*tail.as_ptr()).next = current };
+
+            match self.retired.compare_exchange_weak(current, head.as_ptr(), Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  Maximum number of slots pinned at any time.
+const CAPACITY: usize = 128;
+
+//  Number of retirements between collections.
+const PERIOD: usize = 64;
+
+//  The record of a retired allocation.
+struct Record {
+    next: *mut Record,
+    pointer: NonNull<u8>,
+    epoch: u64,
+}
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use std::vec::Vec;
+
+use super::*;
+
+//  Both the nodes and the records are allocated as `RECORD_LAYOUT`, so as to be freed alike.
+fn allocate() -> NonNull<u8> {
+    NonNull::new(unsafe { std::alloc::alloc(Reclamation::RECORD_LAYOUT) }).expect("Allocated")
+}
+
+fn free(pointer: NonNull<u8>) { unsafe { std::alloc::dealloc(pointer.as_ptr(), Reclamation::RECORD_LAYOUT) } }
+
+fn retire(reclamation: &Reclamation) -> bool { unsafe { reclamation.retire(allocate(), allocate()) } }
+
+#[test]
+fn reclamation_collect() {
+    let reclamation = Reclamation::new();
+
+    retire(&reclamation);
+
+    //  Each collection advances the epoch, freeing the node once advanced twice past its stamp.
+    assert_eq!(0, reclamation.collect(free));
+    assert_eq!(1, reclamation.collect(free));
+    assert_eq!(0, reclamation.collect(free));
+}
+
+#[test]
+fn reclamation_pinned() {
+    let reclamation = Reclamation::new();
+
+    let slot = reclamation.pin().unwrap();
+
+    retire(&reclamation);
+    retire(&reclamation);
+
+    //  The epoch advances once, as observed by the pinned slot, but not twice.
+    for _ in 0..4 {
+        assert_eq!(0, reclamation.collect(free));
+    }
+
+    reclamation.unpin(slot);
+
+    assert_eq!(2, reclamation.collect(free));
+}
+
+#[test]
+fn reclamation_pin_exhausted() {
+    let reclamation = Reclamation::new();
+
+    let slots: Vec<_> = (0..CAPACITY).map(|_| reclamation.pin().unwrap()).collect();
+    assert_eq!(None, reclamation.pin());
+
+    reclamation.unpin(slots[7]);
+    assert_eq!(Some(7), reclamation.pin());
+}
+
+#[test]
+fn reclamation_retire_period() {
+    let reclamation = Reclamation::new();
+
+    let due: Vec<_> = (0..PERIOD).map(|_| retire(&reclamation)).collect();
+
+    assert!(due[..PERIOD - 1].iter().all(|due| !due));
+    assert!(due[PERIOD - 1]);
+
+    assert_eq!(0, reclamation.collect(free));
+    assert_eq!(PERIOD, reclamation.collect(free));
+}
+
+} // mod tests
@@ -0,0 +1,134 @@
+//! Registration
+//!
+//! The thread-local state of the allocator is created implicitly, on the first allocation of each thread, and released
+//! by the destructor of the thread-local storage, as the thread exits. The threads created by raw `clone`, or by some
+//! foreign runtimes, never run this destructor, hence the memory cached by their state leaks as they exit.
+//!
+//! Such threads are instead to register with the allocator, with `LLAllocator::register_thread`, and to unregister
+//! prior to exiting, with `LLAllocator::unregister_thread`, releasing their state. The registration may further be
+//! required, with `LLAllocator::set_thread_registration`, in which case the state of the unregistered threads is no
+//! longer created implicitly:
+//!
+//! -   Their allocations are served from a single state, shared by all unregistered threads, under a lock.
+//! -   Their deallocations are returned to the heap directly, bypassing any cache.
+//!
+//! This shared slow path strands no memory, at the cost of serializing the unregistered threads. Their allocations are
+//! counted, see `LLAllocator::unregistered_allocations`, for their threads to be tracked down and registered. Neither
+//! `allocate_on_node`, nor the facilities of the thread-local state, such as frame mode, are available to them.
+//!
+//! The unregistered threads are detected as having no thread-local state, hence each thread is to have thread-local
+//! storage of its own: a thread created by raw `clone` without `CLONE_SETTLS` shares that of its parent, and is served
+//! as its parent, registered or not.
+
+use core::{
+    hint,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+};
+
+/// Process-wide selection of the registration of threads, and the state shared by the unregistered threads.
+pub(crate) struct Registration {
+    required: AtomicBool,
+    //  Whether the shared state is in use.
+    lock: AtomicBool,
+    //  The shared state, or null if not yet created.
+    shared: AtomicPtr<u8>,
+    //  Number of allocations attempted from the shared state.
+    unregistered: AtomicUsize,
+}
+
+impl Registration {
+    /// Creates an instance, not requiring registration.
+    pub(crate) const fn new() -> Self {
+        Self {
+            required: AtomicBool::new(false),
+            lock: AtomicBool::new(false),
+            shared: AtomicPtr::new(ptr::null_mut()),
+            unregistered: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns whether the registration of threads is required.
+    #[inline(always)]
+    pub(crate) fn is_required(&self) -> bool { self.required.load(Ordering::Relaxed) }
+
+    /// Selects whether the registration of threads is required.
+    pub(crate) fn set(&self, required: bool) { self.required.store(required, Ordering::Relaxed); }
+
+    /// Returns the number of allocations attempted from the shared state, since the start of the process.
+    pub(crate) fn unregistered(&self) -> usize { self.unregistered.load(Ordering::Relaxed) }
+
+    /// Invokes `f` with the shared state, under the lock, creating it with `create` if not yet created.
+    ///
+    /// Returns None, without invoking `f`, if the shared state cannot be created.
+    #[cold]
+    pub(crate) fn with_shared<R, C, F>(&self, create: C, f: F) -> Option<R>
+        where
+            C: FnOnce() -> Option<NonNull<u8>>,
+            F: FnOnce(NonNull<u8>) -> R,
+    {
+        while self.lock.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            hint::spin_loop();
+        }
+
+        let shared = NonNull::new(self.shared.load(Ordering::Relaxed)).or_else(|| {
+            let shared = create()?;
+            self.shared.store(shared.as_ptr(), Ordering::Relaxed);
+            Some(shared)
+        });
+
+        let result = shared.map(f);
+
+        self.lock.store(false, Ordering::Release);
+
+        if result.is_some() {
+            self.unregistered.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result
+    }
+
+    /// Abandons the shared state, if in use, for another to be created on first use.
+    ///
+    /// To be called in the child of a fork, where the thread using it no longer exists, the shared state being left in
+    /// an unknown state, and thus leaked.
+    #[cold]
+    pub(crate) fn recover(&self) {
+        if self.lock.load(Ordering::Relaxed) {
+            self.shared.store(ptr::null_mut(), Ordering::Relaxed);
+            self.lock.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Selection of the registration of threads, shared by the allocator and its threads.
+pub(crate) static REGISTRATION: Registration = Registration::new();
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn registration_shared() {
+    let registration = Registration::new();
+    let mut value = 0u8;
+
+    assert!(!registration.is_required());
+
+    registration.set(true);
+    assert!(registration.is_required());
+
+    //  Without a shared state, `f` is not invoked.
+    assert_eq!(None, registration.with_shared(|| None, |_| 1));
+    assert_eq!(0, registration.unregistered());
+
+    //  The shared state is created once, on first use.
+    let state = NonNull::from(&mut value);
+
+    assert_eq!(Some(state), registration.with_shared(|| Some(state), |shared| shared));
+    assert_eq!(Some(state), registration.with_shared(|| None, |shared| shared));
+    assert_eq!(2, registration.unregistered());
+}
+
+} // mod tests
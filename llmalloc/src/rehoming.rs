@@ -0,0 +1,27 @@
+//! Rehoming
+//!
+//! A thread is served by the socket of the NUMA node it runs on when it first allocates, and keeps its thread cache on
+//! that socket thereafter. A long-lived thread rescheduled onto another node thus keeps allocating from the memory of
+//! its former node, hitting remote memory for as long as it lives.
+//!
+//! With rehoming, each thread checks the node it runs on every `PERIOD`-th allocation, and, on finding it served by
+//! another socket, retires its thread cache to its former socket and acquires one from the socket of its new node. The
+//! allocations already made stay where they are, and are deallocated as any allocation of another socket. A thread may
+//! also rehome itself on demand, through `LLAllocator::rehome_thread`, for example once pinned to the CPUs of a node.
+//!
+//! The threads in frame mode, or within a scope forbidding allocations, are not rehomed, and neither are the threads
+//! of a platform supplied by the embedder, whose thread-local storage cannot be replaced.
+//!
+//! The flag is resolved from the `LLMALLOC_REHOME` environment variable, enabled if set to any value other than an
+//! empty string or `0`, unless set explicitly beforehand by `LLAllocator::set_rehoming`.
+
+use crate::toggle::Toggle;
+
+/// Name of the environment variable selecting rehoming, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_REHOME\0";
+
+/// Number of allocations of a thread between two checks of its node.
+pub(crate) const PERIOD: usize = 4096;
+
+/// Selection of rehoming, shared by the allocator and its threads.
+pub(crate) static REHOMING: Toggle = Toggle::arming(ENVIRONMENT_VARIABLE);
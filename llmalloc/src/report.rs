@@ -0,0 +1,123 @@
+//! Reports
+//!
+//! Reports cross-check the allocator's view of its memory against the kernel's view, to help diagnose anomalies such
+//! as Transparent Huge Pages being split, or pages being migrated to a different NUMA node, and to tell apart the
+//! retained memory which costs RSS from the retained memory which does not.
+
+/// Reconciliation of a single `HugePage` against the kernel's view of it.
+///
+/// The kernel reports its statistics per mapping, and a single mapping may span multiple `HugePage` if they were
+/// allocated next to one another. In this case, the statistics of the mapping are apportioned according to the
+/// fraction of the mapping covered by the `HugePage`, hence are approximate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HugePageReport {
+    /// The address of the `HugePage`.
+    pub address: usize,
+    /// The size of the `HugePage`, in bytes.
+    pub size: usize,
+    /// The NUMA node of the socket owning the `HugePage`.
+    pub node: u32,
+    /// The number of kernel mappings covering the `HugePage`.
+    ///
+    /// A `HugePage` is allocated as a single mapping, hence more than 1 means the kernel split the mapping.
+    pub mappings: usize,
+    /// The smallest kernel page size backing the `HugePage`, in bytes.
+    pub kernel_page_size: usize,
+    /// The number of resident bytes.
+    pub resident: usize,
+    /// The number of resident bytes backed by Transparent Huge Pages.
+    pub anonymous_huge: usize,
+    /// The number of kernel pages residing on the node of the socket, or a node clustered with it.
+    pub local_pages: usize,
+    /// The number of kernel pages residing on any other node.
+    pub foreign_pages: usize,
+}
+
+impl HugePageReport {
+    /// Returns whether the kernel split the `HugePage`.
+    ///
+    /// A `HugePage` is split if its mapping was split, or if it is not backed by a kernel page of the same size and
+    /// some of its resident bytes are not backed by Transparent Huge Pages.
+    pub fn is_split(&self) -> bool {
+        self.mappings > 1 ||
+            (self.kernel_page_size < self.size && self.anonymous_huge < self.resident)
+    }
+
+    /// Returns whether some of the kernel pages backing the `HugePage` were migrated away from its node.
+    pub fn is_migrated(&self) -> bool { self.foreign_pages > 0 }
+
+    /// Returns whether the `HugePage` is anomalous, that is either split or migrated.
+    pub fn is_anomalous(&self) -> bool { self.is_split() || self.is_migrated() }
+}
+
+/// Residency of a range of memory retained by the allocator, as reported by the kernel.
+///
+/// Retained memory costs RSS only as long as it is resident: memory never touched since it was mapped, or reclaimed by
+/// the kernel, is retained at no cost but that of its addresses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResidencyReport {
+    /// The address of the range.
+    pub address: usize,
+    /// The size of the range, in bytes.
+    pub size: usize,
+    /// The NUMA node of the socket owning the range, if a `HugePage`, or None if a Huge block retained for reuse.
+    pub node: Option<u32>,
+    /// The number of resident bytes.
+    pub resident: usize,
+}
+
+impl ResidencyReport {
+    /// Returns the number of bytes which are not resident, whether never touched or reclaimed.
+    pub fn reclaimed(&self) -> usize { self.size - self.resident }
+
+    /// Returns whether the range is fully resident.
+    pub fn is_resident(&self) -> bool { self.resident == self.size }
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+const HUGE_PAGE_SIZE: usize = 1 << 30;
+
+fn report(mappings: usize, kernel_page_size: usize, resident: usize, anonymous_huge: usize) -> HugePageReport {
+    let size = HUGE_PAGE_SIZE;
+    HugePageReport { size, mappings, kernel_page_size, resident, anonymous_huge, ..HugePageReport::default() }
+}
+
+#[test]
+fn huge_page_report_is_split() {
+    //  Backed by a 1GB page, or fully backed by THP.
+    assert!(!report(1, HUGE_PAGE_SIZE, HUGE_PAGE_SIZE, 0).is_split());
+    assert!(!report(1, 4096, HUGE_PAGE_SIZE, HUGE_PAGE_SIZE).is_split());
+    assert!(!report(1, 4096, 0, 0).is_split());
+
+    //  Split mapping, or partially backed by THP.
+    assert!(report(2, HUGE_PAGE_SIZE, HUGE_PAGE_SIZE, 0).is_split());
+    assert!(report(1, 4096, HUGE_PAGE_SIZE, HUGE_PAGE_SIZE / 2).is_split());
+}
+
+#[test]
+fn huge_page_report_is_migrated() {
+    let local = HugePageReport { local_pages: 3, ..report(1, HUGE_PAGE_SIZE, HUGE_PAGE_SIZE, 0) };
+    assert!(!local.is_migrated());
+    assert!(!local.is_anomalous());
+
+    let foreign = HugePageReport { foreign_pages: 1, ..local };
+    assert!(foreign.is_migrated());
+    assert!(foreign.is_anomalous());
+}
+
+#[test]
+fn residency_report_reclaimed() {
+    let report = ResidencyReport { size: HUGE_PAGE_SIZE, resident: HUGE_PAGE_SIZE / 4, ..ResidencyReport::default() };
+    assert_eq!(HUGE_PAGE_SIZE / 4 * 3, report.reclaimed());
+    assert!(!report.is_resident());
+
+    let resident = ResidencyReport { resident: HUGE_PAGE_SIZE, ..report };
+    assert_eq!(0, resident.reclaimed());
+    assert!(resident.is_resident());
+}
+
+} // mod tests
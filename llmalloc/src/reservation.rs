@@ -0,0 +1,430 @@
+//! Reservation
+//!
+//! Memory mapped piecemeal from the OS is scattered across the address space, interleaved with the mappings of others,
+//! hence telling whether a pointer belongs to the heap requires a map, and a reference to memory of the heap a full
+//! pointer. With a reservation, the platform instead reserves a single contiguous range of the address space on its
+//! first mapping, inaccessible and committing no memory, then commits each `HugePage` it maps, and each Huge
+//! allocation, out of it on demand, and decommits them back into it once unmapped. All the memory of the heap lying
+//! within the reservation, membership is a mere bounds check, and memory may be referred to by its offset within the
+//! reservation, see `Reservation::offset`.
+//!
+//! The reservation is carved into chunks of the Huge Page size, of which there are at most `MAXIMUM_CHUNKS`, that is
+//! 1 TB of address space with 1 GB Huge Pages, and 2 GB with the 2 MB Huge Pages of `small-heap`. Once the reservation
+//! is exhausted, mappings fail, rather than spill outside of it. A Huge allocation which cannot be resized in place is
+//! moved by copy, onto other chunks.
+//!
+//! As a mapping is unmapped according to where it lies, the selection is latched by the first mapping, after which it
+//! can no longer change. The flag is resolved from the `LLMALLOC_RESERVE` environment variable, enabled with the
+//! default size if set to any value other than an empty string or `0`, unless set explicitly beforehand by
+//! `LLAllocator::set_reservation`.
+//!
+//! A reservation is honored by the platform of Linux, its memory being committed with `mprotect`, hence backed by
+//! Normal pages, possibly promoted to Transparent Huge Pages, and never by HugeTLB pages, nor flanked by guard pages.
+//! With a shared heap, the reservation is a mapping of its file, unless backed by HugeTLB pages, which cannot be
+//! reserved without committing them.
+
+#![cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "bare-metal",
+    feature = "custom-platform", feature = "test-platform", feature = "no-libc")))), allow(dead_code))]
+
+use core::{
+    hint,
+    ptr::NonNull,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use llmalloc_core::{Configuration, PowerOf2};
+
+use crate::{LLConfiguration, Platform, LLPlatform};
+
+/// Name of the environment variable selecting a reservation, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_RESERVE\0";
+
+/// Maximum number of chunks of a reservation, each of the Huge Page size.
+pub(crate) const MAXIMUM_CHUNKS: usize = WORDS * 64;
+
+/// Size of the reservation selected by the environment: 64 GB, or the maximum of 2 GB with `small-heap`.
+#[cfg(not(feature = "small-heap"))]
+pub(crate) const DEFAULT_SIZE: usize = 64 * HUGE_PAGE_SIZE.value();
+
+/// Size of the reservation selected by the environment: 64 GB, or the maximum of 2 GB with `small-heap`.
+#[cfg(feature = "small-heap")]
+pub(crate) const DEFAULT_SIZE: usize = MAXIMUM_CHUNKS * HUGE_PAGE_SIZE.value();
+
+/// Reservation of the address space, as returned by `LLAllocator::reservation`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Reservation {
+    /// The address of the start of the reservation, aligned on the Huge Page size.
+    pub base: usize,
+    /// The size of the reservation, in bytes.
+    pub size: usize,
+    /// The size of the memory committed out of the reservation, in bytes.
+    pub committed: usize,
+}
+
+impl Reservation {
+    /// Returns whether `pointer` lies within the reservation.
+    pub fn contains(&self, pointer: *const u8) -> bool { self.offset(pointer).is_some() }
+
+    /// Returns the offset of `pointer` within the reservation, or None if it lies outside.
+    pub fn offset(&self, pointer: *const u8) -> Option<usize> {
+        let offset = (pointer as usize).wrapping_sub(self.base);
+
+        if offset < self.size { Some(offset) } else { None }
+    }
+
+    /// Returns the pointer at `offset` within the reservation, or None if it lies outside.
+    pub fn pointer(&self, offset: usize) -> Option<NonNull<u8>> {
+        if offset < self.size { NonNull::new((self.base + offset) as *mut u8) } else { None }
+    }
+}
+
+/// Process-wide selection of a reservation, and its chunks.
+pub(crate) struct AddressSpace {
+    //  The size selected, with the LATCHED bit, or UNRESOLVED.
+    size: AtomicUsize,
+    //  The base of the reservation, or one of the BASE_ sentinels.
+    base: AtomicUsize,
+    //  One bit per chunk, set if committed.
+    chunks: [AtomicU64; WORDS],
+}
+
+impl AddressSpace {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+
+        Self { size: AtomicUsize::new(UNRESOLVED), base: AtomicUsize::new(BASE_UNSET), chunks: [ZERO; WORDS] }
+    }
+
+    /// Returns the size of the reservation selected, if any, resolving it from `platform` if not yet resolved.
+    pub(crate) fn size(&self, platform: &LLPlatform) -> Option<usize> {
+        Some(self.resolve(platform)).filter(|size| *size != DISABLED)
+    }
+
+    /// Selects the size of the reservation, or none, overriding the environment.
+    ///
+    /// Returns Err if the size is 0 or exceeds `MAXIMUM_CHUNKS` chunks, once rounded up to a whole number of chunks, or
+    /// if the selection differs from the one already latched.
+    pub(crate) fn set(&self, size: Option<usize>) -> Result<(), ()> {
+        let selected = match size {
+            None => DISABLED,
+            Some(size) => Self::round(size).ok_or(())?,
+        };
+
+        let mut current = self.size.load(Ordering::Relaxed);
+
+        loop {
+            if current & LATCHED != 0 {
+                return if current & !LATCHED == selected { Ok(()) } else { Err(()) };
+            }
+
+            match self.size.compare_exchange(current, selected, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Returns the reservation, if reserved.
+    pub(crate) fn reservation(&self) -> Option<Reservation> {
+        let (base, size) = self.range()?;
+
+        let chunks: u32 = self.chunks.iter().map(|word| word.load(Ordering::Relaxed).count_ones()).sum();
+
+        Some(Reservation { base, size, committed: chunks as usize * HUGE_PAGE_SIZE.value() })
+    }
+
+    /// Returns whether the address space is reserved, resolving the selection from `platform` if not yet resolved,
+    /// latching it, and invoking `create` to reserve the selected size, once, if selected.
+    ///
+    /// To be called by the platform on each mapping; `create` returns the base of the reservation, aligned on the
+    /// Huge Page size, or None if the address space could not be reserved.
+    #[inline(always)]
+    pub(crate) fn latch(&self, platform: &LLPlatform, create: fn(usize) -> Option<NonNull<u8>>) -> bool {
+        match self.base.load(Ordering::Acquire) {
+            BASE_UNSET | BASE_CREATING => self.latch_slow(platform, create),
+            base => base != BASE_NONE,
+        }
+    }
+
+    /// Returns whether `pointer` lies within the reservation.
+    #[inline(always)]
+    pub(crate) fn contains(&self, pointer: NonNull<u8>) -> bool {
+        self.range().is_some_and(|(base, size)| (pointer.as_ptr() as usize).wrapping_sub(base) < size)
+    }
+
+    /// Claims `size` bytes of the reservation, a multiple of the Huge Page size, returning their start, or None if no
+    /// contiguous range of free chunks is large enough.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn claim(&self, size: usize) -> Option<NonNull<u8>> {
+        let (base, total) = self.range()?;
+
+        let (total, count) = (total / HUGE_PAGE_SIZE, size / HUGE_PAGE_SIZE);
+
+        let mut first = 0;
+
+        while first + count <= total {
+            //  Skip past the last committed chunk of the candidate range, if any.
+            if let Some(index) = (first..first + count).rev().find(|index| self.is_claimed(*index)) {
+                first = index + 1;
+                continue;
+            }
+
+            //  Another thread may race for the same chunks, in which case the next candidate range is tried.
+            if self.claim_chunks(first, count) {
+                return NonNull::new((base + first * HUGE_PAGE_SIZE.value()) as *mut u8);
+            }
+
+            first += 1;
+        }
+
+        None
+    }
+
+    /// Claims the `size` bytes of the reservation located at `pointer`, a multiple of the Huge Page size, returning
+    /// whether they were all free, and are now claimed.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn claim_at(&self, pointer: NonNull<u8>, size: usize) -> bool {
+        match self.index(pointer, size) {
+            Some(first) => self.claim_chunks(first, size / HUGE_PAGE_SIZE),
+            None => false,
+        }
+    }
+
+    /// Releases the `size` bytes of the reservation located at `pointer`, a multiple of the Huge Page size, previously
+    /// claimed.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn release(&self, pointer: NonNull<u8>, size: usize) {
+        if let Some(first) = self.index(pointer, size) {
+            self.release_chunks(first, size / HUGE_PAGE_SIZE);
+        }
+    }
+
+    /// Waits until the address space is no longer being reserved by another thread.
+    ///
+    /// To be called prior to forking, for the child to inherit the reservation, if any, fully reserved.
+    #[cold]
+    pub(crate) fn settle(&self) {
+        while self.base.load(Ordering::Acquire) == BASE_CREATING {
+            hint::spin_loop();
+        }
+    }
+
+    /// Resets the reservation being reserved, if any, to be reserved anew on first use.
+    ///
+    /// To be called in the child of a fork, where the thread reserving it no longer exists.
+    #[cold]
+    pub(crate) fn recover(&self) {
+        let _ = self.base.compare_exchange(BASE_CREATING, BASE_UNSET, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn latch_slow(&self, platform: &LLPlatform, create: fn(usize) -> Option<NonNull<u8>>) -> bool {
+        self.resolve(platform);
+
+        //  An explicit selection, racing with the latch, is latched instead.
+        let size = self.size.fetch_or(LATCHED, Ordering::Relaxed) & !LATCHED;
+
+        loop {
+            match self.base.compare_exchange(BASE_UNSET, BASE_CREATING, Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => {
+                    let base = if size == DISABLED { None } else { create(size) };
+                    let base = base.map_or(BASE_NONE, |base| base.as_ptr() as usize);
+
+                    self.base.store(base, Ordering::Release);
+
+                    return base != BASE_NONE;
+                },
+                //  The address space is reserved by another thread, which is merely a system call or two away from
+                //  publishing it.
+                Err(BASE_CREATING) => hint::spin_loop(),
+                Err(base) => return base != BASE_NONE,
+            }
+        }
+    }
+
+    //  Returns the size selected, resolving it if unresolved.
+    #[inline(always)]
+    fn resolve(&self, platform: &LLPlatform) -> usize {
+        match self.size.load(Ordering::Relaxed) {
+            UNRESOLVED => self.resolve_slow(platform),
+            current => current & !LATCHED,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve_slow(&self, platform: &LLPlatform) -> usize {
+        let resolved = if platform.environment_flag(ENVIRONMENT_VARIABLE) { DEFAULT_SIZE } else { DISABLED };
+
+        //  A size selected by `LLAllocator::set_reservation` while the environment is read is kept.
+        match self.size.compare_exchange(UNRESOLVED, resolved, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => resolved,
+            Err(current) => current & !LATCHED,
+        }
+    }
+
+    //  Returns the base and size of the reservation, if reserved.
+    #[inline(always)]
+    fn range(&self) -> Option<(usize, usize)> {
+        match self.base.load(Ordering::Acquire) {
+            BASE_UNSET | BASE_CREATING | BASE_NONE => None,
+            //  The selection is latched prior to the reservation.
+            base => Some((base, self.size.load(Ordering::Relaxed) & !LATCHED)),
+        }
+    }
+
+    //  Returns the index of the first chunk of the `size` bytes located at `pointer`, if within the reservation.
+    fn index(&self, pointer: NonNull<u8>, size: usize) -> Option<usize> {
+        let (base, total) = self.range()?;
+
+        let offset = (pointer.as_ptr() as usize).checked_sub(base)?;
+
+        debug_assert!(offset % HUGE_PAGE_SIZE == 0 && size % HUGE_PAGE_SIZE == 0, "{:x} {:x}", offset, size);
+
+        if offset.checked_add(size)? > total {
+            return None;
+        }
+
+        Some(offset / HUGE_PAGE_SIZE)
+    }
+
+    //  Claims the `count` chunks starting at `first`, none of which is claimed on failure.
+    fn claim_chunks(&self, first: usize, count: usize) -> bool {
+        for index in first..first + count {
+            let previous = self.chunks[index / 64].fetch_or(Self::bit(index), Ordering::Relaxed);
+
+            if previous & Self::bit(index) != 0 {
+                self.release_chunks(first, index - first);
+                return false;
+            }
+        }
+
+        true
+    }
+
+    //  Releases the `count` chunks starting at `first`.
+    fn release_chunks(&self, first: usize, count: usize) {
+        for index in first..first + count {
+            self.chunks[index / 64].fetch_and(!Self::bit(index), Ordering::Relaxed);
+        }
+    }
+
+    fn is_claimed(&self, index: usize) -> bool {
+        self.chunks[index / 64].load(Ordering::Relaxed) & Self::bit(index) != 0
+    }
+
+    fn bit(index: usize) -> u64 { 1 << (index % 64) }
+
+    //  Rounds up `size` to a whole number of chunks, if neither 0 nor exceeding the maximum.
+    fn round(size: usize) -> Option<usize> {
+        let size = size.checked_add(HUGE_PAGE_SIZE.value() - 1)? & !(HUGE_PAGE_SIZE.value() - 1);
+
+        if size == 0 || size / HUGE_PAGE_SIZE > MAXIMUM_CHUNKS {
+            return None;
+        }
+
+        Some(size)
+    }
+}
+
+/// Selection of a reservation, shared by the allocator and the platforms.
+pub(crate) static ADDRESS_SPACE: AddressSpace = AddressSpace::new();
+
+//
+//  Implementation Details
+//
+
+const HUGE_PAGE_SIZE: PowerOf2 = LLConfiguration::HUGE_PAGE_SIZE;
+
+const WORDS: usize = 16;
+
+//  Sizes are multiples of the Huge Page size, hence neither the low bit, nor 2, are valid sizes.
+const DISABLED: usize = 0;
+const LATCHED: usize = 1;
+const UNRESOLVED: usize = 2;
+
+//  Bases are aligned on the Huge Page size, hence none of the sentinels are valid bases.
+const BASE_UNSET: usize = 0;
+const BASE_CREATING: usize = 1;
+const BASE_NONE: usize = 2;
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+const CHUNK: usize = HUGE_PAGE_SIZE.value();
+
+fn reserved(chunks: usize) -> AddressSpace {
+    let space = AddressSpace::new();
+
+    space.size.store((chunks * CHUNK) | LATCHED, Ordering::Relaxed);
+    space.base.store(16 * CHUNK, Ordering::Relaxed);
+
+    space
+}
+
+fn at(chunk: usize) -> NonNull<u8> { NonNull::new(((16 + chunk) * CHUNK) as *mut u8).unwrap() }
+
+#[test]
+fn address_space_set() {
+    let space = AddressSpace::new();
+
+    assert_eq!(Err(()), space.set(Some(0)));
+    assert_eq!(Err(()), space.set(Some(MAXIMUM_CHUNKS * CHUNK + 1)));
+
+    assert_eq!(Ok(()), space.set(Some(CHUNK + 1)));
+    assert_eq!(2 * CHUNK, space.size.load(Ordering::Relaxed));
+
+    space.size.fetch_or(LATCHED, Ordering::Relaxed);
+
+    assert_eq!(Err(()), space.set(None));
+    assert_eq!(Ok(()), space.set(Some(2 * CHUNK)));
+}
+
+#[test]
+fn address_space_claim_release() {
+    let space = reserved(4);
+
+    assert!(space.contains(at(0)));
+    assert!(space.contains(at(3)));
+    assert!(!space.contains(at(4)));
+
+    assert_eq!(Some(at(0)), space.claim(CHUNK));
+    assert_eq!(Some(at(1)), space.claim(2 * CHUNK));
+    assert_eq!(None, space.claim(2 * CHUNK));
+
+    assert_eq!(2 * CHUNK + CHUNK, space.reservation().unwrap().committed);
+
+    //  Releasing the first chunk leaves a hole too small for 2 chunks.
+    space.release(at(0), CHUNK);
+    assert_eq!(None, space.claim(2 * CHUNK));
+
+    assert!(space.claim_at(at(3), CHUNK));
+    assert!(!space.claim_at(at(2), CHUNK));
+    assert!(!space.claim_at(at(3), 2 * CHUNK));
+
+    space.release(at(1), 3 * CHUNK);
+    assert_eq!(Some(at(0)), space.claim(4 * CHUNK));
+}
+
+#[test]
+fn reservation_offset() {
+    let reservation = Reservation { base: 4 * CHUNK, size: 2 * CHUNK, committed: 0 };
+
+    assert_eq!(None, reservation.offset((4 * CHUNK - 1) as *const u8));
+    assert_eq!(Some(0), reservation.offset((4 * CHUNK) as *const u8));
+    assert_eq!(Some(2 * CHUNK - 1), reservation.offset((6 * CHUNK - 1) as *const u8));
+    assert_eq!(None, reservation.offset((6 * CHUNK) as *const u8));
+
+    assert_eq!(NonNull::new((5 * CHUNK) as *mut u8), reservation.pointer(CHUNK));
+    assert_eq!(None, reservation.pointer(2 * CHUNK));
+}
+
+} // mod tests
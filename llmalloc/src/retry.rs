@@ -0,0 +1,204 @@
+//! Retry
+//!
+//! A mapping may fail transiently: a pool of HugeTLB pages exhausted, or memory too fragmented for a huge page to be
+//! compacted, may well recover shortly, as other processes release their memory. By default, an allocation for which
+//! no memory could be mapped fails immediately. With a retry policy, the allocation is instead retried, up to
+//! `RetryPolicy::retries` times, each retry preceded by a backoff doubling from `RetryPolicy::backoff`, and the first
+//! optionally preceded by a forced purge of the Huge blocks retained for reuse, returning their memory to the OS.
+//!
+//! Each retry is recorded as a `FallbackMetrics::allocation_retries`, and each allocation which succeeded once retried
+//! as a `FallbackMetrics::recovered_allocations`, whereas the failures to map memory are recorded, as ever, as
+//! `FallbackMetrics::mmap_failures`.
+//!
+//! The policy is resolved from the `LLMALLOC_RETRY` environment variable, selecting 3 retries, backing off from 1 ms,
+//! and purging, if set to any value other than an empty string or `0`, unless set explicitly beforehand by
+//! `LLAllocator::set_retry_policy`.
+//!
+//! The allocations bounded by a deadline are never retried. The backoff is only waited on the platforms which can
+//! sleep, see `Platform::sleep`, the retries following one another immediately on the others.
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{Fallback, Platform, LLPlatform};
+
+/// Name of the environment variable selecting a retry policy, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_RETRY\0";
+
+/// Retry policy selected by the environment variable.
+pub(crate) const DEFAULT_POLICY: RetryPolicy =
+    RetryPolicy { retries: 3, backoff: Duration::from_millis(1), purge: true };
+
+/// Policy of retry of the allocations for which no memory could be mapped, see `LLAllocator::set_retry_policy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RetryPolicy {
+    /// The number of retries, at most 63.
+    pub retries: u32,
+    /// The backoff preceding the first retry, doubled for each following retry.
+    pub backoff: Duration,
+    /// Whether the Huge blocks retained for reuse are forcibly purged ahead of the first retry.
+    pub purge: bool,
+}
+
+impl RetryPolicy {
+    /// Returns the backoff preceding the `retry`-th retry, counting from 0, saturated at `u64::MAX` nanoseconds.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u128.checked_shl(retry).unwrap_or(u128::MAX);
+        let nanos = self.backoff.as_nanos().saturating_mul(factor);
+
+        Duration::from_nanos(nanos.min(u128::from(u64::MAX)) as u64)
+    }
+}
+
+/// Process-wide selection of a retry policy.
+pub(crate) struct Retry {
+    //  Policy, encoded, or DISABLED, or UNRESOLVED.
+    state: AtomicU64,
+}
+
+impl Retry {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self { Self { state: AtomicU64::new(UNRESOLVED) } }
+
+    /// Returns the retry policy, if any, resolving it from `platform` if not yet resolved.
+    pub(crate) fn policy(&self, platform: &LLPlatform) -> Option<RetryPolicy> {
+        match self.state.load(Ordering::Relaxed) {
+            UNRESOLVED => decode(self.resolve(platform)),
+            state => decode(state),
+        }
+    }
+
+    /// Selects the retry policy, or none, overriding the environment.
+    pub(crate) fn set(&self, policy: Option<RetryPolicy>) { self.state.store(encode(policy), Ordering::Relaxed); }
+
+    /// Retries `attempt`, as per the policy, invoking `purge` ahead of the first retry if the policy purges.
+    ///
+    /// To be called once an allocation failed, as no memory could be mapped; returns the result of the first successful
+    /// retry, or None if none succeeded, or if there is no policy.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn retry<T, P, A>(&self, platform: &LLPlatform, mut purge: P, mut attempt: A) -> Option<T>
+        where
+            P: FnMut(),
+            A: FnMut() -> Option<T>,
+    {
+        let policy = self.policy(platform)?;
+
+        for retry in 0..policy.retries {
+            if retry == 0 && policy.purge {
+                purge();
+            }
+
+            let delay = policy.delay(retry);
+
+            if delay > Duration::ZERO {
+                platform.sleep(delay);
+            }
+
+            platform.fallbacks().record(Fallback::AllocationRetry);
+
+            if let Some(result) = attempt() {
+                platform.fallbacks().record(Fallback::RecoveredAllocation);
+                return Some(result);
+            }
+        }
+
+        None
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self, platform: &LLPlatform) -> u64 {
+        let enabled = platform.environment_flag(ENVIRONMENT_VARIABLE);
+        let resolved = encode(if enabled { Some(DEFAULT_POLICY) } else { None });
+
+        //  A policy set by `LLAllocator::set_retry_policy` while the environment is read is kept over the default.
+        match self.state.compare_exchange(UNRESOLVED, resolved, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => resolved,
+            Err(current) => current,
+        }
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  The policy is encoded as the number of retries, in the lowest 6 bits, the purge flag, in the 7th, and the backoff,
+//  in nanoseconds, in the remaining bits; a policy without retry is encoded as DISABLED.
+const DISABLED: u64 = 0;
+const UNRESOLVED: u64 = u64::MAX;
+
+const MAXIMUM_RETRIES: u32 = 63;
+const PURGE: u64 = 1 << 6;
+const BACKOFF_SHIFT: u32 = 7;
+
+//  About 2 years, short of UNRESOLVED.
+const MAXIMUM_BACKOFF: u64 = (1 << 56) - 1;
+
+fn encode(policy: Option<RetryPolicy>) -> u64 {
+    match policy {
+        Some(policy) if policy.retries > 0 => {
+            let retries = u64::from(policy.retries.min(MAXIMUM_RETRIES));
+            let purge = if policy.purge { PURGE } else { 0 };
+            let backoff = policy.backoff.as_nanos().min(u128::from(MAXIMUM_BACKOFF)) as u64;
+
+            retries | purge | (backoff << BACKOFF_SHIFT)
+        },
+        _ => DISABLED,
+    }
+}
+
+fn decode(state: u64) -> Option<RetryPolicy> {
+    if state == DISABLED {
+        return None;
+    }
+
+    let retries = (state & (PURGE - 1)) as u32;
+    let purge = state & PURGE != 0;
+    let backoff = Duration::from_nanos(state >> BACKOFF_SHIFT);
+
+    Some(RetryPolicy { retries, backoff, purge })
+}
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn retry_policy_delay() {
+    let policy = RetryPolicy { retries: 3, backoff: Duration::from_millis(1), purge: false };
+
+    assert_eq!(Duration::from_millis(1), policy.delay(0));
+    assert_eq!(Duration::from_millis(2), policy.delay(1));
+    assert_eq!(Duration::from_millis(4), policy.delay(2));
+
+    //  Saturated, rather than overflowed.
+    assert_eq!(Duration::from_nanos(u64::MAX), policy.delay(62));
+    assert_eq!(Duration::from_nanos(u64::MAX), policy.delay(u32::MAX));
+}
+
+#[test]
+fn retry_policy_encode_decode() {
+    assert_eq!(None, decode(encode(None)));
+
+    let policy = RetryPolicy { retries: 0, ..DEFAULT_POLICY };
+    assert_eq!(None, decode(encode(Some(policy))));
+
+    assert_eq!(Some(DEFAULT_POLICY), decode(encode(Some(DEFAULT_POLICY))));
+
+    let policy = RetryPolicy { retries: 1, backoff: Duration::ZERO, purge: false };
+    assert_eq!(Some(policy), decode(encode(Some(policy))));
+
+    //  Clamped.
+    let policy = RetryPolicy { retries: u32::MAX, backoff: Duration::MAX, purge: true };
+    let clamped = RetryPolicy { retries: MAXIMUM_RETRIES, backoff: Duration::from_nanos(MAXIMUM_BACKOFF), purge: true };
+
+    assert_ne!(UNRESOLVED, encode(Some(policy)));
+    assert_eq!(Some(clamped), decode(encode(Some(policy))));
+}
+
+} // mod tests
@@ -0,0 +1,232 @@
+//! Shared Heap
+//!
+//! Anonymous memory cannot be mapped by another process, short of forking. With a shared heap, the platform instead
+//! backs each `HugePage` it maps, and thus each allocation, with a single anonymous file, created by `memfd_create`,
+//! whose descriptor a cooperating process may map, for example to read the buffers allocated by this process without
+//! copying them.
+//!
+//! The memory located at an address lives at the offset of the file equal to that address, hence an offset is never
+//! looked up, see `SharedHeap::offset`; the file is sparse, and the memory freed is punched out of it. As the offset of
+//! a mapping is fixed by its address, a Huge allocation which cannot be resized in place is moved by copy.
+//!
+//! The file may be backed by 2 MB HugeTLB pages, with `MFD_HUGETLB`, in which case an exhausted pool fails the mapping,
+//! rather than falling back on Normal pages, lest the heap not be shared in its entirety.
+//!
+//! As a mapping is unmapped according to its backing, the selection is latched by the first mapping, after which it can
+//! no longer change. The selection is resolved from the `LLMALLOC_MEMFD_HUGETLB` and `LLMALLOC_MEMFD` environment
+//! variables, in this order, each enabled if set to any value other than an empty string or `0`, unless set explicitly
+//! beforehand by `LLAllocator::set_shared_heap`.
+//!
+//! A shared heap is honored by the platform of Linux. A process forking with a shared heap shares it with its child,
+//! which must not allocate, nor write to the memory inherited, until it executes another program.
+
+use core::{
+    hint,
+    ptr::NonNull,
+    sync::atomic::{AtomicI32, AtomicU8, Ordering},
+};
+
+use crate::{Platform, LLPlatform};
+
+/// Name of the environment variable selecting a shared heap, NUL-terminated.
+pub(crate) const ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_MEMFD\0";
+
+/// Name of the environment variable selecting a shared heap backed by HugeTLB pages, NUL-terminated.
+pub(crate) const HUGE_TLB_ENVIRONMENT_VARIABLE: &[u8] = b"LLMALLOC_MEMFD_HUGETLB\0";
+
+/// Backing of a shared heap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SharedBacking {
+    /// Normal pages, possibly promoted to Transparent Huge Pages.
+    Normal,
+    /// 2 MB HugeTLB pages.
+    HugeTlb,
+}
+
+/// Shared heap, as returned by `LLAllocator::shared_heap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SharedHeap {
+    /// The file descriptor of the anonymous file backing the heap, open for reading and writing.
+    ///
+    /// The descriptor is owned by the allocator, and closed on execution of another program; it may be passed to
+    /// another process over a Unix socket, or duplicated.
+    pub fd: i32,
+    /// The backing of the file.
+    pub backing: SharedBacking,
+}
+
+impl SharedHeap {
+    /// Returns the offset, within the file, of the memory located at `pointer`.
+    ///
+    /// The result is only meaningful if `pointer` points within memory allocated by the allocator; the memory of an
+    /// allocation is contiguous within the file, and an area mapped from the file by another process must be aligned
+    /// on the OS page, or on 2 MB for HugeTLB pages.
+    pub fn offset(&self, pointer: NonNull<u8>) -> u64 { pointer.as_ptr() as usize as u64 }
+}
+
+/// Process-wide selection of a shared heap, and its file descriptor.
+pub(crate) struct Shared {
+    state: AtomicU8,
+    fd: AtomicI32,
+}
+
+impl Shared {
+    /// Creates an instance, unresolved.
+    pub(crate) const fn new() -> Self { Self { state: AtomicU8::new(UNRESOLVED), fd: AtomicI32::new(FD_UNSET) } }
+
+    /// Returns the backing of the shared heap, if selected, resolving it from `platform` if not yet resolved.
+    pub(crate) fn backing(&self, platform: &LLPlatform) -> Option<SharedBacking> { decode(self.resolve(platform)) }
+
+    /// Returns the shared heap, if created.
+    pub(crate) fn heap(&self) -> Option<SharedHeap> { self.heap_of(self.fd.load(Ordering::Acquire)) }
+
+    /// Returns the shared heap, resolving the selection from `platform` if not yet resolved, latching it, and invoking
+    /// `create` to create the file, once, if selected.
+    ///
+    /// To be called by the platform on each mapping, and unmapping; None is returned if the heap is not shared,
+    /// including if the file could not be created.
+    #[cfg_attr(not(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix",
+        feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc")))),
+        allow(dead_code))]
+    #[inline(always)]
+    pub(crate) fn latch(&self, platform: &LLPlatform, create: fn(SharedBacking) -> Option<i32>) -> Option<SharedHeap> {
+        match self.fd.load(Ordering::Acquire) {
+            FD_UNSET | FD_CREATING => self.latch_slow(platform, create),
+            fd => self.heap_of(fd),
+        }
+    }
+
+    /// Selects the backing of the shared heap, or none, overriding the environment.
+    ///
+    /// Returns Err if the selection differs from the one already latched.
+    pub(crate) fn set(&self, backing: Option<SharedBacking>) -> Result<(), ()> {
+        let selected = encode(backing);
+
+        let mut current = self.state.load(Ordering::Relaxed);
+
+        loop {
+            if current & LATCHED != 0 {
+                return if current & !LATCHED == selected { Ok(()) } else { Err(()) };
+            }
+
+            match self.state.compare_exchange(current, selected, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Waits until the file of the shared heap is no longer being created by another thread.
+    ///
+    /// To be called prior to forking, for the child to inherit the file, if any, fully created.
+    #[cold]
+    pub(crate) fn settle(&self) {
+        while self.fd.load(Ordering::Acquire) == FD_CREATING {
+            hint::spin_loop();
+        }
+    }
+
+    /// Resets the file being created, if any, to be created anew on first use.
+    ///
+    /// To be called in the child of a fork, where the thread creating it no longer exists.
+    #[cold]
+    pub(crate) fn recover(&self) {
+        let _ = self.fd.compare_exchange(FD_CREATING, FD_UNSET, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn latch_slow(&self, platform: &LLPlatform, create: fn(SharedBacking) -> Option<i32>) -> Option<SharedHeap> {
+        self.resolve(platform);
+
+        //  An explicit selection, racing with the latch, is latched instead.
+        let backing = decode(self.state.fetch_or(LATCHED, Ordering::Relaxed) & !LATCHED);
+
+        loop {
+            match self.fd.compare_exchange(FD_UNSET, FD_CREATING, Ordering::Acquire, Ordering::Acquire) {
+                Ok(_) => {
+                    let fd = backing.and_then(create).filter(|fd| *fd >= 0).unwrap_or(FD_NONE);
+
+                    self.fd.store(fd, Ordering::Release);
+
+                    return self.heap_of(fd);
+                },
+                //  The file is created by another thread, which is merely a system call or two away from publishing it.
+                Err(FD_CREATING) => hint::spin_loop(),
+                Err(fd) => return self.heap_of(fd),
+            }
+        }
+    }
+
+    //  Returns the state, resolving it if unresolved.
+    #[inline(always)]
+    fn resolve(&self, platform: &LLPlatform) -> u8 {
+        match self.state.load(Ordering::Relaxed) {
+            UNRESOLVED => self.resolve_slow(platform),
+            current => current & !LATCHED,
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve_slow(&self, platform: &LLPlatform) -> u8 {
+        let resolved = if platform.environment_flag(HUGE_TLB_ENVIRONMENT_VARIABLE) {
+            HUGE_TLB
+        } else if platform.environment_flag(ENVIRONMENT_VARIABLE) {
+            NORMAL
+        } else {
+            DISABLED
+        };
+
+        //  A backing selected by `LLAllocator::set_shared_heap` while the environment is read is kept.
+        match self.state.compare_exchange(UNRESOLVED, resolved, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => resolved,
+            Err(current) => current & !LATCHED,
+        }
+    }
+
+    //  Returns the shared heap of `fd`, if any.
+    fn heap_of(&self, fd: i32) -> Option<SharedHeap> {
+        if fd < 0 {
+            return None;
+        }
+
+        //  The selection is latched prior to the creation of the file.
+        let backing = decode(self.state.load(Ordering::Relaxed) & !LATCHED)?;
+
+        Some(SharedHeap { fd, backing })
+    }
+}
+
+/// Selection of a shared heap, shared by the allocator and the platforms.
+pub(crate) static SHARED: Shared = Shared::new();
+
+//
+//  Implementation Details
+//
+
+const UNRESOLVED: u8 = 0;
+const DISABLED: u8 = 1;
+const NORMAL: u8 = 2;
+const HUGE_TLB: u8 = 3;
+const LATCHED: u8 = 4;
+
+const FD_UNSET: i32 = -3;
+const FD_CREATING: i32 = -2;
+const FD_NONE: i32 = -1;
+
+fn encode(backing: Option<SharedBacking>) -> u8 {
+    match backing {
+        None => DISABLED,
+        Some(SharedBacking::Normal) => NORMAL,
+        Some(SharedBacking::HugeTlb) => HUGE_TLB,
+    }
+}
+
+fn decode(state: u8) -> Option<SharedBacking> {
+    match state {
+        NORMAL => Some(SharedBacking::Normal),
+        HUGE_TLB => Some(SharedBacking::HugeTlb),
+        _ => None,
+    }
+}
@@ -0,0 +1,56 @@
+//! Thread Stacks
+//!
+//! Fiber and green-thread runtimes allocate their own stacks, which they would rather have backed by huge pages, and
+//! local to the NUMA node of their threads, as is the heap. A thread stack is a dedicated mapping, outside of the heap:
+//!
+//! -   Its usable area is aligned on, and spans a multiple of, a Large Page, so as to be backed by Transparent Huge
+//!     Pages, when available.
+//! -   It is preceded by an inaccessible guard page, so that an overflow faults rather than corrupting other memory.
+//! -   It is optionally prefaulted, by the allocating thread, so that its memory is local to its NUMA node, and no
+//!     page fault occurs on first use.
+//!
+//! The stacks grow downwards, from `ThreadStack::top` to `ThreadStack::bottom`, and are passed to
+//! `pthread_attr_setstack` as their bottom and size.
+
+use core::ptr::NonNull;
+
+/// A thread stack, allocated by `LLAllocator::allocate_stack`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct ThreadStack {
+    guard: NonNull<u8>,
+    guard_size: usize,
+    size: usize,
+}
+
+//  Safety:
+//  -   The stack only exposes the addresses of its area, the accesses are up to the caller.
+unsafe impl Send for ThreadStack {}
+unsafe impl Sync for ThreadStack {}
+
+impl ThreadStack {
+    /// Creates an instance, from its guard page of `guard_size` bytes, followed by its usable area of `size` bytes.
+    pub fn new(guard: NonNull<u8>, guard_size: usize, size: usize) -> Self { Self { guard, guard_size, size } }
+
+    /// Returns the lowest address of the usable area, as expected by `pthread_attr_setstack`.
+    pub fn bottom(&self) -> NonNull<u8> {
+        //  Safety:
+        //  -   The usable area follows the guard page, within the same mapping.
+        unsafe { NonNull::new_unchecked(self.guard.as_ptr().add(self.guard_size)) }
+    }
+
+    /// Returns the address one past the highest address of the usable area, from which the stack grows downwards.
+    pub fn top(&self) -> NonNull<u8> {
+        //  Safety:
+        //  -   The usable area spans `size` bytes past its bottom, within the same mapping.
+        unsafe { NonNull::new_unchecked(self.bottom().as_ptr().add(self.size)) }
+    }
+
+    /// Returns the size of the usable area, rounded up to a multiple of the Large Page size.
+    pub fn size(&self) -> usize { self.size }
+
+    /// Returns the size of the guard page, below the usable area.
+    pub fn guard_size(&self) -> usize { self.guard_size }
+
+    /// Returns the start of the mapping, that is the guard page, and its size.
+    pub fn mapping(&self) -> (NonNull<u8>, usize) { (self.guard, self.guard_size + self.size) }
+}
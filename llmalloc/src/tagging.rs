@@ -0,0 +1,387 @@
+//! Tagging
+//!
+//! A tag is registered with a callback, invoked whenever a block allocated with the tag is deallocated, with its
+//! pointer and size: a subsystem may thus track the lifetimes of its own blocks, or verify that they are all released
+//! on shutdown, without hooking into every deallocation of the process.
+//!
+//! The tagged blocks are kept in a fixed-capacity table, reserved in the BSS and only touched once a tag is registered,
+//! with a bounded number of probes per tagged allocation and, while a tag is registered, per deallocation. A block
+//! which cannot be tagged, as its probes are all occupied, is counted as untagged rather than evicting another.
+//!
+//! A reallocation, or remap, carries the tag over to the resulting block, with its new size, without invoking the
+//! callback.
+
+use core::{
+    mem,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+};
+
+use crate::armed::ARMED;
+
+/// Callback of a tag, invoked with the pointer and size of each block allocated with the tag, as it is deallocated.
+///
+/// The callback is invoked on the deallocating thread, before the block is deallocated, and should therefore be brief.
+/// It may allocate and deallocate.
+pub type TagCallback = fn(NonNull<u8>, usize);
+
+/// Identifier of a registered tag.
+///
+/// A tag is only valid until unregistered: it never designates a tag registered later, even in the same slot, hence
+/// the blocks tagged with it never invoke the callback of a later tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Tag {
+    index: u8,
+    generation: usize,
+}
+
+/// Registry of the tags, and table of the tagged blocks.
+pub(crate) struct Tags {
+    //  Number of registered tags.
+    registered: AtomicUsize,
+    untagged: AtomicU64,
+    slots: [Slot; MAX_TAGS],
+    entries: [Entry; CAPACITY],
+}
+
+impl Tags {
+    /// Creates an instance, with no tag registered.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const SLOT: Slot = Slot::new();
+
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ENTRY: Entry = Entry::new();
+
+        Self {
+            registered: AtomicUsize::new(0),
+            untagged: AtomicU64::new(0),
+            slots: [SLOT; MAX_TAGS],
+            entries: [ENTRY; CAPACITY],
+        }
+    }
+
+    /// Returns whether any tag is registered.
+    #[inline(always)]
+    pub(crate) fn is_armed(&self) -> bool { self.registered.load(Ordering::Relaxed) != 0 }
+
+    /// Registers a tag, invoking `callback` on each deallocation of a block allocated with it.
+    ///
+    /// Returns None if all tags are already registered.
+    #[cold]
+    pub(crate) fn register(&self, callback: TagCallback) -> Option<Tag> {
+        let (index, generation) =
+            self.slots.iter().enumerate().find_map(|(index, slot)| slot.claim().map(|generation| (index, generation)))?;
+
+        let slot = &self.slots[index];
+
+        slot.callback.store(callback as *mut (), Ordering::Release);
+        slot.state.store(generation | REGISTERED, Ordering::Release);
+
+        self.registered.fetch_add(1, Ordering::Relaxed);
+        ARMED.arm();
+
+        Some(Tag { index: index as u8, generation })
+    }
+
+    /// Unregisters `tag`, untagging the blocks allocated with it.
+    ///
+    /// Returns false if it was not registered, or was already unregistered, even if its slot was since reused.
+    ///
+    /// A deallocation racing with the unregistration may still invoke its callback, one last time.
+    #[cold]
+    pub(crate) fn unregister(&self, tag: Tag) -> bool {
+        let slot = match self.slots.get(tag.index as usize) {
+            Some(slot) => slot,
+            None => return false,
+        };
+
+        if !slot.release(tag.generation) {
+            return false;
+        }
+
+        for entry in &self.entries {
+            if entry.address.load(Ordering::Acquire) != 0 && entry.tag() == tag {
+                entry.clear();
+            }
+        }
+
+        self.registered.fetch_sub(1, Ordering::Relaxed);
+        ARMED.disarm();
+
+        true
+    }
+
+    /// Returns the number of blocks which could not be tagged, since the start of the process.
+    pub(crate) fn untagged(&self) -> u64 { self.untagged.load(Ordering::Relaxed) }
+
+    /// Tags the block located at `pointer`, of `size` bytes, with `tag`.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn record(&self, pointer: NonNull<u8>, size: usize, tag: Tag) {
+        let address = pointer.as_ptr() as usize;
+
+        if !self.probes(address).any(|entry| entry.claim(address, size, tag)) {
+            self.untagged.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Carries the tag of the block located at `from`, if any, over to the block located at `to`, of `size` bytes.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn relocate(&self, from: NonNull<u8>, to: NonNull<u8>, size: usize) {
+        if let Some((_, tag)) = self.take(from.as_ptr() as usize) {
+            self.record(to, size, tag);
+        }
+    }
+
+    /// Untags the block located at `pointer`, if tagged, invoking the callback of its tag.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn release(&self, pointer: NonNull<u8>) {
+        let (size, tag) = match self.take(pointer.as_ptr() as usize) {
+            Some(tagged) => tagged,
+            None => return,
+        };
+
+        if let Some(callback) = self.slots[tag.index as usize].callback(tag.generation) {
+            callback(pointer, size);
+        }
+    }
+
+    //  Untags the block located at `address`, returning its size and tag, if it was tagged.
+    fn take(&self, address: usize) -> Option<(usize, Tag)> {
+        let entry = self.probes(address).find(|entry| entry.address.load(Ordering::Acquire) == address)?;
+
+        let tagged = (entry.size.load(Ordering::Relaxed), entry.tag());
+
+        entry.clear();
+
+        Some(tagged)
+    }
+
+    //  Returns the entries which may hold the tag of `address`.
+    fn probes(&self, address: usize) -> impl Iterator<Item = &Entry> {
+        //  Fibonacci hashing, skipping the low bits which alignment mostly zeroes.
+        let hash = ((address >> 4) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - CAPACITY.trailing_zeros());
+
+        (0..PROBES).map(move |probe| &self.entries[(hash as usize + probe) % CAPACITY])
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  Maximum number of tags registered at any time.
+const MAX_TAGS: usize = 8;
+
+//  Number of entries of the table, a power of 2.
+const CAPACITY: usize = 1 << 16;
+
+//  Number of entries probed per tagged allocation, or deallocation.
+const PROBES: usize = 8;
+
+//  States of a slot: free, claimed and not yet initialized, or registered.
+//
+//  The state occupies the low bits of the word of the slot, and the generation the others, the generation being bumped
+//  on each release so that the tags of the prior registrations no longer match.
+const FREE: usize = 0;
+const CLAIMED: usize = 1;
+const REGISTERED: usize = 2;
+
+const STATE: usize = 0b11;
+const GENERATION: usize = STATE + 1;
+
+struct Slot {
+    state: AtomicUsize,
+    callback: AtomicPtr<()>,
+}
+
+impl Slot {
+    const fn new() -> Self { Self { state: AtomicUsize::new(FREE), callback: AtomicPtr::new(ptr::null_mut()) } }
+
+    //  Claims the slot, if free, returning its generation.
+    fn claim(&self) -> Option<usize> {
+        let word = self.state.load(Ordering::Relaxed);
+
+        if word & STATE != FREE {
+            return None;
+        }
+
+        self.state.compare_exchange(word, word | CLAIMED, Ordering::Acquire, Ordering::Relaxed).ok()?;
+
+        Some(word)
+    }
+
+    //  Releases the slot, if registered under `generation`.
+    fn release(&self, generation: usize) -> bool {
+        let next = generation.wrapping_add(GENERATION) | FREE;
+
+        self.state.compare_exchange(generation | REGISTERED, next, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+    }
+
+    //  Returns the callback of the slot, if registered under `generation`.
+    fn callback(&self, generation: usize) -> Option<TagCallback> {
+        let registered = generation | REGISTERED;
+
+        if self.state.load(Ordering::Acquire) != registered {
+            return None;
+        }
+
+        let callback = self.callback.load(Ordering::Acquire);
+
+        //  Lest the slot was released, and registered anew with another callback, in the meantime.
+        if self.state.load(Ordering::Relaxed) != registered {
+            return None;
+        }
+
+        debug_assert!(!callback.is_null());
+
+        //  Safety:
+        //  -   `callback` was stored from a `TagCallback`, by `register`, prior to the registration of `generation`.
+        Some(unsafe { mem::transmute::<*mut (), TagCallback>(callback) })
+    }
+}
+
+//  An entry, free if `address` is 0.
+struct Entry {
+    address: AtomicUsize,
+    size: AtomicUsize,
+    generation: AtomicUsize,
+    index: AtomicU8,
+}
+
+impl Entry {
+    const fn new() -> Self {
+        Self {
+            address: AtomicUsize::new(0),
+            size: AtomicUsize::new(0),
+            generation: AtomicUsize::new(0),
+            index: AtomicU8::new(0),
+        }
+    }
+
+    //  Claims the entry for `address`, of `size` bytes, with `tag`, if free.
+    fn claim(&self, address: usize, size: usize, tag: Tag) -> bool {
+        if self.address.compare_exchange(0, address, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            return false;
+        }
+
+        self.size.store(size, Ordering::Relaxed);
+        self.generation.store(tag.generation, Ordering::Relaxed);
+        self.index.store(tag.index, Ordering::Release);
+
+        true
+    }
+
+    //  Returns the tag of the entry.
+    fn tag(&self) -> Tag {
+        let index = self.index.load(Ordering::Acquire);
+
+        Tag { index, generation: self.generation.load(Ordering::Relaxed) }
+    }
+
+    //  Frees the entry.
+    fn clear(&self) { self.address.store(0, Ordering::Release); }
+}
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use std::{sync::Mutex, vec::Vec};
+
+use super::*;
+
+static RELEASED: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+fn on_release(pointer: NonNull<u8>, size: usize) { RELEASED.lock().unwrap().push((pointer.as_ptr() as usize, size)); }
+
+fn pointer(address: usize) -> NonNull<u8> { NonNull::new(address as *mut u8).unwrap() }
+
+#[test]
+fn tags_release() {
+    static REGISTRY: Tags = Tags::new();
+
+    let tags = &REGISTRY;
+    assert!(!tags.is_armed());
+
+    let tag = tags.register(on_release).expect("Registered");
+    assert!(tags.is_armed());
+
+    tags.record(pointer(0x1000), 64, tag);
+    tags.record(pointer(0x2000), 128, tag);
+    tags.relocate(pointer(0x2000), pointer(0x3000), 256);
+
+    tags.release(pointer(0x2000));
+    tags.release(pointer(0x3000));
+    tags.release(pointer(0x4000));
+
+    assert_eq!(&[(0x3000, 256)], &RELEASED.lock().unwrap()[..]);
+
+    //  Unregistering untags the blocks.
+    assert!(tags.unregister(tag));
+    assert!(!tags.unregister(tag));
+    assert!(!tags.is_armed());
+
+    let tag = tags.register(on_release).expect("Registered");
+
+    tags.release(pointer(0x1000));
+
+    assert_eq!(1, RELEASED.lock().unwrap().len());
+    assert!(tags.unregister(tag));
+}
+
+#[test]
+fn tags_untagged() {
+    static REGISTRY: Tags = Tags::new();
+
+    let tags = &REGISTRY;
+
+    let registered: Vec<_> = (0..MAX_TAGS).map(|_| tags.register(on_release).expect("Registered")).collect();
+    assert_eq!(None, tags.register(on_release));
+
+    //  Addresses differing in their low bits only share their probes.
+    for address in 0..=PROBES {
+        tags.record(pointer(0x10000 + address), 1, registered[0]);
+    }
+
+    assert_eq!(1, tags.untagged());
+}
+
+#[test]
+fn tags_stale() {
+    static REGISTRY: Tags = Tags::new();
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    fn on_count(_: NonNull<u8>, _: usize) { COUNT.fetch_add(1, Ordering::Relaxed); }
+
+    let tags = &REGISTRY;
+
+    let stale = tags.register(on_count).expect("Registered");
+    assert!(tags.unregister(stale));
+
+    //  The slot is reused, under another generation.
+    let tag = tags.register(on_count).expect("Registered");
+    assert_eq!(stale.index, tag.index);
+    assert_ne!(stale, tag);
+
+    assert!(!tags.unregister(stale));
+    assert!(tags.is_armed());
+
+    //  The blocks tagged with the stale tag never invoke the callback of the current one.
+    tags.record(pointer(0x1000), 64, stale);
+    tags.record(pointer(0x2000), 64, tag);
+
+    tags.release(pointer(0x1000));
+    assert_eq!(0, COUNT.load(Ordering::Relaxed));
+
+    tags.release(pointer(0x2000));
+    assert_eq!(1, COUNT.load(Ordering::Relaxed));
+
+    assert!(tags.unregister(tag));
+}
+
+} // mod tests
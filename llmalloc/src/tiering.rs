@@ -0,0 +1,34 @@
+//! Tiering
+//!
+//! Machines with CXL.mem expanders, or persistent memory exposed as NUMA nodes, present nodes of memory without CPUs,
+//! far slower than DRAM. The kernel readily falls back onto them once the DRAM of a node is exhausted, which suits
+//! throughput, yet silently degrades the latency of whichever allocation lands there.
+//!
+//! Hence the nodes are classified in tiers, near memory, DRAM, and far memory, and the placements of the heap are
+//! deliberately restricted to the nodes of near memory: the pages of the mappings not bound otherwise, and those of
+//! the mappings bound to their node, are bound to near memory, and the pages interleaved across nodes are interleaved
+//! across the nodes of near memory only. Spilling onto far memory is opt-in, selected by `LLAllocator::set_far_memory`:
+//! the mappings are then left to the fallback of the kernel, the pages of the bound mappings may spill onto far memory
+//! once their node is exhausted, and the interleaved pages are interleaved across all nodes.
+//!
+//! The preferred placements fall back onto any node regardless, being preferences. The tiers are honored by the
+//! platform of Linux, on machines with far memory; on other machines, all nodes are near memory.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Process-wide selection of the spilling onto far memory.
+pub(crate) struct Tiering(AtomicBool);
+
+impl Tiering {
+    /// Creates an instance, restricting the placements to near memory.
+    pub(crate) const fn new() -> Self { Self(AtomicBool::new(false)) }
+
+    /// Returns whether the placements may spill onto far memory.
+    pub(crate) fn spills(&self) -> bool { self.0.load(Ordering::Relaxed) }
+
+    /// Selects whether the placements may spill onto far memory.
+    pub(crate) fn set(&self, spills: bool) { self.0.store(spills, Ordering::Relaxed); }
+}
+
+/// Selection of the spilling onto far memory, shared by the allocator and the platforms.
+pub(crate) static TIERING: Tiering = Tiering::new();
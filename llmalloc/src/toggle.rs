@@ -0,0 +1,112 @@
+//! Toggles
+//!
+//! The opt-in modes which are merely on or off share their selection: each is resolved, on first use, from its
+//! environment variable, enabled if set to any value other than an empty string or `0`, unless set explicitly
+//! beforehand.
+//!
+//! The modes checked on each allocation are arming, see `armed`: armed while enabled, and while unresolved.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::{armed::ARMED, Platform, LLPlatform};
+
+/// Process-wide selection of an opt-in mode, resolved from the environment unless set explicitly beforehand.
+pub(crate) struct Toggle {
+    state: AtomicU8,
+    variable: &'static [u8],
+    arming: bool,
+}
+
+impl Toggle {
+    /// Creates an instance, unresolved, to be resolved from the environment `variable`, NUL-terminated.
+    pub(crate) const fn new(variable: &'static [u8]) -> Self {
+        Self { state: AtomicU8::new(UNRESOLVED), variable, arming: false }
+    }
+
+    /// Creates an instance, unresolved, as per `new`, arming `ARMED` while enabled, and until resolved.
+    ///
+    /// `ARMED` is to count the instance as armed from the start.
+    pub(crate) const fn arming(variable: &'static [u8]) -> Self {
+        Self { state: AtomicU8::new(UNRESOLVED), variable, arming: true }
+    }
+
+    /// Returns whether the mode is enabled, resolving it from `platform` if not yet resolved.
+    #[inline(always)]
+    pub(crate) fn is_enabled(&self, platform: &LLPlatform) -> bool {
+        match self.state.load(Ordering::Relaxed) {
+            ENABLED => true,
+            DISABLED => false,
+            _ => self.resolve(platform),
+        }
+    }
+
+    /// Enables or disables the mode, overriding the environment.
+    pub(crate) fn set(&self, enabled: bool) {
+        let previous = self.state.swap(encode(enabled), Ordering::Relaxed);
+
+        if !self.arming {
+            return;
+        }
+
+        match (previous == DISABLED, enabled) {
+            (true, true) => ARMED.arm(),
+            (false, false) => ARMED.disarm(),
+            _ => (),
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn resolve(&self, platform: &LLPlatform) -> bool {
+        let resolved = encode(platform.environment_flag(self.variable));
+
+        //  Setting the mode while the environment is read overrides the environment, as it would have afterwards.
+        match self.state.compare_exchange(UNRESOLVED, resolved, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) if resolved == ENABLED => true,
+            Ok(_) => {
+                if self.arming {
+                    ARMED.disarm();
+                }
+
+                false
+            },
+            Err(current) => current == ENABLED,
+        }
+    }
+}
+
+//
+//  Implementation Details
+//
+
+const UNRESOLVED: u8 = 0;
+const DISABLED: u8 = 1;
+const ENABLED: u8 = 2;
+
+fn encode(enabled: bool) -> u8 { if enabled { ENABLED } else { DISABLED } }
+
+#[cfg(test)]
+mod tests {
+
+use super::*;
+
+#[test]
+fn toggle_unset() {
+    let toggle = Toggle::new(b"LLMALLOC_TEST_TOGGLE_UNSET\0");
+
+    assert!(!toggle.is_enabled(&LLPlatform::new()));
+}
+
+#[test]
+fn toggle_set_overrides_environment() {
+    let platform = LLPlatform::new();
+    let toggle = Toggle::new(b"LLMALLOC_TEST_TOGGLE_SET\0");
+
+    toggle.set(true);
+    assert!(toggle.is_enabled(&platform));
+
+    toggle.set(false);
+    assert!(!toggle.is_enabled(&platform));
+}
+
+} // mod tests
@@ -0,0 +1,181 @@
+//! Unmapping Failures
+//!
+//! Unmapping memory may fail: with `EINVAL` if the range is invalid, or with `ENOMEM` if unmapping part of a mapping
+//! would split it past the maximum number of mappings of the process, for example. The memory which cannot be unmapped
+//! is leaked, by default, as leaking memory is usually preferable to killing the process.
+//!
+//! Each failure is recorded, as a `FallbackMetrics::unmap_failures`, then reported to the callback registered with
+//! `LLAllocator::set_unmap_failure_callback`, if any, after which the process is aborted if the policy, as selected by
+//! `LLAllocator::set_unmap_failure_policy`, is `UnmapFailurePolicy::Abort`.
+//!
+//! The failures are reported by the platforms mapping memory from the OS; bare metal memory is never unmapped, and
+//! custom platforms unmap their memory themselves.
+
+use core::{
+    mem,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU8, Ordering},
+};
+
+use crate::{AtomicFallbackMetrics, Fallback};
+
+/// Callback reporting a failure to unmap memory, invoked on the thread unmapping.
+///
+/// The callback is invoked from within a deallocation, and should therefore be brief, and refrain from allocating.
+pub type UnmapFailureCallback = fn(&UnmapFailure);
+
+/// Failure to unmap memory, as passed to the `UnmapFailureCallback`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UnmapFailure {
+    /// The address of the memory which could not be unmapped.
+    pub address: usize,
+    /// The size of the memory, in bytes.
+    pub size: usize,
+    /// The error reported by the OS: the `errno` on Unix, the `GetLastError` code on Windows, or the status on Fuchsia.
+    pub error: i32,
+}
+
+/// Policy applied to the memory which could not be unmapped, once the failure is reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum UnmapFailurePolicy {
+    /// The memory is leaked, and the process carries on.
+    #[default]
+    Leak,
+    /// The process is aborted.
+    Abort,
+}
+
+/// Process-wide handling of the failures to unmap memory.
+pub(crate) struct Unmapping {
+    policy: AtomicU8,
+    callback: AtomicPtr<()>,
+}
+
+impl Unmapping {
+    /// Creates an instance, leaking without reporting.
+    pub(crate) const fn new() -> Self {
+        Self { policy: AtomicU8::new(LEAK), callback: AtomicPtr::new(ptr::null_mut()) }
+    }
+
+    /// Returns the policy.
+    pub(crate) fn policy(&self) -> UnmapFailurePolicy {
+        match self.policy.load(Ordering::Relaxed) {
+            ABORT => UnmapFailurePolicy::Abort,
+            _ => UnmapFailurePolicy::Leak,
+        }
+    }
+
+    /// Selects the policy.
+    pub(crate) fn set_policy(&self, policy: UnmapFailurePolicy) {
+        let encoded = match policy {
+            UnmapFailurePolicy::Leak => LEAK,
+            UnmapFailurePolicy::Abort => ABORT,
+        };
+
+        self.policy.store(encoded, Ordering::Relaxed);
+    }
+
+    /// Registers the callback, or none, replacing the previous one, if any.
+    pub(crate) fn set_callback(&self, callback: Option<UnmapFailureCallback>) {
+        let callback = callback.map_or(ptr::null_mut(), |callback| callback as *mut ());
+
+        self.callback.store(callback, Ordering::Release);
+    }
+
+    /// Reports the `failure`, recording it in `fallbacks`, then invoking the callback, if any, then invoking `abort`
+    /// if the policy is to abort.
+    ///
+    /// To be called by the platform whenever unmapping fails.
+    #[cfg_attr(any(target_arch = "wasm32", feature = "bare-metal", feature = "custom-platform",
+        feature = "test-platform"), allow(dead_code))]
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn fail(&self, fallbacks: &AtomicFallbackMetrics, failure: UnmapFailure, abort: fn() -> !) {
+        fallbacks.record(Fallback::UnmapFailure);
+
+        let callback = self.callback.load(Ordering::Acquire);
+
+        if !callback.is_null() {
+            //  Safety:
+            //  -   `callback` was stored from an `UnmapFailureCallback`.
+            let callback: UnmapFailureCallback = unsafe { mem::transmute::<*mut (), UnmapFailureCallback>(callback) };
+
+            callback(&failure);
+        }
+
+        if self.policy() == UnmapFailurePolicy::Abort {
+            abort();
+        }
+    }
+}
+
+/// Handling of the failures to unmap memory, shared by the allocator and the platforms.
+pub(crate) static UNMAPPING: Unmapping = Unmapping::new();
+
+//
+//  Implementation Details
+//
+
+const LEAK: u8 = 0;
+const ABORT: u8 = 1;
+
+#[cfg(test)]
+mod tests {
+
+use core::sync::atomic::AtomicUsize;
+
+use super::*;
+
+fn never() -> ! { unreachable!("Not aborted") }
+
+#[test]
+fn unmapping_fail() {
+    static REPORTED: AtomicUsize = AtomicUsize::new(0);
+
+    fn callback(failure: &UnmapFailure) {
+        assert_eq!(UnmapFailure { address: 0x1000, size: 4096, error: 22 }, *failure);
+
+        REPORTED.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let unmapping = Unmapping::new();
+    let fallbacks = AtomicFallbackMetrics::new();
+
+    let failure = UnmapFailure { address: 0x1000, size: 4096, error: 22 };
+
+    //  Recorded, and leaked.
+    assert_eq!(UnmapFailurePolicy::Leak, unmapping.policy());
+
+    unmapping.fail(&fallbacks, failure, never);
+
+    assert_eq!(1, fallbacks.snapshot().unmap_failures);
+    assert_eq!(0, REPORTED.load(Ordering::Relaxed));
+
+    //  Reported.
+    unmapping.set_callback(Some(callback));
+    unmapping.fail(&fallbacks, failure, never);
+
+    assert_eq!(2, fallbacks.snapshot().unmap_failures);
+    assert_eq!(1, REPORTED.load(Ordering::Relaxed));
+
+    unmapping.set_callback(None);
+    unmapping.fail(&fallbacks, failure, never);
+
+    assert_eq!(1, REPORTED.load(Ordering::Relaxed));
+}
+
+#[test]
+#[should_panic(expected = "Aborted")]
+fn unmapping_fail_abort() {
+    fn abort() -> ! { panic!("Aborted") }
+
+    let unmapping = Unmapping::new();
+    let fallbacks = AtomicFallbackMetrics::new();
+
+    unmapping.set_policy(UnmapFailurePolicy::Abort);
+    assert_eq!(UnmapFailurePolicy::Abort, unmapping.policy());
+
+    unmapping.fail(&fallbacks, UnmapFailure { address: 0x1000, size: 4096, error: 22 }, abort);
+}
+
+} // mod tests
@@ -0,0 +1,313 @@
+//! Watermarks
+//!
+//! A watermark invokes a callback whenever the live bytes, that is the bytes allocated and not yet deallocated, cross
+//! one of its thresholds: rising once they reach its `rising` threshold, then falling once they drop below its
+//! `falling` threshold, and so on. The gap between both thresholds is the hysteresis, sparing a flurry of callbacks
+//! whenever the live bytes hover around a single threshold.
+//!
+//! The live bytes are only evaluated while at least one watermark is registered, on the slow paths:
+//!
+//! -   On each allocation or deallocation of a Large or Huge allocation.
+//! -   On every `PERIOD`-th allocation or deallocation of each thread, otherwise.
+//!
+//! Crossings are therefore detected late, by up to `PERIOD` operations per thread, but never missed, unless the live
+//! bytes cross back in the meantime.
+
+use core::{
+    mem,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+use crate::armed::ARMED;
+
+/// Callback of a watermark, invoked on the thread detecting the crossing.
+///
+/// The callback is invoked from within an allocation or deallocation, and should therefore be brief. It may allocate
+/// and deallocate, but a crossing detected during the callback is only reported after it returns, by a later
+/// evaluation.
+pub type WatermarkCallback = fn(&WatermarkEvent);
+
+/// Direction of a crossing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Crossing {
+    /// The live bytes reached the `rising` threshold.
+    Rising,
+    /// The live bytes dropped below the `falling` threshold.
+    Falling,
+}
+
+/// Event passed to the callback of a watermark.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WatermarkEvent {
+    /// Direction of the crossing.
+    pub crossing: Crossing,
+    /// Threshold crossed.
+    pub threshold: usize,
+    /// Live bytes, at the time of the evaluation.
+    pub live_bytes: usize,
+}
+
+/// Identifier of a registered watermark.
+///
+/// An identifier is only valid until its watermark is unregistered: it never designates a watermark registered later,
+/// even in the same slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WatermarkId {
+    index: usize,
+    generation: usize,
+}
+
+/// Registry of the watermarks.
+pub(crate) struct Watermarks {
+    //  Number of registered watermarks.
+    registered: AtomicUsize,
+    slots: [Slot; CAPACITY],
+}
+
+impl Watermarks {
+    /// Number of allocations and deallocations of a thread between evaluations, for Normal allocations.
+    pub(crate) const PERIOD: usize = 1024;
+
+    /// Creates an instance, with no watermark registered.
+    pub(crate) const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const SLOT: Slot = Slot::new();
+
+        Self { registered: AtomicUsize::new(0), slots: [SLOT; CAPACITY] }
+    }
+
+    /// Returns whether any watermark is registered.
+    #[inline(always)]
+    pub(crate) fn is_armed(&self) -> bool { self.registered.load(Ordering::Relaxed) != 0 }
+
+    /// Registers a watermark, initially below its thresholds.
+    ///
+    /// Returns None if `falling` exceeds `rising`, or if all slots are already registered.
+    #[cold]
+    pub(crate) fn register(&self, rising: usize, falling: usize, callback: WatermarkCallback) -> Option<WatermarkId> {
+        if falling > rising {
+            return None;
+        }
+
+        let (index, generation) =
+            self.slots.iter().enumerate().find_map(|(index, slot)| slot.claim().map(|generation| (index, generation)))?;
+
+        let slot = &self.slots[index];
+
+        slot.rising.store(rising, Ordering::Relaxed);
+        slot.falling.store(falling, Ordering::Relaxed);
+        slot.callback.store(callback as *mut (), Ordering::Relaxed);
+        slot.state.store(generation | BELOW, Ordering::Release);
+
+        self.registered.fetch_add(1, Ordering::Relaxed);
+        ARMED.arm();
+
+        Some(WatermarkId { index, generation })
+    }
+
+    /// Unregisters the watermark identified by `id`.
+    ///
+    /// Returns false if it was not registered, or was already unregistered, even if its slot was since reused.
+    ///
+    /// An evaluation racing with the unregistration may still invoke its callback, one last time.
+    #[cold]
+    pub(crate) fn unregister(&self, id: WatermarkId) -> bool {
+        let slot = match self.slots.get(id.index) {
+            Some(slot) => slot,
+            None => return false,
+        };
+
+        if !slot.release(id.generation) {
+            return false;
+        }
+
+        self.registered.fetch_sub(1, Ordering::Relaxed);
+        ARMED.disarm();
+
+        true
+    }
+
+    /// Evaluates the watermarks against `live_bytes`, invoking the callbacks of those crossed.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn evaluate(&self, live_bytes: usize) {
+        for slot in &self.slots {
+            slot.evaluate(live_bytes);
+        }
+    }
+}
+
+//
+//  Implementation Details
+//
+
+//  Maximum number of watermarks registered at any time.
+const CAPACITY: usize = 8;
+
+//  States of a slot: free, claimed and not yet initialized, or registered and either below or above its thresholds.
+//
+//  The state occupies the low bits of the word of the slot, and the generation the others, the generation being bumped
+//  on each release so that the identifiers of the prior registrations no longer match.
+const FREE: usize = 0;
+const CLAIMED: usize = 1;
+const BELOW: usize = 2;
+const ABOVE: usize = 3;
+
+const STATE: usize = 0b11;
+const GENERATION: usize = STATE + 1;
+
+struct Slot {
+    state: AtomicUsize,
+    rising: AtomicUsize,
+    falling: AtomicUsize,
+    callback: AtomicPtr<()>,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(FREE),
+            rising: AtomicUsize::new(0),
+            falling: AtomicUsize::new(0),
+            callback: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    //  Claims the slot, if free, returning its generation.
+    fn claim(&self) -> Option<usize> {
+        let word = self.state.load(Ordering::Relaxed);
+
+        if word & STATE != FREE {
+            return None;
+        }
+
+        self.state.compare_exchange(word, word | CLAIMED, Ordering::Acquire, Ordering::Relaxed).ok()?;
+
+        Some(word)
+    }
+
+    //  Releases the slot, if registered under `generation`.
+    fn release(&self, generation: usize) -> bool {
+        let next = generation.wrapping_add(GENERATION) | FREE;
+
+        let mut state = self.state.load(Ordering::Relaxed);
+
+        while state == generation | BELOW || state == generation | ABOVE {
+            match self.state.compare_exchange_weak(state, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(current) => state = current,
+            }
+        }
+
+        false
+    }
+
+    fn evaluate(&self, live_bytes: usize) {
+        let current = self.state.load(Ordering::Acquire);
+        let generation = current & !STATE;
+
+        let (next, crossing, threshold) = match current & STATE {
+            BELOW => (generation | ABOVE, Crossing::Rising, self.rising.load(Ordering::Relaxed)),
+            ABOVE => (generation | BELOW, Crossing::Falling, self.falling.load(Ordering::Relaxed)),
+            _ => return,
+        };
+
+        let crossed = match crossing {
+            Crossing::Rising => live_bytes >= threshold,
+            Crossing::Falling => live_bytes < threshold,
+        };
+
+        //  Of concurrent evaluations, only the one flipping the state reports the crossing.
+        if !crossed || self.state.compare_exchange(current, next, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            return;
+        }
+
+        let callback = self.callback.load(Ordering::Relaxed);
+
+        debug_assert!(!callback.is_null());
+
+        //  Safety:
+        //  -   `callback` was stored from a `WatermarkCallback`, prior to the release of the state.
+        let callback: WatermarkCallback = unsafe { mem::transmute::<*mut (), WatermarkCallback>(callback) };
+
+        callback(&WatermarkEvent { crossing, threshold, live_bytes });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+extern crate std;
+
+use std::{sync::Mutex, vec::Vec};
+
+use super::*;
+
+static EVENTS: Mutex<Vec<WatermarkEvent>> = Mutex::new(Vec::new());
+
+fn record(event: &WatermarkEvent) { EVENTS.lock().unwrap().push(*event); }
+
+fn event(crossing: Crossing, threshold: usize, live_bytes: usize) -> WatermarkEvent {
+    WatermarkEvent { crossing, threshold, live_bytes }
+}
+
+#[test]
+fn watermarks_register_unregister() {
+    let watermarks = Watermarks::new();
+    assert!(!watermarks.is_armed());
+
+    assert_eq!(None, watermarks.register(10, 20, record));
+
+    let ids: Vec<_> = (0..CAPACITY).map(|_| watermarks.register(20, 10, record).unwrap()).collect();
+    assert!(watermarks.is_armed());
+    assert_eq!(None, watermarks.register(20, 10, record));
+
+    for id in &ids {
+        assert!(watermarks.unregister(*id));
+        assert!(!watermarks.unregister(*id));
+    }
+
+    assert!(!watermarks.is_armed());
+    assert!(!watermarks.unregister(WatermarkId { index: CAPACITY, generation: 0 }));
+}
+
+#[test]
+fn watermarks_unregister_stale() {
+    let watermarks = Watermarks::new();
+
+    let stale = watermarks.register(20, 10, record).unwrap();
+    assert!(watermarks.unregister(stale));
+
+    //  The slot is reused, under another generation.
+    let id = watermarks.register(20, 10, record).unwrap();
+    assert_eq!(stale.index, id.index);
+    assert_ne!(stale, id);
+
+    assert!(!watermarks.unregister(stale));
+    assert!(watermarks.is_armed());
+
+    assert!(watermarks.unregister(id));
+    assert!(!watermarks.is_armed());
+}
+
+#[test]
+fn watermarks_evaluate_hysteresis() {
+    let watermarks = Watermarks::new();
+    let id = watermarks.register(100, 50, record).unwrap();
+
+    for live_bytes in [10, 99, 100, 150, 50, 49, 10, 99, 120] {
+        watermarks.evaluate(live_bytes);
+    }
+
+    assert!(watermarks.unregister(id));
+    watermarks.evaluate(0);
+
+    let events = std::mem::take(&mut *EVENTS.lock().unwrap());
+
+    assert_eq!(
+        [event(Crossing::Rising, 100, 100), event(Crossing::Falling, 50, 49), event(Crossing::Rising, 100, 120)],
+        &events[..]);
+}
+
+} // mod tests
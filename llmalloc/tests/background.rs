@@ -0,0 +1,58 @@
+//  The background thread is process-wide, and takes over the maintenance of all threads, hence it is checked in its own
+//  test binary, on the platforms spawning it.
+#![cfg(all(any(target_os = "linux", feature = "posix"),
+    not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc"))))]
+
+use std::{alloc::Layout, thread, time::{Duration, Instant}};
+
+use llmalloc::LLAllocator;
+
+#[test]
+fn background_thread() {
+    const PERIOD: Duration = Duration::from_millis(5);
+
+    let allocator = LLAllocator::new();
+
+    assert!(!allocator.is_background_thread_running());
+
+    allocator.set_background_thread(Some(PERIOD)).expect("Spawned");
+    assert_eq!(Some(PERIOD), allocator.background_thread_period());
+    assert!(allocator.is_background_thread_running());
+
+    //  A retirement, guarded by no reader, is freed by the background thread within a few passes.
+    let node = allocator.allocate(Layout::from_size_align(8, 8).unwrap()).expect("Allocated");
+    unsafe { allocator.defer_free(node) };
+
+    let passes = allocator.background_thread_passes();
+    wait_until(|| allocator.background_thread_passes() >= passes + 4);
+
+    //  Nothing is left for the explicit collections, however many epochs they advance.
+    for _ in 0..4 {
+        assert_eq!(0, allocator.reclaim());
+    }
+
+    //  Disabled, the thread exits on its next wake-up, and may be spawned anew.
+    allocator.set_background_thread(None).expect("Disabled");
+    assert_eq!(None, allocator.background_thread_period());
+
+    wait_until(|| !allocator.is_background_thread_running());
+
+    allocator.set_background_thread(Some(PERIOD)).expect("Spawned");
+
+    let passes = allocator.background_thread_passes();
+    wait_until(|| allocator.background_thread_passes() > passes);
+
+    allocator.set_background_thread(None).expect("Disabled");
+}
+
+fn wait_until<F>(mut condition: F)
+    where
+        F: FnMut() -> bool,
+{
+    let start = Instant::now();
+
+    while !condition() {
+        assert!(start.elapsed() < Duration::from_secs(10), "Timed out");
+        thread::sleep(Duration::from_millis(1));
+    }
+}
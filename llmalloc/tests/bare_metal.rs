@@ -0,0 +1,63 @@
+//  The state of the bare-metal platform is process-wide, hence all the checks are performed by a single test.
+#![cfg(feature = "bare-metal")]
+
+use std::{
+    alloc::Layout,
+    ptr::{self, NonNull},
+    sync::atomic::AtomicPtr,
+};
+
+use llmalloc::{AllocationError, CodeMapping, LLAllocator};
+
+#[test]
+fn bare_metal() {
+    //  Large enough to hold a few Huge Pages, whether of 1 GB or, with `small-heap`, of 2 MB.
+    const SIZE: usize = 5 << 30;
+
+    fn slot() -> &'static AtomicPtr<u8> {
+        static SLOT: AtomicPtr<u8> = AtomicPtr::new(ptr::null_mut());
+        &SLOT
+    }
+
+    let allocator = LLAllocator::new();
+
+    //  Without a region, there is no memory.
+    assert_eq!(Err(AllocationError::OutOfMemory), allocator.try_allocate(Layout::from_size_align(8, 8).unwrap()));
+
+    //  The region is reserved with `mmap`, standing in for the memory handed in by a bootloader, and handed in
+    //  misaligned, to exercise the alignment.
+    let mapping = unsafe {
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE;
+        libc::mmap(ptr::null_mut(), SIZE, libc::PROT_READ | libc::PROT_WRITE, flags, -1, 0)
+    };
+    assert_ne!(libc::MAP_FAILED, mapping);
+
+    let start = mapping as usize;
+    let region = NonNull::new((start + 8) as *mut u8).expect("Region");
+
+    assert_eq!(Err(()), unsafe { allocator.provide_region(region, 16) });
+    assert_eq!(Ok(()), unsafe { allocator.provide_region(region, SIZE - 8) });
+    assert_eq!(Err(()), unsafe { allocator.provide_region(region, SIZE - 8) });
+
+    allocator.warm_up().expect("Warmed up!");
+
+    //  The single slot is in use, following the warm up.
+    assert_eq!(Err(()), unsafe { allocator.provide_thread_slot(slot) });
+
+    for size in [8, 256, 4096, 1 << 20] {
+        let layout = Layout::from_size_align(size, 8).unwrap();
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        assert!(pointer.as_ptr() as usize > start && (pointer.as_ptr() as usize) < start + SIZE);
+
+        unsafe {
+            pointer.as_ptr().write_bytes(0x5a, size);
+            allocator.deallocate(pointer);
+        }
+    }
+
+    //  Neither code, nor stacks, nor physical buffers, can be mapped.
+    assert!(allocator.allocate_code(4096, CodeMapping::Flip).is_err());
+    assert!(allocator.allocate_stack(4096, false).is_err());
+    assert!(allocator.allocate_physical(4096).is_err());
+}
@@ -1,4 +1,16 @@
-use llmalloc::LLAllocator;
+//  The bare-metal, and custom, platforms have no memory until provided, see `bare_metal.rs` and `custom_platform.rs`,
+//  and the mock platform maps neither code, stacks, nor physical buffers, see `mock_platform.rs`.
+#![cfg(not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))]
+
+use std::{
+    alloc::{GlobalAlloc, Layout},
+    time::Duration,
+};
+
+use llmalloc::{
+    node_box, AllocationError, Capabilities, CodeMapping, Criticality, Crossing, InitStage, LLAllocator, NodeBox,
+    NodeVec, NumaNodeIndex, Relocatable, ThreadProfile, WatermarkEvent, ALLOCATED_POISON,
+};
 
 #[test]
 fn warm_up() {
@@ -6,6 +18,1174 @@ fn warm_up() {
     allocator.warm_up().expect("Warmed up!");
 }
 
-//  FIXME: use sys crates... properly configured for system libraries.
-#[link(name = "numa")]
-extern "C" {}
+#[test]
+fn capabilities() {
+    let allocator = LLAllocator::new();
+    allocator.warm_up().expect("Warmed up!");
+
+    let capabilities = allocator.capabilities();
+    assert_eq!(capabilities, allocator.capabilities());
+
+    //  Whichever the environment, Huge Pages are backed by something, and each downgrade is reported on its own line.
+    let report = capabilities.to_string();
+    assert_eq!(capabilities.downgrades().count().max(1), report.lines().count(), "{}", report);
+
+    let full =
+        Capabilities { huge_tlb: true, huge_tlb_2mb: true, transparent_huge_pages: true, numa: true, sysfs: true };
+    assert!(!full.is_degraded());
+}
+
+#[test]
+fn host_capabilities() {
+    let allocator = LLAllocator::new();
+    allocator.warm_up().expect("Warmed up!");
+
+    let host = allocator.host_capabilities();
+
+    assert_eq!(allocator.capabilities(), host.capabilities);
+    assert!(host.numa_nodes >= 1, "{:?}", host);
+    assert!(host.far_numa_nodes < host.numa_nodes, "{:?}", host);
+    assert!(host.os_page_size >= 4096, "{:?}", host);
+    assert!(host.huge_page_sizes().windows(2).all(|sizes| sizes[0] < sizes[1]), "{:?}", host);
+
+    //  One line per detail, followed by one line per downgrade.
+    let report = host.to_string();
+    assert_eq!(6 + host.capabilities.downgrades().count().max(1), report.lines().count(), "{}", report);
+}
+
+#[test]
+fn fallback_metrics() {
+    let allocator = LLAllocator::new();
+    allocator.warm_up().expect("Warmed up!");
+
+    //  At least one `HugePage` is mapped by the warm-up, one way or another.
+    let metrics = allocator.fallback_metrics();
+    let mappings = metrics.huge_tlb_mappings + metrics.huge_tlb_2mb_mappings + metrics.transparent_huge_page_mappings +
+        metrics.normal_page_mappings;
+
+    assert!(mappings > 0, "{:?}", metrics);
+    assert!(metrics.total() >=
+        metrics.huge_tlb_2mb_mappings + metrics.transparent_huge_page_mappings + metrics.normal_page_mappings);
+
+    //  A downgraded HugeTLB is reflected in both the capabilities and the metrics.
+    if !allocator.capabilities().huge_tlb {
+        assert!(metrics.huge_tlb_mappings == 0 || metrics.huge_tlb_failures > 0, "{:?}", metrics);
+    }
+}
+
+#[test]
+fn stats_print() {
+    let allocator = LLAllocator::new();
+
+    let pointer = allocator.try_allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    let mut report = String::new();
+    allocator.stats_print(&mut report).expect("Printed");
+
+    unsafe { allocator.deallocate(pointer) };
+
+    let lines: Vec<_> = report.lines().collect();
+
+    assert_eq!(Some(&"___ Begin llmalloc statistics ___"), lines.first(), "{}", report);
+    assert_eq!(Some(&"--- End llmalloc statistics ---"), lines.last(), "{}", report);
+
+    for section in &["Configuration:", "Capabilities:", "HugeTLB pools:", "Merged:", "Requested sizes:", "Fallbacks:"] {
+        assert!(lines.contains(section), "{} missing from {}", section, report);
+    }
+
+    let socket = format!("Socket {}:", allocator.socket_index());
+    assert!(lines.contains(&socket.as_str()), "{} missing from {}", socket, report);
+}
+
+#[test]
+fn init() {
+    let allocator = LLAllocator::new();
+
+    for stage in &InitStage::ALL {
+        allocator.init_stage(*stage).expect("Initialized stage");
+    }
+
+    let metrics = allocator.init().expect("Initialized!");
+
+    //  Without libc, the thread-local storage is a `#[thread_local]` static, rather than a key.
+    assert!(metrics.key_creation.is_some() || cfg!(feature = "no-libc"), "{:?}", metrics);
+    assert!(metrics.first_mapping.is_some(), "{:?}", metrics);
+    assert!(metrics.thread_warm_ups >= 1, "{:?}", metrics);
+    assert!(metrics.thread_warm_up_max <= metrics.thread_warm_up_total, "{:?}", metrics);
+}
+
+#[test]
+fn init_latency_critical() {
+    let allocator = LLAllocator::new();
+
+    let report = allocator.init_latency_critical(1 << 20);
+
+    assert_eq!(1 << 20, report.budget);
+    assert_eq!(None, report.failed_stage, "{:?}", report);
+    assert!(report.reserved >= report.budget, "{:?}", report);
+    assert!(report.locked <= report.reserved, "{:?}", report);
+    assert!(report.total_classes > 0, "{:?}", report);
+    assert_eq!(report.total_classes, report.warmed_classes, "{:?}", report);
+    assert!(report.metrics.thread_warm_ups >= 1, "{:?}", report);
+
+    //  Locking may fail, if the limit of locked memory of the process is too low.
+    assert_eq!(report.locked == report.reserved, report.is_complete(), "{:?}", report);
+}
+
+#[test]
+fn criticality() {
+    let allocator = LLAllocator::new();
+
+    assert_eq!(Criticality::Normal, allocator.criticality());
+
+    allocator.set_criticality(Criticality::Critical).expect("Declared");
+    assert_eq!(Criticality::Critical, allocator.criticality());
+
+    assert_eq!(1, allocator.reserve_critical(1));
+
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    unsafe { allocator.deallocate(pointer) };
+
+    assert_eq!(0, allocator.reserve_critical(0));
+}
+
+#[test]
+fn reserve_ahead() {
+    let allocator = LLAllocator::new();
+
+    let layout = Layout::from_size_align(48, 8).unwrap();
+    assert_eq!(16, allocator.reserve_ahead(16, layout));
+
+    let pointers: Vec<_> = (0..16).map(|_| allocator.allocate(layout).expect("Allocated")).collect();
+
+    for pointer in pointers {
+        unsafe { allocator.deallocate(pointer) };
+    }
+
+    //  Only Normal allocations are cached by the thread.
+    assert_eq!(0, allocator.reserve_ahead(16, Layout::from_size_align(1 << 21, 8).unwrap()));
+}
+
+#[test]
+fn forbid_allocation() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    let outer = allocator.forbid_allocation().expect("Forbidden");
+    let inner = allocator.forbid_allocation().expect("Forbidden");
+
+    //  Deallocations remain permitted, hence the pointer is allocated beforehand.
+    drop((inner, outer));
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    let guard = allocator.forbid_allocation().expect("Forbidden");
+
+    //  The attempts fail, in all builds, rather than panic.
+    assert_eq!(Err(AllocationError::Forbidden), allocator.try_allocate(layout));
+    assert!(unsafe { GlobalAlloc::alloc(&allocator, layout) }.is_null());
+    assert!(unsafe { allocator.reallocate(pointer, layout, 4096) }.is_none());
+
+    unsafe { allocator.deallocate(pointer) };
+    drop(guard);
+
+    //  Once all guards are dropped, allocations are permitted again.
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    unsafe { allocator.deallocate(pointer) };
+}
+
+#[test]
+fn flush_thread_cache() {
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    std::thread::spawn(move || {
+        let allocator = LLAllocator::new();
+
+        //  Flushing a thread which never allocated does nothing.
+        llmalloc::flush_thread_cache();
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        allocator.flush_thread_cache();
+
+        //  The thread remains usable, its cache being filled anew, here and once the guard flushes it.
+        let guard = allocator.flush_on_drop();
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        drop(guard);
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+    }).join().expect("Joined");
+}
+
+#[test]
+fn register_thread_with() {
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    std::thread::spawn(move || {
+        let allocator = LLAllocator::new();
+
+        //  A latency-critical thread is declared Critical.
+        allocator.register_thread_with(ThreadProfile::LatencyCritical).expect("Registered");
+        assert_eq!(Criticality::Critical, allocator.criticality());
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        //  A background thread is declared Background, and is served as usual, its cache being flushed periodically.
+        allocator.register_thread_with(ThreadProfile::Background).expect("Registered");
+        assert_eq!(Criticality::Background, allocator.criticality());
+
+        let pointers: Vec<_> = (0..1024).map(|_| allocator.allocate(layout).expect("Allocated")).collect();
+
+        for pointer in pointers {
+            unsafe { allocator.deallocate(pointer) };
+        }
+
+        allocator.register_thread_with(ThreadProfile::Normal).expect("Registered");
+        assert_eq!(Criticality::Normal, allocator.criticality());
+    }).join().expect("Joined");
+}
+
+#[test]
+fn trim_idle_thread_cache() {
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    std::thread::spawn(move || {
+        let allocator = LLAllocator::new();
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        //  Disabled by default.
+        assert_eq!(None, allocator.idle_trim());
+        assert!(!allocator.trim_idle_thread_cache());
+
+        allocator.set_idle_trim(Some(Duration::from_millis(1)));
+        assert_eq!(Some(Duration::from_millis(1)), allocator.idle_trim());
+
+        //  The first call records the activity of the thread, and the first past the threshold trims it, once.
+        assert!(!allocator.trim_idle_thread_cache());
+
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(llmalloc::trim_idle_thread_cache());
+        assert!(!allocator.trim_idle_thread_cache());
+
+        //  Any activity is recorded anew.
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        assert!(!allocator.trim_idle_thread_cache());
+
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(allocator.trim_idle_thread_cache());
+
+        allocator.set_idle_trim(None);
+    }).join().expect("Joined");
+}
+
+#[test]
+fn defer_free() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(8, 8).unwrap();
+
+    let guard = allocator.pin().expect("Pinned");
+
+    let node = allocator.allocate(layout).expect("Allocated").cast::<u64>();
+    unsafe { node.as_ptr().write(42) };
+
+    unsafe { allocator.defer_free(node.cast()) };
+
+    //  The epoch advances once past the guard, but no further while it is alive.
+    for _ in 0..4 {
+        assert_eq!(0, allocator.reclaim());
+    }
+
+    assert_eq!(42, unsafe { node.as_ptr().read() });
+
+    drop(guard);
+
+    assert_eq!(1, allocator.reclaim());
+    assert_eq!(0, allocator.reclaim());
+}
+
+#[test]
+fn compact() {
+    const COUNT: usize = 1 << 15;
+    const STRIDE: usize = 256;
+
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    let pointers: Vec<_> = (0..COUNT).map(|_| allocator.allocate(layout).expect("Allocated")).collect();
+
+    //  Keep one allocation in `STRIDE`, leaving their pages sparse.
+    let mut allocations = Vec::new();
+
+    for (index, pointer) in pointers.into_iter().enumerate() {
+        if index % STRIDE == 0 {
+            unsafe { pointer.cast::<usize>().as_ptr().write(index) };
+            allocations.push(Relocatable { pointer, layout });
+        } else {
+            unsafe { allocator.deallocate(pointer) };
+        }
+    }
+
+    let originals: Vec<_> = allocations.iter().map(|allocation| allocation.pointer).collect();
+    let mut moves = Vec::new();
+
+    let report = unsafe { allocator.compact(&mut allocations, |old, new| moves.push((old, new))) };
+
+    assert!(report.sparse_pages >= 1);
+    assert_eq!(report.sparse_pages, report.evacuated_pages);
+    assert_eq!(allocations.len(), report.relocated);
+    assert_eq!(allocations.len() * layout.size(), report.relocated_bytes);
+
+    //  The content is preserved, and both the handles and the callback observe each move, in page order.
+    assert_eq!(allocations.len(), moves.len());
+
+    for (index, (allocation, original)) in allocations.iter().zip(&originals).enumerate() {
+        assert_ne!(*original, allocation.pointer);
+        assert!(moves.contains(&(*original, allocation.pointer)));
+        assert_eq!(index * STRIDE, unsafe { allocation.pointer.cast::<usize>().as_ptr().read() });
+    }
+
+    for allocation in &allocations {
+        unsafe { allocator.deallocate(allocation.pointer) };
+    }
+
+    //  Nothing to relocate.
+    assert_eq!(0, unsafe { allocator.compact(&mut [], |_, _| ()) }.relocated);
+}
+
+#[test]
+fn seal() {
+    let allocator = LLAllocator::new();
+
+    let normal = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+    assert_eq!(Err(()), unsafe { allocator.seal(normal, 64) });
+
+    let layout = Layout::from_size_align(1 << 20, 8).unwrap();
+    let large = allocator.allocate(layout).expect("Allocated").cast::<u64>();
+
+    unsafe { large.as_ptr().write(42) };
+
+    //  Sealed memory remains readable.
+    unsafe { allocator.seal(large.cast(), layout.size()) }.expect("Sealed");
+    assert_eq!(42, unsafe { large.as_ptr().read() });
+
+    unsafe { allocator.unseal(large.cast(), layout.size()) }.expect("Unsealed");
+    unsafe { large.as_ptr().write(43) };
+
+    unsafe {
+        allocator.deallocate(large.cast());
+        allocator.deallocate(normal);
+    }
+}
+
+#[test]
+fn seal_arena() {
+    const WORDS: usize = 1024;
+
+    let allocator = LLAllocator::new();
+
+    //  The arena spans a Large allocation, however small the request, hence may always be sealed.
+    let mut small = allocator.allocate_arena(1).expect("Allocated");
+    assert!(small.size() > 1);
+
+    unsafe { allocator.seal_arena(&mut small) }.expect("Sealed");
+    allocator.unseal_arena(&mut small).expect("Unsealed");
+
+    unsafe { allocator.deallocate_arena(small) };
+
+    //  A table of small objects, carved out of the arena, then sealed as a whole once built.
+    let mut arena = allocator.allocate_arena(WORDS * 8).expect("Allocated");
+    assert!(arena.size() >= WORDS * 8);
+
+    let layout = Layout::from_size_align(8, 8).unwrap();
+
+    let table: Vec<_> = (0..WORDS).map(|_| arena.allocate(layout).expect("Carved").cast::<u64>()).collect();
+
+    for (index, word) in table.iter().enumerate() {
+        unsafe { word.as_ptr().write(index as u64) };
+    }
+
+    unsafe { allocator.seal_arena(&mut arena) }.expect("Sealed");
+    assert!(arena.is_sealed());
+
+    //  Sealed, the objects remain readable, but no further object is carved out.
+    assert_eq!((WORDS - 1) as u64, unsafe { table[WORDS - 1].as_ptr().read() });
+    assert_eq!(None, arena.allocate(layout));
+
+    allocator.unseal_arena(&mut arena).expect("Unsealed");
+    assert!(!arena.is_sealed());
+
+    unsafe { table[0].as_ptr().write(42) };
+
+    //  Deallocated while sealed, the arena is unsealed first.
+    unsafe { allocator.seal_arena(&mut arena) }.expect("Sealed");
+    unsafe { allocator.deallocate_arena(arena) };
+}
+
+#[test]
+fn allocate_code() {
+    //  `mov eax, 42; ret`
+    const CODE: [u8; 6] = [0xB8, 0x2A, 0x00, 0x00, 0x00, 0xC3];
+
+    let allocator = LLAllocator::new();
+
+    for mapping in [CodeMapping::Dual, CodeMapping::Flip] {
+        let region = allocator.allocate_code(CODE.len(), mapping).expect("Allocated");
+        assert_eq!(mapping, region.mapping());
+        assert!(region.size() >= CODE.len());
+        assert_eq!(mapping == CodeMapping::Flip, region.writable() == region.executable());
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(CODE.as_ptr(), region.writable().as_ptr(), CODE.len());
+            allocator.make_executable(&region).expect("Executable");
+        }
+
+        //  Both views share the same memory.
+        assert_eq!(CODE, unsafe { *region.executable().cast::<[u8; 6]>().as_ptr() });
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let function: extern "C" fn() -> u32 = unsafe { std::mem::transmute(region.executable().as_ptr()) };
+            assert_eq!(42, function());
+        }
+
+        unsafe {
+            allocator.make_writable(&region).expect("Writable");
+            allocator.deallocate_code(region);
+        }
+    }
+
+    assert_eq!(Some(AllocationError::OutOfMemory), allocator.allocate_code(usize::MAX, CodeMapping::Flip).err());
+
+    let bounded = LLAllocator::with_maximum_size(1 << 12);
+    assert_eq!(Some(AllocationError::ExceedsMaximumSize), bounded.allocate_code(1 << 13, CodeMapping::Dual).err());
+}
+
+#[test]
+fn allocate_stack() {
+    extern "C" fn run(_: *mut libc::c_void) -> *mut libc::c_void {
+        //  Use some of the stack.
+        let buffer = std::hint::black_box([7u8; 4096]);
+
+        buffer.iter().map(|byte| *byte as usize).sum::<usize>() as *mut libc::c_void
+    }
+
+    const SIZE: usize = 1 << 16;
+
+    let allocator = LLAllocator::new();
+
+    let stack = allocator.allocate_stack(SIZE, true).expect("Allocated");
+    assert!(stack.size() >= SIZE);
+    assert!(stack.guard_size() > 0);
+    assert_eq!(stack.bottom().as_ptr() as usize + stack.size(), stack.top().as_ptr() as usize);
+
+    //  Run a thread on the stack.
+    let result = unsafe {
+        let mut attributes: libc::pthread_attr_t = std::mem::zeroed();
+        assert_eq!(0, libc::pthread_attr_init(&mut attributes));
+        assert_eq!(0, libc::pthread_attr_setstack(&mut attributes, stack.bottom().as_ptr() as *mut _, stack.size()));
+
+        let mut thread: libc::pthread_t = std::mem::zeroed();
+        assert_eq!(0, libc::pthread_create(&mut thread, &attributes, run, std::ptr::null_mut()));
+
+        let mut result = std::ptr::null_mut();
+        assert_eq!(0, libc::pthread_join(thread, &mut result));
+        assert_eq!(0, libc::pthread_attr_destroy(&mut attributes));
+
+        result as usize
+    };
+
+    assert_eq!(7 * 4096, result);
+
+    unsafe { allocator.deallocate_stack(stack) };
+
+    let bounded = LLAllocator::with_maximum_size(SIZE);
+    assert_eq!(Some(AllocationError::ExceedsMaximumSize), bounded.allocate_stack(SIZE + 1, false).err());
+}
+
+#[test]
+fn allocate_physical() {
+    const SIZE: usize = 1 << 16;
+
+    let allocator = LLAllocator::new();
+
+    //  The limit of locked memory may be too low to lock the buffer.
+    let buffer = match allocator.allocate_physical(SIZE) {
+        Ok(buffer) => buffer,
+        Err(error) => return assert_eq!(AllocationError::OutOfMemory, error),
+    };
+
+    assert!(buffer.size() >= SIZE);
+    assert_eq!(0, buffer.pointer().as_ptr() as usize % (2 << 20));
+
+    unsafe { buffer.pointer().as_ptr().write_bytes(0xA5, buffer.size()) };
+
+    //  The physical addresses are only readable with sufficient privileges.
+    let mut segments = Vec::new();
+
+    if let Ok(count) = allocator.physical_segments(&buffer, |segment| segments.push(*segment)) {
+        assert_eq!(count, segments.len());
+        assert_eq!(buffer.size(), segments.iter().map(|segment| segment.size).sum::<usize>());
+
+        let mut offset = 0;
+
+        for segment in &segments {
+            assert_eq!(offset, segment.offset);
+            assert_ne!(0, segment.physical_address);
+            offset += segment.size;
+        }
+    }
+
+    unsafe { allocator.deallocate_physical(buffer) };
+
+    let bounded = LLAllocator::with_maximum_size(SIZE);
+    assert_eq!(Some(AllocationError::ExceedsMaximumSize), bounded.allocate_physical(SIZE + 1).err());
+}
+
+#[test]
+fn allocate_on_node() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(96, 32).expect("Valid Layout");
+
+    //  Node 0 exists on any machine, NUMA or not.
+    let first = NumaNodeIndex::new(0);
+
+    let pointer = allocator.allocate_on_node(first, layout).expect("Allocated on node 0");
+    assert_eq!(0, pointer.as_ptr() as usize % 32);
+
+    unsafe { pointer.as_ptr().write_bytes(0xA5, layout.size()) };
+    unsafe { allocator.deallocate(pointer) };
+
+    //  The current node is a node of the machine.
+    let node = allocator.current_node();
+
+    let pointer = allocator.allocate_on_node(node, layout).expect("Allocated on the current node");
+    unsafe { allocator.deallocate(pointer) };
+
+    let unknown = [NumaNodeIndex::new(64), NumaNodeIndex::new(u32::MAX)];
+
+    for node in unknown {
+        assert_eq!(Some(AllocationError::UnknownNode), allocator.allocate_on_node(node, layout).err());
+    }
+
+    let bounded = LLAllocator::with_maximum_size(64);
+    assert_eq!(Some(AllocationError::ExceedsMaximumSize), bounded.allocate_on_node(first, layout).err());
+}
+
+#[test]
+fn node_containers() {
+    const FIRST: NumaNodeIndex = NumaNodeIndex::new(0);
+    const UNKNOWN: NumaNodeIndex = NumaNodeIndex::new(64);
+
+    let mut value = node_box(FIRST, [7u64; 16]).expect("Boxed on node 0");
+    value[3] = 3;
+
+    assert_eq!(FIRST, NodeBox::node(&value));
+    assert_eq!(7 * 15 + 3, value.iter().sum::<u64>());
+
+    assert_eq!(Some(AllocationError::UnknownNode), NodeBox::new(UNKNOWN, 0u8).err());
+
+    let mut vector = NodeVec::new(FIRST);
+    assert!(vector.is_empty());
+
+    for i in 0..1000u32 {
+        vector.push(i.to_string()).expect("Pushed");
+    }
+
+    assert_eq!(1000, vector.len());
+    assert!(vector.capacity() >= 1000);
+    assert_eq!("999", vector.pop().expect("Popped"));
+    assert_eq!("42", vector[42]);
+
+    vector.clear();
+    assert!(vector.is_empty());
+
+    let mut unknown = NodeVec::new(UNKNOWN);
+    assert_eq!(Err(5u8), unknown.push(5));
+    assert_eq!(Some(AllocationError::UnknownNode), NodeVec::<u8>::with_capacity(UNKNOWN, 1).err());
+
+    let mut empty = NodeVec::new(UNKNOWN);
+    empty.push(()).expect("Zero-sized elements require no memory");
+    assert_eq!(1, empty.len());
+}
+
+#[test]
+fn frame_mode() {
+    const CAPACITY: usize = 1 << 12;
+
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    assert!(!allocator.is_frame_mode());
+    allocator.enter_frame_mode(CAPACITY).expect("Entered");
+    assert!(allocator.is_frame_mode());
+    assert!(allocator.enter_frame_mode(CAPACITY).is_err());
+
+    let first = allocator.allocate(layout).expect("Allocated");
+    let second = allocator.allocate(layout).expect("Allocated");
+    assert_eq!(first.as_ptr() as usize + layout.size(), second.as_ptr() as usize);
+
+    let in_frame = |pointer: std::ptr::NonNull<u8>| {
+        (first.as_ptr() as usize..first.as_ptr() as usize + CAPACITY).contains(&(pointer.as_ptr() as usize))
+    };
+
+    //  Deallocating frame memory is a no-op, persistent allocations are made from the heap.
+    unsafe { allocator.deallocate(second) };
+
+    let persistent = allocator.allocate_persistent(layout).expect("Allocated");
+    assert!(!in_frame(persistent));
+
+    //  Once the frame region is exhausted, allocations are made from the heap.
+    let exhausting = Layout::from_size_align(2 * CAPACITY, 8).unwrap();
+    let overflow = allocator.allocate(exhausting).expect("Allocated");
+    assert!(!in_frame(overflow));
+
+    unsafe {
+        allocator.deallocate(overflow);
+        allocator.end_frame();
+    }
+
+    let third = allocator.allocate(layout).expect("Allocated");
+    assert_eq!(first, third);
+
+    unsafe { allocator.exit_frame_mode() };
+    assert!(!allocator.is_frame_mode());
+
+    let fourth = allocator.allocate(layout).expect("Allocated");
+
+    unsafe {
+        allocator.deallocate(fourth);
+        allocator.deallocate(persistent);
+    }
+}
+
+#[test]
+fn epoch_tracking() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(48, 8).unwrap();
+
+    allocator.set_epoch_tracking(true);
+    assert!(allocator.is_epoch_tracking());
+
+    let leaked = allocator.allocate(layout).expect("Allocated");
+    let transient = allocator.allocate(layout).expect("Allocated");
+
+    let epoch = allocator.epoch();
+    assert_eq!(epoch + 1, allocator.advance_epoch());
+
+    unsafe { allocator.deallocate(transient) };
+
+    let fresh = allocator.allocate(layout).expect("Allocated");
+
+    let survivors = || {
+        let mut survivors = Vec::new();
+        allocator.surviving_allocations(0, |survivor| survivors.push((survivor.address, survivor.epoch)));
+        survivors
+    };
+
+    //  Other tests allocate concurrently, hence only the allocations of this test are checked.
+    let survived = survivors();
+    assert!(survived.iter().any(|&(address, at)| address == leaked.as_ptr() as usize && at >= epoch), "{:?}", survived);
+    assert!(survived.iter().all(|&(address, _)| address != transient.as_ptr() as usize), "{:?}", survived);
+    assert!(survived.iter().all(|&(address, _)| address != fresh.as_ptr() as usize), "{:?}", survived);
+
+    unsafe {
+        allocator.deallocate(leaked);
+        allocator.deallocate(fresh);
+    }
+
+    assert!(survivors().iter().all(|&(address, _)| address != leaked.as_ptr() as usize));
+
+    allocator.set_epoch_tracking(false);
+}
+
+#[test]
+fn watermarks() {
+    use std::sync::Mutex;
+
+    static EVENTS: Mutex<Vec<WatermarkEvent>> = Mutex::new(Vec::new());
+
+    fn record(event: &WatermarkEvent) { EVENTS.lock().unwrap().push(*event); }
+
+    fn take() -> Vec<WatermarkEvent> { std::mem::take(&mut *EVENTS.lock().unwrap()) }
+
+    const MB: usize = 1 << 20;
+
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(128 * MB, 8).unwrap();
+
+    assert_eq!(None, allocator.register_watermark(0, 1, record));
+
+    //  A watermark at 0 is crossed on the first evaluation, reporting the current live bytes.
+    let id = allocator.register_watermark(0, 0, record).expect("Registered");
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    unsafe { allocator.deallocate(pointer) };
+
+    assert!(allocator.unregister_watermark(id));
+    assert!(!allocator.unregister_watermark(id));
+
+    let events = take();
+    assert_eq!(1, events.len(), "{:?}", events);
+    assert_eq!(Crossing::Rising, events[0].crossing);
+
+    //  The other tests allocate concurrently, hence the thresholds leave a generous margin.
+    let base = events[0].live_bytes - layout.size();
+    let id = allocator.register_watermark(base + 64 * MB, base + 32 * MB, record).expect("Registered");
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    let events = take();
+    assert_eq!(1, events.len(), "{:?}", events);
+    assert_eq!((Crossing::Rising, base + 64 * MB), (events[0].crossing, events[0].threshold));
+
+    unsafe { allocator.deallocate(pointer) };
+
+    let events = take();
+    assert_eq!(1, events.len(), "{:?}", events);
+    assert_eq!((Crossing::Falling, base + 32 * MB), (events[0].crossing, events[0].threshold));
+
+    assert!(allocator.unregister_watermark(id));
+}
+
+#[test]
+fn maximum_size() {
+    const MAXIMUM_SIZE: usize = 1 << 20;
+
+    let allocator = LLAllocator::with_maximum_size(MAXIMUM_SIZE);
+    assert_eq!(MAXIMUM_SIZE, allocator.maximum_size());
+    assert_eq!(usize::MAX, LLAllocator::new().maximum_size());
+
+    let layout = Layout::from_size_align(MAXIMUM_SIZE, 8).unwrap();
+    let pointer = allocator.try_allocate(layout).expect("Allocated");
+    unsafe { allocator.deallocate(pointer) };
+
+    let layout = Layout::from_size_align(MAXIMUM_SIZE + 1, 8).unwrap();
+    assert_eq!(Err(AllocationError::ExceedsMaximumSize), allocator.try_allocate(layout));
+    assert_eq!(None, allocator.allocate(layout));
+
+    let layout = Layout::from_size_align(isize::MAX as usize / 2, 8).unwrap();
+    assert_eq!(Err(AllocationError::ExceedsMaximumSize), allocator.try_allocate(layout));
+
+    #[cfg(not(feature = "system-fallback"))]
+    {
+        let layout = Layout::from_size_align(8, 1 << 31).unwrap();
+        assert_eq!(Err(AllocationError::UnsupportedAlignment), allocator.try_allocate(layout));
+    }
+}
+
+#[test]
+fn direct_threshold() {
+    #[cfg(not(feature = "small-heap"))]
+    const HUGE_PAGE_SIZE: usize = 1 << 30;
+
+    #[cfg(feature = "small-heap")]
+    const HUGE_PAGE_SIZE: usize = 1 << 21;
+
+    const DIRECT_THRESHOLD: usize = 1 << 20;
+
+    //  A directly mapped allocation spans whole `HugePage`s, whereas the others are carved past the header of theirs.
+    fn is_direct(pointer: std::ptr::NonNull<u8>) -> bool { (pointer.as_ptr() as usize).is_multiple_of(HUGE_PAGE_SIZE) }
+
+    let allocator = LLAllocator::new().with_direct_threshold(DIRECT_THRESHOLD);
+    assert_eq!(DIRECT_THRESHOLD, allocator.direct_threshold());
+    assert_eq!(usize::MAX, LLAllocator::new().direct_threshold());
+
+    //  Normal allocations are never directly mapped.
+    let normal = LLAllocator::new().with_direct_threshold(0);
+
+    let pointer = normal.try_allocate(Layout::from_size_align(8, 8).unwrap()).expect("Allocated");
+    assert!(!is_direct(pointer), "{:?}", pointer);
+    unsafe { normal.deallocate(pointer) };
+
+    //  Large allocations above the threshold are.
+    let retained = allocator.is_direct_retained();
+
+    for retain in [true, false] {
+        allocator.set_direct_retained(retain);
+        assert_eq!(retain, allocator.is_direct_retained());
+
+        let layout = Layout::from_size_align(DIRECT_THRESHOLD + 1, 8).unwrap();
+        let pointer = allocator.try_allocate(layout).expect("Allocated");
+        assert!(is_direct(pointer), "{:?}", pointer);
+
+        unsafe { allocator.deallocate(pointer) };
+    }
+
+    allocator.set_direct_retained(retained);
+}
+
+#[test]
+fn reallocate() {
+    const GROWN: usize = (1 << 30) + (1 << 29);
+
+    fn fill(pointer: *mut u8, size: usize) {
+        for offset in (0..size).step_by(4096) {
+            unsafe { *pointer.add(offset) = (offset / 4096) as u8 };
+        }
+    }
+
+    fn check(pointer: *mut u8, size: usize) {
+        for offset in (0..size).step_by(4096) {
+            assert_eq!((offset / 4096) as u8, unsafe { *pointer.add(offset) }, "{}", offset);
+        }
+    }
+
+    //  Copied.
+    let allocator = LLAllocator::new();
+
+    let layout = Layout::from_size_align(8, 8).unwrap();
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    unsafe { pointer.as_ptr().write_bytes(0x42, 8) };
+
+    let pointer = unsafe { allocator.reallocate(pointer, layout, 100) }.expect("Reallocated");
+    assert_eq!([0x42; 8], unsafe { *(pointer.as_ptr() as *const [u8; 8]) });
+
+    unsafe { allocator.deallocate(pointer) };
+
+    //  Remapped, as directly mapped.
+    let allocator = LLAllocator::new().with_direct_threshold(1 << 20);
+
+    let layout = Layout::from_size_align(1 << 21, 8).unwrap();
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    fill(pointer.as_ptr(), layout.size());
+
+    let pointer = unsafe { allocator.reallocate(pointer, layout, GROWN) }.expect("Reallocated");
+    check(pointer.as_ptr(), layout.size());
+    fill(pointer.as_ptr(), GROWN);
+
+    let layout = Layout::from_size_align(GROWN, 8).unwrap();
+
+    let pointer = unsafe { allocator.reallocate(pointer, layout, 1 << 21) }.expect("Reallocated");
+    check(pointer.as_ptr(), 1 << 21);
+
+    unsafe { allocator.deallocate(pointer) };
+
+    //  Through `GlobalAlloc`.
+    let layout = Layout::from_size_align(16, 8).unwrap();
+
+    unsafe {
+        let pointer = allocator.alloc(layout);
+        fill(pointer, 16);
+
+        let pointer = allocator.realloc(pointer, layout, 1 << 21);
+        assert!(!pointer.is_null());
+        check(pointer, 16);
+
+        allocator.dealloc(pointer, Layout::from_size_align(1 << 21, 8).unwrap());
+    }
+}
+
+#[test]
+fn remap() {
+    const GROWN: usize = (1 << 30) + (1 << 29);
+
+    let allocator = LLAllocator::with_maximum_size(GROWN).with_direct_threshold(1 << 20);
+
+    //  Normal allocations are not remappable.
+    let normal = allocator.allocate(Layout::from_size_align(8, 8).unwrap()).expect("Allocated");
+    assert_eq!(Err(AllocationError::NotRemappable), unsafe { allocator.remap(normal, 8, 1 << 21) });
+    unsafe { allocator.deallocate(normal) };
+
+    //  Directly mapped allocations are, to directly mapped lengths.
+    let pointer = allocator.allocate(Layout::from_size_align(1 << 21, 8).unwrap()).expect("Allocated");
+    unsafe { pointer.as_ptr().write_bytes(0x42, 4096) };
+
+    assert_eq!(Err(AllocationError::NotRemappable), unsafe { allocator.remap(pointer, 1 << 21, 8) });
+    assert_eq!(Err(AllocationError::ExceedsMaximumSize), unsafe { allocator.remap(pointer, 1 << 21, GROWN + 1) });
+
+    let grown = unsafe { allocator.remap(pointer, 1 << 21, GROWN) };
+
+    //  Without `mremap`, the generic POSIX platform only grows in place, if the adjacent address space is free, and
+    //  `mremap` cannot grow HugeTLB mappings, which the allocation may be backed by if any was mapped.
+    let metrics = allocator.fallback_metrics();
+    let huge_tlb = metrics.huge_tlb_mappings + metrics.huge_tlb_2mb_mappings > 0;
+
+    if (cfg!(feature = "posix") || huge_tlb) && grown.is_err() {
+        assert_eq!(Err(AllocationError::OutOfMemory), grown);
+        assert_eq!([0x42; 4096], unsafe { *(pointer.as_ptr() as *const [u8; 4096]) });
+
+        unsafe { allocator.deallocate(pointer) };
+        return;
+    }
+
+    let pointer = grown.expect("Remapped");
+    assert_eq!([0x42; 4096], unsafe { *(pointer.as_ptr() as *const [u8; 4096]) });
+
+    unsafe { pointer.as_ptr().add(GROWN - 1).write(0x42) };
+
+    let pointer = unsafe { allocator.remap(pointer, GROWN, 1 << 21) }.expect("Remapped");
+    assert_eq!([0x42; 4096], unsafe { *(pointer.as_ptr() as *const [u8; 4096]) });
+
+    unsafe { allocator.deallocate(pointer) };
+}
+
+#[test]
+fn allocate_with_deadline() {
+    use std::time::Duration;
+
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(8, 8).unwrap();
+
+    //  The initialization of a thread is a slow path.
+    let cold = std::thread::spawn(move || LLAllocator::new().allocate_with_deadline(layout, Duration::MAX).err());
+    assert_eq!(Some(AllocationError::DeadlineExceeded), cold.join().unwrap());
+
+    allocator.warm_up().expect("Warmed up!");
+
+    //  Normal allocations are served from the memory already acquired by the socket.
+    let pointer = allocator.allocate_with_deadline(layout, Duration::ZERO).expect("Allocated");
+    unsafe { allocator.deallocate(pointer) };
+
+    //  Huge allocations, larger than any retained, require a mapping, unless the deadline covers it.
+    let huge = Layout::from_size_align(1 << 36, 8).unwrap();
+    assert_eq!(Err(AllocationError::DeadlineExceeded), allocator.allocate_with_deadline(huge, Duration::ZERO));
+}
+
+//  Without libc, there is no system allocator to delegate to.
+#[cfg(all(feature = "system-fallback", not(feature = "no-libc")))]
+#[test]
+fn system_fallback() {
+    let allocator = LLAllocator::new();
+
+    //  Served by llmalloc.
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    unsafe { allocator.deallocate(pointer) };
+
+    //  Delegated to the system allocator, due to its alignment.
+    let layout = Layout::from_size_align(8, 1 << 31).unwrap();
+    let pointer = allocator.try_allocate(layout).expect("Allocated");
+    assert_eq!(0, pointer.as_ptr() as usize % (1 << 31));
+
+    unsafe { pointer.as_ptr().write_bytes(0xFF, 8) };
+    unsafe { allocator.deallocate(pointer) };
+}
+
+#[test]
+fn hardened() {
+    let allocator = LLAllocator::new();
+
+    //  The hardened mode may be enabled from the environment.
+    let previous = allocator.is_hardened();
+
+    allocator.set_hardened(true);
+    assert!(allocator.is_hardened());
+
+    for size in &[8, 100, 4096, 1 << 20] {
+        let layout = Layout::from_size_align(*size, 8).unwrap();
+        let pointer = allocator.allocate(layout).expect("Allocated");
+
+        let bytes = unsafe { std::slice::from_raw_parts(pointer.as_ptr(), *size) };
+        assert!(bytes.iter().all(|byte| *byte == ALLOCATED_POISON), "{}", size);
+
+        unsafe { allocator.dealloc(pointer.as_ptr(), layout) };
+    }
+
+    allocator.set_hardened(previous);
+    assert_eq!(previous, allocator.is_hardened());
+}
+
+#[test]
+fn reconcile() {
+    #[cfg(not(feature = "small-heap"))]
+    const HUGE_PAGE_SIZE: usize = 1 << 30;
+
+    #[cfg(feature = "small-heap")]
+    const HUGE_PAGE_SIZE: usize = 1 << 21;
+
+    let allocator = LLAllocator::new();
+    allocator.warm_up().expect("Warmed up!");
+
+    let mut reports = 0;
+
+    allocator.reconcile(|report| {
+        reports += 1;
+
+        assert_eq!(0, report.address % HUGE_PAGE_SIZE, "{:?}", report);
+        assert_eq!(HUGE_PAGE_SIZE, report.size, "{:?}", report);
+        assert!(report.mappings >= 1, "{:?}", report);
+        assert!(report.kernel_page_size >= 4096, "{:?}", report);
+    });
+
+    assert!(reports >= 1);
+}
+
+#[test]
+fn node_stats() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(256, 8).expect("Valid Layout");
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    let mut in_use = 0;
+
+    let nodes = allocator.node_stats(|stats| {
+        assert!(stats.huge_pages >= 1, "{:?}", stats);
+
+        in_use += stats.in_use_bytes;
+    });
+
+    assert!(nodes >= 1);
+    assert!(in_use >= layout.size());
+
+    unsafe { allocator.deallocate(pointer) };
+}
+
+#[test]
+fn node_of() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(256, 8).expect("Valid Layout");
+
+    //  The node of the heap is that of the socket serving the node, after clustering.
+    let node = allocator.current_node();
+
+    let pointer = allocator.allocate_on_node(node, layout).expect("Allocated");
+    assert_eq!(Some(node), unsafe { allocator.node_of(pointer) });
+
+    //  Once faulted in, the page resides on a node of the machine, if it can be looked up.
+    unsafe { pointer.as_ptr().write_bytes(0xA5, layout.size()) };
+
+    if let Some(resident) = allocator.resident_node_of(pointer) {
+        assert!(resident.value() < 64, "{:?}", resident);
+    }
+
+    unsafe { allocator.deallocate(pointer) };
+}
+
+#[test]
+fn refresh_topology() {
+    use std::time::Duration;
+
+    let allocator = LLAllocator::new();
+
+    //  A platform whose topology is fixed reports nothing; the others, the nodes found on the host.
+    let nodes = allocator.refresh_topology();
+    assert!(nodes.is_none_or(|nodes| nodes == allocator.host_capabilities().numa_nodes), "{:?}", nodes);
+
+    assert_eq!(None, allocator.topology_refresh());
+
+    allocator.set_topology_refresh(Some(Duration::from_secs(60)));
+    assert_eq!(Some(Duration::from_secs(60)), allocator.topology_refresh());
+
+    allocator.set_topology_refresh(None);
+    assert_eq!(None, allocator.topology_refresh());
+}
+
+#[test]
+fn residency() {
+    const SIZE: usize = 16 << 10;
+
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    unsafe { pointer.as_ptr().write_bytes(0x42, SIZE) };
+
+    let address = pointer.as_ptr() as usize;
+    let mut hosting = None;
+
+    let resident = allocator.residency(|report| {
+        assert!(report.resident <= report.size, "{:?}", report);
+        assert_eq!(report.size - report.resident, report.reclaimed());
+
+        if (report.address..report.address + report.size).contains(&address) {
+            hosting = Some(*report);
+        }
+    });
+
+    //  The freshly written allocation is resident, within its `HugePage`.
+    let hosting = hosting.expect("Hosting HugePage reported");
+    assert!(hosting.node.is_some(), "{:?}", hosting);
+    assert!(hosting.resident >= SIZE, "{:?}", hosting);
+    assert!(resident >= hosting.resident);
+
+    unsafe { allocator.deallocate(pointer) };
+}
+
+#[test]
+fn collapse() {
+    const SIZE: usize = 16 << 10;
+
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+    unsafe { pointer.as_ptr().write_bytes(0x42, SIZE) };
+
+    //  The region hosting the allocation, at least, is attempted.
+    let report = allocator.collapse(1);
+
+    if report.supported {
+        assert!(report.collapsed + report.ineligible + report.failed >= 1, "{:?}", report);
+    } else {
+        assert_eq!(0, report.regions(), "{:?}", report);
+    }
+
+    //  No region is resident enough.
+    let report = allocator.collapse(usize::MAX);
+
+    assert_eq!(0, report.collapsed + report.ineligible + report.failed, "{:?}", report);
+
+    //  The content is preserved.
+    assert_eq!(0x42, unsafe { pointer.as_ptr().add(SIZE - 1).read() });
+
+    unsafe { allocator.deallocate(pointer) };
+}
+
+#[test]
+fn tagging() {
+    use std::{ptr::NonNull, sync::Mutex};
+
+    static RELEASED: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+    fn record(pointer: NonNull<u8>, size: usize) { RELEASED.lock().unwrap().push((pointer.as_ptr() as usize, size)); }
+
+    fn take() -> Vec<(usize, usize)> { std::mem::take(&mut *RELEASED.lock().unwrap()) }
+
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(48, 8).unwrap();
+
+    let tag = allocator.register_tag(record).expect("Registered");
+
+    let tagged = allocator.allocate_tagged(layout, tag).expect("Allocated");
+    let untagged = allocator.allocate(layout).expect("Allocated");
+
+    unsafe {
+        allocator.deallocate(untagged);
+        allocator.deallocate(tagged);
+    }
+
+    assert_eq!(vec![(tagged.as_ptr() as usize, 48)], take());
+
+    //  A reallocation carries the tag over, with the new size.
+    let tagged = allocator.allocate_tagged(layout, tag).expect("Allocated");
+    let moved = unsafe { allocator.reallocate(tagged, layout, 4096) }.expect("Reallocated");
+
+    assert!(take().is_empty());
+
+    unsafe { GlobalAlloc::dealloc(&allocator, moved.as_ptr(), Layout::from_size_align(4096, 8).unwrap()) };
+
+    assert_eq!(vec![(moved.as_ptr() as usize, 4096)], take());
+
+    //  Once unregistered, the deallocations of its blocks are no longer reported.
+    let tagged = allocator.allocate_tagged(layout, tag).expect("Allocated");
+
+    assert!(allocator.unregister_tag(tag));
+    assert!(!allocator.unregister_tag(tag));
+
+    unsafe { allocator.deallocate(tagged) };
+
+    assert!(take().is_empty());
+    assert_eq!(0, allocator.untagged_allocations());
+}
@@ -0,0 +1,184 @@
+//  The registration of the custom platform is process-wide, hence all the checks are performed by a single test.
+#![cfg(feature = "custom-platform")]
+
+use std::{
+    alloc::Layout,
+    cell::Cell,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use llmalloc::{
+    AllocationError, AtomicFallbackMetrics, Capabilities, CodeMapping, CodeRegion, Configuration, CorePlatform,
+    HostCapabilities, HugePageReport, LLAllocator, LLConfiguration, NumaNodeIndex, PhysicalBuffer, PhysicalSegment,
+    Platform, ThreadLocal, ThreadStack,
+};
+
+#[test]
+fn custom_platform() {
+    static PLATFORM: MmapPlatform = MmapPlatform::new();
+    static THREAD_LOCAL: StdThreadLocal = StdThreadLocal;
+
+    let allocator = LLAllocator::new();
+
+    //  Without a platform, there is no memory.
+    assert_eq!(Err(AllocationError::OutOfMemory), allocator.try_allocate(Layout::from_size_align(8, 8).unwrap()));
+
+    let registered = LLAllocator::with_platform(&PLATFORM, &THREAD_LOCAL).expect("Registered");
+    assert!(LLAllocator::with_platform(&PLATFORM, &THREAD_LOCAL).is_err());
+
+    //  Both the instance created beforehand, and the one created on registration, use the platform.
+    for allocator in [&allocator, &registered] {
+        for size in [8, 256, 4096, 1 << 20] {
+            let layout = Layout::from_size_align(size, 8).unwrap();
+
+            let pointer = allocator.allocate(layout).expect("Allocated");
+
+            unsafe {
+                pointer.as_ptr().write_bytes(0x5a, size);
+                allocator.deallocate(pointer);
+            }
+        }
+    }
+
+    assert!(PLATFORM.mapped.load(Ordering::Relaxed) >= LLConfiguration::HUGE_PAGE_SIZE.value());
+
+    //  The threads release their thread-local state prior to exiting.
+    thread::spawn(|| {
+        let allocator = LLAllocator::new();
+
+        let pointer = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+        unsafe {
+            allocator.deallocate(pointer);
+            allocator.release_thread();
+        }
+    }).join().expect("Joined");
+
+    //  The platform maps neither code, nor stacks, nor physical buffers.
+    assert!(allocator.allocate_code(4096, CodeMapping::Flip).is_err());
+    assert!(allocator.allocate_stack(4096, false).is_err());
+    assert!(allocator.allocate_physical(4096).is_err());
+}
+
+//  A platform mapping memory with `mmap`, standing in for the primitives of an RTOS.
+struct MmapPlatform {
+    mapped: AtomicUsize,
+    fallbacks: AtomicFallbackMetrics,
+}
+
+impl MmapPlatform {
+    const fn new() -> Self { Self { mapped: AtomicUsize::new(0), fallbacks: AtomicFallbackMetrics::new() } }
+}
+
+impl CorePlatform for MmapPlatform {
+    unsafe fn allocate(&self, layout: Layout) -> Option<NonNull<u8>> {
+        //  Over-map, then trim the head and tail, to align the mapping.
+        let size = layout.size() + layout.align();
+
+        let flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE;
+        let mapping = libc::mmap(ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE, flags, -1, 0);
+
+        if mapping == libc::MAP_FAILED {
+            return None;
+        }
+
+        let start = mapping as usize;
+        let aligned = (start + layout.align() - 1) & !(layout.align() - 1);
+
+        if aligned > start {
+            libc::munmap(mapping, aligned - start);
+        }
+
+        let tail = aligned + layout.size();
+
+        if tail < start + size {
+            libc::munmap(tail as *mut libc::c_void, start + size - tail);
+        }
+
+        self.mapped.fetch_add(layout.size(), Ordering::Relaxed);
+
+        NonNull::new(aligned as *mut u8)
+    }
+
+    unsafe fn deallocate(&self, pointer: NonNull<u8>, layout: Layout) {
+        libc::munmap(pointer.as_ptr() as *mut libc::c_void, layout.size());
+    }
+}
+
+impl Platform for MmapPlatform {
+    fn current_node(&self) -> NumaNodeIndex { NumaNodeIndex::new(0) }
+
+    fn numa_node(&self, node: u32) -> Option<NumaNodeIndex> {
+        if node == 0 { Some(NumaNodeIndex::new(0)) } else { None }
+    }
+
+    fn now(&self) -> u64 { 0 }
+
+    fn reconcile(&self, page: NonNull<u8>, size: usize, node: NumaNodeIndex) -> HugePageReport {
+        HugePageReport { address: page.as_ptr() as usize, size, node: node.value(), ..Default::default() }
+    }
+
+    fn resident(&self, _pointer: NonNull<u8>, _size: usize) -> Option<usize> { None }
+
+    fn lock(&self, _pointer: NonNull<u8>, _size: usize) -> bool { false }
+
+    unsafe fn protect(&self, _pointer: NonNull<u8>, _size: usize, _writable: bool) -> bool { false }
+
+    fn map_code(&self, _size: usize, _mapping: CodeMapping) -> Option<CodeRegion> { None }
+
+    unsafe fn unmap_code(&self, _region: CodeRegion) {}
+
+    unsafe fn protect_code(&self, _region: &CodeRegion, _executable: bool) -> bool { false }
+
+    fn map_stack(&self, _size: usize, _prefault: bool) -> Option<ThreadStack> { None }
+
+    unsafe fn unmap_stack(&self, _stack: ThreadStack) {}
+
+    fn map_physical(&self, _size: usize) -> Option<PhysicalBuffer> { None }
+
+    unsafe fn unmap_physical(&self, _buffer: PhysicalBuffer) {}
+
+    fn physical_segments(&self, _buffer: &PhysicalBuffer, _report: &mut dyn FnMut(&PhysicalSegment)) -> Option<usize> {
+        None
+    }
+
+    fn environment_flag(&self, _name: &[u8]) -> bool { false }
+
+    fn capabilities(&self) -> Capabilities { Capabilities::default() }
+
+    fn host_capabilities(&self) -> HostCapabilities { HostCapabilities::default() }
+
+    fn mapping_latency(&self) -> Option<Duration> { None }
+
+    fn fallbacks(&self) -> &AtomicFallbackMetrics { &self.fallbacks }
+
+    #[cfg(feature = "system-fallback")]
+    fn system_allocate(&self, _layout: Layout) -> Option<NonNull<u8>> { None }
+
+    #[cfg(feature = "system-fallback")]
+    unsafe fn system_deallocate(&self, _pointer: NonNull<u8>) {}
+
+    #[cfg(feature = "system-fallback")]
+    fn owns(&self, _pointer: NonNull<u8>) -> bool { true }
+}
+
+//  A thread-local storage over the one of the standard library, standing in for the one of an RTOS.
+struct StdThreadLocal;
+
+thread_local! {
+    static SLOT: Cell<*mut u8> = const { Cell::new(ptr::null_mut()) };
+}
+
+impl ThreadLocal<u8> for StdThreadLocal {
+    fn prepare(&self) -> bool { false }
+
+    fn get(&self) -> Option<NonNull<u8>> { NonNull::new(SLOT.with(|slot| slot.get())) }
+
+    fn set(&self, value: NonNull<u8>) -> bool {
+        SLOT.with(|slot| slot.set(value.as_ptr()));
+        true
+    }
+}
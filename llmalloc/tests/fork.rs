@@ -0,0 +1,56 @@
+//  Forking duplicates the whole process, of which the child only inherits the forking thread, hence it is checked in
+//  its own test binary, on the platforms registering the handlers around fork.
+#![cfg(all(any(target_os = "linux", feature = "posix"),
+    not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform", feature = "no-libc"))))]
+
+use std::{alloc::Layout, time::Duration};
+
+use llmalloc::LLAllocator;
+
+#[test]
+fn fork() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    //  The handlers are registered on the first use of the allocator.
+    allocator.warm_up().expect("Warmed up");
+    assert!(allocator.is_fork_safe());
+
+    allocator.set_background_thread(Some(Duration::from_millis(5))).expect("Spawned");
+    assert!(allocator.is_background_thread_running());
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    //  Safety:
+    //  -   The child only uses the allocator, prior to exiting without unwinding.
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0, "Forked");
+
+    if pid == 0 {
+        //  The child does not inherit the background thread, yet inherits the heap, and may allocate.
+        let recovered = !allocator.is_background_thread_running() && allocator.allocate(layout).map(|other| unsafe {
+            allocator.deallocate(other);
+            allocator.deallocate(pointer);
+        }).is_some();
+
+        //  The background thread may be spawned anew.
+        let respawned = allocator.set_background_thread(Some(Duration::from_millis(5))).is_ok()
+            && allocator.is_background_thread_running();
+
+        unsafe { libc::_exit(if recovered && respawned { 0 } else { 1 }) };
+    }
+
+    let mut status = 0;
+
+    //  Safety:
+    //  -   `status` is valid for writes.
+    let waited = unsafe { libc::waitpid(pid, &mut status as *mut _, 0) };
+
+    assert_eq!(pid, waited);
+    assert!(libc::WIFEXITED(status), "{:x}", status);
+    assert_eq!(0, libc::WEXITSTATUS(status));
+
+    unsafe { allocator.deallocate(pointer) };
+
+    allocator.set_background_thread(None).expect("Disabled");
+}
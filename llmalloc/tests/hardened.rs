@@ -0,0 +1,57 @@
+//  The canaries are only padded if the hardened mode is enabled before the first allocation, hence it is checked in its
+//  own test binary.
+#![cfg(not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))]
+
+use std::alloc::Layout;
+
+use llmalloc::{LLAllocator, DEALLOCATED_POISON};
+
+#[test]
+fn hardened() {
+    let allocator = LLAllocator::new();
+
+    allocator.set_hardened(true);
+    assert!(allocator.is_hardened());
+    assert_eq!(0, allocator.hardening_violations());
+
+    //  A size whose padding exactly fills its class size, a power of 2, so that the first byte past the end is the
+    //  canary.
+    let size = 128 - 8;
+    let layout = Layout::from_size_align(size, 8).unwrap();
+
+    //  An overflow clobbers the canary.
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    unsafe {
+        let end = pointer.as_ptr().add(size);
+        end.write(!end.read());
+        allocator.deallocate(pointer);
+    }
+
+    assert_eq!(1, allocator.hardening_violations());
+
+    //  A write after free clobbers the poison, detected once the block is evicted from the quarantine.
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    unsafe {
+        allocator.deallocate(pointer);
+
+        assert_eq!(DEALLOCATED_POISON, pointer.as_ptr().read());
+        pointer.as_ptr().write(0);
+    }
+
+    for _ in 0..1024 {
+        let other = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(other) };
+    }
+
+    assert_eq!(2, allocator.hardening_violations());
+
+    //  Intact blocks are silently recycled.
+    for _ in 0..4096 {
+        let other = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(other) };
+    }
+
+    assert_eq!(2, allocator.hardening_violations());
+}
@@ -0,0 +1,93 @@
+//  The state of the mock platform is process-wide, hence all the checks are performed by a single test.
+#![cfg(feature = "test-platform")]
+
+use std::{alloc::Layout, time::Duration};
+
+use llmalloc::{AllocationError, CodeMapping, Configuration, LLAllocator, LLConfiguration, MockCall, RetryPolicy};
+
+#[test]
+fn mock_platform() {
+    const HUGE_PAGE_SIZE: usize = LLConfiguration::HUGE_PAGE_SIZE.value();
+
+    let allocator = LLAllocator::new();
+    let platform = allocator.mock_platform();
+
+    //  The first allocation maps the first Huge Page of the arena.
+    let small = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    assert_eq!(HUGE_PAGE_SIZE, platform.allocated());
+    assert!(platform.offset_of(small).expect("Within the arena") < HUGE_PAGE_SIZE);
+
+    let mut first = None;
+    assert_eq!(1, platform.calls(|call| first = Some(*call)));
+    assert_eq!(Some(MockCall::Allocate { size: HUGE_PAGE_SIZE, offset: Some(0) }), first);
+
+    platform.clear_calls();
+
+    //  A Huge allocation is mapped, and unmapped, on its own, first fit, unless retained.
+    allocator.set_direct_retained(false);
+
+    let huge = Layout::from_size_align(2 * HUGE_PAGE_SIZE, 8).unwrap();
+
+    let pointer = allocator.allocate(huge).expect("Allocated");
+
+    assert_eq!(Some(HUGE_PAGE_SIZE), platform.offset_of(pointer));
+
+    unsafe { allocator.deallocate(pointer) };
+
+    let mut calls = Vec::new();
+    assert_eq!(2, platform.calls(|call| calls.push(*call)));
+
+    let mapped = MockCall::Allocate { size: 2 * HUGE_PAGE_SIZE, offset: Some(HUGE_PAGE_SIZE) };
+    let unmapped = MockCall::Deallocate { offset: HUGE_PAGE_SIZE, size: 2 * HUGE_PAGE_SIZE };
+    assert_eq!(vec![mapped, unmapped], calls);
+
+    //  The failures injected are reported as such, and are consumed.
+    platform.fail_next(1);
+
+    assert_eq!(Err(AllocationError::OutOfMemory), allocator.try_allocate(huge));
+
+    let pointer = allocator.allocate(huge).expect("Allocated");
+
+    unsafe { allocator.deallocate(pointer) };
+
+    //  With a retry policy, the failures are retried, up to the number of retries.
+    let policy = RetryPolicy { retries: 2, backoff: Duration::ZERO, purge: true };
+
+    allocator.set_retry_policy(Some(policy));
+    assert_eq!(Some(policy), allocator.retry_policy());
+
+    let before = allocator.fallback_metrics();
+
+    platform.fail_next(2);
+
+    let pointer = allocator.allocate(huge).expect("Allocated");
+
+    unsafe { allocator.deallocate(pointer) };
+
+    platform.fail_next(3);
+
+    assert_eq!(Err(AllocationError::OutOfMemory), allocator.try_allocate(huge));
+
+    let after = allocator.fallback_metrics();
+
+    assert_eq!(4, after.allocation_retries - before.allocation_retries);
+    assert_eq!(1, after.recovered_allocations - before.recovered_allocations);
+
+    allocator.set_retry_policy(None);
+    assert_eq!(None, allocator.retry_policy());
+
+    //  The arena is bounded.
+    let oversized = Layout::from_size_align(platform.arena_size() + HUGE_PAGE_SIZE, 8).unwrap();
+
+    assert_eq!(Err(AllocationError::OutOfMemory), allocator.try_allocate(oversized));
+
+    //  The platform maps neither code, nor stacks, nor physical buffers.
+    assert!(allocator.allocate_code(4096, CodeMapping::Flip).is_err());
+    assert!(allocator.allocate_stack(4096, false).is_err());
+    assert!(allocator.allocate_physical(4096).is_err());
+
+    unsafe { allocator.deallocate(small) };
+
+    assert_eq!(HUGE_PAGE_SIZE, platform.allocated());
+}
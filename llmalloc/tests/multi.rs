@@ -1,3 +1,7 @@
+//  The bare-metal, and custom, platforms have no memory until provided, see `bare_metal.rs` and `custom_platform.rs`,
+//  and the mock platform maps neither code, stacks, nor physical buffers, see `mock_platform.rs`.
+#![cfg(not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))]
+
 use std::{
     alloc::Layout,
     collections::BTreeSet,
@@ -47,7 +51,15 @@ fn acquire_release_thread_handles() {
                 //  Sanity check, to ensure no other thread allocate the same pointer.
                 assert_eq!(i, *pointer);
 
-                LL_ALLOCATOR.thread_index()
+                let index = LL_ALLOCATOR.thread_index();
+
+                mem::drop(pointer);
+
+                //  Without libc, the thread-local state is not released on thread destruction, but explicitly.
+                #[cfg(feature = "no-libc")]
+                unsafe { LL_ALLOCATOR.release_thread() };
+
+                index
             }
         });
 
@@ -181,7 +193,7 @@ fn producer_consumer_ring() {
 
                     allocation.wait_until_all_ready();
 
-                    push_victims(victims, &mut *sink);
+                    push_victims(victims, &mut sink);
                 }
 
                 //  Rearm next iteration.
@@ -197,7 +209,7 @@ fn producer_consumer_ring() {
 
                 //  Shuffle the pointers.
                 if custodian {
-                    shuffle_ring(&*ring);
+                    shuffle_ring(&ring);
                 }
 
                 shuffle_end.wait_until_all_ready();
@@ -214,7 +226,7 @@ fn producer_consumer_ring() {
 
                     deallocation.wait_until_all_ready();
 
-                    pop_victims(&mut *stream, &mut victims);
+                    pop_victims(&mut stream, &mut victims);
 
                     victims
                 };
@@ -263,7 +275,7 @@ impl<T> Pool<T> {
     }
 
     fn join(mut self) -> Vec<T> {
-        let thread_handles = std::mem::replace(&mut self.0, vec!());
+        let thread_handles = std::mem::take(&mut self.0);
         Self::join_handles(thread_handles)
     }
 
@@ -281,7 +293,7 @@ impl<T> Pool<T> {
 
 impl<T> Drop for Pool<T> {
     fn drop(&mut self) {
-        let thread_handles = std::mem::replace(&mut self.0, vec!());
+        let thread_handles = std::mem::take(&mut self.0);
         Self::join_handles(thread_handles);
     }
 }
@@ -376,7 +388,7 @@ impl<T> Pointer<T> {
         where
             T: Default
     {
-        std::mem::replace(&mut *self, T::default())
+        std::mem::take(&mut *self)
     }
 }
 
@@ -414,7 +426,3 @@ unsafe impl<T> Send for Pointer<T>
 fn layout(size: usize, alignment: usize) -> Layout {
     Layout::from_size_align(size, alignment).expect("Valid Layout")
 }
-
-//  FIXME: use sys crates... properly configured for system libraries.
-#[link(name = "numa")]
-extern "C" {}
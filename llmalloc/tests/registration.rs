@@ -0,0 +1,65 @@
+//  The registration of threads is process-wide, hence it is checked in its own test binary.
+#![cfg(not(any(feature = "bare-metal", feature = "custom-platform")))]
+
+use std::{alloc::Layout, thread};
+
+use llmalloc::LLAllocator;
+
+#[test]
+fn registration() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    assert!(!allocator.is_thread_registration_required());
+
+    //  A registered thread may unregister, and is then registered implicitly anew, as registration is not required.
+    thread::spawn(move || {
+        let allocator = LLAllocator::new();
+
+        allocator.register_thread().expect("Registered");
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        unsafe { allocator.unregister_thread() };
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        assert_eq!(0, allocator.unregistered_allocations());
+    }).join().expect("Joined");
+
+    allocator.set_thread_registration(true);
+    assert!(allocator.is_thread_registration_required());
+
+    //  An unregistered thread is served from the shared state, without any for itself.
+    thread::spawn(move || {
+        let allocator = LLAllocator::new();
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { pointer.as_ptr().write_bytes(0xA5, layout.size()) };
+
+        assert_eq!(1, allocator.unregistered_allocations());
+        assert!(allocator.warm_up().is_err());
+
+        unsafe { allocator.deallocate(pointer) };
+
+        //  Once registered, the thread is served from its own state.
+        allocator.register_thread().expect("Registered");
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        assert_eq!(1, allocator.unregistered_allocations());
+
+        //  And once unregistered, from the shared state anew.
+        unsafe { allocator.unregister_thread() };
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+
+        assert_eq!(2, allocator.unregistered_allocations());
+    }).join().expect("Joined");
+
+    allocator.set_thread_registration(false);
+}
@@ -0,0 +1,37 @@
+//  Rehoming is process-wide, hence it is checked in its own test binary.
+#![cfg(not(any(feature = "bare-metal", feature = "custom-platform")))]
+
+use std::alloc::Layout;
+
+use llmalloc::LLAllocator;
+
+#[test]
+fn rehoming() {
+    let allocator = LLAllocator::new();
+    let layout = Layout::from_size_align(64, 8).unwrap();
+
+    allocator.set_rehoming(true);
+    assert!(allocator.is_rehoming());
+
+    //  The node is checked every so often, the thread only moving if rescheduled onto another node.
+    for _ in 0..10_000 {
+        let pointer = allocator.allocate(layout).expect("Allocated");
+        unsafe { allocator.deallocate(pointer) };
+    }
+
+    //  Once rehomed, if need be, the thread is served by the socket of its node.
+    allocator.rehome_thread();
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    //  Threads within a scope forbidding allocations are not rehomed.
+    {
+        let _guard = allocator.forbid_allocation().expect("Forbidden");
+        assert!(!allocator.rehome_thread());
+    }
+
+    unsafe { allocator.deallocate(pointer) };
+
+    allocator.set_rehoming(false);
+    assert!(!allocator.is_rehoming());
+}
@@ -0,0 +1,768 @@
+//  The settings checked are process-wide, and most are latched by the first mapping, hence each test is run alone in
+//  a child process, re-executing this binary, and only with the 2 MB Huge Pages of `small-heap`.
+#![cfg(all(unix, feature = "small-heap", not(any(feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+
+use std::{env, process::Command};
+
+//  Set in the environment of the child processes.
+const ISOLATED: &str = "LLMALLOC_ISOLATED_TEST";
+
+//  Runs `test` in a child process of its own, on the first call, or directly within the child.
+fn isolated(name: &str, test: fn()) {
+    if env::var_os(ISOLATED).is_some() {
+        return test();
+    }
+
+    let executable = env::current_exe().expect("Current executable");
+
+    let output = Command::new(executable).args(["--exact", name, "--nocapture"]).env(ISOLATED, name).output()
+        .expect("Spawned");
+
+    let (stdout, stderr) = (String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+
+    assert!(output.status.success(), "{}: {}\n{}{}", name, output.status, stdout, stderr);
+    assert!(stdout.contains("1 passed"), "{}: not run\n{}", name, stdout);
+}
+
+//  Prefaulting is process-wide, and commits all the memory mapped.
+mod prefault {
+
+use std::alloc::Layout;
+
+use llmalloc::LLAllocator;
+
+use super::isolated;
+
+#[test]
+fn prefault() { isolated("prefault::prefault", run) }
+
+fn run() {
+    let allocator = LLAllocator::new();
+
+    allocator.set_prefault(true);
+    assert!(allocator.is_prefaulting());
+
+    //  The first allocation maps the first `HugePage`, fully resident before any access.
+    let pointer = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    let mut pages = 0;
+
+    allocator.residency(|report| {
+        if report.node.is_some() {
+            pages += 1;
+            assert!(report.is_resident(), "{:?}", report);
+        }
+    });
+
+    assert!(pages > 0);
+
+    unsafe { allocator.deallocate(pointer) };
+
+    allocator.set_prefault(false);
+    assert!(!allocator.is_prefaulting());
+}
+
+} // mod prefault
+
+//  Pinning is process-wide, and locks all the memory mapped.
+mod pinning {
+
+use std::alloc::Layout;
+
+use llmalloc::LLAllocator;
+
+use super::isolated;
+
+#[test]
+fn pinning() { isolated("pinning::pinning", run) }
+
+fn run() {
+    const SIZE: usize = 4 * 1024 * 1024;
+
+    let allocator = LLAllocator::new();
+
+    //  The `HugePage` mapped beforehand is locked on enabling.
+    let pointer = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    let result = allocator.set_pinned(true);
+    assert!(allocator.is_pinned());
+
+    let before = allocator.pinning_report();
+
+    assert!(before.enabled);
+    assert!(before.locked + before.unlocked > 0, "{:?}", before);
+    assert_eq!(result.is_ok(), before.is_complete(), "{:?}", before);
+
+    //  The Huge allocation mapped afterwards is locked too, unless the limit of locked memory is reached.
+    let huge = allocator.allocate(Layout::from_size_align(SIZE, 8).unwrap()).expect("Allocated");
+
+    let after = allocator.pinning_report();
+
+    assert!(after.locked + after.unlocked >= before.locked + before.unlocked + SIZE as u64, "{:?}", after);
+    assert_eq!(after.unlocked > 0, allocator.fallback_metrics().lock_failures > 0, "{:?}", after);
+
+    if after.limit.is_none() {
+        assert!(after.is_complete(), "{:?}", after);
+    }
+
+    unsafe { allocator.deallocate(huge) };
+    unsafe { allocator.deallocate(pointer) };
+
+    assert_eq!(Ok(()), allocator.set_pinned(false));
+    assert!(!allocator.is_pinned());
+}
+
+} // mod pinning
+
+//  Decommit is process-wide, and checked with Large allocations of a few `LargePage`s.
+#[cfg(any(target_os = "linux", feature = "posix"))]
+mod decommit {
+
+use std::{alloc::Layout, ptr};
+
+use llmalloc::LLAllocator;
+
+use super::isolated;
+
+#[test]
+fn decommit() { isolated("decommit::decommit", run) }
+
+fn run() {
+    const SIZE: usize = 256 * 1024;
+
+    let allocator = LLAllocator::new();
+
+    allocator.set_decommit(true);
+    assert!(allocator.is_decommitting());
+
+    let pointer = allocator.allocate(Layout::from_size_align(SIZE, 8).unwrap()).expect("Allocated");
+
+    //  Commit the whole allocation.
+    unsafe { ptr::write_bytes(pointer.as_ptr(), 0x5A, SIZE) };
+
+    let committed = allocator.residency(|_| ());
+    assert!(committed >= SIZE, "{} < {}", committed, SIZE);
+
+    unsafe { allocator.deallocate(pointer) };
+
+    let decommitted = allocator.residency(|_| ());
+    assert!(decommitted + SIZE <= committed, "{} + {} > {}", decommitted, SIZE, committed);
+
+    allocator.set_decommit(false);
+    assert!(!allocator.is_decommitting());
+}
+
+} // mod decommit
+
+//  Decay is process-wide, and checked with retained Huge blocks, kept small.
+#[cfg(any(target_os = "linux", feature = "posix"))]
+mod decay {
+
+use std::{alloc::Layout, ptr, time::Duration};
+
+use llmalloc::LLAllocator;
+
+use super::isolated;
+
+#[test]
+fn decay() { isolated("decay::decay", run) }
+
+fn run() {
+    const SIZE: usize = 4 * 1024 * 1024;
+
+    let allocator = LLAllocator::new();
+
+    //  A long window, so that the passes are only run explicitly, bar the first.
+    allocator.set_decay(Some(Duration::from_secs(3600)));
+    assert_eq!(Some(Duration::from_secs(3600)), allocator.decay_window());
+
+    let pointer = allocator.allocate(Layout::from_size_align(SIZE, 8).unwrap()).expect("Allocated");
+
+    //  Commit the whole allocation.
+    unsafe { ptr::write_bytes(pointer.as_ptr(), 0x5A, SIZE) };
+
+    unsafe { allocator.deallocate(pointer) };
+
+    //  Lazily purged, the retained block remains resident, absent memory pressure.
+    let committed = allocator.residency(|_| ());
+    assert!(committed >= SIZE, "{} < {}", committed, SIZE);
+
+    //  Aged by the first pass, purged by the second.
+    let purged: usize = (0..2).map(|_| allocator.decay()).sum();
+    assert!(purged >= SIZE, "{} < {}", purged, SIZE);
+
+    let decayed = allocator.residency(|_| ());
+    assert!(decayed + SIZE <= committed, "{} + {} > {}", decayed, SIZE, committed);
+
+    //  The purged block remains available for reuse.
+    let other = allocator.allocate(Layout::from_size_align(SIZE, 8).unwrap()).expect("Allocated");
+    assert_eq!(pointer, other);
+
+    unsafe { allocator.deallocate(other) };
+
+    allocator.set_decay(None);
+    assert_eq!(None, allocator.decay_window());
+}
+
+} // mod decay
+
+//  The address range is process-wide, and latched by the first mapping.
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "no-libc"))))]
+mod address_range {
+
+use std::{alloc::Layout, ptr};
+
+use llmalloc::{AddressRange, LLAllocator};
+
+use super::isolated;
+
+#[test]
+fn address_range() { isolated("address_range::address_range", run) }
+
+fn run() {
+    const SIZE: usize = 4 * 1024 * 1024;
+
+    let range = AddressRange { start: 0x1000_0000_0000, end: 0x1000_1000_0000 };
+
+    let allocator = LLAllocator::new();
+
+    //  The reservation, if any, would be hinted within the range too, yet is checked separately.
+    assert_eq!(Ok(()), allocator.set_reservation(None));
+
+    //  Empty, once shrunk.
+    assert_eq!(Err(()), allocator.set_address_range(Some(AddressRange { start: 0x1000, end: 0x2000 })));
+
+    assert_eq!(Ok(()), allocator.set_address_range(Some(range)));
+    assert_eq!(Some(range), allocator.address_range());
+
+    let small = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    //  The selection is latched by the first mapping.
+    assert_eq!(Err(()), allocator.set_address_range(None));
+    assert_eq!(Ok(()), allocator.set_address_range(Some(range)));
+
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+    let huge = allocator.allocate(layout).expect("Allocated");
+
+    assert!(range.contains(small.as_ptr()), "{:x}", small.as_ptr() as usize);
+    assert!(range.contains_all(huge.as_ptr(), SIZE), "{:x}", huge.as_ptr() as usize);
+
+    unsafe { ptr::write_bytes(huge.as_ptr(), 0x5A, SIZE) };
+
+    //  Grown, whether in place or moved, the allocation remains within the range.
+    let grown = unsafe { allocator.reallocate(huge, layout, 4 * SIZE) }.expect("Reallocated");
+
+    assert!(range.contains_all(grown.as_ptr(), 4 * SIZE), "{:x}", grown.as_ptr() as usize);
+    assert_eq!(0x5A, unsafe { grown.as_ptr().add(SIZE - 1).read() });
+
+    assert_eq!(0, allocator.fallback_metrics().out_of_range_mappings);
+
+    unsafe { allocator.deallocate(grown) };
+    unsafe { allocator.deallocate(small) };
+}
+
+} // mod address_range
+
+//  Guard pages are process-wide, and latched by the first mapping.
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "no-libc"))))]
+mod guard_pages {
+
+use std::{alloc::Layout, fs, ptr};
+
+use llmalloc::LLAllocator;
+
+use super::isolated;
+
+#[test]
+fn guard_pages() { isolated("guard_pages::guard_pages", run) }
+
+fn run() {
+    const SIZE: usize = 4 * 1024 * 1024;
+
+    let allocator = LLAllocator::new();
+
+    //  Guard pages are forgone within a reservation.
+    assert_eq!(Ok(()), allocator.set_reservation(None));
+
+    assert_eq!(Ok(()), allocator.set_guarded(true));
+    assert!(allocator.is_guarded());
+
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    //  The selection is latched by the first mapping.
+    assert_eq!(Err(()), allocator.set_guarded(false));
+    assert_eq!(Ok(()), allocator.set_guarded(true));
+
+    assert_guarded(pointer.as_ptr() as usize, SIZE);
+
+    unsafe { ptr::write_bytes(pointer.as_ptr(), 0x5A, SIZE) };
+
+    //  Grown, the allocation is moved, with guards of its own.
+    let grown = unsafe { allocator.reallocate(pointer, layout, 2 * SIZE) }.expect("Reallocated");
+
+    assert_guarded(grown.as_ptr() as usize, 2 * SIZE);
+
+    assert_eq!(0x5A, unsafe { grown.as_ptr().read() });
+    assert_eq!(0x5A, unsafe { grown.as_ptr().add(SIZE - 1).read() });
+
+    //  Shrunk, likewise.
+    let layout = Layout::from_size_align(2 * SIZE, 8).unwrap();
+    let shrunk = unsafe { allocator.reallocate(grown, layout, SIZE) }.expect("Reallocated");
+
+    assert_guarded(shrunk.as_ptr() as usize, SIZE);
+
+    assert_eq!(0x5A, unsafe { shrunk.as_ptr().add(SIZE - 1).read() });
+
+    unsafe { allocator.deallocate(shrunk) };
+}
+
+//  Asserts that the `size` bytes at `start` are flanked by inaccessible pages, as per `/proc/self/maps`.
+fn assert_guarded(start: usize, size: usize) {
+    let maps = fs::read_to_string("/proc/self/maps").expect("Readable");
+
+    let protection = |address: usize| {
+        maps.lines()
+            .find(|line| {
+                let range = line.split(' ').next().unwrap();
+                let (low, high) = range.split_once('-').unwrap();
+                let (low, high) = (usize::from_str_radix(low, 16).unwrap(), usize::from_str_radix(high, 16).unwrap());
+
+                low <= address && address < high
+            })
+            //  The protection, bar the trailing private or shared flag, as the heap may be shared.
+            .map(|line| line.split(' ').nth(1).unwrap()[..3].to_owned())
+    };
+
+    assert_eq!(Some("rw-"), protection(start).as_deref(), "{:x}", start);
+    assert_eq!(Some("---"), protection(start - 1).as_deref(), "{:x}", start);
+    assert_eq!(Some("---"), protection(start + size).as_deref(), "{:x}", start);
+}
+
+} // mod guard_pages
+
+//  Map options are process-wide, and apply to the mappings which follow.
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "no-libc"))))]
+mod map_options {
+
+use std::{alloc::Layout, fs, ptr};
+
+use llmalloc::{LLAllocator, MapOptions, Merging, NodeBinding};
+
+use super::isolated;
+
+#[test]
+fn map_options() { isolated("map_options::map_options", run) }
+
+fn run() {
+    const SIZE: usize = 4 * 1024 * 1024;
+    const HINT: usize = 0x2000_0000_0000;
+
+    let allocator = LLAllocator::new();
+
+    //  Map options are forgone within a reservation.
+    assert_eq!(Ok(()), allocator.set_reservation(None));
+
+    //  Lest the second allocation reuse the memory of the first.
+    allocator.set_direct_retained(false);
+
+    assert_eq!(MapOptions::new(), allocator.map_options());
+
+    let options = MapOptions::new().with_no_reserve(true).with_address_hint(HINT);
+
+    allocator.set_map_options(options);
+    assert_eq!(options, allocator.map_options());
+
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    unsafe { ptr::write_bytes(pointer.as_ptr(), 0x5A, SIZE) };
+
+    //  The first mapping lands at the hint, the range being free.
+    assert!(has_flag(HINT, "nr"), "{:x}", HINT);
+    assert!(has_flag(pointer.as_ptr() as usize, "nr"), "{:x}", pointer.as_ptr() as usize);
+
+    unsafe { allocator.deallocate(pointer) };
+
+    //  The mappings which follow are affected, not those already made.
+    allocator.set_map_options(MapOptions::new());
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    assert!(!has_flag(pointer.as_ptr() as usize, "nr"), "{:x}", pointer.as_ptr() as usize);
+
+    unsafe { allocator.deallocate(pointer) };
+
+    //  Binding is only honored if NUMA is available.
+    if fs::metadata("/sys/devices/system/node/node0").is_ok() {
+        allocator.set_map_options(MapOptions::new().with_binding(NodeBinding::Bind));
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+
+        let policy = policy_of(pointer.as_ptr() as usize);
+        assert!(policy.starts_with("bind:"), "{:x}: {}", pointer.as_ptr() as usize, policy);
+
+        unsafe { allocator.deallocate(pointer) };
+
+        //  A preferred placement falls back to other nodes, rather than failing.
+        allocator.set_map_options(MapOptions::new().with_binding(NodeBinding::Preferred));
+
+        let pointer = allocator.allocate(layout).expect("Allocated");
+
+        let policy = policy_of(pointer.as_ptr() as usize);
+        assert!(policy.starts_with("prefer:"), "{:x}: {}", pointer.as_ptr() as usize, policy);
+
+        unsafe { allocator.deallocate(pointer) };
+
+        //  An interleaving instance interleaves its Huge allocations, whichever the map options.
+        allocator.set_map_options(MapOptions::new());
+
+        let interleaving = LLAllocator::new().with_interleaved(true);
+        assert!(interleaving.is_interleaved());
+
+        let pointer = interleaving.allocate(layout).expect("Allocated");
+
+        let policy = policy_of(pointer.as_ptr() as usize);
+        assert!(policy.starts_with("interleave:"), "{:x}: {}", pointer.as_ptr() as usize, policy);
+
+        unsafe { interleaving.deallocate(pointer) };
+    }
+
+    //  Same-page merging is only advised if the kernel supports it.
+    if fs::metadata("/sys/kernel/mm/ksm").is_err() {
+        return;
+    }
+
+    allocator.set_map_options(MapOptions::new().with_merging(Merging::Mergeable));
+
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    assert!(has_flag(pointer.as_ptr() as usize, "mg"), "{:x}", pointer.as_ptr() as usize);
+
+    unsafe { allocator.deallocate(pointer) };
+}
+
+//  Returns whether the mapping containing `address` bears `flag`, as per the `VmFlags` of `/proc/self/smaps`.
+fn has_flag(address: usize, flag: &str) -> bool {
+    let smaps = fs::read_to_string("/proc/self/smaps").expect("Readable");
+
+    let mut within = false;
+
+    for line in smaps.lines() {
+        if let Some(flags) = line.strip_prefix("VmFlags:") {
+            if within {
+                return flags.split_whitespace().any(|candidate| candidate == flag);
+            }
+
+            continue;
+        }
+
+        //  The header of a mapping starts with its range, as in `7f0000000000-7f0000200000 rw-p ...`.
+        let range = line.split(' ').next().unwrap();
+
+        if let Some((low, high)) = range.split_once('-') {
+            if let (Ok(low), Ok(high)) = (usize::from_str_radix(low, 16), usize::from_str_radix(high, 16)) {
+                within = low <= address && address < high;
+            }
+        }
+    }
+
+    false
+}
+
+//  Returns the NUMA policy of the mapping containing `address`, as per `/proc/self/numa_maps`.
+fn policy_of(address: usize) -> String {
+    let maps = fs::read_to_string("/proc/self/numa_maps").expect("Readable");
+
+    //  Each line starts with the start of a mapping, and its policy, as in `7f0000000000 bind:0 anon=512 ...`.
+    let mut policy = String::new();
+    let mut best = 0;
+
+    for line in maps.lines() {
+        let mut fields = line.split(' ');
+        let start = usize::from_str_radix(fields.next().unwrap(), 16).unwrap();
+
+        if best <= start && start <= address {
+            best = start;
+            policy = fields.next().unwrap_or_default().to_string();
+        }
+    }
+
+    policy
+}
+
+} // mod map_options
+
+//  The names of the mappings are checked against `/proc/self/maps`.
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "no-libc"))))]
+mod mapping_names {
+
+use std::{alloc::Layout, fs, ptr};
+
+use llmalloc::LLAllocator;
+
+use super::isolated;
+
+#[test]
+fn mapping_names() { isolated("mapping_names::mapping_names", run) }
+
+fn run() {
+    if !naming_supported() {
+        return;
+    }
+
+    const SIZE: usize = 4 * 1024 * 1024;
+
+    let allocator = LLAllocator::new();
+
+    let small = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+    let huge = allocator.allocate(layout).expect("Allocated");
+
+    unsafe { ptr::write_bytes(huge.as_ptr(), 0x5A, SIZE) };
+
+    //  HugeTLB pages cannot be named.
+    if allocator.fallback_metrics().huge_tlb_mappings == 0 {
+        let node = format!("llmalloc:node{}", allocator.socket_index());
+
+        assert_eq!(Some(node), name_of(small.as_ptr() as usize));
+        assert_eq!(Some("llmalloc:huge".to_string()), name_of(huge.as_ptr() as usize));
+    }
+
+    unsafe { allocator.deallocate(huge) };
+    unsafe { allocator.deallocate(small) };
+}
+
+//  Returns whether the kernel supports naming anonymous mappings, by naming one.
+fn naming_supported() -> bool {
+    const PR_SET_VMA: libc::c_int = 0x53564d41;
+    const PR_SET_VMA_ANON_NAME: libc::c_ulong = 0;
+
+    const SIZE: usize = 4096;
+
+    let name = b"llmalloc:probe\0";
+
+    unsafe {
+        let pointer = libc::mmap(ptr::null_mut(), SIZE, libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0);
+        assert_ne!(libc::MAP_FAILED, pointer);
+
+        let result = libc::prctl(PR_SET_VMA, PR_SET_VMA_ANON_NAME, pointer as libc::c_ulong, SIZE as libc::c_ulong,
+            name.as_ptr() as libc::c_ulong);
+
+        libc::munmap(pointer, SIZE);
+
+        result == 0
+    }
+}
+
+//  Returns the name of the anonymous mapping containing `address`, if any, as per `/proc/self/maps`.
+fn name_of(address: usize) -> Option<String> {
+    let maps = fs::read_to_string("/proc/self/maps").expect("Readable");
+
+    for line in maps.lines() {
+        //  A line starts with the range of the mapping, and ends with its name, as in
+        //  `7f0000000000-7f0000200000 rw-p 00000000 00:00 0    [anon:llmalloc:huge]`.
+        let (low, high) = line.split(' ').next().unwrap().split_once('-').unwrap();
+        let (low, high) = (usize::from_str_radix(low, 16).unwrap(), usize::from_str_radix(high, 16).unwrap());
+
+        if low <= address && address < high {
+            let name = line.rsplit(' ').next().unwrap();
+
+            return name.strip_prefix("[anon:").and_then(|name| name.strip_suffix(']')).map(str::to_string);
+        }
+    }
+
+    None
+}
+
+} // mod mapping_names
+
+//  The reservation is process-wide, and latched by the first mapping.
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "no-libc"))))]
+mod reservation {
+
+use std::{alloc::Layout, ptr};
+
+use llmalloc::LLAllocator;
+
+use super::isolated;
+
+#[test]
+fn reservation() { isolated("reservation::reservation", run) }
+
+fn run() {
+    const CHUNK: usize = 2 * 1024 * 1024;
+    const SIZE: usize = 32 * CHUNK;
+
+    let allocator = LLAllocator::new();
+
+    assert_eq!(Err(()), allocator.set_reservation(Some(0)));
+    assert_eq!(Ok(()), allocator.set_reservation(Some(SIZE - 1)));
+    assert_eq!(Some(SIZE), allocator.reservation_size());
+
+    let normal = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    //  The selection is latched by the first mapping.
+    assert_eq!(Err(()), allocator.set_reservation(None));
+    assert_eq!(Ok(()), allocator.set_reservation(Some(SIZE)));
+
+    let reservation = allocator.reservation().expect("Reserved");
+
+    assert_eq!(SIZE, reservation.size);
+    assert_eq!(0, reservation.base % CHUNK);
+    assert!(reservation.contains(normal.as_ptr()));
+
+    let offset = reservation.offset(normal.as_ptr()).expect("Within");
+    assert_eq!(Some(normal), reservation.pointer(offset));
+
+    //  Huge allocations are committed out of the reservation too.
+    let layout = Layout::from_size_align(4 * CHUNK, 8).unwrap();
+    let huge = allocator.allocate(layout).expect("Allocated");
+
+    assert!(reservation.contains(huge.as_ptr()));
+    assert!(allocator.reservation().unwrap().committed >= 5 * CHUNK);
+
+    unsafe { ptr::write_bytes(huge.as_ptr(), 0x5A, 4 * CHUNK) };
+
+    //  Resized, in place or by copy, they remain within it.
+    let grown = unsafe { allocator.reallocate(huge, layout, 8 * CHUNK) }.expect("Reallocated");
+
+    assert!(reservation.contains(grown.as_ptr()));
+    assert!(reservation.contains(unsafe { grown.as_ptr().add(8 * CHUNK - 1) }));
+
+    assert_eq!(0x5A, unsafe { grown.as_ptr().read() });
+    assert_eq!(0x5A, unsafe { grown.as_ptr().add(4 * CHUNK - 1).read() });
+
+    unsafe { grown.as_ptr().add(8 * CHUNK - 1).write(0x42) };
+
+    let layout = Layout::from_size_align(8 * CHUNK, 8).unwrap();
+    let shrunk = unsafe { allocator.reallocate(grown, layout, 2 * CHUNK) }.expect("Reallocated");
+
+    assert_eq!(grown, shrunk);
+    assert_eq!(0x5A, unsafe { shrunk.as_ptr().add(2 * CHUNK - 1).read() });
+
+    //  Exhausted, the reservation fails the mappings, rather than spill outside of it.
+    if !cfg!(feature = "system-fallback") {
+        assert_eq!(None, allocator.allocate(Layout::from_size_align(SIZE, 8).unwrap()));
+    }
+
+    unsafe { allocator.deallocate(shrunk) };
+    unsafe { allocator.deallocate(normal) };
+}
+
+} // mod reservation
+
+//  The shared heap is process-wide, and latched by the first mapping.
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(any(feature = "posix", feature = "no-libc"))))]
+mod shared_heap {
+
+use std::{alloc::Layout, ptr::{self, NonNull}};
+
+use llmalloc::{LLAllocator, SharedBacking, SharedHeap};
+
+use super::isolated;
+
+#[test]
+fn shared_heap() { isolated("shared_heap::shared_heap", run) }
+
+fn run() {
+    const SIZE: usize = 4 * 1024 * 1024;
+
+    let allocator = LLAllocator::new();
+
+    assert_eq!(Ok(()), allocator.set_shared_heap(Some(SharedBacking::Normal)));
+    assert_eq!(Some(SharedBacking::Normal), allocator.shared_heap_backing());
+
+    let layout = Layout::from_size_align(SIZE, 8).unwrap();
+    let pointer = allocator.allocate(layout).expect("Allocated");
+
+    //  The selection is latched by the first mapping.
+    assert_eq!(Err(()), allocator.set_shared_heap(None));
+    assert_eq!(Ok(()), allocator.set_shared_heap(Some(SharedBacking::Normal)));
+
+    let heap = allocator.shared_heap().expect("Shared");
+    assert_eq!(SharedBacking::Normal, heap.backing);
+
+    //  The memory is visible through another mapping of the file, as it would be from another process.
+    unsafe { ptr::write_bytes(pointer.as_ptr(), 0x5A, SIZE) };
+
+    let view = View::new(heap, pointer, SIZE);
+
+    assert_eq!(0x5A, view.read(0));
+    assert_eq!(0x5A, view.read(SIZE - 1));
+
+    unsafe { pointer.as_ptr().write(0x42) };
+    assert_eq!(0x42, view.read(0));
+
+    //  Normal allocations live within the file too.
+    let normal = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+    unsafe { normal.as_ptr().write(0x17) };
+
+    assert_eq!(0x17, View::new(heap, normal, 64).read(0));
+
+    unsafe { allocator.deallocate(normal) };
+
+    //  Grown, the allocation is moved by copy unless grown in place, either way still within the file.
+    let grown = unsafe { allocator.reallocate(pointer, layout, 2 * SIZE) }.expect("Reallocated");
+
+    let grown_view = View::new(heap, grown, 2 * SIZE);
+
+    assert_eq!(0x42, grown_view.read(0));
+    assert_eq!(0x5A, grown_view.read(SIZE - 1));
+
+    //  Unmapped, the memory is punched out of the file, whether moved from, or shrunk in place.
+    if grown != pointer {
+        assert_eq!(0, view.read(0));
+    }
+
+    unsafe { grown.as_ptr().add(2 * SIZE - 1).write(0x33) };
+    assert_eq!(0x33, grown_view.read(2 * SIZE - 1));
+
+    let layout = Layout::from_size_align(2 * SIZE, 8).unwrap();
+    let shrunk = unsafe { allocator.reallocate(grown, layout, SIZE) }.expect("Reallocated");
+
+    assert_eq!(grown, shrunk);
+    assert_eq!(0x42, grown_view.read(0));
+    assert_eq!(0, grown_view.read(2 * SIZE - 1));
+
+    unsafe { allocator.deallocate(shrunk) };
+}
+
+//  A read-only mapping of the file of the heap, as another process would map it.
+struct View {
+    base: *mut u8,
+    length: usize,
+    skew: usize,
+}
+
+impl View {
+    fn new(heap: SharedHeap, pointer: NonNull<u8>, size: usize) -> Self {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+
+        let offset = heap.offset(pointer);
+        let skew = (offset % page_size) as usize;
+        let length = skew + size;
+
+        let base = unsafe {
+            libc::mmap(ptr::null_mut(), length, libc::PROT_READ, libc::MAP_SHARED, heap.fd,
+                (offset - skew as u64) as libc::off_t)
+        };
+
+        assert_ne!(libc::MAP_FAILED, base);
+
+        Self { base: base as *mut u8, length, skew }
+    }
+
+    fn read(&self, index: usize) -> u8 { unsafe { ptr::read_volatile(self.base.add(self.skew + index)) } }
+}
+
+impl Drop for View {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base as *mut libc::c_void, self.length) };
+    }
+}
+
+} // mod shared_heap
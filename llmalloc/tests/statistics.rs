@@ -0,0 +1,49 @@
+//  The statistics, and their reset and interval epochs, are process-wide, hence they are checked in their own test
+//  binary, where no other test allocates concurrently.
+//
+//  The bare-metal, and custom, platforms have no memory until provided, see `bare_metal.rs` and `custom_platform.rs`.
+#![cfg(not(any(feature = "bare-metal", feature = "custom-platform", feature = "test-platform")))]
+
+use std::alloc::Layout;
+
+use llmalloc::LLAllocator;
+
+#[test]
+fn stats_reset_interval() {
+    let allocator = LLAllocator::new();
+    allocator.warm_up().expect("Warmed up!");
+
+    allocator.stats_reset();
+    assert_eq!(0, allocator.stats().total().allocations);
+
+    let layout = Layout::from_size_align(24, 8).unwrap();
+
+    let baseline = allocator.stats();
+    allocator.stats_interval();
+
+    let first = allocator.allocate(layout).expect("Allocated");
+    let second = allocator.allocate(layout).expect("Allocated");
+
+    let interval = allocator.stats_interval();
+    assert_eq!(2, interval.normal.allocations);
+    assert_eq!(0, interval.normal.deallocations);
+    assert!(interval.normal.allocated_bytes >= 2 * layout.size());
+
+    unsafe {
+        allocator.deallocate(first);
+        allocator.deallocate(second);
+    }
+
+    let interval = allocator.stats_interval();
+    assert_eq!(0, interval.normal.allocations);
+    assert_eq!(2, interval.normal.deallocations);
+
+    let delta = allocator.stats().since(&baseline);
+    assert_eq!(2, delta.normal.allocations);
+    assert_eq!(2, delta.normal.deallocations);
+    assert_eq!(0, delta.normal.live_bytes());
+
+    allocator.stats_reset();
+    assert_eq!(0, allocator.stats().total().allocations);
+    assert_eq!(0, allocator.stats_interval().total().deallocations);
+}
@@ -0,0 +1,30 @@
+//  Forgoing HugeTLB is process-wide, hence it is checked in its own test binary.
+#![cfg(all(target_os = "linux", not(any(feature = "posix", feature = "bare-metal", feature = "custom-platform",
+    feature = "test-platform"))))]
+
+use std::alloc::Layout;
+
+use llmalloc::LLAllocator;
+
+#[test]
+fn transparent_huge_pages() {
+    let allocator = LLAllocator::new();
+
+    assert!(allocator.use_transparent_huge_pages());
+
+    let capabilities = allocator.capabilities();
+
+    assert!(!capabilities.huge_tlb, "{:?}", capabilities);
+    assert!(!capabilities.huge_tlb_2mb, "{:?}", capabilities);
+
+    let pointer = allocator.allocate(Layout::from_size_align(64, 8).unwrap()).expect("Allocated");
+
+    //  The `HugePage` is mapped without `MAP_HUGETLB`.
+    let metrics = allocator.fallback_metrics();
+
+    assert_eq!(0, metrics.huge_tlb_mappings + metrics.huge_tlb_2mb_mappings, "{:?}", metrics);
+    assert_eq!(0, metrics.huge_tlb_failures + metrics.huge_tlb_exhaustions, "{:?}", metrics);
+    assert!(metrics.transparent_huge_page_mappings + metrics.normal_page_mappings > 0, "{:?}", metrics);
+
+    unsafe { allocator.deallocate(pointer) };
+}